@@ -0,0 +1,40 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Generate a triangle that covers the whole screen
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+struct AccumulationUniform {
+    weight: f32,
+}
+
+@group(0) @binding(0)
+var current_image: texture_2d<f32>;
+
+@group(0) @binding(1)
+var history_image: texture_2d<f32>;
+
+@group(0) @binding(2)
+var<uniform> accumulation: AccumulationUniform;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coords = vec2<i32>(in.clip_position.xy);
+    let current = textureLoad(current_image, coords, 0);
+    let history = textureLoad(history_image, coords, 0);
+
+    // Running average, the weight is 1 / (n + 1) for the n-th frame
+    return mix(history, current, accumulation.weight);
+}
@@ -0,0 +1,129 @@
+// Auxiliary buffers of a capture: world normal and linear depth in one target, the entity behind every pixel in
+// the other
+struct CameraUniform {
+    view_position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    inv_projection: mat4x4<f32>,
+}
+
+struct TransformUniform {
+    matrix: mat4x4<f32>,
+}
+
+struct JointPalette {
+    joints: array<mat4x4<f32>, 128>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    // One past the transform index, zero is left for the background
+    @location(2) @interpolate(flat) id: u32,
+}
+
+struct AovOutput {
+    // The w component holds the distance along the view direction
+    @location(0) normal_depth: vec4<f32>,
+    @location(1) id: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> transforms: array<TransformUniform>;
+
+@group(1) @binding(1)
+var<storage, read> normals: array<TransformUniform>;
+
+@group(1) @binding(4)
+var<storage, read> joint_palettes: array<JointPalette>;
+
+// Set for compact vertices, normals arrive octahedral encoded
+override QUANTIZED: bool = false;
+
+fn oct_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var vector = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let fold = max(-vector.z, 0.0);
+    vector.x += select(fold, -fold, vector.x >= 0.0);
+    vector.y += select(fold, -fold, vector.y >= 0.0);
+    return normalize(vector);
+}
+
+fn decode_normal(normal: vec3<f32>) -> vec3<f32> {
+    if QUANTIZED {
+        return oct_decode(normal.xy);
+    }
+    return normal;
+}
+
+fn vertex_output(position: vec3<f32>, normal: vec3<f32>, transform_index: u32, normal_index: u32) -> VertexOutput {
+    let world_position = transforms[transform_index].matrix * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    out.normal = (normals[normal_index].matrix * vec4<f32>(normal, 0.0)).xyz;
+    out.id = transform_index + 1u;
+    return out;
+}
+
+@vertex
+fn vs_mesh(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(3) transform_index: u32,
+    @location(4) normal_index: u32,
+) -> VertexOutput {
+    return vertex_output(position, decode_normal(normal), transform_index, normal_index);
+}
+
+// Skinned vertices are always full precision, joints and weights follow the tangent
+@vertex
+fn vs_skinned(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(3) joints: vec4<u32>,
+    @location(4) weights: vec4<f32>,
+    @location(5) transform_index: u32,
+    @location(6) normal_index: u32,
+    @location(7) palette_index: u32,
+) -> VertexOutput {
+    let skin = joint_palettes[palette_index].joints[joints.x] * weights.x
+        + joint_palettes[palette_index].joints[joints.y] * weights.y
+        + joint_palettes[palette_index].joints[joints.z] * weights.z
+        + joint_palettes[palette_index].joints[joints.w] * weights.w;
+    let skinned_position = (skin * vec4<f32>(position, 1.0)).xyz;
+    let skinned_normal = (skin * vec4<f32>(normal, 0.0)).xyz;
+    return vertex_output(skinned_position, skinned_normal, transform_index, normal_index);
+}
+
+// Points without estimated normals keep a zero normal
+@vertex
+fn vs_points(
+    @location(0) position: vec3<f32>,
+    @location(3) normal: vec3<f32>,
+    @location(4) transform_index: u32,
+    @location(5) normal_index: u32,
+) -> VertexOutput {
+    return vertex_output(position, normal, transform_index, normal_index);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> AovOutput {
+    let forward = -camera.inv_view[2].xyz;
+    let normal = select(vec3<f32>(0.0), normalize(in.normal), length(in.normal) > 0.0);
+
+    var out: AovOutput;
+    out.normal_depth = vec4<f32>(normal, dot(in.world_position - camera.view_position.xyz, forward));
+    out.id = in.id;
+    return out;
+}
+
+// Only the id, for picking the entity under a pixel
+@fragment
+fn fs_pick(in: VertexOutput) -> @location(0) u32 {
+    return in.id;
+}
@@ -0,0 +1,64 @@
+const PI : f32 = 3.141592653589793;
+
+// Scale and bias applied to f0 by the split sum, indexed by n·v along x and roughness along y
+@group(0) @binding(0) var dst_lut : texture_storage_2d<rgba16float, write>;
+
+const SAMPLE_COUNT : u32 = 512u;
+
+fn radical_inverse(bits_in : u32) -> f32 {
+    var bits = (bits_in << 16u) | (bits_in >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn importance_sample_ggx(xi : vec2<f32>, alpha : f32) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+}
+
+// Schlick-GGX with the k of image based lighting
+fn geometry_schlick_ggx(n_dot_x : f32, roughness : f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn integrate_brdf(@builtin(global_invocation_id) gid : vec3<u32>) {
+    let tex_size : vec2<u32> = textureDimensions(dst_lut);
+    if (gid.x >= tex_size.x || gid.y >= tex_size.y) {
+        return;
+    }
+
+    let n_dot_v = max((f32(gid.x) + 0.5) / f32(tex_size.x), 0.001);
+    let roughness = (f32(gid.y) + 0.5) / f32(tex_size.y);
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let alpha = roughness * roughness;
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i : u32 = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let xi = vec2<f32>(f32(i) / f32(SAMPLE_COUNT), radical_inverse(i));
+        let h = importance_sample_ggx(xi, alpha);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        let v_dot_h = max(dot(v, h), 0.0);
+        if (n_dot_l > 0.0) {
+            let g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let g_vis = g * v_dot_h / max(n_dot_h * n_dot_v, 0.0001);
+            let fc = pow(1.0 - v_dot_h, 5.0);
+            scale = scale + (1.0 - fc) * g_vis;
+            bias = bias + fc * g_vis;
+        }
+    }
+
+    let result = vec2<f32>(scale, bias) / f32(SAMPLE_COUNT);
+    textureStore(dst_lut, vec2<i32>(gid.xy), vec4<f32>(result, 0.0, 1.0));
+}
@@ -0,0 +1,39 @@
+// Ghosted entities, a flat translucent tint that leaves the isolated entities in front of them untouched
+struct CameraUniform {
+    view_position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+}
+
+struct TransformUniform {
+    matrix: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> transforms: array<TransformUniform>;
+
+// Linear, blended over the scene with its alpha
+const GHOST_COLOR = vec4<f32>(0.55, 0.6, 0.7, 0.12);
+
+@vertex
+fn vs_mesh(
+    @location(0) position: vec3<f32>,
+    @location(3) transform_index: u32,
+) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * transforms[transform_index].matrix * vec4<f32>(position, 1.0);
+}
+
+@vertex
+fn vs_points(
+    @location(0) position: vec3<f32>,
+    @location(4) transform_index: u32,
+) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * transforms[transform_index].matrix * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return GHOST_COLOR;
+}
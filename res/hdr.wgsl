@@ -47,9 +47,26 @@ var hdr_image: texture_2d<f32>;
 @binding(1)
 var hdr_sampler: sampler;
 
+struct ToneMapUniform {
+    exposure: f32,
+    // Radial coefficient, relative to the distance from the center to a corner
+    distortion: f32,
+}
+
+@group(0)
+@binding(2)
+var<uniform> tone_map: ToneMapUniform;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let hdr = textureSample(hdr_image, hdr_sampler, in.uv);
-    let sdr = aces_tone_map(hdr.rgb);
-    return vec4(sdr, hdr.a);
+    let size = vec2<f32>(textureDimensions(hdr_image));
+    let offset = (in.uv - 0.5) * size;
+    let radius = length(offset) / length(size * 0.5);
+    let uv = 0.5 + offset * (1.0 + tone_map.distortion * radius * radius) / size;
+    // Pincushion pulls in the edges from outside the rendered image
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+
+    let hdr = textureSample(hdr_image, hdr_sampler, uv);
+    let sdr = aces_tone_map(hdr.rgb * tone_map.exposure);
+    return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4(sdr, hdr.a), inside);
 }
@@ -0,0 +1,77 @@
+// Projects one rendered cube face onto first order spherical harmonics, accumulated per probe over its six faces
+struct BakeParams {
+    right: vec4<f32>,
+    up: vec4<f32>,
+    forward: vec4<f32>,
+    probe_index: u32,
+    face: u32,
+}
+
+// Red, green and blue coefficients in xyz, band 0 first
+struct ProbeCoefficients {
+    coefficients: array<vec4<f32>, 4>,
+}
+
+@group(0) @binding(0)
+var face_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> probes: array<ProbeCoefficients>;
+
+@group(0) @binding(2)
+var<uniform> params: BakeParams;
+
+const WORKGROUP_SIZE: u32 = 64u;
+
+var<workgroup> partial: array<array<vec3<f32>, 4>, WORKGROUP_SIZE>;
+
+@compute
+@workgroup_size(64, 1, 1)
+fn project_face(@builtin(local_invocation_index) index: u32) {
+    let size = textureDimensions(face_texture);
+    var sums = array<vec3<f32>, 4>(vec3<f32>(0.0), vec3<f32>(0.0), vec3<f32>(0.0), vec3<f32>(0.0));
+
+    for (var texel = index; texel < size.x * size.y; texel += WORKGROUP_SIZE) {
+        let pixel = vec2<u32>(texel % size.x, texel / size.x);
+        let ndc = vec2<f32>(
+            (f32(pixel.x) + 0.5) / f32(size.x) * 2.0 - 1.0,
+            1.0 - (f32(pixel.y) + 0.5) / f32(size.y) * 2.0,
+        );
+        let direction = params.forward.xyz + ndc.x * params.right.xyz + ndc.y * params.up.xyz;
+
+        // Solid angle of the texel on a face at unit distance
+        let length_squared = dot(direction, direction);
+        let texel_area = 4.0 / f32(size.x * size.y);
+        let solid_angle = texel_area / (length_squared * sqrt(length_squared));
+
+        let l = normalize(direction);
+        let radiance = textureLoad(face_texture, pixel, 0).rgb * solid_angle;
+        sums[0] += radiance * 0.282095;
+        sums[1] += radiance * 0.488603 * l.y;
+        sums[2] += radiance * 0.488603 * l.z;
+        sums[3] += radiance * 0.488603 * l.x;
+    }
+
+    partial[index] = sums;
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if (index < stride) {
+            for (var i = 0u; i < 4u; i++) {
+                partial[index][i] += partial[index + stride][i];
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if (index == 0u) {
+        for (var i = 0u; i < 4u; i++) {
+            var coefficient = partial[0][i];
+            // The first face overwrites whatever the previous bake left behind
+            if (params.face > 0u) {
+                coefficient += probes[params.probe_index].coefficients[i].xyz;
+            }
+            probes[params.probe_index].coefficients[i] = vec4<f32>(coefficient, 0.0);
+        }
+    }
+}
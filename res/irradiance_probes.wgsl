@@ -0,0 +1,90 @@
+// Baked irradiance probes drawn as camera facing spheres, shaded with their own coefficients
+struct CameraUniform {
+    view_position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    inv_projection: mat4x4<f32>,
+}
+
+struct ProbeCoefficients {
+    coefficients: array<vec4<f32>, 4>,
+}
+
+// At least two probes per axis, the w component of resolution is one while the volume holds a bake
+struct GridUniform {
+    min: vec4<f32>,
+    max: vec4<f32>,
+    resolution: vec4<u32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) offset: vec2<f32>,
+    @location(1) @interpolate(flat) probe_index: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> probes: array<ProbeCoefficients>;
+
+@group(1) @binding(1)
+var<uniform> grid: GridUniform;
+
+fn probe_position(index: u32) -> vec3<f32> {
+    let resolution = grid.resolution.xyz;
+    let cell = vec3<u32>(index % resolution.x, (index / resolution.x) % resolution.y, index / (resolution.x * resolution.y));
+    return mix(grid.min.xyz, grid.max.xyz, vec3<f32>(cell) / vec3<f32>(resolution - 1u));
+}
+
+fn evaluate_irradiance(index: u32, n: vec3<f32>) -> vec3<f32> {
+    let c = probes[index].coefficients;
+    // Cosine lobe convolution, divided by pi to match the prefiltered irradiance map
+    let irradiance = c[0].xyz * 0.282095 + (c[1].xyz * n.y + c[2].xyz * n.z + c[3].xyz * n.x) * 0.488603 * (2.0 / 3.0);
+    return max(irradiance, vec3<f32>(0.0));
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let offset = corners[vertex_index];
+
+    // A fraction of the smallest cell, so neighbouring spheres never touch
+    let cell = (grid.max.xyz - grid.min.xyz) / vec3<f32>(grid.resolution.xyz - 1u);
+    let radius = max(min(cell.x, min(cell.y, cell.z)) * 0.15, 0.01);
+
+    let right = camera.inv_view[0].xyz;
+    let up = camera.inv_view[1].xyz;
+    let position = probe_position(instance_index) + (right * offset.x + up * offset.y) * radius;
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_projection * vec4<f32>(position, 1.0);
+    out.offset = offset;
+    out.probe_index = instance_index;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance_squared = dot(in.offset, in.offset);
+    if (distance_squared > 1.0) {
+        discard;
+    }
+
+    let right = camera.inv_view[0].xyz;
+    let up = camera.inv_view[1].xyz;
+    let towards_camera = cross(right, up);
+    let n = normalize(right * in.offset.x + up * in.offset.y + towards_camera * sqrt(1.0 - distance_squared));
+    return vec4<f32>(evaluate_irradiance(in.probe_index, n), 1.0);
+}
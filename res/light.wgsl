@@ -36,9 +36,12 @@ struct TransformUniform {
 
 struct LightUniform {
     color: vec3<f32>,
-    cutoff: f32,    
-    intensity: f32, 
-    kind: u32,    
+    intensity: f32,
+    kind: u32,
+    range: f32,
+    cos_inner_cutoff: f32,
+    cos_outer_cutoff: f32,
+    flags: u32,
 }
 
 @group(1) @binding(0)
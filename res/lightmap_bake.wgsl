@@ -0,0 +1,176 @@
+struct LightmapUniform {
+    resolution: u32,
+    sample_index: u32,
+    samples_per_step: u32,
+    max_bounces: u32,
+    light_count: u32,
+    triangle_count: u32,
+}
+
+// Lights are captured in world space when the bake starts
+struct BakeLight {
+    position: vec3<f32>,
+    kind: u32,
+    direction: vec3<f32>,
+    range: f32,
+    radiance: vec3<f32>,
+    cos_inner_cutoff: f32,
+    cos_outer_cutoff: f32,
+    flags: u32,
+}
+
+const CASTS_SHADOWS: u32 = 1u;
+
+@group(0) @binding(0) var<uniform> params: LightmapUniform;
+@group(0) @binding(1) var<storage, read_write> accumulation: array<vec4<f32>>;
+@group(0) @binding(2) var position_texture: texture_2d<f32>;
+@group(0) @binding(3) var normal_texture: texture_2d<f32>;
+@group(0) @binding(4) var<storage, read> lights: array<BakeLight>;
+@group(0) @binding(5) var lightmap: texture_storage_2d<rgba16float, write>;
+
+@group(2) @binding(0) var environment_map: texture_cube<f32>;
+@group(2) @binding(1) var environment_sampler: sampler;
+
+const RAY_OFFSET: f32 = 0.001;
+const DILATION: i32 = 4;
+
+// Each texel stores the irradiance divided by pi, which the mesh shader scales by the diffuse albedo
+@compute
+@workgroup_size(8, 8, 1)
+fn bake_samples(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.resolution || id.y >= params.resolution) {
+        return;
+    }
+
+    let texel = textureLoad(position_texture, id.xy, 0);
+    if (texel.w == 0.0) {
+        return;
+    }
+
+    let n = normalize(textureLoad(normal_texture, id.xy, 0).xyz);
+    let origin = texel.xyz + n * RAY_OFFSET;
+    let index = id.y * params.resolution + id.x;
+
+    // Direct light is the same for every sample, only the bounces need more paths
+    var sum = direct_light(origin, n) / PI * f32(params.samples_per_step);
+    for (var offset = 0u; offset < params.samples_per_step; offset++) {
+        var state = hash(index ^ hash(params.sample_index + offset));
+        sum += indirect_light(origin, n, &state);
+    }
+
+    var previous = vec4<f32>(0.0);
+    if (params.sample_index > 0u) {
+        previous = accumulation[index];
+    }
+
+    accumulation[index] = previous + vec4<f32>(sum, f32(params.samples_per_step));
+}
+
+// Averages the samples and grows every chart by a few texels, so bilinear filtering along chart edges doesn't pull in
+// the black of uncovered texels
+@compute
+@workgroup_size(8, 8, 1)
+fn resolve(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.resolution || id.y >= params.resolution) {
+        return;
+    }
+
+    let size = i32(params.resolution);
+    let pixel = vec2<i32>(id.xy);
+    var nearest = f32(DILATION) + 1.0;
+    var color = vec3<f32>(0.0);
+
+    for (var y = -DILATION; y <= DILATION; y++) {
+        for (var x = -DILATION; x <= DILATION; x++) {
+            let neighbour = pixel + vec2<i32>(x, y);
+            if (any(neighbour < vec2<i32>(0)) || any(neighbour >= vec2<i32>(size))) {
+                continue;
+            }
+
+            let distance = length(vec2<f32>(f32(x), f32(y)));
+            if (distance >= nearest || textureLoad(position_texture, neighbour, 0).w == 0.0) {
+                continue;
+            }
+
+            let samples = accumulation[u32(neighbour.y) * params.resolution + u32(neighbour.x)];
+            nearest = distance;
+            color = samples.rgb / max(samples.w, 1.0);
+        }
+    }
+
+    textureStore(lightmap, pixel, vec4<f32>(color, 1.0));
+}
+
+// Irradiance from the scene lights, with a shadow ray towards each of them that casts shadows
+fn direct_light(position: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    var irradiance = vec3<f32>(0.0);
+    for (var i = 0u; i < params.light_count; i++) {
+        let light = lights[i];
+        var l = -light.direction;
+        var distance = MISS;
+        var attenuation = 1.0;
+
+        // Anything but a directional light falls off with the distance squared
+        if (light.kind != 0u) {
+            let to_light = light.position - position;
+            distance = length(to_light);
+            l = to_light / max(distance, 0.0001);
+            attenuation = 1.0 / max(distance * distance, 0.0001);
+            if (light.range > 0.0) {
+                let falloff = saturate(1.0 - pow(distance / light.range, 4.0));
+                attenuation *= falloff * falloff;
+            }
+        }
+        if (light.kind == 2u) {
+            attenuation *= smoothstep(light.cos_outer_cutoff, light.cos_inner_cutoff, dot(-l, light.direction));
+        }
+
+        let n_dot_l = dot(n, l);
+        if (n_dot_l <= 0.0 || attenuation <= 0.0) {
+            continue;
+        }
+        if ((light.flags & CASTS_SHADOWS) != 0u && trace(position, l, params.triangle_count).distance < distance) {
+            continue;
+        }
+
+        irradiance += light.radiance * attenuation * n_dot_l;
+    }
+
+    return irradiance;
+}
+
+// Cosine weighted path from the texel, so the average radiance it returns is the irradiance divided by pi. Surfaces
+// along the way are treated as diffuse and look up the lights directly
+fn indirect_light(origin: vec3<f32>, n: vec3<f32>, state: ptr<function, u32>) -> vec3<f32> {
+    var position = origin;
+    var direction = sample_cosine_hemisphere(n, vec2<f32>(random(state), random(state)));
+    var throughput = vec3<f32>(1.0);
+    var radiance = vec3<f32>(0.0);
+
+    for (var bounce = 0u; bounce < params.max_bounces; bounce++) {
+        let hit = trace(position, direction, params.triangle_count);
+        if (hit.distance >= MISS) {
+            radiance += throughput * textureSampleLevel(environment_map, environment_sampler, direction, 0.0).rgb;
+            break;
+        }
+
+        let triangle = triangles[hit.triangle];
+        let material = materials[triangle.material];
+        let w = 1.0 - hit.barycentric.x - hit.barycentric.y;
+        var hit_normal = normalize(triangle.n0 * w + triangle.n1 * hit.barycentric.x + triangle.n2 * hit.barycentric.y);
+        let face_normal = normalize(cross(triangle.p1 - triangle.p0, triangle.p2 - triangle.p0));
+        if (dot(face_normal, direction) > 0.0) {
+            hit_normal = -hit_normal;
+        }
+
+        position = triangle.p0 * w + triangle.p1 * hit.barycentric.x + triangle.p2 * hit.barycentric.y
+            + hit_normal * RAY_OFFSET;
+        let albedo = material.base_color.rgb * (1.0 - material.metallic);
+        radiance += throughput * (material.emissive + albedo * direct_light(position, hit_normal) / PI);
+
+        throughput *= albedo;
+        direction = sample_cosine_hemisphere(hit_normal, vec2<f32>(random(state), random(state)));
+    }
+
+    return radiance;
+}
@@ -0,0 +1,62 @@
+// Rasterizes a mesh in its second UV set, storing the world position and normal behind every lightmap texel
+struct RasterUniform {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+}
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(3) uv2: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+struct GBuffer {
+    // The w component marks the texel as covered
+    @location(0) position: vec4<f32>,
+    @location(1) normal: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> raster: RasterUniform;
+
+// Set for compact vertices, normals and tangents arrive octahedral encoded with the tangent's sign in z
+override QUANTIZED: bool = false;
+
+fn oct_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var vector = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let fold = max(-vector.z, 0.0);
+    vector.x += select(fold, -fold, vector.x >= 0.0);
+    vector.y += select(fold, -fold, vector.y >= 0.0);
+    return normalize(vector);
+}
+
+fn decode_normal(normal: vec3<f32>) -> vec3<f32> {
+    if QUANTIZED {
+        return oct_decode(normal.xy);
+    }
+    return normal;
+}
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.world_position = (raster.model * vec4<f32>(in.position, 1.0)).xyz;
+    out.normal = (raster.normal * vec4<f32>(decode_normal(in.normal), 0.0)).xyz;
+    // UV space has its origin at the top left, clip space at the bottom left
+    out.clip_position = vec4<f32>(in.uv2.x * 2.0 - 1.0, 1.0 - in.uv2.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    var out: GBuffer;
+    out.position = vec4<f32>(in.world_position, 1.0);
+    out.normal = vec4<f32>(normalize(in.normal), 0.0);
+    return out;
+}
@@ -0,0 +1,71 @@
+// Omni-directional stereo: every column of an eye's equirectangular half is filled from a cubemap captured at an
+// eye position on a circle around the camera, so the parallax holds all the way around
+const PI: f32 = 3.14159265359;
+
+struct PanoramaUniform {
+    // Size of one eye's half in pixels, its first row in the output and the longitude at its center
+    size: vec2<f32>,
+    top: f32,
+    yaw: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var faces: texture_cube<f32>;
+
+@group(0) @binding(1)
+var faces_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> panorama: PanoramaUniform;
+
+// Same as hdr.wgsl, the panorama skips the regular tone map pass
+fn aces_tone_map(hdr: vec3<f32>) -> vec3<f32> {
+    let input = mat3x3(
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777,
+    );
+    let output = mat3x3(
+        1.60475, -0.10208, -0.00327,
+        -0.53108,  1.10813, -0.07276,
+        -0.07367, -0.00605,  1.07602,
+    );
+    let v = input * hdr;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return clamp(output * (a / b), vec3(0.0), vec3(1.0));
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// Drawn once per strip of columns, the scissor keeps it to the columns of the eye position it was captured from
+@fragment
+fn fs_strip(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = vec2<f32>(in.clip_position.x, in.clip_position.y - panorama.top) / panorama.size;
+    let longitude = panorama.yaw + (uv.x - 0.5) * 2.0 * PI;
+    let latitude = (0.5 - uv.y) * PI;
+    let direction = vec3<f32>(
+        cos(latitude) * sin(longitude),
+        sin(latitude),
+        -cos(latitude) * cos(longitude),
+    );
+
+    let hdr = textureSample(faces, faces_sampler, direction);
+    return vec4<f32>(aces_tone_map(hdr.rgb), 1.0);
+}
@@ -0,0 +1,59 @@
+// Draws a band around the selection mask by dilating it in screen space
+struct OutlineUniform {
+    color: vec4<f32>,
+    width: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> outline: OutlineUniform;
+
+@group(0) @binding(1)
+var mask: texture_2d<f32>;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(mask));
+    let pixel = vec2<i32>(in.clip_position.xy);
+    if textureLoad(mask, pixel, 0).r > 0.0 {
+        discard;
+    }
+
+    // Distance to the nearest covered pixel within the outline width
+    let radius = i32(ceil(outline.width));
+    var nearest = outline.width + 1.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2<i32>(x, y);
+            let neighbour = clamp(pixel + offset, vec2<i32>(0), size - 1);
+            if textureLoad(mask, neighbour, 0).r > 0.0 {
+                nearest = min(nearest, length(vec2<f32>(offset)));
+            }
+        }
+    }
+
+    // One pixel of falloff keeps the outer edge smooth
+    let coverage = clamp(outline.width + 1.0 - nearest, 0.0, 1.0);
+    if coverage <= 0.0 {
+        discard;
+    }
+
+    return vec4<f32>(outline.color.rgb, outline.color.a * coverage);
+}
@@ -0,0 +1,42 @@
+struct PathTracerUniform {
+    inv_view: mat4x4<f32>,
+    inv_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    width: u32,
+    height: u32,
+    frame_index: u32,
+    max_bounces: u32,
+    triangle_count: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Generate a triangle that covers the whole screen
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@group(0) @binding(0) var<uniform> params: PathTracerUniform;
+@group(0) @binding(1) var<storage, read> accumulation: array<vec4<f32>>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<u32>(in.clip_position.xy);
+    let sample = accumulation[pixel.y * params.width + pixel.x];
+    let color = sample.rgb / max(sample.w, 1.0);
+
+    // Same tone mapping and gamma the raster shader applies, so both modes line up in the HDR pass
+    let mapped = color / (color + vec3<f32>(1.0));
+    return vec4<f32>(pow(mapped, vec3<f32>(1.0 / 2.2)), 1.0);
+}
@@ -0,0 +1,147 @@
+struct PathTracerUniform {
+    inv_view: mat4x4<f32>,
+    inv_projection: mat4x4<f32>,
+    position: vec4<f32>,
+    width: u32,
+    height: u32,
+    frame_index: u32,
+    max_bounces: u32,
+    triangle_count: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: PathTracerUniform;
+@group(0) @binding(1) var<storage, read_write> accumulation: array<vec4<f32>>;
+
+@group(2) @binding(0) var environment_map: texture_cube<f32>;
+@group(2) @binding(1) var environment_sampler: sampler;
+
+@compute
+@workgroup_size(8, 8, 1)
+fn trace_paths(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+
+    var state = hash(id.x ^ hash(id.y ^ hash(params.frame_index)));
+
+    // Jitter the primary ray inside the pixel for anti-aliasing
+    let pixel = vec2<f32>(id.xy) + vec2<f32>(random(&state), random(&state));
+    let ndc = vec2<f32>(
+        pixel.x / f32(params.width) * 2.0 - 1.0,
+        1.0 - pixel.y / f32(params.height) * 2.0,
+    );
+    let view_target = params.inv_projection * vec4<f32>(ndc, 1.0, 1.0);
+    var direction = normalize((params.inv_view * vec4<f32>(view_target.xyz / view_target.w, 0.0)).xyz);
+    var origin = params.position.xyz;
+
+    var throughput = vec3<f32>(1.0);
+    var radiance = vec3<f32>(0.0);
+
+    for (var bounce = 0u; bounce <= params.max_bounces; bounce++) {
+        let hit = trace(origin, direction, params.triangle_count);
+        if (hit.distance >= MISS) {
+            radiance += throughput * textureSampleLevel(environment_map, environment_sampler, direction, 0.0).rgb;
+            break;
+        }
+
+        let triangle = triangles[hit.triangle];
+        let material = materials[triangle.material];
+        let w = 1.0 - hit.barycentric.x - hit.barycentric.y;
+        var n = normalize(triangle.n0 * w + triangle.n1 * hit.barycentric.x + triangle.n2 * hit.barycentric.y);
+        let face_normal = normalize(cross(triangle.p1 - triangle.p0, triangle.p2 - triangle.p0));
+        if (dot(face_normal, direction) > 0.0) {
+            n = -n;
+        }
+
+        radiance += throughput * material.emissive;
+        if (bounce == params.max_bounces) {
+            break;
+        }
+
+        let v = -direction;
+        let albedo = material.base_color.rgb;
+        let metallic = material.metallic;
+        let roughness = clamp(material.roughness, 0.04, 1.0);
+        let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+
+        // Pick a lobe proportionally to its Fresnel weight and evaluate the mixture pdf
+        let specular_probability = clamp(luminance(fresnel_schlick(max(dot(n, v), 0.0), f0)) + metallic, 0.1, 0.9);
+        var l: vec3<f32>;
+        if (random(&state) < specular_probability) {
+            l = sample_ggx(n, v, roughness, vec2<f32>(random(&state), random(&state)));
+        } else {
+            l = sample_cosine_hemisphere(n, vec2<f32>(random(&state), random(&state)));
+        }
+
+        let n_dot_l = dot(n, l);
+        if (n_dot_l <= 0.0) {
+            break;
+        }
+
+        let h = normalize(v + l);
+        let n_dot_v = max(dot(n, v), 0.0001);
+        let n_dot_h = max(dot(n, h), 0.0);
+        let v_dot_h = max(dot(v, h), 0.0001);
+
+        let d = distribution_ggx(n_dot_h, roughness);
+        let f = fresnel_schlick(v_dot_h, f0);
+        let specular = d * geometry_smith(n_dot_v, n_dot_l, roughness) * f / max(4.0 * n_dot_v * n_dot_l, 0.0001);
+        let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic) * albedo / PI;
+
+        let pdf = specular_probability * d * n_dot_h / (4.0 * v_dot_h) + (1.0 - specular_probability) * n_dot_l / PI;
+        throughput *= (diffuse + specular) * n_dot_l / max(pdf, 0.0001);
+
+        // Russian roulette once the path has contributed a few bounces
+        if (bounce >= 2u) {
+            let survival = clamp(max(throughput.r, max(throughput.g, throughput.b)), 0.05, 1.0);
+            if (random(&state) > survival) {
+                break;
+            }
+            throughput /= survival;
+        }
+
+        origin = triangle.p0 * w + triangle.p1 * hit.barycentric.x + triangle.p2 * hit.barycentric.y + n * 0.0001;
+        direction = l;
+    }
+
+    let index = id.y * params.width + id.x;
+    var previous = vec4<f32>(0.0);
+    if (params.frame_index > 0u) {
+        previous = accumulation[index];
+    }
+
+    // Clamp fireflies, the reference converges slower but stays readable
+    accumulation[index] = previous + vec4<f32>(min(radiance, vec3<f32>(64.0)), 1.0);
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
+    return f0 + (vec3<f32>(1.0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let denominator = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * denominator * denominator);
+}
+
+fn geometry_smith(n_dot_v: f32, n_dot_l: f32, roughness: f32) -> f32 {
+    let r = roughness + 1.0;
+    let k = (r * r) / 8.0;
+    let ggx1 = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    let ggx2 = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx1 * ggx2;
+}
+
+fn sample_ggx(n: vec3<f32>, v: vec3<f32>, roughness: f32, xi: vec2<f32>) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let h = normalize(tangent_frame(n) * vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta));
+    return reflect(-v, h);
+}
@@ -3,15 +3,22 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec3<f32>,
     @location(2) intensity: f32,    
+    @location(3) normal: vec3<f32>,
+    @location(4) classification: u32,
 }
 
 struct InstanceInput {
-    @location(3) transform_index: u32, 
+    @location(5) transform_index: u32, 
+    @location(6) normal_index: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) intensity: f32,
+    @location(4) @interpolate(flat) classification: u32,
 }
 
 struct CameraUniform {
@@ -19,16 +26,55 @@ struct CameraUniform {
     view_projection: mat4x4<f32>,
 }
 
+struct SettingsUniform {
+    displacement_scale: f32,
+    parallax_step_scale: f32,
+    environment_sampling: u32,
+    environment_samples: u32,
+    frame_index: u32,
+    pointcloud_shading: u32,
+    ramp_min: f32,
+    ramp_max: f32,
+}
+
 struct TransformUniform {
     matrix: mat4x4<f32>,
 }
 
+struct LightUniform {
+    color: vec3<f32>,
+    intensity: f32,
+    kind: u32,
+    range: f32,
+    cos_inner_cutoff: f32,
+    cos_outer_cutoff: f32,
+    flags: u32,
+}
+
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+@group(1) @binding(1)
+var<uniform> settings: SettingsUniform;
+
+@group(1) @binding(4)
+var ramp_texture: texture_2d<f32>;
+
+@group(1) @binding(5)
+var ramp_sampler: sampler;
+
 @group(2) @binding(0)
 var<storage, read> transforms: array<TransformUniform>;
 
+@group(2) @binding(1)
+var<storage, read> normals: array<TransformUniform>;
+
+@group(2) @binding(2)
+var<storage, read> lights: array<LightUniform>;
+
+@group(2) @binding(3)
+var<storage, read> light_transform_index: array<u32>;
+
 @vertex
 fn vs_main(
     points: VertexInput,    
@@ -36,13 +82,84 @@ fn vs_main(
 ) -> VertexOutput {
     let transform = transforms[instance.transform_index].matrix;
 
+    let normal_matrix = normals[instance.normal_index].matrix;
+    let world_position = transform * vec4<f32>(points.position, 1.0);
+
     var out: VertexOutput;
-    out.clip_position = camera.view_projection * transform * vec4<f32>(points.position, 1.0);
+    out.clip_position = camera.view_projection * world_position;
     out.color = points.color;
+    out.world_position = world_position.xyz;
+    out.normal = (normal_matrix * vec4<f32>(points.normal, 0.0)).xyz;
+    out.intensity = points.intensity;
+    out.classification = points.classification;
     return out;
 }
 
+// Colors for the ASPRS standard classes, user defined classes share one
+fn classification_color(classification: u32) -> vec3<f32> {
+    switch classification {
+        case 0u, 1u: { return vec3<f32>(0.6, 0.6, 0.6); }
+        case 2u: { return vec3<f32>(0.55, 0.35, 0.15); }
+        case 3u: { return vec3<f32>(0.6, 0.85, 0.3); }
+        case 4u: { return vec3<f32>(0.3, 0.7, 0.15); }
+        case 5u: { return vec3<f32>(0.1, 0.45, 0.1); }
+        case 6u: { return vec3<f32>(0.85, 0.2, 0.15); }
+        case 7u, 18u: { return vec3<f32>(1.0, 0.0, 1.0); }
+        case 9u: { return vec3<f32>(0.15, 0.4, 0.9); }
+        case 10u, 11u: { return vec3<f32>(0.35, 0.35, 0.4); }
+        case 17u: { return vec3<f32>(0.9, 0.75, 0.2); }
+        case 13u, 14u, 15u, 16u: { return vec3<f32>(1.0, 0.9, 0.0); }
+        default: { return vec3<f32>(0.0, 0.9, 0.9); }
+    }
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);    
+    if (settings.pointcloud_shading == 2u) {
+        let range = max(settings.ramp_max - settings.ramp_min, 0.0001);
+        let value = clamp((in.intensity - settings.ramp_min) / range, 0.0, 1.0);
+        return vec4<f32>(textureSampleLevel(ramp_texture, ramp_sampler, vec2<f32>(value, 0.5), 0.0).rgb, 1.0);
+    }
+
+    if (settings.pointcloud_shading == 3u) {
+        return vec4<f32>(classification_color(in.classification), 1.0);
+    }
+
+    // Points without an estimated normal keep their plain color
+    if (settings.pointcloud_shading == 0u || dot(in.normal, in.normal) < 0.0001) {
+        return vec4<f32>(in.color, 1.0);
+    }
+
+    // Estimated normals have no reliable facing, turn them towards the viewer
+    let v = normalize(camera.view_position.xyz - in.world_position);
+    var n = normalize(in.normal);
+    if (dot(n, v) < 0.0) {
+        n = -n;
+    }
+
+    var lighting = vec3<f32>(0.1);
+    for (var i = 0u; i < arrayLength(&lights); i++) {
+        let light = lights[i];
+        let model = transforms[light_transform_index[i]].matrix;
+
+        var l = normalize(model[2].xyz);
+        var attenuation = 1.0;
+        if (light.kind != 0u) {
+            let to_light = model[3].xyz - in.world_position;
+            let distance = length(to_light);
+            l = normalize(to_light);
+            attenuation = 1.0 / max(distance * distance, 0.0001);
+            if (light.range > 0.0) {
+                let falloff = saturate(1.0 - pow(distance / light.range, 4.0));
+                attenuation *= falloff * falloff;
+            }
+        }
+        if (light.kind == 2u) {
+            attenuation *= smoothstep(light.cos_outer_cutoff, light.cos_inner_cutoff, dot(l, normalize(model[2].xyz)));
+        }
+
+        lighting += light.color * light.intensity * attenuation * max(dot(n, l), 0.0);
+    }
+
+    return vec4<f32>(in.color * lighting, 1.0);
 }
\ No newline at end of file
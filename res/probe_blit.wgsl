@@ -0,0 +1,38 @@
+// Copies a captured face into the probe cubemap and builds its mip chain
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+// Faces are rendered with the usual cubemap up vectors, which puts them upside down in a texture with a top left origin
+@fragment
+fn fs_face(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, vec2<f32>(in.uv.x, 1.0 - in.uv.y));
+}
+
+// Linear filtering averages the four texels of the previous level
+@fragment
+fn fs_downsample(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
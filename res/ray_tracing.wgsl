@@ -0,0 +1,142 @@
+// Scene triangles, BVH traversal and sampling shared by the path tracer and the lightmapper, prepended to both
+struct Triangle {
+    p0: vec3<f32>,
+    material: u32,
+    p1: vec3<f32>,
+    p2: vec3<f32>,
+    n0: vec3<f32>,
+    n1: vec3<f32>,
+    n2: vec3<f32>,
+}
+
+struct BvhNode {
+    min: vec3<f32>,
+    left_first: u32,
+    max: vec3<f32>,
+    count: u32,
+}
+
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+}
+
+struct Hit {
+    distance: f32,
+    triangle: u32,
+    barycentric: vec2<f32>,
+}
+
+@group(1) @binding(0) var<storage, read> triangles: array<Triangle>;
+@group(1) @binding(1) var<storage, read> nodes: array<BvhNode>;
+@group(1) @binding(2) var<storage, read> materials: array<Material>;
+
+const PI: f32 = 3.14159265;
+const MISS: f32 = 1e30;
+const STACK_SIZE: u32 = 32u;
+
+fn trace(origin: vec3<f32>, direction: vec3<f32>, triangle_count: u32) -> Hit {
+    var hit: Hit;
+    hit.distance = MISS;
+    if (triangle_count == 0u) {
+        return hit;
+    }
+
+    let inv_direction = 1.0 / direction;
+    var stack: array<u32, STACK_SIZE>;
+    var stack_size = 1u;
+    stack[0] = 0u;
+
+    while (stack_size > 0u) {
+        stack_size -= 1u;
+        let node = nodes[stack[stack_size]];
+        if (intersect_aabb(origin, inv_direction, node.min, node.max) >= hit.distance) {
+            continue;
+        }
+
+        if (node.count > 0u) {
+            for (var i = node.left_first; i < node.left_first + node.count; i++) {
+                let result = intersect_triangle(origin, direction, triangles[i]);
+                if (result.x > 0.0 && result.x < hit.distance) {
+                    hit.distance = result.x;
+                    hit.triangle = i;
+                    hit.barycentric = result.yz;
+                }
+            }
+        } else if (stack_size + 2u <= STACK_SIZE) {
+            stack[stack_size] = node.left_first;
+            stack[stack_size + 1u] = node.left_first + 1u;
+            stack_size += 2u;
+        }
+    }
+
+    return hit;
+}
+
+// Returns the entry distance, or MISS
+fn intersect_aabb(origin: vec3<f32>, inv_direction: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> f32 {
+    let t0 = (box_min - origin) * inv_direction;
+    let t1 = (box_max - origin) * inv_direction;
+    let t_near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), min(t0.z, t1.z));
+    let t_far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), max(t0.z, t1.z));
+
+    if (t_far < max(t_near, 0.0)) {
+        return MISS;
+    }
+
+    return max(t_near, 0.0);
+}
+
+// Möller-Trumbore, returns the distance and barycentric coordinates, or a negative distance on a miss
+fn intersect_triangle(origin: vec3<f32>, direction: vec3<f32>, triangle: Triangle) -> vec3<f32> {
+    let edge1 = triangle.p1 - triangle.p0;
+    let edge2 = triangle.p2 - triangle.p0;
+    let p = cross(direction, edge2);
+    let determinant = dot(edge1, p);
+    if (abs(determinant) < 1e-8) {
+        return vec3<f32>(-1.0);
+    }
+
+    let inv_determinant = 1.0 / determinant;
+    let s = origin - triangle.p0;
+    let u = dot(s, p) * inv_determinant;
+    if (u < 0.0 || u > 1.0) {
+        return vec3<f32>(-1.0);
+    }
+
+    let q = cross(s, edge1);
+    let v = dot(direction, q) * inv_determinant;
+    if (v < 0.0 || u + v > 1.0) {
+        return vec3<f32>(-1.0);
+    }
+
+    return vec3<f32>(dot(edge2, q) * inv_determinant, u, v);
+}
+
+fn hash(value: u32) -> u32 {
+    // PCG hash
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(n.y) < 0.999);
+    let t = normalize(cross(up, n));
+    let b = cross(n, t);
+    return mat3x3<f32>(t, b, n);
+}
+
+fn sample_cosine_hemisphere(n: vec3<f32>, xi: vec2<f32>) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let r = sqrt(xi.y);
+    let local = vec3<f32>(r * cos(phi), r * sin(phi), sqrt(max(1.0 - xi.y, 0.0)));
+    return normalize(tangent_frame(n) * local);
+}
@@ -0,0 +1,36 @@
+// Coverage of the selected entity, only the position is needed
+struct CameraUniform {
+    view_position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+}
+
+struct TransformUniform {
+    matrix: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(0)
+var<storage, read> transforms: array<TransformUniform>;
+
+@vertex
+fn vs_mesh(
+    @location(0) position: vec3<f32>,
+    @location(3) transform_index: u32,
+) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * transforms[transform_index].matrix * vec4<f32>(position, 1.0);
+}
+
+@vertex
+fn vs_points(
+    @location(0) position: vec3<f32>,
+    @location(4) transform_index: u32,
+) -> @builtin(position) vec4<f32> {
+    return camera.view_projection * transforms[transform_index].matrix * vec4<f32>(position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
@@ -16,6 +16,23 @@ struct InstanceInput {
     @location(10) normal_index: u32,
 }
 
+// Joints and weights take the place of the last UV set
+struct SkinnedVertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tangent: vec4<f32>,
+    @location(3) uv1: vec2<f32>,
+    @location(4) uv2: vec2<f32>,
+    @location(8) joints: vec4<u32>,
+    @location(9) weights: vec4<f32>,
+}
+
+struct SkinnedInstanceInput {
+    @location(10) transform_index: u32,
+    @location(11) normal_index: u32,
+    @location(12) palette_index: u32,
+}
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) world_position: vec3<f32>,
@@ -23,6 +40,7 @@ struct VertexOutput {
     @location(2) tangent: vec4<f32>,
     @location(3) tex_coords: vec2<f32>,
     @location(4) view_position: vec3<f32>,
+    @location(5) lightmap_coords: vec2<f32>,
 }
 
 struct CameraUniform {
@@ -32,6 +50,36 @@ struct CameraUniform {
     inv_projection: mat4x4<f32>,
 }
 
+struct SettingsUniform {
+    displacement_scale: f32,
+    parallax_step_scale: f32,
+    environment_sampling: u32,
+    environment_samples: u32,
+    frame_index: u32,
+}
+
+struct EnvironmentLight {
+    diffuse: vec3<f32>,
+    specular: vec3<f32>,
+}
+
+// Position and radius per probe, a zero radius marks an empty slot
+struct ProbeUniform {
+    probes: array<vec4<f32>, 4>,
+}
+
+// First order spherical harmonics per irradiance probe, red, green and blue in xyz
+struct IrradianceCoefficients {
+    coefficients: array<vec4<f32>, 4>,
+}
+
+// At least two probes per axis, the w component of resolution is one while the volume holds a bake
+struct IrradianceGrid {
+    min: vec4<f32>,
+    max: vec4<f32>,
+    resolution: vec4<u32>,
+}
+
 struct TransformUniform {
     matrix: mat4x4<f32>,
 }
@@ -40,17 +88,50 @@ struct NormalUniform {
     matrix: mat4x4<f32>,
 }
 
+struct JointPalette {
+    joints: array<mat4x4<f32>, 128>,
+}
+
 struct LightUniform {
     color: vec3<f32>,
-    cutoff: f32,    
-    intensity: f32,  
-    kind: u32,  
-    padding: vec2<u32>,
+    intensity: f32,
+    kind: u32,
+    range: f32,
+    cos_inner_cutoff: f32,
+    cos_outer_cutoff: f32,
+    flags: u32,
+}
+
+// Per shadow map. Cascades end at the camera distances in splits, a cube spans near to far
+struct ShadowUniform {
+    view_projections: array<mat4x4<f32>, 3>,
+    splits: vec4<f32>,
+    layer: u32,
+    near: f32,
+    far: f32,
+}
+
+// The cascaded maps first, then the cubes. Lights holds the light index of each, packed four to a vector
+struct ShadowsUniform {
+    shadows: array<ShadowUniform, 8>,
+    lights: array<vec4<u32>, 2>,
 }
 
+const SHADOW_CASCADES: u32 = 3u;
+const MAX_SHADOWS: u32 = 8u;
+
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+@group(1) @binding(1)
+var<uniform> settings: SettingsUniform;
+
+@group(1) @binding(2)
+var scene_color_texture: texture_2d<f32>;
+
+@group(1) @binding(3)
+var scene_color_sampler: sampler;
+
 @group(2) @binding(0)
 var<storage, read> transforms: array<TransformUniform>;
 
@@ -63,6 +144,46 @@ var<storage, read> lights: array<LightUniform>;
 @group(2) @binding(3)
 var<storage, read> light_transform_index: array<u32>;
 
+@group(2) @binding(4)
+var<storage, read> joint_palettes: array<JointPalette>;
+
+@group(2) @binding(5)
+var<uniform> shadows: ShadowsUniform;
+
+@group(2) @binding(6)
+var shadow_cascades: texture_depth_2d_array;
+
+@group(2) @binding(7)
+var shadow_cubes: texture_depth_cube_array;
+
+@group(2) @binding(8)
+var shadow_sampler: sampler_comparison;
+
+// Set for compact vertices, normals and tangents arrive octahedral encoded with the tangent's sign in z
+override QUANTIZED: bool = false;
+
+fn oct_decode(encoded: vec2<f32>) -> vec3<f32> {
+    var vector = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let fold = max(-vector.z, 0.0);
+    vector.x += select(fold, -fold, vector.x >= 0.0);
+    vector.y += select(fold, -fold, vector.y >= 0.0);
+    return normalize(vector);
+}
+
+fn decode_normal(normal: vec3<f32>) -> vec3<f32> {
+    if QUANTIZED {
+        return oct_decode(normal.xy);
+    }
+    return normal;
+}
+
+fn decode_tangent(tangent: vec4<f32>) -> vec4<f32> {
+    if QUANTIZED {
+        return vec4<f32>(oct_decode(tangent.xy), tangent.z);
+    }
+    return tangent;
+}
+
 @vertex
 fn vs_main(
     mesh: VertexInput,
@@ -71,15 +192,54 @@ fn vs_main(
     let model = transforms[instance.transform_index].matrix;
     let normal_matrix = mat4_to_mat3(normals[instance.normal_index].matrix);
     
-    let world_position = model * vec4<f32>(mesh.position, 1.0);    
-    let world_normal =  normalize(normal_matrix * mesh.normal);
-    let world_tangent = vec4<f32>(normalize(normal_matrix * mesh.tangent.xyz), mesh.tangent.w);
+    let normal = decode_normal(mesh.normal);
+    let tangent = decode_tangent(mesh.tangent);
+    return mesh_vertex(mesh.position, normal, tangent, mesh.uv1, mesh.uv2, model, normal_matrix);
+}
+
+// Blends the joint matrices of the entity's palette by the vertex weights, in object space before the model matrix
+@vertex
+fn vs_skinned(
+    mesh: SkinnedVertexInput,
+    instance: SkinnedInstanceInput,
+) -> VertexOutput {
+    let palette = instance.palette_index;
+    let skin = joint_palettes[palette].joints[mesh.joints.x] * mesh.weights.x
+        + joint_palettes[palette].joints[mesh.joints.y] * mesh.weights.y
+        + joint_palettes[palette].joints[mesh.joints.z] * mesh.weights.z
+        + joint_palettes[palette].joints[mesh.joints.w] * mesh.weights.w;
+    // Joints rarely scale, the linear part of the skin stands in for its inverse transpose
+    let skin_normal = mat4_to_mat3(skin);
+    let position = (skin * vec4<f32>(mesh.position, 1.0)).xyz;
+    let normal = normalize(skin_normal * mesh.normal);
+    let tangent = vec4<f32>(normalize(skin_normal * mesh.tangent.xyz), mesh.tangent.w);
+
+    let model = transforms[instance.transform_index].matrix;
+    let normal_matrix = mat4_to_mat3(normals[instance.normal_index].matrix);
+    return mesh_vertex(position, normal, tangent, mesh.uv1, mesh.uv2, model, normal_matrix);
+}
+
+fn mesh_vertex(
+    position: vec3<f32>,
+    normal: vec3<f32>,
+    tangent: vec4<f32>,
+    uv1: vec2<f32>,
+    uv2: vec2<f32>,
+    model: mat4x4<f32>,
+    normal_matrix: mat3x3<f32>,
+) -> VertexOutput {
+    let height = textureSampleLevel(height_texture, height_sampler, uv1, 0.0).r;
+    let displacement = normal * height * material.displacement_scale * settings.displacement_scale;
+    let world_position = model * vec4<f32>(position + displacement, 1.0);    
+    let world_normal =  normalize(normal_matrix * normal);
+    let world_tangent = vec4<f32>(normalize(normal_matrix * tangent.xyz), tangent.w);
 
     var out: VertexOutput;
     out.world_position = world_position.xyz;
     out.normal = world_normal;
     out.tangent = world_tangent;
-    out.tex_coords = mesh.uv1;
+    out.tex_coords = uv1;
+    out.lightmap_coords = uv2;
     out.view_position = camera.view_position.xyz;
     out.clip_position = camera.view_projection * world_position;
     return out;
@@ -89,6 +249,7 @@ fn vs_main(
 struct MaterialUniform {
     base_color_factor: vec4<f32>,
     emissive_factor: vec3<f32>,
+    texture_mask: u32,
     metallic_factor: f32,
     roughness_factor: f32,
     occlusion_strength: f32,
@@ -96,6 +257,19 @@ struct MaterialUniform {
     alpha_cutoff: f32,
     alpha_mode: u32,
     double_sided: u32,
+    displacement_scale: f32,
+    parallax_scale: f32,
+    parallax_steps: u32,
+    clearcoat_factor: f32,
+    clearcoat_roughness_factor: f32,
+    sheen_color_factor: vec3<f32>,
+    sheen_roughness_factor: f32,
+    clearcoat_normal_scale: f32,
+    transmission_factor: f32,
+    ior: f32,
+    thickness_factor: f32,
+    attenuation_color: vec3<f32>,
+    attenuation_distance: f32,
 }
 
 struct LightModel {
@@ -114,22 +288,63 @@ struct LightModel {
 @group(0) @binding(8) var occlusion_sampler: sampler;
 @group(0) @binding(9) var emissive_texture: texture_2d<f32>;
 @group(0) @binding(10) var emissive_sampler: sampler;
-
+@group(0) @binding(11) var height_texture: texture_2d<f32>;
+@group(0) @binding(12) var height_sampler: sampler;
+@group(0) @binding(13) var clearcoat_normal_texture: texture_2d<f32>;
+@group(0) @binding(14) var clearcoat_normal_sampler: sampler;
+@group(0) @binding(15) var lightmap_texture: texture_2d<f32>;
+@group(0) @binding(16) var lightmap_sampler: sampler;
+
+@group(3) @binding(0) var environment_map: texture_cube<f32>;
+@group(3) @binding(1) var environment_sampler: sampler;
 @group(3) @binding(2) var irradiance_map: texture_cube<f32>;
 @group(3) @binding(3) var irradiance_sampler: sampler;
+@group(3) @binding(4) var probe_map: texture_cube_array<f32>;
+@group(3) @binding(5) var probe_sampler: sampler;
+@group(3) @binding(6) var<uniform> probes: ProbeUniform;
+@group(3) @binding(7) var<storage, read> irradiance_probes: array<IrradianceCoefficients>;
+@group(3) @binding(8) var<uniform> irradiance_grid: IrradianceGrid;
+@group(3) @binding(9) var specular_map: texture_cube<f32>;
+@group(3) @binding(10) var specular_sampler: sampler;
+@group(3) @binding(11) var brdf_lut: texture_2d<f32>;
+@group(3) @binding(12) var brdf_sampler: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {       
-    let normal_sample = textureSample(normal_texture, normal_sampler, in.tex_coords).rgb;
-    let n = get_normal_from_map(normal_sample, in.normal, in.tangent, material.normal_scale);
     let v = normalize(in.view_position - in.world_position);
+    let tex_coords = parallax_occlusion(in.tex_coords, v, in.normal, in.tangent);
+
+    var n = normalize(in.normal);
+    if (has_texture(TEXTURE_NORMAL)) {
+        let normal_sample = textureSample(normal_texture, normal_sampler, tex_coords).rgb;
+        n = get_normal_from_map(normal_sample, in.normal, in.tangent, material.normal_scale);
+    }
     
-    let base_color_sample = textureSample(base_color_texture, base_color_sampler, in.tex_coords).rgb;
-    let albedo = pow(base_color_sample, vec3<f32>(2.2));
+    var albedo = vec3<f32>(1.0);
+    if (has_texture(TEXTURE_BASE_COLOR)) {
+        let base_color_sample = textureSample(base_color_texture, base_color_sampler, tex_coords).rgb;
+        albedo = pow(base_color_sample, vec3<f32>(2.2));
+    }
     
-    let mr_sample = textureSample(mr_texture, mr_sampler, in.tex_coords).rgb;
+    var mr_sample = vec3<f32>(1.0);
+    if (has_texture(TEXTURE_METALLIC_ROUGHNESS)) {
+        mr_sample = textureSample(mr_texture, mr_sampler, tex_coords).rgb;
+    }
     let metallic = mr_sample.b;
     let roughness = clamp(mr_sample.g, 0.04, 1.0);
+
+    // Without a clearcoat normal map the coating follows the geometric normal
+    var clearcoat_n = normalize(in.normal);
+    if (has_texture(TEXTURE_CLEARCOAT_NORMAL) && material.clearcoat_normal_scale > 0.0) {
+        let clearcoat_sample = textureSample(clearcoat_normal_texture, clearcoat_normal_sampler, tex_coords).rgb;
+        clearcoat_n = get_normal_from_map(clearcoat_sample, in.normal, in.tangent, material.clearcoat_normal_scale);
+    }
+    let clearcoat = material.clearcoat_factor;
+    let clearcoat_roughness = clamp(material.clearcoat_roughness_factor, 0.04, 1.0);
+    let sheen_color = material.sheen_color_factor;
+    let sheen_roughness = clamp(material.sheen_roughness_factor, 0.07, 1.0);
+    // Approximate sheen albedo scaling without the directional albedo lookup table
+    let sheen_scaling = 1.0 - max(sheen_color.r, max(sheen_color.g, sheen_color.b)) * 0.157;
     
     let occlusion = 1.0;
     // let occlusion = textureSample(occlusionTexture, occlusionSampler, in.tex_coords).r;
@@ -137,6 +352,7 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 
     var f0 = mix(vec3<f32>(0.04), albedo, metallic);
     var lo = vec3<f32>(0.0);
+    var lo_diffuse = vec3<f32>(0.0);
     
     for (var i = 0u; i < arrayLength(&lights); i++) {
         let transform_index = light_transform_index[i];
@@ -154,13 +370,14 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
                 let to_light = model.position - in.world_position; 
                 let distance = length(to_light);
                 l = normalize(to_light);                
-                attenuation = 1.0 / max(distance * distance, 0.0001);
+                attenuation = range_attenuation(distance, light.range);
             }
             case 2u: { // spot
                 let to_light = model.position - in.world_position;
                 let distance = length(to_light);                
                 l = normalize(to_light);
-                attenuation = 1.0 / max(distance * distance, 0.0001);                
+                attenuation = range_attenuation(distance, light.range)
+                    * smoothstep(light.cos_outer_cutoff, light.cos_inner_cutoff, dot(-l, model.direction));
             }
             default: {}
         }
@@ -185,16 +402,64 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
         let kd = (vec3<f32>(1.0) - ks) * (1.0 - metallic);
 
         let diffuse = kd * albedo / PI;
-        let radiance = light.color * light.intensity * attenuation;
-
-        lo += (diffuse + specular) * radiance * n_dot_l;
+        let shadow = light_shadow(i, light.kind, in.world_position, normalize(in.normal), model.position);
+        let radiance = light.color * light.intensity * attenuation * shadow;
+
+        let sheen = sheen_color * distribution_charlie(n, h, sheen_roughness) * visibility_neubelt(n_dot_l, n_dot_v);
+        let base = (diffuse + specular) * sheen_scaling + sheen;
+
+        let clearcoat_n_dot_v = max(dot(clearcoat_n, v), 0.0);
+        let clearcoat_n_dot_l = max(dot(clearcoat_n, l), 0.0);
+        let clearcoat_fresnel = fresnel_schlick(max(dot(h, v), 0.0), vec3<f32>(0.04)).x * clearcoat;
+        let clearcoat_specular = distribution_ggx(clearcoat_n, h, clearcoat_roughness)
+            * geometry_smith(clearcoat_n, v, l, clearcoat_roughness)
+            * clearcoat_fresnel
+            / max(4.0 * clearcoat_n_dot_v * clearcoat_n_dot_l, 0.0001);
+
+        lo += base * (1.0 - clearcoat_fresnel) * radiance * n_dot_l + clearcoat_specular * radiance * clearcoat_n_dot_l;
+        lo_diffuse += diffuse * sheen_scaling * (1.0 - clearcoat_fresnel) * radiance * n_dot_l;
     }
 
-    let irradiance = textureSample(irradiance_map, irradiance_sampler, n).rgb;
-    let kd = (vec3<f32>(1.0) - f0) * (1.0 - metallic);
-    let diffuse = irradiance * albedo * kd;
+    let clearcoat_ambient_fresnel = fresnel_schlick(max(dot(clearcoat_n, v), 0.0), vec3<f32>(0.04)).x * clearcoat;
+    var environment: EnvironmentLight;
+    if (settings.environment_sampling == 1u) {
+        environment = sample_environment(n, v, albedo, f0, metallic, roughness, in.world_position, in.clip_position.xy);
+    } else {
+        // Inside the irradiance volume the baked probes replace the environment's irradiance
+        let global_irradiance = textureSample(irradiance_map, irradiance_sampler, n).rgb;
+        let volume = volume_irradiance(in.world_position, n);
+        let irradiance = mix(global_irradiance, volume.rgb, volume.a);
+        let kd = (vec3<f32>(1.0) - f0) * (1.0 - metallic);
+        environment.diffuse = irradiance * albedo * kd;
+        // Split sum: the prefiltered radiance of the probes over the environment's, rougher surfaces read blurrier
+        // mips, scaled by the integrated BRDF
+        let r = reflect(-v, n);
+        let probe = probe_radiance(r, in.world_position, roughness * f32(textureNumLevels(probe_map) - 1));
+        let level = roughness * f32(textureNumLevels(specular_map) - 1);
+        let global_radiance = textureSampleLevel(specular_map, specular_sampler, r, level).rgb;
+        let radiance = probe.rgb + global_radiance * (1.0 - probe.a);
+        let brdf = textureSampleLevel(brdf_lut, brdf_sampler, vec2<f32>(max(dot(n, v), 0.0), roughness), 0.0).rg;
+        environment.specular = radiance * (f0 * brdf.x + brdf.y);
+    }
+    var diffuse = environment.diffuse * sheen_scaling * (1.0 - clearcoat_ambient_fresnel);
+    // Baked lighting stands in for the diffuse part of the lights and the environment, specular stays dynamic
+    if (has_texture(TEXTURE_LIGHTMAP)) {
+        let baked = textureSample(lightmap_texture, lightmap_sampler, in.lightmap_coords).rgb;
+        let kd = (vec3<f32>(1.0) - f0) * (1.0 - metallic);
+        diffuse = baked * albedo * kd * sheen_scaling * (1.0 - clearcoat_ambient_fresnel);
+        lo -= lo_diffuse;
+        lo_diffuse = vec3<f32>(0.0);
+    }
+    let ambient_specular = environment.specular * sheen_scaling * (1.0 - clearcoat_ambient_fresnel);
     // let ambient = vec3<f32>(0.001) * albedo * occlusion;
-    var color = lo + diffuse;
+    var color = lo + diffuse + ambient_specular;
+
+    // Transmission replaces the diffuse lobe with the refracted opaque scene
+    let transmission = material.transmission_factor * (1.0 - metallic);
+    if (transmission > 0.0) {
+        let transmitted = get_transmitted_light(n, v, in.world_position, roughness) * albedo;
+        color += (transmitted * (1.0 - clearcoat_ambient_fresnel) - diffuse - lo_diffuse) * transmission;
+    }
 
     // Tone map and gamma correct
     let mapped = color / (color + vec3<f32>(1.0));
@@ -220,6 +485,83 @@ fn from_transform(matrix: mat4x4<f32>) -> LightModel {
     return model;
 }
 
+// Fraction of the light reaching the position, one for lights without a shadow map. Filtered over 3x3 texels, the
+// position is pushed out along the normal by about a texel so surfaces don't shadow themselves
+fn light_shadow(index: u32, kind: u32, position: vec3<f32>, normal: vec3<f32>, light_position: vec3<f32>) -> f32 {
+    var slot = MAX_SHADOWS;
+    for (var i = 0u; i < MAX_SHADOWS; i++) {
+        if (shadows.lights[i / 4u][i % 4u] == index) {
+            slot = i;
+            break;
+        }
+    }
+    if (slot == MAX_SHADOWS) {
+        return 1.0;
+    }
+    let shadow = shadows.shadows[slot];
+
+    if (kind == 0u) {
+        let forward = -camera.inv_view[2].xyz;
+        let depth = dot(position - camera.view_position.xyz, forward);
+        var cascade = 0u;
+        while (cascade < SHADOW_CASCADES - 1u && depth > shadow.splits[cascade]) {
+            cascade++;
+        }
+        if (depth > shadow.splits[SHADOW_CASCADES - 1u]) {
+            return 1.0;
+        }
+
+        let view_projection = shadow.view_projections[cascade];
+        let size = f32(textureDimensions(shadow_cascades).x);
+        let texel = 2.0 / (view_projection[0][0] * size);
+        let clip = view_projection * vec4<f32>(position + normal * texel * 1.5, 1.0);
+        let uv = clip.xy * vec2<f32>(0.5, -0.5) + 0.5;
+        let layer = shadow.layer + cascade;
+
+        var lit = 0.0;
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let offset = vec2<f32>(f32(x), f32(y)) / size;
+                lit += textureSampleCompareLevel(shadow_cascades, shadow_sampler, uv + offset, layer, clip.z);
+            }
+        }
+        return lit / 9.0;
+    } else {
+        let size = f32(textureDimensions(shadow_cubes).x);
+        let distance = length(position - light_position);
+        // A cube texel covers about two distances over the resolution
+        let texel = 2.0 * distance / size;
+        let to_position = position + normal * texel * 1.5 - light_position;
+        let major = abs(to_position);
+        let z = max(major.x, max(major.y, major.z));
+        if (z >= shadow.far) {
+            return 1.0;
+        }
+        let reference = shadow.far * (z - shadow.near) / ((shadow.far - shadow.near) * z);
+
+        let frame = tangent_frame(normalize(to_position));
+        var lit = 0.0;
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let direction = to_position + (frame[0] * f32(x) + frame[1] * f32(y)) * texel;
+                lit += textureSampleCompareLevel(shadow_cubes, shadow_sampler, direction, shadow.layer, reference);
+            }
+        }
+        return lit / 9.0;
+    }
+}
+
+// Inverse square, windowed to reach zero at the light's range when it has one
+fn range_attenuation(distance: f32, range: f32) -> f32 {
+    var attenuation = 1.0 / max(distance * distance, 0.0001);
+    if (range > 0.0) {
+        let falloff = saturate(1.0 - pow(distance / range, 4.0));
+        attenuation *= falloff * falloff;
+    }
+
+    return attenuation;
+}
+
 fn fresnel_schlick(cos_theta: f32, f0: vec3<f32>) -> vec3<f32> {
     return f0 + (vec3<f32>(1.0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
 }
@@ -235,6 +577,17 @@ fn distribution_ggx(n: vec3<f32>, h: vec3<f32>, roughness: f32) -> f32 {
     return numerator / (3.14159265 * denominator * denominator);
 }
 
+fn distribution_charlie(n: vec3<f32>, h: vec3<f32>, roughness: f32) -> f32 {
+    let inv_alpha = 1.0 / (roughness * roughness);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let sin2 = max(1.0 - n_dot_h * n_dot_h, 0.0078125);
+    return (2.0 + inv_alpha) * pow(sin2, inv_alpha * 0.5) / (2.0 * 3.14159265);
+}
+
+fn visibility_neubelt(n_dot_l: f32, n_dot_v: f32) -> f32 {
+    return 1.0 / max(4.0 * (n_dot_l + n_dot_v - n_dot_l * n_dot_v), 0.0001);
+}
+
 fn geometry_schlick_ggx(n_dot_v: f32, roughness: f32) -> f32 {
     let r = (roughness + 1.0);
     let k = (r * r) / 8.0;
@@ -249,6 +602,17 @@ fn geometry_smith(n: vec3<f32>, v: vec3<f32>, l: vec3<f32>, roughness: f32) -> f
     return ggx1 * ggx2;
 }
 
+// Bits of MaterialUniform.texture_mask, in TextureInstanceSlot order
+const TEXTURE_BASE_COLOR: u32 = 0u;
+const TEXTURE_METALLIC_ROUGHNESS: u32 = 1u;
+const TEXTURE_NORMAL: u32 = 2u;
+const TEXTURE_CLEARCOAT_NORMAL: u32 = 6u;
+const TEXTURE_LIGHTMAP: u32 = 7u;
+
+fn has_texture(slot: u32) -> bool {
+    return (material.texture_mask & (1u << slot)) != 0u;
+}
+
 fn get_normal_from_map(normal_sample: vec3<f32>, normal: vec3<f32>, tangent: vec4<f32>, scale: f32) -> vec3<f32> {
     let n = normal;
     let t = tangent.xyz;
@@ -258,4 +622,244 @@ fn get_normal_from_map(normal_sample: vec3<f32>, normal: vec3<f32>, tangent: vec
     let tbn = mat3x3<f32>(t, b, n);
 
     return normalize(tbn * normal_tangent);
-}
\ No newline at end of file
+}
+
+fn parallax_occlusion(uv: vec2<f32>, v: vec3<f32>, normal: vec3<f32>, tangent: vec4<f32>) -> vec2<f32> {
+    let steps = floor(f32(material.parallax_steps) * settings.parallax_step_scale);
+    if (material.parallax_scale <= 0.0 || steps < 1.0) {
+        return uv;
+    }
+
+    let n = normalize(normal);
+    let t = normalize(tangent.xyz);
+    let b = normalize(cross(n, t) * tangent.w);
+    let view_tangent = vec3<f32>(dot(v, t), dot(v, b), dot(v, n));
+
+    // Take more layers at grazing angles, where the offset is largest
+    let layer_count = mix(steps * 2.0, steps, abs(view_tangent.z));
+    let layer_depth = 1.0 / layer_count;
+    let delta_uv = view_tangent.xy / max(view_tangent.z, 0.05) * material.parallax_scale / layer_count;
+
+    var current_uv = uv;
+    var previous_uv = uv;
+    var current_depth = 0.0;
+    var sampled_depth = 1.0 - textureSampleLevel(height_texture, height_sampler, current_uv, 0.0).r;
+
+    for (var i = 0u; i < u32(layer_count) && current_depth < sampled_depth; i++) {
+        previous_uv = current_uv;
+        current_uv -= delta_uv;
+        current_depth += layer_depth;
+        sampled_depth = 1.0 - textureSampleLevel(height_texture, height_sampler, current_uv, 0.0).r;
+    }
+
+    let previous_depth = 1.0 - textureSampleLevel(height_texture, height_sampler, previous_uv, 0.0).r;
+    let after = sampled_depth - current_depth;
+    let before = previous_depth - current_depth + layer_depth;
+    let denominator = after - before;
+    let weight = select(after / denominator, 0.0, abs(denominator) < 0.0001);
+
+    return mix(current_uv, previous_uv, weight);
+}
+
+fn get_transmitted_light(n: vec3<f32>, v: vec3<f32>, world_position: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let refracted = refract(-v, n, 1.0 / material.ior);
+    let exit_position = world_position + refracted * material.thickness_factor;
+
+    let clip = camera.view_projection * vec4<f32>(exit_position, 1.0);
+    let ndc = clip.xy / clip.w;
+    let screen_uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+
+    // Refraction through a thin surface is sharp, so scale the blur by how much the IOR bends light
+    let blur = roughness * clamp(material.ior * 2.0 - 2.0, 0.0, 1.0) * 0.05;
+    var transmitted = textureSampleLevel(scene_color_texture, scene_color_sampler, screen_uv, 0.0).rgb;
+    for (var i = 0u; i < 8u; i++) {
+        let angle = f32(i) * 0.785398;
+        let offset = vec2<f32>(cos(angle), sin(angle)) * blur;
+        transmitted += textureSampleLevel(scene_color_texture, scene_color_sampler, screen_uv + offset, 0.0).rgb;
+    }
+    transmitted /= 9.0;
+
+    // Beer-Lambert absorption through the volume, disabled when the distance is zero
+    if (material.attenuation_distance > 0.0) {
+        let attenuation = -log(max(material.attenuation_color, vec3<f32>(0.0001))) / material.attenuation_distance;
+        transmitted *= exp(-attenuation * material.thickness_factor);
+    }
+
+    return transmitted;
+}
+
+fn hash(value: u32) -> u32 {
+    // PCG hash
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random(state: ptr<function, u32>) -> f32 {
+    *state = hash(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(n.y) < 0.999);
+    let t = normalize(cross(up, n));
+    let b = cross(n, t);
+    return mat3x3<f32>(t, b, n);
+}
+
+fn sample_cosine_hemisphere(n: vec3<f32>, xi: vec2<f32>) -> vec3<f32> {
+    let phi = 2.0 * 3.14159265 * xi.x;
+    let r = sqrt(xi.y);
+    let local = vec3<f32>(r * cos(phi), r * sin(phi), sqrt(max(1.0 - xi.y, 0.0)));
+    return normalize(tangent_frame(n) * local);
+}
+
+fn sample_ggx(n: vec3<f32>, v: vec3<f32>, roughness: f32, xi: vec2<f32>) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * 3.14159265 * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let h = normalize(tangent_frame(n) * vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta));
+    return reflect(-v, h);
+}
+
+// Weighted average of the probes covering a position, alpha is the total weight clamped to one
+fn probe_radiance(direction: vec3<f32>, position: vec3<f32>, level: f32) -> vec4<f32> {
+    var radiance = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < 4u; i++) {
+        let probe = probes.probes[i];
+        if (probe.w <= 0.0) {
+            continue;
+        }
+
+        // Full influence up to three quarters of the radius, fading out towards the edge
+        let w = clamp((probe.w - distance(position, probe.xyz)) / (probe.w * 0.25), 0.0, 1.0);
+        if (w > 0.0) {
+            radiance += textureSampleLevel(probe_map, probe_sampler, direction, i, level).rgb * w;
+            weight += w;
+        }
+    }
+
+    if (weight > 1.0) {
+        return vec4<f32>(radiance / weight, 1.0);
+    }
+    return vec4<f32>(radiance, weight);
+}
+
+fn probe_irradiance(index: u32, n: vec3<f32>) -> vec3<f32> {
+    let c = irradiance_probes[index].coefficients;
+    // Cosine lobe convolution, divided by pi to match the prefiltered irradiance map
+    let irradiance = c[0].xyz * 0.282095 + (c[1].xyz * n.y + c[2].xyz * n.z + c[3].xyz * n.x) * 0.488603 * (2.0 / 3.0);
+    return max(irradiance, vec3<f32>(0.0));
+}
+
+// Trilinear blend of the eight surrounding probes, alpha fades from one to zero over the cell outside the grid
+fn volume_irradiance(position: vec3<f32>, n: vec3<f32>) -> vec4<f32> {
+    if (irradiance_grid.resolution.w == 0u) {
+        return vec4<f32>(0.0);
+    }
+
+    let resolution = irradiance_grid.resolution.xyz;
+    let last = vec3<f32>(resolution - 1u);
+    let extent = max(irradiance_grid.max.xyz - irradiance_grid.min.xyz, vec3<f32>(0.0001));
+    let local = (position - irradiance_grid.min.xyz) / extent * last;
+
+    let outside = max(max(-local, local - last), vec3<f32>(0.0));
+    let weight = clamp(1.0 - max(outside.x, max(outside.y, outside.z)), 0.0, 1.0);
+    if (weight <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+
+    let clamped = clamp(local, vec3<f32>(0.0), last);
+    let base = min(vec3<u32>(floor(clamped)), resolution - 2u);
+    let t = clamped - vec3<f32>(base);
+
+    var irradiance = vec3<f32>(0.0);
+    for (var corner = 0u; corner < 8u; corner++) {
+        let offset = vec3<u32>(corner & 1u, (corner >> 1u) & 1u, (corner >> 2u) & 1u);
+        let cell = base + offset;
+        let index = cell.x + cell.y * resolution.x + cell.z * resolution.x * resolution.y;
+        let factors = mix(vec3<f32>(1.0) - t, t, vec3<f32>(offset));
+        irradiance += probe_irradiance(index, n) * factors.x * factors.y * factors.z;
+    }
+
+    return vec4<f32>(irradiance, weight);
+}
+
+fn environment_radiance(direction: vec3<f32>, position: vec3<f32>) -> vec3<f32> {
+    let probe = probe_radiance(direction, position, 0.0);
+    let global = textureSampleLevel(environment_map, environment_sampler, direction, 0.0).rgb;
+    return probe.rgb + global * (1.0 - probe.a);
+}
+
+fn environment_contribution(
+    l: vec3<f32>,
+    n: vec3<f32>,
+    v: vec3<f32>,
+    albedo: vec3<f32>,
+    f0: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    position: vec3<f32>,
+) -> EnvironmentLight {
+    var out: EnvironmentLight;
+    out.diffuse = vec3<f32>(0.0);
+    out.specular = vec3<f32>(0.0);
+
+    let n_dot_l = dot(n, l);
+    if (n_dot_l <= 0.0) {
+        return out;
+    }
+
+    let PI = 3.14159265;
+    let h = normalize(v + l);
+    let n_dot_v = max(dot(n, v), 0.0001);
+    let n_dot_h = max(dot(n, h), 0.0);
+    let v_dot_h = max(dot(v, h), 0.0001);
+
+    let d = distribution_ggx(n, h, roughness);
+    let f = fresnel_schlick(v_dot_h, f0);
+    let specular = d * geometry_smith(n, v, l, roughness) * f / max(4.0 * n_dot_v * n_dot_l, 0.0001);
+    let diffuse = (vec3<f32>(1.0) - f) * (1.0 - metallic) * albedo / PI;
+
+    // Balance heuristic over one cosine and one GGX sample, which reduces to dividing by the summed pdfs
+    let pdf = n_dot_l / PI + d * n_dot_h / (4.0 * v_dot_h);
+    let radiance = environment_radiance(l, position) * n_dot_l / max(pdf, 0.0001);
+
+    out.diffuse = diffuse * radiance;
+    out.specular = specular * radiance;
+    return out;
+}
+
+// Monte Carlo reference for the prefiltered irradiance lookup, averaged over frames by the accumulation pass
+fn sample_environment(
+    n: vec3<f32>,
+    v: vec3<f32>,
+    albedo: vec3<f32>,
+    f0: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    position: vec3<f32>,
+    pixel: vec2<f32>,
+) -> EnvironmentLight {
+    var state = hash(u32(pixel.x) ^ hash(u32(pixel.y) ^ hash(settings.frame_index)));
+    var out: EnvironmentLight;
+    out.diffuse = vec3<f32>(0.0);
+    out.specular = vec3<f32>(0.0);
+
+    let samples = max(settings.environment_samples, 1u);
+    for (var i = 0u; i < samples; i++) {
+        let diffuse_l = sample_cosine_hemisphere(n, vec2<f32>(random(&state), random(&state)));
+        let specular_l = sample_ggx(n, v, roughness, vec2<f32>(random(&state), random(&state)));
+
+        let diffuse_sample = environment_contribution(diffuse_l, n, v, albedo, f0, metallic, roughness, position);
+        let specular_sample = environment_contribution(specular_l, n, v, albedo, f0, metallic, roughness, position);
+        out.diffuse += diffuse_sample.diffuse + specular_sample.diffuse;
+        out.specular += diffuse_sample.specular + specular_sample.specular;
+    }
+
+    out.diffuse /= f32(samples);
+    out.specular /= f32(samples);
+    return out;
+}
@@ -0,0 +1,40 @@
+// Depth of the scene from one shadow map layer, the view projection sits at a dynamic offset
+struct TransformUniform {
+    matrix: mat4x4<f32>,
+}
+
+struct JointPalette {
+    joints: array<mat4x4<f32>, 128>,
+}
+
+@group(0) @binding(0)
+var<uniform> light_view_projection: mat4x4<f32>;
+
+@group(0) @binding(1)
+var<storage, read> transforms: array<TransformUniform>;
+
+@group(0) @binding(2)
+var<storage, read> joint_palettes: array<JointPalette>;
+
+@vertex
+fn vs_mesh(
+    @location(0) position: vec3<f32>,
+    @location(3) transform_index: u32,
+) -> @builtin(position) vec4<f32> {
+    return light_view_projection * transforms[transform_index].matrix * vec4<f32>(position, 1.0);
+}
+
+@vertex
+fn vs_skinned(
+    @location(0) position: vec3<f32>,
+    @location(3) joints: vec4<u32>,
+    @location(4) weights: vec4<f32>,
+    @location(5) transform_index: u32,
+    @location(7) palette_index: u32,
+) -> @builtin(position) vec4<f32> {
+    let skin = joint_palettes[palette_index].joints[joints.x] * weights.x
+        + joint_palettes[palette_index].joints[joints.y] * weights.y
+        + joint_palettes[palette_index].joints[joints.z] * weights.z
+        + joint_palettes[palette_index].joints[joints.w] * weights.w;
+    return light_view_projection * transforms[transform_index].matrix * skin * vec4<f32>(position, 1.0);
+}
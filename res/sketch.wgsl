@@ -0,0 +1,19 @@
+// Shader sketch, edit sketch.wgsl in the working directory to replace it.
+// Available: sketch.resolution, sketch.mouse (pixels), sketch.time (seconds), sketch.frame,
+// scene_color, scene_depth and scene_sampler. Output is linear HDR color.
+// Custom glTF attributes (_CUSTOM0 and so on) of the loaded primitives are in custom_attributes, the first
+// sketch.custom_count entries of custom_ranges say where each primitive's values are.
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(scene_color, scene_sampler, in.uv).rgb;
+    let depth = textureLoad(scene_depth, vec2<i32>(in.clip_position.xy), 0);
+
+    // Ripple around the mouse, only over geometry so the background keeps its color
+    let distance_to_mouse = length(in.clip_position.xy - sketch.mouse) / sketch.resolution.y;
+    let ripple = 0.5 + 0.5 * sin(distance_to_mouse * 40.0 - sketch.time * 4.0);
+    let coverage = select(1.0, 0.0, depth >= 1.0);
+    let tint = mix(vec3<f32>(1.0), vec3<f32>(0.4, 0.8, 1.0), ripple * coverage);
+
+    return vec4<f32>(color * tint, 1.0);
+}
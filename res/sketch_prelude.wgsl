@@ -0,0 +1,43 @@
+// Prepended to every shader sketch
+struct SketchUniform {
+    resolution: vec2<f32>,
+    mouse: vec2<f32>,
+    time: f32,
+    frame: u32,
+    // Primitives in custom_ranges
+    custom_count: u32,
+}
+
+// Attribute `a` of vertex `v` is custom_attributes[offset + a * vertex_count + v]
+struct CustomRange {
+    offset: u32,
+    vertex_count: u32,
+    attribute_count: u32,
+}
+
+struct VertexOutput {
+    @location(0) uv: vec2<f32>,
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> sketch: SketchUniform;
+@group(0) @binding(1) var scene_color: texture_2d<f32>;
+@group(0) @binding(2) var scene_depth: texture_depth_2d;
+@group(0) @binding(3) var scene_sampler: sampler;
+@group(0) @binding(4) var<storage, read> custom_attributes: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read> custom_ranges: array<CustomRange>;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Generate a triangle that covers the whole screen
+    out.uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+    out.clip_position = vec4<f32>(out.uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv.y = 1.0 - out.uv.y;
+    return out;
+}
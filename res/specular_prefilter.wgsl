@@ -0,0 +1,95 @@
+const PI : f32 = 3.141592653589793;
+
+// Bindings:
+// 0 = src cubemap (texture_cube<f32>)
+// 1 = sampler
+// 2 = dst storage of one mip of the prefiltered cube
+// 3 = roughness of that mip
+@group(0) @binding(0) var src_cubemap : texture_cube<f32>;
+@group(0) @binding(1) var src_sampler : sampler;
+@group(0) @binding(2) var dst_specular : texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(3) var<uniform> roughness : vec4<f32>;
+
+const SAMPLE_COUNT : u32 = 1024u;
+
+// Standard WGPU cube mapping
+fn face_uv_to_dir(face : u32, uv : vec2<f32>) -> vec3<f32> {
+    let sc = uv.x;
+    let tc = uv.y;
+
+    switch (face) {
+        case 0u: { return normalize(vec3<f32>( 1.0,   -tc,  -sc)); } // +X
+        case 1u: { return normalize(vec3<f32>(-1.0,   -tc,   sc)); } // -X
+        case 2u: { return normalize(vec3<f32>( sc,    1.0,   tc)); } // +Y
+        case 3u: { return normalize(vec3<f32>( sc,   -1.0,  -tc)); } // -Y
+        case 4u: { return normalize(vec3<f32>( sc,   -tc,   1.0)); } // +Z
+        default: { return normalize(vec3<f32>(-sc,  -tc,  -1.0));  } // -Z
+    }
+}
+
+fn radical_inverse(bits_in : u32) -> f32 {
+    var bits = (bits_in << 16u) | (bits_in >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+// GGX distributed half vector around +Z
+fn importance_sample_ggx(xi : vec2<f32>, alpha : f32) -> vec3<f32> {
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn prefilter_specular(@builtin(global_invocation_id) gid : vec3<u32>) {
+    let tex_size : vec2<u32> = textureDimensions(dst_specular);
+    let face : u32 = gid.z;
+    if (gid.x >= tex_size.x || gid.y >= tex_size.y || face >= 6u) {
+        return;
+    }
+
+    let px  = vec2<f32>(f32(gid.x) + 0.5, f32(gid.y) + 0.5);
+    let uv0 = (px / vec2<f32>(tex_size)) * 2.0 - vec2<f32>(1.0, 1.0);
+    let uv = vec2<f32>(uv0.x, -uv0.y);
+
+    // View and normal along the reflection, the usual split sum assumption
+    let n = face_uv_to_dir(face, uv);
+
+    // The top mip is the mirror reflection
+    if (roughness.x <= 0.0) {
+        let color = textureSampleLevel(src_cubemap, src_sampler, n, 0.0).rgb;
+        textureStore(dst_specular, vec2<i32>(gid.xy), i32(face), vec4<f32>(color, 1.0));
+        return;
+    }
+
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(n.y) > 0.999) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+
+    let alpha = roughness.x * roughness.x;
+    var accum = vec3<f32>(0.0, 0.0, 0.0);
+    var weight = 0.0;
+    for (var i : u32 = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let xi = vec2<f32>(f32(i) / f32(SAMPLE_COUNT), radical_inverse(i));
+        let local = importance_sample_ggx(xi, alpha);
+        let h = normalize(tangent * local.x + bitangent * local.y + n * local.z);
+        let l = normalize(2.0 * dot(n, h) * h - n);
+
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            accum = accum + textureSampleLevel(src_cubemap, src_sampler, l, 0.0).rgb * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+    }
+
+    let result = accum / max(weight, 0.0001);
+    textureStore(dst_specular, vec2<i32>(gid.xy), i32(face), vec4<f32>(result, 1.0));
+}
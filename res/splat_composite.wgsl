@@ -0,0 +1,44 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Generate a triangle that covers the whole screen
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+@group(0) @binding(0)
+var splat_color: texture_2d<f32>;
+
+@group(0) @binding(1)
+var splat_depth: texture_depth_2d;
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let depth = textureLoad(splat_depth, pixel, 0);
+
+    // No point landed here yet
+    if (depth >= 1.0) {
+        discard;
+    }
+
+    var out: FragmentOutput;
+    out.color = textureLoad(splat_color, pixel, 0);
+    out.depth = depth;
+    return out;
+}
@@ -0,0 +1,121 @@
+struct CameraUniform {
+    view_position: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+    inv_projection: mat4x4<f32>,
+}
+
+struct SettingsUniform {
+    displacement_scale: f32,
+    parallax_step_scale: f32,
+    environment_sampling: u32,
+    environment_samples: u32,
+    frame_index: u32,
+    pointcloud_shading: u32,
+    ramp_min: f32,
+    ramp_max: f32,
+}
+
+struct VolumeUniform {
+    world_to_volume: mat4x4<f32>,
+    steps: u32,
+    density: f32,
+    use_color_ramp: u32,
+    _padding: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> volume: VolumeUniform;
+@group(0) @binding(1) var volume_texture: texture_3d<f32>;
+@group(0) @binding(2) var volume_sampler: sampler;
+@group(0) @binding(3) var transfer_texture: texture_2d<f32>;
+@group(0) @binding(4) var depth_texture: texture_depth_2d;
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+@group(1) @binding(1)
+var<uniform> settings: SettingsUniform;
+
+@group(1) @binding(4)
+var ramp_texture: texture_2d<f32>;
+
+@group(1) @binding(5)
+var ramp_sampler: sampler;
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // Generate a triangle that covers the whole screen
+    let uv = vec2<f32>(
+        f32((index << 1u) & 2u),
+        f32(index & 2u),
+    );
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+// View space position, inv_view only holds the camera rotation so it is applied to directions only
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let view = camera.inv_projection * vec4<f32>(ndc, depth, 1.0);
+    return view.xyz / view.w;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<f32>(textureDimensions(depth_texture));
+    let uv = in.clip_position.xy / size;
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+
+    // March from the camera up to whatever opaque geometry is already in the depth buffer
+    let origin = camera.view_position.xyz;
+    let to_surface = unproject(ndc, textureLoad(depth_texture, pixel, 0));
+    let max_distance = length(to_surface);
+    let direction = (camera.inv_view * vec4<f32>(to_surface / max_distance, 0.0)).xyz;
+
+    // Box intersection in volume space, where the volume spans [0, 1] on every axis
+    let volume_origin = (volume.world_to_volume * vec4<f32>(origin, 1.0)).xyz;
+    let volume_direction = (volume.world_to_volume * vec4<f32>(direction, 0.0)).xyz;
+    let inv_direction = 1.0 / volume_direction;
+    let t0 = (vec3<f32>(0.0) - volume_origin) * inv_direction;
+    let t1 = (vec3<f32>(1.0) - volume_origin) * inv_direction;
+    let t_near = min(t0, t1);
+    let t_far = max(t0, t1);
+    let t_min = max(max(t_near.x, max(t_near.y, t_near.z)), 0.0);
+    let t_max = min(min(t_far.x, min(t_far.y, t_far.z)), max_distance);
+    if (t_min >= t_max) {
+        discard;
+    }
+
+    let step = (t_max - t_min) / f32(volume.steps);
+    var color = vec3<f32>(0.0);
+    var alpha = 0.0;
+    for (var i = 0u; i < volume.steps; i++) {
+        let t = t_min + (f32(i) + 0.5) * step;
+        let value = textureSampleLevel(volume_texture, volume_sampler, volume_origin + volume_direction * t, 0.0).r;
+        var sample = textureSampleLevel(transfer_texture, volume_sampler, vec2<f32>(value, 0.5), 0.0);
+        // The transfer function keeps control of the opacity
+        if (volume.use_color_ramp != 0u) {
+            let range = max(settings.ramp_max - settings.ramp_min, 0.0001);
+            let ramp_value = clamp((value - settings.ramp_min) / range, 0.0, 1.0);
+            sample = vec4<f32>(textureSampleLevel(ramp_texture, ramp_sampler, vec2<f32>(ramp_value, 0.5), 0.0).rgb, sample.a);
+        }
+
+        // Opacity is defined per world unit so it doesn't depend on the step count
+        let sample_alpha = 1.0 - pow(1.0 - sample.a, step * volume.density);
+        color += (1.0 - alpha) * sample.rgb * sample_alpha;
+        alpha += (1.0 - alpha) * sample_alpha;
+
+        if (alpha > 0.99) {
+            break;
+        }
+    }
+
+    return vec4<f32>(color, alpha);
+}
@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, path::Path};
+
+use uuid::Uuid;
+
+use crate::{entity::EntityStore, renderer::AovImages};
+
+// Written next to screenshot.png. Depth is an EXR with the distance in every color channel and coverage in alpha,
+// normals a PNG mapped from -1..1 to 0..255, and the id mask a 16 bit grayscale PNG whose values are listed with their
+// entity in a JSON file. Ids are numbered from one in the order the renderer stores the entities, zero is the
+// background
+pub fn save_aovs(images: &AovImages, entities: &EntityStore) -> anyhow::Result<()> {
+    let (width, height) = (images.width, images.height);
+    let pixel_count = (width * height) as usize;
+    if images.depth.len() != pixel_count || images.ids.len() != pixel_count {
+        anyhow::bail!("The auxiliary buffers could not be read back");
+    }
+
+    let depth = images
+        .depth
+        .iter()
+        .zip(&images.ids)
+        .flat_map(|(&depth, &id)| [depth, depth, depth, if id == 0 { 0.0 } else { 1.0 }])
+        .collect();
+    let depth = image::Rgba32FImage::from_raw(width, height, depth).unwrap();
+    depth.save("screenshot_depth.exr")?;
+
+    let normals = images
+        .normals
+        .iter()
+        .zip(&images.ids)
+        .flat_map(|(normal, &id)| {
+            let color = (*normal * 0.5 + 0.5).clamp(glam::Vec3::ZERO, glam::Vec3::ONE) * 255.0;
+            [
+                color.x as u8,
+                color.y as u8,
+                color.z as u8,
+                if id == 0 { 0 } else { 255 },
+            ]
+        })
+        .collect();
+    let normals = image::RgbaImage::from_raw(width, height, normals).unwrap();
+    normals.save("screenshot_normals.png")?;
+
+    // Renderer ids are transform slots, sparse and shared with lights, so the visible ones are renumbered
+    let mut mask_ids = images
+        .ids
+        .iter()
+        .filter(|&&id| id != 0)
+        .map(|&id| (id, 0))
+        .collect::<BTreeMap<u32, u16>>();
+    if mask_ids.len() > u16::MAX as usize {
+        anyhow::bail!("More than {} entities are visible, too many for the id mask", u16::MAX);
+    }
+    let mut legend = serde_json::Map::new();
+    for (index, (id, mask_id)) in mask_ids.iter_mut().enumerate() {
+        *mask_id = index as u16 + 1;
+        legend.insert(mask_id.to_string(), entity_legend(images.entities.get(id), entities));
+    }
+
+    let mask = images
+        .ids
+        .iter()
+        .map(|id| mask_ids.get(id).copied().unwrap_or(0))
+        .collect();
+    save_mask("screenshot_ids.png", width, height, mask)?;
+    std::fs::write("screenshot_ids.json", serde_json::to_string_pretty(&legend)?)?;
+
+    Ok(())
+}
+
+// What a mask id stands for, the entity may have been removed since the capture
+pub fn entity_legend(entity_id: Option<&Uuid>, entities: &EntityStore) -> serde_json::Value {
+    let entity = entity_id.and_then(|entity_id| entities.get(entity_id));
+    serde_json::json!({
+        "entity": entity_id.map(ToString::to_string),
+        "label": entity.and_then(|entity| entity.label().clone()),
+        "tags": entity.map(|entity| entity.tags().iter().collect::<Vec<_>>()).unwrap_or_default(),
+    })
+}
+
+// 16 bit grayscale, top row first
+pub fn save_mask(path: impl AsRef<Path>, width: u32, height: u32, mask: Vec<u16>) -> anyhow::Result<()> {
+    let mask = image::ImageBuffer::<image::Luma<u16>, Vec<u16>>::from_raw(width, height, mask)
+        .ok_or_else(|| anyhow::anyhow!("The mask doesn't match its size"))?;
+    mask.save(path)?;
+    Ok(())
+}
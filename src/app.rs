@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, KeyEvent, WindowEvent},
+    event::{DeviceEvent, KeyEvent, StartCause, WindowEvent},
     event_loop::ActiveEventLoop,
     keyboard::{KeyCode, PhysicalKey},
     window::{Window, WindowId},
@@ -84,11 +84,10 @@ impl ApplicationHandler<State> for App {
         {
             if let Some(proxy) = self.proxy.take() {
                 wasm_bindgen_futures::spawn_local(async move {
-                    assert!(
-                        proxy
-                            .send_event(State::new(window).await.expect("Unable to create canvas"))
-                            .is_ok()
-                    )
+                    match State::new(window).await {
+                        Ok(state) => assert!(proxy.send_event(state).is_ok()),
+                        Err(error) => crate::capability::show_report(&error),
+                    }
                 });
             }
         }
@@ -113,6 +112,15 @@ impl ApplicationHandler<State> for App {
         self.state = Some(event);
     }
 
+    // Throttled redraws wait for the timeout set in `State::update`
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: StartCause) {
+        if let StartCause::ResumeTimeReached { .. } = cause
+            && let Some(state) = &self.state
+        {
+            state.window().request_redraw();
+        }
+    }
+
     fn device_event(
         &mut self,
         _event_loop: &ActiveEventLoop,
@@ -125,23 +133,31 @@ impl ApplicationHandler<State> for App {
         };
 
         match event {
-            DeviceEvent::MouseMotion { delta: (dx, dy) } => {
-                let controller = state.camera_controller_mut();
-
-                if controller.is_mouse_pressed() {
-                    controller.handle_mouse(-dx, dy);
-                }
-            }
+            DeviceEvent::MouseMotion { delta: (dx, dy) } => state.handle_mouse_motion(dx, dy),
             _ => (),
         }
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
         let state = match &mut self.state {
             Some(state) => state,
             None => return,
         };
 
+        // Focus moving between the app's own windows arrives as a loss on one right before a gain on the other
+        if let WindowEvent::Focused(is_focused) = event {
+            state.set_focused(is_focused);
+        }
+
+        if window_id != state.window().id() {
+            state.handle_viewport_event(window_id, event);
+            return;
+        }
+
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            state.request_redraw();
+        }
+
         if state.ui_mut().on_event(&event) {
             return;
         }
@@ -149,19 +165,22 @@ impl ApplicationHandler<State> for App {
         match event {
             WindowEvent::CloseRequested => state.exit(),
             WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            WindowEvent::Occluded(is_occluded) => state.set_occluded(is_occluded),
             WindowEvent::RedrawRequested => state.update(event_loop),
             WindowEvent::MouseInput {
                 state: button_state,
                 button,
                 ..
             } => {
-                state
-                    .camera_controller_mut()
-                    .handle_mouse_button(button, button_state.is_pressed());
+                state.handle_mouse_button(button, button_state.is_pressed());
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                state.handle_cursor_moved(position.x, position.y);
             }
             WindowEvent::MouseWheel { delta, .. } => {
                 state.camera_controller_mut().handle_scroll(&delta);
             }
+            WindowEvent::ModifiersChanged(modifiers) => state.handle_modifiers(modifiers.state()),
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
@@ -171,10 +190,11 @@ impl ApplicationHandler<State> for App {
                     },
                 ..
             } => {
+                let is_shortcut = key_state.is_pressed() && state.handle_shortcut(code);
                 // TODO Move elsewhere
-                if code == KeyCode::Escape && key_state.is_pressed() {
+                if code == KeyCode::Escape && key_state.is_pressed() && !is_shortcut {
                     state.exit();
-                } else {
+                } else if !is_shortcut {
                     state.camera_controller_mut().handle_key(code, key_state);
                     // self.handle_key(event_loop, code, key_state.is_pressed())
                 }
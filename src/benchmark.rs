@@ -0,0 +1,221 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use instant::Instant;
+use serde::Serialize;
+
+// `--benchmark <scene> [--benchmark-frames <count>] [--benchmark-output <path>]`, the output path gets a .json and a
+// .csv next to each other
+#[derive(Clone, Debug)]
+pub struct BenchmarkConfig {
+    pub scene: String,
+    pub frames: u32,
+    pub output: PathBuf,
+}
+
+impl BenchmarkConfig {
+    const DEFAULT_FRAMES: u32 = 600;
+
+    pub fn from_args() -> Option<Self> {
+        if cfg!(target_family = "wasm") {
+            return None;
+        }
+
+        let args = std::env::args().collect::<Vec<_>>();
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .cloned()
+        };
+
+        let scene = value("--benchmark")?;
+        let frames = value("--benchmark-frames")
+            .and_then(|frames| frames.parse().ok())
+            .unwrap_or(Self::DEFAULT_FRAMES);
+        let output = value("--benchmark-output").unwrap_or_else(|| "benchmark".to_string());
+
+        Some(Self {
+            scene,
+            frames: frames.max(1),
+            output: PathBuf::from(output),
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Phase {
+    Loading(Instant),
+    Warmup(u32),
+    Running(u32),
+    Finished,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    average_ms: f32,
+    p95_ms: f32,
+    p99_ms: f32,
+    min_ms: f32,
+    max_ms: f32,
+}
+
+impl Stats {
+    fn from_samples(samples: &[f32]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let percentile = |p: f32| {
+            let index = ((sorted.len() as f32 * p).ceil() as usize).saturating_sub(1);
+            sorted.get(index).copied().unwrap_or(0.0)
+        };
+
+        Self {
+            average_ms: sorted.iter().sum::<f32>() / sorted.len().max(1) as f32,
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            min_ms: sorted.first().copied().unwrap_or(0.0),
+            max_ms: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BenchmarkReport {
+    scene: String,
+    frames: u32,
+    frame_time: Stats,
+    gpu_passes: BTreeMap<&'static str, Stats>,
+}
+
+// Orbits the origin once over the configured frame count after the scene loaded and the pipelines warmed up
+pub struct Benchmark {
+    config: BenchmarkConfig,
+    phase: Phase,
+    frame_times: Vec<f32>,
+    pass_times: BTreeMap<&'static str, Vec<f32>>,
+}
+
+impl Benchmark {
+    const WARMUP_FRAMES: u32 = 60;
+    const ORBIT_RADIUS: f32 = 15.0;
+    const ORBIT_HEIGHT: f32 = 5.0;
+    // Gives up on a scene that fails to load instead of waiting forever
+    const LOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+    pub fn new(config: BenchmarkConfig) -> Self {
+        Self {
+            config,
+            phase: Phase::Loading(Instant::now()),
+            frame_times: Vec::new(),
+            pass_times: BTreeMap::new(),
+        }
+    }
+
+    pub fn scene(&self) -> &str {
+        &self.config.scene
+    }
+
+    pub fn on_load_complete(&mut self) {
+        if matches!(self.phase, Phase::Loading(_)) {
+            self.phase = Phase::Warmup(0);
+        }
+    }
+
+    // Loading counts too, so the load timeout is checked while nothing else redraws
+    pub fn is_active(&self) -> bool {
+        self.phase != Phase::Finished
+    }
+
+    pub fn has_load_timed_out(&self) -> bool {
+        matches!(self.phase, Phase::Loading(start) if start.elapsed() > Self::LOAD_TIMEOUT)
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.phase, Phase::Warmup(_) | Phase::Running(_))
+    }
+
+    // Camera position and target for the current frame
+    pub fn camera(&self) -> Option<(glam::Vec3, glam::Vec3)> {
+        let frame = match self.phase {
+            Phase::Warmup(_) => 0,
+            Phase::Running(frame) => frame,
+            _ => return None,
+        };
+
+        let angle = frame as f32 / self.config.frames as f32 * std::f32::consts::TAU;
+        let position = glam::Vec3::new(
+            angle.cos() * Self::ORBIT_RADIUS,
+            Self::ORBIT_HEIGHT,
+            angle.sin() * Self::ORBIT_RADIUS,
+        );
+        Some((position, glam::Vec3::ZERO))
+    }
+
+    pub fn record_gpu_timings(&mut self, passes: &[(&'static str, f32)]) {
+        if !matches!(self.phase, Phase::Running(_)) {
+            return;
+        }
+
+        for (name, milliseconds) in passes {
+            self.pass_times.entry(name).or_default().push(*milliseconds);
+        }
+    }
+
+    // Returns true on the frame the benchmark finishes
+    pub fn record_frame(&mut self, timestep: Duration) -> bool {
+        let was_running = matches!(self.phase, Phase::Running(_));
+        self.phase = match self.phase {
+            Phase::Warmup(frame) if frame + 1 >= Self::WARMUP_FRAMES => Phase::Running(0),
+            Phase::Warmup(frame) => Phase::Warmup(frame + 1),
+            Phase::Running(frame) => {
+                self.frame_times.push(timestep.as_secs_f32() * 1000.0);
+                if frame + 1 >= self.config.frames {
+                    Phase::Finished
+                } else {
+                    Phase::Running(frame + 1)
+                }
+            }
+            phase => phase,
+        };
+
+        was_running && self.phase == Phase::Finished
+    }
+
+    pub fn write_report(&self) -> anyhow::Result<()> {
+        let report = BenchmarkReport {
+            scene: self.config.scene.clone(),
+            frames: self.frame_times.len() as u32,
+            frame_time: Stats::from_samples(&self.frame_times),
+            gpu_passes: self
+                .pass_times
+                .iter()
+                .map(|(name, samples)| (*name, Stats::from_samples(samples)))
+                .collect(),
+        };
+
+        let json_path = self.config.output.with_extension("json");
+        std::fs::write(&json_path, serde_json::to_string_pretty(&report)?)?;
+
+        let mut csv = String::from("name,average_ms,p95_ms,p99_ms,min_ms,max_ms\n");
+        let rows = std::iter::once(("frame", &report.frame_time))
+            .chain(report.gpu_passes.iter().map(|(name, stats)| (*name, stats)));
+        for (name, stats) in rows {
+            csv.push_str(&format!(
+                "{},{:.4},{:.4},{:.4},{:.4},{:.4}\n",
+                name, stats.average_ms, stats.p95_ms, stats.p99_ms, stats.min_ms, stats.max_ms
+            ));
+        }
+
+        let csv_path = self.config.output.with_extension("csv");
+        std::fs::write(&csv_path, csv)?;
+
+        log::info!(
+            "Benchmark finished: {:.3} ms average, {:.3} ms p95, {:.3} ms p99, written to {} and {}",
+            report.frame_time.average_ms,
+            report.frame_time.p95_ms,
+            report.frame_time.p99_ms,
+            json_path.display(),
+            csv_path.display()
+        );
+        Ok(())
+    }
+}
@@ -6,6 +6,9 @@ use winit::{
     keyboard::KeyCode,
 };
 
+use crate::renderer::Ray;
+
+#[derive(Clone)]
 pub struct Camera {
     position: glam::Vec3,
     orientation: glam::Quat,
@@ -21,15 +24,49 @@ impl Camera {
         Self { position, orientation }
     }
 
+    pub fn look_at(position: glam::Vec3, target: glam::Vec3) -> Self {
+        let view = glam::Mat4::look_at_rh(position, target, glam::Vec3::Y);
+        let orientation = glam::Quat::from_mat4(&view.inverse()).normalize();
+
+        Self { position, orientation }
+    }
+
+    pub fn from_orientation(position: glam::Vec3, orientation: glam::Quat) -> Self {
+        Self { position, orientation }
+    }
+
     pub fn position(&self) -> glam::Vec3 {
         self.position
     }
 
+    pub fn orientation(&self) -> glam::Quat {
+        self.orientation
+    }
+
     pub fn view_matrix(&self) -> glam::Mat4 {
         glam::Mat4::from_rotation_translation(self.orientation, self.position).inverse()
     }
 
-    fn forward(&self) -> glam::Vec3 {
+    pub fn screen_ray(&self, projection: &Projection, cursor: glam::Vec2, viewport: glam::Vec2) -> Ray {
+        let ndc = glam::Vec2::new(
+            cursor.x / viewport.x.max(1.0) * 2.0 - 1.0,
+            1.0 - cursor.y / viewport.y.max(1.0) * 2.0,
+        );
+
+        let half_height = (projection.fov_y() * 0.5).tan();
+        let direction = glam::Vec3::new(ndc.x * half_height * projection.aspect, ndc.y * half_height, -1.0);
+
+        Ray::new(self.position, self.orientation * direction)
+    }
+
+    // Circles the focus about the vertical axis, still facing the same way relative to it
+    pub fn orbit(&mut self, focus: glam::Vec3, angle: f32) {
+        let rotation = glam::Quat::from_rotation_y(angle);
+        self.position = focus + rotation * (self.position - focus);
+        self.orientation = (rotation * self.orientation).normalize();
+    }
+
+    pub fn forward(&self) -> glam::Vec3 {
         self.orientation * -glam::Vec3::Z
     }
 
@@ -47,6 +84,8 @@ pub struct Projection {
     fov_y: f32,
     z_near: f32,
     z_far: f32,
+    // Focal length and sensor width in millimeters, overrides the field of view while set
+    lens: Option<(f32, f32)>,
     matrix: glam::Mat4,
 }
 
@@ -60,6 +99,7 @@ impl Projection {
             fov_y: fov_y_radians,
             z_near,
             z_far,
+            lens: None,
             matrix,
         }
     }
@@ -68,9 +108,38 @@ impl Projection {
         self.matrix
     }
 
+    // The effective one, derived from the lens while set
+    pub fn fov_y(&self) -> f32 {
+        match self.lens {
+            // The sensor width spans the horizontal view, the height follows the aspect ratio
+            Some((focal_length, sensor_width)) => {
+                2.0 * (sensor_width / (2.0 * focal_length.max(f32::EPSILON)) / self.aspect).atan()
+            }
+            None => self.fov_y,
+        }
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
-        self.matrix = glam::Mat4::perspective_rh(self.fov_y, self.aspect, self.z_near, self.z_far);
+        self.update_matrix();
+    }
+
+    pub fn set_clip_planes(&mut self, z_near: f32, z_far: f32) {
+        self.z_near = z_near;
+        self.z_far = z_far;
+        self.update_matrix();
+    }
+
+    // None goes back to the field of view the projection was created with
+    pub fn set_lens(&mut self, lens: Option<(f32, f32)>) {
+        if self.lens != lens {
+            self.lens = lens;
+            self.update_matrix();
+        }
+    }
+
+    fn update_matrix(&mut self) {
+        self.matrix = glam::Mat4::perspective_rh(self.fov_y(), self.aspect, self.z_near, self.z_far);
     }
 }
 
@@ -95,10 +164,18 @@ impl CameraController {
         }
     }
 
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
     pub fn is_mouse_pressed(&self) -> bool {
         self.mouse_pressed
     }
 
+    pub fn is_moving(&self) -> bool {
+        self.velocity != glam::Vec3::ZERO || self.rotation != glam::Vec2::ZERO || self.scroll != 0.0
+    }
+
     pub fn handle_key(&mut self, key: KeyCode, state: ElementState) -> bool {
         let increment = if state.is_pressed() { 1.0 } else { 0.0 };
         match key {
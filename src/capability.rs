@@ -0,0 +1,65 @@
+use wasm_bindgen::JsValue;
+
+// Everything the renderer needs from the browser before wgpu gets involved, wgpu itself panics on a missing
+// navigator.gpu. There is no GL fallback, the renderer relies on compute shaders and storage buffers that WebGL2 lacks
+pub struct Capabilities {
+    pub has_webgpu: bool,
+    pub is_secure_context: bool,
+    pub user_agent: String,
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        let window: JsValue = web_sys::window().unwrap().into();
+        let navigator = js_sys::Reflect::get(&window, &"navigator".into()).unwrap_or(JsValue::UNDEFINED);
+        let gpu = js_sys::Reflect::get(&navigator, &"gpu".into()).unwrap_or(JsValue::UNDEFINED);
+
+        Self {
+            has_webgpu: !gpu.is_undefined() && !gpu.is_null(),
+            is_secure_context: js_sys::Reflect::get(&window, &"isSecureContext".into())
+                .is_ok_and(|value| value.is_truthy()),
+            user_agent: js_sys::Reflect::get(&navigator, &"userAgent".into())
+                .ok()
+                .and_then(|value| value.as_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn check(&self) -> anyhow::Result<()> {
+        if !self.is_secure_context {
+            anyhow::bail!("WebGPU is only available on pages served over HTTPS or from localhost");
+        }
+        if !self.has_webgpu {
+            anyhow::bail!("This browser does not support WebGPU");
+        }
+
+        Ok(())
+    }
+}
+
+// Replaces the canvas with what was checked and why starting failed
+pub fn show_report(error: &anyhow::Error) {
+    let capabilities = Capabilities::detect();
+    let row = |label: &str, value: &str| format!("<tr><td>{}</td><td>{}</td></tr>", label, escape(value));
+    let yes_no = |value: bool| if value { "Yes" } else { "No" };
+
+    let html = format!(
+        "<div id=\"capability-report\" style=\"font-family: sans-serif; max-width: 40em; margin: 4em auto; \
+         line-height: 1.5\"><h1>Unable to start the viewer</h1><p>{}</p><table>{}{}{}</table><p>Recent versions of \
+         Chrome and Edge support WebGPU, as do Firefox and Safari on some platforms.</p></div>",
+        escape(&format!("{:#}", error)),
+        row("WebGPU", yes_no(capabilities.has_webgpu)),
+        row("Secure context", yes_no(capabilities.is_secure_context)),
+        row("User agent", &capabilities.user_agent),
+    );
+
+    let document = web_sys::window().and_then(|window| window.document());
+    match document.and_then(|document| document.get_element_by_id("canvas")) {
+        Some(canvas) => canvas.set_outer_html(&html),
+        None => log::error!("Unable to start: {:#}", error),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,205 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crossbeam::channel::{Receiver, Sender};
+
+use crate::{
+    entity::EntityId,
+    renderer::{
+        ColorRamp, DistanceReference, PointDistances, PointcloudBuffer, RampStop, RenderId, SceneQuery, TaskHandle,
+    },
+};
+
+// Distances of the last comparison, kept to recolor the scan when the thresholds change
+pub struct ChangeResult {
+    pub distances: Arc<PointDistances>,
+    pub histogram: Vec<u32>,
+    pub total: usize,
+}
+
+// Sent by the background task, the result is None when only the colors changed
+type ColoredPoints = (RenderId, Option<(PointcloudBuffer, Option<ChangeResult>)>);
+
+// Colors a scan by how far each point is from a reference scan or mesh, through the color ramp. The thresholds are
+// the distances at either end of the ramp, points beyond them get the end color. The ramp position also goes in the
+// intensity channel, so scalar shading can pick out a band of changes. Points without anything of the reference
+// within the maximum distance get the far end of the ramp
+pub struct ChangeDetection {
+    pub reference: Option<EntityId>,
+    pub compared: Option<EntityId>,
+    // World units, the search radius around every point
+    pub max_distance: f32,
+    pub thresholds: [f32; 2],
+    result: Option<(RenderId, ChangeResult)>,
+    ramp: (ColorRamp, Vec<RampStop>),
+    // Points as loaded, to restore the colors after comparing
+    originals: HashMap<RenderId, Arc<PointcloudBuffer>>,
+    // None when the task was cancelled or the reference was empty
+    result_tx: Sender<ColoredPoints>,
+    result_rx: Receiver<ColoredPoints>,
+    is_computing: bool,
+    // Thresholds changed while a task was running
+    is_recolor_pending: bool,
+}
+
+impl ChangeDetection {
+    const HISTOGRAM_BINS: usize = 48;
+
+    pub fn new() -> Self {
+        let (result_tx, result_rx) = crossbeam::channel::unbounded();
+        Self {
+            reference: None,
+            compared: None,
+            max_distance: 0.1,
+            thresholds: [0.0, 0.1],
+            result: None,
+            ramp: (ColorRamp::Viridis, Vec::new()),
+            originals: HashMap::new(),
+            result_tx,
+            result_rx,
+            is_computing: false,
+            is_recolor_pending: false,
+        }
+    }
+
+    pub fn is_computing(&self) -> bool {
+        self.is_computing
+    }
+
+    pub fn result(&self) -> Option<&ChangeResult> {
+        self.result.as_ref().map(|(_, result)| result)
+    }
+
+    // The color a distance gets with the current thresholds, for the histogram
+    pub fn color(&self, distance: Option<f32>) -> [f32; 3] {
+        let (ramp, custom_ramp) = &self.ramp;
+        ramp.sample(ramp_position(distance, self.thresholds), custom_ramp)
+    }
+
+    // `render_id` is what the compared scan draws, every entity sharing it is recolored
+    pub fn compute(
+        &mut self,
+        render_id: RenderId,
+        query: &SceneQuery,
+        ramp: ColorRamp,
+        custom_ramp: Vec<RampStop>,
+        task: TaskHandle,
+    ) {
+        let (Some(reference), Some(compared)) = (self.reference, self.compared) else {
+            return;
+        };
+        let Some((compared, compared_transform)) = query.pointcloud(compared) else {
+            return;
+        };
+        let reference = match (query.pointcloud(reference), query.mesh(reference)) {
+            (Some((points, transform)), _) => DistanceReference::Points(points, transform),
+            (None, Some((mesh, transform))) => DistanceReference::Mesh(mesh, transform),
+            (None, None) => return,
+        };
+
+        // Compared again after a change, the query already holds the recolored points
+        let compared = Arc::clone(self.originals.entry(render_id).or_insert(compared));
+        let max_distance = self.max_distance;
+        self.thresholds = match reference.is_signed() {
+            true => [-max_distance, max_distance],
+            false => [0.0, max_distance],
+        };
+        self.ramp = (ramp, custom_ramp);
+
+        let colorize = self.colorize(Arc::clone(&compared));
+        let result_tx = self.result_tx.clone();
+        self.spawn(move || {
+            let result = PointDistances::compute(&compared, compared_transform, &reference, max_distance, || {
+                task.is_cancelled()
+            })
+            .map(|distances| {
+                let result = ChangeResult {
+                    histogram: distances.histogram(Self::HISTOGRAM_BINS),
+                    total: distances.distances.len(),
+                    distances: Arc::new(distances),
+                };
+                (colorize(&result.distances), Some(result))
+            });
+            result_tx.send((render_id, result)).ok();
+        });
+    }
+
+    // Applies changed thresholds to the last result
+    pub fn recolor(&mut self) {
+        if self.is_computing {
+            self.is_recolor_pending = true;
+            return;
+        }
+        let Some((render_id, result)) = &self.result else {
+            return;
+        };
+        let Some(original) = self.originals.get(render_id) else {
+            return;
+        };
+
+        let render_id = *render_id;
+        let distances = Arc::clone(&result.distances);
+        let colorize = self.colorize(Arc::clone(original));
+        let result_tx = self.result_tx.clone();
+        self.spawn(move || {
+            result_tx.send((render_id, Some((colorize(&distances), None)))).ok();
+        });
+    }
+
+    // The recolored points, once the background task is done
+    pub fn poll(&mut self) -> Option<(RenderId, PointcloudBuffer)> {
+        let (render_id, result) = self.result_rx.try_recv().ok()?;
+        self.is_computing = false;
+        let (buffer, result) = match result {
+            Some((buffer, result)) => (Some(buffer), result),
+            None => (None, None),
+        };
+        if let Some(result) = result {
+            self.result = Some((render_id, result));
+        }
+        if std::mem::take(&mut self.is_recolor_pending) {
+            self.recolor();
+        }
+        Some((render_id, buffer?))
+    }
+
+    // The points as loaded, None when they were never recolored
+    pub fn restore(&mut self, render_id: RenderId) -> Option<PointcloudBuffer> {
+        self.result = None;
+        self.originals
+            .remove(&render_id)
+            .map(|original| original.as_ref().clone())
+    }
+
+    // Recolors the points as loaded, with the thresholds and ramp at the time of the call
+    fn colorize(
+        &self,
+        original: Arc<PointcloudBuffer>,
+    ) -> impl Fn(&PointDistances) -> PointcloudBuffer + Send + 'static {
+        let thresholds = self.thresholds;
+        let (ramp, custom_ramp) = self.ramp.clone();
+        move |distances| {
+            let values = distances
+                .distances
+                .iter()
+                .map(|distance| ramp_position(*distance, thresholds))
+                .collect::<Vec<_>>();
+            original.with_scalars(&values, |value| ramp.sample(value, &custom_ramp))
+        }
+    }
+
+    fn spawn(&mut self, task: impl FnOnce() + Send + 'static) {
+        self.is_computing = true;
+        #[cfg(not(target_family = "wasm"))]
+        std::thread::spawn(task);
+        // No threads on the web, the page stalls while it runs
+        #[cfg(target_family = "wasm")]
+        task();
+    }
+}
+
+// Unmatched points go to the far end
+fn ramp_position(distance: Option<f32>, [lower, upper]: [f32; 2]) -> f32 {
+    distance.map_or(1.0, |distance| {
+        ((distance - lower) / (upper - lower).max(f32::EPSILON)).clamp(0.0, 1.0)
+    })
+}
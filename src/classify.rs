@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use crate::renderer::{PointcloudBuffer, RenderId};
+
+const MAX_UNDO: usize = 32;
+
+// The classes a stroke replaced, so it can be undone as a whole
+struct Stroke {
+    render_id: RenderId,
+    previous: HashMap<u32, u8>,
+}
+
+// Reassigns the class of every point of the selected cloud under a circle around the cursor, in pixels. It paints
+// through the cloud, points hidden behind the surface are changed as well
+pub struct ClassificationBrush {
+    pub enabled: bool,
+    pub classification: u8,
+    pub radius: f32,
+    stroke: Option<Stroke>,
+    last_cursor: Option<glam::Vec2>,
+    undo: Vec<Stroke>,
+}
+
+impl ClassificationBrush {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            classification: 2,
+            radius: 20.0,
+            stroke: None,
+            last_cursor: None,
+            undo: Vec::new(),
+        }
+    }
+
+    pub fn is_painting(&self) -> bool {
+        self.stroke.is_some()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn begin_stroke(&mut self, render_id: RenderId) {
+        self.stroke = Some(Stroke {
+            render_id,
+            previous: HashMap::new(),
+        });
+        self.last_cursor = None;
+    }
+
+    pub fn end_stroke(&mut self) {
+        if let Some(stroke) = self.stroke.take()
+            && !stroke.previous.is_empty()
+        {
+            if self.undo.len() == MAX_UNDO {
+                self.undo.remove(0);
+            }
+            self.undo.push(stroke);
+        }
+    }
+
+    // Points that change class under the cursor, `view_projection` takes the cloud's local positions to clip space.
+    // Only runs when the cursor moved, the classes won't change while it holds still
+    pub fn paint(
+        &mut self,
+        buffer: &PointcloudBuffer,
+        view_projection: glam::Mat4,
+        cursor: glam::Vec2,
+        size: glam::Vec2,
+    ) -> Option<(RenderId, Vec<(u32, u8)>)> {
+        let stroke = self.stroke.as_mut()?;
+        if self.last_cursor == Some(cursor) {
+            return None;
+        }
+        self.last_cursor = Some(cursor);
+
+        let attributes = buffer.attributes();
+        let radius_squared = self.radius * self.radius;
+        let changes = buffer
+            .points()
+            .iter()
+            .zip(attributes.iter())
+            .enumerate()
+            .filter(|(_, (_, attributes))| attributes.classification != self.classification)
+            .filter(|(_, (point, _))| {
+                let clip = view_projection * glam::Vec3::from_array(point.position).extend(1.0);
+                if clip.w <= 0.0 {
+                    return false;
+                }
+
+                let ndc = clip.truncate() / clip.w;
+                let pixel = glam::Vec2::new(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * size;
+                pixel.distance_squared(cursor) <= radius_squared
+            })
+            .map(|(index, (_, attributes))| {
+                stroke.previous.entry(index as u32).or_insert(attributes.classification);
+                (index as u32, self.classification)
+            })
+            .collect::<Vec<_>>();
+
+        (!changes.is_empty()).then_some((stroke.render_id, changes))
+    }
+
+    // The changes that restore the classes from before the last stroke
+    pub fn undo(&mut self) -> Option<(RenderId, Vec<(u32, u8)>)> {
+        let stroke = self.undo.pop()?;
+        Some((stroke.render_id, stroke.previous.into_iter().collect()))
+    }
+}
@@ -0,0 +1,42 @@
+use crate::{entity::Entity, renderer::RenderId};
+
+// Copied entities, pasted as new entities sharing the same renderable. Every paste moves one offset further so
+// repeated pastes don't stack on top of each other
+pub struct EntityClipboard {
+    pub offset: glam::Vec3,
+    entries: Vec<(RenderId, glam::Mat4)>,
+    pastes: u32,
+}
+
+impl EntityClipboard {
+    pub fn new() -> Self {
+        Self {
+            offset: glam::Vec3::new(2.0, 0.0, 0.0),
+            entries: Vec::new(),
+            pastes: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Entities without a renderable, like lights, are skipped
+    pub fn copy<'a>(&mut self, entities: impl IntoIterator<Item = &'a Entity>) {
+        self.entries = entities
+            .into_iter()
+            .filter_map(|entity| entity.render_id().map(|render_id| (render_id, entity.transform())))
+            .collect();
+        self.pastes = 0;
+    }
+
+    pub fn paste(&mut self) -> Vec<(RenderId, glam::Mat4)> {
+        self.pastes += 1;
+        let translation = glam::Mat4::from_translation(self.offset * self.pastes as f32);
+
+        self.entries
+            .iter()
+            .map(|(render_id, transform)| (*render_id, translation * *transform))
+            .collect()
+    }
+}
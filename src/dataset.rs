@@ -0,0 +1,320 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    aov::{entity_legend, save_mask},
+    entity::EntityStore,
+    poses::{CameraPose, PoseSampler},
+    renderer::{AovImages, SceneQuery},
+};
+
+// `--dataset <spec.json>` loads the scenes, renders every pose at the window size and exits. Each pose gets an RGB
+// image, an instance mask and a class mask in the output directory, with a dataset.json that lists the poses and
+// what the mask values stand for. Classes are entity tags, an entity gets the first listed class among its tags. When
+// the spec lists none, every tag in the scene is a class in alphabetical order. Poses from the sampler follow the
+// listed ones
+#[derive(Clone, Debug, Deserialize)]
+pub struct DatasetSpec {
+    pub scenes: Vec<String>,
+    #[serde(default)]
+    pub poses: Vec<CameraPose>,
+    #[serde(default)]
+    pub sampler: Option<PoseSampler>,
+    #[serde(default)]
+    pub classes: Vec<String>,
+    #[serde(default = "DatasetSpec::default_output")]
+    pub output: PathBuf,
+}
+
+impl DatasetSpec {
+    fn default_output() -> PathBuf {
+        PathBuf::from("dataset")
+    }
+
+    pub fn from_args() -> Option<Self> {
+        let args = std::env::args().collect::<Vec<_>>();
+        let path = args
+            .iter()
+            .position(|arg| arg == "--dataset")
+            .and_then(|index| args.get(index + 1))?;
+
+        match Self::read(Path::new(path)) {
+            Ok(spec) => Some(spec),
+            Err(error) => {
+                log::error!("Unable to read dataset spec {}: {}", path, error);
+                None
+            }
+        }
+    }
+
+    fn read(path: &Path) -> anyhow::Result<Self> {
+        let spec: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if spec.scenes.is_empty() {
+            anyhow::bail!("No scenes to load");
+        }
+        if spec.poses.is_empty() && spec.sampler.is_none() {
+            anyhow::bail!("No poses to render");
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Phase {
+    Loading,
+    // Frames rendered since loading finished, the scene query is rebuilt in the background
+    Loaded(u32),
+    // Frames rendered at the pose so far
+    Settling(usize, u32),
+    Capturing(usize),
+    Finished,
+}
+
+#[derive(Serialize)]
+struct PoseRecord {
+    index: usize,
+    position: glam::Vec3,
+    target: glam::Vec3,
+    view: glam::Mat4,
+    projection: glam::Mat4,
+}
+
+pub struct Dataset {
+    spec: DatasetSpec,
+    phase: Phase,
+    remaining_scenes: HashSet<String>,
+    rgb: Option<(u32, u32, Vec<u8>)>,
+    aovs: Option<AovImages>,
+    // Instance mask values by entity, the same entity keeps its value across poses
+    instance_ids: HashMap<Uuid, u16>,
+    instances: BTreeMap<u16, serde_json::Value>,
+    records: Vec<PoseRecord>,
+}
+
+impl Dataset {
+    // Lets accumulation and streamed levels of detail catch up after the camera jumps
+    const SETTLE_FRAMES: u32 = 8;
+
+    pub fn new(spec: DatasetSpec) -> Self {
+        let remaining_scenes = spec.scenes.iter().cloned().collect();
+        Self {
+            spec,
+            phase: Phase::Loading,
+            remaining_scenes,
+            rgb: None,
+            aovs: None,
+            instance_ids: HashMap::new(),
+            instances: BTreeMap::new(),
+            records: Vec::new(),
+        }
+    }
+
+    pub fn scenes(&self) -> &[String] {
+        &self.spec.scenes
+    }
+
+    pub fn on_load_complete(&mut self, label: &str) {
+        self.remaining_scenes.retain(|scene| !scene.ends_with(label));
+    }
+
+    // Starts a few frames after every scene is in and the loader went idle, spawned entities are in the store and the
+    // scene query is up to date by then
+    pub fn start_when_loaded(
+        &mut self,
+        is_loader_idle: bool,
+        entities: &EntityStore,
+        query: &SceneQuery,
+        fov_y: f32,
+    ) -> anyhow::Result<()> {
+        match self.phase {
+            Phase::Loading if self.remaining_scenes.is_empty() && is_loader_idle => {
+                self.phase = Phase::Loaded(0);
+                return Ok(());
+            }
+            Phase::Loaded(frame) if frame + 1 < Self::SETTLE_FRAMES => {
+                self.phase = Phase::Loaded(frame + 1);
+                return Ok(());
+            }
+            Phase::Loaded(_) => {}
+            _ => return Ok(()),
+        }
+
+        if let Some(sampler) = &self.spec.sampler {
+            let poses = sampler
+                .sample(query, fov_y)
+                .ok_or_else(|| anyhow::anyhow!("There is no geometry to sample poses around"))?;
+            log::info!("Sampled {} poses with seed {}", poses.len(), sampler.seed);
+            self.spec.poses.extend(poses);
+        }
+        if self.spec.poses.is_empty() {
+            anyhow::bail!("No poses to render");
+        }
+        if self.spec.classes.is_empty() {
+            self.spec.classes = entities.tags().into_iter().map(str::to_string).collect();
+        }
+        std::fs::create_dir_all(&self.spec.output)?;
+        log::info!(
+            "Rendering {} poses with {} classes to {}",
+            self.spec.poses.len(),
+            self.spec.classes.len(),
+            self.spec.output.display()
+        );
+        self.phase = Self::pose_phase(0, &self.spec.poses);
+        Ok(())
+    }
+
+    // Until the last pose is written, loading included
+    pub fn is_running(&self) -> bool {
+        self.phase != Phase::Finished
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        matches!(self.phase, Phase::Capturing(_))
+    }
+
+    pub fn camera(&self) -> Option<&CameraPose> {
+        match self.phase {
+            Phase::Settling(pose, _) | Phase::Capturing(pose) => self.spec.poses.get(pose),
+            _ => None,
+        }
+    }
+
+    // Returns true on the frame the captures should be requested
+    pub fn record_frame(&mut self) -> bool {
+        match self.phase {
+            Phase::Settling(pose, frame) if frame + 1 >= Self::SETTLE_FRAMES => {
+                self.phase = Phase::Capturing(pose);
+                true
+            }
+            Phase::Settling(pose, frame) => {
+                self.phase = Phase::Settling(pose, frame + 1);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    pub fn record_rgb(&mut self, width: u32, height: u32, pixels: Vec<u8>) {
+        self.rgb = Some((width, height, pixels));
+    }
+
+    pub fn record_aovs(&mut self, images: AovImages) {
+        self.aovs = Some(images);
+    }
+
+    // Writes the pose once both of its captures are in, returns true when that was the last one
+    pub fn write_pose(
+        &mut self,
+        entities: &EntityStore,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+    ) -> anyhow::Result<bool> {
+        let Phase::Capturing(pose) = self.phase else {
+            return Ok(false);
+        };
+        if self.rgb.is_none() || self.aovs.is_none() {
+            return Ok(false);
+        }
+        let (width, height, pixels) = self.rgb.take().unwrap();
+        let images = self.aovs.take().unwrap();
+        if pixels.is_empty() || images.ids.len() != (width * height) as usize {
+            anyhow::bail!("The captures of pose {} could not be read back", pose);
+        }
+
+        let output = self.spec.output.clone();
+        image::save_buffer(
+            output.join(format!("{:04}_rgb.png", pose)),
+            &pixels,
+            width,
+            height,
+            image::ColorType::Rgba8,
+        )?;
+
+        let mut instances = Vec::with_capacity(images.ids.len());
+        let mut classes = Vec::with_capacity(images.ids.len());
+        let mut resolved = HashMap::new();
+        for id in &images.ids {
+            let (instance, class) = *resolved
+                .entry(*id)
+                .or_insert_with(|| self.resolve(images.entities.get(id), entities));
+            instances.push(instance);
+            classes.push(class);
+        }
+        save_mask(
+            output.join(format!("{:04}_instances.png", pose)),
+            width,
+            height,
+            instances,
+        )?;
+        save_mask(output.join(format!("{:04}_classes.png", pose)), width, height, classes)?;
+
+        let CameraPose { position, target } = self.spec.poses[pose].clone();
+        self.records.push(PoseRecord {
+            index: pose,
+            position,
+            target,
+            view,
+            projection,
+        });
+
+        self.phase = Self::pose_phase(pose + 1, &self.spec.poses);
+        if self.phase != Phase::Finished {
+            return Ok(false);
+        }
+
+        self.write_manifest()?;
+        Ok(true)
+    }
+
+    // Instance and class mask values of one renderer id, zero for the background and anything without an entity
+    fn resolve(&mut self, entity_id: Option<&Uuid>, entities: &EntityStore) -> (u16, u16) {
+        let Some(entity_id) = entity_id.filter(|entity_id| entities.contains(entity_id)) else {
+            return (0, 0);
+        };
+
+        let next = (self.instance_ids.len() + 1).min(u16::MAX as usize) as u16;
+        let instance = *self.instance_ids.entry(*entity_id).or_insert_with(|| {
+            self.instances.insert(next, entity_legend(Some(entity_id), entities));
+            next
+        });
+        let class = entities
+            .get(entity_id)
+            .and_then(|entity| self.spec.classes.iter().position(|class| entity.has_tag(class)))
+            .map_or(0, |index| index as u16 + 1);
+        (instance, class)
+    }
+
+    fn pose_phase(pose: usize, poses: &[CameraPose]) -> Phase {
+        match pose < poses.len() {
+            true => Phase::Settling(pose, 0),
+            false => Phase::Finished,
+        }
+    }
+
+    fn write_manifest(&self) -> anyhow::Result<()> {
+        let classes = self
+            .spec
+            .classes
+            .iter()
+            .enumerate()
+            .map(|(index, class)| ((index + 1).to_string(), class))
+            .collect::<BTreeMap<_, _>>();
+        let manifest = serde_json::json!({
+            "scenes": self.spec.scenes,
+            "seed": self.spec.sampler.as_ref().map(|sampler| sampler.seed),
+            "poses": self.records,
+            "instances": self.instances,
+            "classes": classes,
+        });
+
+        let path = self.spec.output.join("dataset.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&manifest)?)?;
+        log::info!("Dataset of {} poses written to {}", self.records.len(), path.display());
+        Ok(())
+    }
+}
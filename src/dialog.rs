@@ -1,29 +1,107 @@
-use crate::renderer::{AssetKind, AssetLoader, ResourcePath};
+use crate::renderer::{AssetKind, AssetLoader, ImportOptions, ResourcePath};
 
-fn create_dialog_future() -> impl Future<Output = Option<rfd::FileHandle>> {
+fn create_dialog_future() -> impl Future<Output = Option<Vec<rfd::FileHandle>>> {
     rfd::AsyncFileDialog::new()
-        .add_filter("Scene", AssetKind::Gltf.extensions())
+        .add_filter(
+            "Scene",
+            &[AssetKind::Gltf.extensions(), AssetKind::Obj.extensions()].concat(),
+        )
         .add_filter("Pointcloud", AssetKind::Pointcloud.extensions())
         .add_filter("Environment Map", AssetKind::EnvironmentMap.extensions())
+        .add_filter("Volume", AssetKind::Volume.extensions())
+        .pick_files()
+}
+
+// Several scenes picked at once are merged into one asset, everything else loads on its own
+#[cfg(not(target_family = "wasm"))]
+pub fn open_file_dialog(loader: AssetLoader, options: ImportOptions) {
+    use futures_lite::future;
+
+    std::thread::spawn(move || {
+        let Some(handles) = future::block_on(create_dialog_future()) else {
+            return;
+        };
+
+        let (scenes, others): (Vec<_>, Vec<_>) = handles
+            .iter()
+            .filter_map(|handle| ResourcePath::new(&handle.file_name()).ok())
+            .partition(|path| {
+                path.extension()
+                    .as_deref()
+                    .and_then(AssetKind::from_extension)
+                    .is_some_and(|kind| matches!(kind, AssetKind::Obj | AssetKind::Gltf))
+            });
+        match scenes.len() {
+            0 => {}
+            1 => loader.load_with_options(scenes[0].clone(), options.clone()),
+            _ => loader.load_merged(scenes, options.clone()),
+        }
+        for path in others {
+            loader.load_with_options(path, options.clone());
+        }
+    });
+}
+
+// No merging on the web, each file loads in its own worker
+#[cfg(target_family = "wasm")]
+pub fn open_file_dialog(loader: AssetLoader, options: ImportOptions) {
+    wasm_bindgen_futures::spawn_local(async move {
+        for handle in create_dialog_future().await.unwrap_or_default() {
+            loader.load_with_options(ResourcePath::Upload(handle.inner().clone()), options.clone());
+        }
+    });
+}
+
+// Encodes once a path is picked, on the dialog's thread since compressing a large cloud takes a while. The chosen
+// extension decides between LAS and LAZ
+#[cfg(not(target_family = "wasm"))]
+pub fn export_points_dialog(file_name: String, encode: impl FnOnce(bool) -> anyhow::Result<Vec<u8>> + Send + 'static) {
+    use futures_lite::future;
+
+    std::thread::spawn(move || {
+        let dialog = rfd::AsyncFileDialog::new()
+            .add_filter("LAZ", &["laz"])
+            .add_filter("LAS", &["las"])
+            .set_file_name(file_name)
+            .save_file();
+        let Some(handle) = future::block_on(dialog) else {
+            return;
+        };
+
+        let path = handle.path().to_path_buf();
+        let compressed = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("laz"));
+        match encode(compressed).and_then(|data| Ok(std::fs::write(&path, data)?)) {
+            Ok(()) => log::info!("Exported {}", path.display()),
+            Err(error) => log::error!("Unable to export {}: {}", path.display(), error),
+        }
+    });
+}
+
+fn create_photo_dialog_future() -> impl Future<Output = Option<rfd::FileHandle>> {
+    rfd::AsyncFileDialog::new()
+        .add_filter("Photo", &["jpg", "jpeg", "png"])
         .pick_file()
 }
 
+// Hands over the file's bytes, nothing happens when the dialog is cancelled
 #[cfg(not(target_family = "wasm"))]
-pub fn open_file_dialog(loader: AssetLoader) {
+pub fn open_photo_dialog(on_picked: impl FnOnce(Vec<u8>) + Send + 'static) {
     use futures_lite::future;
 
     std::thread::spawn(move || {
-        if let Some(handle) = future::block_on(create_dialog_future()) {
-            loader.load(ResourcePath::new(&handle.file_name()).unwrap());
+        if let Some(handle) = future::block_on(create_photo_dialog_future()) {
+            on_picked(future::block_on(handle.read()));
         }
     });
 }
 
 #[cfg(target_family = "wasm")]
-pub fn open_file_dialog(loader: AssetLoader) {
+pub fn open_photo_dialog(on_picked: impl FnOnce(Vec<u8>) + 'static) {
     wasm_bindgen_futures::spawn_local(async move {
-        if let Some(handle) = create_dialog_future().await {
-            loader.load(ResourcePath::Upload(handle.inner().clone()));
+        if let Some(handle) = create_photo_dialog_future().await {
+            on_picked(handle.read().await);
         }
     });
 }
@@ -1,5 +1,9 @@
+use std::collections::{BTreeSet, HashMap};
+
 use uuid::Uuid;
 
+use crate::renderer::{AnimationPhase, RenderId, RenderLayers, Visibility};
+
 pub type EntityId = Uuid;
 
 #[derive(Debug)]
@@ -7,6 +11,16 @@ pub struct Entity {
     id: EntityId,
     transform: glam::Mat4,
     label: Option<String>,
+    // Free-form, an entity can be in any number of sets
+    tags: BTreeSet<String>,
+    visibility: Visibility,
+    layers: RenderLayers,
+    // Only moves entities drawing a skinned renderable with a clip
+    animation_phase: AnimationPhase,
+    // Nodes imported from the same file share it
+    asset_id: Option<Uuid>,
+    // Lights are entities without a renderable
+    render_id: Option<RenderId>,
 }
 
 impl Entity {
@@ -19,9 +33,36 @@ impl Entity {
             id: Self::new_id(),
             transform,
             label,
+            tags: BTreeSet::new(),
+            visibility: Visibility::Visible,
+            layers: RenderLayers::default(),
+            animation_phase: AnimationPhase::default(),
+            asset_id: None,
+            render_id: None,
         }
     }
 
+    // Entities mirrored from another viewer keep the id they have there
+    pub fn with_id(mut self, id: EntityId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_render_id(mut self, render_id: RenderId) -> Self {
+        self.render_id = Some(render_id);
+        self
+    }
+
+    pub fn with_asset_id(mut self, asset_id: Uuid) -> Self {
+        self.asset_id = Some(asset_id);
+        self
+    }
+
+    pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
     pub fn translate(&mut self, translation: glam::Vec3) {
         self.transform = glam::Mat4::from_translation(translation) * self.transform;
     }
@@ -30,10 +71,58 @@ impl Entity {
         self.id
     }
 
+    pub fn render_id(&self) -> Option<RenderId> {
+        self.render_id
+    }
+
+    pub fn asset_id(&self) -> Option<Uuid> {
+        self.asset_id
+    }
+
     pub fn label(&self) -> &Option<String> {
         &self.label
     }
 
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
+    }
+
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn set_tags(&mut self, tags: BTreeSet<String>) {
+        self.tags = tags;
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    pub fn set_visibility(&mut self, visibility: Visibility) {
+        self.visibility = visibility;
+    }
+
+    pub fn layers(&self) -> RenderLayers {
+        self.layers
+    }
+
+    pub fn set_layers(&mut self, layers: RenderLayers) {
+        self.layers = layers;
+    }
+
+    pub fn animation_phase(&self) -> AnimationPhase {
+        self.animation_phase
+    }
+
+    pub fn set_animation_phase(&mut self, animation_phase: AnimationPhase) {
+        self.animation_phase = animation_phase;
+    }
+
     pub fn transform(&self) -> glam::Mat4 {
         self.transform
     }
@@ -42,3 +131,66 @@ impl Entity {
         self.transform = transform;
     }
 }
+
+// Every entity of the app, with lookups by label and tag so features can work on sets of them
+#[derive(Debug, Default)]
+pub struct EntityStore {
+    entities: HashMap<EntityId, Entity>,
+}
+
+impl EntityStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, entity: Entity) -> EntityId {
+        let entity_id = entity.id();
+        self.entities.insert(entity_id, entity);
+        entity_id
+    }
+
+    pub fn remove(&mut self, entity_id: &EntityId) -> Option<Entity> {
+        self.entities.remove(entity_id)
+    }
+
+    pub fn get(&self, entity_id: &EntityId) -> Option<&Entity> {
+        self.entities.get(entity_id)
+    }
+
+    pub fn get_mut(&mut self, entity_id: &EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(entity_id)
+    }
+
+    pub fn contains(&self, entity_id: &EntityId) -> bool {
+        self.entities.contains_key(entity_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.entities.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
+        self.entities.values_mut()
+    }
+
+    // Exact match, labels come from file names and aren't unique
+    pub fn find_by_label<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a Entity> {
+        self.iter()
+            .filter(move |entity| entity.label().as_deref() == Some(label))
+    }
+
+    pub fn find_by_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Entity> {
+        self.iter().filter(move |entity| entity.has_tag(tag))
+    }
+
+    // Every tag in use, sorted
+    pub fn tags(&self) -> BTreeSet<&str> {
+        self.iter()
+            .flat_map(|entity| entity.tags().iter().map(String::as_str))
+            .collect()
+    }
+}
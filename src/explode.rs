@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::entity::EntityId;
+
+// Pushes the nodes of one imported assembly apart, each along the offset of its center from the assembly's
+// centroid. Only changes the transforms sent to the renderer, the entities keep their own
+pub struct ExplodeView {
+    // 0 is assembled, 1 doubles every node's distance from the centroid
+    pub factor: f32,
+    asset_id: Option<Uuid>,
+    offsets: HashMap<EntityId, glam::Vec3>,
+}
+
+impl ExplodeView {
+    pub const MAX_FACTOR: f32 = 3.0;
+
+    pub fn new() -> Self {
+        Self {
+            factor: 0.0,
+            asset_id: None,
+            offsets: HashMap::new(),
+        }
+    }
+
+    pub fn asset_id(&self) -> Option<Uuid> {
+        self.asset_id
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.offsets.keys().copied()
+    }
+
+    // Replaces the exploded assembly with the nodes at these world centers, returns the nodes of the previous one so
+    // they can be put back together
+    pub fn set_assembly(&mut self, asset_id: Option<Uuid>, centers: Vec<(EntityId, glam::Vec3)>) -> Vec<EntityId> {
+        let previous = self.offsets.drain().map(|(entity_id, _)| entity_id).collect();
+        self.asset_id = asset_id;
+
+        // A single node has nothing to move away from
+        if centers.len() > 1 {
+            let centroid = centers.iter().map(|(_, center)| *center).sum::<glam::Vec3>() / centers.len() as f32;
+            self.offsets = centers
+                .into_iter()
+                .map(|(entity_id, center)| (entity_id, center - centroid))
+                .collect();
+        }
+
+        previous
+    }
+
+    // The transform to draw the entity with
+    pub fn apply(&self, entity_id: EntityId, transform: glam::Mat4) -> glam::Mat4 {
+        match self.offsets.get(&entity_id) {
+            Some(offset) => glam::Mat4::from_translation(*offset * self.factor) * transform,
+            None => transform,
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    renderer::{Aabb, MAX_SCREENSHOT_TILES, RenderCommand},
+    units::WorldUnit,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FloorplanView {
+    Plan,
+    SectionX,
+    SectionZ,
+}
+
+impl FloorplanView {
+    pub const ALL: [Self; 3] = [Self::Plan, Self::SectionX, Self::SectionZ];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Plan => "Plan",
+            Self::SectionX => "Section along X",
+            Self::SectionZ => "Section along Z",
+        }
+    }
+
+    // The world axis the slab is measured along
+    pub fn axis(self) -> glam::Vec3 {
+        match self {
+            Self::Plan => glam::Vec3::Y,
+            Self::SectionX => glam::Vec3::X,
+            Self::SectionZ => glam::Vec3::Z,
+        }
+    }
+
+    // Forward and up of the camera. The plan has north, -Z, at the top and the sections look at the face a viewer
+    // standing at the start of the slab would see
+    fn orientation(self) -> (glam::Vec3, glam::Vec3) {
+        match self {
+            Self::Plan => (glam::Vec3::NEG_Y, glam::Vec3::NEG_Z),
+            Self::SectionX => (glam::Vec3::X, glam::Vec3::Y),
+            Self::SectionZ => (glam::Vec3::NEG_Z, glam::Vec3::Y),
+        }
+    }
+}
+
+// Orthographic snapshot of everything inside a slab, a plan cut at an elevation or a section through the scene. The
+// slab ends are the near and far planes, so whatever lies outside of it isn't drawn. Saved as floorplan.png with a
+// scale bar in the world unit
+pub struct Floorplan {
+    pub view: FloorplanView,
+    // World units along the view axis, for the plan the floor and the cut above it
+    pub slab: [f32; 2],
+    // Asked for, the tile limit may lower it on large scenes
+    pub pixels_per_meter: f32,
+    // Pixels per world unit of the capture in flight
+    pending: Option<f32>,
+}
+
+impl Floorplan {
+    // Fraction of the scene added around it, so walls on the edge aren't cut off
+    const MARGIN: f32 = 0.02;
+
+    pub fn new() -> Self {
+        Self {
+            view: FloorplanView::Plan,
+            slab: [0.0, 1.2],
+            pixels_per_meter: 100.0,
+            pending: None,
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    // The whole scene along the view axis
+    pub fn fit_slab(&mut self, bounds: &Aabb) {
+        if bounds.is_empty() {
+            return;
+        }
+        let axis = self.view.axis();
+        self.slab = [bounds.min.dot(axis), bounds.max.dot(axis)];
+    }
+
+    // Covers `bounds` across the view axis with the aspect ratio of the window, None for an empty scene or slab
+    pub fn capture(&mut self, id: u64, bounds: &Aabb, window: (u32, u32), unit: WorldUnit) -> Option<RenderCommand> {
+        let [bottom, top] = self.slab;
+        if bounds.is_empty() || top <= bottom || window.0 == 0 || window.1 == 0 {
+            return None;
+        }
+
+        let axis = self.view.axis();
+        let (forward, up) = self.view.orientation();
+        let right = forward.cross(up);
+        let (min, max) = corners(bounds).into_iter().fold(
+            (glam::Vec2::INFINITY, glam::Vec2::NEG_INFINITY),
+            |(min, max), corner| {
+                let projected = glam::Vec2::new(corner.dot(right), corner.dot(up));
+                (min.min(projected), max.max(projected))
+            },
+        );
+        let center = (min + max) * 0.5;
+        let mut size = (max - min) * (1.0 + 2.0 * Self::MARGIN);
+        let aspect = window.0 as f32 / window.1 as f32;
+        if size.x < size.y * aspect {
+            size.x = size.y * aspect;
+        } else {
+            size.y = size.x / aspect;
+        }
+        if size.x <= 0.0 {
+            return None;
+        }
+
+        let pixels_per_unit = self.pixels_per_meter / unit.per_meter();
+        let tiles = ((pixels_per_unit * size.x / window.0 as f32).ceil() as u32).clamp(1, MAX_SCREENSHOT_TILES);
+        self.pending = Some((window.0 * tiles) as f32 / size.x);
+
+        // The camera sits on the slab end it looks away from
+        let start = match forward.dot(axis) < 0.0 {
+            true => top,
+            false => bottom,
+        };
+        let position = right * center.x + up * center.y + axis * start;
+        let half = size * 0.5;
+        Some(RenderCommand::CaptureTiledScreenshot {
+            id,
+            tiles,
+            position,
+            view: glam::Mat4::look_to_rh(position, forward, up),
+            projection: glam::Mat4::orthographic_rh(-half.x, half.x, -half.y, half.y, 0.0, top - bottom),
+        })
+    }
+
+    // Draws the scale bar into the bottom left corner of the capture and saves it
+    pub fn save(&mut self, width: u32, height: u32, mut pixels: Vec<u8>, unit: WorldUnit) -> anyhow::Result<()> {
+        let pixels_per_unit = self.pending.take().unwrap_or(1.0);
+        ScaleBar::new(width, pixels_per_unit).draw(&mut pixels, width, height, unit);
+        image::save_buffer("floorplan.png", &pixels, width, height, image::ColorType::Rgba8)?;
+        Ok(())
+    }
+}
+
+fn corners(bounds: &Aabb) -> [glam::Vec3; 8] {
+    std::array::from_fn(|index| {
+        glam::Vec3::select(
+            glam::BVec3::new(index & 1 != 0, index & 2 != 0, index & 4 != 0),
+            bounds.max,
+            bounds.min,
+        )
+    })
+}
+
+// Alternating black and white segments on a white backing, labelled with its length
+struct ScaleBar {
+    // World units, a round 1, 2 or 5 times a power of ten
+    length: f32,
+    decimals: usize,
+    pixels: u32,
+    segments: u32,
+}
+
+impl ScaleBar {
+    // Of the image width the bar covers at most
+    const MAX_FRACTION: f32 = 0.2;
+
+    fn new(width: u32, pixels_per_unit: f32) -> Self {
+        let target = width as f32 * Self::MAX_FRACTION / pixels_per_unit;
+        let exponent = target.log10().floor() as i32;
+        let magnitude = 10f32.powi(exponent);
+        let (step, segments) = match target / magnitude {
+            fraction if fraction >= 5.0 => (5.0, 5),
+            fraction if fraction >= 2.0 => (2.0, 4),
+            _ => (1.0, 5),
+        };
+        let length = step * magnitude;
+        Self {
+            length,
+            decimals: (-exponent).max(0) as usize,
+            pixels: (length * pixels_per_unit).round() as u32,
+            segments,
+        }
+    }
+
+    fn draw(&self, pixels: &mut [u8], width: u32, height: u32, unit: WorldUnit) {
+        // Glyph pixels, grown with the image so the bar stays readable on print sized captures
+        let scale = (height / 400).max(1);
+        let margin = 8 * scale;
+        let bar_height = 4 * scale;
+        let label = format!("{:.*} {}", self.decimals, self.length, unit_suffix(unit));
+        let label_width = label.len() as u32 * (GLYPH_WIDTH + 1) * scale;
+
+        let backing_width = self.pixels.max(label_width) + 2 * margin;
+        let backing_height = bar_height + GLYPH_HEIGHT * scale + 3 * margin;
+        if backing_width + margin > width || backing_height + margin > height {
+            return;
+        }
+        let mut fill = |x: u32, y: u32, w: u32, h: u32, color: u8| {
+            for row in y..(y + h).min(height) {
+                for column in x..(x + w).min(width) {
+                    let index = ((row * width + column) * 4) as usize;
+                    pixels[index..index + 4].copy_from_slice(&[color, color, color, 255]);
+                }
+            }
+        };
+
+        let top = height - backing_height - margin;
+        fill(margin, top, backing_width, backing_height, 255);
+        let (left, bar_top) = (2 * margin, top + margin);
+        fill(left, bar_top, self.pixels, bar_height, 0);
+        let segment = self.pixels / self.segments;
+        for index in (1..self.segments).step_by(2) {
+            fill(
+                left + index * segment,
+                bar_top + scale,
+                segment,
+                bar_height - 2 * scale,
+                255,
+            );
+        }
+
+        let label_top = bar_top + bar_height + margin;
+        for (index, character) in label.chars().enumerate() {
+            let x = left + index as u32 * (GLYPH_WIDTH + 1) * scale;
+            for (row, bits) in glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        fill(x + column * scale, label_top + row as u32 * scale, scale, scale, 0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn unit_suffix(unit: WorldUnit) -> &'static str {
+    match unit {
+        WorldUnit::Meters => "m",
+        WorldUnit::Centimeters => "cm",
+        WorldUnit::Feet => "ft",
+    }
+}
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+// Rows from the top, the lowest five bits from left to right. Only what a scale bar label needs
+fn glyph(character: char) -> [u8; 7] {
+    match character {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        'c' => [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e],
+        'm' => [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11],
+        'f' => [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08],
+        't' => [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06],
+        _ => [0; 7],
+    }
+}
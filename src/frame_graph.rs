@@ -0,0 +1,225 @@
+use crate::renderer::{FrameGraphLayout, RenderCommand, Renderer, SlotResource};
+
+// The main frame's passes in the order they run against the slots they touch, with the format and size behind each
+// slot. The renderer only describes its graph while this is open and sends it again whenever it changes
+pub struct FrameGraphWindow {
+    pub is_open: bool,
+    layout: Option<FrameGraphLayout>,
+    is_reporting: bool,
+}
+
+impl FrameGraphWindow {
+    const COLUMN_WIDTH: f32 = 22.0;
+    const ROW_HEIGHT: f32 = 20.0;
+    const HEADER_HEIGHT: f32 = 120.0;
+
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            layout: None,
+            is_reporting: false,
+        }
+    }
+
+    pub fn set_layout(&mut self, layout: FrameGraphLayout) {
+        self.layout = Some(layout);
+    }
+
+    pub fn update(&mut self, renderer: &Renderer) {
+        if self.is_open != self.is_reporting {
+            renderer
+                .send_command(RenderCommand::SetFrameGraphReport(self.is_open))
+                .unwrap();
+            self.is_reporting = self.is_open;
+            self.layout = None;
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.is_open {
+            return;
+        }
+
+        let mut is_open = self.is_open;
+        egui::Window::new("Frame graph")
+            .open(&mut is_open)
+            .resizable(true)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                let Some(layout) = &self.layout else {
+                    ui.label("Waiting for a frame");
+                    return;
+                };
+
+                let culled = layout.passes.iter().filter(|pass| !pass.is_scheduled).count();
+                ui.label(format!(
+                    "{} passes, {} culled, {} slots",
+                    layout.passes.len(),
+                    culled,
+                    layout.slots.len()
+                ));
+                ui.separator();
+
+                egui::ScrollArea::both().max_height(500.0).show(ui, |ui| {
+                    pass_matrix(ui, layout);
+                });
+
+                ui.separator();
+                ui.label(
+                    "● writes   ○ reads   Grey columns are culled, they read nothing written or feed nothing used",
+                );
+            });
+
+        self.is_open = is_open;
+    }
+}
+
+// A column per pass and a row per slot. The bar spans the passes between the first and the last one that touches the
+// slot, which is as long as its texture has to stay alive
+fn pass_matrix(ui: &mut egui::Ui, layout: &FrameGraphLayout) {
+    let font = egui::FontId::monospace(11.0);
+    let text_color = ui.visuals().text_color();
+    let weak_color = ui.visuals().weak_text_color();
+    let read_color = egui::Color32::from_rgb(90, 160, 230);
+    let write_color = egui::Color32::from_rgb(230, 150, 60);
+
+    let rows = layout
+        .slots
+        .iter()
+        .map(|slot| {
+            let name = match slot.is_transient {
+                true => format!("{} (transient)", slot.name),
+                false => slot.name.to_string(),
+            };
+            (name, describe(&slot.resources))
+        })
+        .collect::<Vec<_>>();
+    let text_width = |text: &str| {
+        ui.painter()
+            .layout_no_wrap(text.to_string(), font.clone(), text_color)
+            .size()
+            .x
+    };
+    let name_width = rows.iter().map(|(name, _)| text_width(name)).fold(0.0, f32::max) + 12.0;
+    let description_width = rows
+        .iter()
+        .map(|(_, description)| text_width(description))
+        .fold(0.0, f32::max)
+        + 12.0;
+    let left = name_width + description_width;
+
+    let size = egui::vec2(
+        left + layout.passes.len() as f32 * FrameGraphWindow::COLUMN_WIDTH,
+        FrameGraphWindow::HEADER_HEIGHT + rows.len() as f32 * FrameGraphWindow::ROW_HEIGHT,
+    );
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    let column = |index: usize| rect.left() + left + (index as f32 + 0.5) * FrameGraphWindow::COLUMN_WIDTH;
+    let row = |index: usize| {
+        rect.top() + FrameGraphWindow::HEADER_HEIGHT + (index as f32 + 0.5) * FrameGraphWindow::ROW_HEIGHT
+    };
+
+    // Pass names run upwards from the top of their column
+    for (index, pass) in layout.passes.iter().enumerate() {
+        let color = if pass.is_scheduled { text_color } else { weak_color };
+        if !pass.is_scheduled {
+            let column_rect = egui::Rect::from_center_size(
+                egui::pos2(column(index), rect.center().y),
+                egui::vec2(FrameGraphWindow::COLUMN_WIDTH, rect.height()),
+            );
+            painter.rect_filled(column_rect, 0.0, ui.visuals().faint_bg_color);
+        }
+        let galley = painter.layout_no_wrap(format!("{} {}", index + 1, pass.name), font.clone(), color);
+        let position = egui::pos2(
+            column(index) - galley.size().y * 0.5,
+            rect.top() + FrameGraphWindow::HEADER_HEIGHT - 4.0,
+        );
+        painter.add(egui::epaint::TextShape::new(position, galley, color).with_angle(-std::f32::consts::FRAC_PI_2));
+    }
+
+    for (index, (slot, (name, description))) in layout.slots.iter().zip(&rows).enumerate() {
+        let y = row(index);
+        if index % 2 == 1 {
+            let stripe = egui::Rect::from_min_max(
+                egui::pos2(rect.left(), y - FrameGraphWindow::ROW_HEIGHT * 0.5),
+                egui::pos2(rect.right(), y + FrameGraphWindow::ROW_HEIGHT * 0.5),
+            );
+            painter.rect_filled(stripe, 0.0, ui.visuals().faint_bg_color);
+        }
+        painter.text(
+            egui::pos2(rect.left(), y),
+            egui::Align2::LEFT_CENTER,
+            name,
+            font.clone(),
+            text_color,
+        );
+        painter.text(
+            egui::pos2(rect.left() + name_width, y),
+            egui::Align2::LEFT_CENTER,
+            description,
+            font.clone(),
+            weak_color,
+        );
+
+        let uses = layout
+            .passes
+            .iter()
+            .enumerate()
+            .filter(|(_, pass)| pass.is_scheduled)
+            .filter_map(|(pass_index, pass)| {
+                let reads = pass.reads.contains(&slot.name);
+                let writes = pass.writes.contains(&slot.name);
+                (reads || writes).then_some((pass_index, reads, writes))
+            })
+            .collect::<Vec<_>>();
+        if let (Some(first), Some(last)) = (uses.first(), uses.last()) {
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(column(first.0), y - 2.0),
+                egui::pos2(column(last.0), y + 2.0),
+            );
+            painter.rect_filled(bar, 2.0, ui.visuals().widgets.inactive.bg_fill);
+        }
+        for (pass_index, reads, writes) in uses {
+            let center = egui::pos2(column(pass_index), y);
+            if writes {
+                painter.circle_filled(center, 5.0, write_color);
+            }
+            if reads {
+                painter.circle_stroke(center, 7.0, egui::Stroke::new(2.0, read_color));
+            }
+        }
+    }
+
+    // Everything the hovered pass reads and writes
+    if let Some(position) = response.hover_pos() {
+        let offset = position.x - rect.left() - left;
+        let index = (offset / FrameGraphWindow::COLUMN_WIDTH).floor() as usize;
+        if offset >= 0.0
+            && let Some(pass) = layout.passes.get(index)
+        {
+            let status = if pass.is_scheduled { "" } else { " (culled)" };
+            response.on_hover_text(format!(
+                "{}{}\nReads: {}\nWrites: {}",
+                pass.name,
+                status,
+                pass.reads.join(", "),
+                pass.writes.join(", ")
+            ));
+        }
+    }
+}
+
+fn describe(resources: &[SlotResource]) -> String {
+    resources
+        .iter()
+        .map(|resource| match resource {
+            SlotResource::Texture { format, size } if size.depth_or_array_layers > 1 => format!(
+                "{}×{}×{} {:?}",
+                size.width, size.height, size.depth_or_array_layers, format
+            ),
+            SlotResource::Texture { format, size } => format!("{}×{} {:?}", size.width, size.height, format),
+            SlotResource::Buffer { size } => format!("{:.1} MiB buffer", *size as f64 / (1024.0 * 1024.0)),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use crate::{
+    entity::{Entity, EntityId, EntityStore},
+    renderer::Visibility,
+};
+
+// Shows only a set of entities, everything else hidden or ghosted until it's restored. Lights and other entities
+// without a renderable are left alone
+pub struct Isolation {
+    pub ghost_others: bool,
+    // Visibility from before isolating, None while nothing is isolated
+    previous: Option<HashMap<EntityId, Visibility>>,
+}
+
+impl Isolation {
+    pub fn new() -> Self {
+        Self {
+            ghost_others: false,
+            previous: None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    // Returns the entities whose visibility changed. Isolating again while isolated still restores to the state from
+    // before the first time
+    pub fn isolate(&mut self, entities: &mut EntityStore, isolated: &[EntityId]) -> Vec<(EntityId, Visibility)> {
+        self.previous.get_or_insert_with(|| {
+            entities
+                .iter()
+                .map(|entity| (entity.id(), entity.visibility()))
+                .collect()
+        });
+
+        let others = if self.ghost_others {
+            Visibility::Ghosted
+        } else {
+            Visibility::Hidden
+        };
+
+        entities
+            .iter_mut()
+            .filter(|entity| entity.render_id().is_some())
+            .filter_map(|entity| {
+                let visibility = if isolated.contains(&entity.id()) {
+                    Visibility::Visible
+                } else {
+                    others
+                };
+                set_visibility(entity, visibility)
+            })
+            .collect()
+    }
+
+    // Entities added while isolated weren't hidden, they stay visible
+    pub fn restore(&mut self, entities: &mut EntityStore) -> Vec<(EntityId, Visibility)> {
+        let Some(previous) = self.previous.take() else {
+            return Vec::new();
+        };
+
+        entities
+            .iter_mut()
+            .filter_map(|entity| {
+                let visibility = previous.get(&entity.id()).copied().unwrap_or_default();
+                set_visibility(entity, visibility)
+            })
+            .collect()
+    }
+}
+
+fn set_visibility(entity: &mut Entity, visibility: Visibility) -> Option<(EntityId, Visibility)> {
+    (entity.visibility() != visibility).then(|| {
+        entity.set_visibility(visibility);
+        (entity.id(), visibility)
+    })
+}
@@ -6,12 +6,56 @@ use wasm_bindgen::prelude::*;
 use crate::app::App;
 
 mod app;
+#[cfg(not(target_family = "wasm"))]
+mod aov;
+mod benchmark;
 mod camera;
+mod change_detection;
+#[cfg(target_family = "wasm")]
+mod capability;
+mod classify;
+mod clipboard;
+#[cfg(not(target_family = "wasm"))]
+mod dataset;
 mod dialog;
 mod entity;
 mod error;
+mod explode;
+#[cfg(not(target_family = "wasm"))]
+mod floorplan;
+mod frame_graph;
+mod isolate;
+mod locale;
+mod photo_match;
+mod placement;
+#[cfg(not(target_family = "wasm"))]
+mod poses;
+mod prefab;
+mod preview;
+mod profiler;
+#[cfg(not(target_family = "wasm"))]
+mod proxy;
+mod quality;
+mod recording;
+mod registration;
+#[cfg(not(target_family = "wasm"))]
+mod reload;
+#[cfg(not(target_family = "wasm"))]
+mod remote;
 mod renderer;
+mod scatter;
+#[cfg(not(target_family = "wasm"))]
+mod session;
+mod settings;
 mod state;
+#[cfg(not(target_family = "wasm"))]
+mod sun_study;
+mod turntable;
+mod units;
+mod viewport;
+mod watch;
+#[cfg(target_family = "wasm")]
+mod web;
 
 pub fn run() -> anyhow::Result<()> {
     #[cfg(not(target_family = "wasm"))]
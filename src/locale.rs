@@ -0,0 +1,332 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use serde::{Deserialize, Serialize};
+
+// UI strings are written in English and double as the lookup key, a missing translation shows the English text
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    Dutch,
+}
+
+impl Language {
+    pub const ALL: [Self; 2] = [Self::English, Self::Dutch];
+
+    // In the language itself, so it can be found without reading the current one
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::Dutch => "Nederlands",
+        }
+    }
+
+    pub fn tr<'a>(&self, text: &'a str) -> &'a str {
+        let table = match self {
+            Self::English => return text,
+            Self::Dutch => &DUTCH,
+        };
+
+        table.get(text).copied().unwrap_or(text)
+    }
+}
+
+static DUTCH: LazyLock<HashMap<&str, &str>> = LazyLock::new(|| {
+    HashMap::from([
+        ("Add point", "Punt toevoegen"),
+        ("Add stop", "Kleur toevoegen"),
+        ("Angle snap", "Hoek uitlijnen"),
+        ("Asset", "Asset"),
+        ("Auto", "Automatisch"),
+        ("Bake selected", "Selectie bakken"),
+        ("Buffer", "Buffer"),
+        ("Buffer inspector", "Bufferinspectie"),
+        ("Clear", "Wissen"),
+        ("Color ramp", "Kleurverloop"),
+        (
+            "Ctrl+C / Ctrl+V to copy and paste, Ctrl+D to duplicate",
+            "Ctrl+C / Ctrl+V om te kopiëren en plakken, Ctrl+D om te dupliceren",
+        ),
+        ("Custom", "Aangepast"),
+        ("Debug", "Debug"),
+        ("Density", "Dichtheid"),
+        ("Displacement scale", "Verplaatsingsschaal"),
+        ("Duplicate offset", "Verschuiving bij dupliceren"),
+        ("Enabled", "Ingeschakeld"),
+        ("Environment lighting", "Omgevingslicht"),
+        ("Environment samples", "Omgevingssamples"),
+        (
+            "G to move with the cursor, left click to place, right click to cancel",
+            "G om met de cursor te verplaatsen, linkermuisknop om te plaatsen, rechtermuisknop om te annuleren",
+        ),
+        ("Grid snap", "Raster uitlijnen"),
+        ("Hover tooltips", "Tooltips bij aanwijzen"),
+        ("Import report", "Importrapport"),
+        ("Import subdivision", "Onderverdeling bij import"),
+        ("Intensity", "Intensiteit"),
+        ("Irradiance volume", "Irradiantievolume"),
+        ("Language", "Taal"),
+        ("Light color", "Lichtkleur"),
+        ("Lightmap", "Lightmap"),
+        ("Load Asset", "Asset laden"),
+        ("Max bounces", "Maximaal aantal kaatsingen"),
+        ("None", "Geen"),
+        ("Opacity", "Dekking"),
+        ("Open window", "Venster openen"),
+        ("Paint with left mouse button", "Schilderen met de linkermuisknop"),
+        ("Parallax quality", "Parallaxkwaliteit"),
+        ("Shadow resolution", "Schaduwresolutie"),
+        ("Place at camera", "Bij de camera plaatsen"),
+        ("Place imports on the ground", "Imports op de grond plaatsen"),
+        ("Pointcloud shading", "Puntenwolkweergave"),
+        ("Position", "Positie"),
+        ("Preview", "Voorbeeld"),
+        ("Probes", "Probes"),
+        ("Profiler", "Profiler"),
+        ("Quality", "Kwaliteit"),
+        ("Radius", "Straal"),
+        ("Ramp range", "Bereik van het verloop"),
+        ("Re-bake", "Opnieuw bakken"),
+        ("Read back", "Teruglezen"),
+        ("Recapture all", "Alles opnieuw vastleggen"),
+        ("Record", "Opnemen"),
+        ("Recording", "Opname"),
+        ("Reflection probes", "Reflectieprobes"),
+        ("Remove", "Verwijderen"),
+        ("Render mode", "Weergavemodus"),
+        ("Replay", "Afspelen"),
+        ("Resolution", "Resolutie"),
+        ("Rotation", "Rotatie"),
+        ("Rotation jitter", "Willekeurige rotatie"),
+        ("Samples", "Samples"),
+        ("Scale", "Schaal"),
+        ("Scale jitter", "Willekeurige schaal"),
+        ("Scatter", "Verspreiden"),
+        ("Selected", "Geselecteerd"),
+        ("Selection", "Selectie"),
+        ("Show probes", "Probes tonen"),
+        ("Steps", "Stappen"),
+        ("Stop recording", "Opname stoppen"),
+        ("Stop replay", "Afspelen stoppen"),
+        ("Surface snap", "Uitlijnen op oppervlak"),
+        ("Units", "Eenheden"),
+        ("Use color ramp", "Kleurverloop gebruiken"),
+        ("Volume", "Volume"),
+        ("World", "Wereld"),
+        ("Custom accent", "Eigen accentkleur"),
+        ("Dark", "Donker"),
+        ("Interface", "Interface"),
+        ("Light", "Licht"),
+        ("Theme", "Thema"),
+        ("UI scale", "UI-schaal"),
+        ("Baking lightmap", "Lightmap bakken"),
+        ("Cancel", "Annuleren"),
+        ("entities", "entiteiten"),
+        ("points", "punten"),
+        ("triangles", "driehoeken"),
+        ("Concurrent loads", "Gelijktijdige laadtaken"),
+        ("Transparent background", "Transparante achtergrond"),
+        ("Save screenshot", "Schermafbeelding opslaan"),
+        ("with depth, normals and ids", "met diepte, normalen en id's"),
+        ("Save tiled screenshot", "Schermafbeelding in tegels opslaan"),
+        ("Save stereo panorama", "Stereopanorama opslaan"),
+        ("strips", "stroken"),
+        ("16-bit indices", "16-bit indices"),
+        ("Vertex precision", "Vertexprecisie"),
+        ("GPU memory is running low", "GPU-geheugen raakt op"),
+        ("Load waits for free GPU memory", "Laden wacht op vrij GPU-geheugen"),
+        ("loads waiting for GPU memory", "laadacties wachten op GPU-geheugen"),
+        ("Load anyway", "Toch laden"),
+        ("Discard", "Verwerpen"),
+        ("In the background", "Op de achtergrond"),
+        ("Full rate", "Volledige snelheid"),
+        ("Paused", "Gepauzeerd"),
+        ("Render on demand", "Renderen op aanvraag"),
+        ("Only draws after a change", "Tekent alleen na een wijziging"),
+        ("Light type", "Soort licht"),
+        ("Directional", "Richting"),
+        ("Point", "Punt"),
+        ("Spot", "Spot"),
+        ("Casts shadows", "Werpt schaduw"),
+        ("Range", "Bereik"),
+        ("Zero never cuts the light off", "Bij nul reikt het licht onbeperkt ver"),
+        ("Inner cone", "Binnenste kegel"),
+        ("Outer cone", "Buitenste kegel"),
+        ("Points per frame", "Punten per frame"),
+        ("The rest fills in while still", "De rest vult aan bij stilstand"),
+        ("Point", "Punt"),
+        ("Index", "Index"),
+        ("Source position", "Bronpositie"),
+        ("Color", "Kleur"),
+        ("Classification", "Classificatie"),
+        ("Return", "Echo"),
+        ("GPS time", "GPS-tijd"),
+        ("Never classified", "Nooit geclassificeerd"),
+        ("Unclassified", "Ongeclassificeerd"),
+        ("Ground", "Maaiveld"),
+        ("Low vegetation", "Lage vegetatie"),
+        ("Medium vegetation", "Middelhoge vegetatie"),
+        ("High vegetation", "Hoge vegetatie"),
+        ("Building", "Gebouw"),
+        ("Low point", "Laag punt"),
+        ("Model key point", "Modelsleutelpunt"),
+        ("Water", "Water"),
+        ("Rail", "Spoor"),
+        ("Road surface", "Wegdek"),
+        ("Overlap", "Overlap"),
+        ("Wire guard", "Bliksemdraad"),
+        ("Wire conductor", "Geleider"),
+        ("Transmission tower", "Hoogspanningsmast"),
+        ("Wire connector", "Draadverbinding"),
+        ("Bridge deck", "Brugdek"),
+        ("High noise", "Hoge ruis"),
+        ("User defined", "Gebruikersgedefinieerd"),
+        ("Export points", "Punten exporteren"),
+        ("Select a pointcloud first", "Selecteer eerst een puntenwolk"),
+        ("Only points in view", "Alleen punten in beeld"),
+        ("Materials", "Materialen"),
+        ("Import mode", "Importmodus"),
+        ("Append to scene", "Aan scène toevoegen"),
+        ("Replace scene", "Scène vervangen"),
+        ("Prefabs", "Prefabs"),
+        ("Instance of", "Instantie van"),
+        ("Apply to prefab", "Op prefab toepassen"),
+        ("Save as prefab", "Opslaan als prefab"),
+        (
+            "Saves the selection's instance or assembly, lights included",
+            "Slaat de instantie of het samenstel van de selectie op, inclusief lampen",
+        ),
+        ("Place", "Plaatsen"),
+        ("Tags", "Labels"),
+        ("Add tag", "Label toevoegen"),
+        ("Isolate", "Isoleren"),
+        ("Show all", "Alles tonen"),
+        ("Ghost others", "Overige doorschijnend"),
+        ("Isolate tag", "Label isoleren"),
+        ("Explode", "Exploderen"),
+        ("Turntable", "Draaitafel"),
+        ("Spin", "Draaien"),
+        ("Speed", "Snelheid"),
+        ("Render layers", "Renderlagen"),
+        ("Main window", "Hoofdvenster"),
+        ("Window", "Venster"),
+        ("Scene", "Scène"),
+        ("Helpers", "Hulpmiddelen"),
+        ("Extra", "Extra"),
+        ("Session", "Sessie"),
+        ("you", "jij"),
+        (
+            "I to isolate the selection, Escape to show everything again",
+            "I om de selectie te isoleren, Escape om alles weer te tonen",
+        ),
+        ("Classify points", "Punten classificeren"),
+        ("Class", "Klasse"),
+        ("Undo", "Ongedaan maken"),
+        ("Registration", "Registratie"),
+        ("Reference scan", "Referentiescan"),
+        ("Moving scan", "Te verplaatsen scan"),
+        ("Pick point pairs", "Puntparen kiezen"),
+        (
+            "Click a point on the moving scan",
+            "Klik een punt op de te verplaatsen scan",
+        ),
+        (
+            "Click the matching point on the reference scan",
+            "Klik het bijbehorende punt op de referentiescan",
+        ),
+        ("Align pairs", "Paren uitlijnen"),
+        ("Maximum distance", "Maximale afstand"),
+        ("Refine with ICP", "Verfijnen met ICP"),
+        ("RMS error", "RMS-fout"),
+        ("matches", "overeenkomsten"),
+        ("iterations", "iteraties"),
+        ("Camera", "Camera"),
+        ("Physical camera", "Fysieke camera"),
+        ("Focal length", "Brandpuntsafstand"),
+        ("Sensor width", "Sensorbreedte"),
+        ("Sensor", "Sensor"),
+        ("Full frame", "Volledig beeld"),
+        ("ISO", "ISO"),
+        ("Shutter speed", "Sluitertijd"),
+        ("Aperture", "Diafragma"),
+        ("Lens distortion", "Lensvervorming"),
+        (
+            "Preview only, picking and captured buffers are undistorted",
+            "Alleen als voorbeeld, selecteren en vastgelegde buffers blijven onvervormd",
+        ),
+        ("Match photo", "Foto matchen"),
+        ("Open photo", "Foto openen"),
+        ("Ground grid", "Grondraster"),
+        ("Vanishing lines", "Vluchtlijnen"),
+        (
+            "Drag the red lines along edges parallel to X, the blue ones along Z",
+            "Sleep de rode lijnen langs randen evenwijdig aan X, de blauwe langs Z",
+        ),
+        ("Horizontal field of view", "Horizontale beeldhoek"),
+        ("Match camera", "Camera matchen"),
+        ("Frustum culling", "Frustum culling"),
+        ("Skips meshes outside the view", "Slaat meshes buiten beeld over"),
+        ("Pick animated meshes", "Geanimeerde meshes selecteren"),
+        (
+            "Selects and measures skinned meshes in the pose they're drawn in",
+            "Selecteert en meet geskinde meshes in de houding waarin ze getekend worden",
+        ),
+        ("Change detection", "Veranderingsdetectie"),
+        ("Compared scan", "Vergeleken scan"),
+        ("Compare", "Vergelijken"),
+        ("Restore colors", "Kleuren herstellen"),
+        ("Mean distance", "Gemiddelde afstand"),
+        ("Reference", "Referentie"),
+        (
+            "Points further from the reference count as unmatched",
+            "Verdere punten tellen als niet gevonden",
+        ),
+        ("Lower threshold", "Ondergrens"),
+        ("Upper threshold", "Bovengrens"),
+        ("Floorplan", "Plattegrond"),
+        ("Plan", "Bovenaanzicht"),
+        ("Section along X", "Doorsnede langs X"),
+        ("Section along Z", "Doorsnede langs Z"),
+        ("Slab", "Laag"),
+        ("Fit", "Passend maken"),
+        ("px per meter", "px per meter"),
+        ("Save floorplan", "Plattegrond opslaan"),
+        ("Sun study", "Bezonningsstudie"),
+        ("Latitude", "Breedtegraad"),
+        ("Date", "Datum"),
+        ("Hours", "Uren"),
+        ("Export sun study", "Bezonningsstudie exporteren"),
+        ("Stop", "Stoppen"),
+        ("Fit clip planes to the scene", "Clipvlakken aan de scène aanpassen"),
+        (
+            "Avoids clipped large scenes and flickering small ones",
+            "Voorkomt afgesneden grote scènes en flikkerende kleine",
+        ),
+        ("Clip planes", "Clipvlakken"),
+        ("Batch small meshes", "Kleine meshes bundelen"),
+        (
+            "Merges small parts sharing a material into one draw",
+            "Voegt kleine onderdelen met hetzelfde materiaal samen tot één draw",
+        ),
+        ("Pack textures into atlases", "Texturen in atlassen verpakken"),
+        (
+            "Merges materials that only differ in their small textures",
+            "Voegt materialen samen die alleen in hun kleine texturen verschillen",
+        ),
+        ("Material variant", "Materiaalvariant"),
+        ("Default", "Standaard"),
+        ("Unload asset", "Asset verwijderen"),
+        (
+            "Removes every entity of the selected asset and frees its memory",
+            "Verwijdert elke entiteit van de geselecteerde asset en geeft het geheugen vrij",
+        ),
+        ("Animation offset", "Animatie-offset"),
+        ("Animation speed", "Animatiesnelheid"),
+        ("Stagger copies", "Kopieën spreiden"),
+        (
+            "Starts every copy of this node at a random point in its clip, at a slightly different speed",
+            "Start elke kopie van deze node op een willekeurig punt in de clip, met een iets andere snelheid",
+        ),
+        ("Frame graph", "Framegraaf"),
+    ])
+});
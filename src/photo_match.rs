@@ -0,0 +1,274 @@
+use crossbeam::channel::{Receiver, Sender};
+
+use crate::dialog::open_photo_dialog;
+
+// Two lines per horizontal axis, traced along edges in the photo that run parallel to it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VanishingAxis {
+    X,
+    Z,
+}
+
+impl VanishingAxis {
+    pub const ALL: [Self; 2] = [Self::X, Self::Z];
+
+    fn color(self) -> egui::Color32 {
+        match self {
+            Self::X => egui::Color32::from_rgb(230, 60, 60),
+            Self::Z => egui::Color32::from_rgb(60, 120, 230),
+        }
+    }
+}
+
+// The camera that lines the vanishing points up with the world axes, seen from where the camera already is
+#[derive(Copy, Clone, Debug)]
+pub struct CameraMatch {
+    pub orientation: glam::Quat,
+    // Focal length as a fraction of the view width, independent of the window size
+    pub focal_length: f32,
+}
+
+// A photo behind the UI to line a scan or model up with. The plate is painted over the rendered scene, so its opacity
+// blends the two. Aids are a ground grid in world space and vanishing lines whose vanishing points give the camera's
+// orientation and focal length, assuming the photo's principal point is its center
+pub struct PhotoMatch {
+    pub opacity: f32,
+    pub show_grid: bool,
+    // World units between grid lines
+    pub grid_spacing: f32,
+    pub show_vanishing_lines: bool,
+    photo: Option<egui::TextureHandle>,
+    // Endpoints as fractions of the photo, so they stay on its features when the window is resized
+    lines: [[glam::Vec2; 2]; 4],
+    photo_tx: Sender<egui::ColorImage>,
+    photo_rx: Receiver<egui::ColorImage>,
+}
+
+impl PhotoMatch {
+    // Larger photos are scaled down to stay within texture limits
+    const MAX_PHOTO_SIZE: u32 = 4096;
+    const GRID_LINES: i32 = 20;
+    const HANDLE_RADIUS: f32 = 6.0;
+
+    pub fn new() -> Self {
+        let (photo_tx, photo_rx) = crossbeam::channel::unbounded();
+        Self {
+            opacity: 0.5,
+            show_grid: true,
+            grid_spacing: 1.0,
+            show_vanishing_lines: false,
+            photo: None,
+            lines: [
+                [glam::vec2(0.2, 0.6), glam::vec2(0.45, 0.55)],
+                [glam::vec2(0.2, 0.8), glam::vec2(0.45, 0.7)],
+                [glam::vec2(0.55, 0.55), glam::vec2(0.8, 0.6)],
+                [glam::vec2(0.55, 0.7), glam::vec2(0.8, 0.8)],
+            ],
+            photo_tx,
+            photo_rx,
+        }
+    }
+
+    pub fn has_photo(&self) -> bool {
+        self.photo.is_some()
+    }
+
+    pub fn open(&self) {
+        let photo_tx = self.photo_tx.clone();
+        open_photo_dialog(move |bytes| match image::load_from_memory(&bytes) {
+            Ok(photo) => {
+                let photo = match photo.width().max(photo.height()) > Self::MAX_PHOTO_SIZE {
+                    true => photo.resize(
+                        Self::MAX_PHOTO_SIZE,
+                        Self::MAX_PHOTO_SIZE,
+                        image::imageops::FilterType::Triangle,
+                    ),
+                    false => photo,
+                };
+                let photo = photo.to_rgba8();
+                let size = [photo.width() as usize, photo.height() as usize];
+                photo_tx
+                    .send(egui::ColorImage::from_rgba_unmultiplied(size, photo.as_raw()))
+                    .unwrap();
+            }
+            Err(error) => log::error!("Unable to read photo: {}", error),
+        });
+    }
+
+    pub fn close(&mut self) {
+        self.photo = None;
+    }
+
+    // Uploads a photo once the dialog has read it
+    pub fn poll(&mut self, ctx: &egui::Context) {
+        if let Some(photo) = self.photo_rx.try_iter().last() {
+            self.photo = Some(ctx.load_texture("photo_plate", photo, egui::TextureOptions::LINEAR));
+        }
+    }
+
+    // Draws the plate and the aids, and lets the vanishing line endpoints be dragged
+    pub fn show(&mut self, ctx: &egui::Context, view_projection: glam::Mat4) {
+        let Some(photo) = &self.photo else {
+            return;
+        };
+        let screen = ctx.content_rect();
+        let plate = Self::fit(photo.size_vec2(), screen);
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        painter.image(
+            photo.id(),
+            plate,
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            egui::Color32::from_white_alpha((self.opacity * 255.0) as u8),
+        );
+
+        if self.show_grid {
+            let extent = Self::GRID_LINES as f32 * self.grid_spacing;
+            let stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(120));
+            for line in -Self::GRID_LINES..=Self::GRID_LINES {
+                let offset = line as f32 * self.grid_spacing;
+                for (start, end) in [
+                    (glam::vec3(offset, 0.0, -extent), glam::vec3(offset, 0.0, extent)),
+                    (glam::vec3(-extent, 0.0, offset), glam::vec3(extent, 0.0, offset)),
+                ] {
+                    if let Some(segment) = project_segment(view_projection, start, end, screen) {
+                        painter.line_segment(segment, stroke);
+                    }
+                }
+            }
+        }
+
+        if !self.show_vanishing_lines {
+            return;
+        }
+        let to_screen = |point: glam::Vec2| plate.min + egui::vec2(point.x, point.y) * plate.size();
+        for (index, line) in self.lines.iter_mut().enumerate() {
+            for (end, point) in line.iter_mut().enumerate() {
+                let position = to_screen(*point);
+                let radius = Self::HANDLE_RADIUS;
+                let response = egui::Area::new(egui::Id::new(("vanishing_handle", index, end)))
+                    .fixed_pos(position - egui::vec2(radius, radius))
+                    .show(ctx, |ui| {
+                        ui.allocate_exact_size(egui::vec2(radius, radius) * 2.0, egui::Sense::drag())
+                            .1
+                    })
+                    .inner;
+                if response.dragged() {
+                    *point += glam::Vec2::new(response.drag_delta().x, response.drag_delta().y)
+                        / glam::Vec2::new(plate.width(), plate.height());
+                }
+            }
+        }
+
+        for axis in VanishingAxis::ALL {
+            let color = axis.color();
+            let vanishing_point = self.vanishing_point(axis, plate);
+            for line in self.axis_lines(axis) {
+                let [start, end] = line.map(to_screen);
+                painter.line_segment([start, end], egui::Stroke::new(2.0, color));
+                // Extended toward the vanishing point, from whichever end is closer to it
+                if let Some(point) = vanishing_point {
+                    let closest = match start.distance_sq(point) < end.distance_sq(point) {
+                        true => start,
+                        false => end,
+                    };
+                    painter.line_segment([closest, point], egui::Stroke::new(1.0, color.gamma_multiply(0.5)));
+                }
+                for position in [start, end] {
+                    painter.circle_stroke(position, Self::HANDLE_RADIUS, egui::Stroke::new(2.0, color));
+                }
+            }
+            if let Some(point) = vanishing_point {
+                painter.circle_filled(point, 4.0, color);
+            }
+        }
+    }
+
+    // None while a pair of lines is parallel or the vanishing points can't belong to perpendicular directions
+    pub fn solve(&self, screen: egui::Rect) -> Option<CameraMatch> {
+        let plate = Self::fit(self.photo.as_ref()?.size_vec2(), screen);
+        // Relative to the photo's center with y up, like camera space
+        let relative = |axis| {
+            let point = self.vanishing_point(axis, plate)? - plate.center();
+            Some(glam::Vec2::new(point.x, -point.y))
+        };
+        let (x, z) = (relative(VanishingAxis::X)?, relative(VanishingAxis::Z)?);
+
+        // Directions toward perpendicular vanishing points meet at a right angle, which fixes the focal length
+        let focal_squared = -x.dot(z);
+        if focal_squared <= 0.0 {
+            return None;
+        }
+        let focal = focal_squared.sqrt();
+
+        // The world axes in camera space
+        let mut x_axis = x.extend(-focal).normalize();
+        let z_axis = z.extend(-focal);
+        let z_axis = (z_axis - x_axis * z_axis.dot(x_axis)).normalize();
+        // Either end of an axis may be the one in view, the vertical axis has to come out pointing up
+        let rotation = |x_axis: glam::Vec3| {
+            glam::Mat3::from_cols(glam::Vec3::X, glam::Vec3::Z, glam::Vec3::NEG_Y)
+                * glam::Mat3::from_cols(x_axis, z_axis, x_axis.cross(z_axis)).transpose()
+        };
+        if (rotation(x_axis) * glam::Vec3::Y).y < 0.0 {
+            x_axis = -x_axis;
+        }
+
+        Some(CameraMatch {
+            orientation: glam::Quat::from_mat3(&rotation(x_axis)).normalize(),
+            focal_length: focal / screen.width(),
+        })
+    }
+
+    fn axis_lines(&self, axis: VanishingAxis) -> [[glam::Vec2; 2]; 2] {
+        let first = match axis {
+            VanishingAxis::X => 0,
+            VanishingAxis::Z => 2,
+        };
+        [self.lines[first], self.lines[first + 1]]
+    }
+
+    // Where the two lines of an axis cross on screen, None when they're parallel
+    fn vanishing_point(&self, axis: VanishingAxis, plate: egui::Rect) -> Option<egui::Pos2> {
+        let to_screen = |point: glam::Vec2| {
+            (glam::Vec2::new(plate.min.x, plate.min.y) + point * glam::Vec2::new(plate.width(), plate.height()))
+                .extend(1.0)
+        };
+        // Lines and their intersection in homogeneous coordinates
+        let [first, second] = self
+            .axis_lines(axis)
+            .map(|[start, end]| to_screen(start).cross(to_screen(end)));
+        let point = first.cross(second);
+        (point.z.abs() > f32::EPSILON).then(|| egui::pos2(point.x / point.z, point.y / point.z))
+    }
+
+    // The largest rectangle with the photo's aspect ratio that fits the screen, centered
+    fn fit(size: egui::Vec2, screen: egui::Rect) -> egui::Rect {
+        let scale = (screen.width() / size.x).min(screen.height() / size.y);
+        egui::Rect::from_center_size(screen.center(), size * scale)
+    }
+}
+
+// Clipped against the near plane, None when the segment is entirely behind the camera
+fn project_segment(
+    view_projection: glam::Mat4,
+    start: glam::Vec3,
+    end: glam::Vec3,
+    screen: egui::Rect,
+) -> Option<[egui::Pos2; 2]> {
+    const MIN_W: f32 = 1e-3;
+    let (mut start, mut end) = (view_projection * start.extend(1.0), view_projection * end.extend(1.0));
+    if start.w < MIN_W && end.w < MIN_W {
+        return None;
+    }
+    if start.w < MIN_W {
+        start = start.lerp(end, (MIN_W - start.w) / (end.w - start.w));
+    } else if end.w < MIN_W {
+        end = end.lerp(start, (MIN_W - end.w) / (start.w - end.w));
+    }
+
+    let to_screen = |clip: glam::Vec4| {
+        let ndc = clip.truncate() / clip.w;
+        screen.min + egui::vec2(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * screen.size()
+    };
+    Some([to_screen(start), to_screen(end)])
+}
@@ -0,0 +1,118 @@
+use crate::{
+    entity::{Entity, EntityId},
+    renderer::{Aabb, Ray, SceneQuery},
+};
+
+pub struct Snapping {
+    pub grid: bool,
+    pub grid_step: f32,
+    pub angle: bool,
+    // Degrees
+    pub angle_step: f32,
+    pub surface: bool,
+}
+
+impl Snapping {
+    pub fn new() -> Self {
+        Self {
+            grid: false,
+            grid_step: 0.5,
+            angle: false,
+            angle_step: 15.0,
+            surface: false,
+        }
+    }
+
+    pub fn snap_position(&self, position: glam::Vec3) -> glam::Vec3 {
+        if !self.grid || self.grid_step <= 0.0 {
+            return position;
+        }
+
+        (position / self.grid_step).round() * self.grid_step
+    }
+
+    pub fn snap_angle(&self, radians: f32) -> f32 {
+        if !self.angle || self.angle_step <= 0.0 {
+            return radians;
+        }
+
+        let step = self.angle_step.to_radians();
+        (radians / step).round() * step
+    }
+}
+
+// Unsnapped values of the selection's transform fields while one of them is being edited. Snapping the transform
+// itself would round every small drag step back to where it started
+pub struct TransformEdit {
+    pub entity_id: EntityId,
+    pub translation: glam::Vec3,
+    // Degrees, yaw pitch roll
+    pub angles: [f32; 3],
+}
+
+// Vertical shift that rests world space bounds on the first surface below them, or on y = 0 when there is none
+pub fn ground_offset(bounds: &Aabb, query: &SceneQuery) -> f32 {
+    if bounds.is_empty() {
+        return 0.0;
+    }
+
+    let bottom = bounds.centroid().with_y(bounds.min.y);
+    let ground = query
+        .closest_hit(&Ray::new(bottom, glam::Vec3::NEG_Y))
+        .map(|hit| hit.surface.position.y)
+        .unwrap_or(0.0);
+
+    ground - bounds.min.y
+}
+
+// Moves an entity with the cursor. Without surface snap it slides over the horizontal plane at its starting height,
+// with it the entity sits on whatever is under the cursor with its up axis along the hit normal. Grid snap applies to
+// both
+pub struct MoveTool {
+    entity_id: EntityId,
+    original: glam::Mat4,
+}
+
+impl MoveTool {
+    pub fn new(entity: &Entity) -> Self {
+        Self {
+            entity_id: entity.id(),
+            original: entity.transform(),
+        }
+    }
+
+    pub fn entity_id(&self) -> EntityId {
+        self.entity_id
+    }
+
+    pub fn original(&self) -> glam::Mat4 {
+        self.original
+    }
+
+    pub fn update(&self, ray: &Ray, query: &SceneQuery, snapping: &Snapping) -> Option<glam::Mat4> {
+        let (scale, rotation, translation) = self.original.to_scale_rotation_translation();
+
+        if snapping.surface
+            && let Some(hit) = query.closest_hit_excluding(ray, self.entity_id)
+        {
+            // Grid snapped, then pushed back onto the plane of the hit so it doesn't sink in or float off
+            let normal = hit.surface.normal;
+            let snapped = snapping.snap_position(hit.surface.position);
+            let position = snapped - normal * (snapped - hit.surface.position).dot(normal);
+            let align = glam::Quat::from_rotation_arc(glam::Vec3::Y, normal);
+            return Some(glam::Mat4::from_scale_rotation_translation(
+                scale,
+                align * rotation,
+                position,
+            ));
+        }
+
+        let distance = ray.intersect_plane(translation, glam::Vec3::Y)?;
+        let position = snapping.snap_position(ray.at(distance));
+        Some(glam::Mat4::from_scale_rotation_translation(
+            scale,
+            rotation,
+            glam::Vec3::new(position.x, translation.y, position.z),
+        ))
+    }
+}
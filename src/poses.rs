@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::{Aabb, Ray, SceneQuery};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub position: glam::Vec3,
+    pub target: glam::Vec3,
+}
+
+// Cameras spread around a center, looking at it. The azimuth is stratified so a shell covers every side
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct OrbitShell {
+    // The center of the scene bounds and one and a half times their radius when left out
+    pub center: Option<glam::Vec3>,
+    pub radius: Option<f32>,
+    pub count: u32,
+    // Degrees above the horizon
+    pub min_elevation: f32,
+    pub max_elevation: f32,
+}
+
+impl Default for OrbitShell {
+    fn default() -> Self {
+        Self {
+            center: None,
+            radius: None,
+            count: 24,
+            min_elevation: 10.0,
+            max_elevation: 45.0,
+        }
+    }
+}
+
+// Cameras anywhere inside a box, looking at a random point of the scene. A pose is only kept when the view ray hits
+// something at least `min_distance` away and at least `min_coverage` of a grid of rays across the view hit the scene
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RandomPoses {
+    pub count: u32,
+    // The scene bounds when left out
+    pub min: Option<glam::Vec3>,
+    pub max: Option<glam::Vec3>,
+    pub min_distance: f32,
+    pub min_coverage: f32,
+}
+
+impl Default for RandomPoses {
+    fn default() -> Self {
+        Self {
+            count: 100,
+            min: None,
+            max: None,
+            min_distance: 0.0,
+            min_coverage: 0.5,
+        }
+    }
+}
+
+// The same seed and scene give the same poses
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PoseSampler {
+    pub seed: u64,
+    pub orbits: Vec<OrbitShell>,
+    pub random: Option<RandomPoses>,
+}
+
+impl PoseSampler {
+    // Candidates tried per random pose before giving up on it
+    const MAX_ATTEMPTS: u32 = 100;
+    // Per axis of the view
+    const COVERAGE_RAYS: u32 = 5;
+
+    // Orbit poses first, shell by shell. None while the query holds no meshes to take the bounds from
+    pub fn sample(&self, query: &SceneQuery, fov_y: f32) -> Option<Vec<CameraPose>> {
+        let bounds = query.bounds()?;
+        let mut rng = fastrand::Rng::with_seed(self.seed);
+        let mut poses = Vec::new();
+
+        for shell in &self.orbits {
+            let center = shell.center.unwrap_or_else(|| bounds.centroid());
+            let radius = shell
+                .radius
+                .unwrap_or_else(|| (bounds.max - bounds.min).length() * 0.75);
+            for index in 0..shell.count {
+                let azimuth = (index as f32 + rng.f32()) / shell.count as f32 * std::f32::consts::TAU;
+                let elevation =
+                    (shell.min_elevation + (shell.max_elevation - shell.min_elevation) * rng.f32()).to_radians();
+                let direction = glam::Vec3::new(
+                    elevation.cos() * azimuth.cos(),
+                    elevation.sin(),
+                    elevation.cos() * azimuth.sin(),
+                );
+                poses.push(CameraPose {
+                    position: center + direction * radius,
+                    target: center,
+                });
+            }
+        }
+
+        if let Some(random) = &self.random {
+            let region = Aabb {
+                min: random.min.unwrap_or(bounds.min),
+                max: random.max.unwrap_or(bounds.max),
+            };
+            let mut sampled = 0;
+            for _ in 0..random.count * Self::MAX_ATTEMPTS {
+                if sampled == random.count {
+                    break;
+                }
+                let position = random_point(&mut rng, &region);
+                if let Some(target) = random.accept(query, position, random_point(&mut rng, &bounds), fov_y) {
+                    poses.push(CameraPose { position, target });
+                    sampled += 1;
+                }
+            }
+            if sampled < random.count {
+                log::warn!(
+                    "Only {} of {} random poses met the visibility constraints",
+                    sampled,
+                    random.count
+                );
+            }
+        }
+
+        Some(poses)
+    }
+}
+
+impl RandomPoses {
+    // The surface point the camera ends up looking at, when the candidate passes
+    fn accept(&self, query: &SceneQuery, position: glam::Vec3, toward: glam::Vec3, fov_y: f32) -> Option<glam::Vec3> {
+        let forward = (toward - position).try_normalize()?;
+        let hit = query.closest_hit(&Ray::new(position, forward))?;
+        if hit.surface.distance < self.min_distance {
+            return None;
+        }
+
+        let right = forward.cross(glam::Vec3::Y).try_normalize()?;
+        let up = right.cross(forward);
+        let extent = (fov_y * 0.5).tan();
+        let rays = PoseSampler::COVERAGE_RAYS;
+        let hits = (0..rays * rays)
+            .filter(|index| {
+                let offset = glam::Vec2::new((index % rays) as f32, (index / rays) as f32) / (rays - 1) as f32;
+                let offset = (offset * 2.0 - 1.0) * extent;
+                let direction = forward + right * offset.x + up * offset.y;
+                query.any_hit(&Ray::new(position, direction), f32::INFINITY)
+            })
+            .count();
+
+        (hits as f32 >= self.min_coverage * (rays * rays) as f32).then_some(hit.surface.position)
+    }
+}
+
+fn random_point(rng: &mut fastrand::Rng, bounds: &Aabb) -> glam::Vec3 {
+    let t = glam::Vec3::new(rng.f32(), rng.f32(), rng.f32());
+    bounds.min + (bounds.max - bounds.min) * t
+}
@@ -0,0 +1,459 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    entity::{Entity, EntityId},
+    renderer::{ImportOptions, Light, RenderId, ResourcePath},
+    watch::FileWatcher,
+};
+
+const DIRECTORY: &str = "prefabs";
+
+// Renderables are stored by the file they were imported from and their node index in it, like a shared session
+// matches them, so a prefab outlives the render ids of one run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PrefabMember {
+    node: Option<(String, usize)>,
+    light: Option<Light>,
+    // Relative to the prefab's origin
+    transform: glam::Mat4,
+    label: Option<String>,
+    tags: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Prefab {
+    // Path and options of every file the nodes come from, imported again when they aren't loaded
+    sources: HashMap<String, (String, ImportOptions)>,
+    members: Vec<PrefabMember>,
+}
+
+struct PrefabFile {
+    name: String,
+    prefab: Prefab,
+    file: FileWatcher,
+}
+
+struct Instance {
+    prefab: usize,
+    origin: glam::Mat4,
+    // Per member, None while its file is still loading
+    entities: Vec<Option<EntityId>>,
+}
+
+// What the app does to its entities to keep the instances in line with their prefab
+pub enum PrefabChange {
+    Spawn(Entity, Option<Light>),
+    Update {
+        entity_id: EntityId,
+        transform: glam::Mat4,
+        label: Option<String>,
+        tags: BTreeSet<String>,
+        light: Option<Light>,
+    },
+    Despawn(EntityId),
+}
+
+// Entity compositions saved as JSON files in `prefabs/` and placed any number of times. Applying the edits made to
+// one instance, or editing the file outside the app, updates every other instance. Instances are only known for as
+// long as the app runs
+pub struct PrefabLibrary {
+    prefabs: Vec<PrefabFile>,
+    instances: Vec<Instance>,
+    // Lights spawned for instances, the app only keeps its own
+    lights: HashMap<EntityId, Light>,
+    sources: HashMap<String, (String, ImportOptions)>,
+    // File name and node index of every loaded renderable
+    node_counts: HashMap<Uuid, usize>,
+    keys: HashMap<RenderId, (String, usize)>,
+    renderables: HashMap<(String, usize), RenderId>,
+    // Files imported for an instance, their nodes aren't spawned by themselves
+    requested: Vec<String>,
+    requested_assets: HashSet<Uuid>,
+    loads: Vec<(ResourcePath, ImportOptions)>,
+    changes: Vec<PrefabChange>,
+}
+
+impl PrefabLibrary {
+    pub fn new() -> Self {
+        let mut prefabs = std::fs::read_dir(DIRECTORY)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+                    .filter_map(|path| {
+                        let name = path.file_stem()?.to_string_lossy().to_string();
+                        let mut file = FileWatcher::new(&path);
+                        let prefab = parse(&path, &file.read()?)?;
+                        Some(PrefabFile { name, prefab, file })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        prefabs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            prefabs,
+            instances: Vec::new(),
+            lights: HashMap::new(),
+            sources: HashMap::new(),
+            node_counts: HashMap::new(),
+            keys: HashMap::new(),
+            renderables: HashMap::new(),
+            requested: Vec::new(),
+            requested_assets: HashSet::new(),
+            loads: Vec::new(),
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.iter().map(|prefab| prefab.name.as_str())
+    }
+
+    // Name of the prefab the entity was placed by
+    pub fn instance_of(&self, entity_id: EntityId) -> Option<&str> {
+        let instance = self.find_instance(entity_id)?;
+        Some(&self.prefabs[self.instances[instance].prefab].name)
+    }
+
+    // Every entity of the entity's instance, or None when it isn't part of one
+    pub fn instance_entities(&self, entity_id: EntityId) -> Option<Vec<EntityId>> {
+        let instance = self.find_instance(entity_id)?;
+        Some(self.instances[instance].entities.iter().flatten().copied().collect())
+    }
+
+    pub fn light(&self, entity_id: EntityId) -> Option<&Light> {
+        self.lights.get(&entity_id)
+    }
+
+    // Remembers where a file was loaded from, for prefabs that use it
+    pub fn record_load(&mut self, path: &ResourcePath, options: &ImportOptions) {
+        self.sources.insert(
+            path.file_name().to_string(),
+            (path.as_str().to_string(), options.clone()),
+        );
+    }
+
+    // Whether the app imported the file for an instance
+    pub fn is_requested(&self, path: &ResourcePath) -> bool {
+        self.requested.iter().any(|requested| *requested == path.file_name())
+    }
+
+    // For every LoadComplete, nodes of one asset arrive in order. Returns whether the asset was imported for an
+    // instance, its nodes are then only spawned as part of that
+    pub fn register_node(&mut self, asset_id: Uuid, render_id: RenderId, label: Option<&str>) -> bool {
+        let Some(label) = label else {
+            return false;
+        };
+
+        let count = self.node_counts.entry(asset_id).or_default();
+        let key = (label.to_string(), *count);
+        *count += 1;
+        self.keys.insert(render_id, key.clone());
+        self.renderables.insert(key, render_id);
+
+        if *count == 1
+            && let Some(index) = self.requested.iter().position(|requested| requested == label)
+        {
+            self.requested.remove(index);
+            self.requested_assets.insert(asset_id);
+        }
+
+        self.spawn_waiting();
+        self.requested_assets.contains(&asset_id)
+    }
+
+    // Stores the entities as a prefab around `origin`, replacing one with the same name. Entities that weren't
+    // imported from a file, and aren't lights, are left out
+    pub fn save(&mut self, name: &str, origin: glam::Mat4, entities: &[(&Entity, Option<Light>)]) {
+        let Some((_, prefab)) = self.capture(name, origin, entities) else {
+            return;
+        };
+
+        match self.prefabs.iter().position(|prefab| prefab.name == name) {
+            Some(index) => self.replace(index, prefab, None),
+            None => {
+                self.prefabs.push(PrefabFile {
+                    name: name.to_string(),
+                    prefab,
+                    file: FileWatcher::new(Path::new(DIRECTORY).join(format!("{}.json", name))),
+                });
+                self.write(self.prefabs.len() - 1);
+            }
+        }
+    }
+
+    // Makes the prefab what the entity's instance looks like now, and updates the other instances to match
+    pub fn apply(&mut self, entity_id: EntityId, entities: &[(&Entity, Option<Light>)]) {
+        let Some(index) = self.find_instance(entity_id) else {
+            return;
+        };
+        let Instance { prefab, origin, .. } = self.instances[index];
+        let Some((entity_ids, mut captured)) = self.capture(&self.prefabs[prefab].name, origin, entities) else {
+            return;
+        };
+
+        // Files loaded for an instance in an earlier run aren't recorded in this one
+        for (file_name, source) in &self.prefabs[prefab].prefab.sources {
+            captured
+                .sources
+                .entry(file_name.clone())
+                .or_insert_with(|| source.clone());
+        }
+        self.instances[index].entities = entity_ids.into_iter().map(Some).collect();
+        self.replace(prefab, captured, Some(index));
+    }
+
+    // Places the prefab at `origin`. Files that aren't loaded are imported first, their members follow once the
+    // nodes are in
+    pub fn instantiate(&mut self, name: &str, origin: glam::Mat4) {
+        let Some(prefab) = self.prefabs.iter().position(|prefab| prefab.name == name) else {
+            return;
+        };
+
+        let members = self.prefabs[prefab].prefab.members.len();
+        self.instances.push(Instance {
+            prefab,
+            origin,
+            entities: vec![None; members],
+        });
+
+        let mut missing = self.prefabs[prefab]
+            .prefab
+            .members
+            .iter()
+            .filter_map(|member| member.node.as_ref())
+            .filter(|key| !self.renderables.contains_key(key))
+            .map(|(file_name, _)| file_name.clone())
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        for file_name in missing {
+            if self.requested.contains(&file_name) {
+                continue;
+            }
+
+            let source = self.prefabs[prefab].prefab.sources.get(&file_name).cloned();
+            match source.and_then(|(path, options)| Some((ResourcePath::new(&path).ok()?, options))) {
+                Some(load) => {
+                    self.requested.push(file_name);
+                    self.loads.push(load);
+                }
+                None => log::warn!(
+                    "Prefab {} uses {}, which isn't loaded and can't be found",
+                    name,
+                    file_name
+                ),
+            }
+        }
+
+        self.spawn_waiting();
+    }
+
+    // Despawned entities drop out of their instance, the prefab keeps the member
+    pub fn remove_entity(&mut self, entity_id: EntityId) {
+        self.lights.remove(&entity_id);
+        for instance in &mut self.instances {
+            for entity in &mut instance.entities {
+                if *entity == Some(entity_id) {
+                    *entity = None;
+                }
+            }
+        }
+        self.instances
+            .retain(|instance| instance.entities.iter().any(Option::is_some));
+    }
+
+    // Files to import for instances
+    pub fn take_loads(&mut self) -> Vec<(ResourcePath, ImportOptions)> {
+        std::mem::take(&mut self.loads)
+    }
+
+    // Picks up prefab files edited outside the app, and returns everything the instances need since the last poll
+    pub fn poll(&mut self) -> Vec<PrefabChange> {
+        for index in 0..self.prefabs.len() {
+            let Some(contents) = self.prefabs[index].file.poll() else {
+                continue;
+            };
+            if let Some(prefab) = parse(self.prefabs[index].file.path(), &contents) {
+                self.prefabs[index].prefab = prefab;
+                self.propagate(index, None);
+            }
+        }
+
+        std::mem::take(&mut self.changes)
+    }
+
+    // The prefab made of the entities, with the ids of the ones it kept in member order
+    fn capture(
+        &self,
+        name: &str,
+        origin: glam::Mat4,
+        entities: &[(&Entity, Option<Light>)],
+    ) -> Option<(Vec<EntityId>, Prefab)> {
+        let inverse = origin.inverse();
+        let (entity_ids, members): (Vec<_>, Vec<_>) = entities
+            .iter()
+            .filter_map(|(entity, light)| {
+                let node = entity
+                    .render_id()
+                    .and_then(|render_id| self.keys.get(&render_id))
+                    .cloned();
+                let member = PrefabMember {
+                    node,
+                    light: light.clone(),
+                    transform: inverse * entity.transform(),
+                    label: entity.label().clone(),
+                    tags: entity.tags().clone(),
+                };
+                (member.node.is_some() || member.light.is_some()).then_some((entity.id(), member))
+            })
+            .unzip();
+        if members.is_empty() {
+            log::warn!(
+                "Nothing to save in prefab {}, only imported nodes and lights are kept",
+                name
+            );
+            return None;
+        }
+
+        let sources = members
+            .iter()
+            .filter_map(|member| member.node.as_ref())
+            .filter_map(|(file_name, _)| Some((file_name.clone(), self.sources.get(file_name)?.clone())))
+            .collect();
+        Some((entity_ids, Prefab { sources, members }))
+    }
+
+    fn find_instance(&self, entity_id: EntityId) -> Option<usize> {
+        self.instances
+            .iter()
+            .position(|instance| instance.entities.contains(&Some(entity_id)))
+    }
+
+    fn replace(&mut self, index: usize, prefab: Prefab, except: Option<usize>) {
+        self.prefabs[index].prefab = prefab;
+        self.write(index);
+        self.propagate(index, except);
+    }
+
+    fn write(&mut self, index: usize) {
+        let file = &mut self.prefabs[index];
+        let result = std::fs::create_dir_all(DIRECTORY)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(serde_json::to_string_pretty(&file.prefab)?))
+            .and_then(|contents| file.file.write(&contents));
+
+        if let Err(error) = result {
+            log::error!("Unable to write {}: {}", file.file.path().display(), error);
+        }
+    }
+
+    // Every instance of the prefab takes its members over. Members whose node changed are spawned again, the ones
+    // the prefab no longer has are despawned
+    fn propagate(&mut self, prefab: usize, except: Option<usize>) {
+        let members = &self.prefabs[prefab].prefab.members;
+        for (index, instance) in self.instances.iter_mut().enumerate() {
+            if instance.prefab != prefab || Some(index) == except {
+                continue;
+            }
+
+            for entity_id in instance
+                .entities
+                .drain(members.len().min(instance.entities.len())..)
+                .flatten()
+            {
+                self.lights.remove(&entity_id);
+                self.changes.push(PrefabChange::Despawn(entity_id));
+            }
+            instance.entities.resize(members.len(), None);
+
+            for (member, entity) in members.iter().zip(&mut instance.entities) {
+                let Some(entity_id) = *entity else {
+                    continue;
+                };
+
+                let render_id = member.node.as_ref().and_then(|key| self.renderables.get(key));
+                let is_light = self.lights.contains_key(&entity_id);
+                let is_same_kind = match render_id {
+                    Some(render_id) => self.keys.get(render_id) == member.node.as_ref() && !is_light,
+                    None => is_light && member.light.is_some(),
+                };
+                if !is_same_kind {
+                    self.lights.remove(&entity_id);
+                    self.changes.push(PrefabChange::Despawn(entity_id));
+                    *entity = None;
+                    continue;
+                }
+
+                let transform = instance.origin * member.transform;
+                let light = member.light.clone().map(|mut light| {
+                    light.set_transform(transform);
+                    self.lights.insert(entity_id, light.clone());
+                    light
+                });
+                self.changes.push(PrefabChange::Update {
+                    entity_id,
+                    transform,
+                    label: member.label.clone(),
+                    tags: member.tags.clone(),
+                    light,
+                });
+            }
+        }
+
+        self.spawn_waiting();
+    }
+
+    // Spawns the members of every instance whose node has loaded by now
+    fn spawn_waiting(&mut self) {
+        for instance in &mut self.instances {
+            let members = &self.prefabs[instance.prefab].prefab.members;
+            for (member, entity) in members.iter().zip(&mut instance.entities) {
+                if entity.is_some() {
+                    continue;
+                }
+
+                let transform = instance.origin * member.transform;
+                let mut spawned = Entity::new(transform, member.label.clone());
+                spawned.set_tags(member.tags.clone());
+                let light = match (&member.node, &member.light) {
+                    (Some(key), _) => match self.renderables.get(key) {
+                        Some(render_id) => {
+                            spawned = spawned.with_render_id(*render_id);
+                            None
+                        }
+                        None => continue,
+                    },
+                    (None, Some(light)) => {
+                        let mut light = light.clone();
+                        light.set_transform(transform);
+                        self.lights.insert(spawned.id(), light.clone());
+                        Some(light)
+                    }
+                    (None, None) => continue,
+                };
+
+                *entity = Some(spawned.id());
+                self.changes.push(PrefabChange::Spawn(spawned, light));
+            }
+        }
+    }
+}
+
+fn parse(path: &Path, contents: &str) -> Option<Prefab> {
+    match serde_json::from_str(contents) {
+        Ok(prefab) => Some(prefab),
+        Err(error) => {
+            log::error!("Unable to parse {}: {}", path.display(), error);
+            None
+        }
+    }
+}
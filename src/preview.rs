@@ -0,0 +1,144 @@
+use crate::{
+    camera::{Camera, Projection},
+    entity::{Entity, EntityId, EntityStore},
+    renderer::{PREVIEW_SIZE, RenderCommand, Renderer},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PreviewSource {
+    Camera,
+    Light(EntityId),
+}
+
+// Inset showing the scene from a light or a secondary camera, for aiming lights and composing shots
+pub struct PreviewWindow {
+    pub is_open: bool,
+    source: PreviewSource,
+    // Secondary camera, placed with "Set from view"
+    camera: Camera,
+    projection: Projection,
+    interval: u32,
+    texture_id: Option<egui::TextureId>,
+    sent_interval: Option<u32>,
+}
+
+impl PreviewWindow {
+    pub const MAX_INTERVAL: u32 = 30;
+
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            source: PreviewSource::Camera,
+            camera: Camera::new((0.0, 5.0, 10.0), 45.0_f32.to_radians(), -20.0_f32.to_radians()),
+            projection: Projection::new(PREVIEW_SIZE[0], PREVIEW_SIZE[1], 60.0_f32.to_radians(), 0.1, 500.0),
+            interval: 4,
+            texture_id: None,
+            sent_interval: None,
+        }
+    }
+
+    pub fn set_texture(&mut self, texture_id: egui::TextureId) {
+        self.texture_id = Some(texture_id);
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, entities: &EntityStore, view: &Camera) {
+        if !self.is_open {
+            return;
+        }
+
+        let mut is_open = self.is_open;
+        egui::Window::new("Preview")
+            .open(&mut is_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let lights = light_entities(entities);
+                let label = |source: &PreviewSource| match source {
+                    PreviewSource::Camera => "Camera".to_string(),
+                    PreviewSource::Light(id) => entities
+                        .get(id)
+                        .and_then(|entity| entity.label().clone())
+                        .unwrap_or_else(|| "Light".to_string()),
+                };
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("preview_source")
+                        .selected_text(label(&self.source))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.source, PreviewSource::Camera, "Camera");
+                            for entity in &lights {
+                                let source = PreviewSource::Light(entity.id());
+                                ui.selectable_value(&mut self.source, source, label(&source));
+                            }
+                        });
+
+                    if self.source == PreviewSource::Camera && ui.button("Set from view").clicked() {
+                        self.camera = view.clone();
+                    }
+                });
+                ui.add(egui::Slider::new(&mut self.interval, 1..=Self::MAX_INTERVAL).text("Update every N frames"));
+
+                let size = egui::vec2(PREVIEW_SIZE[0] as f32, PREVIEW_SIZE[1] as f32);
+                match self.texture_id {
+                    Some(texture_id) => {
+                        ui.image(egui::load::SizedTexture::new(texture_id, size));
+                    }
+                    None => {
+                        ui.allocate_ui(size, |ui| ui.spinner());
+                    }
+                }
+            });
+
+        self.is_open = is_open;
+    }
+
+    pub fn update(&mut self, entities: &EntityStore, renderer: &Renderer) {
+        let interval = self.is_open.then_some(self.interval);
+        if interval != self.sent_interval {
+            renderer.send_command(RenderCommand::SetPreview(interval)).unwrap();
+            self.sent_interval = interval;
+            if interval.is_none() {
+                self.texture_id = None;
+            }
+        }
+
+        if !self.is_open {
+            return;
+        }
+
+        // A removed light falls back to the camera
+        let camera = match self.source {
+            PreviewSource::Light(id) => match entities.get(&id) {
+                Some(entity) => light_camera(entity.transform()),
+                None => {
+                    self.source = PreviewSource::Camera;
+                    self.camera.clone()
+                }
+            },
+            PreviewSource::Camera => self.camera.clone(),
+        };
+
+        renderer
+            .send_command(RenderCommand::UpdatePreviewCamera {
+                position: camera.position(),
+                view: camera.view_matrix(),
+                projection: self.projection.matrix(),
+            })
+            .unwrap();
+    }
+}
+
+fn light_entities(entities: &EntityStore) -> Vec<&Entity> {
+    let mut lights = entities.find_by_tag("light").collect::<Vec<_>>();
+    lights.sort_by_key(|entity| entity.label().clone());
+    lights
+}
+
+// Point lights carry no orientation and look at the origin, the others along their -Z axis
+fn light_camera(transform: glam::Mat4) -> Camera {
+    let position = transform.w_axis.truncate();
+    if transform.z_axis == glam::Vec4::Z {
+        Camera::look_at(position, glam::Vec3::ZERO)
+    } else {
+        Camera::look_at(position, position - transform.z_axis.truncate())
+    }
+}
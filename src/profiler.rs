@@ -0,0 +1,346 @@
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use instant::Instant;
+
+// Scoped CPU timings from every thread, grouped into frames by the app thread. Disabled scopes cost one atomic load
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PROFILER: Mutex<Profiler> = Mutex::new(Profiler::new());
+
+thread_local! {
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiler::scope($name);
+    };
+}
+
+#[derive(Clone, Debug)]
+pub struct ScopeRecord {
+    pub name: &'static str,
+    pub thread: String,
+    pub depth: u32,
+    pub start: Instant,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProfileFrame {
+    pub start: Instant,
+    pub duration: Duration,
+    pub scopes: Vec<ScopeRecord>,
+}
+
+struct Profiler {
+    frame_start: Option<Instant>,
+    current: Vec<ScopeRecord>,
+    frames: VecDeque<ProfileFrame>,
+}
+
+impl Profiler {
+    const HISTORY: usize = 120;
+
+    const fn new() -> Self {
+        Self {
+            frame_start: None,
+            current: Vec::new(),
+            frames: VecDeque::new(),
+        }
+    }
+}
+
+pub struct ScopeGuard {
+    name: &'static str,
+    depth: u32,
+    start: Instant,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(self.depth));
+        let record = ScopeRecord {
+            name: self.name,
+            thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+            depth: self.depth,
+            start: self.start,
+            duration: self.start.elapsed(),
+        };
+
+        if let Ok(mut profiler) = PROFILER.lock() {
+            profiler.current.push(record);
+        }
+    }
+}
+
+pub fn scope(name: &'static str) -> Option<ScopeGuard> {
+    if !is_enabled() {
+        return None;
+    }
+
+    let depth = DEPTH.with(|depth| depth.replace(depth.get() + 1));
+    Some(ScopeGuard {
+        name,
+        depth,
+        start: Instant::now(),
+    })
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+    if !enabled && let Ok(mut profiler) = PROFILER.lock() {
+        *profiler = Profiler::new();
+    }
+}
+
+// Called once per frame on the app thread, scopes that ended since the previous call belong to the closed frame
+pub fn new_frame() {
+    if !is_enabled() {
+        return;
+    }
+
+    let Ok(mut profiler) = PROFILER.lock() else {
+        return;
+    };
+
+    let now = Instant::now();
+    if let Some(start) = profiler.frame_start {
+        let scopes = std::mem::take(&mut profiler.current);
+        profiler.frames.push_back(ProfileFrame {
+            start,
+            duration: now - start,
+            scopes,
+        });
+
+        while profiler.frames.len() > Profiler::HISTORY {
+            profiler.frames.pop_front();
+        }
+    }
+
+    profiler.frame_start = Some(now);
+}
+
+pub fn frames() -> Vec<ProfileFrame> {
+    PROFILER
+        .lock()
+        .map(|profiler| profiler.frames.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub struct ProfilerWindow {
+    pub is_open: bool,
+    is_paused: bool,
+    selected: Option<usize>,
+    frames: Vec<ProfileFrame>,
+}
+
+impl ProfilerWindow {
+    const ROW_HEIGHT: f32 = 18.0;
+
+    pub fn new() -> Self {
+        Self {
+            is_open: false,
+            is_paused: false,
+            selected: None,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        set_enabled(self.is_open);
+        if !self.is_open {
+            return;
+        }
+
+        if !self.is_paused {
+            self.frames = frames();
+            self.selected = None;
+        }
+
+        let mut is_open = self.is_open;
+        egui::Window::new("Profiler")
+            .open(&mut is_open)
+            .resizable(true)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.is_paused, "Pause");
+                    if let Some(frame) = self.frame() {
+                        ui.label(format!("Frame: {:.2} ms", frame.duration.as_secs_f64() * 1000.0));
+                    }
+                });
+
+                self.frame_history(ui);
+                ui.separator();
+
+                let Some(frame) = self.frame().cloned() else {
+                    ui.label("Waiting for frames");
+                    return;
+                };
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    flame_graph(ui, &frame);
+                });
+
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .id_salt("profiler_totals")
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        scope_totals(ui, &frame);
+                    });
+            });
+
+        self.is_open = is_open;
+    }
+
+    fn frame(&self) -> Option<&ProfileFrame> {
+        self.selected
+            .and_then(|index| self.frames.get(index))
+            .or_else(|| self.frames.last())
+    }
+
+    // Bar per frame, clicking one pauses on it
+    fn frame_history(&mut self, ui: &mut egui::Ui) {
+        let longest = self
+            .frames
+            .iter()
+            .map(|frame| frame.duration.as_secs_f32())
+            .fold(1.0 / 60.0, f32::max);
+        let (rect, response) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 40.0), egui::Sense::click());
+        let width = rect.width() / Profiler::HISTORY as f32;
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            let height = frame.duration.as_secs_f32() / longest * rect.height();
+            let x = rect.left() + index as f32 * width;
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(x, rect.bottom() - height),
+                egui::pos2(x + width - 1.0, rect.bottom()),
+            );
+            let color = if Some(index) == self.selected {
+                ui.visuals().selection.bg_fill
+            } else {
+                ui.visuals().widgets.inactive.bg_fill
+            };
+            ui.painter().rect_filled(bar, 0.0, color);
+        }
+
+        if response.clicked()
+            && let Some(position) = response.interact_pointer_pos()
+        {
+            let index = ((position.x - rect.left()) / width) as usize;
+            if index < self.frames.len() {
+                self.selected = Some(index);
+                self.is_paused = true;
+            }
+        }
+    }
+}
+
+// One lane per thread, nested scopes stack downwards
+fn flame_graph(ui: &mut egui::Ui, frame: &ProfileFrame) {
+    let mut threads = BTreeMap::<&str, Vec<&ScopeRecord>>::new();
+    for scope in &frame.scopes {
+        threads.entry(&scope.thread).or_default().push(scope);
+    }
+
+    let frame_seconds = frame.duration.as_secs_f32().max(f32::EPSILON);
+    for (thread, scopes) in threads {
+        ui.label(thread);
+
+        let depth = scopes.iter().map(|scope| scope.depth).max().unwrap_or(0) + 1;
+        let (rect, response) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), depth as f32 * ProfilerWindow::ROW_HEIGHT),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter_at(rect);
+        let hovered = response.hover_pos();
+
+        for scope in scopes {
+            // Scopes that started in an earlier frame are clamped to its start
+            let offset = if scope.start > frame.start {
+                scope.start - frame.start
+            } else {
+                Duration::ZERO
+            };
+            let start = offset.as_secs_f32() / frame_seconds;
+            let end = start + scope.duration.as_secs_f32() / frame_seconds;
+            let top = rect.top() + scope.depth as f32 * ProfilerWindow::ROW_HEIGHT;
+            let bar = egui::Rect::from_min_max(
+                egui::pos2(rect.left() + start * rect.width(), top),
+                egui::pos2(
+                    (rect.left() + end * rect.width()).max(rect.left() + start * rect.width() + 1.0),
+                    top + ProfilerWindow::ROW_HEIGHT - 1.0,
+                ),
+            );
+
+            painter.rect_filled(bar, 2.0, scope_color(scope.name));
+
+            if bar.width() > 40.0 {
+                painter.text(
+                    bar.left_center() + egui::vec2(3.0, 0.0),
+                    egui::Align2::LEFT_CENTER,
+                    scope.name,
+                    egui::FontId::monospace(11.0),
+                    egui::Color32::WHITE,
+                );
+            }
+
+            if hovered.is_some_and(|position| bar.contains(position)) {
+                response.clone().on_hover_text(format!(
+                    "{}: {:.3} ms",
+                    scope.name,
+                    scope.duration.as_secs_f64() * 1000.0
+                ));
+            }
+        }
+    }
+}
+
+// Stable color per scope name
+fn scope_color(name: &str) -> egui::Color32 {
+    let hash = name
+        .bytes()
+        .fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32));
+    let hue = (hash % 360) as f32 / 360.0;
+    egui::Color32::from(egui::ecolor::Hsva::new(hue, 0.5, 0.6, 1.0))
+}
+
+fn scope_totals(ui: &mut egui::Ui, frame: &ProfileFrame) {
+    let mut totals = BTreeMap::<&str, (Duration, u32)>::new();
+    for scope in &frame.scopes {
+        let (duration, count) = totals.entry(scope.name).or_default();
+        *duration += scope.duration;
+        *count += 1;
+    }
+
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_by(|a, b| b.1.0.cmp(&a.1.0));
+
+    egui::Grid::new("profiler_totals_grid").striped(true).show(ui, |ui| {
+        ui.strong("Scope");
+        ui.strong("Total");
+        ui.strong("Calls");
+        ui.end_row();
+
+        for (name, (duration, count)) in totals {
+            ui.monospace(name);
+            ui.monospace(format!("{:.3} ms", duration.as_secs_f64() * 1000.0));
+            ui.monospace(count.to_string());
+            ui.end_row();
+        }
+    });
+}
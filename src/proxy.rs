@@ -0,0 +1,62 @@
+use uuid::Uuid;
+
+use crate::{entity::EntityId, renderer::Aabb};
+
+struct Proxy {
+    label: String,
+    entity_id: EntityId,
+}
+
+// Shows where an import will land right away: the built-in cube, ghosted on the helper layer, grown to the file's
+// bounds once the loader has read its header. The first node of the asset is spawned under the proxy's entity id, so
+// a selection made in the meantime carries over. Imports that fail leave their proxy behind
+pub struct LoadingProxies {
+    proxies: Vec<Proxy>,
+    // Nodes of one asset arrive one after the other, only the first takes over a proxy
+    last_asset: Option<Uuid>,
+}
+
+impl LoadingProxies {
+    // Until the loader knows better
+    pub const DEFAULT_BOUNDS: Aabb = Aabb {
+        min: glam::Vec3::splat(-0.5),
+        max: glam::Vec3::splat(0.5),
+    };
+
+    pub fn new() -> Self {
+        Self {
+            proxies: Vec::new(),
+            last_asset: None,
+        }
+    }
+
+    pub fn insert(&mut self, label: String, entity_id: EntityId) {
+        self.proxies.push(Proxy { label, entity_id });
+    }
+
+    pub fn find(&self, label: &str) -> Option<EntityId> {
+        self.proxies
+            .iter()
+            .find(|proxy| proxy.label == label)
+            .map(|proxy| proxy.entity_id)
+    }
+
+    pub fn contains(&self, entity_id: EntityId) -> bool {
+        self.proxies.iter().any(|proxy| proxy.entity_id == entity_id)
+    }
+
+    pub fn take(&mut self, label: &str, asset_id: Uuid) -> Option<EntityId> {
+        if self.last_asset.replace(asset_id) == Some(asset_id) {
+            return None;
+        }
+
+        let index = self.proxies.iter().position(|proxy| proxy.label == label)?;
+        Some(self.proxies.remove(index).entity_id)
+    }
+
+    // Scales the two unit wide cube over the bounds
+    pub fn transform(bounds: &Aabb) -> glam::Mat4 {
+        let size = (bounds.max - bounds.min).max(glam::Vec3::splat(1e-3));
+        glam::Mat4::from_scale_rotation_translation(size * 0.5, glam::Quat::IDENTITY, bounds.centroid())
+    }
+}
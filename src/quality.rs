@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use instant::Instant;
+
+use crate::renderer::QualityPreset;
+
+// Steps the quality preset down when frames run long and back up while there is headroom, never past what the
+// adapter was rated for
+pub struct AutoQuality {
+    ceiling: QualityPreset,
+    adapter_quality: QualityPreset,
+    changed_at: Instant,
+}
+
+impl AutoQuality {
+    // Frame rate has to settle after a change before it says anything about the new preset
+    const SETTLE_TIME: Duration = Duration::from_secs(3);
+    const DEMOTE_FPS: f32 = 30.0;
+    const PROMOTE_FPS: f32 = 55.0;
+
+    pub fn new(adapter_quality: QualityPreset) -> Self {
+        Self {
+            ceiling: adapter_quality,
+            adapter_quality,
+            changed_at: Instant::now(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.ceiling = self.adapter_quality;
+        self.changed_at = Instant::now();
+    }
+
+    pub fn update(&mut self, fps: f32, current: Option<QualityPreset>) -> Option<QualityPreset> {
+        let Some(current) = current else {
+            self.changed_at = Instant::now();
+            return Some(self.ceiling);
+        };
+
+        if self.changed_at.elapsed() < Self::SETTLE_TIME {
+            return None;
+        }
+
+        // A preset that was too slow once stays out of reach, otherwise the two neighbours keep trading places
+        let next = if fps < Self::DEMOTE_FPS {
+            let lower = current.lower()?;
+            self.ceiling = lower;
+            lower
+        } else if fps > Self::PROMOTE_FPS && current < self.ceiling {
+            current.higher()?
+        } else {
+            return None;
+        };
+
+        self.changed_at = Instant::now();
+        Some(next)
+    }
+}
@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{camera::Camera, renderer::RenderSettings, turntable::TurntableSettings, watch::FileWatcher};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum RecordedEvent {
+    Camera {
+        position: glam::Vec3,
+        orientation: glam::Quat,
+    },
+    Settings(RenderSettings),
+    Turntable(TurntableSettings),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RecordedFrame {
+    // Seconds since the recording started
+    time: f32,
+    event: RecordedEvent,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    Recording,
+    Replaying,
+}
+
+#[derive(Default)]
+pub struct ReplayFrame {
+    pub camera: Option<Camera>,
+    pub settings: Option<RenderSettings>,
+    pub turntable: Option<TurntableSettings>,
+}
+
+// Camera poses and settings changes on a timeline, replayed against wall clock time so a run looks the same
+// regardless of frame rate. Kept in recording.json so a run can be shared along with a bug report
+pub struct InputRecorder {
+    file: FileWatcher,
+    phase: Phase,
+    frames: Vec<RecordedFrame>,
+    elapsed: Duration,
+    // Next frame to replay
+    cursor: usize,
+    last_camera: Option<(glam::Vec3, glam::Quat)>,
+    last_settings: Option<RenderSettings>,
+    last_turntable: Option<TurntableSettings>,
+}
+
+impl InputRecorder {
+    pub fn new(path: &str) -> Self {
+        Self {
+            file: FileWatcher::new(path),
+            phase: Phase::Idle,
+            frames: Vec::new(),
+            elapsed: Duration::ZERO,
+            cursor: 0,
+            last_camera: None,
+            last_settings: None,
+            last_turntable: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.phase == Phase::Recording
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.phase == Phase::Replaying
+    }
+
+    pub fn duration(&self) -> f32 {
+        match self.phase {
+            Phase::Recording => self.elapsed.as_secs_f32(),
+            _ => self.frames.last().map_or(0.0, |frame| frame.time),
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.elapsed.as_secs_f32() / self.duration().max(f32::EPSILON)
+    }
+
+    pub fn start_recording(&mut self) {
+        self.phase = Phase::Recording;
+        self.frames.clear();
+        self.elapsed = Duration::ZERO;
+        self.last_camera = None;
+        self.last_settings = None;
+        self.last_turntable = None;
+    }
+
+    // Falls back to the file when nothing was recorded this session
+    pub fn start_replay(&mut self) -> anyhow::Result<()> {
+        if self.frames.is_empty() {
+            let Some(contents) = self.file.read() else {
+                anyhow::bail!("No recording in {}", self.file.path().display());
+            };
+            self.frames = serde_json::from_str(&contents)?;
+        }
+
+        self.phase = Phase::Replaying;
+        self.elapsed = Duration::ZERO;
+        self.cursor = 0;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if self.phase == Phase::Recording {
+            let result = serde_json::to_string(&self.frames)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| self.file.write(&contents));
+
+            if let Err(error) = result {
+                log::error!("Unable to write {}: {}", self.file.path().display(), error);
+            }
+        }
+
+        self.phase = Phase::Idle;
+    }
+
+    // Records what changed since the last frame, or returns what the replay applies this frame
+    pub fn update(
+        &mut self,
+        timestep: Duration,
+        camera: &Camera,
+        settings: &RenderSettings,
+        turntable: &TurntableSettings,
+    ) -> Option<ReplayFrame> {
+        match self.phase {
+            Phase::Idle => None,
+            Phase::Recording => {
+                self.elapsed += timestep;
+                self.record(camera, settings, turntable);
+                None
+            }
+            Phase::Replaying => {
+                self.elapsed += timestep;
+                let frame = self.replay();
+                if self.cursor >= self.frames.len() {
+                    self.phase = Phase::Idle;
+                }
+                Some(frame)
+            }
+        }
+    }
+
+    fn record(&mut self, camera: &Camera, settings: &RenderSettings, turntable: &TurntableSettings) {
+        let time = self.elapsed.as_secs_f32();
+
+        let pose = (camera.position(), camera.orientation());
+        if self.last_camera != Some(pose) {
+            self.last_camera = Some(pose);
+            self.frames.push(RecordedFrame {
+                time,
+                event: RecordedEvent::Camera {
+                    position: pose.0,
+                    orientation: pose.1,
+                },
+            });
+        }
+
+        if self.last_settings.as_ref() != Some(settings) {
+            self.last_settings = Some(settings.clone());
+            self.frames.push(RecordedFrame {
+                time,
+                event: RecordedEvent::Settings(settings.clone()),
+            });
+        }
+
+        if self.last_turntable.as_ref() != Some(turntable) {
+            self.last_turntable = Some(*turntable);
+            self.frames.push(RecordedFrame {
+                time,
+                event: RecordedEvent::Turntable(*turntable),
+            });
+        }
+    }
+
+    fn replay(&mut self) -> ReplayFrame {
+        let time = self.elapsed.as_secs_f32();
+        let mut frame = ReplayFrame::default();
+
+        while let Some(recorded) = self.frames.get(self.cursor)
+            && recorded.time <= time
+        {
+            match &recorded.event {
+                RecordedEvent::Settings(settings) => frame.settings = Some(settings.clone()),
+                RecordedEvent::Turntable(turntable) => frame.turntable = Some(*turntable),
+                RecordedEvent::Camera { .. } => {}
+            }
+            self.cursor += 1;
+        }
+
+        let pose = |recorded: &RecordedFrame| match recorded.event {
+            RecordedEvent::Camera { position, orientation } => Some((recorded.time, position, orientation)),
+            RecordedEvent::Settings(_) | RecordedEvent::Turntable(_) => None,
+        };
+        let previous = self.frames[..self.cursor].iter().rev().find_map(pose);
+        let next = self.frames[self.cursor..].iter().find_map(pose);
+
+        // Interpolated between the surrounding poses, the recording rarely lines up with the replay's frames
+        frame.camera = match (previous, next) {
+            (Some((start, position, orientation)), Some((end, next_position, next_orientation))) => {
+                let t = ((time - start) / (end - start).max(f32::EPSILON)).clamp(0.0, 1.0);
+                Some(Camera::from_orientation(
+                    position.lerp(next_position, t),
+                    orientation.slerp(next_orientation, t),
+                ))
+            }
+            (Some((_, position, orientation)), None) => Some(Camera::from_orientation(position, orientation)),
+            (None, _) => None,
+        };
+
+        frame
+    }
+}
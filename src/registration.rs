@@ -0,0 +1,151 @@
+use crossbeam::channel::{Receiver, Sender};
+
+use crate::{
+    entity::EntityId,
+    renderer::{AssetLoader, PointHit, Registration, SceneQuery},
+};
+
+// Registers a moving scan onto a reference scan: a coarse fit from point pairs picked in the viewport, refined with
+// ICP in the background. Only the moving scan's transform changes
+pub struct ScanRegistration {
+    pub reference: Option<EntityId>,
+    pub moving: Option<EntityId>,
+    pub is_picking: bool,
+    // World units, matches further apart are treated as outliers
+    pub max_distance: f32,
+    // World positions on the moving and the reference scan
+    pairs: Vec<(glam::Vec3, glam::Vec3)>,
+    pending: Option<glam::Vec3>,
+    result: Option<Registration>,
+    // None when the task was cancelled, Some(None) when ICP found too few matches
+    result_tx: Sender<(EntityId, Option<Option<Registration>>)>,
+    result_rx: Receiver<(EntityId, Option<Option<Registration>>)>,
+    is_refining: bool,
+}
+
+impl ScanRegistration {
+    pub const PAIRS: usize = 3;
+
+    pub fn new() -> Self {
+        let (result_tx, result_rx) = crossbeam::channel::unbounded();
+        Self {
+            reference: None,
+            moving: None,
+            is_picking: false,
+            max_distance: 0.5,
+            pairs: Vec::new(),
+            pending: None,
+            result: None,
+            result_tx,
+            result_rx,
+            is_refining: false,
+        }
+    }
+
+    pub fn pair_count(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_waiting_for_reference(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn is_refining(&self) -> bool {
+        self.is_refining
+    }
+
+    pub fn result(&self) -> Option<&Registration> {
+        self.result.as_ref()
+    }
+
+    pub fn clear_pairs(&mut self) {
+        self.pairs.clear();
+        self.pending = None;
+    }
+
+    // A point on the moving scan followed by the matching one on the reference makes a pair. Returns whether the
+    // point was used, picking stops by itself once there are enough pairs
+    pub fn pick(&mut self, point: &PointHit) -> bool {
+        if !self.is_picking {
+            return false;
+        }
+
+        if Some(point.entity_id) == self.moving {
+            self.pending = Some(point.position);
+        } else if Some(point.entity_id) == self.reference
+            && let Some(moving) = self.pending.take()
+        {
+            self.pairs.push((moving, point.position));
+            self.is_picking = self.pairs.len() < Self::PAIRS;
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    // New transform of the moving scan that lines up the picked pairs, which are used up by it
+    pub fn align(&mut self, moving_transform: glam::Mat4) -> Option<(EntityId, glam::Mat4)> {
+        let moving = self.moving?;
+        let correction = Registration::from_pairs(&self.pairs)?;
+        self.clear_pairs();
+        Some((moving, correction * moving_transform))
+    }
+
+    pub fn refine(&mut self, query: &SceneQuery, loader: &AssetLoader) {
+        let (Some(reference), Some(moving)) = (self.reference, self.moving) else {
+            return;
+        };
+        let (Some((target, target_transform)), Some((source, source_transform))) =
+            (query.pointcloud(reference), query.pointcloud(moving))
+        else {
+            return;
+        };
+
+        let max_distance = self.max_distance;
+        let result_tx = self.result_tx.clone();
+        self.is_refining = true;
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let task = loader.tasks().start("Registering scans");
+            std::thread::spawn(move || {
+                let (source_positions, target_positions) =
+                    Registration::samples(&source, source_transform, &target, target_transform);
+                let registration = Registration::refine(
+                    &source_positions,
+                    &target_positions,
+                    source_transform,
+                    max_distance,
+                    || task.is_cancelled(),
+                );
+                result_tx
+                    .send((moving, (!task.is_cancelled()).then_some(registration)))
+                    .ok();
+            });
+        }
+        // Sampling is cheap enough for the page, the ICP iterations go to a worker
+        #[cfg(target_family = "wasm")]
+        loader.refine_registration(
+            Registration::samples(&source, source_transform, &target, target_transform),
+            source_transform,
+            max_distance,
+            move |registration| {
+                result_tx.send((moving, registration)).ok();
+            },
+        );
+    }
+
+    // The moving scan and its refined transform, once the background task is done
+    pub fn poll(&mut self) -> Option<(EntityId, glam::Mat4)> {
+        let (moving, registration) = self.result_rx.try_recv().ok()?;
+        self.is_refining = false;
+        let registration = registration?;
+        self.result = registration;
+        if registration.is_none() {
+            log::warn!("ICP found too few matches, align the scans more closely or raise the maximum distance");
+        }
+
+        registration.map(|registration| (moving, registration.transform))
+    }
+}
@@ -0,0 +1,92 @@
+use uuid::Uuid;
+
+use crate::{
+    renderer::{AssetKind, AssetLoader, ImportOptions, LoadRequest, RenderId, ResourcePath},
+    watch::FileWatcher,
+};
+
+struct WatchedAsset {
+    path: ResourcePath,
+    options: ImportOptions,
+    watcher: FileWatcher,
+    // Environment maps and volumes have no nodes to wait for
+    has_nodes: bool,
+    // None until the first node arrives
+    asset_id: Option<Uuid>,
+    render_ids: Vec<RenderId>,
+}
+
+// Imports loaded files again when they change on disk, so exports from a modelling tool show up by themselves.
+// The geometry and materials behind the render ids are swapped, entities keep their transforms
+pub struct AssetReloader {
+    assets: Vec<WatchedAsset>,
+}
+
+impl AssetReloader {
+    pub fn new() -> Self {
+        Self { assets: Vec::new() }
+    }
+
+    // Files from a URL can't be watched
+    pub fn watch(&mut self, request: LoadRequest) {
+        let Some(local_path) = request.path.local_path() else {
+            return;
+        };
+        let has_nodes = request
+            .path
+            .extension()
+            .as_deref()
+            .and_then(AssetKind::from_extension)
+            .is_some_and(|kind| kind.adds_nodes());
+
+        self.assets.push(WatchedAsset {
+            path: request.path,
+            options: request.options,
+            watcher: FileWatcher::from_current(local_path),
+            has_nodes,
+            asset_id: None,
+            render_ids: Vec::new(),
+        });
+    }
+
+    // For every LoadComplete, nodes of one asset arrive in order
+    pub fn register_node(&mut self, asset_id: Uuid, render_id: RenderId, label: Option<&str>) {
+        let asset = match self.assets.iter().position(|asset| asset.asset_id == Some(asset_id)) {
+            Some(index) => &mut self.assets[index],
+            None => {
+                let Some(asset) = self.assets.iter_mut().find(|asset| {
+                    asset.has_nodes && asset.asset_id.is_none() && Some(asset.path.file_name().as_ref()) == label
+                }) else {
+                    return;
+                };
+                asset.asset_id = Some(asset_id);
+                asset
+            }
+        };
+
+        asset.render_ids.push(render_id);
+    }
+
+    pub fn unwatch(&mut self, asset_id: Uuid) {
+        self.assets.retain(|asset| asset.asset_id != Some(asset_id));
+    }
+
+    pub fn poll(&mut self, loader: &AssetLoader) {
+        for asset in &mut self.assets {
+            // Still loading, a change made meanwhile is picked up once its nodes are in
+            if asset.has_nodes && asset.asset_id.is_none() {
+                continue;
+            }
+
+            if asset.watcher.has_changed() {
+                log::info!("Reloading {}", asset.path);
+                loader.reload(
+                    asset.path.clone(),
+                    asset.options.clone(),
+                    asset.asset_id.unwrap_or_default(),
+                    asset.render_ids.clone(),
+                );
+            }
+        }
+    }
+}
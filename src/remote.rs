@@ -0,0 +1,124 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::PathBuf,
+};
+
+use crossbeam::channel::{Receiver, Sender};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+// Method names and params of the JSON-RPC requests, one per line:
+// `{"jsonrpc": "2.0", "id": 1, "method": "loadAsset", "params": {"path": "scan.laz"}}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", content = "params", rename_all = "camelCase")]
+pub enum RemoteRequest {
+    LoadAsset { path: String },
+    ListEntities,
+    // Entities matching every given filter, all of them without any
+    FindEntities { label: Option<String>, tag: Option<String> },
+    // Outlines the entities matching every given filter, clears the outline without any
+    HighlightEntities { label: Option<String>, tag: Option<String> },
+    // Column major, like glam
+    SetTransform { entity: String, transform: [f32; 16] },
+    SetCamera { position: [f32; 3], target: [f32; 3] },
+    // Answered once the PNG is written
+    CaptureFrame { path: PathBuf },
+}
+
+pub struct RemoteCall {
+    pub request: RemoteRequest,
+    reply: Sender<Result<Value, String>>,
+}
+
+impl RemoteCall {
+    pub fn reply(self, result: Result<Value, String>) {
+        let _ = self.reply.send(result);
+    }
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    request: RemoteRequest,
+}
+
+// `--remote <port>`. Only listens on localhost, there's no authentication
+pub struct RemoteServer {
+    call_rx: Receiver<RemoteCall>,
+}
+
+impl RemoteServer {
+    pub fn from_args() -> Option<Self> {
+        let args = std::env::args().collect::<Vec<_>>();
+        let port = args
+            .iter()
+            .position(|arg| arg == "--remote")
+            .and_then(|index| args.get(index + 1))?
+            .parse()
+            .ok()?;
+
+        match Self::listen(port) {
+            Ok(server) => Some(server),
+            Err(error) => {
+                log::error!("Unable to start remote control server: {}", error);
+                None
+            }
+        }
+    }
+
+    fn listen(port: u16) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        log::info!("Remote control listening on {}", listener.local_addr()?);
+
+        let (call_tx, call_rx) = crossbeam::channel::unbounded();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let call_tx = call_tx.clone();
+                std::thread::spawn(move || {
+                    if let Err(error) = serve(stream, call_tx) {
+                        log::warn!("Remote control connection closed: {}", error);
+                    }
+                });
+            }
+        });
+
+        Ok(Self { call_rx })
+    }
+
+    pub fn poll(&self) -> Vec<RemoteCall> {
+        self.call_rx.try_iter().collect()
+    }
+}
+
+fn serve(stream: TcpStream, call_tx: Sender<RemoteCall>) -> anyhow::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Envelope>(&line) {
+            Ok(Envelope { id, request }) => {
+                let (reply, reply_rx) = crossbeam::channel::bounded(1);
+                call_tx.send(RemoteCall { request, reply })?;
+                match reply_rx.recv()? {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err(message) => error_response(id, -32000, &message),
+                }
+            }
+            Err(error) => error_response(Value::Null, -32600, &error.to_string()),
+        };
+
+        writeln!(writer, "{}", response)?;
+    }
+
+    Ok(())
+}
+
+fn error_response(id: Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
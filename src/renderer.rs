@@ -1,6 +1,5 @@
 use std::{sync::Arc, time::Duration};
 
-use crossbeam::channel::Sender;
 use uuid::Uuid;
 use winit::{event_loop::ActiveEventLoop, window::Window};
 
@@ -9,34 +8,101 @@ use crate::{
     // ui::{Ui, UiData},
 };
 
+#[cfg(not(target_family = "wasm"))]
+pub use asset::LoadRequest;
 pub use {
-    asset::{AssetKind, AssetLoader, ResourcePath},
-    light::Light,
-    scene::RenderId,
-    ui::Ui,
+    animation::AnimationPhase,
+    aov::AovImages,
+    asset::{AssetKind, AssetLoader, AssetStats, ImportMode, ImportOptions, ImportReport, ResourcePath},
+    bvh::Aabb,
+    change::{DistanceReference, PointDistances},
+    client::RendererClient,
+    graph::{FrameGraphLayout, SlotResource},
+    irradiance_volume::IrradianceGrid,
+    layers::RenderLayers,
+    light::{Light, LightKind},
+    lightmap::MAX_LIGHTMAP_RESOLUTION,
+    memory::MemoryUsage,
+    mesh::NodeMetadata,
+    pointcloud::PointcloudBuffer,
+    preview::PREVIEW_SIZE,
+    probe::MAX_PROBES,
+    quantize::VertexPrecision,
+    query::{PointHit, SceneHit, SceneQuery},
+    ramp::{ColorRamp, RampStop},
+    ray::{Ray, SurfaceHit},
+    readback::{InspectedBuffer, MAX_SCREENSHOT_TILES},
+    registration::Registration,
+    scene::{EntityTags, RenderId, TagFilter, Visibility},
+    scheduler::TaskPriority,
+    settings::{
+        BackgroundMode, EnvironmentSampling, ParallaxQuality, PhysicalCamera, PointcloudShading, QualityPreset,
+        RenderMode, RenderSettings,
+    },
+    task::TaskHandle,
+    ui::{Ui, UiStyle, UiTheme},
+    viewport::ViewportId,
+    volume::{TransferFunction, TransferPoint},
 };
 
+mod accumulation;
+mod animation;
+mod aov;
 mod asset;
+mod atlas;
 mod backend;
 mod binary;
+mod bvh;
 mod camera;
+mod change;
+mod client;
 mod component;
 mod context;
 mod core;
 mod environment;
+mod frustum;
+mod ghost;
+#[cfg(all(test, not(target_family = "wasm")))]
+mod golden;
+mod graph;
 mod hdr;
 mod instance;
+mod irradiance_volume;
+mod layers;
 mod light;
+mod lightmap;
 mod material;
+mod memory;
 mod mesh;
+mod ods;
+mod outline;
+mod path_tracer;
+mod pick;
 mod pipeline;
 mod pointcloud;
+mod preview;
+mod probe;
+mod quantize;
+mod query;
+mod ramp;
+mod ray;
+mod readback;
+mod registration;
 mod scene;
+mod scheduler;
+mod settings;
+mod shadow;
+mod sketch;
+mod splatting;
 mod surface;
+mod task;
 mod texture;
+mod timer;
 mod transform;
 mod ui;
 mod vertex;
+mod viewport;
+mod volume;
 #[cfg(target_family = "wasm")]
 mod worker;
 
@@ -52,11 +118,22 @@ pub enum RenderCommand {
     },
     Resize(wgpu::SurfaceConfiguration),
     LoadAsset(AssetBuffer),
+    // Swaps the geometry and materials of an earlier load node by node, entities keep their render ids. Nodes the
+    // new file adds arrive as a LoadComplete of the same asset, nodes it lost keep their old geometry
+    ReloadAsset {
+        asset_id: Uuid,
+        render_ids: Vec<RenderId>,
+        asset: AssetBuffer,
+    },
     SpawnAsset {
         entity_id: Uuid,
         render_id: RenderId,
         transform: glam::Mat4,
     },
+    // Removes the entity's node, its renderable stays loaded for the other entities drawing it
+    DespawnAsset(Uuid),
+    // Removes the renderables of an asset with every entity's node drawing them and frees their geometry and materials
+    UnloadAsset(Vec<RenderId>),
     SpawnLight {
         entity_id: Uuid,
         light: Light,
@@ -65,48 +142,317 @@ pub enum RenderCommand {
         entity_id: Uuid,
         transform: glam::Mat4,
     },
+    // Replaces both the light's parameters and its transform
     UpdateLight {
         entity_id: Uuid,
-        kind: u32,
-        color: glam::Vec3,
-        intensity: f32,
-        cutoff: f32,
+        light: Light,
+    },
+    UpdateSettings(RenderSettings),
+    UpdateSketch(String),
+    UpdateCursor(glam::Vec2),
+    InspectBuffer(InspectedBuffer),
+    // Answered with a Screenshot or ScreenshotFailed event carrying the same id once the pixels are read back
+    CaptureScreenshot {
+        id: u64,
+    },
+    // Same, at `tiles` times the size along both axes. Rendered tile by tile from the camera it carries
+    CaptureTiledScreenshot {
+        id: u64,
+        tiles: u32,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+    },
+    // Depth, world normals and entity ids of the current view at the surface size, answered with an Aovs event
+    CaptureAovs,
+    // The entity drawn at a surface pixel from the top left, answered with a PickResult event
+    Pick {
+        x: u32,
+        y: u32,
+    },
+    // Over/under stereo panorama around `position`, centered on `yaw`, answered with a Screenshot event. Eye
+    // distance in world units, each of the strips costs two cubemap captures
+    CaptureStereoPanorama {
+        id: u64,
+        position: glam::Vec3,
+        yaw: f32,
+        width: u32,
+        eye_distance: f32,
+        strips: u32,
+    },
+    SetGpuTiming(bool),
+    // Sends a FrameGraph event whenever the main frame's graph changes, while enabled
+    SetFrameGraphReport(bool),
+    UpdateTransferFunction(TransferFunction),
+    // Creates the viewport on first use
+    ResizeViewport {
+        viewport: ViewportId,
+        config: wgpu::SurfaceConfiguration,
+    },
+    UpdateViewportCamera {
+        viewport: ViewportId,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+    },
+    RenderViewport {
+        viewport: ViewportId,
+        view: wgpu::TextureView,
+    },
+    CloseViewport(ViewportId),
+    // Frame interval of the inset preview, None hides it
+    SetPreview(Option<u32>),
+    UpdatePreviewCamera {
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+    },
+    // Entities left out are unchanged
+    SetVisibility(Vec<(Uuid, Visibility)>),
+    SetEntityLayers {
+        entity_id: Uuid,
+        layers: RenderLayers,
+    },
+    // Offsets and scales the time an entity's skin plays its clip at, the default phase plays in step with the rest
+    SetAnimationPhase {
+        entity_id: Uuid,
+        phase: AnimationPhase,
+    },
+    // Draws the renderables with the materials of a material variant, None with their default ones
+    SetMaterialVariant {
+        render_ids: Vec<RenderId>,
+        variant: Option<usize>,
+    },
+    // None is the main window
+    SetViewLayers {
+        viewport: Option<ViewportId>,
+        layers: RenderLayers,
+    },
+    // Mirrors an entity's label and tags, for commands that act on sets of entities
+    UpdateTags {
+        entity_id: Uuid,
+        tags: EntityTags,
+    },
+    // Entity drawn with an outline, None clears it
+    SetSelection(Option<Uuid>),
+    // Outlines every entity matching the filter, None clears it
+    SetHighlight(Option<TagFilter>),
+    // Adds or moves a reflection probe and captures it right away
+    PlaceProbe {
+        probe_id: Uuid,
+        position: glam::Vec3,
+        radius: f32,
+    },
+    CaptureProbes,
+    RemoveProbe(Uuid),
+    // Replaces the irradiance volume and bakes it, None removes it
+    SetIrradianceVolume(Option<IrradianceGrid>),
+    BakeIrradianceVolume,
+    // Bakes progressively over the following frames, replacing the entity's dynamic diffuse lighting once done
+    BakeLightmap {
+        entity_id: Uuid,
+        resolution: u32,
+        samples: u32,
+    },
+    ClearLightmap(Uuid),
+    // Class per point index, applies to every entity spawned from the pointcloud
+    ClassifyPoints {
+        render_id: RenderId,
+        changes: Vec<(u32, u8)>,
+    },
+    // Swaps the points of a loaded pointcloud for another set with the same entities, like recolored copies
+    ReplacePoints {
+        render_id: RenderId,
+        buffer: PointcloudBuffer,
+    },
+    // A chunk of a pointcloud still being decoded, the first one adds the cloud with room for `total` points and the
+    // rest are drawn behind it as they arrive. `progress` is the fraction of the file read so far. The chunk is shared
+    // with the loader, which joins them into the whole cloud
+    StreamPoints {
+        stream_id: Uuid,
+        buffer: Arc<PointcloudBuffer>,
+        total: u32,
+        label: Option<String>,
+        progress: f32,
     },
+    // Swaps the streamed chunks for the whole cloud with its normals, None removes what arrived after a failure or cancel
+    FinishPointStream {
+        stream_id: Uuid,
+        buffer: Option<PointcloudBuffer>,
+    },
+    // Loads held back by the GPU memory budget
+    ForceQueuedLoads,
+    DiscardQueuedLoads,
     Stop,
 }
 
+impl RenderCommand {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::RenderFrame { .. } => "RenderFrame",
+            Self::UpdateCamera { .. } => "UpdateCamera",
+            Self::Resize(_) => "Resize",
+            Self::LoadAsset(_) => "LoadAsset",
+            Self::ReloadAsset { .. } => "ReloadAsset",
+            Self::SpawnAsset { .. } => "SpawnAsset",
+            Self::DespawnAsset(_) => "DespawnAsset",
+            Self::UnloadAsset(_) => "UnloadAsset",
+            Self::SpawnLight { .. } => "SpawnLight",
+            Self::UpdateTransform { .. } => "UpdateTransform",
+            Self::UpdateLight { .. } => "UpdateLight",
+            Self::UpdateSettings(_) => "UpdateSettings",
+            Self::UpdateSketch(_) => "UpdateSketch",
+            Self::UpdateCursor(_) => "UpdateCursor",
+            Self::InspectBuffer(_) => "InspectBuffer",
+            Self::CaptureScreenshot { .. } => "CaptureScreenshot",
+            Self::CaptureTiledScreenshot { .. } => "CaptureTiledScreenshot",
+            Self::CaptureAovs => "CaptureAovs",
+            Self::Pick { .. } => "Pick",
+            Self::CaptureStereoPanorama { .. } => "CaptureStereoPanorama",
+            Self::SetGpuTiming(_) => "SetGpuTiming",
+            Self::SetFrameGraphReport(_) => "SetFrameGraphReport",
+            Self::UpdateTransferFunction(_) => "UpdateTransferFunction",
+            Self::ResizeViewport { .. } => "ResizeViewport",
+            Self::UpdateViewportCamera { .. } => "UpdateViewportCamera",
+            Self::RenderViewport { .. } => "RenderViewport",
+            Self::CloseViewport(_) => "CloseViewport",
+            Self::SetPreview(_) => "SetPreview",
+            Self::UpdatePreviewCamera { .. } => "UpdatePreviewCamera",
+            Self::SetVisibility(_) => "SetVisibility",
+            Self::SetEntityLayers { .. } => "SetEntityLayers",
+            Self::SetAnimationPhase { .. } => "SetAnimationPhase",
+            Self::SetMaterialVariant { .. } => "SetMaterialVariant",
+            Self::SetViewLayers { .. } => "SetViewLayers",
+            Self::UpdateTags { .. } => "UpdateTags",
+            Self::SetSelection(_) => "SetSelection",
+            Self::SetHighlight(_) => "SetHighlight",
+            Self::PlaceProbe { .. } => "PlaceProbe",
+            Self::CaptureProbes => "CaptureProbes",
+            Self::RemoveProbe(_) => "RemoveProbe",
+            Self::SetIrradianceVolume(_) => "SetIrradianceVolume",
+            Self::BakeIrradianceVolume => "BakeIrradianceVolume",
+            Self::BakeLightmap { .. } => "BakeLightmap",
+            Self::ClearLightmap(_) => "ClearLightmap",
+            Self::ClassifyPoints { .. } => "ClassifyPoints",
+            Self::ReplacePoints { .. } => "ReplacePoints",
+            Self::StreamPoints { .. } => "StreamPoints",
+            Self::FinishPointStream { .. } => "FinishPointStream",
+            Self::ForceQueuedLoads => "ForceQueuedLoads",
+            Self::DiscardQueuedLoads => "DiscardQueuedLoads",
+            Self::Stop => "Stop",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum RenderEvent {
     FrameComplete,
     LoadComplete {
         render_id: RenderId,
+        // Shared by every node of one imported file
+        asset_id: Uuid,
         transform: Option<glam::Mat4>,
         label: Option<String>,
+        report: Option<ImportReport>,
+        // Imported with ImportMode::Replace, set on the first node only
+        replaces_scene: bool,
+        stats: AssetStats,
+        metadata: NodeMetadata,
+    },
+    // A node swapped in place by ReloadAsset
+    AssetReloaded {
+        render_id: RenderId,
+        stats: AssetStats,
     },
     ResizeComplete {
         config: wgpu::SurfaceConfiguration,
         device: wgpu::Device,
     },
+    // The main surface now has this size, sent by the backend rather than the render thread
+    SurfaceConfigured {
+        width: u32,
+        height: u32,
+    },
+    BufferContents {
+        buffer: InspectedBuffer,
+        entries: Vec<String>,
+    },
+    // Tone mapped RGBA8 pixels, top row first
+    Screenshot {
+        id: u64,
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+    // Answers a capture whose pixels could not be read back
+    ScreenshotFailed {
+        id: u64,
+        message: String,
+    },
+    Aovs(AovImages),
+    // None when the pixel showed the background
+    PickResult {
+        entity_id: Option<Uuid>,
+    },
+    Error {
+        label: &'static str,
+        message: String,
+    },
+    // Milliseconds per frame graph pass
+    GpuTimings(Vec<(&'static str, f32)>),
+    FrameGraph(FrameGraphLayout),
+    ViewportResized {
+        viewport: ViewportId,
+        config: wgpu::SurfaceConfiguration,
+        device: wgpu::Device,
+    },
+    ViewportFrameComplete(ViewportId),
+    PreviewTexture(egui::TextureId),
+    // Samples baked so far out of the total, the lightmap is applied once they match
+    LightmapProgress {
+        entity_id: Uuid,
+        samples: u32,
+        total: u32,
+    },
+    // Percent of a streamed load read so far, 100 once it's done
+    LoadProgress {
+        label: String,
+        percent: f32,
+    },
+    // A streamed load that failed or was cancelled after its first chunk was added, the asset is removed again
+    LoadAborted {
+        asset_id: Uuid,
+    },
+    // Whether more frames would still change the image, on-demand rendering keeps drawing until this is false
+    Refining(bool),
+    // Sent whenever the estimate or the number of loads waiting for memory changes
+    MemoryUsage {
+        usage: MemoryUsage,
+        queued_loads: usize,
+    },
     Stopped,
 }
 
 pub struct Renderer {
-    render_tx: Sender<RenderCommand>,
+    client: RendererClient,
     backend: Box<dyn RenderBackend>,
+    scene_query: SceneQuery,
+    adapter_info: wgpu::AdapterInfo,
+    adapter_quality: QualityPreset,
 }
 
 impl Renderer {
-    pub async fn new(window: Arc<Window>) -> Self {
+    // `is_uncapped` skips vsync where the surface supports it, for benchmarking
+    pub async fn new(window: Arc<Window>, is_uncapped: bool) -> anyhow::Result<Self> {
         let (render_tx, render_rx) = crossbeam::channel::unbounded();
         let (event_tx, event_rx) = crossbeam::channel::unbounded();
 
-        let (surface, context) = Surface::initialize(Arc::clone(&window))
-            .await
-            .expect("Unable to initialize surface");
+        let (surface, context) = Surface::initialize(Arc::clone(&window), is_uncapped).await?;
+        let adapter_info = context.adapter_info.clone();
+        let adapter_quality = QualityPreset::from_adapter(&adapter_info, &context.device.limits());
 
-        let core = RenderCore::new(context, render_rx, event_tx)
-            .await
-            .expect("Unable to create renderer");
+        let core = RenderCore::new(context, render_rx, event_tx).await?;
+        let scene_query = core.scene_query();
 
         let backend: Box<dyn RenderBackend> = Box::new({
             #[cfg(not(target_family = "wasm"))]
@@ -121,7 +467,13 @@ impl Renderer {
             }
         });
 
-        Self { render_tx, backend }
+        Ok(Self {
+            client: RendererClient::new(render_tx),
+            backend,
+            scene_query,
+            adapter_info,
+            adapter_quality,
+        })
     }
 
     pub fn request_frame(&mut self, window: &Window, ui: Option<UiData>) {
@@ -140,12 +492,55 @@ impl Renderer {
         self.backend.exit();
     }
 
+    pub fn open_viewport(&mut self, window: Arc<Window>) -> Option<ViewportId> {
+        self.backend.open_viewport(window)
+    }
+
+    pub fn close_viewport(&mut self, viewport: ViewportId) {
+        self.backend.close_viewport(viewport);
+    }
+
+    pub fn resize_viewport(&mut self, viewport: ViewportId, width: u32, height: u32) {
+        self.backend.resize_viewport(viewport, width, height);
+    }
+
+    pub fn request_viewport_frame(&mut self, viewport: ViewportId, window: &Window) {
+        self.backend.request_viewport_frame(viewport, window);
+    }
+
+    pub fn update_viewport_camera(
+        &mut self,
+        viewport: ViewportId,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+    ) {
+        self.backend.send_command(RenderCommand::UpdateViewportCamera {
+            viewport,
+            position,
+            view,
+            projection,
+        });
+    }
+
     pub fn is_ready(&self) -> bool {
         self.backend.is_configured()
     }
 
-    pub fn sender(&self) -> Sender<RenderCommand> {
-        self.render_tx.clone()
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    pub fn adapter_quality(&self) -> QualityPreset {
+        self.adapter_quality
+    }
+
+    pub fn scene_query(&self) -> &SceneQuery {
+        &self.scene_query
+    }
+
+    pub fn client(&self) -> &RendererClient {
+        &self.client
     }
 
     pub fn poll_events(&mut self, queue: &mut Vec<RenderEvent>, event_loop: &ActiveEventLoop) -> bool {
@@ -0,0 +1,246 @@
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{context::RenderContext, texture::Texture};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AccumulationUniform {
+    weight: f32,
+    _padding: [u32; 3],
+}
+
+// Temporal accumulation of the HDR image while nothing in the view changes
+pub struct Accumulation {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    history: [Texture; 2],
+    bind_groups: [wgpu::BindGroup; 2],
+    current: usize,
+    frame_count: u32,
+}
+
+impl Accumulation {
+    // Half float history runs out of precision long before this converges any further
+    pub const MAX_FRAMES: u32 = 256;
+
+    pub fn new(context: &RenderContext) -> Self {
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Accumulation layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Accumulation buffer"),
+            contents: bytemuck::bytes_of(&AccumulationUniform {
+                weight: 1.0,
+                _padding: [0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Accumulation shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/accumulate.wgsl").into()),
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Accumulation pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Accumulation pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.hdr.format(),
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let history = Self::create_history(context);
+        let bind_groups = Self::create_bind_groups(&history, &buffer, &layout, context);
+
+        Self {
+            pipeline,
+            layout,
+            buffer,
+            history,
+            bind_groups,
+            current: 0,
+            frame_count: 0,
+        }
+    }
+
+    pub fn resize(&mut self, context: &RenderContext) {
+        self.history = Self::create_history(context);
+        self.bind_groups = Self::create_bind_groups(&self.history, &self.buffer, &self.layout, context);
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.frame_count = 0;
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.frame_count >= Self::MAX_FRAMES
+    }
+
+    pub fn history(&self) -> &[Texture; 2] {
+        &self.history
+    }
+
+    // Blends the freshly rendered HDR image into the history and writes the average back
+    pub fn accumulate(&mut self, encoder: &mut wgpu::CommandEncoder, context: &RenderContext) {
+        if !self.is_converged() {
+            let uniform = AccumulationUniform {
+                weight: 1.0 / (self.frame_count + 1) as f32,
+                _padding: [0; 3],
+            };
+            context
+                .queue
+                .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+
+            let target = 1 - self.current;
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Accumulation render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.history[target].view(),
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, &self.bind_groups[self.current], &[]);
+            render_pass.draw(0..3, 0..1);
+            drop(render_pass);
+
+            self.current = target;
+            self.frame_count += 1;
+        }
+
+        let hdr = context.hdr.texture().texture();
+        encoder.copy_texture_to_texture(
+            self.history[self.current].texture().as_image_copy(),
+            hdr.as_image_copy(),
+            hdr.size(),
+        );
+    }
+
+    fn create_history(context: &RenderContext) -> [Texture; 2] {
+        [0, 1].map(|_| {
+            Texture::create_2d_texture(
+                &context.device,
+                context.config.width,
+                context.config.height,
+                context.hdr.format(),
+                &wgpu::SamplerDescriptor::default(),
+                Some("Accumulation history texture"),
+            )
+        })
+    }
+
+    // bind_groups[i] reads history[i] and is used to render into the other history texture
+    fn create_bind_groups(
+        history: &[Texture; 2],
+        buffer: &wgpu::Buffer,
+        layout: &wgpu::BindGroupLayout,
+        context: &RenderContext,
+    ) -> [wgpu::BindGroup; 2] {
+        [0, 1].map(|index| {
+            context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Accumulation bind group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(context.hdr.view()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(history[index].view()),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        })
+    }
+}
@@ -0,0 +1,454 @@
+use std::sync::Arc;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::renderer::{mesh::StringHeader, vertex::Vertex};
+
+// Joints in one palette, skins with more are imported in their bind pose
+pub const MAX_JOINTS: usize = 128;
+// Parent of a root node in the skeleton section
+pub const NO_PARENT: u32 = u32::MAX;
+
+// Four joints and their weights per vertex, bound in the slot of the last UV set
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct SkinVertex {
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
+}
+
+impl Vertex for SkinVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Uint16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u16; 4]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+// Rest pose of a node of the file, whether or not it has a mesh. Joints and animation channels point in here
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct SkeletonNodeHeader {
+    pub parent: u32,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct JointHeader {
+    pub node: u32,
+    pub inverse_bind_matrix: [f32; 16],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct SkinHeader {
+    pub joint_offset: usize,
+    pub joint_count: usize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct AnimationHeader {
+    pub name: StringHeader,
+    pub channel_offset: usize,
+    pub channel_count: usize,
+}
+
+// Key times and values are byte offsets into the keyframe section, cubic spline values come as in tangent, value and
+// out tangent per key
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ChannelHeader {
+    pub node: u32,
+    pub path: u32,
+    pub interpolation: u32,
+    pub _padding: u32,
+    pub time_offset: usize,
+    pub value_offset: usize,
+    pub key_count: usize,
+}
+
+// Sections of the scene buffer for skins and animations, empty for formats without them
+#[derive(Default)]
+pub struct AnimationSections {
+    pub skeleton_nodes: Vec<SkeletonNodeHeader>,
+    pub joints: Vec<JointHeader>,
+    pub skins: Vec<SkinHeader>,
+    pub animations: Vec<AnimationHeader>,
+    pub channels: Vec<ChannelHeader>,
+    pub keyframes: Vec<f32>,
+    pub skin_vertices: Vec<SkinVertex>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChannelPath {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+impl ChannelPath {
+    pub fn index(&self) -> u32 {
+        match self {
+            Self::Translation => 0,
+            Self::Rotation => 1,
+            Self::Scale => 2,
+        }
+    }
+
+    fn from_index(index: u32) -> Option<Self> {
+        match index {
+            0 => Some(Self::Translation),
+            1 => Some(Self::Rotation),
+            2 => Some(Self::Scale),
+            _ => None,
+        }
+    }
+
+    pub fn components(&self) -> usize {
+        match self {
+            Self::Rotation => 4,
+            Self::Translation | Self::Scale => 3,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    CubicSpline,
+}
+
+impl Interpolation {
+    pub fn index(&self) -> u32 {
+        match self {
+            Self::Linear => 0,
+            Self::Step => 1,
+            Self::CubicSpline => 2,
+        }
+    }
+
+    fn from_index(index: u32) -> Self {
+        match index {
+            1 => Self::Step,
+            2 => Self::CubicSpline,
+            _ => Self::Linear,
+        }
+    }
+
+    // Floats per key of a value with `components`
+    pub fn stride(&self, components: usize) -> usize {
+        match self {
+            Self::CubicSpline => components * 3,
+            Self::Linear | Self::Step => components,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AnimationChannel {
+    node: usize,
+    path: ChannelPath,
+    interpolation: Interpolation,
+    times: Vec<f32>,
+    values: Vec<f32>,
+}
+
+impl AnimationChannel {
+    fn sample(&self, time: f32) -> glam::Vec4 {
+        let components = self.path.components();
+        let stride = self.interpolation.stride(components);
+        // Element 1 of a cubic spline key is the value, 0 and 2 its tangents
+        let value = |key: usize, element: usize| {
+            let start = key * stride + element * components;
+            let mut value = [0.0; 4];
+            value[..components].copy_from_slice(&self.values[start..start + components]);
+            glam::Vec4::from_array(value)
+        };
+        let element = match self.interpolation {
+            Interpolation::CubicSpline => 1,
+            Interpolation::Linear | Interpolation::Step => 0,
+        };
+
+        let next = self.times.partition_point(|&key_time| key_time <= time);
+        if next == 0 {
+            return value(0, element);
+        }
+        if next == self.times.len() {
+            return value(next - 1, element);
+        }
+
+        let previous = next - 1;
+        let delta = self.times[next] - self.times[previous];
+        let t = match delta > 0.0 {
+            true => (time - self.times[previous]) / delta,
+            false => 0.0,
+        };
+        match self.interpolation {
+            Interpolation::Step => value(previous, 0),
+            Interpolation::Linear if self.path == ChannelPath::Rotation => {
+                let (start, end) = (value(previous, 0), value(next, 0));
+                glam::Vec4::from(glam::Quat::from_vec4(start).slerp(glam::Quat::from_vec4(end), t))
+            }
+            Interpolation::Linear => value(previous, 0).lerp(value(next, 0), t),
+            // Hermite spline, the tangents are per second so they scale with the key interval
+            Interpolation::CubicSpline => {
+                let (t2, t3) = (t * t, t * t * t);
+                value(previous, 1) * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + value(previous, 2) * delta * (t3 - 2.0 * t2 + t)
+                    + value(next, 1) * (-2.0 * t3 + 3.0 * t2)
+                    + value(next, 0) * delta * (t3 - t2)
+            }
+        }
+    }
+}
+
+// Channels of one glTF animation, looped over the time of its last key
+#[derive(Debug)]
+pub struct AnimationClip {
+    pub duration: f32,
+    channels: Vec<AnimationChannel>,
+}
+
+impl AnimationClip {
+    // Channels with an unknown path or fewer values than keys are left out
+    pub fn new(channels: &[ChannelHeader], keyframes: &[u8]) -> Self {
+        let read = |offset: usize, count: usize| -> Option<Vec<f32>> {
+            let bytes = keyframes.get(offset..offset + count * std::mem::size_of::<f32>())?;
+            Some(
+                bytes
+                    .chunks_exact(std::mem::size_of::<f32>())
+                    .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+                    .collect(),
+            )
+        };
+
+        let channels = channels
+            .iter()
+            .filter_map(|channel| {
+                let path = ChannelPath::from_index(channel.path)?;
+                let interpolation = Interpolation::from_index(channel.interpolation);
+                let value_count = channel.key_count * interpolation.stride(path.components());
+                Some(AnimationChannel {
+                    node: channel.node as usize,
+                    path,
+                    interpolation,
+                    times: read(channel.time_offset, channel.key_count)?,
+                    values: read(channel.value_offset, value_count)?,
+                })
+            })
+            .filter(|channel| !channel.times.is_empty())
+            .collect::<Vec<_>>();
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max);
+
+        Self { duration, channels }
+    }
+
+    fn targets(&self, node: usize) -> bool {
+        self.channels.iter().any(|channel| channel.node == node)
+    }
+}
+
+// Where an entity is in its clip relative to the clock every skin shares, so copies of one asset don't move in step
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnimationPhase {
+    // Seconds into the clip at the shared clock's start
+    pub offset: f32,
+    // Negative plays the clip backwards
+    pub speed: f32,
+}
+
+impl Default for AnimationPhase {
+    fn default() -> Self {
+        Self {
+            offset: 0.0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl AnimationPhase {
+    pub fn time(&self, clock: f32) -> f32 {
+        self.offset + clock * self.speed
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct SkeletonNode {
+    parent: Option<usize>,
+    translation: glam::Vec3,
+    rotation: glam::Quat,
+    scale: glam::Vec3,
+}
+
+// The joints of a skin with the node hierarchy they hang in, and the first clip that moves them
+#[derive(Clone, Debug)]
+pub struct Skeleton {
+    nodes: Vec<SkeletonNode>,
+    // Parents before their children
+    order: Vec<usize>,
+    joints: Vec<(usize, glam::Mat4)>,
+    // The entity's transform is applied on top of the palette, a skinned mesh is placed by its joints alone
+    mesh_inverse: glam::Mat4,
+    clip: Option<Arc<AnimationClip>>,
+}
+
+impl Skeleton {
+    // None when a joint points outside the nodes
+    pub fn new(
+        nodes: &[SkeletonNodeHeader],
+        joints: &[JointHeader],
+        mesh_transform: glam::Mat4,
+        clips: &[Arc<AnimationClip>],
+    ) -> Option<Self> {
+        let nodes = nodes
+            .iter()
+            .map(|node| SkeletonNode {
+                parent: (node.parent != NO_PARENT)
+                    .then_some(node.parent as usize)
+                    .filter(|&parent| parent < nodes.len()),
+                translation: glam::Vec3::from_array(node.translation),
+                rotation: glam::Quat::from_array(node.rotation).normalize(),
+                scale: glam::Vec3::from_array(node.scale),
+            })
+            .collect::<Vec<_>>();
+        let joints = joints
+            .iter()
+            .map(|joint| {
+                let node = joint.node as usize;
+                (node < nodes.len()).then(|| (node, glam::Mat4::from_cols_array(&joint.inverse_bind_matrix)))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        // Depth of every node, capped so a malformed cycle can't hang the import
+        let depth = |mut node: usize| {
+            let mut depth = 0;
+            while let Some(parent) = nodes[node].parent
+                && depth < nodes.len()
+            {
+                node = parent;
+                depth += 1;
+            }
+            depth
+        };
+        let mut order = (0..nodes.len()).collect::<Vec<_>>();
+        order.sort_by_cached_key(|&node| depth(node));
+
+        // Any clip moving a joint or one of its ancestors moves the skin
+        let mut moving = vec![false; nodes.len()];
+        for &(joint, _) in &joints {
+            let mut node = Some(joint);
+            while let Some(index) = node
+                && !moving[index]
+            {
+                moving[index] = true;
+                node = nodes[index].parent;
+            }
+        }
+        let clip = clips
+            .iter()
+            .find(|clip| (0..nodes.len()).any(|node| moving[node] && clip.targets(node)))
+            .cloned();
+
+        Some(Self {
+            nodes,
+            order,
+            joints,
+            mesh_inverse: mesh_transform.inverse(),
+            clip,
+        })
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    // Seconds before the clip loops, None without one
+    pub fn duration(&self) -> Option<f32> {
+        self.clip.as_ref().map(|clip| clip.duration)
+    }
+
+    // Joint matrices `time` seconds into the looped clip, in the entity's object space. Without a clip the skin stays
+    // in its rest pose
+    pub fn palette(&self, time: f32) -> JointPalette {
+        let mut poses = self
+            .nodes
+            .iter()
+            .map(|node| (node.translation, node.rotation, node.scale))
+            .collect::<Vec<_>>();
+        if let Some(clip) = &self.clip {
+            let time = time.rem_euclid(clip.duration.max(f32::EPSILON));
+            for channel in &clip.channels {
+                let Some(pose) = poses.get_mut(channel.node) else {
+                    continue;
+                };
+                let value = channel.sample(time);
+                match channel.path {
+                    ChannelPath::Translation => pose.0 = value.truncate(),
+                    ChannelPath::Rotation => pose.1 = glam::Quat::from_vec4(value).normalize(),
+                    ChannelPath::Scale => pose.2 = value.truncate(),
+                }
+            }
+        }
+
+        let mut globals = vec![glam::Mat4::IDENTITY; self.nodes.len()];
+        for &node in &self.order {
+            let (translation, rotation, scale) = poses[node];
+            let local = glam::Mat4::from_scale_rotation_translation(scale, rotation, translation);
+            globals[node] = match self.nodes[node].parent {
+                Some(parent) => globals[parent] * local,
+                None => local,
+            };
+        }
+
+        let mut palette = JointPalette::zeroed();
+        for (matrix, (node, inverse_bind_matrix)) in palette.joints.iter_mut().zip(&self.joints) {
+            *matrix = (self.mesh_inverse * globals[*node] * *inverse_bind_matrix).to_cols_array();
+        }
+        palette
+    }
+}
+
+// Skinning matrices of one entity, read by the vertex shader through the instance's palette index
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct JointPalette {
+    pub joints: [[f32; 16]; MAX_JOINTS],
+}
+
+impl JointPalette {
+    // The weighted joint matrices of a vertex, as the vertex shader blends them
+    pub fn skin(&self, vertex: &SkinVertex) -> glam::Mat4 {
+        vertex
+            .joints
+            .iter()
+            .zip(vertex.weights)
+            .fold(glam::Mat4::ZERO, |skin, (&joint, weight)| {
+                let joint = self
+                    .joints
+                    .get(joint as usize)
+                    .map_or(glam::Mat4::ZERO, glam::Mat4::from_cols_array);
+                skin + joint * weight
+            })
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::renderer::{
+    context::RenderContext,
+    instance::Instance,
+    pointcloud::PointVertex,
+    quantize::MeshPipelines,
+    readback::TextureReadback,
+    scene::{Geometry, Renderable, SceneGraph},
+    texture::Texture,
+    vertex::VertexLayoutBuilder,
+};
+
+// Auxiliary buffers of one capture, top row first like the screenshot they go with
+#[derive(Debug)]
+pub struct AovImages {
+    pub width: u32,
+    pub height: u32,
+    // Distance along the view direction in world units, zero where nothing was hit
+    pub depth: Vec<f32>,
+    // World space, zero where nothing was hit and for points without estimated normals
+    pub normals: Vec<glam::Vec3>,
+    // Zero for the background, every other id is a key of `entities`
+    pub ids: Vec<u32>,
+    pub entities: HashMap<u32, Uuid>,
+}
+
+// Scene geometry drawn once more with depth testing but no shading, so every material and render mode gives the same
+// buffers. Debug helpers are left out
+pub struct AovPass {
+    mesh_pipelines: MeshPipelines,
+    point_pipeline: wgpu::RenderPipeline,
+}
+
+impl AovPass {
+    // Normal in xyz and linear depth in w
+    const NORMAL_DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+    const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    pub fn new(scene_layout: &wgpu::BindGroupLayout, context: &RenderContext) -> Self {
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("AOV shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/aov.wgsl").into()),
+        });
+
+        let layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("AOV pipeline layout"),
+            bind_group_layouts: &[&context.camera_bind_group_layout, scene_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point, topology, constants, buffers: &[wgpu::VertexBufferLayout]| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants,
+                        ..Default::default()
+                    },
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[
+                        Some(wgpu::ColorTargetState {
+                            format: Self::NORMAL_DEPTH_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                        Some(wgpu::ColorTargetState {
+                            format: Self::ID_FORMAT,
+                            blend: None,
+                            write_mask: wgpu::ColorWrites::ALL,
+                        }),
+                    ],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let mesh_pipelines = MeshPipelines::new(|precision| {
+            create_pipeline(
+                &format!("AOV mesh pipeline ({})", precision.to_str()),
+                "vs_mesh",
+                wgpu::PrimitiveTopology::TriangleList,
+                precision.shader_constants(),
+                &precision.layout_builder(0).push::<Instance>().build(),
+            )
+        });
+        let point_pipeline = create_pipeline(
+            "AOV pointcloud pipeline",
+            "vs_points",
+            wgpu::PrimitiveTopology::PointList,
+            &[],
+            &VertexLayoutBuilder::new()
+                .push::<PointVertex>()
+                .push::<Instance>()
+                .build(),
+        );
+
+        Self {
+            mesh_pipelines,
+            point_pipeline,
+        }
+    }
+
+    // Renders at the surface size from the current camera and starts reading the buffers back. Entities are resolved
+    // now, they may be gone by the time the pixels arrive
+    pub fn capture(
+        &self,
+        scene: &SceneGraph,
+        camera_bind_group: &wgpu::BindGroup,
+        context: &RenderContext,
+    ) -> AovReadback {
+        let create_target = |label, format| {
+            context.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: context.config.width,
+                    height: context.config.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        let normal_depth = create_target("AOV normal depth texture", Self::NORMAL_DEPTH_FORMAT);
+        let ids = create_target("AOV id texture", Self::ID_FORMAT);
+        let depth = Texture::create_depth_texture(&context.device, &context.config, Some("AOV depth texture"));
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("AOV encoder"),
+        });
+        {
+            let normal_depth_view = normal_depth.create_view(&wgpu::TextureViewDescriptor::default());
+            let id_view = ids.create_view(&wgpu::TextureViewDescriptor::default());
+            let attachment = |view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            };
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("AOV render pass"),
+                color_attachments: &[attachment(&normal_depth_view), attachment(&id_view)],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            render_pass.set_bind_group(1, scene.bind_group(), &[]);
+            render_pass.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+
+            for batch in scene
+                .render_batches
+                .iter()
+                .filter(|batch| batch.key.render_id != scene.debug_id)
+            {
+                match scene.renderables.get(&batch.key.render_id) {
+                    Some(Renderable::Mesh(handles)) => {
+                        for handle in handles {
+                            if let Some(Geometry::Primitive(primitive)) =
+                                scene.geometries.get_by_id(handle.geometry_index)
+                            {
+                                render_pass.set_pipeline(self.mesh_pipelines.get(primitive.vertex_precision));
+                                render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                                render_pass.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
+                                render_pass.draw_indexed(0..primitive.num_elements, 0, batch.instance_range());
+                            }
+                        }
+                    }
+                    Some(Renderable::Pointcloud(handle)) => {
+                        if let Some(Geometry::Pointcloud(pointcloud)) =
+                            scene.geometries.get_by_id(handle.geometry_index)
+                        {
+                            render_pass.set_pipeline(&self.point_pipeline);
+                            render_pass.set_vertex_buffer(0, pointcloud.vertex_buffer.slice(..));
+                            render_pass.draw(0..pointcloud.num_points, batch.instance_range());
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+        context.queue.submit(Some(encoder.finish()));
+
+        let entities = scene
+            .transforms
+            .iter_with_index()
+            .map(|(entity, index, _)| (index as u32 + 1, *entity))
+            .collect();
+
+        AovReadback {
+            normal_depth: TextureReadback::new(&normal_depth, context),
+            ids: TextureReadback::new(&ids, context),
+            normal_depth_pixels: None,
+            entities,
+        }
+    }
+}
+
+// Both buffers of a capture, the first one to arrive is held until the other is in
+pub struct AovReadback {
+    normal_depth: TextureReadback,
+    ids: TextureReadback,
+    normal_depth_pixels: Option<Vec<u8>>,
+    entities: HashMap<u32, Uuid>,
+}
+
+impl AovReadback {
+    // None while a copy is still in flight, the buffers are empty when either of them failed
+    pub fn try_read(&mut self) -> Option<AovImages> {
+        if self.normal_depth_pixels.is_none() {
+            self.normal_depth_pixels = Some(self.normal_depth.try_read()?.unwrap_or_default());
+        }
+        let ids = self.ids.try_read()?.unwrap_or_default();
+        let normal_depth = self.normal_depth_pixels.take().unwrap_or_default();
+
+        let (width, height) = self.ids.size();
+        let mut images = AovImages {
+            width,
+            height,
+            depth: Vec::new(),
+            normals: Vec::new(),
+            ids: Vec::new(),
+            entities: std::mem::take(&mut self.entities),
+        };
+        if normal_depth.is_empty() || ids.is_empty() {
+            return Some(images);
+        }
+
+        // Staging memory carries no alignment guarantee
+        let texels = normal_depth
+            .chunks_exact(16)
+            .map(bytemuck::pod_read_unaligned::<[f32; 4]>)
+            .collect::<Vec<_>>();
+        images.depth = texels.iter().map(|texel| texel[3]).collect();
+        images.normals = texels
+            .iter()
+            .map(|texel| glam::Vec3::new(texel[0], texel[1], texel[2]))
+            .collect();
+        images.ids = ids.chunks_exact(4).map(bytemuck::pod_read_unaligned).collect();
+        Some(images)
+    }
+}
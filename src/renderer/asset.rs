@@ -1,7 +1,7 @@
-use std::{borrow::Cow, io::Cursor, path::Path};
-
-use crossbeam::channel::Sender;
+use std::{borrow::Cow, io::Cursor, ops::ControlFlow, path::Path};
 
+#[cfg(not(target_family = "wasm"))]
+use crossbeam::channel::{Receiver, Sender};
 #[cfg(not(target_family = "wasm"))]
 use futures_lite::future;
 use image::{ImageDecoder, codecs::hdr::HdrDecoder};
@@ -10,10 +10,21 @@ use instant::Instant;
 
 use serde::{Deserialize, Serialize};
 
+#[cfg(not(target_family = "wasm"))]
+use uuid::Uuid;
+
+#[cfg(not(target_family = "wasm"))]
+use crate::renderer::{RenderCommand, scene::RenderId, scheduler::LoadThreads};
 #[cfg(target_family = "wasm")]
-use crate::renderer::worker::{LoadTask, UploadTask, WorkerPool};
+use crate::renderer::{
+    registration::Registration,
+    worker::{LoadTask, RefineTask, UploadTask, WorkerPool},
+};
 
-use crate::renderer::{RenderCommand, environment::HdrBuffer, mesh::SceneBuffer, pointcloud::PointcloudBuffer};
+use crate::renderer::{
+    RendererClient, bvh::Aabb, environment::HdrBuffer, mesh::SceneBuffer, pointcloud::PointcloudBuffer,
+    quantize::VertexPrecision, scheduler::TaskPriority, task::TaskList, volume::VolumeBuffer,
+};
 
 #[derive(Clone)]
 pub enum ResourcePath {
@@ -70,6 +81,12 @@ impl ResourcePath {
         }
     }
 
+    // Where a file is read from, relative paths are in the resources copied next to the build
+    pub fn local_path(&self) -> Option<std::path::PathBuf> {
+        self.path()
+            .map(|path| Path::new(env!("OUT_DIR")).join("res").join(path))
+    }
+
     pub fn as_str(&self) -> Cow<'_, str> {
         match self {
             Self::File(path) => match path.to_str() {
@@ -155,10 +172,7 @@ impl ResourcePath {
 
     pub async fn load_string(&self) -> anyhow::Result<String> {
         let text = match self {
-            Self::File(path) => {
-                let path_buf = std::path::Path::new(env!("OUT_DIR")).join("res").join(path);
-                std::fs::read_to_string(path_buf)?
-            }
+            Self::File(_) => std::fs::read_to_string(self.local_path().unwrap())?,
             Self::Url(url) => {
                 let response = reqwest::get(url.as_str()).await?;
                 response.text().await?
@@ -175,10 +189,7 @@ impl ResourcePath {
 
     pub async fn load_binary(&self) -> anyhow::Result<Vec<u8>> {
         let data = match self {
-            Self::File(path) => {
-                let path_buf = std::path::Path::new(env!("OUT_DIR")).join("res").join(path);
-                std::fs::read(path_buf)?
-            }
+            Self::File(_) => std::fs::read(self.local_path().unwrap())?,
             Self::Url(url) => {
                 let response = reqwest::get(url.as_str()).await?;
                 response.bytes().await?.to_vec()
@@ -227,10 +238,190 @@ impl std::fmt::Display for ResourcePath {
     }
 }
 
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    pub subdivision_levels: u32,
+    pub compact_indices: bool,
+    pub vertex_precision: VertexPrecision,
+    pub mode: ImportMode,
+    // Merges small primitives sharing a material, for scenes of many tiny parts like CAD exports
+    pub static_batching: bool,
+    // Packs the small textures of materials that only differ in their textures into atlases and merges the materials
+    pub texture_atlas: bool,
+}
+
+impl ImportOptions {
+    pub const MAX_SUBDIVISION_LEVELS: u32 = 4;
+
+    pub fn subdivision_levels(&self) -> u32 {
+        self.subdivision_levels.min(Self::MAX_SUBDIVISION_LEVELS)
+    }
+}
+
+// What an imported scene does to the entities already there. Pointclouds are always appended
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportMode {
+    #[default]
+    Append,
+    // Every entity with geometry is despawned once the import finishes, lights stay
+    Replace,
+}
+
+impl ImportMode {
+    pub const ALL: [Self; 2] = [Self::Append, Self::Replace];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Append => "Append to scene",
+            Self::Replace => "Replace scene",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ImportWarning {
+    MissingNormals { mesh: String },
+    GeneratedTangents { mesh: String },
+    UnsupportedExtension { name: String, required: bool },
+    MissingTexture { path: String, error: String },
+    MissingPositions { mesh: String },
+    MissingTexCoords { mesh: String },
+    IndicesOutOfRange { mesh: String, triangles: usize },
+    MissingMaterial { mesh: String, index: usize },
+    MissingMaterialLibrary { error: String },
+    UnsupportedPrimitiveMode { mesh: String, mode: String },
+    SubdivisionLimited { mesh: String },
+    UnsupportedAttribute { mesh: String, name: String },
+    TooManyJoints { mesh: String, joints: usize },
+}
+
+impl std::fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingNormals { mesh } => write!(f, "{}: no normals, generated from faces", mesh),
+            Self::GeneratedTangents { mesh } => write!(f, "{}: no tangents, generated from UVs", mesh),
+            Self::UnsupportedExtension { name, required: true } => {
+                write!(f, "Required extension {} is not supported", name)
+            }
+            Self::UnsupportedExtension { name, required: false } => write!(f, "Extension {} is ignored", name),
+            Self::MissingTexture { path, error } => write!(f, "{}: {}, using a placeholder", path, error),
+            Self::MissingPositions { mesh } => write!(f, "{}: primitive without positions, skipped", mesh),
+            Self::MissingTexCoords { mesh } => write!(f, "{}: no texture coordinates, normal maps won't line up", mesh),
+            Self::IndicesOutOfRange { mesh, triangles } => {
+                write!(f, "{}: dropped {} triangles with out of range indices", mesh, triangles)
+            }
+            Self::MissingMaterial { mesh, index } => {
+                write!(f, "{}: material {} does not exist, using the fallback", mesh, index)
+            }
+            Self::MissingMaterialLibrary { error } => write!(f, "Material library: {}", error),
+            Self::UnsupportedPrimitiveMode { mesh, mode } => {
+                write!(f, "{}: {} primitives are not supported, skipped", mesh, mode)
+            }
+            Self::SubdivisionLimited { mesh } => write!(f, "{}: subdivision stopped at the 32-bit index limit", mesh),
+            Self::UnsupportedAttribute { mesh, name } => {
+                write!(f, "{}: attribute {} can't be read, skipped", mesh, name)
+            }
+            Self::TooManyJoints { mesh, joints } => {
+                write!(
+                    f,
+                    "{}: skin with {} joints is over the limit, left in its bind pose",
+                    mesh, joints
+                )
+            }
+        }
+    }
+}
+
+// Size of a loaded renderable, shown when hovering its instances
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AssetStats {
+    pub triangles: u32,
+    pub points: u32,
+    pub materials: u32,
+    // Local space
+    pub bounds: Aabb,
+}
+
+// Everything worth knowing about an import that didn't stop it
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    warnings: Vec<ImportWarning>,
+}
+
+impl ImportReport {
+    pub fn push(&mut self, warning: ImportWarning) {
+        log::warn!("{}", warning);
+        self.warnings.push(warning);
+    }
+
+    pub fn warnings(&self) -> &[ImportWarning] {
+        &self.warnings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn extend(&mut self, other: ImportReport) {
+        self.warnings.extend(other.warnings);
+    }
+}
+
 pub enum AssetBuffer {
     EnvironmentMap { buffer: HdrBuffer, label: Option<String> },
     Pointcloud(PointcloudBuffer, Option<String>),
-    Scene(SceneBuffer, Option<String>),
+    Scene(SceneBuffer, Option<String>, ImportReport, ImportMode),
+    Volume(VolumeBuffer, Option<String>),
+}
+
+impl AssetBuffer {
+    // Close to what the upload allocates, the vertex and texture data goes to the GPU as is
+    pub fn estimated_size(&self) -> u64 {
+        match self {
+            // Replaces the current map instead of adding to the scene
+            Self::EnvironmentMap { .. } => 0,
+            Self::Pointcloud(buffer, _) => std::mem::size_of_val(buffer.points()) as u64,
+            Self::Scene(buffer, _, _, _) => buffer.buffer().len() as u64,
+            Self::Volume(buffer, _) => buffer.data.len() as u64,
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Self::EnvironmentMap { label, .. }
+            | Self::Pointcloud(_, label)
+            | Self::Scene(_, label, _, _)
+            | Self::Volume(_, label) => label.as_deref(),
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub async fn import(kind: AssetKind, path: &ResourcePath, options: &ImportOptions) -> anyhow::Result<Self> {
+        let filename = path.file_name().to_string();
+        Ok(match kind {
+            AssetKind::Obj => {
+                let (scene, report) = SceneBuffer::from_obj(path, options).await?;
+                Self::Scene(scene, Some(filename), report, options.mode)
+            }
+            AssetKind::Gltf => {
+                let (scene, report) = SceneBuffer::from_gltf(path.load_binary().await?, options)?;
+                Self::Scene(scene, Some(filename), report, options.mode)
+            }
+            AssetKind::Pointcloud => {
+                let pointcloud = PointcloudBuffer::from_file(&filename, path.load_binary().await?)?;
+                Self::Pointcloud(pointcloud, Some(filename))
+            }
+            AssetKind::EnvironmentMap => Self::EnvironmentMap {
+                buffer: HdrBuffer::from_hdr(&path.load_binary().await?)?,
+                label: Some(filename),
+            },
+            AssetKind::Volume => {
+                let volume = VolumeBuffer::from_file(&filename, &path.load_binary().await?)?;
+                Self::Volume(volume, Some(filename))
+            }
+        })
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -239,6 +430,7 @@ pub enum AssetKind {
     Gltf,
     Pointcloud,
     EnvironmentMap,
+    Volume,
 }
 
 impl AssetKind {
@@ -248,6 +440,7 @@ impl AssetKind {
             AssetKind::Gltf => "gltf",
             AssetKind::Pointcloud => "pointcloud",
             AssetKind::EnvironmentMap => "environment_map",
+            AssetKind::Volume => "volume",
         }
     }
 
@@ -257,23 +450,36 @@ impl AssetKind {
             "gltf" => Some(AssetKind::Gltf),
             "pointcloud" => Some(AssetKind::Pointcloud),
             "environment_map" => Some(AssetKind::EnvironmentMap),
+            "volume" => Some(AssetKind::Volume),
             _ => None,
         }
     }
 
     pub fn from_extension(extension: &str) -> Option<Self> {
         let extension = extension.to_ascii_lowercase();
-        [Self::Obj, Self::Gltf, Self::Pointcloud, Self::EnvironmentMap]
-            .into_iter()
-            .find(|kind| kind.extensions().contains(&extension.as_str()))
+        [
+            Self::Obj,
+            Self::Gltf,
+            Self::Pointcloud,
+            Self::EnvironmentMap,
+            Self::Volume,
+        ]
+        .into_iter()
+        .find(|kind| kind.extensions().contains(&extension.as_str()))
+    }
+
+    // Environment maps and volumes replace the previous one instead
+    pub fn adds_nodes(&self) -> bool {
+        !matches!(self, Self::EnvironmentMap | Self::Volume)
     }
 
     pub fn extensions(&self) -> &[&'static str] {
         match self {
             AssetKind::Obj => &["obj"],
             AssetKind::Gltf => &["gltf", "glb"],
-            AssetKind::Pointcloud => &["las", "laz"],
+            AssetKind::Pointcloud => &["las", "laz", "csv", "geojson"],
             AssetKind::EnvironmentMap => &["hdr", "exr"],
+            AssetKind::Volume => &["nrrd", "raw", "dcm", "dicom"],
         }
     }
 }
@@ -284,52 +490,246 @@ impl std::fmt::Display for AssetKind {
     }
 }
 
+#[cfg(not(target_family = "wasm"))]
+pub struct LoadRequest {
+    pub path: ResourcePath,
+    pub options: ImportOptions,
+    pub priority: TaskPriority,
+}
+
 #[derive(Clone)]
 pub struct AssetLoader {
-    render_tx: Sender<RenderCommand>,
+    client: RendererClient,
+    tasks: TaskList,
+    // Every load, so a shared session can have the others load them too and changed files can be reloaded
+    #[cfg(not(target_family = "wasm"))]
+    requested_tx: Sender<LoadRequest>,
+    #[cfg(not(target_family = "wasm"))]
+    requested_rx: Receiver<LoadRequest>,
+    // Bounds by file name, known from the headers long before an import finishes
+    #[cfg(not(target_family = "wasm"))]
+    estimate_tx: Sender<(String, Aabb)>,
+    #[cfg(not(target_family = "wasm"))]
+    estimate_rx: Receiver<(String, Aabb)>,
+    #[cfg(not(target_family = "wasm"))]
+    threads: LoadThreads,
     #[cfg(target_family = "wasm")]
     worker_pool: WorkerPool,
 }
 
 impl AssetLoader {
-    pub fn new(sender: Sender<RenderCommand>) -> Self {
+    pub fn new(client: RendererClient) -> Self {
+        let tasks = TaskList::default();
+        #[cfg(not(target_family = "wasm"))]
+        let (requested_tx, requested_rx) = crossbeam::channel::unbounded();
+        #[cfg(not(target_family = "wasm"))]
+        let (estimate_tx, estimate_rx) = crossbeam::channel::unbounded();
         Self {
-            render_tx: sender.clone(),
+            client: client.clone(),
+            tasks: tasks.clone(),
+            #[cfg(not(target_family = "wasm"))]
+            requested_tx,
+            #[cfg(not(target_family = "wasm"))]
+            requested_rx,
+            #[cfg(not(target_family = "wasm"))]
+            estimate_tx,
+            #[cfg(not(target_family = "wasm"))]
+            estimate_rx,
+            #[cfg(not(target_family = "wasm"))]
+            threads: LoadThreads::new(std::thread::available_parallelism().map_or(4, |count| count.get())),
             #[cfg(target_family = "wasm")]
-            worker_pool: WorkerPool::new(sender),
+            worker_pool: WorkerPool::new(client, tasks),
         }
     }
 
+    #[cfg(not(target_family = "wasm"))]
+    pub fn max_concurrency(&self) -> usize {
+        self.threads.max_concurrency()
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn max_concurrency(&self) -> usize {
+        self.worker_pool.max_concurrency()
+    }
+
+    pub fn set_max_concurrency(&self, max_concurrency: usize) {
+        #[cfg(not(target_family = "wasm"))]
+        self.threads.set_max_concurrency(max_concurrency);
+        #[cfg(target_family = "wasm")]
+        self.worker_pool.set_max_concurrency(max_concurrency);
+    }
+
+    // ICP on a worker, see `RefineTask` for what `on_result` gets
+    #[cfg(target_family = "wasm")]
+    pub fn refine_registration(
+        &self,
+        (source, target): (Vec<glam::Vec3>, Vec<glam::Vec3>),
+        source_transform: glam::Mat4,
+        max_distance: f32,
+        on_result: impl Fn(Option<Option<Registration>>) + 'static,
+    ) {
+        let task = RefineTask {
+            source,
+            target,
+            source_transform,
+            max_distance,
+            on_result: Some(Box::new(on_result)),
+        };
+        self.worker_pool.submit(task, TaskPriority::User);
+    }
+
+    pub fn tasks(&self) -> &TaskList {
+        &self.tasks
+    }
+
+    // Paths loaded since the last call, from any clone of the loader
+    #[cfg(not(target_family = "wasm"))]
+    pub fn take_requested(&self) -> Vec<LoadRequest> {
+        self.requested_rx.try_iter().collect()
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub fn take_estimates(&self) -> Vec<(String, Aabb)> {
+        self.estimate_rx.try_iter().collect()
+    }
+
     pub fn load(&self, path: ResourcePath) {
+        self.load_with_options(path, ImportOptions::default());
+    }
+
+    pub fn load_with_options(&self, path: ResourcePath, options: ImportOptions) {
+        self.load_with_priority(path, options, TaskPriority::User);
+    }
+
+    pub fn load_with_priority(&self, path: ResourcePath, options: ImportOptions, priority: TaskPriority) {
         if let Some(extension) = path.extension().as_deref() {
             if let Some(kind) = AssetKind::from_extension(extension) {
-                self.load_kind(kind, path);
+                #[cfg(not(target_family = "wasm"))]
+                self.requested_tx
+                    .send(LoadRequest {
+                        path: path.clone(),
+                        options: options.clone(),
+                        priority,
+                    })
+                    .ok();
+                self.load_kind(kind, path, options, priority);
             } else {
                 log::error!("Unsupported resource");
             }
         }
     }
 
-    fn load_kind(&self, kind: AssetKind, path: ResourcePath) {
+    // Imports the file again and swaps it in for the nodes of an earlier load
+    #[cfg(not(target_family = "wasm"))]
+    pub fn reload(&self, path: ResourcePath, options: ImportOptions, asset_id: Uuid, render_ids: Vec<RenderId>) {
+        let Some(kind) = path.extension().as_deref().and_then(AssetKind::from_extension) else {
+            return;
+        };
+
+        let client = self.client.clone();
+        let timestamp = Instant::now();
+        let task = self.tasks.start(format!("Reloading {}", path.file_name()));
+
+        self.threads.spawn(TaskPriority::User, move || {
+            // Exporters write in several steps, a file caught halfway fails here and is read again on its next change
+            let asset = match future::block_on(AssetBuffer::import(kind, &path, &options)) {
+                Ok(asset) => asset,
+                Err(error) => {
+                    log::warn!("Unable to reload {}: {}", path, error);
+                    return;
+                }
+            };
+            if task.is_cancelled() {
+                return;
+            }
+
+            client
+                .send(RenderCommand::ReloadAsset {
+                    asset_id,
+                    render_ids,
+                    asset,
+                })
+                .unwrap();
+            log::info!("Reloaded {} in {} s", path, timestamp.elapsed().as_secs_f32());
+        });
+    }
+
+    // Imports the scenes one after the other and uploads them as a single asset, labeled with all their names. Merged
+    // loads aren't shared with a session or reloaded when the files change
+    #[cfg(not(target_family = "wasm"))]
+    pub fn load_merged(&self, paths: Vec<ResourcePath>, options: ImportOptions) {
+        let client = self.client.clone();
+        let timestamp = Instant::now();
+        let label = paths
+            .iter()
+            .map(ResourcePath::file_name)
+            .collect::<Vec<_>>()
+            .join(" + ");
+        let task = self.tasks.start(format!("Loading {}", label));
+
+        self.threads.spawn(TaskPriority::User, move || {
+            let mut scenes = Vec::new();
+            let mut merged_report = ImportReport::default();
+            for path in &paths {
+                let Some(kind) = path.extension().as_deref().and_then(AssetKind::from_extension) else {
+                    continue;
+                };
+                match future::block_on(AssetBuffer::import(kind, path, &options)) {
+                    Ok(AssetBuffer::Scene(scene, _, report, _)) => {
+                        scenes.push(scene);
+                        merged_report.extend(report);
+                    }
+                    Ok(_) => log::warn!("Only scenes can be merged, skipped {}", path),
+                    Err(error) => log::error!("Unable to load {}: {}", path, error),
+                }
+                if task.is_cancelled() {
+                    return;
+                }
+            }
+
+            if scenes.is_empty() {
+                return;
+            }
+
+            let scene = SceneBuffer::merge(&scenes);
+            client
+                .load_asset(AssetBuffer::Scene(
+                    scene,
+                    Some(label.clone()),
+                    merged_report,
+                    options.mode,
+                ))
+                .unwrap();
+            log::info!("Loaded {} in {} s", label, timestamp.elapsed().as_secs_f32());
+        });
+    }
+
+    fn load_kind(&self, kind: AssetKind, path: ResourcePath, options: ImportOptions, priority: TaskPriority) {
         match kind {
-            AssetKind::Obj => self.load_obj(path),
-            AssetKind::Gltf => self.load_gltf(path),
-            AssetKind::Pointcloud => self.load_pointcloud(path),
-            AssetKind::EnvironmentMap => self.load_skybox(path),
+            AssetKind::Obj => self.load_obj(path, options, priority),
+            AssetKind::Gltf => self.load_gltf(path, options, priority),
+            AssetKind::Pointcloud => self.load_pointcloud(path, priority),
+            AssetKind::EnvironmentMap => self.load_skybox(path, priority),
+            AssetKind::Volume => self.load_volume(path, priority),
         }
     }
 
-    fn load_obj(&self, path: ResourcePath) {
+    fn load_obj(&self, path: ResourcePath, options: ImportOptions, priority: TaskPriority) {
         #[cfg(not(target_family = "wasm"))]
         {
-            let sender = self.render_tx.clone();
+            let client = self.client.clone();
             let timestamp = Instant::now();
             let filename = path.file_name().to_string();
+            let task = self.tasks.start(format!("Loading {}", filename));
 
-            std::thread::spawn(move || {
-                let scene = future::block_on(SceneBuffer::from_obj(&path)).unwrap();
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Scene(scene, Some(filename))))
+            self.threads.spawn(priority, move || {
+                let (scene, report) = future::block_on(SceneBuffer::from_obj(&path, &options)).unwrap();
+                if task.is_cancelled() {
+                    return;
+                }
+
+                client
+                    .load_asset(AssetBuffer::Scene(scene, Some(filename), report, options.mode))
                     .unwrap();
                 log::info!("Loaded {} in {} s", path.as_str(), timestamp.elapsed().as_secs_f32());
             });
@@ -339,33 +739,50 @@ impl AssetLoader {
         {
             match path {
                 ResourcePath::File(_) | ResourcePath::Url(_) => {
-                    self.worker_pool.submit(LoadTask {
-                        kind: AssetKind::Obj,
-                        path: path.as_serializable().unwrap(),
-                    });
+                    self.worker_pool.submit(
+                        LoadTask {
+                            kind: AssetKind::Obj,
+                            path: path.as_serializable().unwrap(),
+                            options,
+                        },
+                        priority,
+                    );
                 }
                 ResourcePath::Upload(_) => {
-                    self.worker_pool.submit(UploadTask {
-                        kind: AssetKind::Obj,
-                        path,
-                    });
+                    self.worker_pool.submit(
+                        UploadTask {
+                            kind: AssetKind::Obj,
+                            path,
+                            options,
+                        },
+                        priority,
+                    );
                 }
             };
         }
     }
 
-    fn load_gltf(&self, path: ResourcePath) {
+    fn load_gltf(&self, path: ResourcePath, options: ImportOptions, priority: TaskPriority) {
         #[cfg(not(target_family = "wasm"))]
         {
-            let sender = self.render_tx.clone();
+            let client = self.client.clone();
             let timestamp = Instant::now();
             let filename = path.file_name().to_string();
+            let task = self.tasks.start(format!("Loading {}", filename));
 
-            std::thread::spawn(move || {
+            let estimate_tx = self.estimate_tx.clone();
+            self.threads.spawn(priority, move || {
                 let data = future::block_on(path.load_binary()).unwrap();
-                let scene = SceneBuffer::from_gltf(data).unwrap();
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Scene(scene, Some(filename))))
+                if let Some(bounds) = SceneBuffer::estimate_gltf_bounds(&data) {
+                    estimate_tx.send((filename.clone(), bounds)).ok();
+                }
+                let (scene, report) = SceneBuffer::from_gltf(data, &options).unwrap();
+                if task.is_cancelled() {
+                    return;
+                }
+
+                client
+                    .load_asset(AssetBuffer::Scene(scene, Some(filename), report, options.mode))
                     .unwrap();
                 log::info!("Loaded {} in {} s", path.as_str(), timestamp.elapsed().as_secs_f32());
             });
@@ -375,38 +792,91 @@ impl AssetLoader {
         {
             match path {
                 ResourcePath::File(_) | ResourcePath::Url(_) => {
-                    self.worker_pool.submit(LoadTask {
-                        kind: AssetKind::Gltf,
-                        path: path.as_serializable().unwrap(),
-                    });
+                    self.worker_pool.submit(
+                        LoadTask {
+                            kind: AssetKind::Gltf,
+                            path: path.as_serializable().unwrap(),
+                            options,
+                        },
+                        priority,
+                    );
                 }
                 ResourcePath::Upload(_) => {
-                    self.worker_pool.submit(UploadTask {
-                        kind: AssetKind::Gltf,
-                        path,
-                    });
+                    self.worker_pool.submit(
+                        UploadTask {
+                            kind: AssetKind::Gltf,
+                            path,
+                            options,
+                        },
+                        priority,
+                    );
                 }
             };
         }
     }
 
-    fn load_pointcloud(&self, path: ResourcePath) {
+    fn load_pointcloud(&self, path: ResourcePath, priority: TaskPriority) {
         #[cfg(not(target_family = "wasm"))]
         {
-            let sender = self.render_tx.clone();
+            let client = self.client.clone();
             let timestamp = Instant::now();
             let filename = path.file_name().to_string();
+            let task = self.tasks.start(format!("Loading {}", filename));
 
-            std::thread::spawn(move || {
+            let estimate_tx = self.estimate_tx.clone();
+            self.threads.spawn(priority, move || {
                 let data = future::block_on(path.load_binary()).unwrap();
-                let pointcloud = PointcloudBuffer::from_las(data).unwrap();
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Pointcloud(
-                        pointcloud,
-                        Some(filename),
-                    )))
-                    .unwrap();
-                log::info!("Loaded {} in {} s", path, timestamp.elapsed().as_secs_f32());
+                if let Some(bounds) = PointcloudBuffer::estimate_bounds(&filename, &data) {
+                    estimate_tx.send((filename.clone(), bounds)).ok();
+                }
+
+                // Large LAS and LAZ files are drawn chunk by chunk while the rest decodes
+                let stream_id = client.allocate_id();
+                let mut is_streaming = false;
+                let pointcloud = PointcloudBuffer::from_file_streamed(&filename, data, |chunk, read, total| {
+                    if task.is_cancelled() {
+                        return ControlFlow::Break(());
+                    }
+                    client
+                        .send(RenderCommand::StreamPoints {
+                            stream_id,
+                            buffer: chunk,
+                            total: total as u32,
+                            label: Some(filename.clone()),
+                            progress: (read as f32 / total as f32).min(1.0),
+                        })
+                        .unwrap();
+                    is_streaming = true;
+                    ControlFlow::Continue(())
+                });
+                let pointcloud = match pointcloud {
+                    Ok(pointcloud) if !task.is_cancelled() => Some(pointcloud),
+                    Ok(_) => None,
+                    Err(error) => {
+                        if !task.is_cancelled() {
+                            log::error!("Unable to load pointcloud {}: {}", path, error);
+                        }
+                        None
+                    }
+                };
+
+                let is_loaded = pointcloud.is_some();
+                // Also after a failure, so the chunks drawn so far are removed again
+                if is_streaming {
+                    client
+                        .send(RenderCommand::FinishPointStream {
+                            stream_id,
+                            buffer: pointcloud,
+                        })
+                        .unwrap();
+                } else if let Some(pointcloud) = pointcloud {
+                    client
+                        .load_asset(AssetBuffer::Pointcloud(pointcloud, Some(filename)))
+                        .unwrap();
+                }
+                if is_loaded {
+                    log::info!("Loaded {} in {} s", path, timestamp.elapsed().as_secs_f32());
+                }
             });
         }
 
@@ -414,39 +884,52 @@ impl AssetLoader {
         {
             match path {
                 ResourcePath::File(_) | ResourcePath::Url(_) => {
-                    self.worker_pool.submit(LoadTask {
-                        kind: AssetKind::Pointcloud,
-                        path: path.as_serializable().unwrap(),
-                    });
+                    self.worker_pool.submit(
+                        LoadTask {
+                            kind: AssetKind::Pointcloud,
+                            path: path.as_serializable().unwrap(),
+                            options: ImportOptions::default(),
+                        },
+                        priority,
+                    );
                 }
                 ResourcePath::Upload(_) => {
-                    self.worker_pool.submit(UploadTask {
-                        kind: AssetKind::Pointcloud,
-                        path,
-                    });
+                    self.worker_pool.submit(
+                        UploadTask {
+                            kind: AssetKind::Pointcloud,
+                            path,
+                            options: ImportOptions::default(),
+                        },
+                        priority,
+                    );
                 }
             };
         }
     }
 
-    fn load_skybox(&self, path: ResourcePath) {
+    fn load_skybox(&self, path: ResourcePath, priority: TaskPriority) {
         #[cfg(not(target_family = "wasm"))]
         {
-            let sender = self.render_tx.clone();
+            let client = self.client.clone();
             let timestamp = Instant::now();
             let filename = path.file_name().to_string();
+            let task = self.tasks.start(format!("Loading {}", filename));
 
-            std::thread::spawn(move || {
+            self.threads.spawn(priority, move || {
                 use crate::renderer::environment::HdrBuffer;
 
                 let data = future::block_on(path.load_binary()).unwrap();
-                let buffer = HdrBuffer::from_hdr(&data);
+                let buffer = HdrBuffer::from_hdr(&data).unwrap();
 
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::EnvironmentMap {
+                if task.is_cancelled() {
+                    return;
+                }
+
+                client
+                    .load_asset(AssetBuffer::EnvironmentMap {
                         buffer,
                         label: Some(filename),
-                    }))
+                    })
                     .unwrap();
                 log::info!("Loaded {} in {} s", path, timestamp.elapsed().as_secs_f32());
             });
@@ -456,16 +939,78 @@ impl AssetLoader {
         {
             match path {
                 ResourcePath::File(_) | ResourcePath::Url(_) => {
-                    self.worker_pool.submit(LoadTask {
-                        kind: AssetKind::EnvironmentMap,
-                        path: path.as_serializable().unwrap(),
-                    });
+                    self.worker_pool.submit(
+                        LoadTask {
+                            kind: AssetKind::EnvironmentMap,
+                            path: path.as_serializable().unwrap(),
+                            options: ImportOptions::default(),
+                        },
+                        priority,
+                    );
+                }
+                ResourcePath::Upload(_) => {
+                    self.worker_pool.submit(
+                        UploadTask {
+                            kind: AssetKind::EnvironmentMap,
+                            path,
+                            options: ImportOptions::default(),
+                        },
+                        priority,
+                    );
+                }
+            };
+        }
+    }
+
+    fn load_volume(&self, path: ResourcePath, priority: TaskPriority) {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let client = self.client.clone();
+            let timestamp = Instant::now();
+            let filename = path.file_name().to_string();
+            let task = self.tasks.start(format!("Loading {}", filename));
+
+            self.threads.spawn(priority, move || {
+                let data = future::block_on(path.load_binary()).unwrap();
+                let buffer = match VolumeBuffer::from_file(&filename, &data) {
+                    Ok(buffer) => buffer,
+                    Err(error) => {
+                        log::error!("Unable to load volume {}: {}", path, error);
+                        return;
+                    }
+                };
+
+                if task.is_cancelled() {
+                    return;
+                }
+
+                client.load_asset(AssetBuffer::Volume(buffer, Some(filename))).unwrap();
+                log::info!("Loaded {} in {} s", path, timestamp.elapsed().as_secs_f32());
+            });
+        }
+
+        #[cfg(target_family = "wasm")]
+        {
+            match path {
+                ResourcePath::File(_) | ResourcePath::Url(_) => {
+                    self.worker_pool.submit(
+                        LoadTask {
+                            kind: AssetKind::Volume,
+                            path: path.as_serializable().unwrap(),
+                            options: ImportOptions::default(),
+                        },
+                        priority,
+                    );
                 }
                 ResourcePath::Upload(_) => {
-                    self.worker_pool.submit(UploadTask {
-                        kind: AssetKind::EnvironmentMap,
-                        path,
-                    });
+                    self.worker_pool.submit(
+                        UploadTask {
+                            kind: AssetKind::Volume,
+                            path,
+                            options: ImportOptions::default(),
+                        },
+                        priority,
+                    );
                 }
             };
         }
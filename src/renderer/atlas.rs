@@ -0,0 +1,67 @@
+// Rows of rectangles filled left to right, a new row opens below the tallest rectangle of the last one. Inserting
+// from tall to short keeps the rows tight
+pub struct ShelfPacker {
+    size: u32,
+    // Top, height and the width used so far of every row
+    shelves: Vec<(u32, u32, u32)>,
+}
+
+impl ShelfPacker {
+    pub fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    // Top left corner of the rectangle, None once it doesn't fit
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<glam::UVec2> {
+        if width > self.size {
+            return None;
+        }
+
+        for (top, shelf_height, used) in &mut self.shelves {
+            if height <= *shelf_height && *used + width <= self.size {
+                let position = glam::UVec2::new(*used, *top);
+                *used += width;
+                return Some(position);
+            }
+        }
+
+        let top = self.extent().y;
+        if top + height > self.size {
+            return None;
+        }
+        self.shelves.push((top, height, width));
+        Some(glam::UVec2::new(0, top))
+    }
+
+    // Smallest size holding everything inserted
+    pub fn extent(&self) -> glam::UVec2 {
+        self.shelves
+            .iter()
+            .fold(glam::UVec2::ZERO, |extent, &(top, height, used)| {
+                extent.max(glam::UVec2::new(used, top + height))
+            })
+    }
+}
+
+// Copies `tile` to `position` and repeats its outer pixels `gutter` pixels outwards, so filtering at the edge of the
+// tile doesn't bleed in its neighbours
+pub fn blit_tile(atlas: &mut image::RgbaImage, tile: &image::RgbaImage, position: glam::UVec2, gutter: u32) {
+    let (width, height) = tile.dimensions();
+    let gutter = gutter as i64;
+    for y in -gutter..height as i64 + gutter {
+        for x in -gutter..width as i64 + gutter {
+            let (atlas_x, atlas_y) = (position.x as i64 + x, position.y as i64 + y);
+            if atlas_x < 0 || atlas_y < 0 || atlas_x >= atlas.width() as i64 || atlas_y >= atlas.height() as i64 {
+                continue;
+            }
+            let source = tile.get_pixel(
+                x.clamp(0, width as i64 - 1) as u32,
+                y.clamp(0, height as i64 - 1) as u32,
+            );
+            atlas.put_pixel(atlas_x as u32, atlas_y as u32, *source);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::{collections::HashMap, sync::Arc};
+
 use crossbeam::channel::{Receiver, Sender};
 use winit::{event_loop::ActiveEventLoop, window::Window};
 
@@ -6,6 +8,7 @@ use crate::renderer::{
     core::RenderCore,
     surface::{Surface, SurfaceState},
     ui::UiData,
+    viewport::ViewportId,
 };
 
 pub trait RenderBackend {
@@ -16,14 +19,22 @@ pub trait RenderBackend {
     fn request_frame(&mut self, window: &Window, ui: Option<UiData>);
     fn is_configured(&self) -> bool;
     fn exit(&mut self);
+    fn open_viewport(&mut self, window: Arc<Window>) -> Option<ViewportId>;
+    fn close_viewport(&mut self, viewport: ViewportId);
+    fn resize_viewport(&mut self, viewport: ViewportId, width: u32, height: u32);
+    fn request_viewport_frame(&mut self, viewport: ViewportId, window: &Window);
 }
 
 pub struct NativeBackend {
     surface: Surface,
+    viewports: HashMap<ViewportId, Surface>,
+    next_viewport: u32,
     render_tx: Sender<RenderCommand>,
     event_rx: Receiver<RenderEvent>,
     handle: Option<std::thread::JoinHandle<()>>,
     is_running: bool,
+    // Latest size the window asked for, sent on once the previous resize has been applied
+    pending_size: Option<(u32, u32)>,
 }
 
 impl RenderBackend for NativeBackend {
@@ -40,13 +51,46 @@ impl RenderBackend for NativeBackend {
                 RenderEvent::ResizeComplete { config, device } => {
                     self.surface.apply_resize(config, device);
                 }
-                RenderEvent::LoadComplete { .. } => {
+                RenderEvent::LoadComplete { .. }
+                | RenderEvent::AssetReloaded { .. }
+                | RenderEvent::BufferContents { .. }
+                | RenderEvent::Screenshot { .. }
+                | RenderEvent::ScreenshotFailed { .. }
+                | RenderEvent::Aovs(_)
+                | RenderEvent::PickResult { .. }
+                | RenderEvent::Error { .. }
+                | RenderEvent::GpuTimings(_)
+                | RenderEvent::FrameGraph(_)
+                | RenderEvent::MemoryUsage { .. }
+                | RenderEvent::Refining(_)
+                | RenderEvent::LightmapProgress { .. }
+                | RenderEvent::LoadProgress { .. }
+                | RenderEvent::LoadAborted { .. }
+                | RenderEvent::SurfaceConfigured { .. }
+                | RenderEvent::PreviewTexture(_) => {
                     queue.push(event);
                 }
+                RenderEvent::ViewportResized {
+                    viewport,
+                    config,
+                    device,
+                } => {
+                    if let Some(surface) = self.viewports.get_mut(&viewport) {
+                        surface.apply_resize(config, device);
+                    }
+                }
+                RenderEvent::ViewportFrameComplete(viewport) => {
+                    if let Some(surface) = self.viewports.get_mut(&viewport) {
+                        surface.present();
+                    }
+                }
                 RenderEvent::Stopped => {
                     if let Some(handle) = self.handle.take() {
                         match handle.join() {
-                            Ok(_) => self.surface.drop(),
+                            Ok(_) => {
+                                self.viewports.clear();
+                                self.surface.drop();
+                            }
                             Err(error) => log::warn!("Error while terminating renderer {:?}", error),
                         }
 
@@ -55,11 +99,16 @@ impl RenderBackend for NativeBackend {
                 }
             }
         }
+
+        self.flush_resize();
+        if let Some((width, height)) = self.surface.take_configured_size() {
+            queue.push(RenderEvent::SurfaceConfigured { width, height });
+        }
     }
 
     fn resize(&mut self, width: u32, height: u32) {
-        let config = self.surface.request_resize(width, height);
-        self.render_tx.send(RenderCommand::Resize(config)).unwrap();
+        self.pending_size = Some((width, height));
+        self.flush_resize();
     }
 
     fn request_frame(&mut self, window: &Window, ui: Option<UiData>) {
@@ -72,6 +121,9 @@ impl RenderBackend for NativeBackend {
                     let size = window.inner_size();
                     self.resize(size.width, size.height);
                 }
+                Err(wgpu::SurfaceError::Timeout) => {
+                    log::warn!("Timed out acquiring the surface, skipping the frame");
+                }
                 Err(error) => {
                     log::error!("Unable to render surface: {}", error);
                 }
@@ -98,6 +150,68 @@ impl RenderBackend for NativeBackend {
     fn exit(&mut self) {
         self.is_running = false;
     }
+
+    fn open_viewport(&mut self, window: Arc<Window>) -> Option<ViewportId> {
+        let size = window.inner_size();
+        let surface = match self.surface.create_secondary(window) {
+            Ok(surface) => surface,
+            Err(error) => {
+                log::error!("Unable to create viewport surface: {}", error);
+                return None;
+            }
+        };
+
+        let viewport = ViewportId(self.next_viewport);
+        self.next_viewport += 1;
+        self.viewports.insert(viewport, surface);
+        self.resize_viewport(viewport, size.width, size.height);
+
+        Some(viewport)
+    }
+
+    fn close_viewport(&mut self, viewport: ViewportId) {
+        self.viewports.remove(&viewport);
+        self.render_tx.send(RenderCommand::CloseViewport(viewport)).unwrap();
+    }
+
+    fn resize_viewport(&mut self, viewport: ViewportId, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        if let Some(surface) = self.viewports.get_mut(&viewport)
+            && let Some(config) = surface.request_resize(width, height)
+        {
+            self.render_tx
+                .send(RenderCommand::ResizeViewport { viewport, config })
+                .unwrap();
+        }
+    }
+
+    fn request_viewport_frame(&mut self, viewport: ViewportId, window: &Window) {
+        let Some(surface) = self.viewports.get_mut(&viewport) else {
+            return;
+        };
+
+        if !self.is_running || !matches!(surface.state(), SurfaceState::Configured) {
+            return;
+        }
+
+        match surface.acquire() {
+            Ok(view) => {
+                self.render_tx
+                    .send(RenderCommand::RenderViewport { viewport, view })
+                    .unwrap();
+            }
+            Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
+                let size = window.inner_size();
+                self.resize_viewport(viewport, size.width, size.height);
+            }
+            Err(error) => {
+                log::error!("Unable to render viewport surface: {}", error);
+            }
+        }
+    }
 }
 
 impl NativeBackend {
@@ -107,18 +221,37 @@ impl NativeBackend {
         render_tx: Sender<RenderCommand>,
         event_rx: Receiver<RenderEvent>,
     ) -> Self {
-        let join_handle = std::thread::spawn(move || {
-            if let Err(error) = core.run() {
-                log::error!("Renderer encountered an error: {}", error);
-            }
-        });
+        let join_handle = std::thread::Builder::new()
+            .name("render".to_string())
+            .spawn(move || {
+                if let Err(error) = core.run() {
+                    log::error!("Renderer encountered an error: {}", error);
+                }
+            })
+            .expect("Unable to spawn render thread");
 
         Self {
             surface,
+            viewports: HashMap::new(),
+            next_viewport: 0,
             handle: Some(join_handle),
             render_tx,
             event_rx,
             is_running: true,
+            pending_size: None,
+        }
+    }
+
+    // Rapid resizes collapse into the latest size, with at most one on its way through the render thread
+    fn flush_resize(&mut self) {
+        if self.surface.is_resizing() {
+            return;
+        }
+
+        if let Some((width, height)) = self.pending_size.take()
+            && let Some(config) = self.surface.request_resize(width, height)
+        {
+            self.render_tx.send(RenderCommand::Resize(config)).unwrap();
         }
     }
 }
@@ -142,10 +275,16 @@ impl RenderBackend for WasmBackend {
         }
 
         queue.extend(self.event_rx.try_iter());
+        if let Some((width, height)) = self.surface.take_configured_size() {
+            queue.push(RenderEvent::SurfaceConfigured { width, height });
+        }
     }
 
     fn resize(&mut self, width: u32, height: u32) {
-        let config = self.surface.request_resize(width, height);
+        let Some(config) = self.surface.request_resize(width, height) else {
+            return;
+        };
+
         let device = self.core.device();
         self.surface.apply_resize(config.clone(), device.clone());
         self.core.update_config(config);
@@ -160,10 +299,11 @@ impl RenderBackend for WasmBackend {
             Ok(view) => view,
             Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
                 let size = window.inner_size();
-                if size.width > 0 && size.height > 0 {
-                    self.resize(size.width, size.height);
-                }
-
+                self.resize(size.width, size.height);
+                return;
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::warn!("Timed out acquiring the surface, skipping the frame");
                 return;
             }
             Err(error) => {
@@ -172,7 +312,7 @@ impl RenderBackend for WasmBackend {
             }
         };
 
-        self.core.render_frame(view, ui);
+        self.core.scoped("RenderFrame", |core| core.render_frame(view, ui));
         self.surface.present();
     }
 
@@ -187,6 +327,18 @@ impl RenderBackend for WasmBackend {
     fn exit(&mut self) {
         self.is_running = false;
     }
+
+    // The page has a single canvas
+    fn open_viewport(&mut self, _window: Arc<Window>) -> Option<ViewportId> {
+        log::warn!("Additional windows are not supported on the web");
+        None
+    }
+
+    fn close_viewport(&mut self, _viewport: ViewportId) {}
+
+    fn resize_viewport(&mut self, _viewport: ViewportId, _width: u32, _height: u32) {}
+
+    fn request_viewport_frame(&mut self, _viewport: ViewportId, _window: &Window) {}
 }
 
 impl WasmBackend {
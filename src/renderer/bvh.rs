@@ -0,0 +1,492 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::renderer::{
+    animation::JointPalette,
+    mesh::PrimitiveGeometry,
+    ray::{Ray, SurfaceHit},
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    pub const EMPTY: Self = Self {
+        min: glam::Vec3::splat(f32::INFINITY),
+        max: glam::Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn from_triangle(triangle: &[glam::Vec3; 3]) -> Self {
+        Self {
+            min: triangle[0].min(triangle[1]).min(triangle[2]),
+            max: triangle[0].max(triangle[1]).max(triangle[2]),
+        }
+    }
+
+    pub fn grow(&mut self, point: glam::Vec3) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn centroid(&self) -> glam::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn transform(&self, matrix: glam::Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+
+        (0..8).fold(Self::EMPTY, |mut bounds, corner| {
+            let point = glam::Vec3::new(
+                if corner & 1 == 0 { self.min.x } else { self.max.x },
+                if corner & 2 == 0 { self.min.y } else { self.max.y },
+                if corner & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            bounds.grow(matrix.transform_point3(point));
+            bounds
+        })
+    }
+
+    // Slab test, returns the entry distance along the ray
+    pub fn intersect(&self, ray: &Ray, inv_direction: glam::Vec3, max_distance: f32) -> Option<f32> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let t0 = (self.min - ray.origin) * inv_direction;
+        let t1 = (self.max - ray.origin) * inv_direction;
+        let near = t0.min(t1).max_element().max(0.0);
+        let far = t0.max(t1).min_element().min(max_distance);
+
+        (near <= far).then_some(near)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    // Zero inside the box, infinite when it's empty
+    pub fn distance_squared(&self, point: glam::Vec3) -> f32 {
+        (self.min - point)
+            .max(point - self.max)
+            .max(glam::Vec3::ZERO)
+            .length_squared()
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct BvhNode {
+    pub min: [f32; 3],
+    // First primitive for leaves, left child for interior nodes. The right child always follows the left one
+    pub left_first: u32,
+    pub max: [f32; 3],
+    pub count: u32,
+}
+
+impl BvhNode {
+    fn leaf(bounds: Aabb, first: u32, count: u32) -> Self {
+        Self {
+            min: bounds.min.to_array(),
+            max: bounds.max.to_array(),
+            left_first: first,
+            count,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb {
+            min: glam::Vec3::from_array(self.min),
+            max: glam::Vec3::from_array(self.max),
+        }
+    }
+}
+
+// Binned SAH bounding volume hierarchy. Leaves index into `indices`, which is the primitive order after building
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    pub indices: Vec<u32>,
+}
+
+impl Bvh {
+    const BIN_COUNT: usize = 12;
+    const MAX_LEAF_SIZE: u32 = 4;
+
+    pub fn from_triangles(triangles: &[[glam::Vec3; 3]]) -> Self {
+        let bounds = triangles.iter().map(Aabb::from_triangle).collect::<Vec<_>>();
+        Self::build(&bounds)
+    }
+
+    pub fn build(bounds: &[Aabb]) -> Self {
+        let centroids = bounds.iter().map(Aabb::centroid).collect::<Vec<_>>();
+
+        let mut indices = (0..bounds.len() as u32).collect::<Vec<_>>();
+        let mut nodes = Vec::with_capacity(bounds.len().max(1) * 2);
+        let root_bounds = Self::range_bounds(bounds, &indices);
+        nodes.push(BvhNode::leaf(root_bounds, 0, indices.len() as u32));
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = nodes[node_index];
+            if node.count <= Self::MAX_LEAF_SIZE {
+                continue;
+            }
+
+            let first = node.left_first as usize;
+            let range = first..first + node.count as usize;
+            let Some((axis, split)) = Self::find_split(bounds, &centroids, &indices[range.clone()], &node) else {
+                continue;
+            };
+
+            let mut i = range.start;
+            let mut j = range.end;
+            while i < j {
+                if centroids[indices[i] as usize][axis] < split {
+                    i += 1;
+                } else {
+                    j -= 1;
+                    indices.swap(i, j);
+                }
+            }
+
+            let left_count = (i - first) as u32;
+            if left_count == 0 || left_count == node.count {
+                continue;
+            }
+
+            let left_index = nodes.len();
+            let left_bounds = Self::range_bounds(bounds, &indices[first..i]);
+            let right_bounds = Self::range_bounds(bounds, &indices[i..range.end]);
+            nodes.push(BvhNode::leaf(left_bounds, first as u32, left_count));
+            nodes.push(BvhNode::leaf(right_bounds, i as u32, node.count - left_count));
+
+            nodes[node_index].left_first = left_index as u32;
+            nodes[node_index].count = 0;
+
+            stack.push(left_index);
+            stack.push(left_index + 1);
+        }
+
+        Self { nodes, indices }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes[0].bounds()
+    }
+
+    // `intersect` is called with the primitive index and the current closest distance
+    pub fn closest_hit(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        intersect: impl FnMut(u32, f32) -> Option<f32>,
+    ) -> Option<(u32, f32)> {
+        self.traverse(ray, max_distance, false, intersect)
+    }
+
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32, intersect: impl FnMut(u32, f32) -> Option<f32>) -> bool {
+        self.traverse(ray, max_distance, true, intersect).is_some()
+    }
+
+    // `distance_squared` is called with the primitive index and the current closest squared distance, the nearer child
+    // is visited first so the search radius shrinks quickly. Returns the primitive and its distance
+    pub fn nearest(
+        &self,
+        point: glam::Vec3,
+        max_distance: f32,
+        mut distance_squared: impl FnMut(u32, f32) -> Option<f32>,
+    ) -> Option<(u32, f32)> {
+        let mut closest: Option<(u32, f32)> = None;
+        let mut limit = max_distance * max_distance;
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index as usize];
+            // An empty root has no primitives, which would read as an interior node
+            if node.bounds().is_empty() || node.bounds().distance_squared(point) > limit {
+                continue;
+            }
+
+            if node.count == 0 {
+                let [left, right] = [node.left_first, node.left_first + 1];
+                match self.nodes[left as usize].bounds().distance_squared(point)
+                    <= self.nodes[right as usize].bounds().distance_squared(point)
+                {
+                    true => stack.extend([right, left]),
+                    false => stack.extend([left, right]),
+                }
+                continue;
+            }
+
+            for &primitive in &self.indices[node.left_first as usize..(node.left_first + node.count) as usize] {
+                if let Some(distance) = distance_squared(primitive, limit)
+                    && distance <= limit
+                {
+                    closest = Some((primitive, distance));
+                    limit = distance;
+                }
+            }
+        }
+
+        closest.map(|(primitive, distance)| (primitive, distance.sqrt()))
+    }
+
+    fn traverse(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        stop_at_first: bool,
+        mut intersect: impl FnMut(u32, f32) -> Option<f32>,
+    ) -> Option<(u32, f32)> {
+        let inv_direction = ray.direction.recip();
+        let mut closest: Option<(u32, f32)> = None;
+        let mut limit = max_distance;
+        let mut stack = vec![0];
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index as usize];
+            if node.bounds().intersect(ray, inv_direction, limit).is_none() {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack.push(node.left_first);
+                stack.push(node.left_first + 1);
+                continue;
+            }
+
+            for &primitive in &self.indices[node.left_first as usize..(node.left_first + node.count) as usize] {
+                if let Some(distance) = intersect(primitive, limit)
+                    && distance < limit
+                {
+                    closest = Some((primitive, distance));
+                    limit = distance;
+
+                    if stop_at_first {
+                        return closest;
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+
+    fn range_bounds(bounds: &[Aabb], indices: &[u32]) -> Aabb {
+        indices.iter().fold(Aabb::EMPTY, |mut total, &index| {
+            total.merge(&bounds[index as usize]);
+            total
+        })
+    }
+
+    // Returns the split axis and position, or None when keeping the leaf is cheaper
+    fn find_split(bounds: &[Aabb], centroids: &[glam::Vec3], indices: &[u32], node: &BvhNode) -> Option<(usize, f32)> {
+        let centroid_bounds = indices.iter().fold(Aabb::EMPTY, |mut total, &index| {
+            total.grow(centroids[index as usize]);
+            total
+        });
+
+        let mut best: Option<(usize, f32, f32)> = None;
+        for axis in 0..3 {
+            let min = centroid_bounds.min[axis];
+            let extent = centroid_bounds.max[axis] - min;
+            if extent <= f32::EPSILON {
+                continue;
+            }
+
+            let mut bins = [(Aabb::EMPTY, 0u32); Self::BIN_COUNT];
+            for &index in indices {
+                let position = (centroids[index as usize][axis] - min) / extent;
+                let bin = ((position * Self::BIN_COUNT as f32) as usize).min(Self::BIN_COUNT - 1);
+                bins[bin].0.merge(&bounds[index as usize]);
+                bins[bin].1 += 1;
+            }
+
+            let mut left_costs = [0.0; Self::BIN_COUNT - 1];
+            let mut left = (Aabb::EMPTY, 0u32);
+            for bin in 0..Self::BIN_COUNT - 1 {
+                left.0.merge(&bins[bin].0);
+                left.1 += bins[bin].1;
+                left_costs[bin] = left.0.surface_area() * left.1 as f32;
+            }
+
+            let mut right = (Aabb::EMPTY, 0u32);
+            for bin in (1..Self::BIN_COUNT).rev() {
+                right.0.merge(&bins[bin].0);
+                right.1 += bins[bin].1;
+
+                let cost = left_costs[bin - 1] + right.0.surface_area() * right.1 as f32;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    let split = min + extent * bin as f32 / Self::BIN_COUNT as f32;
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        let leaf_cost = node.bounds().surface_area() * node.count as f32;
+        best.filter(|(_, _, cost)| *cost < leaf_cost)
+            .map(|(axis, split, _)| (axis, split))
+    }
+}
+
+// Bottom level hierarchy over the triangles of one renderable, in object space
+pub struct MeshBvh {
+    bvh: Bvh,
+    triangles: Vec<[glam::Vec3; 3]>,
+    normals: Vec<[glam::Vec3; 3]>,
+}
+
+impl MeshBvh {
+    // Skinned primitives are posed by the palette like the vertex shader does, without one they keep their bind pose
+    pub fn new<'a>(
+        geometries: impl IntoIterator<Item = &'a PrimitiveGeometry>,
+        palette: Option<&JointPalette>,
+    ) -> Self {
+        let mut triangles = Vec::new();
+        let mut normals = Vec::new();
+
+        for geometry in geometries {
+            let skins = palette.filter(|_| !geometry.skin_vertices.is_empty()).map(|palette| {
+                geometry
+                    .skin_vertices
+                    .iter()
+                    .map(|vertex| palette.skin(vertex))
+                    .collect::<Vec<_>>()
+            });
+            let vertex = |index: u32| {
+                let vertex = &geometry.vertices[index as usize];
+                let position = glam::Vec3::from_array(vertex.position);
+                let normal = glam::Vec3::from_array(vertex.normal);
+                match skins.as_ref().and_then(|skins| skins.get(index as usize)) {
+                    Some(skin) => (skin.transform_point3(position), skin.transform_vector3(normal)),
+                    None => (position, normal),
+                }
+            };
+
+            for triangle in geometry.indices.chunks_exact(3) {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(vertex);
+                triangles.push([a.0, b.0, c.0]);
+                normals.push([a.1, b.1, c.1]);
+            }
+        }
+
+        Self {
+            bvh: Bvh::from_triangles(&triangles),
+            triangles,
+            normals,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    pub fn closest_hit(&self, ray: &Ray, max_distance: f32) -> Option<SurfaceHit> {
+        let (index, distance) = self.bvh.closest_hit(ray, max_distance, |index, _| {
+            ray.intersect_triangle(&self.triangles[index as usize])
+                .map(|(distance, _)| distance)
+        })?;
+        let (_, barycentric) = ray.intersect_triangle(&self.triangles[index as usize])?;
+
+        let normals = &self.normals[index as usize];
+        let normal = normals[0] * (1.0 - barycentric.x - barycentric.y)
+            + normals[1] * barycentric.x
+            + normals[2] * barycentric.y;
+
+        Some(SurfaceHit {
+            position: ray.at(distance),
+            normal: normal.normalize_or_zero(),
+            distance,
+        })
+    }
+
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.bvh.any_hit(ray, max_distance, |index, _| {
+            ray.intersect_triangle(&self.triangles[index as usize])
+                .map(|(distance, _)| distance)
+        })
+    }
+
+    // Nearest point on the surface within `max_distance`, with the face normal there. The face normal is turned to
+    // the side the vertex normals point to, so it stays outward whatever the winding
+    pub fn closest_point(&self, point: glam::Vec3, max_distance: f32) -> Option<(glam::Vec3, glam::Vec3)> {
+        let (index, _) = self.bvh.nearest(point, max_distance, |index, _| {
+            Some(closest_point_on_triangle(point, &self.triangles[index as usize]).distance_squared(point))
+        })?;
+        let triangle = &self.triangles[index as usize];
+        let normal = (triangle[1] - triangle[0])
+            .cross(triangle[2] - triangle[0])
+            .normalize_or_zero();
+        let normal = match normal.dot(self.normals[index as usize].iter().sum()) < 0.0 {
+            true => -normal,
+            false => normal,
+        };
+        Some((closest_point_on_triangle(point, triangle), normal))
+    }
+}
+
+// From Real-Time Collision Detection, by the region of the triangle the point projects into
+fn closest_point_on_triangle(point: glam::Vec3, [a, b, c]: &[glam::Vec3; 3]) -> glam::Vec3 {
+    let (ab, ac, ap) = (*b - *a, *c - *a, point - *a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return *a;
+    }
+
+    let bp = point - *b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return *b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return *a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - *c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return *c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return *a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return *b + (*c - *b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    // Inside the face, degenerate triangles end up here too
+    let denominator = va + vb + vc;
+    if denominator.abs() <= f32::EPSILON {
+        return *a;
+    }
+    *a + ab * (vb / denominator) + ac * (vc / denominator)
+}
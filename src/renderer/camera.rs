@@ -1,6 +1,6 @@
 use wgpu::util::DeviceExt;
 
-use crate::renderer::context::RenderContext;
+use crate::renderer::{context::RenderContext, hdr::HdrPipeline, settings::SettingsBuffer};
 
 pub struct Camera {
     uniform: CameraUniform,
@@ -10,7 +10,12 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new(context: &RenderContext) -> Self {
+    pub fn new(context: &RenderContext, settings: &SettingsBuffer) -> Self {
+        Self::with_hdr(&context.hdr, settings, context)
+    }
+
+    // Secondary viewports bind their own scene color
+    pub fn with_hdr(hdr: &HdrPipeline, settings: &SettingsBuffer, context: &RenderContext) -> Self {
         let uniform = CameraUniform::new();
         let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera buffer"),
@@ -34,14 +39,7 @@ impl Camera {
         //         }],
         //     });
 
-        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera bind group"),
-            layout: &context.camera_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: buffer.as_entire_binding(),
-            }],
-        });
+        let bind_group = Self::create_bind_group(&buffer, hdr, settings, context);
 
         Self {
             uniform,
@@ -51,24 +49,95 @@ impl Camera {
         }
     }
 
-    pub fn update(&mut self, position: glam::Vec3, view: glam::Mat4, projection: glam::Mat4, context: &RenderContext) {
+    // Returns whether the camera actually moved
+    pub fn update(
+        &mut self,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+        context: &RenderContext,
+    ) -> bool {
+        let previous = self.uniform;
         self.uniform.update(position, view, projection);
+        if previous == self.uniform {
+            return false;
+        }
+
         context
             .queue
             .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+        true
     }
 
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
 
+    pub fn view_projection(&self) -> glam::Mat4 {
+        glam::Mat4::from_cols_array_2d(&self.uniform.view_projection)
+    }
+
+    pub fn position(&self) -> glam::Vec3 {
+        glam::Vec4::from_array(self.uniform.view_position).truncate()
+    }
+
+    // The scene color texture is recreated on resize, so the bind group has to follow
+    pub fn rebind(&mut self, settings: &SettingsBuffer, context: &RenderContext) {
+        self.rebind_hdr(&context.hdr, settings, context);
+    }
+
+    pub fn rebind_hdr(&mut self, hdr: &HdrPipeline, settings: &SettingsBuffer, context: &RenderContext) {
+        self.bind_group = Self::create_bind_group(&self.buffer, hdr, settings, context);
+    }
+
+    fn create_bind_group(
+        buffer: &wgpu::Buffer,
+        hdr: &HdrPipeline,
+        settings: &SettingsBuffer,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
+        let scene_color = hdr.scene_color();
+        let ramp = settings.ramp().texture();
+
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Camera bind group"),
+            layout: &context.camera_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: settings.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(scene_color.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(scene_color.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(ramp.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(ramp.sampler()),
+                },
+            ],
+        })
+    }
+
     // pub fn layout(&self) -> &wgpu::BindGroupLayout {
     //     &self.layout
     // }
 }
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 struct CameraUniform {
     view_position: [f32; 4],
     view_projection: [[f32; 4]; 4],
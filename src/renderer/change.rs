@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use crate::renderer::{
+    bvh::MeshBvh,
+    pointcloud::{PointGrid, PointcloudBuffer},
+};
+
+// Checked between batches of points, so a cancelled comparison stops quickly
+const CANCEL_CHECK_INTERVAL: usize = 65_536;
+
+// What a scan is compared against, with its transform
+pub enum DistanceReference {
+    Points(Arc<PointcloudBuffer>, glam::Mat4),
+    Mesh(Arc<MeshBvh>, glam::Mat4),
+}
+
+impl DistanceReference {
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Self::Mesh(..))
+    }
+}
+
+// Distance from every point of a scan to the nearest point of a reference scan or the surface of a reference mesh,
+// usually an earlier epoch of the same site or the design model. Both are compared in world space, so they should be
+// registered first
+#[derive(Clone, Debug)]
+pub struct PointDistances {
+    // In the order of the compared scan's points, None where nothing of the reference is within the search radius
+    pub distances: Vec<Option<f32>>,
+    // Against a mesh, points behind the surface are negative
+    pub is_signed: bool,
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub matched: usize,
+}
+
+impl PointDistances {
+    // None when cancelled or the reference is empty
+    pub fn compute(
+        compared: &PointcloudBuffer,
+        compared_transform: glam::Mat4,
+        reference: &DistanceReference,
+        max_distance: f32,
+        is_cancelled: impl Fn() -> bool,
+    ) -> Option<Self> {
+        match reference {
+            DistanceReference::Points(points, transform) => Self::to_points(
+                compared,
+                compared_transform,
+                points,
+                *transform,
+                max_distance,
+                is_cancelled,
+            ),
+            DistanceReference::Mesh(mesh, transform) => Self::to_mesh(
+                compared,
+                compared_transform,
+                mesh,
+                *transform,
+                max_distance,
+                is_cancelled,
+            ),
+        }
+    }
+
+    // Exact within `max_distance`, the grid cells are that size and every neighbouring cell is searched
+    fn to_points(
+        compared: &PointcloudBuffer,
+        compared_transform: glam::Mat4,
+        reference: &PointcloudBuffer,
+        reference_transform: glam::Mat4,
+        max_distance: f32,
+        is_cancelled: impl Fn() -> bool,
+    ) -> Option<Self> {
+        crate::profile_scope!("Point distances");
+        let reference_positions = reference
+            .points()
+            .iter()
+            .map(|point| reference_transform.transform_point3(glam::Vec3::from_array(point.position)))
+            .collect::<Vec<_>>();
+        if reference_positions.is_empty() {
+            return None;
+        }
+
+        let grid = PointGrid::with_cell_size(&reference_positions, max_distance);
+        let distances = Self::measure(compared, compared_transform, is_cancelled, |position| {
+            let nearest = *grid.nearest(&reference_positions, position, 1).first()?;
+            let distance = reference_positions[nearest as usize].distance(position);
+            (distance <= max_distance).then_some(distance)
+        })?;
+        Some(Self::from_distances(distances, false))
+    }
+
+    // Signed by the side of the surface the point is on. The search runs in the mesh's object space, a non-uniform
+    // scale may pick a triangle that isn't quite the nearest in world space
+    fn to_mesh(
+        compared: &PointcloudBuffer,
+        compared_transform: glam::Mat4,
+        reference: &MeshBvh,
+        reference_transform: glam::Mat4,
+        max_distance: f32,
+        is_cancelled: impl Fn() -> bool,
+    ) -> Option<Self> {
+        crate::profile_scope!("Mesh distances");
+        if reference.bounds().is_empty() {
+            return None;
+        }
+
+        let inverse = reference_transform.inverse();
+        let normal_matrix = glam::Mat3::from_mat4(inverse.transpose());
+        // The Frobenius norm bounds how far the world radius can reach in object space, in any direction
+        let linear = glam::Mat3::from_mat4(inverse);
+        let local_distance = max_distance
+            * (linear.x_axis.length_squared() + linear.y_axis.length_squared() + linear.z_axis.length_squared()).sqrt();
+        let distances = Self::measure(compared, compared_transform, is_cancelled, |position| {
+            let (closest, normal) = reference.closest_point(inverse.transform_point3(position), local_distance)?;
+            let offset = position - reference_transform.transform_point3(closest);
+            let distance = offset.length();
+            let sign = match offset.dot(normal_matrix * normal) < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            };
+            (distance <= max_distance).then_some(distance * sign)
+        })?;
+        Some(Self::from_distances(distances, true))
+    }
+
+    // Counts of the matched distances in `bins` equal steps between the smallest and largest
+    pub fn histogram(&self, bins: usize) -> Vec<u32> {
+        let bins = bins.max(1);
+        let mut histogram = vec![0; bins];
+        let range = (self.max - self.min).max(f32::EPSILON);
+        for distance in self.distances.iter().flatten() {
+            let bin = ((distance - self.min) / range * bins as f32) as usize;
+            histogram[bin.min(bins - 1)] += 1;
+        }
+        histogram
+    }
+
+    // None when cancelled
+    fn measure(
+        compared: &PointcloudBuffer,
+        compared_transform: glam::Mat4,
+        is_cancelled: impl Fn() -> bool,
+        distance: impl Fn(glam::Vec3) -> Option<f32>,
+    ) -> Option<Vec<Option<f32>>> {
+        let mut distances = Vec::with_capacity(compared.points().len());
+        for batch in compared.points().chunks(CANCEL_CHECK_INTERVAL) {
+            if is_cancelled() {
+                return None;
+            }
+
+            distances.extend(
+                batch
+                    .iter()
+                    .map(|point| distance(compared_transform.transform_point3(glam::Vec3::from_array(point.position)))),
+            );
+        }
+        Some(distances)
+    }
+
+    fn from_distances(distances: Vec<Option<f32>>, is_signed: bool) -> Self {
+        let matched = distances.iter().flatten().count();
+        let mean = distances.iter().flatten().sum::<f32>() / matched.max(1) as f32;
+        let (min, max) = match matched {
+            0 => (0.0, 0.0),
+            _ => distances
+                .iter()
+                .flatten()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &distance| {
+                    (min.min(distance), max.max(distance))
+                }),
+        };
+        Self {
+            distances,
+            is_signed,
+            min,
+            max,
+            mean,
+            matched,
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crossbeam::channel::Sender;
+use uuid::Uuid;
+
+use crate::renderer::{RenderCommand, asset::AssetBuffer};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("The render thread has stopped")]
+    Disconnected,
+    #[error("The render thread is {0} commands behind")]
+    Busy(usize),
+}
+
+// Cloneable handle that issues render commands from any thread. Commands of one client arrive in the order they were
+// sent, those of different clients interleave
+#[derive(Clone)]
+pub struct RendererClient {
+    render_tx: Sender<RenderCommand>,
+}
+
+impl RendererClient {
+    // Queue length from which `try_send` turns commands away
+    pub const MAX_QUEUED: usize = 1024;
+
+    pub(super) fn new(render_tx: Sender<RenderCommand>) -> Self {
+        Self { render_tx }
+    }
+
+    // Random, so clients never have to coordinate their ids
+    pub fn allocate_id(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+
+    // Commands the render thread hasn't picked up yet, from every client
+    pub fn queued(&self) -> usize {
+        self.render_tx.len()
+    }
+
+    pub fn send(&self, command: RenderCommand) -> Result<(), ClientError> {
+        self.render_tx.send(command).map_err(|_| ClientError::Disconnected)
+    }
+
+    // For commands a newer one supersedes, like cursor updates, which can be dropped while the render thread catches up
+    pub fn try_send(&self, command: RenderCommand) -> Result<(), ClientError> {
+        match self.queued() {
+            queued if queued >= Self::MAX_QUEUED => Err(ClientError::Busy(queued)),
+            _ => self.send(command),
+        }
+    }
+
+    pub fn load_asset(&self, buffer: AssetBuffer) -> Result<(), ClientError> {
+        self.send(RenderCommand::LoadAsset(buffer))
+    }
+
+    pub fn update_transform(&self, entity_id: Uuid, transform: glam::Mat4) -> Result<(), ClientError> {
+        self.send(RenderCommand::UpdateTransform { entity_id, transform })
+    }
+
+    pub fn update_sketch(&self, source: String) -> Result<(), ClientError> {
+        self.send(RenderCommand::UpdateSketch(source))
+    }
+
+    pub fn update_cursor(&self, position: glam::Vec2) -> Result<(), ClientError> {
+        self.try_send(RenderCommand::UpdateCursor(position))
+    }
+}
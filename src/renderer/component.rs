@@ -16,7 +16,7 @@ impl<T> Clone for ComponentId<T> {
 }
 
 impl<T> ComponentId<T> {
-    pub fn new(index: usize) -> Self {
+    pub const fn new(index: usize) -> Self {
         Self(index as u32, PhantomData)
     }
 
@@ -283,7 +283,8 @@ fn create_buffer<T>(capacity: usize, context: &RenderContext) -> wgpu::Buffer {
     context.device.create_buffer(&wgpu::BufferDescriptor {
         label: Some("Component storage buffer"),
         size: (capacity * std::mem::size_of::<T>()) as u64,
-        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        // Copy source for the buffer inspector
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
         mapped_at_creation: false,
     })
 }
@@ -303,8 +304,9 @@ fn create_bind_group(
     })
 }
 
+// Removed components are dropped right away, along with the GPU resources they hold
 pub struct HostComponentStore<T> {
-    components: Vec<T>,
+    components: Vec<Option<T>>,
     index_map: HashMap<Uuid, usize>,
     free_indices: Vec<usize>,
 }
@@ -320,16 +322,16 @@ impl<T> HostComponentStore<T> {
 
     pub fn add(&mut self, key: Uuid, component: T) -> ComponentId<T> {
         if let Some(&index) = self.index_map.get(&key) {
-            self.components[index] = component;
+            self.components[index] = Some(component);
             return ComponentId::new(index);
         }
 
         let index = if let Some(free) = self.free_indices.pop() {
-            self.components[free] = component;
+            self.components[free] = Some(component);
             free
         } else {
             let index = self.components.len();
-            self.components.push(component);
+            self.components.push(Some(component));
             index
         };
 
@@ -339,24 +341,43 @@ impl<T> HostComponentStore<T> {
 
     pub fn remove(&mut self, key: &Uuid) {
         if let Some(index) = self.index_map.remove(key) {
+            self.components[index] = None;
+            self.free_indices.push(index);
+        }
+    }
+
+    pub fn remove_by_id(&mut self, id: ComponentId<T>) {
+        let index = id.index() as usize;
+        let count = self.index_map.len();
+        self.index_map.retain(|_, mapped| *mapped != index);
+        if self.index_map.len() != count {
+            self.components[index] = None;
             self.free_indices.push(index);
         }
     }
 
     pub fn get(&self, key: &Uuid) -> Option<&T> {
-        self.index_map.get(key).map(|&index| &self.components[index])
+        self.index_map
+            .get(key)
+            .and_then(|&index| self.components[index].as_ref())
     }
 
     pub fn get_mut(&mut self, key: &Uuid) -> Option<&mut T> {
-        self.index_map.get(key).map(|&index| &mut self.components[index])
+        self.index_map
+            .get(key)
+            .and_then(|&index| self.components[index].as_mut())
     }
 
     pub fn get_by_id(&self, id: ComponentId<T>) -> Option<&T> {
-        self.components.get(id.index() as usize)
+        self.components.get(id.index() as usize).and_then(Option::as_ref)
+    }
+
+    pub fn get_by_id_mut(&mut self, id: ComponentId<T>) -> Option<&mut T> {
+        self.components.get_mut(id.index() as usize).and_then(Option::as_mut)
     }
 
     pub fn get_by_index(&self, index: usize) -> Option<&T> {
-        self.components.get(index)
+        self.components.get(index).and_then(Option::as_ref)
     }
 
     pub fn get_index(&self, key: &Uuid) -> Option<ComponentId<T>> {
@@ -366,10 +387,11 @@ impl<T> HostComponentStore<T> {
     pub fn iter_with_index(&self) -> impl Iterator<Item = (&Uuid, usize, &T)> {
         self.index_map
             .iter()
-            .map(|(key, &index)| (key, index, &self.components[index]))
+            .filter_map(|(key, &index)| Some((key, index, self.components[index].as_ref()?)))
     }
 
-    pub fn components(&self) -> &Vec<T> {
+    // Every slot in index order, None for the free ones
+    pub fn components(&self) -> &[Option<T>] {
         &self.components
     }
 }
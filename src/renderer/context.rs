@@ -1,8 +1,11 @@
 use std::cell::OnceCell;
 
-use crate::renderer::{hdr::HdrPipeline, texture::Texture};
+use crate::renderer::{
+    hdr::HdrPipeline, material::TextureInstanceSlot, memory::MemoryBudget, texture::Texture, timer::GpuTimer,
+};
 
 pub struct RenderContext {
+    pub adapter_info: wgpu::AdapterInfo,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -11,19 +14,21 @@ pub struct RenderContext {
     pub camera_bind_group_layout: wgpu::BindGroupLayout,
     pub depth_texture: Texture,
     pub pending_resize: Option<wgpu::SurfaceConfiguration>,
-    pub placeholder_texture: OnceCell<Texture>,
+    pub placeholder_textures: [OnceCell<Texture>; Self::TEXTURE_COUNT],
     pub hdr: HdrPipeline,
+    pub memory: MemoryBudget,
 }
 
 impl RenderContext {
     pub const MAX_UV_SETS: usize = 6;
-    pub const TEXTURE_COUNT: usize = 5;
+    pub const TEXTURE_COUNT: usize = 8;
 
     pub async fn new(adapter: &wgpu::Adapter, config: wgpu::SurfaceConfiguration) -> anyhow::Result<Self> {
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
+                // Only Vulkan can serialize compiled pipelines, timestamps are optional and only used for pass timings
+                required_features: adapter.features() & (wgpu::Features::PIPELINE_CACHE | GpuTimer::FEATURES),
                 required_limits: if cfg!(target_family = "wasm") {
                     wgpu::Limits::downlevel_defaults()
                 } else {
@@ -38,7 +43,7 @@ impl RenderContext {
         let mut bind_group_layout_entries = Vec::new();
         bind_group_layout_entries.push(wgpu::BindGroupLayoutEntry {
             binding: 0,
-            visibility: wgpu::ShaderStages::FRAGMENT,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
             ty: wgpu::BindingType::Buffer {
                 ty: wgpu::BufferBindingType::Uniform,
                 has_dynamic_offset: false,
@@ -48,10 +53,17 @@ impl RenderContext {
         });
 
         (0..Self::TEXTURE_COUNT).for_each(|index| {
+            // The height map is sampled in the vertex shader for displacement
+            let visibility = if index == TextureInstanceSlot::Height as usize {
+                wgpu::ShaderStages::VERTEX_FRAGMENT
+            } else {
+                wgpu::ShaderStages::FRAGMENT
+            };
+
             bind_group_layout_entries.extend_from_slice(&[
                 wgpu::BindGroupLayoutEntry {
                     binding: (index * 2 + 1) as u32,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
                         view_dimension: wgpu::TextureViewDimension::D2,
@@ -61,7 +73,7 @@ impl RenderContext {
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: (index * 2 + 2) as u32,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
@@ -76,9 +88,10 @@ impl RenderContext {
         let environment_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Environment map bind group layout"),
             entries: &[
+                // The path tracer samples the environment map from a compute shader
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Texture {
                         sample_type: wgpu::TextureSampleType::Float { filterable: false },
                         view_dimension: wgpu::TextureViewDimension::Cube,
@@ -88,7 +101,7 @@ impl RenderContext {
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
                     count: None,
                 },
@@ -108,28 +121,156 @@ impl RenderContext {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                // Reflection probes, blended over the environment map near their position
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::CubeArray,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Irradiance volume coefficients and grid
+                wgpu::BindGroupLayoutEntry {
+                    binding: 7,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 8,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                // Prefiltered specular mip chain and the split sum BRDF lookup table
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 10,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 11,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 12,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
             ],
         });
 
         let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Camera bind group layout"),
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            }],
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                // Color ramp lookup table
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
         });
 
-        let placeholder_texture = OnceCell::new();
+        let placeholder_textures = Default::default();
         let depth_texture = Texture::create_depth_texture(&device, &config, Some("Depth texture"));
         let hdr = HdrPipeline::new(&device, &config);
+        let memory = MemoryBudget::new(&adapter.get_info());
 
         Ok(Self {
+            adapter_info: adapter.get_info(),
             device,
             queue,
             config,
@@ -138,15 +279,15 @@ impl RenderContext {
             camera_bind_group_layout,
             depth_texture,
             pending_resize: None,
-            placeholder_texture,
+            placeholder_textures,
             hdr,
+            memory,
         })
     }
 
-    pub fn placeholder_texture(&self) -> Texture {
-        let texture = self
-            .placeholder_texture
-            .get_or_init(|| Texture::create_placeholder(&self.device, &self.queue));
+    pub fn placeholder_texture(&self, slot: TextureInstanceSlot) -> Texture {
+        let texture = self.placeholder_textures[slot as usize]
+            .get_or_init(|| Texture::create_placeholder(&self.device, &self.queue, slot.placeholder_color()));
 
         texture.clone()
     }
@@ -1,54 +1,142 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+};
+
 use crossbeam::channel::{Receiver, Sender};
 use egui_wgpu::Renderer as EguiRenderer;
+#[cfg(not(target_family = "wasm"))]
+use futures_lite::future;
 use uuid::Uuid;
 
 use crate::renderer::{
     RenderCommand, RenderEvent,
-    asset::AssetBuffer,
+    accumulation::Accumulation,
+    aov::{AovPass, AovReadback},
+    asset::{AssetBuffer, AssetStats, ImportMode},
+    bvh::Aabb,
     camera::Camera,
     context::RenderContext,
     environment::{EnvironmentMap, HdrLoader},
-    instance::Instance,
-    light::{Light, LightUniform},
-    mesh::{MeshVertex, Scene, TextureCoordinate},
-    pipeline::PipelineCache,
-    pointcloud::{PointVertex, Pointcloud},
-    scene::{DrawScene, RenderId, SceneGraph},
-    texture::Texture,
+    ghost::GhostPass,
+    graph::{FrameGraph, FrameGraphLayout, Slot, SlotResource, TransientDesc, TransientTextures},
+    irradiance_volume::IrradianceVolume,
+    layers::RenderLayers,
+    light::Light,
+    lightmap::Lightmapper,
+    memory::MemoryUsage,
+    mesh::{Mesh, NodeMetadata, Scene},
+    ods::StereoPanorama,
+    outline::SelectionOutline,
+    path_tracer::PathTracer,
+    pick::{PickPass, PickReadback},
+    pipeline::{PipelineCache, PipelineKey},
+    pointcloud::{MAT4_SWAP_YZ, PointVertex, Pointcloud, PointcloudBuffer},
+    preview::Preview,
+    probe::ReflectionProbes,
+    query::SceneQuery,
+    readback::{BufferReadback, InspectedBuffer, MAX_SCREENSHOT_TILES, TextureReadback, TiledReadback},
+    scene::{DrawScene, Geometry, RenderId, Renderable, SceneGraph, ScenePass},
+    settings::{RenderMode, RenderSettings, SettingsBuffer},
+    shadow::ShadowPass,
+    sketch::ShaderSketch,
+    splatting::PointSplats,
+    timer::{GpuTimer, PassTimestamps},
     transform::TransformUniform,
     ui::UiData,
-    vertex::VertexLayoutBuilder,
+    viewport::{Viewport, ViewportId},
+    volume::VolumeRenderer,
 };
 
-const MAT4_SWAP_YZ: glam::Mat4 = glam::Mat4::from_cols_array(&[
-    1.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
-]);
-
 pub struct Frame {
     encoder: wgpu::CommandEncoder,
     view: wgpu::TextureView,
+    timestamps: Option<PassTimestamps>,
 }
 
 impl Frame {
-    pub fn new(view: wgpu::TextureView, device: &wgpu::Device) -> Self {
-        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+    pub fn new(view: wgpu::TextureView, context: &RenderContext) -> Self {
+        let encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Render encoder"),
         });
 
-        Self { encoder, view }
+        Self {
+            encoder,
+            view,
+            timestamps: None,
+        }
+    }
+
+    pub fn begin_pass(&mut self, name: &'static str) -> bool {
+        self.timestamps
+            .as_mut()
+            .is_some_and(|timestamps| timestamps.begin_pass(name, &mut self.encoder))
+    }
+
+    pub fn end_pass(&mut self) {
+        if let Some(timestamps) = &mut self.timestamps {
+            timestamps.end_pass(&mut self.encoder);
+        }
     }
 
     pub fn finish(self) -> wgpu::CommandBuffer {
         self.encoder.finish()
     }
 }
+
+// A pointcloud drawn while the rest of its file is still being decoded. Without a render id it didn't fit the memory
+// budget, and waits for the whole cloud to queue like any other load
+struct PointStream {
+    ids: Option<(Uuid, RenderId)>,
+    label: String,
+    stats: AssetStats,
+}
+
 pub struct RenderCore {
     is_running: bool,
     context: RenderContext,
     camera: Camera,
+    render_settings: RenderSettings,
+    settings: SettingsBuffer,
+    accumulation: Accumulation,
+    point_splats: PointSplats,
+    path_tracer: PathTracer,
+    sketch: ShaderSketch,
     scene: SceneGraph,
+    scene_query: SceneQuery,
+    is_query_dirty: bool,
     pipeline_cache: PipelineCache,
+    transients: TransientTextures,
+    readbacks: Vec<BufferReadback>,
+    // Keyed by the id of the capture command they answer
+    screenshots: Vec<(u64, TextureReadback)>,
+    tiled_screenshots: Vec<(u64, TiledReadback)>,
+    aovs: Vec<AovReadback>,
+    picks: Vec<PickReadback>,
+    queued_loads: VecDeque<AssetBuffer>,
+    point_streams: HashMap<Uuid, PointStream>,
+    memory_report: (MemoryUsage, usize),
+    is_refining: bool,
+    gpu_timer: Option<GpuTimer>,
+    is_timing: bool,
+    is_reporting_graph: bool,
+    // Last one sent to the app
+    graph_layout: FrameGraphLayout,
+    volume: VolumeRenderer,
+    outline: SelectionOutline,
+    aov: AovPass,
+    picking: PickPass,
+    ghosts: GhostPass,
+    shadows: ShadowPass,
+    lightmapper: Lightmapper,
+    viewports: HashMap<ViewportId, Viewport>,
+    // Of the main window, the preview inset shares it
+    layers: RenderLayers,
+    preview: Option<Preview>,
     egui_renderer: EguiRenderer,
+    // Popped error scopes that haven't resolved yet, polled on every command instead of blocking on them
+    #[cfg(not(target_family = "wasm"))]
+    error_scopes: Vec<future::Boxed<()>>,
     render_rx: Receiver<RenderCommand>,
     result_tx: Sender<RenderEvent>,
 }
@@ -59,231 +147,192 @@ impl RenderCore {
         render_receiver: Receiver<RenderCommand>,
         error_sender: Sender<RenderEvent>,
     ) -> anyhow::Result<Self> {
-        let camera = Camera::new(&context);
+        let render_settings = RenderSettings::default();
+        let settings = SettingsBuffer::new(&render_settings, &context);
+        let camera = Camera::new(&context, &settings);
+        let accumulation = Accumulation::new(&context);
+        let point_splats = PointSplats::new(&context);
+        let path_tracer = PathTracer::new(&context);
+        let sketch = ShaderSketch::new(&context);
+        let volume = VolumeRenderer::new(&context);
+        let gpu_timer = GpuTimer::new(&context);
         let egui_renderer = EguiRenderer::new(
             &context.device,
             context.config.format.add_srgb_suffix(),
             Default::default(),
         );
         let scene = SceneGraph::new(&context);
-        let mut pipeline_cache = PipelineCache::new();
-
-        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/shader.wgsl").into()),
-        });
-
-        let pointcloud_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Pointcloud shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/pc_shader.wgsl").into()),
-        });
-
-        let light_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Light shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/light.wgsl").into()),
-        });
-
-        let render_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render pipeline layout"),
-            bind_group_layouts: &[
-                &context.texture_bind_group_layout,
-                &context.camera_bind_group_layout,
-                scene.layout(),
-                &context.environment_bind_group_layout,
-            ],
-            push_constant_ranges: &[],
-        });
-
-        let mesh_vertex_layout = (0..RenderContext::MAX_UV_SETS)
-            .fold(VertexLayoutBuilder::new().push::<MeshVertex>(), |builder, _| {
-                builder.push::<TextureCoordinate>()
-            })
-            .push::<Instance>()
-            .build();
-
-        let render_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &mesh_vertex_layout,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: context.hdr.format(),
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        let pointcloud_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Pointcloud pipeline layout"),
-            bind_group_layouts: &[
-                &context.texture_bind_group_layout,
-                &context.camera_bind_group_layout,
-                scene.layout(),
-            ],
-            push_constant_ranges: &[],
-        });
-
-        let pointcloud_vertex_layout = VertexLayoutBuilder::new()
-            .push::<PointVertex>()
-            .push::<Instance>()
-            .build();
-
-        let pointcloud_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Pointcloud pipeline"),
-            layout: Some(&pointcloud_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &pointcloud_shader,
-                entry_point: Some("vs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                buffers: &pointcloud_vertex_layout,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &pointcloud_shader,
-                entry_point: Some("fs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: context.hdr.format(),
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::PointList,
-                cull_mode: None,
-                ..Default::default()
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        let light_debug_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Debug light pipeline layout"),
-            bind_group_layouts: &[
-                &context.texture_bind_group_layout,
-                &context.camera_bind_group_layout,
-                scene.layout(),
-            ],
-            push_constant_ranges: &[],
-        });
-
-        let light_debug_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Light debug pipeline"),
-            layout: Some(&light_debug_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &light_shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &mesh_vertex_layout,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &light_shader,
-                entry_point: Some("fs_main"),
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: context.hdr.format(),
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
-
-        pipeline_cache.insert("mesh", render_pipeline);
-        pipeline_cache.insert("pointcloud", pointcloud_pipeline);
-        pipeline_cache.insert("light", light_debug_pipeline);
+        let outline = SelectionOutline::new(scene.layout(), &context);
+        let aov = AovPass::new(scene.layout(), &context);
+        let picking = PickPass::new(scene.layout(), &context);
+        let ghosts = GhostPass::new(scene.layout(), &context);
+        let shadows = ShadowPass::new(&context);
+        let lightmapper = Lightmapper::new(path_tracer.scene_layout(), &context);
+        let mut pipeline_cache = PipelineCache::new(&context, scene.layout());
+        pipeline_cache.warmup([PipelineKey::MESH, PipelineKey::POINTCLOUD, PipelineKey::LIGHT]);
 
         Ok(Self {
             is_running: true,
             context,
             camera,
+            render_settings,
+            settings,
+            accumulation,
+            point_splats,
+            path_tracer,
+            sketch,
             scene,
+            scene_query: SceneQuery::default(),
+            is_query_dirty: false,
             pipeline_cache,
+            transients: TransientTextures::default(),
+            readbacks: Vec::new(),
+            screenshots: Vec::new(),
+            tiled_screenshots: Vec::new(),
+            aovs: Vec::new(),
+            picks: Vec::new(),
+            queued_loads: VecDeque::new(),
+            point_streams: HashMap::new(),
+            memory_report: Default::default(),
+            is_refining: false,
+            gpu_timer,
+            is_timing: false,
+            is_reporting_graph: false,
+            graph_layout: FrameGraphLayout::default(),
+            volume,
+            outline,
+            aov,
+            picking,
+            ghosts,
+            shadows,
+            lightmapper,
+            viewports: HashMap::new(),
+            layers: RenderLayers::ALL,
+            preview: None,
             egui_renderer,
+            #[cfg(not(target_family = "wasm"))]
+            error_scopes: Vec::new(),
             render_rx: render_receiver,
             result_tx: error_sender,
         })
     }
 
+    pub fn scene_query(&self) -> SceneQuery {
+        self.scene_query.clone()
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         &self.context.device
     }
 
+    #[cfg(test)]
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.context.queue
+    }
+
+    #[cfg(test)]
+    pub fn is_compiling(&self) -> bool {
+        self.pipeline_cache.is_compiling()
+    }
+
+    // Uploads that would go over the memory budget wait until something is freed, or the user forces them through.
+    // Later loads queue behind them so assets still arrive in order
+    fn queue_load(&mut self, asset: AssetBuffer) -> anyhow::Result<()> {
+        let size = asset.estimated_size();
+        let label = asset.label().unwrap_or("Asset").to_string();
+        if self.context.memory.exceeds_budget(size) {
+            self.result_tx.send(RenderEvent::Error {
+                label: "LoadAsset",
+                message: format!(
+                    "{} needs about {} MiB, more than the GPU memory budget of {} MiB",
+                    label,
+                    size / (1024 * 1024),
+                    self.context.memory.usage().budget_mib(),
+                ),
+            })?;
+            return Ok(());
+        }
+
+        if !self.queued_loads.is_empty() || !self.context.memory.fits(size) {
+            log::warn!("{} is waiting for GPU memory", label);
+            self.queued_loads.push_back(asset);
+            return Ok(());
+        }
+
+        self.load_asset(asset)
+    }
+
+    fn retry_queued_loads(&mut self) -> anyhow::Result<()> {
+        while let Some(asset) = self.queued_loads.front()
+            && self.context.memory.fits(asset.estimated_size())
+        {
+            let asset = self.queued_loads.pop_front().unwrap();
+            self.load_asset(asset)?;
+        }
+
+        Ok(())
+    }
+
+    fn report_memory(&mut self) -> anyhow::Result<()> {
+        let report = (self.context.memory.usage(), self.queued_loads.len());
+        if report != self.memory_report {
+            if report.0.is_near_limit() && !self.memory_report.0.is_near_limit() {
+                log::warn!(
+                    "GPU memory estimate at {} of {} MiB",
+                    report.0.allocated_mib(),
+                    report.0.budget_mib()
+                );
+            }
+
+            self.memory_report = report;
+            self.result_tx.send(RenderEvent::MemoryUsage {
+                usage: report.0,
+                queued_loads: report.1,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn report_refining(&mut self) -> anyhow::Result<()> {
+        let is_converged = match self.render_settings.render_mode {
+            RenderMode::Raster => {
+                (!self.render_settings.is_accumulating() || self.accumulation.is_converged())
+                    && self.point_splats.is_complete()
+            }
+            RenderMode::PathTraced => self.path_tracer.is_converged(),
+            // Animated by time and the cursor
+            RenderMode::Sketch => false,
+        };
+
+        // Batches are skipped until their pipeline is ready
+        let is_refining = !is_converged
+            || self.lightmapper.is_baking()
+            || self.pipeline_cache.is_compiling()
+            || self.scene.is_animating();
+        if is_refining != self.is_refining {
+            self.is_refining = is_refining;
+            self.result_tx.send(RenderEvent::Refining(is_refining))?;
+        }
+
+        Ok(())
+    }
+
     fn load_asset(&mut self, asset: AssetBuffer) -> anyhow::Result<()> {
         match asset {
             AssetBuffer::EnvironmentMap { buffer, label } => {
                 let loader = HdrLoader::new(&self.context.device);
                 let texture = loader.from_buffer(buffer, 1080, label.as_deref(), &self.context)?;
-                let mut environment_map = EnvironmentMap::new(texture, &self.context);
-                environment_map.compute_irradiance(&self.context);
+                let mut environment_map = EnvironmentMap::new(
+                    texture,
+                    &self.scene.probes,
+                    &self.scene.irradiance_volume,
+                    &self.context,
+                );
+                environment_map.compute_irradiance(&self.scene.probes, &self.scene.irradiance_volume, &self.context);
                 self.scene.set_environment_map(environment_map);
             }
-            AssetBuffer::Scene(buffer, label) => {
+            AssetBuffer::Scene(buffer, label, report, mode) => {
                 let scene = Scene::from_buffer(buffer, &self.context, label.clone());
                 let material_ids = scene
                     .materials
@@ -291,39 +340,312 @@ impl RenderCore {
                     .map(|material| self.scene.add_material(material))
                     .collect::<Vec<_>>();
 
+                // The report and the mode belong to the asset, not to each node, so only the first one carries them
+                let mut report = Some(report);
+                let mut replaces_scene = mode == ImportMode::Replace;
+                let asset_id = Uuid::new_v4();
                 for node in scene.nodes {
+                    let stats = mesh_stats(&node.mesh);
                     let render_id = self.scene.add_mesh(node.mesh, &material_ids);
+                    self.scene.set_skeleton(render_id, node.skeleton, &self.context);
                     self.result_tx.send(RenderEvent::LoadComplete {
                         render_id,
+                        asset_id,
                         transform: Some(node.transform),
                         label: label.clone(),
+                        report: report.take(),
+                        replaces_scene: std::mem::take(&mut replaces_scene),
+                        stats,
+                        metadata: node.metadata,
                     })?;
                 }
+
+                self.update_sketch_attributes();
             }
             AssetBuffer::Pointcloud(buffer, label) => {
+                let bounds = pointcloud_bounds(&buffer);
                 let pointcloud = Pointcloud::from_buffer(buffer, &self.context, label.clone());
-                let render_id = self.scene.add_pointcloud(pointcloud);
+                self.add_pointcloud(pointcloud, label, bounds)?;
+            }
+            AssetBuffer::Volume(buffer, label) => {
+                self.volume.set_volume(buffer, label.as_deref(), &self.context);
+            }
+        }
 
-                self.result_tx.send(RenderEvent::LoadComplete {
-                    render_id,
-                    transform: Some(MAT4_SWAP_YZ),
-                    label,
-                })?;
+        Ok(())
+    }
+
+    fn add_pointcloud(
+        &mut self,
+        pointcloud: Pointcloud,
+        label: Option<String>,
+        bounds: Aabb,
+    ) -> anyhow::Result<(Uuid, RenderId)> {
+        let stats = AssetStats {
+            points: pointcloud.num_points,
+            bounds,
+            ..Default::default()
+        };
+        let render_id = self.scene.add_pointcloud(pointcloud);
+        let asset_id = Uuid::new_v4();
+
+        self.result_tx.send(RenderEvent::LoadComplete {
+            render_id,
+            asset_id,
+            transform: Some(MAT4_SWAP_YZ),
+            label,
+            report: None,
+            replaces_scene: false,
+            stats,
+            metadata: NodeMetadata::default(),
+        })?;
+        Ok((asset_id, render_id))
+    }
+
+    fn reload_asset(&mut self, asset_id: Uuid, render_ids: Vec<RenderId>, asset: AssetBuffer) -> anyhow::Result<()> {
+        match asset {
+            AssetBuffer::Scene(buffer, label, report, _) => {
+                let scene = Scene::from_buffer(buffer, &self.context, label.clone());
+                let material_ids = scene
+                    .materials
+                    .into_iter()
+                    .map(|material| self.scene.add_material(material))
+                    .collect::<Vec<_>>();
+
+                let mut report = Some(report);
+                let mut previous_materials = Vec::new();
+                for (index, node) in scene.nodes.into_iter().enumerate() {
+                    let stats = mesh_stats(&node.mesh);
+                    match render_ids.get(index) {
+                        Some(&render_id) => {
+                            previous_materials.extend(self.scene.replace_mesh(render_id, node.mesh, &material_ids));
+                            self.scene.set_skeleton(render_id, node.skeleton, &self.context);
+                            self.result_tx.send(RenderEvent::AssetReloaded { render_id, stats })?;
+                        }
+                        None => {
+                            let render_id = self.scene.add_mesh(node.mesh, &material_ids);
+                            self.scene.set_skeleton(render_id, node.skeleton, &self.context);
+                            self.result_tx.send(RenderEvent::LoadComplete {
+                                render_id,
+                                asset_id,
+                                transform: Some(node.transform),
+                                label: label.clone(),
+                                report: report.take(),
+                                replaces_scene: false,
+                                stats,
+                                metadata: node.metadata,
+                            })?;
+                        }
+                    }
+                }
+
+                self.scene.remove_unused_materials(previous_materials);
+                self.scene.build_render_batches(&self.context);
+                self.update_sketch_attributes();
+            }
+            AssetBuffer::Pointcloud(buffer, label) => {
+                let Some(&render_id) = render_ids.first() else {
+                    return self.load_asset(AssetBuffer::Pointcloud(buffer, label));
+                };
+
+                let bounds = pointcloud_bounds(&buffer);
+                let pointcloud = Pointcloud::from_buffer(buffer, &self.context, label);
+                let stats = AssetStats {
+                    points: pointcloud.num_points,
+                    bounds,
+                    ..Default::default()
+                };
+                self.scene.replace_pointcloud(render_id, pointcloud);
+                self.scene.build_render_batches(&self.context);
+                self.result_tx.send(RenderEvent::AssetReloaded { render_id, stats })?;
             }
+            // Loading these replaces the previous one already
+            asset @ (AssetBuffer::EnvironmentMap { .. } | AssetBuffer::Volume(..)) => self.load_asset(asset)?,
         }
 
         Ok(())
     }
 
+    // In the order of their slots in the geometry store, which is load order
+    fn update_sketch_attributes(&mut self) {
+        let mut geometries = self
+            .scene
+            .geometries
+            .iter_with_index()
+            .filter_map(|(_, index, geometry)| match geometry {
+                Geometry::Primitive(primitive) => Some((index, primitive.geometry.as_ref())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        geometries.sort_by_key(|(index, _)| *index);
+        self.sketch
+            .set_custom_attributes(geometries.into_iter().map(|(_, geometry)| geometry), &self.context);
+    }
+
     fn spawn_asset(&mut self, entity_id: Uuid, render_id: RenderId, transform: glam::Mat4) {
         self.scene.add_node(entity_id, render_id, transform, &self.context);
     }
 
+    fn despawn_asset(&mut self, entity_id: Uuid) {
+        self.lightmapper.clear(entity_id, &mut self.scene, &self.context);
+        self.scene.remove_node(entity_id, &self.context);
+    }
+
+    fn unload_asset(&mut self, render_ids: &[RenderId]) {
+        let entities = self
+            .scene
+            .nodes
+            .iter_with_index()
+            .filter(|(_, _, render_id)| render_ids.contains(render_id))
+            .map(|(entity_id, _, _)| *entity_id)
+            .collect::<Vec<_>>();
+        for entity_id in entities {
+            self.lightmapper.clear(entity_id, &mut self.scene, &self.context);
+        }
+
+        self.scene.unload(render_ids, &self.context);
+        self.update_sketch_attributes();
+    }
+
+    fn classify_points(&mut self, render_id: RenderId, changes: &[(u32, u8)]) {
+        let Some(Renderable::Pointcloud(handle)) = self.scene.renderables.get(&render_id) else {
+            return;
+        };
+
+        if let Some(Geometry::Pointcloud(pointcloud)) = self.scene.geometries.get_by_id(handle.geometry_index) {
+            pointcloud.classify(changes, &self.context);
+        }
+    }
+
+    // Keeps the label of the points it replaces
+    fn replace_points(&mut self, render_id: RenderId, buffer: PointcloudBuffer) {
+        let Some(Renderable::Pointcloud(handle)) = self.scene.renderables.get(&render_id) else {
+            return;
+        };
+        let Some(Geometry::Pointcloud(previous)) = self.scene.geometries.get_by_id(handle.geometry_index) else {
+            return;
+        };
+
+        let pointcloud = Pointcloud::from_buffer(buffer, &self.context, previous.label.clone());
+        self.scene.replace_pointcloud(render_id, pointcloud);
+        self.scene.build_render_batches(&self.context);
+    }
+
+    // The first chunk sizes the buffers for `total` points, charging all of them to the memory budget up front. When
+    // they don't fit, or other loads already wait for memory, the chunks aren't drawn and the whole cloud is queued
+    // once it's decoded
+    fn stream_points(
+        &mut self,
+        stream_id: Uuid,
+        buffer: Arc<PointcloudBuffer>,
+        total: u32,
+        label: Option<String>,
+        progress: f32,
+    ) -> anyhow::Result<()> {
+        let bounds = pointcloud_bounds(&buffer);
+        let points = buffer.points().len() as u32;
+        match self.point_streams.get_mut(&stream_id) {
+            Some(stream) => {
+                let Some((_, render_id)) = stream.ids else {
+                    return self.report_stream_progress(stream_id, progress);
+                };
+                let Some(Renderable::Pointcloud(handle)) = self.scene.renderables.get(&render_id) else {
+                    return Ok(());
+                };
+                if let Some(Geometry::Pointcloud(pointcloud)) =
+                    self.scene.geometries.get_by_id_mut(handle.geometry_index)
+                    && pointcloud.append(&buffer, &self.context)
+                {
+                    stream.stats.points += points;
+                    stream.stats.bounds.merge(&bounds);
+                    self.result_tx.send(RenderEvent::AssetReloaded {
+                        render_id,
+                        stats: stream.stats,
+                    })?;
+                }
+            }
+            None => {
+                let size = total as u64 * std::mem::size_of::<PointVertex>() as u64;
+                let ids = if self.queued_loads.is_empty() && self.context.memory.fits(size) {
+                    let pointcloud = Pointcloud::with_capacity(buffer, total, &self.context, label.clone());
+                    Some(self.add_pointcloud(pointcloud, label.clone(), bounds)?)
+                } else {
+                    None
+                };
+                let stream = PointStream {
+                    ids,
+                    label: label.unwrap_or_default(),
+                    stats: AssetStats {
+                        points,
+                        bounds,
+                        ..Default::default()
+                    },
+                };
+                self.point_streams.insert(stream_id, stream);
+            }
+        }
+
+        self.report_stream_progress(stream_id, progress)
+    }
+
+    fn report_stream_progress(&self, stream_id: Uuid, progress: f32) -> anyhow::Result<()> {
+        if let Some(stream) = self.point_streams.get(&stream_id) {
+            self.result_tx.send(RenderEvent::LoadProgress {
+                label: stream.label.clone(),
+                percent: progress * 100.0,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn finish_point_stream(&mut self, stream_id: Uuid, buffer: Option<PointcloudBuffer>) -> anyhow::Result<()> {
+        let Some(stream) = self.point_streams.remove(&stream_id) else {
+            return Ok(());
+        };
+
+        match (stream.ids, buffer) {
+            // Unless the cloud was removed while it streamed in
+            (Some((_, render_id)), Some(buffer)) => {
+                if self.scene.renderables.get(&render_id).is_some() {
+                    let stats = AssetStats {
+                        points: buffer.points().len() as u32,
+                        bounds: pointcloud_bounds(&buffer),
+                        ..Default::default()
+                    };
+                    self.replace_points(render_id, buffer);
+                    self.result_tx.send(RenderEvent::AssetReloaded { render_id, stats })?;
+                }
+            }
+            (Some((asset_id, _)), None) => self.result_tx.send(RenderEvent::LoadAborted { asset_id })?,
+            (None, Some(buffer)) => {
+                self.queue_load(AssetBuffer::Pointcloud(buffer, Some(stream.label.clone())))?;
+            }
+            (None, None) => {}
+        }
+        self.result_tx.send(RenderEvent::LoadProgress {
+            label: stream.label,
+            percent: 100.0,
+        })?;
+        Ok(())
+    }
+
     fn spawn_light(&mut self, entity_id: Uuid, light: Light) {
         self.scene.add_light(entity_id, light, &self.context);
     }
 
-    pub fn render_scene(&self, frame: &mut Frame) {
+    fn render_opaque(&self, frame: &mut Frame, pass: ScenePass) {
+        // Premultiplied, so page content shows through a transparent canvas on the web
+        let clear_color = if self.render_settings.transparent_background {
+            wgpu::Color::TRANSPARENT
+        } else {
+            wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            }
+        };
+
         let mut render_pass = frame.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Render pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -331,12 +653,7 @@ impl RenderCore {
                 resolve_target: None,
                 depth_slice: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.1,
-                        g: 0.2,
-                        b: 0.3,
-                        a: 1.0,
-                    }),
+                    load: wgpu::LoadOp::Clear(clear_color),
                     store: wgpu::StoreOp::Store,
                 },
             })],
@@ -352,7 +669,46 @@ impl RenderCore {
             timestamp_writes: None,
         });
 
-        render_pass.draw_scene(&self.scene, &self.camera.bind_group(), &self.pipeline_cache);
+        if !self.render_settings.transparent_background {
+            render_pass.set_pipeline(self.scene.environment_map.pipeline());
+            render_pass.set_bind_group(0, self.scene.environment_map.bind_group(), &[]);
+            render_pass.set_bind_group(1, self.camera.bind_group(), &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        render_pass.draw_scene(&self.scene, &self.camera.bind_group(), &self.pipeline_cache, pass);
+    }
+
+    fn render_transmissive(&self, frame: &mut Frame) {
+        let mut render_pass = frame.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Transmission render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.context.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.draw_scene(
+            &self.scene,
+            &self.camera.bind_group(),
+            &self.pipeline_cache,
+            ScenePass::Transmissive,
+        );
     }
 
     pub fn render_ui(&mut self, frame: &mut Frame, ui: UiData) {
@@ -414,36 +770,888 @@ impl RenderCore {
         render_pass.draw(0..3, 0..1);
     }
 
+    fn add_raster_passes(&mut self, graph: &mut FrameGraph<Self>) {
+        let accumulate = self.render_settings.is_accumulating();
+        let splat = self
+            .point_splats
+            .prepare(&self.scene, self.render_settings.points_per_frame);
+
+        // Averaging frames that are still missing points would leave holes in the result, the history starts over
+        // until every slice is in
+        if accumulate && !self.point_splats.is_complete() {
+            self.accumulation.reset();
+        }
+
+        if accumulate {
+            // The frame index seeds the stochastic environment samples
+            self.settings
+                .update(&self.render_settings, self.accumulation.frame_count(), &self.context);
+        }
+
+        if !accumulate || !self.accumulation.is_converged() {
+            if self.scene.shadow_maps.has_casters() {
+                self.add_shadow_pass(graph);
+            }
+            graph.add_pass("Opaque", &[Slot::ShadowMaps], &[Slot::Hdr, Slot::Depth], move |core, frame| {
+                let pass = if splat { ScenePass::Meshes } else { ScenePass::Opaque };
+                core.render_opaque(frame, pass);
+            });
+
+            if splat {
+                graph.add_pass("Point splats", &[], &[Slot::Splats], |core, frame| {
+                    core.point_splats.render(
+                        &mut frame.encoder,
+                        &core.scene,
+                        core.camera.bind_group(),
+                        &core.pipeline_cache,
+                    );
+                });
+                graph.add_pass(
+                    "Point splat composite",
+                    &[Slot::Hdr, Slot::Depth, Slot::Splats],
+                    &[Slot::Hdr, Slot::Depth],
+                    |core, frame| core.point_splats.composite(&mut frame.encoder, &core.context),
+                );
+            }
+
+            if self.scene.has_transmissive() {
+                graph.add_pass("Scene color copy", &[Slot::Hdr], &[Slot::SceneColor], |core, frame| {
+                    core.context.hdr.copy_to_scene_color(&mut frame.encoder);
+                });
+                graph.add_pass(
+                    "Transmissive",
+                    &[Slot::Hdr, Slot::Depth, Slot::SceneColor, Slot::ShadowMaps],
+                    &[Slot::Hdr, Slot::Depth],
+                    |core, frame| core.render_transmissive(frame),
+                );
+            }
+
+            if self.render_settings.show_irradiance_probes && self.scene.irradiance_volume.grid().is_some() {
+                graph.add_pass(
+                    "Irradiance probes",
+                    &[Slot::Hdr, Slot::Depth],
+                    &[Slot::Hdr, Slot::Depth],
+                    |core, frame| {
+                        core.scene
+                            .irradiance_volume
+                            .render_probes(&mut frame.encoder, &core.camera, &core.context)
+                    },
+                );
+            }
+
+            if self.volume.has_volume() {
+                graph.add_pass("Volume", &[Slot::Hdr, Slot::Depth], &[Slot::Hdr], |core, frame| {
+                    core.volume.render(&mut frame.encoder, &core.camera, &core.context);
+                });
+            }
+
+            if self.scene.has_ghosts() {
+                self.add_ghost_pass(graph);
+            }
+        }
+
+        if accumulate {
+            graph.add_pass(
+                "Accumulate",
+                &[Slot::Hdr, Slot::History],
+                &[Slot::Hdr, Slot::History],
+                |core, frame| core.accumulation.accumulate(&mut frame.encoder, &core.context),
+            );
+        }
+    }
+
+    // Everything a slot of the main frame's graph stands for
+    fn slot_resources(&self, slot: Slot) -> Vec<SlotResource> {
+        match slot {
+            Slot::Surface => vec![SlotResource::Texture {
+                format: self.context.config.format,
+                size: wgpu::Extent3d {
+                    width: self.context.config.width,
+                    height: self.context.config.height,
+                    depth_or_array_layers: 1,
+                },
+            }],
+            Slot::Hdr => vec![SlotResource::texture(&self.context.hdr.texture().texture)],
+            Slot::Depth => vec![SlotResource::texture(&self.context.depth_texture.texture)],
+            Slot::SceneColor => vec![SlotResource::texture(&self.context.hdr.scene_color().texture)],
+            Slot::History => self
+                .accumulation
+                .history()
+                .iter()
+                .map(|history| SlotResource::texture(&history.texture))
+                .collect(),
+            Slot::PathSamples => vec![SlotResource::Buffer {
+                size: self.path_tracer.accumulation_buffer().size(),
+            }],
+            Slot::Splats => self
+                .point_splats
+                .targets()
+                .map(|target| SlotResource::texture(&target.texture))
+                .to_vec(),
+            Slot::ShadowMaps => self.scene.shadow_maps.textures().map(SlotResource::texture).to_vec(),
+            Slot::Transient(name) => self
+                .transients
+                .texture(name)
+                .map(SlotResource::texture)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn add_shadow_pass(&self, graph: &mut FrameGraph<Self>) {
+        graph.add_pass("Shadows", &[], &[Slot::ShadowMaps], |core, frame| {
+            core.shadows.render(&mut frame.encoder, &core.scene, &core.context);
+        });
+    }
+
+    fn add_ghost_pass(&self, graph: &mut FrameGraph<Self>) {
+        graph.add_pass("Ghosts", &[Slot::Hdr, Slot::Depth], &[Slot::Hdr], |core, frame| {
+            core.ghosts
+                .render(&mut frame.encoder, &core.scene, core.camera.bind_group(), &core.context);
+        });
+    }
+
+    fn add_outline_passes(&mut self, graph: &mut FrameGraph<Self>) {
+        let mask = graph.create_texture(
+            "Selection mask",
+            TransientDesc {
+                format: SelectionOutline::MASK_FORMAT,
+                scale: 1.0,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        );
+
+        graph.add_pass("Selection mask", &[], &[mask], |core, frame| {
+            let mask = core.transients.view("Selection mask").unwrap();
+            core.outline
+                .render_mask(&mut frame.encoder, &core.scene, core.camera.bind_group(), mask);
+        });
+        graph.add_pass("Outline", &[Slot::Surface, mask], &[Slot::Surface], |core, frame| {
+            let mask = core.transients.view("Selection mask").unwrap();
+            core.outline
+                .render_outline(&mut frame.encoder, mask, &frame.view, &core.context);
+        });
+    }
+
     pub fn render_frame(&mut self, view: wgpu::TextureView, ui: Option<UiData>) {
+        crate::profile_scope!("Render frame");
+        // Moving skins invalidate the accumulated frames like a moving camera does
+        if self.scene.animate(&self.context) {
+            self.accumulation.reset();
+            self.is_query_dirty |= self.render_settings.animated_picking;
+        }
+        self.scene.set_view_layers(self.layers, &self.context);
+        self.update_shadows();
         self.scene.sync(&self.context);
+        self.cull_scene();
+        self.pipeline_cache
+            .prepare(self.scene.render_batches.iter().map(|batch| batch.key.pipeline));
+        if self.is_query_dirty {
+            crate::profile_scope!("Rebuild scene query");
+            self.scene_query.rebuild(&self.scene);
+            self.is_query_dirty = false;
+        }
 
-        let mut frame = Frame::new(view, &self.context.device);
-        self.render_scene(&mut frame);
-        self.render_hdr(&mut frame);
+        // Drawn ahead of the main frame so egui shows it in the same frame
+        self.render_preview();
 
+        let mut graph = FrameGraph::<Self>::new();
+        match self.render_settings.render_mode {
+            RenderMode::Raster => self.add_raster_passes(&mut graph),
+            RenderMode::PathTraced => {
+                graph.add_pass("Path trace", &[], &[Slot::PathSamples], |core, frame| {
+                    // wgpu has a single queue per device, so tracing can't overlap with the raster work of the frame
+                    core.path_tracer.trace(&core.scene, &mut frame.encoder, &core.context);
+                });
+                graph.add_pass("Path resolve", &[Slot::PathSamples], &[Slot::Hdr], |core, frame| {
+                    core.path_tracer.resolve(&mut frame.encoder, &core.context);
+                });
+            }
+            RenderMode::Sketch => {
+                self.add_raster_passes(&mut graph);
+                graph.add_pass("Scene color copy", &[Slot::Hdr], &[Slot::SceneColor], |core, frame| {
+                    core.context.hdr.copy_to_scene_color(&mut frame.encoder);
+                });
+                graph.add_pass(
+                    "Sketch",
+                    &[Slot::SceneColor, Slot::Depth],
+                    &[Slot::Hdr],
+                    |core, frame| core.sketch.render(&mut frame.encoder, &core.context),
+                );
+            }
+        }
+
+        graph.add_pass("Tone map", &[Slot::Hdr], &[Slot::Surface], |core, frame| {
+            core.render_hdr(frame);
+        });
+        if self.scene.has_selection() {
+            self.add_outline_passes(&mut graph);
+        }
         if let Some(data) = ui {
-            self.render_ui(&mut frame, data);
+            graph.add_pass("Egui", &[Slot::Surface], &[Slot::Surface], move |core, frame| {
+                core.render_ui(frame, data);
+            });
+        }
+
+        self.transients.prepare(graph.transients(), &self.context);
+        if self.is_reporting_graph {
+            let layout = graph.layout(|slot| self.slot_resources(slot));
+            if layout != self.graph_layout {
+                self.graph_layout = layout.clone();
+                let _ = self.result_tx.send(RenderEvent::FrameGraph(layout));
+            }
+        }
+        let mut frame = Frame::new(view, &self.context);
+        if self.is_timing {
+            frame.timestamps = self.gpu_timer.as_ref().map(GpuTimer::begin_frame);
+        }
+        graph.execute(self, &mut frame);
+
+        if let (Some(timer), Some(timestamps)) = (&mut self.gpu_timer, frame.timestamps.take()) {
+            timer.end_frame(timestamps, &mut frame.encoder, &self.context);
+        }
+
+        {
+            crate::profile_scope!("Submit");
+            self.context.queue.submit(Some(frame.finish()));
+        }
+
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.after_submit();
         }
 
+        if let Err(error) = self.poll_readbacks() {
+            log::error!("Unable to send buffer contents: {}", error);
+        }
+    }
+
+    // Secondary windows get the opaque and transmissive passes, no accumulation, path tracing or UI
+    fn render_viewport(&mut self, viewport_id: ViewportId, view: wgpu::TextureView) {
+        if let Some(mut viewport) = self.viewports.remove(&viewport_id) {
+            self.draw_viewport(&mut viewport, view);
+            self.viewports.insert(viewport_id, viewport);
+        }
+    }
+
+    fn render_preview(&mut self) {
+        let Some((mut viewport, view)) = self.preview.as_mut().and_then(Preview::take_due) else {
+            return;
+        };
+
+        crate::profile_scope!("Preview");
+        viewport.set_layers(self.layers);
+        self.draw_viewport(&mut viewport, view);
+        // The cascades were fit to the preview's camera
+        self.update_shadows();
+        if let Some(preview) = &mut self.preview {
+            preview.restore(viewport);
+        }
+    }
+
+    fn draw_viewport(&mut self, viewport: &mut Viewport, view: wgpu::TextureView) {
+        viewport.swap(&mut self.camera, &mut self.context);
+        self.scene.set_view_layers(viewport.layers(), &self.context);
+        self.update_shadows();
+        self.scene.sync(&self.context);
+        self.cull_scene();
+        self.pipeline_cache
+            .prepare(self.scene.render_batches.iter().map(|batch| batch.key.pipeline));
+
+        let mut graph = FrameGraph::<Self>::new();
+        if self.scene.shadow_maps.has_casters() {
+            self.add_shadow_pass(&mut graph);
+        }
+        graph.add_pass("Opaque", &[Slot::ShadowMaps], &[Slot::Hdr, Slot::Depth], |core, frame| {
+            core.render_opaque(frame, ScenePass::Opaque);
+        });
+        if self.scene.has_transmissive() {
+            graph.add_pass("Scene color copy", &[Slot::Hdr], &[Slot::SceneColor], |core, frame| {
+                core.context.hdr.copy_to_scene_color(&mut frame.encoder);
+            });
+            graph.add_pass(
+                "Transmissive",
+                &[Slot::Hdr, Slot::Depth, Slot::SceneColor, Slot::ShadowMaps],
+                &[Slot::Hdr, Slot::Depth],
+                |core, frame| core.render_transmissive(frame),
+            );
+        }
+        if self.scene.has_ghosts() {
+            self.add_ghost_pass(&mut graph);
+        }
+        graph.add_pass("Tone map", &[Slot::Hdr], &[Slot::Surface], |core, frame| {
+            core.render_hdr(frame);
+        });
+
+        let mut frame = Frame::new(view, &self.context);
+        graph.execute(self, &mut frame);
         self.context.queue.submit(Some(frame.finish()));
+
+        viewport.swap(&mut self.camera, &mut self.context);
+    }
+
+    fn place_probe(&mut self, probe_id: Uuid, position: glam::Vec3, radius: f32) -> anyhow::Result<()> {
+        if !self.scene.probes.place(probe_id, position, radius) {
+            self.result_tx.send(RenderEvent::Error {
+                label: "PlaceProbe",
+                message: "All reflection probe slots are in use".to_string(),
+            })?;
+            return Ok(());
+        }
+
+        self.capture_probes(&[probe_id]);
+        Ok(())
+    }
+
+    fn capture_probes(&mut self, probe_ids: &[Uuid]) {
+        crate::profile_scope!("Probe capture");
+        let config = ReflectionProbes::capture_config(self.context.config.format);
+        let mut viewport = Viewport::new(config, &self.settings, &self.context);
+        self.scene.probes.upload(false, &self.context);
+
+        for probe_id in probe_ids {
+            let Some(position) = self.scene.probes.position(probe_id) else {
+                continue;
+            };
+
+            self.capture_faces(&mut viewport, position, |core, encoder, face, _| {
+                core.scene
+                    .probes
+                    .store_face(encoder, probe_id, face, core.context.hdr.view(), &core.context);
+            });
+
+            let mut encoder = self
+                .context
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Probe mip encoder"),
+                });
+            self.scene.probes.generate_mips(&mut encoder, probe_id, &self.context);
+            self.context.queue.submit(Some(encoder.finish()));
+        }
+
+        self.scene.probes.upload(true, &self.context);
+    }
+
+    fn bake_irradiance_volume(&mut self) {
+        let Some(grid) = self.scene.irradiance_volume.grid() else {
+            self.scene.irradiance_volume.upload(false, &self.context);
+            return;
+        };
+
+        crate::profile_scope!("Irradiance bake");
+        let config = IrradianceVolume::capture_config(self.context.config.format);
+        let mut viewport = Viewport::new(config, &self.settings, &self.context);
+        self.scene.irradiance_volume.upload(false, &self.context);
+
+        for (index, position) in grid.positions().into_iter().enumerate() {
+            self.capture_faces(&mut viewport, position, |core, encoder, face, view| {
+                core.scene.irradiance_volume.bake_face(
+                    encoder,
+                    index as u32,
+                    face,
+                    view,
+                    core.context.hdr.view(),
+                    &core.context,
+                );
+            });
+        }
+
+        self.scene.irradiance_volume.upload(true, &self.context);
+    }
+
+    fn bake_lightmap(&mut self, entity_id: Uuid, resolution: u32, samples: u32) -> anyhow::Result<()> {
+        self.path_tracer.prepare_scene(&self.scene, &self.context);
+        if let Err(error) = self
+            .lightmapper
+            .begin(entity_id, resolution, samples, &self.scene, &self.context)
+        {
+            self.result_tx.send(RenderEvent::Error {
+                label: "BakeLightmap",
+                message: error.to_string(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // A few samples per texel each frame keep the window responsive while the lightmap converges
+    fn step_lightmap(&mut self) -> anyhow::Result<()> {
+        if !self.lightmapper.is_baking() {
+            return Ok(());
+        }
+
+        crate::profile_scope!("Lightmap bake");
+        self.path_tracer.prepare_scene(&self.scene, &self.context);
+        let mut encoder = self
+            .context
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Lightmap encoder"),
+            });
+        let progress = self.lightmapper.step(
+            &mut encoder,
+            self.path_tracer.triangle_count(),
+            self.path_tracer.scene_bind_group(),
+            self.scene.environment_map.bind_group(),
+            &self.context,
+        );
+        self.context.queue.submit(Some(encoder.finish()));
+
+        if self.lightmapper.is_converged() {
+            self.lightmapper
+                .finish(self.path_tracer.scene_bind_group(), &mut self.scene, &self.context);
+            self.accumulation.reset();
+        }
+
+        if let Some((entity_id, samples, total)) = progress {
+            self.result_tx.send(RenderEvent::LightmapProgress {
+                entity_id,
+                samples,
+                total,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // Cascades follow whichever camera is swapped in, like the culling
+    fn update_shadows(&mut self) {
+        self.scene
+            .update_shadows(self.camera.view_projection(), self.camera.position(), &self.context);
+    }
+
+    // Against whichever camera is swapped in, viewports and cube faces included
+    fn cull_scene(&mut self) {
+        let view_projection = self
+            .render_settings
+            .frustum_culling
+            .then(|| self.camera.view_projection());
+        self.scene.cull(view_projection, &self.context);
+    }
+
+    // Renders the opaque scene into each cube face from `position`, through a viewport like the preview. `store` reads
+    // the face out of the HDR target before the next one overwrites it
+    fn capture_faces(
+        &mut self,
+        viewport: &mut Viewport,
+        position: glam::Vec3,
+        store: impl Fn(&Self, &mut wgpu::CommandEncoder, u32, glam::Mat4),
+    ) {
+        self.scene.set_view_layers(RenderLayers::CAPTURE, &self.context);
+        for (face, view) in ReflectionProbes::face_views(position).into_iter().enumerate() {
+            viewport.update_camera(position, view, ReflectionProbes::projection(), &self.context);
+            viewport.swap(&mut self.camera, &mut self.context);
+            self.scene.sync(&self.context);
+            self.cull_scene();
+            self.pipeline_cache
+                .prepare(self.scene.render_batches.iter().map(|batch| batch.key.pipeline));
+
+            let mut frame = Frame::new(self.context.hdr.view().clone(), &self.context);
+            self.render_opaque(&mut frame, ScenePass::Opaque);
+            store(self, &mut frame.encoder, face as u32, view);
+            self.context.queue.submit(Some(frame.finish()));
+            viewport.swap(&mut self.camera, &mut self.context);
+        }
+    }
+
+    // None turns the preview off, otherwise it renders every `interval` frames
+    fn set_preview(&mut self, interval: Option<u32>) -> anyhow::Result<()> {
+        match (interval, &mut self.preview) {
+            (Some(interval), Some(preview)) => preview.set_interval(interval),
+            (Some(interval), None) => {
+                let preview = Preview::new(interval, &self.settings, &self.context, &mut self.egui_renderer);
+                self.result_tx.send(RenderEvent::PreviewTexture(preview.texture_id()))?;
+                self.preview = Some(preview);
+            }
+            (None, _) => {
+                if let Some(preview) = self.preview.take() {
+                    preview.free(&mut self.egui_renderer);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn resize_viewport(&mut self, viewport_id: ViewportId, config: wgpu::SurfaceConfiguration) {
+        match self.viewports.get_mut(&viewport_id) {
+            Some(viewport) => viewport.resize(config, &self.settings, &self.context),
+            None => {
+                let viewport = Viewport::new(config, &self.settings, &self.context);
+                self.viewports.insert(viewport_id, viewport);
+            }
+        }
     }
 
     pub fn update_camera(&mut self, position: glam::Vec3, view: glam::Mat4, projection: glam::Mat4) {
-        self.camera.update(position, view, projection, &self.context);
+        self.path_tracer.update_camera(position, view, projection);
+        if self.camera.update(position, view, projection, &self.context) {
+            self.accumulation.reset();
+            self.point_splats.reset();
+            self.path_tracer.reset();
+        }
     }
 
     pub fn update_config(&mut self, config: wgpu::SurfaceConfiguration) {
         self.context.resize(config);
+        self.camera.rebind(&self.settings, &self.context);
+        self.accumulation.resize(&self.context);
+        self.point_splats.resize(&self.context);
+        self.path_tracer.resize(&self.context);
+        self.sketch.resize(&self.context);
+        self.volume.resize(&self.context);
+    }
+
+    pub fn update_settings(&mut self, settings: RenderSettings) {
+        self.settings.update(&settings, 0, &self.context);
+        self.settings.update_ramp(&settings, &self.context);
+        self.path_tracer.set_max_bounces(settings.max_bounces);
+        self.scene
+            .shadow_maps
+            .set_resolution(settings.shadow_map_size, &self.context);
+        let camera = &settings.physical_camera;
+        self.context
+            .hdr
+            .set_tone_map(camera.exposure(), camera.lens_distortion(), &self.context.queue);
+        self.render_settings = settings;
+    }
+
+    fn inspect_buffer(&mut self, buffer: InspectedBuffer) {
+        let (source, count) = match buffer {
+            InspectedBuffer::Transforms => (self.scene.transforms.buffer(), self.scene.transforms.components().len()),
+            InspectedBuffer::Normals => (self.scene.normals.buffer(), self.scene.normals.components().len()),
+            InspectedBuffer::Lights => (self.scene.lights.buffer(), self.scene.lights.components().len()),
+            InspectedBuffer::Instances => {
+                let count = self
+                    .scene
+                    .render_batches
+                    .iter()
+                    .map(|batch| batch.instance_range().end)
+                    .max()
+                    .unwrap_or(0);
+                (self.scene.instance_pool.buffer(), count as usize)
+            }
+        };
+
+        self.readbacks
+            .push(BufferReadback::new(buffer, source, count, &self.context));
+    }
+
+    // The frame is rendered again into an offscreen target without the UI, the surface texture can't be copied from
+    fn capture_screenshot(&mut self, id: u64) {
+        let texture = self.render_offscreen();
+        self.screenshots
+            .push((id, TextureReadback::new(&texture, &self.context)));
+    }
+
+    fn capture_aovs(&mut self) {
+        self.cull_scene();
+        let readback = self.aov.capture(&self.scene, self.camera.bind_group(), &self.context);
+        self.aovs.push(readback);
+    }
+
+    fn pick(&mut self, x: u32, y: u32) {
+        // A viewport may have drawn last, the batches follow the main view again
+        self.scene.set_view_layers(self.layers, &self.context);
+        self.cull_scene();
+        let readback = self
+            .picking
+            .pick(x, y, &self.scene, self.camera.bind_group(), &self.context);
+        self.picks.push(readback);
+    }
+
+    // Renders the view again in tiles × tiles parts, each through its slice of the projection, for an image larger
+    // than any texture the adapter allows. Screen space effects like ambient occlusion and bloom start over at every
+    // tile edge
+    fn capture_tiled_screenshot(
+        &mut self,
+        id: u64,
+        tiles: u32,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+    ) {
+        let tiles = tiles.clamp(1, MAX_SCREENSHOT_TILES);
+        let mut readbacks = Vec::new();
+        for row in 0..tiles {
+            for column in 0..tiles {
+                let tile_projection = TiledReadback::tile_projection(tiles, column, row) * projection;
+                self.update_camera(position, view, tile_projection);
+                let texture = self.render_offscreen();
+                readbacks.push(TextureReadback::new(&texture, &self.context));
+            }
+        }
+
+        self.update_camera(position, view, projection);
+        self.tiled_screenshots.push((id, TiledReadback::new(tiles, readbacks)));
+    }
+
+    fn capture_stereo_panorama(
+        &mut self,
+        id: u64,
+        position: glam::Vec3,
+        yaw: f32,
+        width: u32,
+        eye_distance: f32,
+        strips: u32,
+    ) {
+        crate::profile_scope!("Stereo panorama capture");
+        let panorama = StereoPanorama::new(width, yaw, &self.context);
+        let config = panorama.capture_config(self.context.config.format);
+        // At least a column each
+        let strips = strips.clamp(1, config.width * 4);
+        let mut viewport = Viewport::new(config, &self.settings, &self.context);
+
+        for eye in 0..2 {
+            for strip in 0..strips {
+                let eye_position = panorama.eye_position(position, eye_distance, eye, strip, strips);
+                self.capture_faces(&mut viewport, eye_position, |core, encoder, face, _| {
+                    panorama.store_face(encoder, face, core.context.hdr.view(), &core.context);
+                });
+
+                let mut encoder = self
+                    .context
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("Panorama strip encoder"),
+                    });
+                panorama.render_strip(&mut encoder, eye, strip, strips, &self.context);
+                self.context.queue.submit(Some(encoder.finish()));
+            }
+        }
+
+        self.screenshots
+            .push((id, TextureReadback::new(panorama.output(), &self.context)));
+    }
+
+    fn render_offscreen(&mut self) -> wgpu::Texture {
+        let texture = self.context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Screenshot texture"),
+            size: wgpu::Extent3d {
+                width: self.context.config.width,
+                height: self.context.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.context.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[self.context.config.format.add_srgb_suffix()],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.context.config.format.add_srgb_suffix()),
+            ..Default::default()
+        });
+
+        let layers = self.layers;
+        self.layers = layers.without(RenderLayers::HELPERS);
+        self.render_frame(view, None);
+        self.layers = layers;
+        texture
+    }
+
+    fn poll_readbacks(&mut self) -> anyhow::Result<()> {
+        let has_timings = self.gpu_timer.as_ref().is_some_and(GpuTimer::has_pending);
+        if self.readbacks.is_empty()
+            && self.screenshots.is_empty()
+            && self.tiled_screenshots.is_empty()
+            && self.aovs.is_empty()
+            && self.picks.is_empty()
+            && !has_timings
+        {
+            return Ok(());
+        }
+
+        // The browser resolves mappings on its own, native needs a nudge
+        let _ = self.context.device.poll(wgpu::PollType::Poll);
+
+        let mut pending = Vec::new();
+        for readback in self.readbacks.drain(..) {
+            match readback.try_read() {
+                Some(entries) => self.result_tx.send(RenderEvent::BufferContents {
+                    buffer: readback.kind(),
+                    entries,
+                })?,
+                None => pending.push(readback),
+            }
+        }
+
+        self.readbacks = pending;
+
+        let mut pending = Vec::new();
+        for (id, screenshot) in self.screenshots.drain(..) {
+            match screenshot.try_read() {
+                Some(Ok(pixels)) => {
+                    let (width, height) = screenshot.size();
+                    self.result_tx.send(RenderEvent::Screenshot {
+                        id,
+                        width,
+                        height,
+                        pixels,
+                    })?;
+                }
+                Some(Err(error)) => self.result_tx.send(RenderEvent::ScreenshotFailed {
+                    id,
+                    message: error.to_string(),
+                })?,
+                None => pending.push((id, screenshot)),
+            }
+        }
+
+        self.screenshots = pending;
+
+        let mut pending = Vec::new();
+        for (id, mut screenshot) in self.tiled_screenshots.drain(..) {
+            match screenshot.try_read() {
+                Some(Ok(pixels)) => {
+                    let (width, height) = screenshot.size();
+                    self.result_tx.send(RenderEvent::Screenshot {
+                        id,
+                        width,
+                        height,
+                        pixels,
+                    })?;
+                }
+                Some(Err(error)) => self.result_tx.send(RenderEvent::ScreenshotFailed {
+                    id,
+                    message: error.to_string(),
+                })?,
+                None => pending.push((id, screenshot)),
+            }
+        }
+
+        self.tiled_screenshots = pending;
+
+        let mut pending = Vec::new();
+        for mut aovs in self.aovs.drain(..) {
+            match aovs.try_read() {
+                Some(images) => self.result_tx.send(RenderEvent::Aovs(images))?,
+                None => pending.push(aovs),
+            }
+        }
+
+        self.aovs = pending;
+
+        let mut pending = Vec::new();
+        for pick in self.picks.drain(..) {
+            match pick.try_read() {
+                Some(entity_id) => self.result_tx.send(RenderEvent::PickResult { entity_id })?,
+                None => pending.push(pick),
+            }
+        }
+
+        self.picks = pending;
+
+        if let Some(timer) = &mut self.gpu_timer {
+            for passes in timer.poll() {
+                self.result_tx.send(RenderEvent::GpuTimings(passes))?;
+            }
+        }
+
+        Ok(())
     }
 
     pub fn handle_command(&mut self, command: RenderCommand) -> anyhow::Result<()> {
+        self.scoped(command.label(), |core| core.execute_command(command))
+    }
+
+    // Validation errors raised while running `execute` are reported as events instead of hitting the uncaptured
+    // error handler, which panics natively and only logs to the console on the web
+    pub fn scoped<R>(&mut self, label: &'static str, execute: impl FnOnce(&mut Self) -> R) -> R {
+        crate::profile_scope!(label);
+        self.context.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let result = execute(self);
+        let error = self.context.device.pop_error_scope();
+
+        let result_tx = self.result_tx.clone();
+        let report = async move {
+            if let Some(error) = error.await {
+                log::error!("Validation error in {}: {}", label, error);
+                let _ = result_tx.send(RenderEvent::Error {
+                    label,
+                    message: error.to_string(),
+                });
+            }
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            self.error_scopes.push(Box::pin(report));
+            self.error_scopes
+                .retain_mut(|report| future::block_on(future::poll_once(report)).is_none());
+        }
+        #[cfg(target_family = "wasm")]
+        wasm_bindgen_futures::spawn_local(report);
+
+        result
+    }
+
+    fn execute_command(&mut self, command: RenderCommand) -> anyhow::Result<()> {
+        // Scene changes invalidate the accumulated image, camera and frame commands handle it themselves
+        if !matches!(
+            command,
+            RenderCommand::RenderFrame { .. }
+                | RenderCommand::UpdateCamera { .. }
+                | RenderCommand::Resize(_)
+                | RenderCommand::UpdateCursor(_)
+                | RenderCommand::InspectBuffer(_)
+                | RenderCommand::CaptureScreenshot { .. }
+                | RenderCommand::CaptureTiledScreenshot { .. }
+                | RenderCommand::CaptureAovs
+                | RenderCommand::Pick { .. }
+                | RenderCommand::CaptureStereoPanorama { .. }
+                | RenderCommand::SetGpuTiming(_)
+                | RenderCommand::SetFrameGraphReport(_)
+                | RenderCommand::ResizeViewport { .. }
+                | RenderCommand::UpdateViewportCamera { .. }
+                | RenderCommand::RenderViewport { .. }
+                | RenderCommand::CloseViewport(_)
+                | RenderCommand::SetPreview(_)
+                | RenderCommand::UpdatePreviewCamera { .. }
+                | RenderCommand::SetSelection(_)
+                | RenderCommand::SetHighlight(_)
+                | RenderCommand::SetVisibility(_)
+                | RenderCommand::SetEntityLayers { .. }
+                | RenderCommand::SetAnimationPhase { .. }
+                | RenderCommand::SetViewLayers { .. }
+                | RenderCommand::DiscardQueuedLoads
+        ) {
+            self.accumulation.reset();
+            self.point_splats.reset();
+            self.path_tracer.reset();
+        }
+
+        if matches!(
+            command,
+            RenderCommand::LoadAsset(_)
+                | RenderCommand::ReloadAsset { .. }
+                | RenderCommand::SpawnAsset { .. }
+                | RenderCommand::DespawnAsset(_)
+                | RenderCommand::UnloadAsset(_)
+                | RenderCommand::UpdateTransform { .. }
+                | RenderCommand::SetVisibility(_)
+                | RenderCommand::SetMaterialVariant { .. }
+                | RenderCommand::SetAnimationPhase { .. }
+                | RenderCommand::ReplacePoints { .. }
+                | RenderCommand::StreamPoints { .. }
+                | RenderCommand::FinishPointStream { .. }
+        ) {
+            self.path_tracer.invalidate_scene();
+            self.is_query_dirty = true;
+        }
+
         match command {
             RenderCommand::RenderFrame { view, ui } => {
+                self.retry_queued_loads()?;
+                self.report_memory()?;
+                self.step_lightmap()?;
                 self.render_frame(view, ui);
                 self.result_tx.send(RenderEvent::FrameComplete)?;
+                self.report_refining()?;
 
                 if let Some(config) = self.context.pending_resize.take() {
-                    self.context.resize(config);
+                    self.update_config(config);
                 }
             }
             RenderCommand::UpdateCamera {
@@ -451,12 +1659,19 @@ impl RenderCore {
                 view,
                 projection,
             } => self.update_camera(position, view, projection),
-            RenderCommand::LoadAsset(asset) => self.load_asset(asset)?,
+            RenderCommand::LoadAsset(asset) => self.queue_load(asset)?,
+            RenderCommand::ReloadAsset {
+                asset_id,
+                render_ids,
+                asset,
+            } => self.reload_asset(asset_id, render_ids, asset)?,
             RenderCommand::SpawnAsset {
                 entity_id,
                 render_id,
                 transform,
             } => self.spawn_asset(entity_id, render_id, transform),
+            RenderCommand::DespawnAsset(entity_id) => self.despawn_asset(entity_id),
+            RenderCommand::UnloadAsset(render_ids) => self.unload_asset(&render_ids),
             RenderCommand::SpawnLight { entity_id, light } => self.spawn_light(entity_id, light),
             RenderCommand::Resize(config) => {
                 self.context.pending_resize = Some(config.clone());
@@ -467,19 +1682,142 @@ impl RenderCore {
             }
             RenderCommand::UpdateTransform { entity_id, transform } => {
                 let uniform = TransformUniform::new(transform);
-                self.scene.transforms.set(&entity_id, uniform, &self.context);
+                self.scene.set_transform(&entity_id, uniform, &self.context);
             }
-            RenderCommand::UpdateLight {
-                entity_id,
-                kind,
-                color,
-                intensity,
-                cutoff,
-            } => {
-                // Need more controls
-                let uniform = LightUniform::new(1, color, intensity, cutoff);
+            RenderCommand::UpdateLight { entity_id, light } => {
+                let (uniform, transform) = light.to_parts();
                 self.scene.lights.set(&entity_id, uniform, &self.context);
+                self.scene.set_transform(&entity_id, transform, &self.context);
+            }
+            RenderCommand::UpdateSettings(settings) => self.update_settings(settings),
+            RenderCommand::UpdateSketch(source) => self.sketch.set_source(&source, &self.context),
+            RenderCommand::UpdateCursor(position) => self.sketch.set_mouse(position),
+            RenderCommand::InspectBuffer(buffer) => self.inspect_buffer(buffer),
+            RenderCommand::CaptureScreenshot { id } => self.capture_screenshot(id),
+            RenderCommand::CaptureTiledScreenshot {
+                id,
+                tiles,
+                position,
+                view,
+                projection,
+            } => self.capture_tiled_screenshot(id, tiles, position, view, projection),
+            RenderCommand::CaptureAovs => self.capture_aovs(),
+            RenderCommand::Pick { x, y } => self.pick(x, y),
+            RenderCommand::CaptureStereoPanorama {
+                id,
+                position,
+                yaw,
+                width,
+                eye_distance,
+                strips,
+            } => self.capture_stereo_panorama(id, position, yaw, width, eye_distance, strips),
+            RenderCommand::SetGpuTiming(is_timing) => self.is_timing = is_timing,
+            RenderCommand::SetFrameGraphReport(is_reporting) => {
+                self.is_reporting_graph = is_reporting;
+                self.graph_layout = FrameGraphLayout::default();
+            }
+            RenderCommand::UpdateTransferFunction(transfer_function) => {
+                self.volume.set_transfer_function(transfer_function, &self.context)
+            }
+            RenderCommand::ResizeViewport { viewport, config } => {
+                // Frames acquired before the resize were queued ahead of it, so this can apply right away
+                self.resize_viewport(viewport, config.clone());
+                self.result_tx.send(RenderEvent::ViewportResized {
+                    viewport,
+                    config,
+                    device: self.context.device.clone(),
+                })?;
+            }
+            RenderCommand::UpdateViewportCamera {
+                viewport,
+                position,
+                view,
+                projection,
+            } => {
+                if let Some(viewport) = self.viewports.get_mut(&viewport) {
+                    viewport.update_camera(position, view, projection, &self.context);
+                }
+            }
+            RenderCommand::RenderViewport { viewport, view } => {
+                self.render_viewport(viewport, view);
+                self.result_tx.send(RenderEvent::ViewportFrameComplete(viewport))?;
             }
+            RenderCommand::CloseViewport(viewport) => {
+                self.viewports.remove(&viewport);
+            }
+            RenderCommand::SetPreview(interval) => self.set_preview(interval)?,
+            RenderCommand::UpdatePreviewCamera {
+                position,
+                view,
+                projection,
+            } => {
+                if let Some(preview) = &mut self.preview {
+                    preview.update_camera(position, view, projection, &self.context);
+                }
+            }
+            RenderCommand::SetVisibility(changes) => self.scene.set_visibility(changes, &self.context),
+            RenderCommand::SetEntityLayers { entity_id, layers } => {
+                self.scene.set_layers(entity_id, layers, &self.context)
+            }
+            RenderCommand::SetAnimationPhase { entity_id, phase } => {
+                self.scene.set_animation_phase(entity_id, phase, &self.context)
+            }
+            RenderCommand::SetMaterialVariant { render_ids, variant } => {
+                for render_id in render_ids {
+                    self.scene.set_material_variant(render_id, variant);
+                }
+            }
+            RenderCommand::SetViewLayers { viewport: None, layers } => self.layers = layers,
+            RenderCommand::SetViewLayers {
+                viewport: Some(viewport),
+                layers,
+            } => {
+                if let Some(viewport) = self.viewports.get_mut(&viewport) {
+                    viewport.set_layers(layers);
+                }
+            }
+            RenderCommand::UpdateTags { entity_id, tags } => self.scene.set_tags(entity_id, tags, &self.context),
+            RenderCommand::SetHighlight(filter) => self.scene.set_highlight(filter, &self.context),
+            RenderCommand::SetSelection(entity_id) => self.scene.set_selection(entity_id, &self.context),
+            RenderCommand::PlaceProbe {
+                probe_id,
+                position,
+                radius,
+            } => self.place_probe(probe_id, position, radius)?,
+            RenderCommand::CaptureProbes => {
+                let probe_ids = self.scene.probes.ids();
+                self.capture_probes(&probe_ids);
+            }
+            RenderCommand::RemoveProbe(probe_id) => self.scene.probes.remove(&probe_id, &self.context),
+            RenderCommand::SetIrradianceVolume(grid) => {
+                self.scene.irradiance_volume.set_grid(grid);
+                self.bake_irradiance_volume();
+            }
+            RenderCommand::BakeIrradianceVolume => self.bake_irradiance_volume(),
+            RenderCommand::BakeLightmap {
+                entity_id,
+                resolution,
+                samples,
+            } => self.bake_lightmap(entity_id, resolution, samples)?,
+            RenderCommand::ClearLightmap(entity_id) => {
+                self.lightmapper.clear(entity_id, &mut self.scene, &self.context)
+            }
+            RenderCommand::ClassifyPoints { render_id, changes } => self.classify_points(render_id, &changes),
+            RenderCommand::ReplacePoints { render_id, buffer } => self.replace_points(render_id, buffer),
+            RenderCommand::StreamPoints {
+                stream_id,
+                buffer,
+                total,
+                label,
+                progress,
+            } => self.stream_points(stream_id, buffer, total, label, progress)?,
+            RenderCommand::FinishPointStream { stream_id, buffer } => self.finish_point_stream(stream_id, buffer)?,
+            RenderCommand::ForceQueuedLoads => {
+                while let Some(asset) = self.queued_loads.pop_front() {
+                    self.load_asset(asset)?;
+                }
+            }
+            RenderCommand::DiscardQueuedLoads => self.queued_loads.clear(),
             RenderCommand::Stop => {
                 self.is_running = false;
             }
@@ -557,3 +1895,27 @@ impl RenderCore {
         Ok(())
     }
 }
+
+fn mesh_stats(mesh: &Mesh) -> AssetStats {
+    let primitives = &mesh.primitives;
+    AssetStats {
+        triangles: primitives.iter().map(|primitive| primitive.num_elements / 3).sum(),
+        points: 0,
+        materials: primitives
+            .iter()
+            .map(|primitive| primitive.material_index)
+            .collect::<HashSet<_>>()
+            .len() as u32,
+        bounds: primitives.iter().fold(Aabb::EMPTY, |mut bounds, primitive| {
+            bounds.merge(&primitive.bounds);
+            bounds
+        }),
+    }
+}
+
+fn pointcloud_bounds(buffer: &PointcloudBuffer) -> Aabb {
+    buffer.points().iter().fold(Aabb::EMPTY, |mut bounds, point| {
+        bounds.grow(glam::Vec3::from_array(point.position));
+        bounds
+    })
+}
@@ -2,9 +2,12 @@ use std::io::Cursor;
 
 use half::f16;
 use image::{ImageDecoder, codecs::hdr::HdrDecoder};
+use wgpu::util::DeviceExt;
 
 use crate::renderer::{
     context::RenderContext,
+    irradiance_volume::IrradianceVolume,
+    probe::ReflectionProbes,
     texture::{CubeTexture, Texture},
 };
 
@@ -143,22 +146,301 @@ impl IrradianceMap {
     }
 }
 
+// Mip chain of the environment convolved with the GGX lobe, roughness rising linearly from the mirror reflection at
+// the top mip to one at the last
+pub struct SpecularMap;
+
+impl SpecularMap {
+    const SIZE: u32 = 256;
+    const LEVELS: u32 = 6;
+
+    pub fn default(context: &RenderContext) -> CubeTexture {
+        let data: [f16; 4] = [
+            f16::from_f32(0.03),
+            f16::from_f32(0.03),
+            f16::from_f32(0.03),
+            f16::from_f32(1.0),
+        ];
+
+        CubeTexture::create_placeholder(&context.device, &context.queue, &data, wgpu::FilterMode::Linear)
+    }
+
+    pub fn prefilter(environment_map: &CubeTexture, context: &RenderContext) -> CubeTexture {
+        let label = Some("Specular map");
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: Self::LEVELS,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label,
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            array_layer_count: Some(6),
+            ..Default::default()
+        });
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Specular prefilter shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/specular_prefilter.wgsl").into()),
+        });
+
+        let bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Specular prefilter bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Specular prefilter pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Specular prefilter pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("prefilter_specular"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        // Every mip is written through its own storage view with its roughness
+        let bind_groups = (0..Self::LEVELS)
+            .map(|level| {
+                let mip_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    label,
+                    dimension: Some(wgpu::TextureViewDimension::D2Array),
+                    base_mip_level: level,
+                    mip_level_count: Some(1),
+                    array_layer_count: Some(6),
+                    ..Default::default()
+                });
+
+                let roughness = level as f32 / (Self::LEVELS - 1) as f32;
+                let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label,
+                    contents: bytemuck::cast_slice(&[roughness, 0.0, 0.0, 0.0]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+
+                context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label,
+                    layout: &bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(environment_map.view()),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(environment_map.sampler()),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: wgpu::BindingResource::TextureView(&mip_view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: buffer.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = context.device.create_command_encoder(&Default::default());
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label,
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&pipeline);
+            for (level, bind_group) in bind_groups.iter().enumerate() {
+                let num_workgroup = (Self::SIZE >> level).div_ceil(8);
+                compute_pass.set_bind_group(0, bind_group, &[]);
+                compute_pass.dispatch_workgroups(num_workgroup, num_workgroup, 6);
+            }
+        }
+
+        context.queue.submit(Some(encoder.finish()));
+        CubeTexture { texture, view, sampler }
+    }
+}
+
+// Split sum scale and bias of f0 over n·v and roughness, independent of the environment
+pub struct BrdfLut;
+
+impl BrdfLut {
+    const SIZE: u32 = 128;
+
+    pub fn integrate(context: &RenderContext) -> Texture {
+        let label = Some("BRDF lookup table");
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba16Float,
+            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("BRDF lookup table shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/brdf_lut.wgsl").into()),
+        });
+
+        let bind_group_layout = context.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("BRDF lookup table bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: wgpu::TextureFormat::Rgba16Float,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("BRDF lookup table pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = context.device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("BRDF lookup table pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: Some("integrate_brdf"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label,
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            }],
+        });
+
+        let mut encoder = context.device.create_command_encoder(&Default::default());
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label,
+                timestamp_writes: None,
+            });
+            let num_workgroup = Self::SIZE.div_ceil(8);
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(num_workgroup, num_workgroup, 1);
+        }
+
+        context.queue.submit(Some(encoder.finish()));
+        Texture { texture, view, sampler }
+    }
+}
+
 pub struct EnvironmentMap {
     environment: CubeTexture,
     irradiance: CubeTexture,
+    specular: CubeTexture,
+    brdf_lut: Texture,
     bind_group: wgpu::BindGroup,
     pipeline: wgpu::RenderPipeline,
 }
 
 impl EnvironmentMap {
-    pub fn default(context: &RenderContext) -> Self {
+    pub fn default(probes: &ReflectionProbes, volume: &IrradianceVolume, context: &RenderContext) -> Self {
         let environment = CubeTexture::create_placeholder(&context.device, &context.queue, &[0.1f32,0.2,0.3,1.0], wgpu::FilterMode::Nearest);
-        Self::new(environment, context)
+        Self::new(environment, probes, volume, context)
     }
 
-    pub fn new(environment: CubeTexture, context: &RenderContext) -> Self {
+    pub fn new(environment: CubeTexture, probes: &ReflectionProbes, volume: &IrradianceVolume, context: &RenderContext) -> Self {
         let irradiance = IrradianceMap::default(context);
-        let bind_group = Self::create_bind_group(&environment, &irradiance, context);
+        let specular = SpecularMap::default(context);
+        let brdf_lut = BrdfLut::integrate(context);
+        let bind_group =
+            Self::create_bind_group(&environment, &irradiance, &specular, &brdf_lut, probes, volume, context);
 
         let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Skybox shader"),
@@ -221,6 +503,8 @@ impl EnvironmentMap {
         Self {
             environment,
             irradiance,
+            specular,
+            brdf_lut,
             bind_group,
             pipeline,
         }
@@ -234,12 +518,29 @@ impl EnvironmentMap {
         &self.pipeline
     }
 
-    pub fn compute_irradiance(&mut self, context: &RenderContext) {
+    pub fn compute_irradiance(&mut self, probes: &ReflectionProbes, volume: &IrradianceVolume, context: &RenderContext) {
         self.irradiance = IrradianceMap::new(&self.environment, context);
-        self.bind_group = Self::create_bind_group(&self.environment, &self.irradiance, context)
+        self.specular = SpecularMap::prefilter(&self.environment, context);
+        self.bind_group = Self::create_bind_group(
+            &self.environment,
+            &self.irradiance,
+            &self.specular,
+            &self.brdf_lut,
+            probes,
+            volume,
+            context,
+        )
     }
 
-    fn create_bind_group(environment: &CubeTexture, irradiance: &CubeTexture, context: &RenderContext) -> wgpu::BindGroup {
+    fn create_bind_group(
+        environment: &CubeTexture,
+        irradiance: &CubeTexture,
+        specular: &CubeTexture,
+        brdf_lut: &Texture,
+        probes: &ReflectionProbes,
+        volume: &IrradianceVolume,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
         context.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("Environment map bind group"),
             layout: &context.environment_bind_group_layout,
@@ -260,6 +561,42 @@ impl EnvironmentMap {
                     binding: 3,
                     resource: wgpu::BindingResource::Sampler(irradiance.sampler()),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(probes.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(probes.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: probes.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: volume.coefficients().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 8,
+                    resource: volume.uniform().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 9,
+                    resource: wgpu::BindingResource::TextureView(specular.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 10,
+                    resource: wgpu::BindingResource::Sampler(specular.sampler()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 11,
+                    resource: wgpu::BindingResource::TextureView(brdf_lut.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 12,
+                    resource: wgpu::BindingResource::Sampler(brdf_lut.sampler()),
+                },
             ],
         })
     }
@@ -437,13 +774,13 @@ pub struct HdrBuffer {
 }
 
 impl HdrBuffer {
-    pub fn from_hdr(data: &[u8]) -> Self {
-        let decoder = HdrDecoder::new(Cursor::new(data)).unwrap();
+    pub fn from_hdr(data: &[u8]) -> anyhow::Result<Self> {
+        let decoder = HdrDecoder::new(Cursor::new(data))?;
         let metadata = decoder.metadata();
 
         let buffer_size = (metadata.height * metadata.width) as usize * std::mem::size_of::<[f32; 3]>();
         let mut pixels = vec![0; buffer_size];
-        decoder.read_image(&mut pixels).unwrap();
+        decoder.read_image(&mut pixels)?;
 
         let mut rgba = Vec::with_capacity(pixels.len() / 3 * 4);
         for chunk in pixels.chunks_exact(12) {
@@ -451,10 +788,10 @@ impl HdrBuffer {
             rgba.extend_from_slice(&[0, 0, 128, 63]);
         }
 
-        Self {
+        Ok(Self {
             pixels: rgba,
             width: metadata.width,
             height: metadata.height,
-        }
+        })
     }
 }
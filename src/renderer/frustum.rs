@@ -0,0 +1,31 @@
+use crate::renderer::bvh::Aabb;
+
+// Planes point inward, a point is inside when its distance to every plane is positive
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    planes: [glam::Vec4; 6],
+}
+
+impl Frustum {
+    // Extracted from the rows of the matrix, with the 0..1 clip depth wgpu uses
+    pub fn from_view_projection(view_projection: glam::Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|index| view_projection.row(index));
+        let planes =
+            [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().length().max(f32::EPSILON));
+        Self { planes }
+    }
+
+    // Conservative, a box near a corner may pass without being in view
+    pub fn intersects(&self, bounds: &Aabb) -> bool {
+        if bounds.is_empty() {
+            return false;
+        }
+
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane normal
+            let normal = plane.truncate();
+            let corner = glam::Vec3::select(normal.cmpge(glam::Vec3::ZERO), bounds.max, bounds.min);
+            normal.dot(corner) + plane.w >= 0.0
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use crate::renderer::{
+    context::RenderContext,
+    instance::Instance,
+    pointcloud::PointVertex,
+    quantize::MeshPipelines,
+    scene::{Geometry, Renderable, SceneGraph},
+    texture::Texture,
+    vertex::VertexLayoutBuilder,
+};
+
+// Ghosted entities drawn as a faint tint over the lit scene. Depth tested against what was drawn but not written,
+// so they never hide the isolated entities and stay out of the path tracer and picking
+pub struct GhostPass {
+    mesh_pipelines: MeshPipelines,
+    point_pipeline: wgpu::RenderPipeline,
+}
+
+impl GhostPass {
+    pub fn new(scene_layout: &wgpu::BindGroupLayout, context: &RenderContext) -> Self {
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Ghost shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/ghost.wgsl").into()),
+        });
+
+        let layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Ghost pipeline layout"),
+            bind_group_layouts: &[&context.camera_bind_group_layout, scene_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point, topology, buffers: &[wgpu::VertexBufferLayout]| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: context.hdr.format(),
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let mesh_pipelines = MeshPipelines::new(|precision| {
+            create_pipeline(
+                &format!("Ghost mesh pipeline ({})", precision.to_str()),
+                "vs_mesh",
+                wgpu::PrimitiveTopology::TriangleList,
+                &precision.layout_builder(0).push::<Instance>().build(),
+            )
+        });
+        let point_pipeline = create_pipeline(
+            "Ghost pointcloud pipeline",
+            "vs_points",
+            wgpu::PrimitiveTopology::PointList,
+            &VertexLayoutBuilder::new()
+                .push::<PointVertex>()
+                .push::<Instance>()
+                .build(),
+        );
+
+        Self {
+            mesh_pipelines,
+            point_pipeline,
+        }
+    }
+
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &SceneGraph,
+        camera_bind_group: &wgpu::BindGroup,
+        context: &RenderContext,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Ghost render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &context.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, scene.bind_group(), &[]);
+        render_pass.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+
+        for batch in &scene.ghost_batches {
+            match scene.renderables.get(&batch.key.render_id) {
+                Some(Renderable::Mesh(handles)) => {
+                    for handle in handles {
+                        if let Some(Geometry::Primitive(primitive)) = scene.geometries.get_by_id(handle.geometry_index)
+                        {
+                            render_pass.set_pipeline(self.mesh_pipelines.get(primitive.vertex_precision));
+                            render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                            render_pass.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
+                            render_pass.draw_indexed(0..primitive.num_elements, 0, batch.instance_range());
+                        }
+                    }
+                }
+                Some(Renderable::Pointcloud(handle)) => {
+                    if let Some(Geometry::Pointcloud(pointcloud)) = scene.geometries.get_by_id(handle.geometry_index) {
+                        render_pass.set_pipeline(&self.point_pipeline);
+                        render_pass.set_vertex_buffer(0, pointcloud.vertex_buffer.slice(..));
+                        render_pass.draw(0..pointcloud.num_points, batch.instance_range());
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
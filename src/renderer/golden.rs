@@ -0,0 +1,363 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use crossbeam::channel::{Receiver, Sender};
+use futures_lite::future;
+use instant::Instant;
+
+use crate::{
+    camera::{Camera, Projection},
+    renderer::{
+        RenderCommand, RenderEvent,
+        asset::{AssetBuffer, ImportMode, ImportOptions, ResourcePath},
+        context::RenderContext,
+        core::RenderCore,
+        environment::HdrBuffer,
+        light::Light,
+        mesh::SceneBuffer,
+        scene::RenderId,
+        settings::{RenderMode, RenderSettings},
+    },
+};
+
+// Renders the bundled scenes offscreen and compares them against the PNGs in golden/. They need a GPU adapter, so they
+// are ignored by default: `cargo test -- --include-ignored` runs them, `UPDATE_GOLDEN=1` writes new references instead.
+// A missing reference or adapter fails the test
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+// Allowed per channel difference, and the fraction of pixels allowed to exceed it
+const CHANNEL_TOLERANCE: u8 = 8;
+const MAX_MISMATCH: f32 = 0.005;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+struct Harness {
+    core: RenderCore,
+    event_rx: Receiver<RenderEvent>,
+    // Keeps the command channel open, the core stops on disconnect
+    _render_tx: Sender<RenderCommand>,
+    target: wgpu::Texture,
+    format: wgpu::TextureFormat,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let _ = env_logger::builder().is_test(true).try_init();
+
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::PRIMARY,
+            ..Default::default()
+        });
+        let adapter = future::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }))
+        .unwrap_or_else(|error| panic!("Golden image tests need an adapter: {}", error));
+
+        // Stands in for the surface, the core only reads the format and size
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: WIDTH,
+            height: HEIGHT,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![format.add_srgb_suffix()],
+            desired_maximum_frame_latency: 2,
+        };
+
+        let context = future::block_on(RenderContext::new(&adapter, config)).unwrap();
+        let target = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Golden image target"),
+            size: wgpu::Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[format.add_srgb_suffix()],
+        });
+
+        let (render_tx, render_rx) = crossbeam::channel::unbounded();
+        let (event_tx, event_rx) = crossbeam::channel::unbounded();
+        let core = future::block_on(RenderCore::new(context, render_rx, event_tx)).unwrap();
+
+        let mut harness = Self {
+            core,
+            event_rx,
+            _render_tx: render_tx,
+            target,
+            format,
+        };
+        harness.set_camera(glam::Vec3::new(3.0, 2.0, 3.0), glam::Vec3::ZERO);
+        harness
+    }
+
+    fn send(&mut self, command: RenderCommand) {
+        self.core.handle_command(command).unwrap();
+    }
+
+    fn set_camera(&mut self, position: glam::Vec3, target: glam::Vec3) {
+        let camera = Camera::look_at(position, target);
+        let projection = Projection::new(WIDTH, HEIGHT, 45f32.to_radians(), 0.1, 100.0);
+        self.send(RenderCommand::UpdateCamera {
+            position: camera.position(),
+            view: camera.view_matrix(),
+            projection: projection.matrix(),
+        });
+    }
+
+    fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.send(RenderCommand::UpdateSettings(RenderSettings {
+            render_mode,
+            ..Default::default()
+        }));
+    }
+
+    // Loads and spawns every node of the scene at its own transform
+    fn load_obj(&mut self, path: &str) {
+        let path = ResourcePath::new(path).unwrap();
+        let (scene, report) = future::block_on(SceneBuffer::from_obj(&path, &ImportOptions::default())).unwrap();
+        self.send(RenderCommand::LoadAsset(AssetBuffer::Scene(
+            scene,
+            Some(path.file_name().to_string()),
+            report,
+            ImportMode::Append,
+        )));
+
+        for (render_id, transform) in self.loaded() {
+            self.send(RenderCommand::SpawnAsset {
+                entity_id: uuid::Uuid::new_v4(),
+                render_id,
+                transform,
+            });
+        }
+    }
+
+    fn load_environment(&mut self, path: &str) {
+        let path = ResourcePath::new(path).unwrap();
+        let data = future::block_on(path.load_binary()).unwrap();
+        self.send(RenderCommand::LoadAsset(AssetBuffer::EnvironmentMap {
+            buffer: HdrBuffer::from_hdr(&data).unwrap(),
+            label: Some(path.file_name().to_string()),
+        }));
+    }
+
+    fn spawn_light(&mut self, light: Light) {
+        self.send(RenderCommand::SpawnLight {
+            entity_id: uuid::Uuid::new_v4(),
+            light,
+        });
+    }
+
+    fn loaded(&self) -> Vec<(RenderId, glam::Mat4)> {
+        self.event_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                RenderEvent::LoadComplete {
+                    render_id, transform, ..
+                } => Some((render_id, transform.unwrap_or(glam::Mat4::IDENTITY))),
+                RenderEvent::Error { label, message } => panic!("{}: {}", label, message),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Frames rendered while pipelines compile in the background skip their draws
+    fn render(&mut self) -> image::RgbaImage {
+        let timestamp = Instant::now();
+        loop {
+            self.render_frame();
+            if !self.core.is_compiling() {
+                break;
+            }
+
+            assert!(timestamp.elapsed() < TIMEOUT, "Pipelines did not compile in time");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        self.render_frame();
+        self.read_target()
+    }
+
+    fn render_frame(&mut self) {
+        let view = self.target.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.format.add_srgb_suffix()),
+            ..Default::default()
+        });
+        self.core.render_frame(view, None);
+    }
+
+    fn read_target(&self) -> image::RgbaImage {
+        let device = self.core.device();
+        let row_bytes = WIDTH * 4;
+        let padded_row_bytes =
+            row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Golden image staging buffer"),
+            size: (padded_row_bytes * HEIGHT) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Golden image readback encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(HEIGHT),
+                },
+            },
+            self.target.size(),
+        );
+        self.core.queue().submit(Some(encoder.finish()));
+
+        let status = Arc::new(OnceLock::new());
+        let callback_status = Arc::clone(&status);
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = callback_status.set(result.is_ok());
+        });
+
+        let timestamp = Instant::now();
+        while status.get().is_none() {
+            assert!(timestamp.elapsed() < TIMEOUT, "Golden image readback timed out");
+            let _ = device.poll(wgpu::PollType::Poll);
+        }
+        assert!(
+            status.get().copied().unwrap_or(false),
+            "Unable to map golden image buffer"
+        );
+
+        let pixels = {
+            let data = staging.slice(..).get_mapped_range();
+            data.chunks_exact(padded_row_bytes as usize)
+                .flat_map(|row| &row[..row_bytes as usize])
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        staging.unmap();
+
+        image::RgbaImage::from_raw(WIDTH, HEIGHT, pixels).unwrap()
+    }
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+// Failing renders are written next to a diff image so the regression can be inspected
+fn output_path(name: &str, suffix: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden")
+        .join(format!("{}.{}.png", name, suffix))
+}
+
+fn assert_golden(name: &str, actual: &image::RgbaImage) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        actual.save(&path).unwrap();
+        log::warn!("Wrote golden image {}", path.display());
+        return;
+    }
+    assert!(
+        path.exists(),
+        "{}: no golden image at {}, run with UPDATE_GOLDEN=1 to write it",
+        name,
+        path.display()
+    );
+
+    let expected = image::open(&path).unwrap().to_rgba8();
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{}: golden image size differs",
+        name
+    );
+
+    let mut diff = image::RgbaImage::new(actual.width(), actual.height());
+    let mut mismatched = 0;
+    for ((expected_pixel, actual_pixel), diff_pixel) in expected.pixels().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let difference = (0..3)
+            .map(|channel| expected_pixel[channel].abs_diff(actual_pixel[channel]))
+            .max()
+            .unwrap_or(0);
+        // Mismatches in magenta over a darkened copy of the render
+        *diff_pixel = if difference > CHANNEL_TOLERANCE {
+            mismatched += 1;
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([actual_pixel[0] / 4, actual_pixel[1] / 4, actual_pixel[2] / 4, 255])
+        };
+    }
+
+    let fraction = mismatched as f32 / (actual.width() * actual.height()) as f32;
+    if fraction > MAX_MISMATCH {
+        let actual_path = output_path(name, "actual");
+        std::fs::create_dir_all(actual_path.parent().unwrap()).unwrap();
+        actual.save(&actual_path).unwrap();
+        diff.save(output_path(name, "diff")).unwrap();
+
+        panic!(
+            "{}: {:.2}% of pixels differ from {}, render written to {}",
+            name,
+            fraction * 100.0,
+            path.display(),
+            actual_path.display()
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn cube_raster() {
+    let mut harness = Harness::new();
+
+    harness.load_obj("cube.obj");
+    harness.spawn_light(Light::Point {
+        position: glam::Vec3::new(2.0, 3.0, 2.0),
+        color: glam::Vec3::new(0.9, 0.9, 0.6),
+        intensity: 100.0,
+        range: 0.0,
+        casts_shadows: true,
+    });
+
+    assert_golden("cube_raster", &harness.render());
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn cube_environment() {
+    let mut harness = Harness::new();
+
+    harness.load_environment("pure-sky.hdr");
+    harness.load_obj("cube.obj");
+
+    assert_golden("cube_environment", &harness.render());
+}
+
+#[test]
+#[ignore = "needs a GPU adapter"]
+fn default_sketch() {
+    let mut harness = Harness::new();
+
+    harness.set_render_mode(RenderMode::Sketch);
+
+    assert_golden("default_sketch", &harness.render());
+}
@@ -0,0 +1,287 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::renderer::{context::RenderContext, core::Frame};
+
+// Resources passes read and write. Everything except transients lives outside the graph and survives the frame
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Slot {
+    Surface,
+    Hdr,
+    Depth,
+    SceneColor,
+    History,
+    PathSamples,
+    Splats,
+    ShadowMaps,
+    Transient(&'static str),
+}
+
+impl Slot {
+    fn is_transient(&self) -> bool {
+        matches!(self, Self::Transient(_))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Surface => "Surface",
+            Self::Hdr => "HDR",
+            Self::Depth => "Depth",
+            Self::SceneColor => "Scene color",
+            Self::History => "History",
+            Self::PathSamples => "Path samples",
+            Self::Splats => "Splats",
+            Self::ShadowMaps => "Shadow maps",
+            Self::Transient(name) => name,
+        }
+    }
+}
+
+// What backs a slot in the frame it was described for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SlotResource {
+    Texture {
+        format: wgpu::TextureFormat,
+        size: wgpu::Extent3d,
+    },
+    Buffer {
+        size: wgpu::BufferAddress,
+    },
+}
+
+impl SlotResource {
+    pub fn texture(texture: &wgpu::Texture) -> Self {
+        Self::Texture {
+            format: texture.format(),
+            size: texture.size(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassLayout {
+    pub name: &'static str,
+    pub reads: Vec<&'static str>,
+    pub writes: Vec<&'static str>,
+    // False for passes left out of the frame, see FrameGraph::compile
+    pub is_scheduled: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotLayout {
+    pub name: &'static str,
+    pub is_transient: bool,
+    pub resources: Vec<SlotResource>,
+}
+
+// A frame graph as the app shows it. Passes in the order they were added, slots in the order a pass first touched
+// them
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameGraphLayout {
+    pub passes: Vec<PassLayout>,
+    pub slots: Vec<SlotLayout>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransientDesc {
+    pub format: wgpu::TextureFormat,
+    // Relative to the surface size
+    pub scale: f32,
+    pub usage: wgpu::TextureUsages,
+}
+
+type PassFn<T> = Box<dyn FnOnce(&mut T, &mut Frame)>;
+
+struct Pass<T> {
+    name: &'static str,
+    reads: Vec<Slot>,
+    writes: Vec<Slot>,
+    execute: PassFn<T>,
+}
+
+// Built every frame. Passes are never reordered, they run in the order they were added and the declared reads and
+// writes only validate that order. Passes that read something nobody wrote earlier, or only produce transients
+// nobody reads, are skipped
+pub struct FrameGraph<T> {
+    passes: Vec<Pass<T>>,
+    transients: HashMap<&'static str, TransientDesc>,
+}
+
+impl<T> FrameGraph<T> {
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            transients: HashMap::new(),
+        }
+    }
+
+    pub fn create_texture(&mut self, name: &'static str, desc: TransientDesc) -> Slot {
+        self.transients.insert(name, desc);
+        Slot::Transient(name)
+    }
+
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        reads: &[Slot],
+        writes: &[Slot],
+        execute: impl FnOnce(&mut T, &mut Frame) + 'static,
+    ) {
+        self.passes.push(Pass {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            execute: Box::new(execute),
+        });
+    }
+
+    pub fn transients(&self) -> &HashMap<&'static str, TransientDesc> {
+        &self.transients
+    }
+
+    // `resources` looks up what backs each slot, transients included
+    pub fn layout(&self, resources: impl Fn(Slot) -> Vec<SlotResource>) -> FrameGraphLayout {
+        let names = |slots: &[Slot]| slots.iter().map(Slot::name).collect();
+        let passes = self
+            .passes
+            .iter()
+            .zip(self.compile())
+            .map(|(pass, is_scheduled)| PassLayout {
+                name: pass.name,
+                reads: names(&pass.reads),
+                writes: names(&pass.writes),
+                is_scheduled,
+            })
+            .collect();
+
+        let mut slots = Vec::<Slot>::new();
+        for slot in self
+            .passes
+            .iter()
+            .flat_map(|pass| pass.reads.iter().chain(&pass.writes))
+        {
+            if !slots.contains(slot) {
+                slots.push(*slot);
+            }
+        }
+        let slots = slots
+            .into_iter()
+            .map(|slot| SlotLayout {
+                name: slot.name(),
+                is_transient: slot.is_transient(),
+                resources: resources(slot),
+            })
+            .collect();
+
+        FrameGraphLayout { passes, slots }
+    }
+
+    pub fn execute(self, target: &mut T, frame: &mut Frame) {
+        let schedule = self.compile();
+        for (pass, is_scheduled) in self.passes.into_iter().zip(schedule) {
+            if is_scheduled {
+                crate::profile_scope!(pass.name);
+                let is_timed = frame.begin_pass(pass.name);
+                (pass.execute)(target, frame);
+                if is_timed {
+                    frame.end_pass();
+                }
+            }
+        }
+    }
+
+    // Which passes run, in insertion order
+    fn compile(&self) -> Vec<bool> {
+        let mut written = HashSet::new();
+        let mut is_valid = vec![false; self.passes.len()];
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            let unwritten = pass
+                .reads
+                .iter()
+                .find(|slot| slot.is_transient() && !written.contains(*slot));
+            if let Some(slot) = unwritten {
+                log::error!("Pass {} reads {:?} before any pass writes it", pass.name, slot);
+                continue;
+            }
+
+            let undeclared = pass.writes.iter().find(|slot| match slot {
+                Slot::Transient(name) => !self.transients.contains_key(name),
+                _ => false,
+            });
+            if let Some(slot) = undeclared {
+                log::error!("Pass {} writes {:?}, which was never created", pass.name, slot);
+                continue;
+            }
+
+            written.extend(pass.writes.iter().copied());
+            is_valid[index] = true;
+        }
+
+        // Walk backwards from the persistent resources to find what actually contributes
+        let mut needed = HashSet::new();
+        let mut is_scheduled = vec![false; self.passes.len()];
+        for (index, pass) in self.passes.iter().enumerate().rev() {
+            let contributes = pass
+                .writes
+                .iter()
+                .any(|slot| !slot.is_transient() || needed.contains(slot));
+            if is_valid[index] && contributes {
+                needed.extend(pass.reads.iter().copied());
+                is_scheduled[index] = true;
+            }
+        }
+
+        is_scheduled
+    }
+}
+
+// Textures backing transient slots, kept between frames while their description stays the same
+#[derive(Default)]
+pub struct TransientTextures {
+    textures: HashMap<&'static str, (TransientDesc, wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl TransientTextures {
+    pub fn prepare(&mut self, transients: &HashMap<&'static str, TransientDesc>, context: &RenderContext) {
+        self.textures.retain(|name, (desc, texture, _)| {
+            let width = Self::scaled(context.config.width, desc.scale);
+            let height = Self::scaled(context.config.height, desc.scale);
+            transients.get(name) == Some(desc) && texture.width() == width && texture.height() == height
+        });
+
+        for (name, desc) in transients {
+            if self.textures.contains_key(name) {
+                continue;
+            }
+
+            let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(name),
+                size: wgpu::Extent3d {
+                    width: Self::scaled(context.config.width, desc.scale),
+                    height: Self::scaled(context.config.height, desc.scale),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: desc.format,
+                usage: desc.usage,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.textures.insert(name, (*desc, texture, view));
+        }
+    }
+
+    pub fn view(&self, name: &str) -> Option<&wgpu::TextureView> {
+        self.textures.get(name).map(|(_, _, view)| view)
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&wgpu::Texture> {
+        self.textures.get(name).map(|(_, texture, _)| texture)
+    }
+
+    fn scaled(size: u32, scale: f32) -> u32 {
+        ((size as f32 * scale) as u32).max(1)
+    }
+}
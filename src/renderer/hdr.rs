@@ -2,14 +2,26 @@ use std::io::Cursor;
 
 use image::{ImageDecoder, codecs::hdr::HdrDecoder};
 
+use wgpu::util::DeviceExt;
+
 use crate::renderer::{
     context::RenderContext,
     texture::{CubeTexture, Texture},
 };
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ToneMapUniform {
+    exposure: f32,
+    distortion: f32,
+    _padding: [f32; 2],
+}
+
 pub struct HdrPipeline {
     pipeline: wgpu::RenderPipeline,
     texture: Texture,
+    tone_map: wgpu::Buffer,
+    scene_color: Texture,
     width: u32,
     height: u32,
     format: wgpu::TextureFormat,
@@ -29,6 +41,7 @@ impl HdrPipeline {
             &sampler,
             Some("HDR texture"),
         );
+        let scene_color = Self::create_scene_color(device, config, format);
 
         let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("HDR layout"),
@@ -49,10 +62,29 @@ impl HdrPipeline {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                     count: None,
                 },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
-        let bind_group = Self::create_bind_group(device, &texture, &layout);
+        let tone_map = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tone map buffer"),
+            contents: bytemuck::bytes_of(&ToneMapUniform {
+                exposure: 1.0,
+                distortion: 0.0,
+                _padding: [0.0; 2],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::create_bind_group(device, &texture, &tone_map, &layout);
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("HDR shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("../../res/hdr.wgsl").into()),
@@ -104,6 +136,8 @@ impl HdrPipeline {
 
         Self {
             texture,
+            tone_map,
+            scene_color,
             width: config.width,
             height: config.height,
             format,
@@ -122,14 +156,42 @@ impl HdrPipeline {
             &wgpu::SamplerDescriptor::default(),
             Some("HDR texture"),
         );
+        self.scene_color = Self::create_scene_color(device, config, self.format);
+
+        self.bind_group = Self::create_bind_group(device, &self.texture, &self.tone_map, &self.layout);
+    }
 
-        self.bind_group = Self::create_bind_group(device, &self.texture, &self.layout);
+    // Exposure multiplies the HDR image before tone mapping, distortion warps it radially
+    pub fn set_tone_map(&self, exposure: f32, distortion: f32, queue: &wgpu::Queue) {
+        let uniform = ToneMapUniform {
+            exposure,
+            distortion,
+            _padding: [0.0; 2],
+        };
+        queue.write_buffer(&self.tone_map, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
     }
 
     pub fn view(&self) -> &wgpu::TextureView {
         &self.texture.view
     }
 
+    pub fn scene_color(&self) -> &Texture {
+        &self.scene_color
+    }
+
+    // Snapshot of the opaque scene, sampled by transmissive materials
+    pub fn copy_to_scene_color(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_texture(
+            self.texture.texture().as_image_copy(),
+            self.scene_color.texture().as_image_copy(),
+            self.texture.texture().size(),
+        );
+    }
+
     pub fn format(&self) -> wgpu::TextureFormat {
         self.format
     }
@@ -146,7 +208,35 @@ impl HdrPipeline {
         &self.layout
     }
 
-    fn create_bind_group(device: &wgpu::Device, texture: &Texture, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
+    fn create_scene_color(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+    ) -> Texture {
+        let sampler = wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        };
+
+        Texture::create_2d_texture(
+            device,
+            config.width,
+            config.height,
+            format,
+            &sampler,
+            Some("Scene color texture"),
+        )
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        texture: &Texture,
+        tone_map: &wgpu::Buffer,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some("HDR bind group"),
             layout,
@@ -159,6 +249,10 @@ impl HdrPipeline {
                     binding: 1,
                     resource: wgpu::BindingResource::Sampler(&texture.sampler),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tone_map.as_entire_binding(),
+                },
             ],
         })
     }
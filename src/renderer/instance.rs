@@ -19,6 +19,8 @@ use crate::renderer::{context::RenderContext, scene::SceneGraph, vertex::Vertex}
 pub struct Instance {
     pub transform_index: u32,
     pub normal_index: u32,
+    // Joint palette of a skinned entity, unused otherwise
+    pub palette_index: u32,
 }
 
 impl Instance {
@@ -41,6 +43,11 @@ impl Vertex for Instance {
                     shader_location: 1,
                     format: wgpu::VertexFormat::Uint32,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[u32; 2]>() as u64,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         }
     }
@@ -57,7 +64,7 @@ impl InstancePool {
         let buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Instance pool"),
             size: (capacity * Instance::STRIDE) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
             mapped_at_creation: false,
         });
 
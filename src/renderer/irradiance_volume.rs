@@ -0,0 +1,403 @@
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{camera::Camera, context::RenderContext, texture::Texture};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridUniform {
+    min: [f32; 4],
+    max: [f32; 4],
+    // The w component is one while the volume holds a bake
+    resolution: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BakeParams {
+    right: [f32; 4],
+    up: [f32; 4],
+    forward: [f32; 4],
+    probe_index: u32,
+    face: u32,
+    _padding: [u32; 2],
+}
+
+// Probes are spread evenly over the box, corners included
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IrradianceGrid {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+    pub resolution: glam::UVec3,
+}
+
+impl Default for IrradianceGrid {
+    fn default() -> Self {
+        Self {
+            min: glam::Vec3::new(-5.0, 0.0, -5.0),
+            max: glam::Vec3::new(5.0, 4.0, 5.0),
+            resolution: glam::UVec3::new(4, 2, 4),
+        }
+    }
+}
+
+impl IrradianceGrid {
+    pub const MAX_RESOLUTION: u32 = 8;
+
+    // Trilinear interpolation needs two probes along every axis
+    pub fn clamped(self) -> Self {
+        Self {
+            min: self.min.min(self.max),
+            max: self.max.max(self.min),
+            resolution: self
+                .resolution
+                .clamp(glam::UVec3::splat(2), glam::UVec3::splat(Self::MAX_RESOLUTION)),
+        }
+    }
+
+    pub fn probe_count(&self) -> u32 {
+        self.resolution.element_product()
+    }
+
+    pub fn positions(&self) -> Vec<glam::Vec3> {
+        let last = (self.resolution - 1).as_vec3();
+        (0..self.resolution.z)
+            .flat_map(|z| (0..self.resolution.y).flat_map(move |y| (0..self.resolution.x).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| self.min + (self.max - self.min) * glam::UVec3::new(x, y, z).as_vec3() / last)
+            .collect()
+    }
+}
+
+// Grid of light probes giving spatially varying diffuse lighting. Each probe renders the scene around it and a compute
+// pass projects the faces onto spherical harmonics, which the mesh shader interpolates by position
+pub struct IrradianceVolume {
+    grid: Option<IrradianceGrid>,
+    coefficients: wgpu::Buffer,
+    uniform: wgpu::Buffer,
+    params: wgpu::Buffer,
+    bake_pipeline: wgpu::ComputePipeline,
+    bake_layout: wgpu::BindGroupLayout,
+    probe_pipeline: wgpu::RenderPipeline,
+    probe_bind_group: wgpu::BindGroup,
+}
+
+impl IrradianceVolume {
+    pub const CAPTURE_SIZE: u32 = 32;
+    const MAX_PROBES: u64 = (IrradianceGrid::MAX_RESOLUTION as u64).pow(3);
+    // Four RGB coefficients padded to vec4
+    const PROBE_SIZE: u64 = 64;
+
+    pub fn new(context: &RenderContext) -> Self {
+        let coefficients = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Irradiance probe buffer"),
+            size: Self::MAX_PROBES * Self::PROBE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let uniform = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Irradiance grid buffer"),
+            contents: bytemuck::bytes_of(&GridUniform {
+                min: [0.0; 4],
+                max: [0.0; 4],
+                resolution: [0; 4],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let params = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Irradiance bake buffer"),
+            size: std::mem::size_of::<BakeParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bake_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Irradiance bake layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let bake_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Irradiance bake shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/irradiance_bake.wgsl").into()),
+        });
+
+        let bake_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Irradiance bake pipeline layout"),
+            bind_group_layouts: &[&bake_layout],
+            push_constant_ranges: &[],
+        });
+
+        let bake_pipeline = context
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Irradiance bake pipeline"),
+                layout: Some(&bake_pipeline_layout),
+                module: &bake_shader,
+                entry_point: Some("project_face"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        let probe_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Irradiance probe layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let probe_bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Irradiance probe bind group"),
+            layout: &probe_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: coefficients.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform.as_entire_binding(),
+                },
+            ],
+        });
+
+        let probe_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Irradiance probe shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/irradiance_probes.wgsl").into()),
+        });
+
+        let probe_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Irradiance probe pipeline layout"),
+            bind_group_layouts: &[&context.camera_bind_group_layout, &probe_layout],
+            push_constant_ranges: &[],
+        });
+
+        let probe_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Irradiance probe pipeline"),
+            layout: Some(&probe_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &probe_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &probe_shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.hdr.format(),
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        Self {
+            grid: None,
+            coefficients,
+            uniform,
+            params,
+            bake_pipeline,
+            bake_layout,
+            probe_pipeline,
+            probe_bind_group,
+        }
+    }
+
+    pub fn coefficients(&self) -> &wgpu::Buffer {
+        &self.coefficients
+    }
+
+    pub fn uniform(&self) -> &wgpu::Buffer {
+        &self.uniform
+    }
+
+    pub fn grid(&self) -> Option<IrradianceGrid> {
+        self.grid
+    }
+
+    pub fn set_grid(&mut self, grid: Option<IrradianceGrid>) {
+        self.grid = grid.map(IrradianceGrid::clamped);
+    }
+
+    // Disabled while baking, so the captures only see the environment's irradiance
+    pub fn upload(&self, is_enabled: bool, context: &RenderContext) {
+        let uniform = match self.grid {
+            Some(grid) => GridUniform {
+                min: grid.min.extend(0.0).to_array(),
+                max: grid.max.extend(0.0).to_array(),
+                resolution: grid.resolution.extend(is_enabled as u32).to_array(),
+            },
+            None => GridUniform {
+                min: [0.0; 4],
+                max: [0.0; 4],
+                resolution: [0; 4],
+            },
+        };
+
+        context
+            .queue
+            .write_buffer(&self.uniform, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn capture_config(format: wgpu::TextureFormat) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: Self::CAPTURE_SIZE,
+            height: Self::CAPTURE_SIZE,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 1,
+        }
+    }
+
+    // Each face needs its own submission since the parameters are rewritten in between
+    pub fn bake_face(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        probe_index: u32,
+        face: u32,
+        view: glam::Mat4,
+        source: &wgpu::TextureView,
+        context: &RenderContext,
+    ) {
+        let inverse = view.inverse();
+        let params = BakeParams {
+            right: inverse.x_axis.to_array(),
+            up: inverse.y_axis.to_array(),
+            forward: (-inverse.z_axis).to_array(),
+            probe_index,
+            face,
+            _padding: [0; 2],
+        };
+        context.queue.write_buffer(&self.params, 0, bytemuck::bytes_of(&params));
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Irradiance bake bind group"),
+            layout: &self.bake_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.coefficients.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.params.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Irradiance bake pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.bake_pipeline);
+        compute_pass.set_bind_group(0, &bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+    }
+
+    pub fn render_probes(&self, encoder: &mut wgpu::CommandEncoder, camera: &Camera, context: &RenderContext) {
+        let Some(grid) = self.grid else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Irradiance probe render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &context.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.probe_pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.probe_bind_group, &[]);
+        render_pass.draw(0..6, 0..grid.probe_count());
+    }
+}
@@ -0,0 +1,45 @@
+// One bit per layer. An entity is drawn in every view whose mask shares a layer with its own
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RenderLayers(pub u32);
+
+impl RenderLayers {
+    // Layers with a name in the UI, the remaining bits are still honored
+    pub const COUNT: usize = 4;
+    pub const NAMES: [&'static str; Self::COUNT] = ["Scene", "Debug", "Helpers", "Extra"];
+
+    pub const ALL: Self = Self(u32::MAX);
+    // Everything loaded or spawned starts out here
+    pub const SCENE: Self = Self(1 << 0);
+    // Light gizmos
+    pub const DEBUG: Self = Self(1 << 1);
+    // Only meant for the UI, left out of screenshots and probe captures
+    pub const HELPERS: Self = Self(1 << 2);
+    // What screenshots and reflection probes see
+    pub const CAPTURE: Self = Self::ALL.without(Self::HELPERS);
+
+    pub fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    pub fn contains_layer(self, index: usize) -> bool {
+        self.0 & (1 << index) != 0
+    }
+
+    pub fn set_layer(&mut self, index: usize, is_enabled: bool) {
+        if is_enabled {
+            self.0 |= 1 << index;
+        } else {
+            self.0 &= !(1 << index);
+        }
+    }
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::SCENE
+    }
+}
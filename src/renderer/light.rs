@@ -1,59 +1,183 @@
 use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
 
 use crate::renderer::{context::RenderContext, transform::TransformUniform};
 
 pub struct LightId(pub usize);
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot,
+}
+
+impl LightKind {
+    pub const ALL: [Self; 3] = [Self::Directional, Self::Point, Self::Spot];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Directional => "Directional",
+            Self::Point => "Point",
+            Self::Spot => "Spot",
+        }
+    }
+}
+
+// Cutoffs are half angles of the cone in radians, a range of zero never cuts the light off
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Light {
     Directional {
         direction: glam::Vec3,
         color: glam::Vec3,
         intensity: f32,
+        casts_shadows: bool,
     },
     Point {
         position: glam::Vec3,
         color: glam::Vec3,
         intensity: f32,
+        range: f32,
+        casts_shadows: bool,
     },
     Spot {
         position: glam::Vec3,
         direction: glam::Vec3,
         color: glam::Vec3,
         intensity: f32,
-        cutoff: f32,
+        inner_cutoff: f32,
+        outer_cutoff: f32,
+        range: f32,
+        casts_shadows: bool,
     },
 }
 
 impl Light {
-    pub fn to_light_uniform(&self) -> LightUniform {
+    pub const DEFAULT_INNER_CUTOFF: f32 = 20.0_f32.to_radians();
+    pub const DEFAULT_OUTER_CUTOFF: f32 = 30.0_f32.to_radians();
+
+    pub fn kind(&self) -> LightKind {
         match self {
-            Self::Directional { color, intensity, .. } => LightUniform {
-                color: color.to_array(),
-                kind: 0,
-                intensity: *intensity,
-                cutoff: 0.0,
-                _padding: [0; 2],
+            Self::Directional { .. } => LightKind::Directional,
+            Self::Point { .. } => LightKind::Point,
+            Self::Spot { .. } => LightKind::Spot,
+        }
+    }
+
+    // Keeps whatever the two kinds share, the rest starts from defaults
+    pub fn with_kind(&self, kind: LightKind) -> Self {
+        let (color, intensity, casts_shadows) = match self {
+            Self::Directional {
+                color,
+                intensity,
+                casts_shadows,
+                ..
+            }
+            | Self::Point {
+                color,
+                intensity,
+                casts_shadows,
+                ..
+            }
+            | Self::Spot {
+                color,
+                intensity,
+                casts_shadows,
+                ..
+            } => (*color, *intensity, *casts_shadows),
+        };
+        let transform = self.to_transform();
+        let position = transform.w_axis.truncate();
+        let direction = match self {
+            Self::Point { .. } => glam::Vec3::NEG_Y,
+            _ => -transform.z_axis.truncate(),
+        };
+        let range = match self {
+            Self::Point { range, .. } | Self::Spot { range, .. } => *range,
+            Self::Directional { .. } => 0.0,
+        };
+
+        match kind {
+            LightKind::Directional => Self::Directional {
+                direction,
+                color,
+                intensity,
+                casts_shadows,
             },
-            Self::Point { color, intensity, .. } => LightUniform {
-                color: color.to_array(),
-                kind: 1,
-                intensity: *intensity,
-                cutoff: 0.0,
-                _padding: [0; 2],
+            LightKind::Point => Self::Point {
+                position,
+                color,
+                intensity,
+                range,
+                casts_shadows,
             },
+            LightKind::Spot => Self::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                inner_cutoff: Self::DEFAULT_INNER_CUTOFF,
+                outer_cutoff: Self::DEFAULT_OUTER_CUTOFF,
+                range,
+                casts_shadows,
+            },
+        }
+    }
+
+    // Picks up a move of the light's entity, the transform's -Z is where the light points
+    pub fn set_transform(&mut self, transform: glam::Mat4) {
+        let new_position = transform.w_axis.truncate();
+        let new_direction = (-transform.z_axis.truncate()).normalize_or(glam::Vec3::NEG_Y);
+        match self {
+            Self::Directional { direction, .. } => *direction = new_direction,
+            Self::Point { position, .. } => *position = new_position,
+            Self::Spot {
+                position, direction, ..
+            } => {
+                *position = new_position;
+                *direction = new_direction;
+            }
+        }
+    }
+
+    pub fn to_light_uniform(&self) -> LightUniform {
+        match self {
+            Self::Directional {
+                color,
+                intensity,
+                casts_shadows,
+                ..
+            } => LightUniform::new(
+                LightKind::Directional,
+                *color,
+                *intensity,
+                0.0,
+                [0.0; 2],
+                *casts_shadows,
+            ),
+            Self::Point {
+                color,
+                intensity,
+                range,
+                casts_shadows,
+                ..
+            } => LightUniform::new(LightKind::Point, *color, *intensity, *range, [0.0; 2], *casts_shadows),
             Self::Spot {
                 color,
                 intensity,
-                cutoff,
+                inner_cutoff,
+                outer_cutoff,
+                range,
+                casts_shadows,
                 ..
-            } => LightUniform {
-                color: color.to_array(),
-                kind: 2,
-                intensity: *intensity,
-                cutoff: *cutoff,
-                _padding: [0; 2],
-            },
+            } => LightUniform::new(
+                LightKind::Spot,
+                *color,
+                *intensity,
+                *range,
+                [*inner_cutoff, *outer_cutoff],
+                *casts_shadows,
+            ),
         }
     }
 
@@ -95,24 +219,46 @@ impl Light {
     }
 }
 
+// The cone is stored as cosines so shaders compare them against a dot product directly
 #[repr(C, align(16))]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct LightUniform {
     pub color: [f32; 3],
-    pub cutoff: f32,
     pub intensity: f32,
     pub kind: u32,
-    _padding: [u32; 2],
+    pub range: f32,
+    pub cos_inner_cutoff: f32,
+    pub cos_outer_cutoff: f32,
+    pub flags: u32,
+    _padding: [u32; 3],
 }
 
 impl LightUniform {
-    pub fn new(kind: u32, color: glam::Vec3, intensity: f32, cutoff: f32) -> Self {
+    pub const CASTS_SHADOWS: u32 = 1;
+
+    pub fn new(
+        kind: LightKind,
+        color: glam::Vec3,
+        intensity: f32,
+        range: f32,
+        [inner_cutoff, outer_cutoff]: [f32; 2],
+        casts_shadows: bool,
+    ) -> Self {
+        // A cone without a soft edge still needs a sliver between the two to avoid dividing by zero
+        let outer_cutoff = outer_cutoff.max(inner_cutoff + 1e-3);
         Self {
             color: color.to_array(),
-            cutoff,
             intensity,
-            kind,
-            _padding: [0; 2],
+            kind: kind as u32,
+            range: range.max(0.0),
+            cos_inner_cutoff: inner_cutoff.cos(),
+            cos_outer_cutoff: outer_cutoff.cos(),
+            flags: if casts_shadows { Self::CASTS_SHADOWS } else { 0 },
+            _padding: [0; 3],
         }
     }
+
+    pub fn casts_shadows(&self) -> bool {
+        self.flags & Self::CASTS_SHADOWS != 0
+    }
 }
@@ -0,0 +1,594 @@
+use std::collections::HashSet;
+
+use bytemuck::{Pod, Zeroable};
+use uuid::Uuid;
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{
+    component::ComponentId,
+    context::RenderContext,
+    light::LightUniform,
+    material::Material,
+    mesh::Primitive,
+    quantize::MeshPipelines,
+    scene::{Geometry, Renderable, SceneGraph},
+    texture::Texture,
+    transform::TransformUniform,
+};
+
+pub const MAX_LIGHTMAP_RESOLUTION: u32 = 2048;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct LightmapUniform {
+    resolution: u32,
+    sample_index: u32,
+    samples_per_step: u32,
+    max_bounces: u32,
+    light_count: u32,
+    triangle_count: u32,
+    _padding: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct RasterUniform {
+    model: [[f32; 4]; 4],
+    normal: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct BakeLight {
+    position: [f32; 3],
+    kind: u32,
+    direction: [f32; 3],
+    range: f32,
+    radiance: [f32; 3],
+    cos_inner_cutoff: f32,
+    cos_outer_cutoff: f32,
+    flags: u32,
+    _padding: [u32; 2],
+}
+
+impl BakeLight {
+    fn new(light: &LightUniform, transform: glam::Mat4) -> Self {
+        Self {
+            position: transform.w_axis.truncate().to_array(),
+            kind: light.kind,
+            direction: (-transform.z_axis.truncate()).normalize_or_zero().to_array(),
+            range: light.range,
+            radiance: (glam::Vec3::from_array(light.color) * light.intensity).to_array(),
+            cos_inner_cutoff: light.cos_inner_cutoff,
+            cos_outer_cutoff: light.cos_outer_cutoff,
+            flags: light.flags,
+            ..Zeroable::zeroed()
+        }
+    }
+}
+
+// A bake in progress, the G-buffer stays fixed while samples accumulate
+struct LightmapBake {
+    entity_id: Uuid,
+    samples: u32,
+    uniform: LightmapUniform,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    lightmap: Texture,
+    materials: Vec<ComponentId<Material>>,
+}
+
+// Bakes direct and indirect diffuse lighting into the second UV set of a static mesh. The mesh is rasterized in UV
+// space once, then every frame adds a few paths per texel until the sample count is reached. The result replaces the
+// dynamic diffuse lighting of the mesh's materials, so other entities sharing those materials show it too
+pub struct Lightmapper {
+    raster_pipelines: MeshPipelines,
+    raster_layout: wgpu::BindGroupLayout,
+    bake_pipeline: wgpu::ComputePipeline,
+    resolve_pipeline: wgpu::ComputePipeline,
+    bake_layout: wgpu::BindGroupLayout,
+    bake: Option<LightmapBake>,
+}
+
+impl Lightmapper {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const GBUFFER_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+    const SAMPLES_PER_STEP: u32 = 4;
+    const MAX_BOUNCES: u32 = 3;
+    const WORKGROUP_SIZE: u32 = 8;
+
+    pub fn new(scene_layout: &wgpu::BindGroupLayout, context: &RenderContext) -> Self {
+        let raster_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Lightmap raster layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let raster_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lightmap raster shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/lightmap_raster.wgsl").into()),
+        });
+
+        let raster_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lightmap raster pipeline layout"),
+            bind_group_layouts: &[&raster_layout],
+            push_constant_ranges: &[],
+        });
+
+        let gbuffer_target = Some(wgpu::ColorTargetState {
+            format: Self::GBUFFER_FORMAT,
+            blend: None,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+
+        // The lightmap UVs are bound as the only UV set
+        let raster_pipelines = MeshPipelines::new(|precision| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("Lightmap raster pipeline ({})", precision.to_str())),
+                layout: Some(&raster_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &raster_shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: precision.shader_constants(),
+                        ..Default::default()
+                    },
+                    buffers: &precision.layout_builder(1).build(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &raster_shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[gbuffer_target.clone(), gbuffer_target.clone()],
+                }),
+                // Winding in UV space says nothing about facing
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        let buffer_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: match read_only {
+                    Some(read_only) => wgpu::BufferBindingType::Storage { read_only },
+                    None => wgpu::BufferBindingType::Uniform,
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let gbuffer_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let bake_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Lightmap bake layout"),
+                entries: &[
+                    buffer_entry(0, None),
+                    buffer_entry(1, Some(false)),
+                    gbuffer_entry(2),
+                    gbuffer_entry(3),
+                    buffer_entry(4, Some(true)),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::StorageTexture {
+                            access: wgpu::StorageTextureAccess::WriteOnly,
+                            format: Self::FORMAT,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let bake_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lightmap bake shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("../../res/ray_tracing.wgsl"),
+                    include_str!("../../res/lightmap_bake.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let bake_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Lightmap bake pipeline layout"),
+            bind_group_layouts: &[&bake_layout, scene_layout, &context.environment_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label, entry_point| {
+            context
+                .device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&bake_pipeline_layout),
+                    module: &bake_shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    cache: None,
+                })
+        };
+
+        let bake_pipeline = create_pipeline("Lightmap bake pipeline", "bake_samples");
+        let resolve_pipeline = create_pipeline("Lightmap resolve pipeline", "resolve");
+
+        Self {
+            raster_pipelines,
+            raster_layout,
+            bake_pipeline,
+            resolve_pipeline,
+            bake_layout,
+            bake: None,
+        }
+    }
+
+    pub fn is_baking(&self) -> bool {
+        self.bake.is_some()
+    }
+
+    // Rasterizes the entity's G-buffer and captures the lights, replacing any bake still in progress
+    pub fn begin(
+        &mut self,
+        entity_id: Uuid,
+        resolution: u32,
+        samples: u32,
+        scene: &SceneGraph,
+        context: &RenderContext,
+    ) -> anyhow::Result<()> {
+        let Some(node_index) = scene.nodes.get_index(&entity_id) else {
+            anyhow::bail!("Only meshes can be lightmapped");
+        };
+        let Some(Renderable::Mesh(handles)) = scene.nodes.get(&entity_id).and_then(|id| scene.renderables.get(id))
+        else {
+            anyhow::bail!("Only meshes can be lightmapped");
+        };
+
+        let primitives = handles
+            .iter()
+            .filter_map(|handle| match scene.geometries.get_by_id(handle.geometry_index) {
+                Some(Geometry::Primitive(primitive)) => Some(primitive),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if primitives.iter().any(|primitive| primitive.uv_set_count < 2) {
+            anyhow::bail!("The mesh has no second UV set to lay the lightmap out in");
+        }
+
+        let transform = scene
+            .node_transform_index
+            .get_mapping(node_index.index() as usize)
+            .and_then(|index| scene.transforms.get_by_index(index as usize))
+            .map(TransformUniform::to_mat4)
+            .unwrap_or_default();
+
+        let resolution = resolution.clamp(16, MAX_LIGHTMAP_RESOLUTION);
+        let size = wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 1,
+        };
+
+        let create_texture = |label, format, usage| {
+            context.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+
+        let gbuffer_usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let position_view = create_texture("Lightmap position texture", Self::GBUFFER_FORMAT, gbuffer_usage)
+            .create_view(&Default::default());
+        let normal_view = create_texture("Lightmap normal texture", Self::GBUFFER_FORMAT, gbuffer_usage)
+            .create_view(&Default::default());
+        self.rasterize(&primitives, transform, &position_view, &normal_view, context);
+
+        let texture = create_texture(
+            "Lightmap texture",
+            Self::FORMAT,
+            wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let view = texture.create_view(&Default::default());
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Lightmap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let lightmap = Texture { texture, view, sampler };
+
+        let mut lights = scene
+            .lights
+            .iter_with_index()
+            .filter_map(|(_, index, light)| {
+                let transform = scene
+                    .lights_transform_index
+                    .get_mapping(index)
+                    .and_then(|index| scene.transforms.get_by_index(index as usize))
+                    .map(TransformUniform::to_mat4)?;
+                Some(BakeLight::new(light, transform))
+            })
+            .collect::<Vec<_>>();
+        let light_count = lights.len() as u32;
+
+        // Storage buffers can't be empty
+        if lights.is_empty() {
+            lights.push(BakeLight::zeroed());
+        }
+
+        let uniform = LightmapUniform {
+            resolution,
+            sample_index: 0,
+            samples_per_step: Self::SAMPLES_PER_STEP,
+            max_bounces: Self::MAX_BOUNCES,
+            light_count,
+            triangle_count: 0,
+            _padding: [0; 2],
+        };
+
+        let uniform_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lightmap uniform buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let accumulation_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lightmap accumulation buffer"),
+            size: (resolution * resolution) as u64 * std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let light_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lightmap light buffer"),
+            contents: bytemuck::cast_slice(&lights),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lightmap bake bind group"),
+            layout: &self.bake_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: accumulation_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&position_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&normal_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&lightmap.view),
+                },
+            ],
+        });
+
+        self.bake = Some(LightmapBake {
+            entity_id,
+            samples: samples.max(1),
+            uniform,
+            uniform_buffer,
+            bind_group,
+            lightmap,
+            materials: Self::materials(entity_id, scene),
+        });
+
+        Ok(())
+    }
+
+    // Adds the next few samples per texel, returns the entity with its sample count and total
+    pub fn step(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        triangle_count: u32,
+        scene_bind_group: &wgpu::BindGroup,
+        environment_bind_group: &wgpu::BindGroup,
+        context: &RenderContext,
+    ) -> Option<(Uuid, u32, u32)> {
+        let bake = self.bake.as_mut()?;
+        bake.uniform.triangle_count = triangle_count;
+        context
+            .queue
+            .write_buffer(&bake.uniform_buffer, 0, bytemuck::bytes_of(&bake.uniform));
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Lightmap bake pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.bake_pipeline);
+            compute_pass.set_bind_group(0, &bake.bind_group, &[]);
+            compute_pass.set_bind_group(1, scene_bind_group, &[]);
+            compute_pass.set_bind_group(2, environment_bind_group, &[]);
+
+            let workgroups = bake.uniform.resolution.div_ceil(Self::WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(workgroups, workgroups, 1);
+        }
+
+        bake.uniform.sample_index += bake.uniform.samples_per_step;
+        Some((
+            bake.entity_id,
+            bake.uniform.sample_index.min(bake.samples),
+            bake.samples,
+        ))
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.bake
+            .as_ref()
+            .is_some_and(|bake| bake.uniform.sample_index >= bake.samples)
+    }
+
+    // Resolves the finished bake and switches the entity's materials over to it
+    pub fn finish(&mut self, scene_bind_group: &wgpu::BindGroup, scene: &mut SceneGraph, context: &RenderContext) {
+        let Some(bake) = self.bake.take() else {
+            return;
+        };
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Lightmap resolve encoder"),
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Lightmap resolve pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.resolve_pipeline);
+            compute_pass.set_bind_group(0, &bake.bind_group, &[]);
+            compute_pass.set_bind_group(1, scene_bind_group, &[]);
+            compute_pass.set_bind_group(2, scene.environment_map.bind_group(), &[]);
+
+            let workgroups = bake.uniform.resolution.div_ceil(Self::WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(workgroups, workgroups, 1);
+        }
+
+        context.queue.submit(Some(encoder.finish()));
+
+        for id in bake.materials {
+            if let Some(material) = scene.materials.get_by_id_mut(id) {
+                material.set_lightmap(Some(bake.lightmap.clone()), context);
+            }
+        }
+    }
+
+    // Cancels a bake of the entity and returns its materials to dynamic lighting
+    pub fn clear(&mut self, entity_id: Uuid, scene: &mut SceneGraph, context: &RenderContext) {
+        if self.bake.as_ref().is_some_and(|bake| bake.entity_id == entity_id) {
+            self.bake = None;
+        }
+
+        for id in Self::materials(entity_id, scene) {
+            if let Some(material) = scene.materials.get_by_id_mut(id) {
+                material.set_lightmap(None, context);
+            }
+        }
+    }
+
+    fn rasterize(
+        &self,
+        primitives: &[&Primitive],
+        transform: glam::Mat4,
+        position_view: &wgpu::TextureView,
+        normal_view: &wgpu::TextureView,
+        context: &RenderContext,
+    ) {
+        let uniform = RasterUniform {
+            model: transform.to_cols_array_2d(),
+            normal: transform.inverse().transpose().to_cols_array_2d(),
+        };
+
+        let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Lightmap raster buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lightmap raster bind group"),
+            layout: &self.raster_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Lightmap raster encoder"),
+        });
+
+        {
+            let attachment = |view| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            };
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Lightmap raster pass"),
+                color_attachments: &[attachment(position_view), attachment(normal_view)],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            for primitive in primitives {
+                render_pass.set_pipeline(self.raster_pipelines.get(primitive.vertex_precision));
+                render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, primitive.uv_buffers[1].slice(..));
+                render_pass.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
+                render_pass.draw_indexed(0..primitive.num_elements, 0, 0..1);
+            }
+        }
+
+        context.queue.submit(Some(encoder.finish()));
+    }
+
+    fn materials(entity_id: Uuid, scene: &SceneGraph) -> Vec<ComponentId<Material>> {
+        let Some(Renderable::Mesh(handles)) = scene.nodes.get(&entity_id).and_then(|id| scene.renderables.get(id))
+        else {
+            return Vec::new();
+        };
+
+        let mut seen = HashSet::new();
+        handles
+            .iter()
+            .map(|handle| handle.material_index)
+            .filter(|id| seen.insert(id.index()))
+            .collect()
+    }
+}
@@ -4,19 +4,47 @@ use wgpu::util::DeviceExt;
 
 use crate::renderer::{
     context::RenderContext,
+    memory::Allocation,
     texture::{Texture, TextureInstance, TextureView},
 };
 
+#[derive(Copy, Clone, Debug)]
 pub enum TextureInstanceSlot {
     BaseColor,
     MetallicRoughness,
     Normal,
     Occlusion,
     Emissive,
+    Height,
+    ClearcoatNormal,
+    Lightmap,
 }
 
 impl TextureInstanceSlot {
-    pub const COUNT: u32 = 5;
+    pub const COUNT: u32 = 8;
+    pub const ALL: [Self; Self::COUNT as usize] = [
+        Self::BaseColor,
+        Self::MetallicRoughness,
+        Self::Normal,
+        Self::Occlusion,
+        Self::Emissive,
+        Self::Height,
+        Self::ClearcoatNormal,
+        Self::Lightmap,
+    ];
+
+    // The value a missing texture stands in for, so the slot shades as if it wasn't there
+    pub fn placeholder_color(&self) -> [u8; 4] {
+        match self {
+            Self::BaseColor | Self::MetallicRoughness | Self::Occlusion => [255, 255, 255, 255],
+            Self::Normal | Self::ClearcoatNormal => [128, 128, 255, 255],
+            Self::Emissive | Self::Height | Self::Lightmap => [0, 0, 0, 255],
+        }
+    }
+
+    pub fn mask(&self) -> u32 {
+        1 << *self as u32
+    }
 }
 
 #[repr(C)]
@@ -24,7 +52,8 @@ impl TextureInstanceSlot {
 pub struct MaterialUniform {
     pub base_color_factor: [f32; 4],
     pub emissive_factor: [f32; 3],
-    _padding0: u32,
+    // One bit per TextureInstanceSlot that has a real texture bound
+    pub texture_mask: u32,
     pub metallic_factor: f32,
     pub roughness_factor: f32,
     pub occlusion_strength: f32,
@@ -32,7 +61,19 @@ pub struct MaterialUniform {
     pub alpha_cutoff: f32,
     pub alpha_mode: u32,
     pub double_sided: u32,
-    _padding1: u32,
+    pub displacement_scale: f32,
+    pub parallax_scale: f32,
+    pub parallax_steps: u32,
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub sheen_color_factor: [f32; 3],
+    pub sheen_roughness_factor: f32,
+    pub clearcoat_normal_scale: f32,
+    pub transmission_factor: f32,
+    pub ior: f32,
+    pub thickness_factor: f32,
+    pub attenuation_color: [f32; 3],
+    pub attenuation_distance: f32,
 }
 
 #[derive(Clone, Debug)]
@@ -41,22 +82,30 @@ pub struct Material {
     pub uniform_buffer: wgpu::Buffer,
     pub textures: Vec<TextureInstance>,
     pub bind_group: wgpu::BindGroup,
+    _allocation: Allocation,
 }
 
 impl Material {
     pub fn new(material: MaterialView, label: Option<&str>, context: &RenderContext) -> Self {
+        let has_height = material.height.is_some();
+        let has_clearcoat_normal = material.clearcoat_normal.is_some();
         let material_textures = [
             material.base_color,
             material.metallic_roughness,
             material.normal,
             material.occlusion,
             material.emissive,
+            material.height,
+            material.clearcoat_normal,
+            // Lightmaps are baked in the renderer, imported materials never carry one
+            None,
         ];
 
         let textures = material_textures
             .iter()
+            .zip(TextureInstanceSlot::ALL)
             .enumerate()
-            .map(|(index, maybe_view)| {
+            .map(|(index, (maybe_view, slot))| {
                 if let Some(view) = maybe_view {
                     TextureInstance {
                         texture: Texture::from_view(&context.device, &context.queue, view, label),
@@ -64,16 +113,23 @@ impl Material {
                     }
                 } else {
                     TextureInstance {
-                        texture: context.placeholder_texture(),
+                        texture: context.placeholder_texture(slot),
                         uv_index: index as u32,
                     }
                 }
             })
             .collect::<Vec<_>>();
 
+        let texture_mask = material_textures
+            .iter()
+            .zip(TextureInstanceSlot::ALL)
+            .filter(|(view, _)| view.is_some())
+            .fold(0, |mask, (_, slot)| mask | slot.mask());
+
         let uniform = MaterialUniform {
             base_color_factor: material.base_color_factor,
             emissive_factor: material.emissive_factor,
+            texture_mask,
             metallic_factor: material.metallic_factor,
             roughness_factor: material.roughness_factor,
             occlusion_strength: material.occlusion_strength,
@@ -81,8 +137,31 @@ impl Material {
             alpha_cutoff: material.alpha_cutoff,
             alpha_mode: material.alpha_mode as u32,
             double_sided: material.double_sided as u32,
-            _padding0: 0,
-            _padding1: 0,
+            displacement_scale: if has_height {
+                material.displacement_scale
+            } else {
+                0.0
+            },
+            parallax_scale: if has_height {
+                material.parallax_scale
+            } else {
+                0.0
+            },
+            parallax_steps: material.parallax_steps,
+            clearcoat_factor: material.clearcoat_factor,
+            clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+            sheen_color_factor: material.sheen_color_factor,
+            sheen_roughness_factor: material.sheen_roughness_factor,
+            clearcoat_normal_scale: if has_clearcoat_normal {
+                material.clearcoat_normal_scale
+            } else {
+                0.0
+            },
+            transmission_factor: material.transmission_factor,
+            ior: material.ior,
+            thickness_factor: material.thickness_factor,
+            attenuation_color: material.attenuation_color,
+            attenuation_distance: material.attenuation_distance,
         };
 
         let uniform_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -91,6 +170,60 @@ impl Material {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let bind_group = Self::create_bind_group(&uniform_buffer, &textures, label, context);
+        // Placeholders are shared, only the material's own textures count against the budget
+        let allocation = context.memory.track_textures(
+            textures
+                .iter()
+                .zip(&material_textures)
+                .filter(|(_, view)| view.is_some())
+                .map(|(instance, _)| &instance.texture.texture),
+        );
+
+        Self {
+            uniform,
+            uniform_buffer,
+            textures,
+            bind_group,
+            _allocation: allocation,
+        }
+    }
+
+    pub fn is_transmissive(&self) -> bool {
+        self.uniform.transmission_factor > 0.0
+    }
+
+    // A lightmap replaces the direct and environment diffuse lighting, None goes back to dynamic lighting
+    pub fn set_lightmap(&mut self, lightmap: Option<Texture>, context: &RenderContext) {
+        let slot = TextureInstanceSlot::Lightmap;
+        match lightmap {
+            Some(texture) => {
+                self.textures[slot as usize].texture = texture;
+                self.uniform.texture_mask |= slot.mask();
+            }
+            None => {
+                self.textures[slot as usize].texture = context.placeholder_texture(slot);
+                self.uniform.texture_mask &= !slot.mask();
+            }
+        }
+
+        context
+            .queue
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniform));
+        self.bind_group = Self::create_bind_group(
+            &self.uniform_buffer,
+            &self.textures,
+            Some("Lightmapped material"),
+            context,
+        );
+    }
+
+    fn create_bind_group(
+        uniform_buffer: &wgpu::Buffer,
+        textures: &[TextureInstance],
+        label: Option<&str>,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
         let mut bind_group_entries = Vec::new();
         bind_group_entries.push(wgpu::BindGroupEntry {
             binding: 0,
@@ -110,18 +243,11 @@ impl Material {
             ]);
         });
 
-        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
             label,
             layout: &context.texture_bind_group_layout,
             entries: &bind_group_entries,
-        });
-
-        Self {
-            uniform,
-            uniform_buffer,
-            textures,
-            bind_group,
-        }
+        })
     }
 }
 
@@ -131,6 +257,8 @@ pub struct MaterialView<'a> {
     pub normal: Option<TextureView<'a>>,
     pub occlusion: Option<TextureView<'a>>,
     pub emissive: Option<TextureView<'a>>,
+    pub height: Option<TextureView<'a>>,
+    pub clearcoat_normal: Option<TextureView<'a>>,
     pub base_color_factor: [f32; 4],
     pub emissive_factor: [f32; 3],
     pub metallic_factor: f32,
@@ -138,10 +266,60 @@ pub struct MaterialView<'a> {
     pub occlusion_strength: f32,
     pub normal_scale: f32,
     pub alpha_cutoff: f32,
+    pub displacement_scale: f32,
+    pub parallax_scale: f32,
+    pub parallax_steps: u32,
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_normal_scale: f32,
+    pub sheen_color_factor: [f32; 3],
+    pub sheen_roughness_factor: f32,
+    pub transmission_factor: f32,
+    pub ior: f32,
+    pub thickness_factor: f32,
+    pub attenuation_color: [f32; 3],
+    pub attenuation_distance: f32,
     pub alpha_mode: u8,
     pub double_sided: u8,
 }
 
+impl MaterialView<'_> {
+    // Bright magenta, meant to stand out on primitives whose material is missing
+    pub fn fallback() -> Self {
+        Self {
+            base_color: None,
+            metallic_roughness: None,
+            normal: None,
+            occlusion: None,
+            emissive: None,
+            height: None,
+            clearcoat_normal: None,
+            base_color_factor: [1.0, 0.0, 1.0, 1.0],
+            emissive_factor: [0.0, 0.0, 0.0],
+            metallic_factor: 0.0,
+            roughness_factor: 1.0,
+            occlusion_strength: 1.0,
+            normal_scale: 1.0,
+            alpha_cutoff: 0.5,
+            displacement_scale: 0.0,
+            parallax_scale: 0.0,
+            parallax_steps: 0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_normal_scale: 1.0,
+            sheen_color_factor: [0.0; 3],
+            sheen_roughness_factor: 0.0,
+            transmission_factor: 0.0,
+            ior: 1.5,
+            thickness_factor: 0.0,
+            attenuation_color: [1.0; 3],
+            attenuation_distance: 0.0,
+            alpha_mode: 0,
+            double_sided: 1,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct RawMaterial {
@@ -150,6 +328,8 @@ pub struct RawMaterial {
     pub normal: Option<TextureSlot>,
     pub occlusion: Option<TextureSlot>,
     pub emissive: Option<TextureSlot>,
+    pub height: Option<TextureSlot>,
+    pub clearcoat_normal: Option<TextureSlot>,
     pub base_color_factor: [f32; 4],
     pub emissive_factor: [f32; 3],
     pub metallic_factor: f32,
@@ -157,14 +337,56 @@ pub struct RawMaterial {
     pub occlusion_strength: f32,
     pub normal_scale: f32,
     pub alpha_cutoff: f32,
+    pub displacement_scale: f32,
+    pub parallax_scale: f32,
+    pub parallax_steps: u32,
+    pub clearcoat_factor: f32,
+    pub clearcoat_roughness_factor: f32,
+    pub clearcoat_normal_scale: f32,
+    pub sheen_color_factor: [f32; 3],
+    pub sheen_roughness_factor: f32,
+    pub transmission_factor: f32,
+    pub ior: f32,
+    pub thickness_factor: f32,
+    pub attenuation_color: [f32; 3],
+    pub attenuation_distance: f32,
     pub alpha_mode: u8,
     pub double_sided: u8,
     pub _padding: [u8; 2],
 }
 
 impl RawMaterial {
-    pub fn from_gltf(material: gltf::Material) -> Self {
+    pub fn texture_slots(&self) -> [Option<TextureSlot>; 7] {
+        [
+            self.base_color,
+            self.metallic_roughness,
+            self.normal,
+            self.occlusion,
+            self.emissive,
+            self.height,
+            self.clearcoat_normal,
+        ]
+    }
+
+    pub fn texture_slots_mut(&mut self) -> [&mut Option<TextureSlot>; 7] {
+        [
+            &mut self.base_color,
+            &mut self.metallic_roughness,
+            &mut self.normal,
+            &mut self.occlusion,
+            &mut self.emissive,
+            &mut self.height,
+            &mut self.clearcoat_normal,
+        ]
+    }
+
+    pub fn from_gltf(material: gltf::Material, document: &gltf::Document) -> Self {
         let pbr = material.pbr_metallic_roughness();
+        let extension = |name: &str| material.extensions().and_then(|extensions| extensions.get(name));
+        let clearcoat = extension("KHR_materials_clearcoat");
+        let sheen = extension("KHR_materials_sheen");
+        let clearcoat_normal = clearcoat.and_then(|clearcoat| clearcoat.get("clearcoatNormalTexture"));
+        let volume = material.volume();
 
         Self {
             base_color: TextureSlot::from_gltf(pbr.base_color_texture()),
@@ -172,6 +394,8 @@ impl RawMaterial {
             normal: TextureSlot::from_gltf(material.normal_texture()),
             occlusion: TextureSlot::from_gltf(material.occlusion_texture()),
             emissive: TextureSlot::from_gltf(material.emissive_texture()),
+            height: None,
+            clearcoat_normal: clearcoat_normal.and_then(|info| TextureSlot::from_json(info, document)),
             base_color_factor: pbr.base_color_factor(),
             emissive_factor: material.emissive_factor(),
             metallic_factor: pbr.metallic_factor(),
@@ -179,6 +403,35 @@ impl RawMaterial {
             occlusion_strength: material.occlusion_texture().map(|t| t.strength()).unwrap_or(1.0),
             normal_scale: material.normal_texture().map(|t| t.scale()).unwrap_or(1.0),
             alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+            displacement_scale: 0.0,
+            parallax_scale: 0.0,
+            parallax_steps: 0,
+            clearcoat_factor: json_f32(clearcoat, "clearcoatFactor").unwrap_or(0.0),
+            clearcoat_roughness_factor: json_f32(clearcoat, "clearcoatRoughnessFactor").unwrap_or(0.0),
+            clearcoat_normal_scale: json_f32(clearcoat_normal, "scale").unwrap_or(1.0),
+            sheen_color_factor: sheen
+                .and_then(|sheen| sheen.get("sheenColorFactor"))
+                .and_then(|value| value.as_array())
+                .filter(|color| color.len() == 3)
+                .map(|color| std::array::from_fn(|index| color[index].as_f64().unwrap_or(0.0) as f32))
+                .unwrap_or([0.0; 3]),
+            sheen_roughness_factor: json_f32(sheen, "sheenRoughnessFactor").unwrap_or(0.0),
+            transmission_factor: material
+                .transmission()
+                .map(|transmission| transmission.transmission_factor())
+                .unwrap_or(0.0),
+            ior: material.ior().unwrap_or(1.5),
+            thickness_factor: volume.as_ref().map(|volume| volume.thickness_factor()).unwrap_or(0.0),
+            attenuation_color: volume
+                .as_ref()
+                .map(|volume| volume.attenuation_color())
+                .unwrap_or([1.0; 3]),
+            // An infinite distance means no absorption, which the shader encodes as zero
+            attenuation_distance: volume
+                .as_ref()
+                .map(|volume| volume.attenuation_distance())
+                .filter(|distance| distance.is_finite())
+                .unwrap_or(0.0),
             alpha_mode: match material.alpha_mode() {
                 AlphaMode::Opaque => 0,
                 AlphaMode::Mask => 1,
@@ -189,17 +442,65 @@ impl RawMaterial {
         }
     }
 
-    pub fn from_obj(material: &tobj::Material) -> Self {
+    // The glTF spec's material for primitives without one, OBJ faces without a material get it as well
+    pub fn gltf_default() -> Self {
+        Self {
+            base_color: None,
+            metallic_roughness: None,
+            normal: None,
+            occlusion: None,
+            emissive: None,
+            height: None,
+            clearcoat_normal: None,
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            emissive_factor: [0.0, 0.0, 0.0],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            occlusion_strength: 1.0,
+            normal_scale: 1.0,
+            alpha_cutoff: 0.5,
+            displacement_scale: 0.0,
+            parallax_scale: 0.0,
+            parallax_steps: 0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_normal_scale: 1.0,
+            sheen_color_factor: [0.0; 3],
+            sheen_roughness_factor: 0.0,
+            transmission_factor: 0.0,
+            ior: 1.5,
+            thickness_factor: 0.0,
+            attenuation_color: [1.0; 3],
+            attenuation_distance: 0.0,
+            alpha_mode: 0,
+            double_sided: 0,
+            _padding: [0; 2],
+        }
+    }
+
+    pub fn from_obj(
+        material: &tobj::Material,
+        diffuse_index: Option<usize>,
+        normal_index: Option<usize>,
+        height_index: Option<usize>,
+    ) -> Self {
+        let slot = |index: Option<usize>| {
+            index.map(|texture_index| TextureSlot {
+                texture_index: texture_index as u32,
+                ..Default::default()
+            })
+        };
+
+        let displacement_scale = obj_displacement_map(material).map(|(_, scale)| scale).unwrap_or(0.0);
+
         Self {
-            base_color: Some(TextureSlot::default()),
+            base_color: slot(diffuse_index),
             metallic_roughness: None,
-            normal: Some(TextureSlot {
-                texture_index: 1,
-                uv_index: 0,
-                sampler_index: 0,
-            }),
+            normal: slot(normal_index),
             occlusion: None,
             emissive: None,
+            height: slot(height_index),
+            clearcoat_normal: None,
             base_color_factor: [1.0, 1.0, 1.0, 1.0],
             emissive_factor: [0.0, 0.0, 0.0],
             metallic_factor: 1.0,
@@ -207,6 +508,19 @@ impl RawMaterial {
             occlusion_strength: 1.0,
             normal_scale: 1.0,
             alpha_cutoff: 0.5,
+            displacement_scale,
+            parallax_scale: displacement_scale,
+            parallax_steps: 16,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            clearcoat_normal_scale: 1.0,
+            sheen_color_factor: [0.0; 3],
+            sheen_roughness_factor: 0.0,
+            transmission_factor: 0.0,
+            ior: material.optical_density.unwrap_or(1.5),
+            thickness_factor: 0.0,
+            attenuation_color: [1.0; 3],
+            attenuation_distance: 0.0,
             alpha_mode: 0,
             double_sided: 0,
             _padding: [0; 2],
@@ -214,6 +528,31 @@ impl RawMaterial {
     }
 }
 
+// tobj does not parse `disp`, so read it from the unknown parameters, e.g. `disp -bm 0.05 height.png`
+pub fn obj_displacement_map(material: &tobj::Material) -> Option<(&str, f32)> {
+    let value = material.unknown_param.get("disp")?;
+    let mut tokens = value.split_whitespace();
+    let mut scale = 0.1;
+    let mut filename = None;
+
+    while let Some(token) = tokens.next() {
+        if token == "-bm" {
+            scale = tokens.next().and_then(|value| value.parse().ok()).unwrap_or(scale);
+        } else {
+            filename = Some(token);
+        }
+    }
+
+    filename.map(|filename| (filename, scale))
+}
+
+fn json_f32(object: Option<&gltf::json::Value>, key: &str) -> Option<f32> {
+    object
+        .and_then(|object| object.get(key))
+        .and_then(|value| value.as_f64())
+        .map(|value| value as f32)
+}
+
 pub trait GltfTextureInfo {
     fn texture(&self) -> gltf::Texture<'_>;
     fn tex_coord(&self) -> u32;
@@ -278,4 +617,19 @@ impl TextureSlot {
             Some(slot)
         })
     }
+
+    // Texture info from extensions that the gltf crate does not expose, e.g. `clearcoatNormalTexture`
+    pub fn from_json(texture_info: &gltf::json::Value, document: &gltf::Document) -> Option<Self> {
+        let texture_index = texture_info.get("index")?.as_u64()? as usize;
+        let texture = document.textures().nth(texture_index)?;
+
+        Some(Self {
+            texture_index: texture.source().index() as u32,
+            uv_index: texture_info
+                .get("texCoord")
+                .and_then(|value| value.as_u64())
+                .unwrap_or(0) as u32,
+            sampler_index: texture.sampler().index().unwrap_or(0) as u32,
+        })
+    }
 }
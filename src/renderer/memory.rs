@@ -0,0 +1,139 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+const MIB: u64 = 1024 * 1024;
+
+// Bytes of a tracked upload, given back to the budget once the last clone is dropped
+#[derive(Debug)]
+struct AllocationInner {
+    allocated: Arc<AtomicU64>,
+    bytes: u64,
+}
+
+impl Drop for AllocationInner {
+    fn drop(&mut self) {
+        self.allocated.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+// Only held for its drop, like the `_allocation` fields of the resources it tracks
+#[derive(Clone, Debug)]
+pub struct Allocation {
+    _inner: Arc<AllocationInner>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    pub allocated: u64,
+    pub budget: u64,
+}
+
+impl MemoryUsage {
+    // Past this share of the budget the UI warns, uploads keep going until the budget itself is reached
+    pub const WARNING_FRACTION: f64 = 0.8;
+
+    pub fn fraction(&self) -> f64 {
+        self.allocated as f64 / self.budget.max(1) as f64
+    }
+
+    pub fn is_near_limit(&self) -> bool {
+        self.fraction() >= Self::WARNING_FRACTION
+    }
+
+    pub fn allocated_mib(&self) -> u64 {
+        self.allocated / MIB
+    }
+
+    pub fn budget_mib(&self) -> u64 {
+        self.budget / MIB
+    }
+}
+
+// Estimated GPU memory held by uploaded assets. Neither wgpu nor WebGPU report how much video memory is free, so the
+// budget is a guess from the adapter type, well below what the device has so render targets and drivers fit too
+pub struct MemoryBudget {
+    allocated: Arc<AtomicU64>,
+    budget: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(adapter_info: &wgpu::AdapterInfo) -> Self {
+        let budget = if cfg!(target_family = "wasm") {
+            // Browsers lose the device well before native drivers run out
+            1024 * MIB
+        } else {
+            match adapter_info.device_type {
+                wgpu::DeviceType::DiscreteGpu => 4096 * MIB,
+                wgpu::DeviceType::IntegratedGpu | wgpu::DeviceType::VirtualGpu => 2048 * MIB,
+                wgpu::DeviceType::Cpu | wgpu::DeviceType::Other => 1024 * MIB,
+            }
+        };
+
+        log::info!("GPU memory budget: {} MiB", budget / MIB);
+        Self {
+            allocated: Arc::new(AtomicU64::new(0)),
+            budget,
+        }
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            allocated: self.allocated.load(Ordering::Relaxed),
+            budget: self.budget,
+        }
+    }
+
+    pub fn fits(&self, bytes: u64) -> bool {
+        self.allocated.load(Ordering::Relaxed) + bytes <= self.budget
+    }
+
+    pub fn exceeds_budget(&self, bytes: u64) -> bool {
+        bytes > self.budget
+    }
+
+    pub fn track(&self, bytes: u64) -> Allocation {
+        self.allocated.fetch_add(bytes, Ordering::Relaxed);
+        Allocation {
+            _inner: Arc::new(AllocationInner {
+                allocated: Arc::clone(&self.allocated),
+                bytes,
+            }),
+        }
+    }
+
+    pub fn track_buffers<'a>(&self, buffers: impl IntoIterator<Item = &'a wgpu::Buffer>) -> Allocation {
+        self.track(buffers.into_iter().map(wgpu::Buffer::size).sum())
+    }
+
+    pub fn track_textures<'a>(&self, textures: impl IntoIterator<Item = &'a wgpu::Texture>) -> Allocation {
+        self.track(textures.into_iter().map(texture_size).sum())
+    }
+}
+
+// Every mip level of every layer, rounded up to whole blocks for compressed formats
+fn texture_size(texture: &wgpu::Texture) -> u64 {
+    let format = texture.format();
+    let block_size = format.block_copy_size(None).unwrap_or(4) as u64;
+    let (block_width, block_height) = format.block_dimensions();
+    let size = texture.size();
+    let layers = match texture.dimension() {
+        wgpu::TextureDimension::D3 => 1,
+        _ => size.depth_or_array_layers as u64,
+    };
+
+    (0..texture.mip_level_count())
+        .map(|mip_level| {
+            let extent = size.mip_level_size(mip_level, texture.dimension());
+            let depth = match texture.dimension() {
+                wgpu::TextureDimension::D3 => extent.depth_or_array_layers as u64,
+                _ => 1,
+            };
+
+            extent.width.div_ceil(block_width) as u64 * extent.height.div_ceil(block_height) as u64 * depth * block_size
+        })
+        .sum::<u64>()
+        * layers
+        * texture.sample_count() as u64
+}
@@ -1,6 +1,9 @@
 use std::{
+    borrow::Cow,
+    collections::HashMap,
     io::{BufReader, Cursor},
     ops::Range,
+    sync::Arc,
 };
 
 use bytemuck::{Pod, Zeroable};
@@ -10,10 +13,18 @@ use image::EncodableLayout;
 use wgpu::util::DeviceExt;
 
 use crate::renderer::{
-    asset::ResourcePath,
+    animation::{
+        AnimationClip, AnimationHeader, AnimationSections, ChannelHeader, ChannelPath, Interpolation, JointHeader,
+        MAX_JOINTS, NO_PARENT, Skeleton, SkeletonNodeHeader, SkinHeader, SkinVertex,
+    },
+    asset::{ImportOptions, ImportReport, ImportWarning, ResourcePath},
+    atlas::{ShelfPacker, blit_tile},
     binary::BlobBuilder,
+    bvh::Aabb,
     context::RenderContext,
-    material::{Material, MaterialUniform, MaterialView, RawMaterial, TextureSlot},
+    material::{Material, MaterialUniform, MaterialView, RawMaterial, TextureSlot, obj_displacement_map},
+    memory::Allocation,
+    quantize::VertexPrecision,
     texture::{Sampler, Texture, TextureFormat, TextureView},
     vertex::Vertex,
 };
@@ -29,7 +40,7 @@ where
 {
     fn draw_primitive_instanced(&mut self, primitive: &'b Primitive, material: &'b Material, instances: Range<u32>) {
         self.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
-        self.set_index_buffer(primitive.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        self.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
 
         primitive
             .uv_buffers
@@ -41,10 +52,12 @@ where
         self.draw_indexed(0..primitive.num_elements, 0, instances);
     }
 
+    // Without the scene's fallback material to turn to, primitives with a missing material are skipped
     fn draw_mesh_instanced(&mut self, mesh: &'b Mesh, materials: &'b [Material], instances: Range<u32>) {
         for primitive in &mesh.primitives {
-            let material = &materials[primitive.material_index];
-            self.draw_primitive_instanced(primitive, material, instances.clone());
+            if let Some(material) = materials.get(primitive.material_index) {
+                self.draw_primitive_instanced(primitive, material, instances.clone());
+            }
         }
     }
 }
@@ -57,14 +70,31 @@ fn index_to_position(positions: &[glam::Vec3], indices: &[u32]) -> [glam::Vec3;
     [v0, v1, v2]
 }
 
+// 16-bit indices halve the index buffer of primitives small enough to address with them
+fn index_stride(options: &ImportOptions, vertex_count: usize) -> usize {
+    if options.compact_indices && vertex_count <= u16::MAX as usize {
+        std::mem::size_of::<u16>()
+    } else {
+        std::mem::size_of::<u32>()
+    }
+}
+
+fn fits_u32_indices(vertex_count: usize, index_count: usize) -> bool {
+    vertex_count <= u32::MAX as usize && index_count <= u32::MAX as usize
+}
+
+// One per vertex, the unnormalized cross product weighs each face by its area
 fn calculate_normals(positions: &[glam::Vec3], indices: &[u32]) -> Vec<glam::Vec3> {
-    indices
-        .chunks_exact(3)
-        .map(|index| {
-            let [v0, v1, v2] = index_to_position(positions, index);
-            (v1 - v0).cross(v2 - v0).normalize_or_zero()
-        })
-        .collect()
+    let mut normals = vec![glam::Vec3::ZERO; positions.len()];
+    for index in indices.chunks_exact(3) {
+        let [v0, v1, v2] = index_to_position(positions, index);
+        let normal = (v1 - v0).cross(v2 - v0);
+        for &vertex in index {
+            normals[vertex as usize] += normal;
+        }
+    }
+
+    normals.into_iter().map(glam::Vec3::normalize_or_zero).collect()
 }
 
 fn calculate_tangents(
@@ -118,6 +148,151 @@ fn calculate_tangents(
         .collect()
 }
 
+fn subdivide(
+    vertices: &mut Vec<MeshVertex>,
+    uv_sets: &mut [Vec<TextureCoordinate>],
+    custom_sets: &mut [Vec<[f32; 4]>],
+    indices: &[u32],
+) -> Vec<u32> {
+    let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut subdivided = Vec::with_capacity(indices.len() * 4);
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+        let [ab, bc, ca] = [(a, b), (b, c), (c, a)].map(|(start, end)| {
+            *midpoints.entry((start.min(end), start.max(end))).or_insert_with(|| {
+                let vertex = vertices[start as usize].midpoint(&vertices[end as usize]);
+                vertices.push(vertex);
+
+                for uv_set in uv_sets.iter_mut() {
+                    if let (Some(uv0), Some(uv1)) = (uv_set.get(start as usize), uv_set.get(end as usize)) {
+                        let uv = uv0.to_vec().lerp(uv1.to_vec(), 0.5);
+                        uv_set.push(TextureCoordinate::new(uv.to_array()));
+                    }
+                }
+
+                for custom_set in custom_sets.iter_mut() {
+                    let value = glam::Vec4::from_array(custom_set[start as usize])
+                        .lerp(glam::Vec4::from_array(custom_set[end as usize]), 0.5);
+                    custom_set.push(value.to_array());
+                }
+
+                vertices.len() as u32 - 1
+            })
+        });
+
+        subdivided.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+    }
+
+    subdivided
+}
+
+// Vertex attributes are widened to four floats, normalized integers map to 0..1 or -1..1 as glTF defines them.
+// Reads interleaved views by their stride, accessors without a view as zeros and applies sparse substitutions on
+// top. Matrices aren't read, nor are views too short or too tightly packed for their elements, which would otherwise
+// come out garbled
+fn read_accessor(accessor: &gltf::Accessor, buffers: &[gltf::buffer::Data]) -> Option<Vec<[f32; 4]>> {
+    use gltf::accessor::{DataType, Dimensions, sparse::IndexType};
+
+    let components = match accessor.dimensions() {
+        Dimensions::Scalar => 1,
+        Dimensions::Vec2 => 2,
+        Dimensions::Vec3 => 3,
+        Dimensions::Vec4 => 4,
+        _ => return None,
+    };
+    let data_type = accessor.data_type();
+    let component_size = data_type.size();
+    let element_size = component_size * components;
+    let is_normalized = accessor.normalized();
+
+    let read_element = |bytes: &[u8]| {
+        let mut value = [0.0; 4];
+        for (component, value) in value.iter_mut().take(components).enumerate() {
+            let bytes = &bytes[component * component_size..(component + 1) * component_size];
+            *value = match data_type {
+                DataType::I8 if is_normalized => (bytes[0] as i8 as f32 / 127.0).max(-1.0),
+                DataType::U8 if is_normalized => bytes[0] as f32 / 255.0,
+                DataType::I16 if is_normalized => (i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32767.0).max(-1.0),
+                DataType::U16 if is_normalized => u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.0,
+                DataType::I8 => bytes[0] as i8 as f32,
+                DataType::U8 => bytes[0] as f32,
+                DataType::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                DataType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                DataType::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+                DataType::F32 => f32::from_le_bytes(bytes.try_into().unwrap()),
+            };
+        }
+        value
+    };
+
+    // Bytes of `count` elements `stride` apart, starting `offset` bytes into the view
+    let view_elements = |view: gltf::buffer::View, offset: usize, count: usize, size: usize| {
+        let stride = view.stride().unwrap_or(size);
+        if stride < size {
+            return None;
+        }
+
+        let buffer = buffers.get(view.buffer().index())?;
+        let view_bytes = buffer.get(view.offset()..view.offset() + view.length())?;
+        (0..count)
+            .map(|index| view_bytes.get(offset + index * stride..offset + index * stride + size))
+            .collect::<Option<Vec<_>>>()
+    };
+
+    let mut values = match accessor.view() {
+        Some(view) => view_elements(view, accessor.offset(), accessor.count(), element_size)?
+            .into_iter()
+            .map(read_element)
+            .collect(),
+        None => vec![[0.0; 4]; accessor.count()],
+    };
+
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse.indices();
+        let index_size = indices.index_type().size();
+        let index_bytes = view_elements(indices.view(), indices.offset(), sparse.count(), index_size)?;
+        let value_bytes = view_elements(
+            sparse.values().view(),
+            sparse.values().offset(),
+            sparse.count(),
+            element_size,
+        )?;
+
+        for (index, bytes) in index_bytes.into_iter().zip(value_bytes) {
+            let index = match indices.index_type() {
+                IndexType::U8 => index[0] as usize,
+                IndexType::U16 => u16::from_le_bytes([index[0], index[1]]) as usize,
+                IndexType::U32 => u32::from_le_bytes(index.try_into().unwrap()) as usize,
+            };
+            *values.get_mut(index)? = read_element(bytes);
+        }
+    }
+
+    Some(values)
+}
+
+// Nodes at the top of the scene, then skinned meshes anywhere below them. A skinned mesh is placed by its joints, not
+// by the nodes above it, so it comes along without flattening the hierarchy
+fn imported_nodes<'a>(scene: &gltf::Scene<'a>) -> Vec<gltf::Node<'a>> {
+    let mut nodes = scene.nodes().collect::<Vec<_>>();
+    let mut visited = nodes
+        .iter()
+        .map(gltf::Node::index)
+        .collect::<std::collections::HashSet<_>>();
+    let mut stack = scene.nodes().flat_map(|node| node.children()).collect::<Vec<_>>();
+    while let Some(node) = stack.pop() {
+        if !visited.insert(node.index()) {
+            continue;
+        }
+        if node.mesh().is_some() && node.skin().is_some() {
+            nodes.push(node.clone());
+        }
+        stack.extend(node.children());
+    }
+    nodes
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct MeshVertex {
@@ -134,6 +309,16 @@ impl MeshVertex {
             tangent: tangent.to_array(),
         }
     }
+
+    pub fn midpoint(&self, other: &Self) -> Self {
+        let position = glam::Vec3::from_array(self.position).lerp(glam::Vec3::from_array(other.position), 0.5);
+        let normal = (glam::Vec3::from_array(self.normal) + glam::Vec3::from_array(other.normal)).normalize_or_zero();
+        let tangent = (glam::Vec4::from_array(self.tangent).xyz() + glam::Vec4::from_array(other.tangent).xyz())
+            .normalize_or_zero()
+            .extend(self.tangent[3]);
+
+        Self::new(position, normal, tangent)
+    }
 }
 
 impl Vertex for MeshVertex {
@@ -204,9 +389,15 @@ impl TextureCoordinate {
 pub struct PrimitiveView<'a> {
     pub vertices: &'a [MeshVertex],
     pub indices: &'a [u32],
+    pub index_format: wgpu::IndexFormat,
+    pub vertex_precision: VertexPrecision,
     pub material_index: usize,
+    pub variants: &'a [VariantMapping],
+    pub skin_vertices: Option<&'a [SkinVertex]>,
     uv_headers: &'a [TexCoordHeader],
     raw_uv_sets: &'a [u8],
+    custom_headers: &'a [CustomAttributeHeader],
+    raw_custom_attributes: &'a [u8],
 }
 
 impl<'a> PrimitiveView<'a> {
@@ -225,6 +416,14 @@ impl<'a> PrimitiveView<'a> {
         })
     }
 
+    // In the order of their names, one value per vertex each
+    pub fn iter_custom_attributes(&self) -> impl Iterator<Item = &'a [[f32; 4]]> {
+        self.custom_headers.iter().map(|header| {
+            let end = header.offset + header.count * std::mem::size_of::<[f32; 4]>();
+            bytemuck::cast_slice(&self.raw_custom_attributes[header.offset..end])
+        })
+    }
+
     pub fn to_owned(self, context: &RenderContext, label: Option<&str>) -> Primitive {
         Primitive::from_view(self, context, label.as_deref())
     }
@@ -233,6 +432,8 @@ impl<'a> PrimitiveView<'a> {
 pub struct NodeView<'a> {
     pub transform: glam::Mat4,
     pub primitives: Vec<PrimitiveView<'a>>,
+    pub metadata: NodeMetadata,
+    pub skeleton: Option<Skeleton>,
 }
 
 impl NodeView<'_> {
@@ -241,10 +442,42 @@ impl NodeView<'_> {
     }
 }
 
+// Names and extras of an imported node, for the app to show. Values of the extras are kept as JSON text, except
+// strings which lose their quotes
+#[derive(Clone, Debug, Default)]
+pub struct NodeMetadata {
+    pub name: Option<String>,
+    pub extras: Vec<(String, String)>,
+    pub materials: Vec<String>,
+    // Material variants of the file, for nodes whose materials change with them
+    pub variants: Vec<String>,
+    // Seconds before the clip moving the node's skin loops, None for a node that doesn't move
+    pub animation_duration: Option<f32>,
+}
+
+impl NodeMetadata {
+    fn parse_extras(json: &str) -> Vec<(String, String)> {
+        let to_text = |value: serde_json::Value| match value {
+            serde_json::Value::String(text) => text,
+            value => value.to_string(),
+        };
+
+        match serde_json::from_str(json) {
+            Ok(serde_json::Value::Object(object)) => {
+                object.into_iter().map(|(key, value)| (key, to_text(value))).collect()
+            }
+            Ok(serde_json::Value::Null) | Err(_) => Vec::new(),
+            Ok(value) => vec![("extras".to_string(), to_text(value))],
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Node {
     pub transform: glam::Mat4,
     pub mesh: Mesh,
+    pub metadata: NodeMetadata,
+    pub skeleton: Option<Skeleton>,
 }
 
 impl Node {
@@ -258,6 +491,8 @@ impl Node {
         Self {
             transform: view.transform,
             mesh: Mesh { primitives },
+            metadata: view.metadata,
+            skeleton: view.skeleton,
         }
     }
 }
@@ -296,12 +531,30 @@ impl Mesh {
             })
             .collect::<Vec<_>>();
 
+        let allocation = context
+            .memory
+            .track_buffers([&vertex_buffer, &index_buffer].into_iter().chain(&uv_buffers));
+        let num_elements = indices.len() as u32;
+        let geometry = Arc::new(PrimitiveGeometry {
+            vertices,
+            indices,
+            custom_attributes: Vec::new(),
+            skin_vertices: Vec::new(),
+        });
         let primitive = Primitive {
             vertex_buffer,
             index_buffer,
+            index_format: wgpu::IndexFormat::Uint32,
+            vertex_precision: VertexPrecision::Full,
             uv_buffers,
-            num_elements: indices.len() as u32,
+            uv_set_count: uv_sets.len(),
+            num_elements,
             material_index: 0,
+            variants: Vec::new(),
+            is_skinned: false,
+            bounds: geometry.bounds(),
+            geometry,
+            _allocation: allocation,
         };
 
         Self {
@@ -331,8 +584,34 @@ pub struct SceneHeader {
     pub indices_count: usize,
     pub uv_sets_offset: usize,
     pub uv_sets_count: usize,
+    pub custom_header_offset: usize,
+    pub custom_header_count: usize,
+    pub custom_attributes_offset: usize,
+    pub custom_attributes_count: usize,
+    pub material_names_offset: usize,
+    pub material_names_count: usize,
+    pub strings_offset: usize,
+    pub strings_size: usize,
     pub texture_offset: usize,
     pub texture_size: usize,
+    pub skeleton_nodes_offset: usize,
+    pub skeleton_nodes_count: usize,
+    pub joints_offset: usize,
+    pub joints_count: usize,
+    pub skins_offset: usize,
+    pub skins_count: usize,
+    pub animations_offset: usize,
+    pub animations_count: usize,
+    pub channels_offset: usize,
+    pub channels_count: usize,
+    pub keyframes_offset: usize,
+    pub keyframes_count: usize,
+    pub skin_vertices_offset: usize,
+    pub skin_vertices_count: usize,
+    pub variant_names_offset: usize,
+    pub variant_names_count: usize,
+    pub variant_mappings_offset: usize,
+    pub variant_mappings_count: usize,
 }
 
 #[repr(C)]
@@ -342,9 +621,21 @@ pub struct PrimitiveHeader {
     pub vertex_count: usize,
     pub index_offset: usize,
     pub index_count: usize,
+    // Bytes per index on the GPU, the scene buffer always holds 32-bit indices
+    pub index_stride: usize,
+    // Likewise the vertices are quantized on upload
+    pub vertex_precision: usize,
     pub uv_header_offset: usize,
     pub uv_set_count: usize,
+    pub custom_header_offset: usize,
+    pub custom_attribute_count: usize,
     pub material_index: usize,
+    // Zero for primitives without a skin
+    pub skin_vertex_offset: usize,
+    pub skin_vertex_count: usize,
+    // Materials the primitive switches to under the file's material variants
+    pub variant_offset: usize,
+    pub variant_count: usize,
 }
 
 #[repr(C)]
@@ -355,6 +646,45 @@ pub struct NodeHeader {
     pub scale: [f32; 3],
     pub primitive_header_offset: usize,
     pub primitive_count: usize,
+    pub name: StringHeader,
+    // A JSON object
+    pub extras: StringHeader,
+    // Index into the skins, NO_SKIN for a rigid node
+    pub skin: usize,
+}
+
+impl NodeHeader {
+    fn transform(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(
+            glam::Vec3::from_slice(&self.scale),
+            glam::Quat::from_slice(&self.rotation),
+            glam::Vec3::from_slice(&self.position),
+        )
+    }
+}
+
+// Text in the string table section of the scene buffer, empty for names a file leaves out
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct StringHeader {
+    offset: usize,
+    size: usize,
+}
+
+impl StringHeader {
+    pub fn push(strings: &mut String, text: Option<&str>) -> Self {
+        let offset = strings.len();
+        strings.push_str(text.unwrap_or_default());
+        Self {
+            offset,
+            size: strings.len() - offset,
+        }
+    }
+
+    fn read(self, strings: &[u8]) -> Option<&str> {
+        let bytes = strings.get(self.offset..self.offset + self.size)?;
+        std::str::from_utf8(bytes).ok().filter(|text| !text.is_empty())
+    }
 }
 
 #[repr(C)]
@@ -364,6 +694,14 @@ pub struct TexCoordHeader {
     count: usize,
 }
 
+// One custom glTF attribute of a primitive, widened to four floats per vertex
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct CustomAttributeHeader {
+    offset: usize,
+    count: usize,
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct TextureHeader {
@@ -374,47 +712,132 @@ pub struct TextureHeader {
     pub height: u32,
 }
 
+// The material a primitive is drawn with while the variant is active, from KHR_materials_variants
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct VariantMapping {
+    pub variant: usize,
+    pub material_index: usize,
+}
+
+#[derive(Default)]
+pub struct VariantSections {
+    pub names: Vec<StringHeader>,
+    pub mappings: Vec<VariantMapping>,
+}
+
+// Host copy of the triangles for ray queries against the scene
+#[derive(Debug, Default)]
+pub struct PrimitiveGeometry {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    // Custom glTF attributes, handed to the shader sketch
+    pub custom_attributes: Vec<Vec<[f32; 4]>>,
+    // Empty for a rigid primitive, picking poses the triangles with them
+    pub skin_vertices: Vec<SkinVertex>,
+}
+
+impl PrimitiveGeometry {
+    pub fn bounds(&self) -> Aabb {
+        self.vertices.iter().fold(Aabb::EMPTY, |mut bounds, vertex| {
+            bounds.grow(glam::Vec3::from_array(vertex.position));
+            bounds
+        })
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [&MeshVertex; 3]> {
+        self.indices.chunks_exact(3).map(|triangle| {
+            [
+                &self.vertices[triangle[0] as usize],
+                &self.vertices[triangle[1] as usize],
+                &self.vertices[triangle[2] as usize],
+            ]
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Primitive {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
+    pub vertex_precision: VertexPrecision,
     pub uv_buffers: Vec<wgpu::Buffer>,
+    // Sets beyond this are bound to a single dummy coordinate
+    pub uv_set_count: usize,
     pub num_elements: u32,
     pub material_index: usize,
+    pub variants: Vec<VariantMapping>,
+    // Drawn through the skinning vertex entry point, other passes draw the bind pose
+    pub is_skinned: bool,
+    pub geometry: Arc<PrimitiveGeometry>,
+    // Object space, for frustum culling
+    pub bounds: Aabb,
+    _allocation: Allocation,
 }
 
 impl Primitive {
     pub fn from_view(view: PrimitiveView, context: &RenderContext, label: Option<&str>) -> Self {
         let vertex_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: label.as_deref(),
-            contents: bytemuck::cast_slice(view.vertices),
+            contents: &view.vertex_precision.vertex_bytes(view.vertices),
             usage: wgpu::BufferUsages::VERTEX,
         });
 
+        let compact_indices: Vec<u16> = match view.index_format {
+            wgpu::IndexFormat::Uint16 => view.indices.iter().map(|&index| index as u16).collect(),
+            wgpu::IndexFormat::Uint32 => Vec::new(),
+        };
         let index_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: label.as_deref(),
-            contents: bytemuck::cast_slice(view.indices),
+            contents: match view.index_format {
+                wgpu::IndexFormat::Uint16 => bytemuck::cast_slice(&compact_indices),
+                wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(view.indices),
+            },
             usage: wgpu::BufferUsages::INDEX,
         });
 
         let dummy_uv_set = [TextureCoordinate::default()];
         let uv_buffers = (0..6)
             .map(|uv_index| {
-                let uv_set = view.get_uv_set(uv_index).unwrap_or(&dummy_uv_set);
+                let contents = match view.skin_vertices {
+                    // The skinned layout reads joints and weights in place of the last set
+                    Some(skin_vertices) if uv_index == 5 => bytemuck::cast_slice(skin_vertices).to_vec(),
+                    _ => view
+                        .vertex_precision
+                        .uv_bytes(view.get_uv_set(uv_index).unwrap_or(&dummy_uv_set)),
+                };
                 context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                     label: label.as_deref(),
-                    contents: bytemuck::cast_slice(&uv_set),
+                    contents: &contents,
                     usage: wgpu::BufferUsages::VERTEX,
                 })
             })
             .collect::<Vec<_>>();
 
+        let allocation = context
+            .memory
+            .track_buffers([&vertex_buffer, &index_buffer].into_iter().chain(&uv_buffers));
+        let geometry = Arc::new(PrimitiveGeometry {
+            vertices: view.vertices.to_vec(),
+            indices: view.indices.to_vec(),
+            custom_attributes: view.iter_custom_attributes().map(<[_]>::to_vec).collect(),
+            skin_vertices: view.skin_vertices.map(<[_]>::to_vec).unwrap_or_default(),
+        });
         Self {
             vertex_buffer,
             index_buffer,
+            index_format: view.index_format,
+            vertex_precision: view.vertex_precision,
             uv_buffers,
+            uv_set_count: view.uv_headers.len(),
             num_elements: view.indices.len() as u32,
             material_index: view.material_index,
+            variants: view.variants.to_vec(),
+            is_skinned: view.skin_vertices.is_some(),
+            bounds: geometry.bounds(),
+            geometry,
+            _allocation: allocation,
         }
     }
 }
@@ -428,6 +851,7 @@ pub struct Scene {
 
 impl Scene {
     pub fn from_buffer(buffer: SceneBuffer, context: &RenderContext, label: Option<String>) -> Self {
+        crate::profile_scope!("Upload scene");
         let materials = buffer
             .iter_materials()
             .map(|material| Material::new(material, label.as_deref(), context))
@@ -446,6 +870,72 @@ impl Scene {
     }
 }
 
+// The primitive sections of a scene buffer, rebuilt when primitives are batched
+#[derive(Default)]
+struct PrimitiveSections {
+    primitive_headers: Vec<PrimitiveHeader>,
+    uv_headers: Vec<TexCoordHeader>,
+    custom_headers: Vec<CustomAttributeHeader>,
+    vertices: Vec<MeshVertex>,
+    indices: Vec<u32>,
+    uv_sets: Vec<TextureCoordinate>,
+    custom_attributes: Vec<[f32; 4]>,
+    skin_vertices: Vec<SkinVertex>,
+}
+
+impl PrimitiveSections {
+    // Copies the geometry in and points `header`'s offsets and counts at it
+    fn push<'a>(
+        &mut self,
+        header: PrimitiveHeader,
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        uv_sets: impl ExactSizeIterator<Item = &'a [TextureCoordinate]>,
+        custom_attributes: impl ExactSizeIterator<Item = &'a [[f32; 4]]>,
+        skin_vertices: &[SkinVertex],
+    ) {
+        self.primitive_headers.push(PrimitiveHeader {
+            vertex_offset: std::mem::size_of::<MeshVertex>() * self.vertices.len(),
+            vertex_count: vertices.len(),
+            index_offset: std::mem::size_of::<u32>() * self.indices.len(),
+            index_count: indices.len(),
+            uv_header_offset: std::mem::size_of::<TexCoordHeader>() * self.uv_headers.len(),
+            uv_set_count: uv_sets.len(),
+            custom_header_offset: std::mem::size_of::<CustomAttributeHeader>() * self.custom_headers.len(),
+            custom_attribute_count: custom_attributes.len(),
+            skin_vertex_offset: std::mem::size_of::<SkinVertex>() * self.skin_vertices.len(),
+            skin_vertex_count: skin_vertices.len(),
+            ..header
+        });
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend_from_slice(indices);
+        self.skin_vertices.extend_from_slice(skin_vertices);
+        for uv_set in uv_sets {
+            self.uv_headers.push(TexCoordHeader {
+                offset: std::mem::size_of::<TextureCoordinate>() * self.uv_sets.len(),
+                count: uv_set.len(),
+            });
+            self.uv_sets.extend_from_slice(uv_set);
+        }
+        for custom_set in custom_attributes {
+            self.custom_headers.push(CustomAttributeHeader {
+                offset: std::mem::size_of::<[f32; 4]>() * self.custom_attributes.len(),
+                count: custom_set.len(),
+            });
+            self.custom_attributes.extend_from_slice(custom_set);
+        }
+    }
+}
+
+// Primitives of one material merged in world space
+struct Batch {
+    header: PrimitiveHeader,
+    vertices: Vec<MeshVertex>,
+    indices: Vec<u32>,
+    uv_sets: Vec<Vec<TextureCoordinate>>,
+    custom_attributes: Vec<Vec<[f32; 4]>>,
+}
+
 pub struct SceneBuffer(Vec<u8>);
 impl SceneBuffer {
     pub fn new(
@@ -458,7 +948,13 @@ impl SceneBuffer {
         vertices: Vec<MeshVertex>,
         indices: Vec<u32>,
         uv_sets: Vec<TextureCoordinate>,
+        custom_headers: Vec<CustomAttributeHeader>,
+        custom_attributes: Vec<[f32; 4]>,
+        material_names: Vec<StringHeader>,
+        strings: String,
         textures: Vec<u8>,
+        animation: AnimationSections,
+        variants: VariantSections,
     ) -> Self {
         let mut builder = BlobBuilder::new();
         let header_offset = builder.reserve::<SceneHeader>();
@@ -472,7 +968,20 @@ impl SceneBuffer {
         let vertices_offset = builder.push_slice(&vertices);
         let indices_offset = builder.push_slice(&indices);
         let uv_sets_offset = builder.push_slice(&uv_sets);
+        let custom_header_offset = builder.push_slice(&custom_headers);
+        let custom_attributes_offset = builder.push_slice(&custom_attributes);
+        let material_names_offset = builder.push_slice(&material_names);
+        let strings_offset = builder.push_bytes(strings.as_bytes());
         let texture_offset = builder.push_bytes(&textures);
+        let skeleton_nodes_offset = builder.push_slice(&animation.skeleton_nodes);
+        let joints_offset = builder.push_slice(&animation.joints);
+        let skins_offset = builder.push_slice(&animation.skins);
+        let animations_offset = builder.push_slice(&animation.animations);
+        let channels_offset = builder.push_slice(&animation.channels);
+        let keyframes_offset = builder.push_slice(&animation.keyframes);
+        let skin_vertices_offset = builder.push_slice(&animation.skin_vertices);
+        let variant_names_offset = builder.push_slice(&variants.names);
+        let variant_mappings_offset = builder.push_slice(&variants.mappings);
 
         let header = SceneHeader {
             node_header_offset,
@@ -484,6 +993,10 @@ impl SceneBuffer {
             vertices_offset,
             indices_offset,
             uv_sets_offset,
+            custom_header_offset,
+            custom_attributes_offset,
+            material_names_offset,
+            strings_offset,
             texture_offset,
             node_header_count: node_headers.len(),
             primitive_header_count: primitive_headers.len(),
@@ -494,7 +1007,29 @@ impl SceneBuffer {
             vertices_count: vertices.len(),
             indices_count: indices.len(),
             uv_sets_count: uv_sets.len(),
+            custom_header_count: custom_headers.len(),
+            custom_attributes_count: custom_attributes.len(),
+            material_names_count: material_names.len(),
+            strings_size: strings.len(),
             texture_size: textures.len(),
+            skeleton_nodes_offset,
+            skeleton_nodes_count: animation.skeleton_nodes.len(),
+            joints_offset,
+            joints_count: animation.joints.len(),
+            skins_offset,
+            skins_count: animation.skins.len(),
+            animations_offset,
+            animations_count: animation.animations.len(),
+            channels_offset,
+            channels_count: animation.channels.len(),
+            keyframes_offset,
+            keyframes_count: animation.keyframes.len(),
+            skin_vertices_offset,
+            skin_vertices_count: animation.skin_vertices.len(),
+            variant_names_offset,
+            variant_names_count: variants.names.len(),
+            variant_mappings_offset,
+            variant_mappings_count: variants.mappings.len(),
         };
 
         builder.write_at(header_offset, &header);
@@ -505,6 +1040,809 @@ impl SceneBuffer {
         Self(bytes.to_vec())
     }
 
+    // One scene with the nodes of all of them, in order. Offsets and the indices of materials, textures and samplers
+    // are moved past those of the scenes before
+    pub fn merge(scenes: &[SceneBuffer]) -> Self {
+        let mut node_headers = Vec::new();
+        let mut primitive_headers = Vec::new();
+        let mut uv_headers = Vec::new();
+        let mut texture_headers = Vec::new();
+        let mut materials = Vec::new();
+        let mut samplers = Vec::new();
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut uv_sets = Vec::new();
+        let mut custom_headers = Vec::new();
+        let mut custom_attributes = Vec::new();
+        let mut material_names = Vec::new();
+        let mut strings = String::new();
+        let mut textures = Vec::new();
+        let mut animation = AnimationSections::default();
+        let mut variants = VariantSections::default();
+
+        for scene in scenes {
+            let header: &SceneHeader = bytemuck::from_bytes(&scene.0[..std::mem::size_of::<SceneHeader>()]);
+            let primitive_header_base = std::mem::size_of::<PrimitiveHeader>() * primitive_headers.len();
+            let vertex_base = std::mem::size_of::<MeshVertex>() * vertices.len();
+            let index_base = std::mem::size_of::<u32>() * indices.len();
+            let uv_header_base = std::mem::size_of::<TexCoordHeader>() * uv_headers.len();
+            let uv_set_base = std::mem::size_of::<TextureCoordinate>() * uv_sets.len();
+            let custom_header_base = std::mem::size_of::<CustomAttributeHeader>() * custom_headers.len();
+            let custom_attribute_base = std::mem::size_of::<[f32; 4]>() * custom_attributes.len();
+            let material_base = materials.len();
+            let texture_base = texture_headers.len() as u32;
+            let sampler_base = samplers.len() as u32;
+            let texture_byte_base = textures.len();
+            let string_base = strings.len();
+            let skeleton_node_base = animation.skeleton_nodes.len() as u32;
+            let joint_base = std::mem::size_of::<JointHeader>() * animation.joints.len();
+            let skin_base = animation.skins.len();
+            let channel_base = std::mem::size_of::<ChannelHeader>() * animation.channels.len();
+            let keyframe_base = std::mem::size_of::<f32>() * animation.keyframes.len();
+            let skin_vertex_base = std::mem::size_of::<SkinVertex>() * animation.skin_vertices.len();
+            let variant_base = variants.names.len();
+            let variant_mapping_base = std::mem::size_of::<VariantMapping>() * variants.mappings.len();
+            let offset_string = |string: &StringHeader| StringHeader {
+                offset: string.offset + string_base,
+                size: string.size,
+            };
+
+            node_headers.extend(
+                scene
+                    .slice::<NodeHeader>(header.node_header_offset, header.node_header_count)
+                    .iter()
+                    .map(|node| NodeHeader {
+                        primitive_header_offset: node.primitive_header_offset + primitive_header_base,
+                        name: offset_string(&node.name),
+                        extras: offset_string(&node.extras),
+                        skin: match node.skin {
+                            Self::NO_SKIN => Self::NO_SKIN,
+                            index => index + skin_base,
+                        },
+                        ..*node
+                    }),
+            );
+            primitive_headers.extend(
+                scene
+                    .slice::<PrimitiveHeader>(header.primitive_header_offset, header.primitive_header_count)
+                    .iter()
+                    .map(|primitive| PrimitiveHeader {
+                        vertex_offset: primitive.vertex_offset + vertex_base,
+                        index_offset: primitive.index_offset + index_base,
+                        uv_header_offset: primitive.uv_header_offset + uv_header_base,
+                        custom_header_offset: primitive.custom_header_offset + custom_header_base,
+                        material_index: match primitive.material_index {
+                            Self::MISSING_MATERIAL => Self::MISSING_MATERIAL,
+                            index => index + material_base,
+                        },
+                        skin_vertex_offset: primitive.skin_vertex_offset + skin_vertex_base,
+                        variant_offset: primitive.variant_offset + variant_mapping_base,
+                        ..*primitive
+                    }),
+            );
+            uv_headers.extend(
+                scene
+                    .slice::<TexCoordHeader>(header.uv_header_offset, header.uv_header_count)
+                    .iter()
+                    .map(|uv_header| TexCoordHeader {
+                        offset: uv_header.offset + uv_set_base,
+                        count: uv_header.count,
+                    }),
+            );
+            custom_headers.extend(
+                scene
+                    .slice::<CustomAttributeHeader>(header.custom_header_offset, header.custom_header_count)
+                    .iter()
+                    .map(|custom_header| CustomAttributeHeader {
+                        offset: custom_header.offset + custom_attribute_base,
+                        count: custom_header.count,
+                    }),
+            );
+            texture_headers.extend(
+                scene
+                    .slice::<TextureHeader>(header.texture_header_offset, header.texture_header_count)
+                    .iter()
+                    .map(|texture_header| TextureHeader {
+                        offset: texture_header.offset + texture_byte_base,
+                        ..*texture_header
+                    }),
+            );
+
+            // Slots without a sampler of their own fall back to the default, they must not land on another scene's
+            let scene_samplers = scene.slice::<Sampler>(header.samplers_offset, header.samplers_count);
+            let offset_slot = |slot: Option<TextureSlot>| {
+                slot.map(|slot| TextureSlot {
+                    texture_index: slot.texture_index + texture_base,
+                    sampler_index: match slot.sampler_index as usize {
+                        index if index < scene_samplers.len() => slot.sampler_index + sampler_base,
+                        _ => u32::MAX,
+                    },
+                    ..slot
+                })
+            };
+            materials.extend(
+                scene
+                    .slice::<RawMaterial>(header.materials_offset, header.materials_count)
+                    .iter()
+                    .map(|material| RawMaterial {
+                        base_color: offset_slot(material.base_color),
+                        metallic_roughness: offset_slot(material.metallic_roughness),
+                        normal: offset_slot(material.normal),
+                        occlusion: offset_slot(material.occlusion),
+                        emissive: offset_slot(material.emissive),
+                        height: offset_slot(material.height),
+                        clearcoat_normal: offset_slot(material.clearcoat_normal),
+                        ..*material
+                    }),
+            );
+            material_names.extend(
+                scene
+                    .slice::<StringHeader>(header.material_names_offset, header.material_names_count)
+                    .iter()
+                    .map(offset_string),
+            );
+
+            animation.skeleton_nodes.extend(
+                scene
+                    .slice::<SkeletonNodeHeader>(header.skeleton_nodes_offset, header.skeleton_nodes_count)
+                    .iter()
+                    .map(|node| SkeletonNodeHeader {
+                        parent: match node.parent {
+                            NO_PARENT => NO_PARENT,
+                            parent => parent + skeleton_node_base,
+                        },
+                        ..*node
+                    }),
+            );
+            animation.joints.extend(
+                scene
+                    .slice::<JointHeader>(header.joints_offset, header.joints_count)
+                    .iter()
+                    .map(|joint| JointHeader {
+                        node: joint.node + skeleton_node_base,
+                        ..*joint
+                    }),
+            );
+            animation.skins.extend(
+                scene
+                    .slice::<SkinHeader>(header.skins_offset, header.skins_count)
+                    .iter()
+                    .map(|skin| SkinHeader {
+                        joint_offset: skin.joint_offset + joint_base,
+                        ..*skin
+                    }),
+            );
+            animation.animations.extend(
+                scene
+                    .slice::<AnimationHeader>(header.animations_offset, header.animations_count)
+                    .iter()
+                    .map(|clip| AnimationHeader {
+                        name: offset_string(&clip.name),
+                        channel_offset: clip.channel_offset + channel_base,
+                        ..*clip
+                    }),
+            );
+            animation.channels.extend(
+                scene
+                    .slice::<ChannelHeader>(header.channels_offset, header.channels_count)
+                    .iter()
+                    .map(|channel| ChannelHeader {
+                        node: channel.node + skeleton_node_base,
+                        time_offset: channel.time_offset + keyframe_base,
+                        value_offset: channel.value_offset + keyframe_base,
+                        ..*channel
+                    }),
+            );
+            animation
+                .keyframes
+                .extend_from_slice(scene.slice::<f32>(header.keyframes_offset, header.keyframes_count));
+            animation
+                .skin_vertices
+                .extend_from_slice(scene.slice::<SkinVertex>(header.skin_vertices_offset, header.skin_vertices_count));
+            variants.names.extend(
+                scene
+                    .slice::<StringHeader>(header.variant_names_offset, header.variant_names_count)
+                    .iter()
+                    .map(offset_string),
+            );
+            variants.mappings.extend(
+                scene
+                    .slice::<VariantMapping>(header.variant_mappings_offset, header.variant_mappings_count)
+                    .iter()
+                    .map(|mapping| VariantMapping {
+                        variant: mapping.variant + variant_base,
+                        material_index: mapping.material_index + material_base,
+                    }),
+            );
+
+            samplers.extend_from_slice(scene_samplers);
+            vertices.extend_from_slice(scene.slice::<MeshVertex>(header.vertices_offset, header.vertices_count));
+            indices.extend_from_slice(scene.slice::<u32>(header.indices_offset, header.indices_count));
+            uv_sets.extend_from_slice(scene.slice::<TextureCoordinate>(header.uv_sets_offset, header.uv_sets_count));
+            custom_attributes.extend_from_slice(
+                scene.slice::<[f32; 4]>(header.custom_attributes_offset, header.custom_attributes_count),
+            );
+            strings.push_str(&String::from_utf8_lossy(
+                scene.slice::<u8>(header.strings_offset, header.strings_size),
+            ));
+            textures.extend_from_slice(scene.slice::<u8>(header.texture_offset, header.texture_size));
+        }
+
+        Self::new(
+            node_headers,
+            primitive_headers,
+            uv_headers,
+            texture_headers,
+            materials,
+            samplers,
+            vertices,
+            indices,
+            uv_sets,
+            custom_headers,
+            custom_attributes,
+            material_names,
+            strings,
+            textures,
+            animation,
+            variants,
+        )
+    }
+
+    // Merges the small rigid primitives sharing a material and attribute layout into one primitive, with the node
+    // transforms baked into the vertices, so scenes of thousands of tiny parts take a handful of draws. Each batch gets
+    // a node of its own and nodes left without primitives are dropped. Skinned nodes and primitives with material
+    // variants are left as they are
+    pub fn batch(&self, options: &ImportOptions) -> Self {
+        crate::profile_scope!("Batch primitives");
+        let header: &SceneHeader = bytemuck::from_bytes(&self.0[..std::mem::size_of::<SceneHeader>()]);
+        let node_headers = self.slice::<NodeHeader>(header.node_header_offset, header.node_header_count);
+        let raw_primitive_headers =
+            self.slice_raw::<PrimitiveHeader>(header.primitive_header_offset, header.primitive_header_count);
+        let raw_vertices = self.slice_raw::<MeshVertex>(header.vertices_offset, header.vertices_count);
+        let raw_indices = self.slice_raw::<u32>(header.indices_offset, header.indices_count);
+        let raw_uv_headers = self.slice_raw::<TexCoordHeader>(header.uv_header_offset, header.uv_header_count);
+        let raw_uv_sets = self.slice_raw::<TextureCoordinate>(header.uv_sets_offset, header.uv_sets_count);
+        let raw_custom_headers =
+            self.slice_raw::<CustomAttributeHeader>(header.custom_header_offset, header.custom_header_count);
+        let raw_custom_attributes =
+            self.slice_raw::<[f32; 4]>(header.custom_attributes_offset, header.custom_attributes_count);
+        let raw_skin_vertices = self.slice_raw::<SkinVertex>(header.skin_vertices_offset, header.skin_vertices_count);
+        let material_names = self.slice::<StringHeader>(header.material_names_offset, header.material_names_count);
+        let mut strings =
+            String::from_utf8_lossy(self.slice::<u8>(header.strings_offset, header.strings_size)).into_owned();
+
+        let node_primitives = |node: &NodeHeader| -> &[PrimitiveHeader] {
+            Self::slice_as(
+                raw_primitive_headers,
+                node.primitive_header_offset,
+                node.primitive_count,
+            )
+        };
+        let uv_sets = |primitive: &PrimitiveHeader| {
+            Self::slice_as::<TexCoordHeader>(raw_uv_headers, primitive.uv_header_offset, primitive.uv_set_count)
+                .iter()
+                .map(|uv_header| Self::slice_as::<TextureCoordinate>(raw_uv_sets, uv_header.offset, uv_header.count))
+        };
+        let custom_attributes = |primitive: &PrimitiveHeader| {
+            Self::slice_as::<CustomAttributeHeader>(
+                raw_custom_headers,
+                primitive.custom_header_offset,
+                primitive.custom_attribute_count,
+            )
+            .iter()
+            .map(|custom_header| {
+                Self::slice_as::<[f32; 4]>(raw_custom_attributes, custom_header.offset, custom_header.count)
+            })
+        };
+        let batch_key = |node: &NodeHeader, primitive: &PrimitiveHeader| {
+            (node.skin == Self::NO_SKIN
+                && primitive.vertex_count <= Self::BATCHED_PRIMITIVE_VERTICES
+                && primitive.variant_count == 0)
+                .then_some((
+                    primitive.material_index,
+                    primitive.vertex_precision,
+                    primitive.uv_set_count,
+                    primitive.custom_attribute_count,
+                ))
+        };
+
+        // A primitive alone with its key gains nothing from losing its node
+        let mut key_counts = HashMap::new();
+        for node in node_headers {
+            for primitive in node_primitives(node) {
+                if let Some(key) = batch_key(node, primitive) {
+                    *key_counts.entry(key).or_insert(0usize) += 1;
+                }
+            }
+        }
+
+        let mut sections = PrimitiveSections::default();
+        let mut kept_nodes = Vec::new();
+        let mut batches: Vec<Batch> = Vec::new();
+        let mut open_batches: HashMap<_, usize> = HashMap::new();
+        let mut batched_primitives = 0;
+        for node in node_headers {
+            let first_primitive = sections.primitive_headers.len();
+            let transform = node.transform();
+            let normal_transform = glam::Mat3::from_mat4(transform).inverse().transpose();
+            let mirrored = transform.determinant() < 0.0;
+
+            for primitive in node_primitives(node) {
+                let vertices: &[MeshVertex] =
+                    Self::slice_as(raw_vertices, primitive.vertex_offset, primitive.vertex_count);
+                let indices: &[u32] = Self::slice_as(raw_indices, primitive.index_offset, primitive.index_count);
+                let Some(key) = batch_key(node, primitive).filter(|key| key_counts[key] > 1) else {
+                    let skin_vertices = Self::slice_as(
+                        raw_skin_vertices,
+                        primitive.skin_vertex_offset,
+                        primitive.skin_vertex_count,
+                    );
+                    sections.push(
+                        *primitive,
+                        vertices,
+                        indices,
+                        uv_sets(primitive),
+                        custom_attributes(primitive),
+                        skin_vertices,
+                    );
+                    continue;
+                };
+
+                // Batches stay addressable with 16-bit indices
+                let index = match open_batches.get(&key) {
+                    Some(&index) if batches[index].vertices.len() + vertices.len() <= Self::BATCH_VERTICES => index,
+                    _ => {
+                        batches.push(Batch {
+                            header: *primitive,
+                            vertices: Vec::new(),
+                            indices: Vec::new(),
+                            uv_sets: vec![Vec::new(); primitive.uv_set_count],
+                            custom_attributes: vec![Vec::new(); primitive.custom_attribute_count],
+                        });
+                        open_batches.insert(key, batches.len() - 1);
+                        batches.len() - 1
+                    }
+                };
+                let batch = &mut batches[index];
+                let base = batch.vertices.len() as u32;
+                batch.vertices.extend(vertices.iter().map(|vertex| {
+                    let tangent = glam::Vec4::from_array(vertex.tangent);
+                    let handedness = if mirrored { -tangent.w } else { tangent.w };
+                    MeshVertex::new(
+                        transform.transform_point3(glam::Vec3::from_array(vertex.position)),
+                        (normal_transform * glam::Vec3::from_array(vertex.normal)).normalize_or_zero(),
+                        transform
+                            .transform_vector3(tangent.xyz())
+                            .normalize_or_zero()
+                            .extend(handedness),
+                    )
+                }));
+                // A mirroring transform turns the triangles inside out
+                batch
+                    .indices
+                    .extend(indices.chunks_exact(3).flat_map(|triangle| match mirrored {
+                        true => [triangle[0] + base, triangle[2] + base, triangle[1] + base],
+                        false => [triangle[0] + base, triangle[1] + base, triangle[2] + base],
+                    }));
+                for (batch_set, uv_set) in batch.uv_sets.iter_mut().zip(uv_sets(primitive)) {
+                    batch_set.extend_from_slice(uv_set);
+                }
+                for (batch_set, custom_set) in batch.custom_attributes.iter_mut().zip(custom_attributes(primitive)) {
+                    batch_set.extend_from_slice(custom_set);
+                }
+                batched_primitives += 1;
+            }
+
+            let primitive_count = sections.primitive_headers.len() - first_primitive;
+            if primitive_count > 0 || node.primitive_count == 0 {
+                kept_nodes.push(NodeHeader {
+                    primitive_header_offset: std::mem::size_of::<PrimitiveHeader>() * first_primitive,
+                    primitive_count,
+                    ..*node
+                });
+            }
+        }
+
+        for batch in &batches {
+            let material = material_names
+                .get(batch.header.material_index)
+                .and_then(|name| name.read(strings.as_bytes()))
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("Material {}", batch.header.material_index));
+            kept_nodes.push(NodeHeader {
+                position: [0.0; 3],
+                rotation: glam::Quat::IDENTITY.to_array(),
+                scale: [1.0; 3],
+                primitive_header_offset: std::mem::size_of::<PrimitiveHeader>() * sections.primitive_headers.len(),
+                primitive_count: 1,
+                name: StringHeader::push(&mut strings, Some(&format!("Batch of {}", material))),
+                extras: StringHeader::default(),
+                skin: Self::NO_SKIN,
+            });
+            sections.push(
+                PrimitiveHeader {
+                    index_stride: index_stride(options, batch.vertices.len()),
+                    ..batch.header
+                },
+                &batch.vertices,
+                &batch.indices,
+                batch.uv_sets.iter().map(Vec::as_slice),
+                batch.custom_attributes.iter().map(Vec::as_slice),
+                &[],
+            );
+        }
+        log::info!("Batched {} primitives into {}", batched_primitives, batches.len());
+
+        Self::new(
+            kept_nodes,
+            sections.primitive_headers,
+            sections.uv_headers,
+            self.slice::<TextureHeader>(header.texture_header_offset, header.texture_header_count)
+                .to_vec(),
+            self.slice::<RawMaterial>(header.materials_offset, header.materials_count)
+                .to_vec(),
+            self.slice::<Sampler>(header.samplers_offset, header.samplers_count)
+                .to_vec(),
+            sections.vertices,
+            sections.indices,
+            sections.uv_sets,
+            sections.custom_headers,
+            sections.custom_attributes,
+            material_names.to_vec(),
+            strings,
+            self.slice::<u8>(header.texture_offset, header.texture_size).to_vec(),
+            AnimationSections {
+                skin_vertices: sections.skin_vertices,
+                ..self.animation_sections(header)
+            },
+            self.variant_sections(header),
+        )
+    }
+
+    // Packs the textures of materials that only differ in their textures into atlases, one per texture slot, and
+    // merges those materials into one per atlas, so scenes of hundreds of small textured parts bind a handful of
+    // materials. Only textures up to MAX_TILE_SIZE read inside [0, 1] are packed, a repeating texture can't be cut
+    // out of an atlas. The UVs of the primitives are moved onto their material's tile
+    pub fn pack_textures(&self) -> Self {
+        crate::profile_scope!("Pack texture atlases");
+        let header: &SceneHeader = bytemuck::from_bytes(&self.0[..std::mem::size_of::<SceneHeader>()]);
+        let primitive_headers =
+            self.slice::<PrimitiveHeader>(header.primitive_header_offset, header.primitive_header_count);
+        let raw_uv_headers = self.slice_raw::<TexCoordHeader>(header.uv_header_offset, header.uv_header_count);
+        let texture_headers = self.slice::<TextureHeader>(header.texture_header_offset, header.texture_header_count);
+        let raw_textures = self.slice::<u8>(header.texture_offset, header.texture_size);
+        let materials = self.slice::<RawMaterial>(header.materials_offset, header.materials_count);
+        let samplers = self.slice::<Sampler>(header.samplers_offset, header.samplers_count);
+        let material_names = self.slice::<StringHeader>(header.material_names_offset, header.material_names_count);
+        let raw_variant_mappings =
+            self.slice_raw::<VariantMapping>(header.variant_mappings_offset, header.variant_mappings_count);
+        let mut uv_sets = self
+            .slice::<TextureCoordinate>(header.uv_sets_offset, header.uv_sets_count)
+            .to_vec();
+        let mut strings =
+            String::from_utf8_lossy(self.slice::<u8>(header.strings_offset, header.strings_size)).into_owned();
+
+        // Where a uv set of the primitive lies in `uv_sets`, None for a set it doesn't have
+        let uv_range = |primitive: &PrimitiveHeader, uv_index: u32| {
+            let uv_headers: &[TexCoordHeader] =
+                Self::slice_as(raw_uv_headers, primitive.uv_header_offset, primitive.uv_set_count);
+            uv_headers.get(uv_index as usize).map(|uv_header| {
+                let start = uv_header.offset / std::mem::size_of::<TextureCoordinate>();
+                start..start + uv_header.count
+            })
+        };
+        let sampler = |slot: TextureSlot| samplers.get(slot.sampler_index as usize).copied().unwrap_or_default();
+        let texture = |slot: TextureSlot| texture_headers.get(slot.texture_index as usize);
+        // The largest texture of the material sets the size of its tile
+        let tile_size = |material: &RawMaterial| {
+            material
+                .texture_slots()
+                .into_iter()
+                .flatten()
+                .filter_map(texture)
+                .fold(glam::UVec2::ONE, |size, texture| {
+                    size.max(glam::UVec2::new(texture.width, texture.height))
+                })
+        };
+
+        let mut material_primitives = vec![Vec::new(); materials.len()];
+        for primitive in primitive_headers {
+            if let Some(primitives) = material_primitives.get_mut(primitive.material_index) {
+                primitives.push(*primitive);
+            }
+        }
+        // A variant swaps the material of a primitive, so its UVs must fit every material it may be drawn with
+        let mut has_variants = vec![false; materials.len()];
+        for primitive in primitive_headers.iter().filter(|primitive| primitive.variant_count > 0) {
+            let mappings: &[VariantMapping] =
+                Self::slice_as(raw_variant_mappings, primitive.variant_offset, primitive.variant_count);
+            for index in mappings
+                .iter()
+                .map(|mapping| mapping.material_index)
+                .chain([primitive.material_index])
+            {
+                if let Some(has_variants) = has_variants.get_mut(index) {
+                    *has_variants = true;
+                }
+            }
+        }
+        let packable = |index: usize, material: &RawMaterial| {
+            let slots: Vec<_> = material.texture_slots().into_iter().flatten().collect();
+            let inside = |uv: &TextureCoordinate| {
+                let uv = uv.to_vec();
+                uv.cmpge(glam::Vec2::splat(-Self::ATLAS_UV_EPSILON)).all()
+                    && uv.cmple(glam::Vec2::splat(1.0 + Self::ATLAS_UV_EPSILON)).all()
+            };
+            !slots.is_empty()
+                && !has_variants[index]
+                && slots.iter().all(|&slot| {
+                    texture(slot).is_some_and(|texture| texture.width.max(texture.height) <= Self::MAX_TILE_SIZE)
+                })
+                && material_primitives[index].iter().all(|primitive| {
+                    slots.iter().all(|slot| {
+                        uv_range(primitive, slot.uv_index).is_some_and(|range| uv_sets[range].iter().all(inside))
+                    })
+                })
+        };
+        // Materials that would be the same with their textures swapped out, filtered alike
+        let group_key = |material: &RawMaterial| {
+            let mut key = *material;
+            let mut filters = Vec::new();
+            for slot in key.texture_slots_mut().into_iter().flatten() {
+                let sampler = sampler(*slot);
+                filters.push((sampler.mag_filter, sampler.min_filter, sampler.mipmap_filter));
+                slot.texture_index = 0;
+                slot.sampler_index = 0;
+            }
+            (bytemuck::bytes_of(&key).to_vec(), filters)
+        };
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_indices = HashMap::new();
+        for (index, material) in materials.iter().enumerate() {
+            if packable(index, material) {
+                let group = *group_indices.entry(group_key(material)).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push(index);
+            }
+        }
+
+        // Every atlas holds the material, top left corner and size of its tiles
+        let mut atlases: Vec<(Vec<(usize, glam::UVec2, glam::UVec2)>, glam::UVec2)> = Vec::new();
+        for group in groups.iter().filter(|group| group.len() > 1) {
+            let mut tiles: Vec<_> = group
+                .iter()
+                .map(|&index| (index, tile_size(&materials[index])))
+                .collect();
+            tiles.sort_by_key(|(_, size)| std::cmp::Reverse(size.y));
+
+            let mut packer = ShelfPacker::new(Self::ATLAS_SIZE);
+            let mut atlas = Vec::new();
+            for (index, size) in tiles {
+                let padded = size + 2 * Self::ATLAS_GUTTER;
+                let position = packer.insert(padded.x, padded.y).or_else(|| {
+                    atlases.push((std::mem::take(&mut atlas), packer.extent()));
+                    packer = ShelfPacker::new(Self::ATLAS_SIZE);
+                    packer.insert(padded.x, padded.y)
+                });
+                if let Some(position) = position {
+                    atlas.push((index, position + Self::ATLAS_GUTTER, size));
+                }
+            }
+            atlases.push((atlas, packer.extent()));
+        }
+        // A material alone in its atlas is better off with its own textures
+        atlases.retain(|(tiles, _)| tiles.len() > 1);
+        if atlases.is_empty() {
+            return Self::from_bytes(&self.0);
+        }
+
+        let mut atlas_materials = vec![None; materials.len()];
+        for (atlas_index, (tiles, _)) in atlases.iter().enumerate() {
+            for &(index, position, size) in tiles {
+                atlas_materials[index] = Some((atlas_index, position, size));
+            }
+        }
+
+        // Textures of the materials left as they are, without those only the packed materials used
+        let mut new_texture_headers = Vec::new();
+        let mut textures = Vec::new();
+        let mut new_samplers = samplers.to_vec();
+        let mut new_materials = Vec::new();
+        let mut new_material_names = Vec::new();
+        let mut material_remap = vec![Self::MISSING_MATERIAL; materials.len()];
+        let mut texture_remap = HashMap::new();
+        for (index, material) in materials.iter().enumerate() {
+            if atlas_materials[index].is_some() {
+                continue;
+            }
+            let mut material = *material;
+            for slot in material.texture_slots_mut().into_iter().flatten() {
+                let old_index = slot.texture_index;
+                slot.texture_index = *texture_remap.entry(old_index).or_insert_with(|| {
+                    let texture = texture_headers[old_index as usize];
+                    new_texture_headers.push(TextureHeader {
+                        offset: textures.len(),
+                        ..texture
+                    });
+                    textures.extend_from_slice(&raw_textures[texture.offset..texture.offset + texture.size]);
+                    (new_texture_headers.len() - 1) as u32
+                });
+            }
+            material_remap[index] = new_materials.len();
+            new_materials.push(material);
+            new_material_names.push(material_names.get(index).copied().unwrap_or_default());
+        }
+
+        for (atlas_index, (tiles, extent)) in atlases.iter().enumerate() {
+            let mut material = materials[tiles[0].0];
+            for (slot_index, slot) in material.texture_slots_mut().into_iter().enumerate() {
+                let Some(slot) = slot else {
+                    continue;
+                };
+
+                let mut image = image::RgbaImage::new(extent.x, extent.y);
+                for &(index, position, size) in tiles {
+                    let Some(texture) = materials[index].texture_slots()[slot_index].and_then(texture) else {
+                        continue;
+                    };
+                    let data = &raw_textures[texture.offset..texture.offset + texture.size];
+                    let Some(tile) = texture.format.to_image(texture.width, texture.height, data) else {
+                        continue;
+                    };
+                    let mut tile = tile.to_rgba8();
+                    if tile.dimensions() != (size.x, size.y) {
+                        tile = image::imageops::resize(&tile, size.x, size.y, image::imageops::FilterType::Triangle);
+                    }
+                    blit_tile(&mut image, &tile, position, Self::ATLAS_GUTTER);
+                }
+
+                // Clamped, so the sampler doesn't wrap into the opposite edge of the atlas
+                new_samplers.push(Sampler {
+                    address_mode_u: 0,
+                    address_mode_v: 0,
+                    ..sampler(*slot)
+                });
+                new_texture_headers.push(TextureHeader {
+                    offset: textures.len(),
+                    size: image.len(),
+                    format: TextureFormat::RGBA8,
+                    width: extent.x,
+                    height: extent.y,
+                });
+                textures.extend_from_slice(image.as_raw());
+                slot.texture_index = (new_texture_headers.len() - 1) as u32;
+                slot.sampler_index = (new_samplers.len() - 1) as u32;
+            }
+
+            for &(index, _, _) in tiles {
+                material_remap[index] = new_materials.len();
+            }
+            new_materials.push(material);
+            new_material_names.push(StringHeader::push(
+                &mut strings,
+                Some(&format!("Texture atlas {}", atlas_index + 1)),
+            ));
+        }
+
+        for (index, primitives) in material_primitives.iter().enumerate() {
+            let Some((atlas_index, position, size)) = atlas_materials[index] else {
+                continue;
+            };
+            let extent = atlases[atlas_index].1.as_vec2();
+            let mut uv_indices: Vec<_> = materials[index]
+                .texture_slots()
+                .into_iter()
+                .flatten()
+                .map(|slot| slot.uv_index)
+                .collect();
+            uv_indices.sort_unstable();
+            uv_indices.dedup();
+            for primitive in primitives {
+                for range in uv_indices.iter().filter_map(|&uv_index| uv_range(primitive, uv_index)) {
+                    for uv in &mut uv_sets[range] {
+                        let tile_uv = uv.to_vec().clamp(glam::Vec2::ZERO, glam::Vec2::ONE);
+                        *uv = TextureCoordinate::new(
+                            ((position.as_vec2() + tile_uv * size.as_vec2()) / extent).to_array(),
+                        );
+                    }
+                }
+            }
+        }
+        let variants = self.variant_sections(header);
+        log::info!(
+            "Packed {} materials into {} texture atlases",
+            atlases.iter().map(|(tiles, _)| tiles.len()).sum::<usize>(),
+            atlases.len()
+        );
+
+        Self::new(
+            self.slice::<NodeHeader>(header.node_header_offset, header.node_header_count)
+                .to_vec(),
+            primitive_headers
+                .iter()
+                .map(|primitive| PrimitiveHeader {
+                    material_index: material_remap
+                        .get(primitive.material_index)
+                        .copied()
+                        .unwrap_or(primitive.material_index),
+                    ..*primitive
+                })
+                .collect(),
+            self.slice::<TexCoordHeader>(header.uv_header_offset, header.uv_header_count)
+                .to_vec(),
+            new_texture_headers,
+            new_materials,
+            new_samplers,
+            self.slice::<MeshVertex>(header.vertices_offset, header.vertices_count)
+                .to_vec(),
+            self.slice::<u32>(header.indices_offset, header.indices_count).to_vec(),
+            uv_sets,
+            self.slice::<CustomAttributeHeader>(header.custom_header_offset, header.custom_header_count)
+                .to_vec(),
+            self.slice::<[f32; 4]>(header.custom_attributes_offset, header.custom_attributes_count)
+                .to_vec(),
+            new_material_names,
+            strings,
+            textures,
+            self.animation_sections(header),
+            VariantSections {
+                mappings: variants
+                    .mappings
+                    .iter()
+                    .map(|mapping| VariantMapping {
+                        material_index: material_remap
+                            .get(mapping.material_index)
+                            .copied()
+                            .unwrap_or(mapping.material_index),
+                        ..*mapping
+                    })
+                    .collect(),
+                ..variants
+            },
+        )
+    }
+
+    fn variant_sections(&self, header: &SceneHeader) -> VariantSections {
+        VariantSections {
+            names: self
+                .slice::<StringHeader>(header.variant_names_offset, header.variant_names_count)
+                .to_vec(),
+            mappings: self
+                .slice::<VariantMapping>(header.variant_mappings_offset, header.variant_mappings_count)
+                .to_vec(),
+        }
+    }
+
+    // Copies of the animation sections
+    fn animation_sections(&self, header: &SceneHeader) -> AnimationSections {
+        AnimationSections {
+            skeleton_nodes: self
+                .slice::<SkeletonNodeHeader>(header.skeleton_nodes_offset, header.skeleton_nodes_count)
+                .to_vec(),
+            joints: self
+                .slice::<JointHeader>(header.joints_offset, header.joints_count)
+                .to_vec(),
+            skins: self
+                .slice::<SkinHeader>(header.skins_offset, header.skins_count)
+                .to_vec(),
+            animations: self
+                .slice::<AnimationHeader>(header.animations_offset, header.animations_count)
+                .to_vec(),
+            channels: self
+                .slice::<ChannelHeader>(header.channels_offset, header.channels_count)
+                .to_vec(),
+            keyframes: self
+                .slice::<f32>(header.keyframes_offset, header.keyframes_count)
+                .to_vec(),
+            skin_vertices: self
+                .slice::<SkinVertex>(header.skin_vertices_offset, header.skin_vertices_count)
+                .to_vec(),
+        }
+    }
+
     pub fn buffer(&self) -> &[u8] {
         &self.0
     }
@@ -535,15 +1873,57 @@ impl SceneBuffer {
         let raw_uv_headers =
             self.slice_raw::<TexCoordHeader>(scene_header.uv_header_offset, scene_header.uv_header_count);
         let raw_uv_sets = self.slice_raw::<TextureCoordinate>(scene_header.uv_sets_offset, scene_header.uv_sets_count);
+        let raw_custom_headers = self
+            .slice_raw::<CustomAttributeHeader>(scene_header.custom_header_offset, scene_header.custom_header_count);
+        let raw_custom_attributes = self.slice_raw::<[f32; 4]>(
+            scene_header.custom_attributes_offset,
+            scene_header.custom_attributes_count,
+        );
+        let material_names =
+            self.slice::<StringHeader>(scene_header.material_names_offset, scene_header.material_names_count);
+        let strings = self.slice::<u8>(scene_header.strings_offset, scene_header.strings_size);
+        let raw_variant_mappings = self.slice_raw::<VariantMapping>(
+            scene_header.variant_mappings_offset,
+            scene_header.variant_mappings_count,
+        );
+        let variant_names = self
+            .slice::<StringHeader>(scene_header.variant_names_offset, scene_header.variant_names_count)
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                name.read(strings)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Variant {}", index))
+            })
+            .collect::<Vec<_>>();
+
+        let skeleton_nodes =
+            self.slice::<SkeletonNodeHeader>(scene_header.skeleton_nodes_offset, scene_header.skeleton_nodes_count);
+        let raw_joints = self.slice_raw::<JointHeader>(scene_header.joints_offset, scene_header.joints_count);
+        let skins = self.slice::<SkinHeader>(scene_header.skins_offset, scene_header.skins_count);
+        let raw_channels = self.slice_raw::<ChannelHeader>(scene_header.channels_offset, scene_header.channels_count);
+        let raw_keyframes = self.slice_raw::<f32>(scene_header.keyframes_offset, scene_header.keyframes_count);
+        let raw_skin_vertices =
+            self.slice_raw::<SkinVertex>(scene_header.skin_vertices_offset, scene_header.skin_vertices_count);
+        let clips = self
+            .slice::<AnimationHeader>(scene_header.animations_offset, scene_header.animations_count)
+            .iter()
+            .map(|animation| {
+                Arc::new(AnimationClip::new(
+                    Self::slice_as(raw_channels, animation.channel_offset, animation.channel_count),
+                    raw_keyframes,
+                ))
+            })
+            .collect::<Vec<_>>();
 
         self.slice::<NodeHeader>(scene_header.node_header_offset, scene_header.node_header_count)
             .iter()
-            .map(|node_header| {
-                let transform = glam::Mat4::from_scale_rotation_translation(
-                    glam::Vec3::from_slice(&node_header.scale),
-                    glam::Quat::from_slice(&node_header.rotation),
-                    glam::Vec3::from_slice(&node_header.position),
-                );
+            .map(move |node_header| {
+                let transform = node_header.transform();
+                let skeleton = skins.get(node_header.skin).and_then(|skin| {
+                    let joints = Self::slice_as(raw_joints, skin.joint_offset, skin.joint_count);
+                    Skeleton::new(skeleton_nodes, joints, transform, &clips)
+                });
 
                 let primitive_headers: &[PrimitiveHeader] = Self::slice_as(
                     raw_primitive_headers,
@@ -565,18 +1945,75 @@ impl SceneBuffer {
                             primitive_header.uv_header_offset,
                             primitive_header.uv_set_count,
                         );
+                        let custom_headers: &[CustomAttributeHeader] = Self::slice_as(
+                            raw_custom_headers,
+                            primitive_header.custom_header_offset,
+                            primitive_header.custom_attribute_count,
+                        );
+
+                        let skin_vertices = (primitive_header.skin_vertex_count > 0 && skeleton.is_some()).then(|| {
+                            Self::slice_as(
+                                raw_skin_vertices,
+                                primitive_header.skin_vertex_offset,
+                                primitive_header.skin_vertex_count,
+                            )
+                        });
 
                         PrimitiveView {
                             vertices,
                             indices,
+                            index_format: match primitive_header.index_stride {
+                                2 => wgpu::IndexFormat::Uint16,
+                                _ => wgpu::IndexFormat::Uint32,
+                            },
+                            vertex_precision: VertexPrecision::from_index(primitive_header.vertex_precision),
                             material_index: primitive_header.material_index,
+                            variants: Self::slice_as(
+                                raw_variant_mappings,
+                                primitive_header.variant_offset,
+                                primitive_header.variant_count,
+                            ),
+                            skin_vertices,
                             uv_headers,
                             raw_uv_sets,
+                            custom_headers,
+                            raw_custom_attributes,
                         }
                     })
-                    .collect();
+                    .collect::<Vec<_>>();
 
-                NodeView { primitives, transform }
+                let mut materials = Vec::new();
+                for primitive in &primitives {
+                    let name = material_names
+                        .get(primitive.material_index)
+                        .and_then(|name| name.read(strings))
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("Material {}", primitive.material_index));
+                    if !materials.contains(&name) {
+                        materials.push(name);
+                    }
+                }
+                let metadata = NodeMetadata {
+                    name: node_header.name.read(strings).map(str::to_string),
+                    extras: node_header
+                        .extras
+                        .read(strings)
+                        .map(NodeMetadata::parse_extras)
+                        .unwrap_or_default(),
+                    materials,
+                    variants: match primitives.iter().any(|primitive| !primitive.variants.is_empty()) {
+                        true => variant_names.clone(),
+                        false => Vec::new(),
+                    },
+                    animation_duration: skeleton.as_ref().and_then(Skeleton::duration),
+                };
+
+                NodeView {
+                    primitives,
+                    transform,
+                    metadata,
+                    skeleton,
+                }
             })
     }
 
@@ -613,6 +2050,8 @@ impl SceneBuffer {
             normal: create_texture_view(material.normal, false),
             occlusion: create_texture_view(material.occlusion, false),
             emissive: create_texture_view(material.emissive, true),
+            height: create_texture_view(material.height, false),
+            clearcoat_normal: create_texture_view(material.clearcoat_normal, false),
             base_color_factor: material.base_color_factor,
             emissive_factor: material.emissive_factor,
             metallic_factor: material.metallic_factor,
@@ -620,16 +2059,118 @@ impl SceneBuffer {
             occlusion_strength: material.occlusion_strength,
             normal_scale: material.normal_scale,
             alpha_cutoff: material.alpha_cutoff,
+            displacement_scale: material.displacement_scale,
+            parallax_scale: material.parallax_scale,
+            parallax_steps: material.parallax_steps,
+            clearcoat_factor: material.clearcoat_factor,
+            clearcoat_roughness_factor: material.clearcoat_roughness_factor,
+            clearcoat_normal_scale: material.clearcoat_normal_scale,
+            sheen_color_factor: material.sheen_color_factor,
+            sheen_roughness_factor: material.sheen_roughness_factor,
+            transmission_factor: material.transmission_factor,
+            ior: material.ior,
+            thickness_factor: material.thickness_factor,
+            attenuation_color: material.attenuation_color,
+            attenuation_distance: material.attenuation_distance,
             alpha_mode: material.alpha_mode,
             double_sided: material.double_sided,
         })
     }
 
-    pub fn from_gltf(data: Vec<u8>) -> anyhow::Result<Self> {
+    // Material extensions read by `RawMaterial::from_gltf`, the variants by `from_gltf`
+    const SUPPORTED_GLTF_EXTENSIONS: [&str; 6] = [
+        "KHR_materials_clearcoat",
+        "KHR_materials_ior",
+        "KHR_materials_sheen",
+        "KHR_materials_transmission",
+        "KHR_materials_variants",
+        "KHR_materials_volume",
+    ];
+
+    // Out of range for every scene, the scene graph swaps in its fallback material
+    const MISSING_MATERIAL: usize = usize::MAX;
+    // Skin of a node without one
+    const NO_SKIN: usize = usize::MAX;
+    // Primitives up to this size are merged by `batch`, into batches of at most a 16-bit index range
+    const BATCHED_PRIMITIVE_VERTICES: usize = 4096;
+    const BATCH_VERTICES: usize = u16::MAX as usize + 1;
+    // Textures up to this size are packed by `pack_textures`, into atlases of at most ATLAS_SIZE with a gutter of
+    // repeated edge pixels around every tile. UVs a little outside [0, 1] are clamped onto the tile
+    const MAX_TILE_SIZE: u32 = 1024;
+    const ATLAS_SIZE: u32 = 4096;
+    const ATLAS_GUTTER: u32 = 2;
+    const ATLAS_UV_EPSILON: f32 = 1e-3;
+
+    // Bounds of the nodes `from_gltf` imports, from the accessors' minimum and maximum without reading any buffer
+    pub fn estimate_gltf_bounds(data: &[u8]) -> Option<Aabb> {
+        let json = match gltf::binary::Glb::from_slice(data) {
+            Ok(glb) => glb.json,
+            Err(_) => Cow::Borrowed(data),
+        };
+        let document = gltf::Document::from_json_without_validation(gltf::json::Root::from_slice(&json).ok()?);
+        let scene = document.default_scene().or_else(|| document.scenes().next())?;
+
+        let mut bounds = Aabb::EMPTY;
+        for node in imported_nodes(&scene) {
+            let Some(mesh) = node.mesh() else {
+                continue;
+            };
+
+            let transform = glam::Mat4::from_cols_array_2d(&node.transform().matrix());
+            for primitive in mesh.primitives() {
+                let Some(positions) = primitive.get(&gltf::Semantic::Positions) else {
+                    continue;
+                };
+                let corner = |value: Option<gltf::json::Value>| {
+                    serde_json::from_value::<[f32; 3]>(value?)
+                        .ok()
+                        .map(glam::Vec3::from_array)
+                };
+                if let (Some(min), Some(max)) = (corner(positions.min()), corner(positions.max())) {
+                    bounds.merge(&Aabb { min, max }.transform(transform));
+                }
+            }
+        }
+
+        (!bounds.is_empty()).then_some(bounds)
+    }
+
+    pub fn from_gltf(data: Vec<u8>, options: &ImportOptions) -> anyhow::Result<(Self, ImportReport)> {
+        crate::profile_scope!("Parse glTF");
         let (gltf, buffers, images) = gltf::import_slice(data)?;
+        let mut report = ImportReport::default();
 
-        let materials = gltf.materials().map(RawMaterial::from_gltf).collect::<Vec<_>>();
+        for name in gltf.extensions_used() {
+            if !Self::SUPPORTED_GLTF_EXTENSIONS.contains(&name) {
+                report.push(ImportWarning::UnsupportedExtension {
+                    name: name.to_string(),
+                    required: gltf.extensions_required().any(|required| required == name),
+                });
+            }
+        }
+
+        let mut strings = String::new();
+        let mut materials = gltf
+            .materials()
+            .map(|material| RawMaterial::from_gltf(material, &gltf))
+            .collect::<Vec<_>>();
+        let mut material_names = gltf
+            .materials()
+            .map(|material| StringHeader::push(&mut strings, material.name()))
+            .collect::<Vec<_>>();
+        let material_count = materials.len();
+        // Appended after the file's own materials once a primitive turns out to need it
+        let mut uses_default_material = false;
         let samplers = gltf.samplers().map(Sampler::from_gltf).collect::<Vec<_>>();
+        let mut variants = VariantSections {
+            names: gltf
+                .variants()
+                .into_iter()
+                .flatten()
+                .map(|variant| StringHeader::push(&mut strings, Some(variant.name())))
+                .collect(),
+            mappings: Vec::new(),
+        };
 
         let mut textures = Vec::new();
         let mut texture_headers = Vec::new();
@@ -647,6 +2188,9 @@ impl SceneBuffer {
             textures.extend(image.pixels);
         }
 
+        let animation = Self::read_gltf_animation(&gltf, &buffers, &mut strings);
+        let mut skin_vertices = Vec::new();
+
         let scene = gltf.default_scene().unwrap_or_else(|| gltf.scenes().next().unwrap());
 
         let mut node_headers = Vec::new();
@@ -655,87 +2199,327 @@ impl SceneBuffer {
         let mut vertices = Vec::new();
         let mut indices = Vec::new();
         let mut uv_sets = Vec::new();
+        let mut custom_headers: Vec<CustomAttributeHeader> = Vec::new();
+        let mut custom_attributes = Vec::new();
 
-        for node in scene.nodes() {
+        for node in imported_nodes(&scene) {
             if let Some(mesh) = node.mesh() {
                 let (position, rotation, scale) = node.transform().decomposed();
+                // The node's extras win over those of a mesh it shares with other nodes
+                let mut extras = serde_json::Map::new();
+                for raw in [mesh.extras(), node.extras()].into_iter().flatten() {
+                    match serde_json::from_str(raw.get()) {
+                        Ok(serde_json::Value::Object(object)) => extras.extend(object),
+                        Ok(serde_json::Value::Null) | Err(_) => {}
+                        Ok(value) => {
+                            extras.insert("extras".to_string(), value);
+                        }
+                    }
+                }
+                let extras = (!extras.is_empty()).then(|| serde_json::Value::Object(extras).to_string());
+
+                let mesh_name = mesh
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Mesh {}", mesh.index()));
+                let joint_count = match node.skin().map(|skin| skin.joints().len()) {
+                    Some(joints) if joints > MAX_JOINTS => {
+                        report.push(ImportWarning::TooManyJoints {
+                            mesh: mesh_name.clone(),
+                            joints,
+                        });
+                        None
+                    }
+                    joints => joints,
+                };
+
                 node_headers.push(NodeHeader {
                     position,
                     rotation,
                     scale,
                     primitive_header_offset: std::mem::size_of::<PrimitiveHeader>() * primitive_headers.len(),
-                    primitive_count: mesh.primitives().len(),
+                    // Skipped primitives don't count, filled in below
+                    primitive_count: 0,
+                    name: StringHeader::push(&mut strings, node.name().or(mesh.name())),
+                    extras: StringHeader::push(&mut strings, extras.as_deref()),
+                    skin: match joint_count {
+                        Some(_) => node.skin().map_or(Self::NO_SKIN, |skin| skin.index()),
+                        None => Self::NO_SKIN,
+                    },
                 });
+                let first_primitive = primitive_headers.len();
 
                 for primitive in mesh.primitives() {
+                    if primitive.mode() != gltf::mesh::Mode::Triangles {
+                        report.push(ImportWarning::UnsupportedPrimitiveMode {
+                            mesh: mesh_name.clone(),
+                            mode: format!("{:?}", primitive.mode()),
+                        });
+                        continue;
+                    }
+
                     let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-                    let mut primitive_uv_headers = Vec::new();
 
+                    let positions = match primitive.get(&gltf::Semantic::Positions) {
+                        Some(accessor) => match read_accessor(&accessor, &buffers) {
+                            Some(values) => values
+                                .into_iter()
+                                .map(|value| glam::Vec3::from_slice(&value))
+                                .collect::<Vec<_>>(),
+                            None => {
+                                report.push(ImportWarning::UnsupportedAttribute {
+                                    mesh: mesh_name.clone(),
+                                    name: gltf::Semantic::Positions.to_string(),
+                                });
+                                continue;
+                            }
+                        },
+                        None => Vec::new(),
+                    };
+                    if positions.is_empty() {
+                        report.push(ImportWarning::MissingPositions {
+                            mesh: mesh_name.clone(),
+                        });
+                        continue;
+                    }
+
+                    // Attributes that can't be read are left out as if the primitive didn't have them
+                    let mut read_attribute = |semantic: gltf::Semantic| {
+                        let values = read_accessor(&primitive.get(&semantic)?, &buffers)
+                            .filter(|values| values.len() == positions.len());
+                        if values.is_none() {
+                            report.push(ImportWarning::UnsupportedAttribute {
+                                mesh: mesh_name.clone(),
+                                name: semantic.to_string(),
+                            });
+                        }
+                        values
+                    };
+
+                    let mut primitive_uv_sets = Vec::new();
                     for set_index in 0..6 {
-                        if let Some(uv_reader) = reader.read_tex_coords(set_index) {
-                            let uv_set = uv_reader
-                                .into_f32()
-                                .map(|uv| TextureCoordinate::new(uv))
-                                .collect::<Vec<_>>();
-                            let header = TexCoordHeader {
-                                offset: std::mem::size_of::<TextureCoordinate>() * uv_sets.len(),
-                                count: uv_set.len(),
-                            };
-
-                            primitive_uv_headers.push(header);
-                            uv_sets.extend(uv_set);
-                        } else {
+                        let Some(uv_set) = read_attribute(gltf::Semantic::TexCoords(set_index)) else {
                             break;
+                        };
+                        primitive_uv_sets.push(
+                            uv_set
+                                .into_iter()
+                                .map(|uv| TextureCoordinate::new([uv[0], uv[1]]))
+                                .collect::<Vec<_>>(),
+                        );
+                    }
+                    let normals = read_attribute(gltf::Semantic::Normals);
+                    let tangents = read_attribute(gltf::Semantic::Tangents);
+                    // Joints outside the skin get no weight, the rest is normalized so the weights add up to one
+                    let primitive_skin = match joint_count {
+                        Some(joint_count) => read_attribute(gltf::Semantic::Joints(0))
+                            .zip(read_attribute(gltf::Semantic::Weights(0)))
+                            .map(|(joints, weights)| {
+                                joints
+                                    .into_iter()
+                                    .zip(weights)
+                                    .map(|(joints, weights)| {
+                                        let joints = joints.map(|joint| joint as usize);
+                                        let weights = std::array::from_fn::<f32, 4, _>(|index| {
+                                            match joints[index] < joint_count {
+                                                true => weights[index].max(0.0),
+                                                false => 0.0,
+                                            }
+                                        });
+                                        let total = weights.iter().sum::<f32>().max(f32::EPSILON);
+                                        SkinVertex {
+                                            joints: joints.map(|joint| joint.min(joint_count.saturating_sub(1)) as u16),
+                                            weights: weights.map(|weight| weight / total),
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()
+                            }),
+                        None => None,
+                    };
+
+                    // Attributes starting with an underscore, sorted by name so _CUSTOM0 comes before _CUSTOM1
+                    let mut custom_accessors = primitive
+                        .attributes()
+                        .filter_map(|(semantic, accessor)| match semantic {
+                            gltf::Semantic::Extras(name) => Some((format!("_{}", name), accessor)),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    custom_accessors.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+                    let mut primitive_custom_sets = Vec::new();
+                    for (name, accessor) in custom_accessors {
+                        match read_accessor(&accessor, &buffers) {
+                            Some(values) if values.len() == positions.len() => primitive_custom_sets.push(values),
+                            _ => report.push(ImportWarning::UnsupportedAttribute {
+                                mesh: mesh_name.clone(),
+                                name,
+                            }),
                         }
                     }
 
-                    let primitive_indices: Vec<u32> = reader
+                    // Non-indexed primitives draw their vertices in order
+                    let mut primitive_indices: Vec<u32> = reader
                         .read_indices()
                         .map(|iter| iter.into_u32().collect())
-                        .unwrap_or_default();
-
-                    let positions: Vec<glam::Vec3> = reader
-                        .read_positions()
-                        .map(|iter| iter.map(glam::Vec3::from_array).collect())
-                        .unwrap_or_default();
+                        .unwrap_or_else(|| (0..positions.len() as u32).collect());
 
-                    let normals = reader
-                        .read_normals()
-                        .map(|iter| iter.map(glam::Vec3::from_array).collect())
-                        .unwrap_or_else(|| calculate_normals(&positions, &indices));
+                    let triangle_count = primitive_indices.len() / 3;
+                    primitive_indices = primitive_indices
+                        .chunks_exact(3)
+                        .filter(|triangle| triangle.iter().all(|&index| (index as usize) < positions.len()))
+                        .flatten()
+                        .copied()
+                        .collect();
+                    let dropped = triangle_count - primitive_indices.len() / 3;
+                    if dropped > 0 {
+                        report.push(ImportWarning::IndicesOutOfRange {
+                            mesh: mesh_name.clone(),
+                            triangles: dropped,
+                        });
+                    }
 
-                    let uv_slice = &uv_sets[..primitive_uv_headers[0].count];
-                    let tangents = reader
-                        .read_tangents()
-                        .map(|iter| iter.map(glam::Vec4::from_array).collect())
-                        .unwrap_or_else(|| calculate_tangents(&positions, &normals, &primitive_indices, uv_slice));
+                    let normals = normals
+                        .map(|values| values.iter().map(|value| glam::Vec3::from_slice(value)).collect())
+                        .unwrap_or_else(|| {
+                            report.push(ImportWarning::MissingNormals {
+                                mesh: mesh_name.clone(),
+                            });
+                            calculate_normals(&positions, &primitive_indices)
+                        });
+
+                    let tangents = match (tangents, primitive_uv_sets.first()) {
+                        (Some(values), _) => values.into_iter().map(glam::Vec4::from_array).collect(),
+                        (None, Some(uvs)) => {
+                            report.push(ImportWarning::GeneratedTangents {
+                                mesh: mesh_name.clone(),
+                            });
+                            calculate_tangents(&positions, &normals, &primitive_indices, uvs)
+                        }
+                        // Nothing to orient normal maps by, any tangent in the surface will do
+                        (None, None) => {
+                            report.push(ImportWarning::MissingTexCoords {
+                                mesh: mesh_name.clone(),
+                            });
+                            normals
+                                .iter()
+                                .map(|normal| normal.any_orthonormal_vector().extend(1.0))
+                                .collect()
+                        }
+                    };
 
-                    let primitive_vertices = positions
+                    let mut primitive_vertices = positions
                         .into_iter()
                         .zip(normals)
                         .zip(tangents)
                         .map(|((position, normal), tangent)| MeshVertex::new(position, normal, tangent))
                         .collect::<Vec<_>>();
 
+                    // Subdivision would need the joints and weights interpolated too, skinned primitives keep theirs
+                    let subdivision_levels = match primitive_skin {
+                        Some(_) => 0,
+                        None => options.subdivision_levels(),
+                    };
+                    for _ in 0..subdivision_levels {
+                        if !fits_u32_indices(primitive_vertices.len() * 4, primitive_indices.len() * 4) {
+                            report.push(ImportWarning::SubdivisionLimited {
+                                mesh: mesh_name.clone(),
+                            });
+                            break;
+                        }
+                        primitive_indices = subdivide(
+                            &mut primitive_vertices,
+                            &mut primitive_uv_sets,
+                            &mut primitive_custom_sets,
+                            &primitive_indices,
+                        );
+                    }
+
+                    // Mappings to variants or materials the file doesn't have are left out
+                    let primitive_variants = primitive
+                        .mappings()
+                        .flat_map(|mapping| {
+                            let material_index = mapping.material().index().filter(|&index| index < material_count);
+                            mapping.variants().iter().filter_map(move |&variant| {
+                                Some(VariantMapping {
+                                    variant: variant as usize,
+                                    material_index: material_index?,
+                                })
+                            })
+                        })
+                        .filter(|mapping| mapping.variant < variants.names.len())
+                        .collect::<Vec<_>>();
+
                     let header = PrimitiveHeader {
                         vertex_offset: std::mem::size_of::<MeshVertex>() * vertices.len(),
                         vertex_count: primitive_vertices.len(),
                         index_offset: std::mem::size_of::<u32>() * indices.len(),
                         index_count: primitive_indices.len(),
+                        index_stride: index_stride(options, primitive_vertices.len()),
+                        // The skinning entry point reads full precision vertices
+                        vertex_precision: match primitive_skin {
+                            Some(_) => VertexPrecision::Full.index(),
+                            None => options.vertex_precision.index(),
+                        },
                         uv_header_offset: std::mem::size_of::<TexCoordHeader>() * uv_headers.len(),
-                        uv_set_count: primitive_uv_headers.len(),
-                        material_index: primitive.material().index().unwrap_or(0),
+                        uv_set_count: primitive_uv_sets.len(),
+                        custom_header_offset: std::mem::size_of::<CustomAttributeHeader>() * custom_headers.len(),
+                        custom_attribute_count: primitive_custom_sets.len(),
+                        material_index: match primitive.material().index() {
+                            Some(index) if index < material_count => index,
+                            Some(index) => {
+                                report.push(ImportWarning::MissingMaterial {
+                                    mesh: mesh_name.clone(),
+                                    index,
+                                });
+                                Self::MISSING_MATERIAL
+                            }
+                            None => {
+                                uses_default_material = true;
+                                material_count
+                            }
+                        },
+                        skin_vertex_offset: std::mem::size_of::<SkinVertex>() * skin_vertices.len(),
+                        skin_vertex_count: primitive_skin.as_ref().map_or(0, Vec::len),
+                        variant_offset: std::mem::size_of::<VariantMapping>() * variants.mappings.len(),
+                        variant_count: primitive_variants.len(),
                     };
 
+                    for uv_set in primitive_uv_sets {
+                        uv_headers.push(TexCoordHeader {
+                            offset: std::mem::size_of::<TextureCoordinate>() * uv_sets.len(),
+                            count: uv_set.len(),
+                        });
+                        uv_sets.extend(uv_set);
+                    }
+
+                    for custom_set in primitive_custom_sets {
+                        custom_headers.push(CustomAttributeHeader {
+                            offset: std::mem::size_of::<[f32; 4]>() * custom_attributes.len(),
+                            count: custom_set.len(),
+                        });
+                        custom_attributes.extend(custom_set);
+                    }
+
                     primitive_headers.push(header);
-                    uv_headers.extend(primitive_uv_headers);
                     vertices.extend(primitive_vertices);
                     indices.extend(primitive_indices);
+                    skin_vertices.extend(primitive_skin.into_iter().flatten());
+                    variants.mappings.extend(primitive_variants);
+                }
+
+                if let Some(node_header) = node_headers.last_mut() {
+                    node_header.primitive_count = primitive_headers.len() - first_primitive;
                 }
             }
         }
 
-        Ok(Self::new(
+        if uses_default_material {
+            materials.push(RawMaterial::gltf_default());
+            material_names.push(StringHeader::push(&mut strings, Some("Default material")));
+        }
+
+        let scene = Self::new(
             node_headers,
             primitive_headers,
             uv_headers,
@@ -745,11 +2529,130 @@ impl SceneBuffer {
             vertices,
             indices,
             uv_sets,
+            custom_headers,
+            custom_attributes,
+            material_names,
+            strings,
             textures,
-        ))
+            AnimationSections {
+                skin_vertices,
+                ..animation
+            },
+            variants,
+        );
+        let scene = match options.texture_atlas {
+            true => scene.pack_textures(),
+            false => scene,
+        };
+        let scene = match options.static_batching {
+            true => scene.batch(options),
+            false => scene,
+        };
+
+        Ok((scene, report))
+    }
+
+    // Every node of the file in its rest pose, the skins and the channels of every animation. Morph target weights
+    // aren't animated
+    fn read_gltf_animation(
+        gltf: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        strings: &mut String,
+    ) -> AnimationSections {
+        use gltf::animation::util::ReadOutputs;
+
+        let mut animation = AnimationSections::default();
+        let mut parents = vec![NO_PARENT; gltf.nodes().len()];
+        for node in gltf.nodes() {
+            for child in node.children() {
+                parents[child.index()] = node.index() as u32;
+            }
+        }
+        animation.skeleton_nodes = gltf
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                SkeletonNodeHeader {
+                    parent: parents[node.index()],
+                    translation,
+                    rotation,
+                    scale,
+                }
+            })
+            .collect();
+
+        for skin in gltf.skins() {
+            let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+            let inverse_bind_matrices = reader
+                .read_inverse_bind_matrices()
+                .map(|matrices| matrices.collect::<Vec<_>>())
+                .unwrap_or_default();
+            animation.skins.push(SkinHeader {
+                joint_offset: std::mem::size_of::<JointHeader>() * animation.joints.len(),
+                joint_count: skin.joints().len(),
+            });
+            // Joints without an inverse bind matrix are bound at the origin
+            for (index, joint) in skin.joints().enumerate() {
+                let inverse_bind_matrix = inverse_bind_matrices
+                    .get(index)
+                    .map_or(glam::Mat4::IDENTITY, glam::Mat4::from_cols_array_2d);
+                animation.joints.push(JointHeader {
+                    node: joint.index() as u32,
+                    inverse_bind_matrix: inverse_bind_matrix.to_cols_array(),
+                });
+            }
+        }
+
+        for clip in gltf.animations() {
+            let first_channel = animation.channels.len();
+            for channel in clip.channels() {
+                let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                let (path, values) = match reader.read_outputs() {
+                    Some(ReadOutputs::Translations(values)) => (ChannelPath::Translation, values.flatten().collect()),
+                    Some(ReadOutputs::Rotations(values)) => {
+                        (ChannelPath::Rotation, values.into_f32().flatten().collect())
+                    }
+                    Some(ReadOutputs::Scales(values)) => (ChannelPath::Scale, values.flatten().collect::<Vec<_>>()),
+                    Some(ReadOutputs::MorphTargetWeights(_)) | None => continue,
+                };
+                let times = reader
+                    .read_inputs()
+                    .map(|times| times.collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let interpolation = match channel.sampler().interpolation() {
+                    gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                    gltf::animation::Interpolation::Step => Interpolation::Step,
+                    gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                };
+                if times.is_empty() || values.len() < times.len() * interpolation.stride(path.components()) {
+                    continue;
+                }
+
+                animation.channels.push(ChannelHeader {
+                    node: channel.target().node().index() as u32,
+                    path: path.index(),
+                    interpolation: interpolation.index(),
+                    _padding: 0,
+                    time_offset: std::mem::size_of::<f32>() * animation.keyframes.len(),
+                    value_offset: std::mem::size_of::<f32>() * (animation.keyframes.len() + times.len()),
+                    key_count: times.len(),
+                });
+                animation.keyframes.extend(times);
+                animation.keyframes.extend(values);
+            }
+
+            animation.animations.push(AnimationHeader {
+                name: StringHeader::push(strings, clip.name()),
+                channel_offset: std::mem::size_of::<ChannelHeader>() * first_channel,
+                channel_count: animation.channels.len() - first_channel,
+            });
+        }
+
+        animation
     }
 
-    pub async fn from_obj(path: &ResourcePath) -> anyhow::Result<Self> {
+    pub async fn from_obj(path: &ResourcePath, options: &ImportOptions) -> anyhow::Result<(Self, ImportReport)> {
+        crate::profile_scope!("Parse OBJ");
         let text = path.load_string().await?;
         let cursor = Cursor::new(text);
         let mut reader = BufReader::new(cursor);
@@ -762,8 +2665,10 @@ impl SceneBuffer {
                 ..Default::default()
             },
             |p| async move {
-                let material_text = path.create_relative(&p).load_string().await.unwrap();
-                tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(material_text)))
+                match path.create_relative(&p).load_string().await {
+                    Ok(material_text) => tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(material_text))),
+                    Err(_) => Err(tobj::LoadError::OpenFileFailed),
+                }
             },
         )
         .await?;
@@ -772,35 +2677,57 @@ impl SceneBuffer {
         let mut texture_headers = Vec::new();
         let mut samplers = Vec::new();
         let mut materials = Vec::new();
+        let mut report = ImportReport::default();
 
-        for material in &obj_materials? {
-            let mut load_texture = async |obj_texture: &Option<String>| -> anyhow::Result<Option<usize>> {
-                if let Some(filename) = obj_texture {
-                    let texture_path = path.create_relative(&filename);
-                    let texture = texture_path.load_binary().await?;
-                    let image = image::load_from_memory(&texture)?.to_rgba8();
-                    let buffer = image.as_bytes();
-                    let header = TextureHeader {
-                        offset: textures.len(),
-                        size: buffer.len(),
-                        width: image.width(),
-                        height: image.height(),
-                        format: TextureFormat::RGBA8,
-                    };
+        let obj_materials = obj_materials.unwrap_or_else(|error| {
+            report.push(ImportWarning::MissingMaterialLibrary {
+                error: error.to_string(),
+            });
+            Vec::new()
+        });
 
-                    texture_headers.push(header);
-                    textures.extend_from_slice(buffer);
+        for material in &obj_materials {
+            // A texture that fails to load leaves its slot empty, which the material fills with a placeholder
+            let mut load_texture = async |obj_texture: &Option<String>| -> Option<usize> {
+                let filename = obj_texture.as_ref()?;
+                let texture_path = path.create_relative(&filename);
+                let image = match texture_path.load_binary().await {
+                    Ok(texture) => image::load_from_memory(&texture).map_err(anyhow::Error::from),
+                    Err(error) => Err(error),
+                };
 
-                    Ok(Some(texture_headers.len() - 1))
-                } else {
-                    Ok(None)
-                }
+                let image = match image {
+                    Ok(image) => image.to_rgba8(),
+                    Err(error) => {
+                        report.push(ImportWarning::MissingTexture {
+                            path: texture_path.to_string(),
+                            error: error.to_string(),
+                        });
+                        return None;
+                    }
+                };
+
+                let buffer = image.as_bytes();
+                let header = TextureHeader {
+                    offset: textures.len(),
+                    size: buffer.len(),
+                    width: image.width(),
+                    height: image.height(),
+                    format: TextureFormat::RGBA8,
+                };
+
+                texture_headers.push(header);
+                textures.extend_from_slice(buffer);
+
+                Some(texture_headers.len() - 1)
             };
 
-            let diffuse_index = load_texture(&material.diffuse_texture).await?;
-            let normal_index = load_texture(&material.normal_texture).await?;
+            let diffuse_index = load_texture(&material.diffuse_texture).await;
+            let normal_index = load_texture(&material.normal_texture).await;
+            let height_texture = obj_displacement_map(material).map(|(filename, _)| filename.to_string());
+            let height_index = load_texture(&height_texture).await;
 
-            let new_material = RawMaterial::from_obj(&material);
+            let new_material = RawMaterial::from_obj(&material, diffuse_index, normal_index, height_index);
             materials.push(new_material);
 
             // if let Some(filename) = &material.diffuse_texture {
@@ -820,6 +2747,15 @@ impl SceneBuffer {
             // textures.extend(buffer);
         }
 
+        let mut strings = String::new();
+        let mut material_names = obj_materials
+            .iter()
+            .map(|material| StringHeader::push(&mut strings, Some(&material.name)))
+            .collect::<Vec<_>>();
+        let material_count = materials.len();
+        // Appended after the library's materials once a face turns out to need it
+        let mut uses_default_material = false;
+
         let (node_headers, primitive_headers, uv_headers, vertices, indices, uv_sets) = models.into_iter().fold(
             (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()),
             |accumulator, model| {
@@ -831,6 +2767,9 @@ impl SceneBuffer {
                     scale: [1.0, 1.0, 1.0],
                     primitive_header_offset: std::mem::size_of::<PrimitiveHeader>() * primitive_headers.len(),
                     primitive_count: 1,
+                    name: StringHeader::push(&mut strings, Some(&model.name)),
+                    extras: StringHeader::default(),
+                    skin: Self::NO_SKIN,
                 });
 
                 // let (model_vertices, tex_coords): (Vec<_>, Vec<_>) = (0..model.mesh.positions.len() / 3)
@@ -874,7 +2813,7 @@ impl SceneBuffer {
                     .map(|vertices| glam::Vec3::from_slice(vertices))
                     .collect::<Vec<_>>();
 
-                let tex_coords = model
+                let mut tex_coords = model
                     .mesh
                     .texcoords
                     .chunks_exact(2)
@@ -882,6 +2821,9 @@ impl SceneBuffer {
                     .collect::<Vec<_>>();
 
                 let normals = if model.mesh.normals.is_empty() {
+                    report.push(ImportWarning::MissingNormals {
+                        mesh: model.name.clone(),
+                    });
                     calculate_normals(&positions, &model.mesh.indices)
                 } else {
                     model
@@ -892,17 +2834,34 @@ impl SceneBuffer {
                         .collect::<Vec<_>>()
                 };
 
+                // OBJ has no tangents, they are always generated
                 let tangents = calculate_tangents(&positions, &normals, &model.mesh.indices, &tex_coords);
 
-                let model_vertices = positions
+                let mut model_vertices = positions
                     .into_iter()
                     .zip(normals)
                     .zip(tangents)
                     .map(|((position, normal), tangent)| MeshVertex::new(position, normal, tangent))
                     .collect::<Vec<_>>();
 
+                let mut model_indices = model.mesh.indices;
+                for _ in 0..options.subdivision_levels() {
+                    if !fits_u32_indices(model_vertices.len() * 4, model_indices.len() * 4) {
+                        report.push(ImportWarning::SubdivisionLimited {
+                            mesh: model.name.clone(),
+                        });
+                        break;
+                    }
+                    model_indices = subdivide(
+                        &mut model_vertices,
+                        std::slice::from_mut(&mut tex_coords),
+                        &mut [],
+                        &model_indices,
+                    );
+                }
+
                 let uv_header = TexCoordHeader {
-                    offset: 0,
+                    offset: std::mem::size_of::<TextureCoordinate>() * uv_sets.len(),
                     count: tex_coords.len(),
                 };
 
@@ -910,23 +2869,49 @@ impl SceneBuffer {
                     vertex_offset: std::mem::size_of::<MeshVertex>() * vertices.len(),
                     vertex_count: model_vertices.len(),
                     index_offset: std::mem::size_of::<u32>() * indices.len(),
-                    index_count: model.mesh.indices.len(),
+                    index_count: model_indices.len(),
+                    index_stride: index_stride(options, model_vertices.len()),
+                    vertex_precision: options.vertex_precision.index(),
                     uv_header_offset: std::mem::size_of::<TexCoordHeader>() * uv_headers.len(),
                     uv_set_count: 1,
-                    material_index: model.mesh.material_id.unwrap_or(0),
+                    custom_header_offset: 0,
+                    custom_attribute_count: 0,
+                    skin_vertex_offset: 0,
+                    skin_vertex_count: 0,
+                    variant_offset: 0,
+                    variant_count: 0,
+                    material_index: match model.mesh.material_id {
+                        Some(index) if index < material_count => index,
+                        Some(index) => {
+                            report.push(ImportWarning::MissingMaterial {
+                                mesh: model.name.clone(),
+                                index,
+                            });
+                            Self::MISSING_MATERIAL
+                        }
+                        None => {
+                            uses_default_material = true;
+                            material_count
+                        }
+                    },
                 };
 
                 primitive_headers.push(header);
                 uv_headers.push(uv_header);
                 vertices.extend(model_vertices);
-                indices.extend(model.mesh.indices);
+                indices.extend(model_indices);
                 uv_sets.extend(tex_coords);
 
                 (node_headers, primitive_headers, uv_headers, vertices, indices, uv_sets)
             },
         );
 
-        Ok(Self::new(
+        if uses_default_material {
+            materials.push(RawMaterial::gltf_default());
+            material_names.push(StringHeader::push(&mut strings, Some("Default material")));
+        }
+
+        let scene = Self::new(
             node_headers,
             primitive_headers,
             uv_headers,
@@ -936,8 +2921,24 @@ impl SceneBuffer {
             vertices,
             indices,
             uv_sets,
+            Vec::new(),
+            Vec::new(),
+            material_names,
+            strings,
             textures,
-        ))
+            AnimationSections::default(),
+            VariantSections::default(),
+        );
+        let scene = match options.texture_atlas {
+            true => scene.pack_textures(),
+            false => scene,
+        };
+        let scene = match options.static_batching {
+            true => scene.batch(options),
+            false => scene,
+        };
+
+        Ok((scene, report))
     }
 }
 
@@ -0,0 +1,350 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::renderer::context::RenderContext;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct PanoramaUniform {
+    size: [f32; 2],
+    top: f32,
+    yaw: f32,
+}
+
+// Over/under omni-directional stereo panorama, the left eye on top, as VR photo viewers expect. The columns are split
+// into strips, each captured as a cubemap from its own eye position half the eye distance beside the camera, at
+// right angles to the direction the strip looks in. More strips give a smoother parallax and take longer
+pub struct StereoPanorama {
+    width: u32,
+    yaw: f32,
+    faces: wgpu::Texture,
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    buffer: wgpu::Buffer,
+    face_layout: wgpu::BindGroupLayout,
+    face_pipeline: wgpu::RenderPipeline,
+    face_sampler: wgpu::Sampler,
+    strip_bind_group: wgpu::BindGroup,
+    strip_pipeline: wgpu::RenderPipeline,
+}
+
+impl StereoPanorama {
+    pub const MIN_WIDTH: u32 = 512;
+    const FACE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    // `width` is that of the output, which is as high as it is wide. `yaw` turns the center of the panorama towards
+    // the camera's heading, in radians from -Z towards +X
+    pub fn new(width: u32, yaw: f32, context: &RenderContext) -> Self {
+        let max_width = context.device.limits().max_texture_dimension_2d;
+        let width = width.clamp(Self::MIN_WIDTH, max_width) & !1;
+        // A face covers a quarter of the horizon
+        let face_size = width / 4;
+
+        let faces = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Panorama face texture"),
+            size: wgpu::Extent3d {
+                width: face_size,
+                height: face_size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FACE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let faces_view = faces.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Panorama face view"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let output = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Panorama texture"),
+            size: wgpu::Extent3d {
+                width,
+                height: width,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::OUTPUT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[Self::OUTPUT_FORMAT.add_srgb_suffix()],
+        });
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(Self::OUTPUT_FORMAT.add_srgb_suffix()),
+            ..Default::default()
+        });
+
+        let face_sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Panorama sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Panorama buffer"),
+            size: std::mem::size_of::<PanoramaUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+
+        let face_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Panorama face layout"),
+                entries: &[texture_entry(0, wgpu::TextureViewDimension::D2), sampler_entry],
+            });
+        let strip_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Panorama strip layout"),
+                entries: &[
+                    texture_entry(0, wgpu::TextureViewDimension::Cube),
+                    sampler_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let strip_bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Panorama strip bind group"),
+            layout: &strip_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&faces_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&face_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        // Faces are stored the way reflection probes store theirs, so they're sampled the same way too
+        let face_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Panorama face shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/probe_blit.wgsl").into()),
+        });
+        let strip_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Panorama strip shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/ods.wgsl").into()),
+        });
+
+        let create_pipeline = |label, layout: &wgpu::BindGroupLayout, shader, entry_point, format| {
+            let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let face_pipeline = create_pipeline(
+            "Panorama face pipeline",
+            &face_layout,
+            &face_shader,
+            "fs_face",
+            Self::FACE_FORMAT,
+        );
+        let strip_pipeline = create_pipeline(
+            "Panorama strip pipeline",
+            &strip_layout,
+            &strip_shader,
+            "fs_strip",
+            Self::OUTPUT_FORMAT.add_srgb_suffix(),
+        );
+
+        Self {
+            width,
+            yaw,
+            faces,
+            output,
+            output_view,
+            buffer,
+            face_layout,
+            face_pipeline,
+            face_sampler,
+            strip_bind_group,
+            strip_pipeline,
+        }
+    }
+
+    pub fn capture_config(&self, format: wgpu::TextureFormat) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: self.faces.width(),
+            height: self.faces.height(),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 1,
+        }
+    }
+
+    pub fn output(&self) -> &wgpu::Texture {
+        &self.output
+    }
+
+    // Where the eye sits while the strip is captured, the left eye is eye 0
+    pub fn eye_position(&self, center: glam::Vec3, eye_distance: f32, eye: u32, strip: u32, strips: u32) -> glam::Vec3 {
+        let longitude = self.yaw + ((strip as f32 + 0.5) / strips as f32 - 0.5) * std::f32::consts::TAU;
+        let right = glam::Vec3::new(longitude.cos(), 0.0, longitude.sin());
+        let side = if eye == 0 { -0.5 } else { 0.5 };
+        center + right * eye_distance * side
+    }
+
+    pub fn store_face(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        face: u32,
+        source: &wgpu::TextureView,
+        context: &RenderContext,
+    ) {
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Panorama face bind group"),
+            layout: &self.face_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.face_sampler),
+                },
+            ],
+        });
+
+        let target = self.faces.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Panorama face target"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: face,
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Panorama face pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.face_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // Fills the strip's columns of the eye's half from the faces captured last. Needs its own submission, the
+    // uniform is rewritten for the next eye
+    pub fn render_strip(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        eye: u32,
+        strip: u32,
+        strips: u32,
+        context: &RenderContext,
+    ) {
+        let height = self.width / 2;
+        let uniform = PanoramaUniform {
+            size: [self.width as f32, height as f32],
+            top: (eye * height) as f32,
+            yaw: self.yaw,
+        };
+        context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let start = strip * self.width / strips;
+        let end = (strip + 1) * self.width / strips;
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Panorama strip pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.output_view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_scissor_rect(start, eye * height, end - start, height);
+        render_pass.set_pipeline(&self.strip_pipeline);
+        render_pass.set_bind_group(0, &self.strip_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
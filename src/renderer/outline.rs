@@ -0,0 +1,277 @@
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{
+    context::RenderContext,
+    instance::Instance,
+    pointcloud::PointVertex,
+    quantize::MeshPipelines,
+    scene::{Geometry, Renderable, SceneGraph},
+    vertex::VertexLayoutBuilder,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineUniform {
+    color: [f32; 4],
+    width: f32,
+    _padding: [f32; 3],
+}
+
+// Selected entity drawn into a coverage mask, which is dilated into an outline over the tone mapped image. Works the
+// same for every material and render mode since only the geometry ends up in the mask
+pub struct SelectionOutline {
+    mesh_pipelines: MeshPipelines,
+    point_pipeline: wgpu::RenderPipeline,
+    outline_pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+}
+
+impl SelectionOutline {
+    pub const MASK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+    // Linear orange, in pixels
+    const COLOR: [f32; 4] = [1.0, 0.35, 0.02, 1.0];
+    const WIDTH: f32 = 2.5;
+
+    pub fn new(scene_layout: &wgpu::BindGroupLayout, context: &RenderContext) -> Self {
+        let mask_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Selection mask shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/selection_mask.wgsl").into()),
+        });
+
+        let mask_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Selection mask pipeline layout"),
+            bind_group_layouts: &[&context.camera_bind_group_layout, scene_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_mask_pipeline = |label: &str, entry_point, topology, buffers: &[wgpu::VertexBufferLayout]| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&mask_layout),
+                vertex: wgpu::VertexState {
+                    module: &mask_shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &mask_shader,
+                    entry_point: Some("fs_main"),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::MASK_FORMAT,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    ..Default::default()
+                },
+                // No depth test, hidden parts of the selection are outlined too
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let mesh_pipelines = MeshPipelines::new(|precision| {
+            create_mask_pipeline(
+                &format!("Selection mask mesh pipeline ({})", precision.to_str()),
+                "vs_mesh",
+                wgpu::PrimitiveTopology::TriangleList,
+                &precision.layout_builder(0).push::<Instance>().build(),
+            )
+        });
+        let point_pipeline = create_mask_pipeline(
+            "Selection mask pointcloud pipeline",
+            "vs_points",
+            wgpu::PrimitiveTopology::PointList,
+            &VertexLayoutBuilder::new()
+                .push::<PointVertex>()
+                .push::<Instance>()
+                .build(),
+        );
+
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Outline layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let outline_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/outline.wgsl").into()),
+        });
+
+        let outline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Outline pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let outline_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline pipeline"),
+            layout: Some(&outline_layout),
+            vertex: wgpu::VertexState {
+                module: &outline_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &outline_shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.config.format.add_srgb_suffix(),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Outline buffer"),
+            contents: bytemuck::bytes_of(&OutlineUniform {
+                color: Self::COLOR,
+                width: Self::WIDTH,
+                _padding: [0.0; 3],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        Self {
+            mesh_pipelines,
+            point_pipeline,
+            outline_pipeline,
+            layout,
+            buffer,
+        }
+    }
+
+    pub fn render_mask(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &SceneGraph,
+        camera_bind_group: &wgpu::BindGroup,
+        mask: &wgpu::TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Selection mask render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: mask,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, scene.bind_group(), &[]);
+        render_pass.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+
+        for batch in &scene.selection_batches {
+            match scene.renderables.get(&batch.key.render_id) {
+                Some(Renderable::Mesh(handles)) => {
+                    for handle in handles {
+                        if let Some(Geometry::Primitive(primitive)) = scene.geometries.get_by_id(handle.geometry_index)
+                        {
+                            render_pass.set_pipeline(self.mesh_pipelines.get(primitive.vertex_precision));
+                            render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                            render_pass.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
+                            render_pass.draw_indexed(0..primitive.num_elements, 0, batch.instance_range());
+                        }
+                    }
+                }
+                Some(Renderable::Pointcloud(handle)) => {
+                    if let Some(Geometry::Pointcloud(pointcloud)) = scene.geometries.get_by_id(handle.geometry_index) {
+                        render_pass.set_pipeline(&self.point_pipeline);
+                        render_pass.set_vertex_buffer(0, pointcloud.vertex_buffer.slice(..));
+                        render_pass.draw(0..pointcloud.num_points, batch.instance_range());
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Blends over the surface, after tone mapping so the outline keeps its color
+    pub fn render_outline(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        mask: &wgpu::TextureView,
+        view: &wgpu::TextureView,
+        context: &RenderContext,
+    ) {
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Outline bind group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(mask),
+                },
+            ],
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Outline render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.outline_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
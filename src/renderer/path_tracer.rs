@@ -0,0 +1,570 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{
+    bvh::Bvh,
+    context::RenderContext,
+    material::MaterialUniform,
+    scene::{Geometry, Renderable, SceneGraph, Visibility},
+    transform::TransformUniform,
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct PathTracerUniform {
+    inv_view: [[f32; 4]; 4],
+    inv_projection: [[f32; 4]; 4],
+    position: [f32; 4],
+    width: u32,
+    height: u32,
+    frame_index: u32,
+    max_bounces: u32,
+    triangle_count: u32,
+    _padding: [u32; 3],
+}
+
+// World space triangle, the whole scene is flattened into a single BVH
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct PathTracerTriangle {
+    p0: [f32; 3],
+    material: u32,
+    p1: [f32; 3],
+    _padding1: u32,
+    p2: [f32; 3],
+    _padding2: u32,
+    n0: [f32; 3],
+    _padding3: u32,
+    n1: [f32; 3],
+    _padding4: u32,
+    n2: [f32; 3],
+    _padding5: u32,
+}
+
+impl PathTracerTriangle {
+    fn new(positions: [glam::Vec3; 3], normals: [glam::Vec3; 3], material: u32) -> Self {
+        Self {
+            p0: positions[0].to_array(),
+            material,
+            p1: positions[1].to_array(),
+            p2: positions[2].to_array(),
+            n0: normals[0].to_array(),
+            n1: normals[1].to_array(),
+            n2: normals[2].to_array(),
+            ..Zeroable::zeroed()
+        }
+    }
+}
+
+// Only the material factors are used, textures are not sampled by the path tracer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct PathTracerMaterial {
+    base_color: [f32; 4],
+    emissive: [f32; 3],
+    metallic: f32,
+    roughness: f32,
+    _padding: [f32; 3],
+}
+
+impl PathTracerMaterial {
+    fn from_uniform(uniform: &MaterialUniform) -> Self {
+        Self {
+            base_color: uniform.base_color_factor,
+            emissive: uniform.emissive_factor,
+            metallic: uniform.metallic_factor,
+            roughness: uniform.roughness_factor,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+impl Default for PathTracerMaterial {
+    fn default() -> Self {
+        Self {
+            base_color: [0.8, 0.8, 0.8, 1.0],
+            emissive: [0.0; 3],
+            metallic: 0.0,
+            roughness: 0.5,
+            _padding: [0.0; 3],
+        }
+    }
+}
+
+pub struct PathTracer {
+    compute_pipeline: wgpu::ComputePipeline,
+    resolve_pipeline: wgpu::RenderPipeline,
+    frame_layout: wgpu::BindGroupLayout,
+    resolve_layout: wgpu::BindGroupLayout,
+    scene_layout: wgpu::BindGroupLayout,
+    uniform: PathTracerUniform,
+    uniform_buffer: wgpu::Buffer,
+    accumulation_buffer: wgpu::Buffer,
+    frame_bind_group: wgpu::BindGroup,
+    resolve_bind_group: wgpu::BindGroup,
+    scene_bind_group: wgpu::BindGroup,
+    is_scene_dirty: bool,
+}
+
+impl PathTracer {
+    pub const MAX_SAMPLES: u32 = 4096;
+    const WORKGROUP_SIZE: u32 = 8;
+
+    pub fn new(context: &RenderContext) -> Self {
+        let frame_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Path tracer frame layout"),
+                entries: &[
+                    Self::uniform_entry(0, wgpu::ShaderStages::COMPUTE),
+                    Self::storage_entry(1, wgpu::ShaderStages::COMPUTE, false),
+                ],
+            });
+
+        let resolve_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Path tracer resolve layout"),
+                entries: &[
+                    Self::uniform_entry(0, wgpu::ShaderStages::FRAGMENT),
+                    Self::storage_entry(1, wgpu::ShaderStages::FRAGMENT, true),
+                ],
+            });
+
+        let scene_layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Path tracer scene layout"),
+                entries: &[
+                    Self::storage_entry(0, wgpu::ShaderStages::COMPUTE, true),
+                    Self::storage_entry(1, wgpu::ShaderStages::COMPUTE, true),
+                    Self::storage_entry(2, wgpu::ShaderStages::COMPUTE, true),
+                ],
+            });
+
+        let uniform = PathTracerUniform {
+            inv_view: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            inv_projection: glam::Mat4::IDENTITY.to_cols_array_2d(),
+            position: [0.0; 4],
+            width: context.config.width.max(1),
+            height: context.config.height.max(1),
+            frame_index: 0,
+            max_bounces: 4,
+            triangle_count: 0,
+            _padding: [0; 3],
+        };
+
+        let uniform_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Path tracer uniform buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Path tracer shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("../../res/ray_tracing.wgsl"),
+                    include_str!("../../res/path_tracer.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let resolve_shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Path tracer resolve shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/path_resolve.wgsl").into()),
+        });
+
+        let compute_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Path tracer pipeline layout"),
+            bind_group_layouts: &[&frame_layout, &scene_layout, &context.environment_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = context
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Path tracer compute pipeline"),
+                layout: Some(&compute_pipeline_layout),
+                module: &shader,
+                entry_point: Some("trace_paths"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        let resolve_pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Path tracer resolve pipeline layout"),
+            bind_group_layouts: &[&resolve_layout],
+            push_constant_ranges: &[],
+        });
+
+        let resolve_pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Path tracer resolve pipeline"),
+            layout: Some(&resolve_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &resolve_shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &resolve_shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.hdr.format(),
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let accumulation_buffer = Self::create_accumulation_buffer(&uniform, context);
+        let frame_bind_group = Self::create_frame_bind_group(
+            "Path tracer frame bind group",
+            &frame_layout,
+            &uniform_buffer,
+            &accumulation_buffer,
+            context,
+        );
+        let resolve_bind_group = Self::create_frame_bind_group(
+            "Path tracer resolve bind group",
+            &resolve_layout,
+            &uniform_buffer,
+            &accumulation_buffer,
+            context,
+        );
+        let scene_bind_group = Self::create_scene_bind_group(
+            &[PathTracerTriangle::zeroed()],
+            &Bvh::build(&[]),
+            &[PathTracerMaterial::default()],
+            &scene_layout,
+            context,
+        );
+
+        Self {
+            compute_pipeline,
+            resolve_pipeline,
+            frame_layout,
+            resolve_layout,
+            scene_layout,
+            uniform,
+            uniform_buffer,
+            accumulation_buffer,
+            frame_bind_group,
+            resolve_bind_group,
+            scene_bind_group,
+            is_scene_dirty: true,
+        }
+    }
+
+    pub fn update_camera(&mut self, position: glam::Vec3, view: glam::Mat4, projection: glam::Mat4) {
+        self.uniform.inv_view = view.inverse().to_cols_array_2d();
+        self.uniform.inv_projection = projection.inverse().to_cols_array_2d();
+        self.uniform.position = position.extend(1.0).to_array();
+    }
+
+    pub fn set_max_bounces(&mut self, max_bounces: u32) {
+        self.uniform.max_bounces = max_bounces;
+    }
+
+    pub fn reset(&mut self) {
+        self.uniform.frame_index = 0;
+    }
+
+    pub fn is_converged(&self) -> bool {
+        self.uniform.frame_index >= Self::MAX_SAMPLES
+    }
+
+    pub fn accumulation_buffer(&self) -> &wgpu::Buffer {
+        &self.accumulation_buffer
+    }
+
+    // Geometry or transforms changed, rebuild the BVH before the next frame
+    pub fn invalidate_scene(&mut self) {
+        self.is_scene_dirty = true;
+        self.reset();
+    }
+
+    pub fn resize(&mut self, context: &RenderContext) {
+        self.uniform.width = context.config.width.max(1);
+        self.uniform.height = context.config.height.max(1);
+        self.accumulation_buffer = Self::create_accumulation_buffer(&self.uniform, context);
+        self.frame_bind_group = Self::create_frame_bind_group(
+            "Path tracer frame bind group",
+            &self.frame_layout,
+            &self.uniform_buffer,
+            &self.accumulation_buffer,
+            context,
+        );
+        self.resolve_bind_group = Self::create_frame_bind_group(
+            "Path tracer resolve bind group",
+            &self.resolve_layout,
+            &self.uniform_buffer,
+            &self.accumulation_buffer,
+            context,
+        );
+        self.reset();
+    }
+
+    // The lightmapper traces against the same BVH
+    pub fn scene_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.scene_layout
+    }
+
+    pub fn scene_bind_group(&self) -> &wgpu::BindGroup {
+        &self.scene_bind_group
+    }
+
+    pub fn triangle_count(&self) -> u32 {
+        self.uniform.triangle_count
+    }
+
+    pub fn prepare_scene(&mut self, scene: &SceneGraph, context: &RenderContext) {
+        if self.is_scene_dirty {
+            self.build_scene(scene, context);
+            self.is_scene_dirty = false;
+        }
+    }
+
+    // Adds one sample per pixel to the accumulation buffer
+    pub fn trace(&mut self, scene: &SceneGraph, encoder: &mut wgpu::CommandEncoder, context: &RenderContext) {
+        self.prepare_scene(scene, context);
+
+        if self.uniform.frame_index < Self::MAX_SAMPLES {
+            context
+                .queue
+                .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniform));
+
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Path tracer compute pass"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_pipeline(&self.compute_pipeline);
+                compute_pass.set_bind_group(0, &self.frame_bind_group, &[]);
+                compute_pass.set_bind_group(1, &self.scene_bind_group, &[]);
+                compute_pass.set_bind_group(2, scene.environment_map.bind_group(), &[]);
+                compute_pass.dispatch_workgroups(
+                    self.uniform.width.div_ceil(Self::WORKGROUP_SIZE),
+                    self.uniform.height.div_ceil(Self::WORKGROUP_SIZE),
+                    1,
+                );
+            }
+
+            self.uniform.frame_index += 1;
+        }
+    }
+
+    // Writes the running average into the HDR target
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder, context: &RenderContext) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Path tracer resolve pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.resolve_pipeline);
+        render_pass.set_bind_group(0, &self.resolve_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn build_scene(&mut self, scene: &SceneGraph, context: &RenderContext) {
+        let mut positions = Vec::new();
+        let mut triangles = Vec::new();
+
+        for (entity_id, node_index, render_id) in scene.nodes.iter_with_index() {
+            if scene.visibility(entity_id) != Visibility::Visible {
+                continue;
+            }
+
+            let Some(transform_index) = scene.node_transform_index.get_mapping(node_index) else {
+                continue;
+            };
+
+            let Some(Renderable::Mesh(handles)) = scene.renderables.get(render_id) else {
+                continue;
+            };
+
+            let transform = scene
+                .transforms
+                .get_by_index(transform_index as usize)
+                .map(TransformUniform::to_mat4)
+                .unwrap_or_default();
+            let normal_matrix = glam::Mat3::from_mat4(transform.inverse().transpose());
+
+            for handle in handles {
+                let Some(Geometry::Primitive(primitive)) = scene.geometries.get_by_id(handle.geometry_index) else {
+                    continue;
+                };
+
+                for vertices in primitive.geometry.triangles() {
+                    let world_positions =
+                        vertices.map(|vertex| transform.transform_point3(glam::Vec3::from_array(vertex.position)));
+                    let world_normals = vertices
+                        .map(|vertex| (normal_matrix * glam::Vec3::from_array(vertex.normal)).normalize_or_zero());
+
+                    positions.push(world_positions);
+                    triangles.push(PathTracerTriangle::new(
+                        world_positions,
+                        world_normals,
+                        handle.material_index.index(),
+                    ));
+                }
+            }
+        }
+
+        let bvh = Bvh::from_triangles(&positions);
+        let mut triangles = bvh
+            .indices
+            .iter()
+            .map(|&index| triangles[index as usize])
+            .collect::<Vec<_>>();
+        self.uniform.triangle_count = triangles.len() as u32;
+
+        // Storage buffers can't be empty
+        if triangles.is_empty() {
+            triangles.push(PathTracerTriangle::zeroed());
+        }
+
+        let mut materials = scene
+            .materials
+            .components()
+            .iter()
+            .map(|material| {
+                material.as_ref().map_or_else(PathTracerMaterial::default, |material| {
+                    PathTracerMaterial::from_uniform(&material.uniform)
+                })
+            })
+            .collect::<Vec<_>>();
+        if materials.is_empty() {
+            materials.push(PathTracerMaterial::default());
+        }
+
+        self.scene_bind_group =
+            Self::create_scene_bind_group(&triangles, &bvh, &materials, &self.scene_layout, context);
+    }
+
+    fn uniform_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    fn storage_entry(binding: u32, visibility: wgpu::ShaderStages, read_only: bool) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+
+    fn create_accumulation_buffer(uniform: &PathTracerUniform, context: &RenderContext) -> wgpu::Buffer {
+        let size = (uniform.width * uniform.height) as u64 * std::mem::size_of::<[f32; 4]>() as u64;
+        context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Path tracer accumulation buffer"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_frame_bind_group(
+        label: &str,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+        accumulation_buffer: &wgpu::Buffer,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: accumulation_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn create_scene_bind_group(
+        triangles: &[PathTracerTriangle],
+        bvh: &Bvh,
+        materials: &[PathTracerMaterial],
+        layout: &wgpu::BindGroupLayout,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
+        let buffers = [
+            ("Path tracer triangles", bytemuck::cast_slice(triangles)),
+            ("Path tracer BVH nodes", bytemuck::cast_slice(&bvh.nodes)),
+            ("Path tracer materials", bytemuck::cast_slice(materials)),
+        ]
+        .map(|(label, contents)| {
+            context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        });
+
+        let entries = buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| wgpu::BindGroupEntry {
+                binding: index as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Path tracer scene bind group"),
+            layout,
+            entries: &entries,
+        })
+    }
+}
@@ -0,0 +1,309 @@
+use std::sync::{Arc, OnceLock};
+
+use uuid::Uuid;
+
+use crate::renderer::{
+    animation::SkinVertex,
+    context::RenderContext,
+    instance::Instance,
+    pointcloud::PointVertex,
+    quantize::{MeshPipelines, VertexPrecision},
+    scene::{Geometry, Renderable, SceneGraph},
+    texture::Texture,
+    vertex::VertexLayoutBuilder,
+};
+
+// Draws the scene's transform indices into an id target, clipped to the clicked pixel, and reads that pixel back.
+// Shares the AOV shader and draws the batches of the main pass, skins posed, so it only hits what's on screen
+pub struct PickPass {
+    mesh_pipelines: MeshPipelines,
+    skinned_pipeline: wgpu::RenderPipeline,
+    point_pipeline: wgpu::RenderPipeline,
+    // Id and depth targets the size of the surface, kept for the next click until the surface resizes
+    targets: Option<(wgpu::Texture, Texture)>,
+}
+
+impl PickPass {
+    const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+    pub fn new(scene_layout: &wgpu::BindGroupLayout, context: &RenderContext) -> Self {
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Pick shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/aov.wgsl").into()),
+        });
+
+        let layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Pick pipeline layout"),
+            bind_group_layouts: &[&context.camera_bind_group_layout, scene_layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point, topology, constants, buffers: &[wgpu::VertexBufferLayout]| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants,
+                        ..Default::default()
+                    },
+                    buffers,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_pick"),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::ID_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let mesh_pipelines = MeshPipelines::new(|precision| {
+            create_pipeline(
+                &format!("Pick mesh pipeline ({})", precision.to_str()),
+                "vs_mesh",
+                wgpu::PrimitiveTopology::TriangleList,
+                precision.shader_constants(),
+                &precision.layout_builder(0).push::<Instance>().build(),
+            )
+        });
+        let skinned_pipeline = create_pipeline(
+            "Pick skinned mesh pipeline",
+            "vs_skinned",
+            wgpu::PrimitiveTopology::TriangleList,
+            &[],
+            &VertexPrecision::Full
+                .layout_builder(0)
+                .push::<SkinVertex>()
+                .push::<Instance>()
+                .build(),
+        );
+        let point_pipeline = create_pipeline(
+            "Pick pointcloud pipeline",
+            "vs_points",
+            wgpu::PrimitiveTopology::PointList,
+            &[],
+            &VertexLayoutBuilder::new()
+                .push::<PointVertex>()
+                .push::<Instance>()
+                .build(),
+        );
+
+        Self {
+            mesh_pipelines,
+            skinned_pipeline,
+            point_pipeline,
+            targets: None,
+        }
+    }
+
+    // Surface pixel from the top left, outside of the surface nothing is hit. Like the AOVs the entities are resolved
+    // now, they may be gone by the time the pixel arrives
+    pub fn pick(
+        &mut self,
+        x: u32,
+        y: u32,
+        scene: &SceneGraph,
+        camera_bind_group: &wgpu::BindGroup,
+        context: &RenderContext,
+    ) -> PickReadback {
+        let (width, height) = (context.config.width, context.config.height);
+        let (x, y) = (x.min(width.saturating_sub(1)), y.min(height.saturating_sub(1)));
+        let is_resized = self
+            .targets
+            .as_ref()
+            .is_none_or(|(ids, _)| ids.width() != width || ids.height() != height);
+        if is_resized {
+            let ids = context.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Pick id texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: Self::ID_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let depth = Texture::create_depth_texture(&context.device, &context.config, Some("Pick depth texture"));
+            self.targets = Some((ids, depth));
+        }
+        let (ids, depth) = self.targets.as_ref().unwrap();
+        let staging = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick staging buffer"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Pick encoder"),
+        });
+        {
+            let id_view = ids.create_view(&wgpu::TextureViewDescriptor::default());
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Pick render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &id_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            // Only the clicked pixel is shaded
+            render_pass.set_scissor_rect(x, y, 1, 1);
+            render_pass.set_bind_group(0, camera_bind_group, &[]);
+            render_pass.set_bind_group(1, scene.bind_group(), &[]);
+
+            for batch in scene
+                .render_batches
+                .iter()
+                .filter(|batch| batch.key.render_id != scene.debug_id)
+            {
+                match scene.renderables.get(&batch.key.render_id) {
+                    Some(Renderable::Mesh(handles)) => {
+                        for handle in handles {
+                            if let Some(Geometry::Primitive(primitive)) =
+                                scene.geometries.get_by_id(handle.geometry_index)
+                            {
+                                render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                                if primitive.is_skinned {
+                                    render_pass.set_pipeline(&self.skinned_pipeline);
+                                    // Joints and weights take the place of the last UV set
+                                    let skin = &primitive.uv_buffers[RenderContext::MAX_UV_SETS - 1];
+                                    render_pass.set_vertex_buffer(1, skin.slice(..));
+                                    render_pass.set_vertex_buffer(2, scene.instance_pool.buffer().slice(..));
+                                } else {
+                                    render_pass.set_pipeline(self.mesh_pipelines.get(primitive.vertex_precision));
+                                    render_pass.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+                                }
+                                render_pass.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
+                                render_pass.draw_indexed(0..primitive.num_elements, 0, batch.instance_range());
+                            }
+                        }
+                    }
+                    Some(Renderable::Pointcloud(handle)) => {
+                        if let Some(Geometry::Pointcloud(pointcloud)) =
+                            scene.geometries.get_by_id(handle.geometry_index)
+                        {
+                            render_pass.set_pipeline(&self.point_pipeline);
+                            render_pass.set_vertex_buffer(0, pointcloud.vertex_buffer.slice(..));
+                            render_pass.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+                            render_pass.draw(0..pointcloud.num_points, batch.instance_range());
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: ids,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        context.queue.submit(Some(encoder.finish()));
+
+        let status = Arc::new(OnceLock::new());
+        let callback_status = Arc::clone(&status);
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if let Err(error) = &result {
+                log::error!("Unable to map pick buffer: {}", error);
+            }
+            let _ = callback_status.set(result.is_ok());
+        });
+
+        let entities = scene
+            .transforms
+            .iter_with_index()
+            .map(|(entity, index, _)| (index as u32 + 1, *entity))
+            .collect();
+
+        PickReadback {
+            staging,
+            status,
+            entities,
+        }
+    }
+}
+
+pub struct PickReadback {
+    staging: wgpu::Buffer,
+    status: Arc<OnceLock<bool>>,
+    // Keyed by id, zero is the background
+    entities: Vec<(u32, Uuid)>,
+}
+
+impl PickReadback {
+    // None while the copy is still in flight, then the entity under the pixel if there was one
+    pub fn try_read(&self) -> Option<Option<Uuid>> {
+        if !*self.status.get()? {
+            return Some(None);
+        }
+
+        let id = {
+            let data = self.staging.slice(..).get_mapped_range();
+            bytemuck::pod_read_unaligned::<u32>(&data[..4])
+        };
+        self.staging.unmap();
+
+        Some(
+            self.entities
+                .iter()
+                .find(|(key, _)| *key == id)
+                .map(|(_, entity)| *entity),
+        )
+    }
+}
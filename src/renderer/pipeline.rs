@@ -1,16 +1,373 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use crossbeam::channel::{Receiver, Sender};
+
+use crate::renderer::{
+    animation::SkinVertex,
+    context::RenderContext,
+    instance::Instance,
+    pointcloud::{PointClassification, PointVertex},
+    quantize::VertexPrecision,
+    texture::Texture,
+    vertex::VertexLayoutBuilder,
+};
+
+// A shader module together with the pipeline layout it is compiled against
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ShaderKind {
+    Mesh,
+    Pointcloud,
+    Light,
+}
+
+impl ShaderKind {
+    const ALL: [Self; 3] = [Self::Mesh, Self::Pointcloud, Self::Light];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Mesh => "Shader",
+            Self::Pointcloud => "Pointcloud shader",
+            Self::Light => "Light shader",
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Self::Mesh => include_str!("../../res/shader.wgsl"),
+            Self::Pointcloud => include_str!("../../res/pc_shader.wgsl"),
+            Self::Light => include_str!("../../res/light.wgsl"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum VertexLayoutKind {
+    Mesh,
+    CompactMesh,
+    // Full precision, with joints and weights in place of the last UV set
+    SkinnedMesh,
+    Pointcloud,
+}
+
+impl VertexLayoutKind {
+    const ALL: [Self; 4] = [Self::Mesh, Self::CompactMesh, Self::SkinnedMesh, Self::Pointcloud];
+
+    // Override constants for shaders reading mesh vertices, compact normals and tangents are decoded when set
+    pub fn shader_constants(&self) -> &'static [(&'static str, f64)] {
+        match self {
+            Self::CompactMesh => &[("QUANTIZED", 1.0)],
+            Self::Mesh | Self::SkinnedMesh | Self::Pointcloud => &[],
+        }
+    }
+
+    fn vertex_entry_point(&self) -> &'static str {
+        match self {
+            Self::SkinnedMesh => "vs_skinned",
+            Self::Mesh | Self::CompactMesh | Self::Pointcloud => "vs_main",
+        }
+    }
+
+    fn build(&self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        match self {
+            Self::Mesh => VertexPrecision::Full
+                .layout_builder(RenderContext::MAX_UV_SETS)
+                .push::<Instance>()
+                .build(),
+            Self::CompactMesh => VertexPrecision::Compact
+                .layout_builder(RenderContext::MAX_UV_SETS)
+                .push::<Instance>()
+                .build(),
+            Self::SkinnedMesh => VertexPrecision::Full
+                .layout_builder(RenderContext::MAX_UV_SETS - 1)
+                .push::<SkinVertex>()
+                .push::<Instance>()
+                .build(),
+            Self::Pointcloud => VertexLayoutBuilder::new()
+                .push::<PointVertex>()
+                .push::<PointClassification>()
+                .push::<Instance>()
+                .build(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct PipelineKey {
+    pub shader: ShaderKind,
+    pub vertex_layout: VertexLayoutKind,
+    pub topology: wgpu::PrimitiveTopology,
+    pub blend: Option<wgpu::BlendState>,
+    pub cull_mode: Option<wgpu::Face>,
+    pub depth_write: bool,
+    pub depth_compare: wgpu::CompareFunction,
+    pub sample_count: u32,
+}
+
+impl PipelineKey {
+    pub const MESH: Self = Self {
+        shader: ShaderKind::Mesh,
+        vertex_layout: VertexLayoutKind::Mesh,
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        blend: Some(wgpu::BlendState::REPLACE),
+        cull_mode: Some(wgpu::Face::Back),
+        depth_write: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        sample_count: 1,
+    };
+
+    pub const POINTCLOUD: Self = Self {
+        shader: ShaderKind::Pointcloud,
+        vertex_layout: VertexLayoutKind::Pointcloud,
+        topology: wgpu::PrimitiveTopology::PointList,
+        cull_mode: None,
+        ..Self::MESH
+    };
+
+    pub const LIGHT: Self = Self {
+        shader: ShaderKind::Light,
+        ..Self::MESH
+    };
+
+    // Draws the same buffers into the same targets, only blending, culling or the depth state differ
+    fn is_compatible(&self, other: &Self) -> bool {
+        self.shader == other.shader
+            && self.vertex_layout == other.vertex_layout
+            && self.topology == other.topology
+            && self.sample_count == other.sample_count
+    }
+}
+
+// Everything needed to compile a pipeline, shared with the compilation threads
+struct PipelineSources {
+    device: wgpu::Device,
+    format: wgpu::TextureFormat,
+    shaders: HashMap<ShaderKind, (wgpu::ShaderModule, wgpu::PipelineLayout)>,
+    vertex_layouts: HashMap<VertexLayoutKind, Vec<wgpu::VertexBufferLayout<'static>>>,
+    cache: Option<wgpu::PipelineCache>,
+}
+
+impl PipelineSources {
+    fn create(&self, key: &PipelineKey) -> wgpu::RenderPipeline {
+        let (module, layout) = &self.shaders[&key.shader];
+        let label = format!("{} pipeline", key.shader.label());
+
+        self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&label),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: Some(key.vertex_layout.vertex_entry_point()),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: key.vertex_layout.shader_constants(),
+                    ..Default::default()
+                },
+                buffers: &self.vertex_layouts[&key.vertex_layout],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.format,
+                    blend: key.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: key.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: key.cull_mode,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: key.depth_write,
+                depth_compare: key.depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: key.sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: self.cache.as_ref(),
+        })
+    }
+}
+
+// Render pipelines are created on first use. Native builds compile them on a background thread, batches draw with a
+// compatible variant meanwhile or are skipped until they're ready. The driver cache is written to disk so later
+// startups skip most of the work
+pub struct PipelineCache {
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    pending: HashSet<PipelineKey>,
+    sources: Arc<PipelineSources>,
+    compiled_tx: Sender<(PipelineKey, wgpu::RenderPipeline)>,
+    compiled_rx: Receiver<(PipelineKey, wgpu::RenderPipeline)>,
+    cache_path: Option<PathBuf>,
+    is_cache_dirty: bool,
+}
 
-pub struct PipelineCache(HashMap<&'static str, wgpu::RenderPipeline>);
 impl PipelineCache {
-    pub fn new() -> Self {
-        Self(HashMap::new())
+    pub fn new(context: &RenderContext, scene_layout: &wgpu::BindGroupLayout) -> Self {
+        let shaders = ShaderKind::ALL
+            .into_iter()
+            .map(|shader| {
+                let module = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some(shader.label()),
+                    source: wgpu::ShaderSource::Wgsl(shader.source().into()),
+                });
+
+                // Only the mesh shader reads the environment
+                let mut bind_group_layouts = vec![
+                    &context.texture_bind_group_layout,
+                    &context.camera_bind_group_layout,
+                    scene_layout,
+                ];
+                if shader == ShaderKind::Mesh {
+                    bind_group_layouts.push(&context.environment_bind_group_layout);
+                }
+
+                let layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some(&format!("{} pipeline layout", shader.label())),
+                    bind_group_layouts: &bind_group_layouts,
+                    push_constant_ranges: &[],
+                });
+
+                (shader, (module, layout))
+            })
+            .collect();
+
+        let vertex_layouts = VertexLayoutKind::ALL
+            .into_iter()
+            .map(|kind| (kind, kind.build()))
+            .collect();
+
+        let (cache, cache_path) = Self::load_cache(context);
+        let (compiled_tx, compiled_rx) = crossbeam::channel::unbounded();
+
+        Self {
+            pipelines: HashMap::new(),
+            pending: HashSet::new(),
+            sources: Arc::new(PipelineSources {
+                device: context.device.clone(),
+                format: context.hdr.format(),
+                shaders,
+                vertex_layouts,
+                cache,
+            }),
+            compiled_tx,
+            compiled_rx,
+            cache_path,
+            is_cache_dirty: false,
+        }
+    }
+
+    pub fn warmup(&mut self, keys: impl IntoIterator<Item = PipelineKey>) {
+        keys.into_iter().for_each(|key| self.request(key));
+    }
+
+    // Collects finished compilations and requests anything the frame needs that isn't there yet
+    pub fn prepare(&mut self, keys: impl IntoIterator<Item = PipelineKey>) {
+        while let Ok((key, pipeline)) = self.compiled_rx.try_recv() {
+            self.pending.remove(&key);
+            self.pipelines.insert(key, pipeline);
+            self.is_cache_dirty = true;
+        }
+
+        self.warmup(keys);
+
+        if self.is_cache_dirty && self.pending.is_empty() {
+            self.save_cache();
+            self.is_cache_dirty = false;
+        }
     }
 
-    pub fn insert(&mut self, id: &'static str, pipeline: wgpu::RenderPipeline) {
-        self.0.insert(id, pipeline);
+    pub fn is_compiling(&self) -> bool {
+        !self.pending.is_empty()
     }
 
-    pub fn get(&self, id: &str) -> Option<&wgpu::RenderPipeline> {
-        self.0.get(id)
+    // The pipeline for `key`, or while that one compiles a finished variant that only differs in render state
+    pub fn get(&self, key: &PipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key).or_else(|| {
+            self.pipelines
+                .iter()
+                .find(|(candidate, _)| candidate.is_compatible(key))
+                .map(|(_, pipeline)| pipeline)
+        })
+    }
+
+    fn request(&mut self, key: PipelineKey) {
+        if self.pipelines.contains_key(&key) || !self.pending.insert(key) {
+            return;
+        }
+
+        let sources = Arc::clone(&self.sources);
+        let compiled_tx = self.compiled_tx.clone();
+        let compile = move || {
+            let pipeline = sources.create(&key);
+            if let Err(error) = compiled_tx.send((key, pipeline)) {
+                log::error!("Unable to deliver pipeline {:?}: {}", key.shader, error);
+            }
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        std::thread::spawn(compile);
+
+        // WebGPU objects can't leave the main thread, the browser compiles in the background on its own
+        #[cfg(target_family = "wasm")]
+        compile();
+    }
+
+    fn load_cache(context: &RenderContext) -> (Option<wgpu::PipelineCache>, Option<PathBuf>) {
+        if cfg!(target_family = "wasm") || !context.device.features().contains(wgpu::Features::PIPELINE_CACHE) {
+            return (None, None);
+        }
+
+        let Some(filename) = wgpu::util::pipeline_cache_key(&context.adapter_info) else {
+            return (None, None);
+        };
+
+        let path = std::env::temp_dir().join("wgpu-playground").join(filename);
+        let data = std::fs::read(&path).ok();
+
+        // Safety: the data was written by `get_data` for this adapter, and a mismatch falls back to an empty cache
+        let cache = unsafe {
+            context.device.create_pipeline_cache(&wgpu::PipelineCacheDescriptor {
+                label: Some("Pipeline cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+
+        (Some(cache), Some(path))
+    }
+
+    fn save_cache(&self) {
+        let (Some(cache), Some(path)) = (&self.sources.cache, &self.cache_path) else {
+            return;
+        };
+
+        let Some(data) = cache.get_data() else {
+            return;
+        };
+
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, data));
+
+        if let Err(error) = result {
+            log::error!("Unable to write pipeline cache to {}: {}", path.display(), error);
+        }
     }
 }
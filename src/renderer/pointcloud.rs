@@ -1,9 +1,23 @@
-use std::{io::Cursor, ops::Range};
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    ops::{ControlFlow, Range},
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
 
 use bytemuck::{Pod, Zeroable};
-use wgpu::util::DeviceExt;
 
-use crate::renderer::{asset::ResourcePath, context::RenderContext, vertex::Vertex};
+use crate::renderer::{
+    asset::ResourcePath, bvh::Aabb, context::RenderContext, memory::Allocation, ramp::ColorRamp, vertex::Vertex,
+};
+
+// Scans are Z up, the scene is Y up
+pub const MAT4_SWAP_YZ: glam::Mat4 = glam::Mat4::from_cols_array(&[
+    1.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+]);
+
+// Points read from a LAS or LAZ file at a time, larger files hand each chunk over as it's decoded
+pub const LAS_CHUNK_POINTS: u64 = 1 << 20;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
@@ -11,6 +25,8 @@ pub struct PointVertex {
     pub position: [f32; 3],
     pub color: [f32; 3],
     pub intensity: f32,
+    // Zero until estimated
+    pub normal: [f32; 3],
 }
 
 impl Vertex for PointVertex {
@@ -34,32 +50,243 @@ impl Vertex for PointVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32,
                 },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
             ],
         }
     }
 }
 
-pub struct PointcloudBuffer(Vec<PointVertex>);
+// What LAS stores per point beyond what's drawn, kept on the CPU for inspecting and exporting points
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable)]
+pub struct PointAttributes {
+    // Seconds, NaN for formats without it
+    pub gps_time: f64,
+    pub classification: u8,
+    pub return_number: u8,
+    pub number_of_returns: u8,
+    _padding: [u8; 5],
+}
+
+impl Default for PointAttributes {
+    fn default() -> Self {
+        Self {
+            gps_time: f64::NAN,
+            ..Zeroable::zeroed()
+        }
+    }
+}
+
+impl PointAttributes {
+    pub fn new(gps_time: Option<f64>, classification: u8, return_number: u8, number_of_returns: u8) -> Self {
+        Self {
+            gps_time: gps_time.unwrap_or(f64::NAN),
+            classification,
+            return_number,
+            number_of_returns,
+            _padding: [0; 5],
+        }
+    }
+
+    pub fn gps_time(&self) -> Option<f64> {
+        (!self.gps_time.is_nan()).then_some(self.gps_time)
+    }
+}
+
+// ASPRS class per point, in its own vertex buffer so edits upload four bytes a point
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct PointClassification(u32);
+
+impl Vertex for PointClassification {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<PointClassification>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Uint32,
+            }],
+        }
+    }
+}
+
+pub struct PointcloudBuffer {
+    points: Vec<PointVertex>,
+    // Classes can be edited after loading, while picking and exporting read them from other threads
+    attributes: RwLock<Vec<PointAttributes>>,
+    // Subtracted from the source coordinates so the positions fit in f32
+    origin: glam::DVec3,
+}
+
+impl std::fmt::Debug for PointcloudBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PointcloudBuffer")
+            .field("points", &self.points.len())
+            .field("origin", &self.origin)
+            .finish()
+    }
+}
+
+// The attributes are copied under the read lock
+impl Clone for PointcloudBuffer {
+    fn clone(&self) -> Self {
+        Self {
+            points: self.points.clone(),
+            attributes: RwLock::new(self.attributes().clone()),
+            origin: self.origin,
+        }
+    }
+}
 
 impl PointcloudBuffer {
     pub fn new(points: Vec<PointVertex>) -> Self {
-        Self(points)
+        Self {
+            attributes: RwLock::new(vec![PointAttributes::default(); points.len()]),
+            points,
+            origin: glam::DVec3::ZERO,
+        }
     }
 
     pub fn points(&self) -> &[PointVertex] {
-        &self.0
+        &self.points
     }
 
-    pub fn from_las(data: Vec<u8>) -> anyhow::Result<Self> {
-        // let data = path.load_binary().await?;
+    pub fn attributes(&self) -> RwLockReadGuard<'_, Vec<PointAttributes>> {
+        self.attributes.read().unwrap()
+    }
+
+    // Changes the class of each point by index, returns the classes they had before
+    pub fn classify(&self, changes: &[(u32, u8)]) -> Vec<(u32, u8)> {
+        let mut attributes = self.attributes.write().unwrap();
+        changes
+            .iter()
+            .filter_map(|&(index, classification)| {
+                let attributes = attributes.get_mut(index as usize)?;
+                let previous = std::mem::replace(&mut attributes.classification, classification);
+                Some((index, previous))
+            })
+            .collect()
+    }
+
+    pub fn origin(&self) -> glam::DVec3 {
+        self.origin
+    }
+
+    // A copy colored by one value per point, which also goes in the intensity channel for scalar shading. Values are
+    // expected between zero and one
+    pub fn with_scalars(&self, values: &[f32], color: impl Fn(f32) -> [f32; 3]) -> Self {
+        let mut buffer = self.clone();
+        for (point, &value) in buffer.points.iter_mut().zip(values) {
+            point.color = color(value);
+            point.intensity = value;
+        }
+        buffer
+    }
+
+    // The origin followed by the points and then their attributes, for handing clouds back from web workers
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = bytemuck::bytes_of(&self.origin.to_array()).to_vec();
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.points));
+        bytes.extend_from_slice(bytemuck::cast_slice(&self.attributes()));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        const ORIGIN_SIZE: usize = std::mem::size_of::<[f64; 3]>();
+        let stride = std::mem::size_of::<PointVertex>() + std::mem::size_of::<PointAttributes>();
+        let count = bytes.len().saturating_sub(ORIGIN_SIZE) / stride;
+        let (origin, rest) = bytes.split_at(ORIGIN_SIZE);
+        let (points, attributes) = rest.split_at(count * std::mem::size_of::<PointVertex>());
+
+        Self {
+            points: bytemuck::pod_collect_to_vec(points),
+            attributes: RwLock::new(bytemuck::pod_collect_to_vec(
+                &attributes[..count * std::mem::size_of::<PointAttributes>()],
+            )),
+            origin: glam::DVec3::from_array(bytemuck::pod_read_unaligned(origin)),
+        }
+    }
+
+    pub fn from_file(file_name: &str, data: Vec<u8>) -> anyhow::Result<Self> {
+        Self::from_file_streamed(file_name, data, |_, _, _| ControlFlow::Continue(()))
+    }
+
+    // CSV and GeoJSON are read in one go, see from_las for `on_chunk`
+    pub fn from_file_streamed(
+        file_name: &str,
+        data: Vec<u8>,
+        on_chunk: impl FnMut(Arc<Self>, u64, u64) -> ControlFlow<()>,
+    ) -> anyhow::Result<Self> {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("csv") => Self::from_csv(&String::from_utf8(data)?),
+            Some("geojson") => Self::from_geojson(&data),
+            _ => Self::from_las(data, on_chunk),
+        }
+    }
+
+    // World bounds of the loaded cloud from the LAS header alone, CSV and GeoJSON have to be read in full
+    pub fn estimate_bounds(file_name: &str, data: &[u8]) -> Option<Aabb> {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase());
+        if matches!(extension.as_deref(), Some("csv" | "geojson")) {
+            return None;
+        }
+
+        let header = las::raw::Header::read_from(data).ok()?;
+        let extent = glam::DVec3::new(
+            header.max_x - header.min_x,
+            header.max_y - header.min_y,
+            header.max_z - header.min_z,
+        );
+        let local = Aabb {
+            min: glam::Vec3::ZERO,
+            max: extent.as_vec3(),
+        };
+        Some(local.transform(MAT4_SWAP_YZ))
+    }
+
+    // Files of more than one chunk call `on_chunk` with every chunk as it's read, along with the points read so far and
+    // in total, and stop reading once it breaks. LAZ is decompressed a chunk at a time as well. Streamed chunks get
+    // normals of their own and are shuffled, the cloud returned estimates them again from all of its points
+    pub fn from_las(
+        data: Vec<u8>,
+        mut on_chunk: impl FnMut(Arc<Self>, u64, u64) -> ControlFlow<()>,
+    ) -> anyhow::Result<Self> {
+        crate::profile_scope!("Parse LAS");
         let cursor = Cursor::new(data);
         let mut reader = las::Reader::new(cursor)?;
 
         let min_bounds = reader.header().bounds().min;
-        let points: Vec<PointVertex> = reader
-            .points()
-            .map(|p| -> anyhow::Result<_> {
-                let point = p?;
+        let total = reader.header().number_of_points();
+        let origin = glam::DVec3::new(min_bounds.x, min_bounds.y, min_bounds.z);
+
+        // Shared with `on_chunk` rather than copied, so the points are held once while the renderer catches up
+        let mut parts = Vec::new();
+        let mut read = 0;
+        let mut chunk = Vec::new();
+        loop {
+            chunk.clear();
+            if reader.read_points_into(LAS_CHUNK_POINTS, &mut chunk)? == 0 {
+                break;
+            }
+
+            let mut part = Self {
+                points: Vec::with_capacity(chunk.len()),
+                attributes: RwLock::new(Vec::with_capacity(chunk.len())),
+                origin,
+            };
+            let attributes = part.attributes.get_mut().unwrap();
+            for point in &chunk {
                 let [x, y, z] = [
                     (point.x - min_bounds.x) as f32,
                     (point.y - min_bounds.y) as f32,
@@ -78,52 +305,603 @@ impl PointcloudBuffer {
                     .unwrap_or([1.0, 1.0, 1.0]);
 
                 let intensity = point.intensity as f32 / u16::MAX as f32;
+                attributes.push(PointAttributes::new(
+                    point.gps_time,
+                    u8::from(point.classification),
+                    point.return_number,
+                    point.number_of_returns,
+                ));
 
-                Ok(PointVertex {
+                part.points.push(PointVertex {
                     position: [x, y, z],
                     color: [r, g, b],
                     intensity,
+                    normal: [0.0; 3],
+                });
+            }
+
+            read += chunk.len() as u64;
+            if total <= LAS_CHUNK_POINTS {
+                parts.push(Arc::new(part));
+                continue;
+            }
+
+            part.estimate_normals();
+            part.shuffle();
+            let part = Arc::new(part);
+            if on_chunk(Arc::clone(&part), read, total).is_break() {
+                anyhow::bail!("Cancelled after {} of {} points", read, total);
+            }
+            parts.push(part);
+        }
+
+        // A part is moved rather than copied once the renderer let go of it
+        let mut buffer = Self {
+            points: Vec::with_capacity(read as usize),
+            attributes: RwLock::new(Vec::with_capacity(read as usize)),
+            origin,
+        };
+        for part in parts {
+            let part = Arc::unwrap_or_clone(part);
+            buffer.points.extend(part.points);
+            buffer
+                .attributes
+                .get_mut()
+                .unwrap()
+                .extend(part.attributes.into_inner().unwrap());
+        }
+
+        // LAS has no normals, estimating them here keeps the work on the loader thread or worker
+        buffer.estimate_normals();
+        buffer.shuffle();
+        Ok(buffer)
+    }
+
+    // Writes the points `keep` accepts, by index and local position. The origin becomes the header offset so
+    // positions land back in the source's coordinate system, at millimetre precision
+    pub fn to_las(&self, compressed: bool, keep: impl Fn(usize, glam::Vec3) -> bool) -> anyhow::Result<Vec<u8>> {
+        crate::profile_scope!("Write LAS");
+        let all_attributes = self.attributes();
+        let has_gps_time = all_attributes.iter().any(|attributes| attributes.gps_time().is_some());
+
+        let mut builder = las::Builder::from((1, 2));
+        builder.point_format = las::point::Format::new(if has_gps_time { 3 } else { 2 })?;
+        builder.point_format.is_compressed = compressed;
+        let transform = |offset| las::Transform { scale: 0.001, offset };
+        builder.transforms = las::Vector {
+            x: transform(self.origin.x),
+            y: transform(self.origin.y),
+            z: transform(self.origin.z),
+        };
+
+        let mut writer = las::Writer::new(Cursor::new(Vec::new()), builder.into_header()?)?;
+        let to_u16 = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+        for (index, (point, attributes)) in self.points.iter().zip(all_attributes.iter()).enumerate() {
+            let position = glam::Vec3::from_array(point.position);
+            if !keep(index, position) {
+                continue;
+            }
+
+            let position = self.origin + position.as_dvec3();
+            let [red, green, blue] = point.color.map(to_u16);
+            // Overlap is a flag rather than a class from LAS 1.4 on, the writer turns it back into class 12
+            let is_overlap = attributes.classification == 12;
+            let classification = if is_overlap {
+                las::point::Classification::Unclassified
+            } else {
+                las::point::Classification::new(attributes.classification)?
+            };
+
+            writer.write_point(las::Point {
+                x: position.x,
+                y: position.y,
+                z: position.z,
+                intensity: to_u16(point.intensity),
+                return_number: attributes.return_number,
+                number_of_returns: attributes.number_of_returns,
+                classification,
+                is_overlap,
+                gps_time: has_gps_time.then(|| attributes.gps_time().unwrap_or_default()),
+                color: Some(las::Color { red, green, blue }),
+                ..Default::default()
+            })?;
+        }
+
+        Ok(writer.into_inner()?.into_inner())
+    }
+
+    // x, y, z and an optional scalar column. A header row picks the columns by name, otherwise they are taken in order.
+    // Longitude and latitude columns are projected to meters like GeoJSON
+    pub fn from_csv(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .peekable();
+        let delimiter = match lines.peek() {
+            Some(line) if line.contains(',') => ',',
+            Some(line) if line.contains(';') => ';',
+            Some(line) if line.contains('\t') => '\t',
+            _ => ' ',
+        };
+
+        fn split(line: &str, delimiter: char) -> Vec<&str> {
+            line.split(delimiter)
+                .map(|field| field.trim().trim_matches('"'))
+                .filter(|field| delimiter != ' ' || !field.is_empty())
+                .collect()
+        }
+
+        let mut columns = [0, 1, 2];
+        let mut scalar_column = Some(3);
+        let mut is_geographic = false;
+        let header = lines.next_if(|line| split(line, delimiter).iter().any(|field| field.parse::<f64>().is_err()));
+        if let Some(header) = header {
+            let header = split(header, delimiter)
+                .into_iter()
+                .map(str::to_ascii_lowercase)
+                .collect::<Vec<_>>();
+            let find = |names: &[&str]| header.iter().position(|field| names.contains(&field.as_str()));
+
+            columns = [
+                find(&["x", "lon", "lng", "longitude", "easting"]).unwrap_or(0),
+                find(&["y", "lat", "latitude", "northing"]).unwrap_or(1),
+                find(&["z", "elevation", "height", "alt", "altitude"]).unwrap_or(2),
+            ];
+            scalar_column = find(&["value", "scalar", "intensity", "w"])
+                .or_else(|| (0..header.len()).find(|column| !columns.contains(column)));
+            is_geographic = find(&["lon", "lng", "longitude"]).is_some() && find(&["lat", "latitude"]).is_some();
+        }
+
+        let mut positions = Vec::new();
+        let mut scalars = Vec::new();
+        for (row, line) in lines.enumerate() {
+            let fields = split(line, delimiter);
+            let field = |column: usize| fields.get(column).and_then(|field| field.parse::<f64>().ok());
+            let [Some(x), Some(y), z] = columns.map(field) else {
+                anyhow::bail!("Row {} has no x and y coordinates", row + 1);
+            };
+
+            positions.push(glam::DVec3::new(x, y, z.unwrap_or(0.0)));
+            scalars.push(scalar_column.and_then(field).map(|scalar| scalar as f32));
+        }
+
+        if positions.is_empty() {
+            anyhow::bail!("CSV contains no points");
+        }
+        if is_geographic {
+            positions = Self::project_degrees(positions);
+        }
+
+        Ok(Self::from_scalar_points(positions, scalars))
+    }
+
+    // Point and MultiPoint features, projected onto a local tangent plane in meters around the first point
+    pub fn from_geojson(data: &[u8]) -> anyhow::Result<Self> {
+        let json: serde_json::Value = serde_json::from_slice(data)?;
+        let kind = json["type"].as_str().unwrap_or_default().to_string();
+        let features = match kind.as_str() {
+            "FeatureCollection" => json["features"].as_array().cloned().unwrap_or_default(),
+            "Feature" => vec![json],
+            _ => anyhow::bail!("Expected a GeoJSON Feature or FeatureCollection"),
+        };
+
+        let mut coordinates = Vec::new();
+        let mut scalars = Vec::new();
+        for feature in &features {
+            let geometry = &feature["geometry"];
+            let points = match geometry["type"].as_str() {
+                Some("Point") => vec![&geometry["coordinates"]],
+                Some("MultiPoint") => geometry["coordinates"].as_array().into_iter().flatten().collect(),
+                _ => continue,
+            };
+
+            // A property called value wins, otherwise the first numeric one
+            let properties = feature["properties"].as_object();
+            let scalar = properties
+                .and_then(|properties| {
+                    properties
+                        .get("value")
+                        .and_then(serde_json::Value::as_f64)
+                        .or_else(|| properties.values().find_map(serde_json::Value::as_f64))
                 })
+                .map(|scalar| scalar as f32);
+
+            for point in points {
+                let Some(position) = point.as_array() else {
+                    continue;
+                };
+                let component = |index: usize| position.get(index).and_then(serde_json::Value::as_f64);
+                if let (Some(lon), Some(lat)) = (component(0), component(1)) {
+                    coordinates.push(glam::DVec3::new(lon, lat, component(2).unwrap_or(0.0)));
+                    scalars.push(scalar);
+                }
+            }
+        }
+
+        if coordinates.is_empty() {
+            anyhow::bail!("GeoJSON contains no point features");
+        }
+
+        Ok(Self::from_scalar_points(Self::project_degrees(coordinates), scalars))
+    }
+
+    // Longitude, latitude and height onto a local tangent plane in meters around the first point
+    fn project_degrees(coordinates: Vec<glam::DVec3>) -> Vec<glam::DVec3> {
+        const METERS_PER_DEGREE: f64 = 111_320.0;
+        let Some(origin) = coordinates.first().copied() else {
+            return coordinates;
+        };
+
+        let scale = glam::DVec3::new(METERS_PER_DEGREE * origin.y.to_radians().cos(), METERS_PER_DEGREE, 1.0);
+        coordinates
+            .into_iter()
+            .map(|coordinate| (coordinate - origin.with_z(0.0)) * scale)
+            .collect()
+    }
+
+    // Offsets to the minimum bounds like LAS and colors the normalized scalar, kept in the intensity channel
+    fn from_scalar_points(positions: Vec<glam::DVec3>, scalars: Vec<Option<f32>>) -> Self {
+        let min_bounds = positions
+            .iter()
+            .fold(glam::DVec3::splat(f64::MAX), |min, position| min.min(*position));
+        let (min, max) = scalars
+            .iter()
+            .flatten()
+            .fold((f32::MAX, f32::MIN), |(min, max), &scalar| {
+                (min.min(scalar), max.max(scalar))
+            });
+        let range = (max - min).max(f32::EPSILON);
+
+        let points = positions
+            .into_iter()
+            .zip(scalars)
+            .map(|(position, scalar)| {
+                let value = scalar.map(|scalar| (scalar - min) / range);
+                PointVertex {
+                    position: (position - min_bounds).as_vec3().to_array(),
+                    color: value.map_or([1.0; 3], |value| ColorRamp::Viridis.sample(value, &[])),
+                    intensity: value.unwrap_or(1.0),
+                    normal: [0.0; 3],
+                }
             })
-            .collect::<anyhow::Result<_>>()?;
+            .collect();
+
+        let mut buffer = Self::new(points);
+        buffer.origin = min_bounds;
+        buffer.estimate_normals();
+        buffer.shuffle();
+        buffer
+    }
+
+    // Scans are stored in acquisition order, shuffled any contiguous range is an even sample of the whole cloud. That's
+    // what lets the renderer draw a slice per frame while the view changes. Seeded, so a file always loads the same way
+    pub fn shuffle(&mut self) {
+        crate::profile_scope!("Shuffle points");
+        let attributes = self.attributes.get_mut().unwrap();
+        let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+        for index in (1..self.points.len()).rev() {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let other = (state % (index as u64 + 1)) as usize;
+            self.points.swap(index, other);
+            attributes.swap(index, other);
+        }
+    }
+
+    // Normal of the plane fitted through the k nearest neighbours of every point, oriented up (+Z in LAS space)
+    pub fn estimate_normals(&mut self) {
+        crate::profile_scope!("Estimate normals");
+        const NEIGHBOURS: usize = 16;
+
+        if self.points.len() < 3 {
+            return;
+        }
 
-        Ok(Self(points))
+        let positions = self
+            .points
+            .iter()
+            .map(|point| glam::Vec3::from_array(point.position))
+            .collect::<Vec<_>>();
+        let grid = PointGrid::new(&positions, NEIGHBOURS);
+
+        let estimate = |index: usize| {
+            let neighbours = grid.nearest(&positions, positions[index], NEIGHBOURS);
+            let normal = fit_plane_normal(neighbours.iter().map(|&neighbour| positions[neighbour as usize]));
+            if normal.z < 0.0 { -normal } else { normal }
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+            let chunk_size = self.points.len().div_ceil(threads);
+            std::thread::scope(|scope| {
+                for (chunk_index, chunk) in self.points.chunks_mut(chunk_size).enumerate() {
+                    let estimate = &estimate;
+                    scope.spawn(move || {
+                        for (offset, point) in chunk.iter_mut().enumerate() {
+                            point.normal = estimate(chunk_index * chunk_size + offset).to_array();
+                        }
+                    });
+                }
+            });
+        }
+
+        #[cfg(target_family = "wasm")]
+        for (index, point) in self.points.iter_mut().enumerate() {
+            point.normal = estimate(index).to_array();
+        }
     }
 }
 
+// Uniform grid over point positions, for approximate nearest neighbour searches
+pub(super) struct PointGrid {
+    cell_size: f32,
+    min: glam::Vec3,
+    cells: HashMap<glam::IVec3, Vec<u32>>,
+}
+
+impl PointGrid {
+    // Sized so a cell holds roughly `per_cell` points, assuming the points cover a surface
+    fn new(positions: &[glam::Vec3], per_cell: usize) -> Self {
+        let (min, max) = bounds(positions);
+
+        // Area of the two largest extents, scans are closer to 2.5D than to a filled volume
+        let extent = (max - min).max(glam::Vec3::splat(1e-3));
+        let area = extent.x * extent.y * extent.z / extent.min_element();
+        let cell_size = (area * per_cell as f32 / positions.len() as f32).sqrt().max(1e-3);
+
+        Self::with_cell_size(positions, cell_size)
+    }
+
+    // Neighbours further than one cell away are never found, so the cell size doubles as a search radius
+    pub(super) fn with_cell_size(positions: &[glam::Vec3], cell_size: f32) -> Self {
+        let mut grid = Self {
+            cell_size: cell_size.max(1e-3),
+            min: bounds(positions).0,
+            cells: HashMap::new(),
+        };
+
+        for (index, position) in positions.iter().enumerate() {
+            grid.cells.entry(grid.cell(*position)).or_default().push(index as u32);
+        }
+
+        grid
+    }
+
+    fn cell(&self, position: glam::Vec3) -> glam::IVec3 {
+        ((position - self.min) / self.cell_size).floor().as_ivec3()
+    }
+
+    // Approximate, only searches the surrounding 3x3x3 cells
+    pub(super) fn nearest(&self, positions: &[glam::Vec3], position: glam::Vec3, count: usize) -> Vec<u32> {
+        let center = self.cell(position);
+        let mut candidates = Vec::new();
+        for z in -1..=1 {
+            for y in -1..=1 {
+                for x in -1..=1 {
+                    if let Some(cell) = self.cells.get(&(center + glam::IVec3::new(x, y, z))) {
+                        candidates.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+
+        let distance = |index: &u32| positions[*index as usize].distance_squared(position);
+        if candidates.len() > count {
+            candidates.select_nth_unstable_by(count, |a, b| distance(a).total_cmp(&distance(b)));
+            candidates.truncate(count);
+        }
+
+        candidates
+    }
+}
+
+fn bounds(positions: &[glam::Vec3]) -> (glam::Vec3, glam::Vec3) {
+    positions.iter().fold(
+        (glam::Vec3::splat(f32::MAX), glam::Vec3::splat(f32::MIN)),
+        |(min, max), position| (min.min(*position), max.max(*position)),
+    )
+}
+
+// Eigenvector of the smallest eigenvalue of the neighbourhood covariance, zero for degenerate neighbourhoods
+fn fit_plane_normal(points: impl Iterator<Item = glam::Vec3> + Clone) -> glam::Vec3 {
+    let count = points.clone().count();
+    if count < 3 {
+        return glam::Vec3::ZERO;
+    }
+
+    let centroid = points.clone().sum::<glam::Vec3>() / count as f32;
+    let [mut xx, mut xy, mut xz, mut yy, mut yz, mut zz] = [0.0; 6];
+    for point in points {
+        let d = point - centroid;
+        xx += d.x * d.x;
+        xy += d.x * d.y;
+        xz += d.x * d.z;
+        yy += d.y * d.y;
+        yz += d.y * d.z;
+        zz += d.z * d.z;
+    }
+
+    // Closed form eigenvalues of a symmetric 3x3 matrix
+    let off_diagonal = xy * xy + xz * xz + yz * yz;
+    let smallest = if off_diagonal <= f32::EPSILON {
+        xx.min(yy).min(zz)
+    } else {
+        let q = (xx + yy + zz) / 3.0;
+        let p = (((xx - q).powi(2) + (yy - q).powi(2) + (zz - q).powi(2) + 2.0 * off_diagonal) / 6.0).sqrt();
+        let b = glam::Mat3::from_cols_array(&[xx - q, xy, xz, xy, yy - q, yz, xz, yz, zz - q]) / p;
+        let phi = (b.determinant() / 2.0).clamp(-1.0, 1.0).acos() / 3.0;
+        q + 2.0 * p * (phi + 2.0 * std::f32::consts::FRAC_PI_3).cos()
+    };
+
+    // The eigenvector is orthogonal to the rows of (C - λI), take the most stable cross product
+    let rows = [
+        glam::Vec3::new(xx - smallest, xy, xz),
+        glam::Vec3::new(xy, yy - smallest, yz),
+        glam::Vec3::new(xz, yz, zz - smallest),
+    ];
+    [rows[0].cross(rows[1]), rows[0].cross(rows[2]), rows[1].cross(rows[2])]
+        .into_iter()
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .map(|normal| normal.normalize_or_zero())
+        .unwrap_or(glam::Vec3::ZERO)
+}
+
 #[derive(Clone, Debug)]
 pub struct Pointcloud {
     pub label: Option<String>,
     pub vertex_buffer: wgpu::Buffer,
+    pub classification_buffer: wgpu::Buffer,
     pub num_points: u32,
+    // CPU copy for picking and exporting
+    pub buffer: Arc<PointcloudBuffer>,
+    _allocation: Allocation,
     // pub transform: [[f32; 4]; 4],
     // pub transform_buffer: wgpu::Buffer,
 }
 
 impl Pointcloud {
     pub fn from_buffer(buffer: PointcloudBuffer, context: &RenderContext, label: Option<String>) -> Self {
+        let capacity = buffer.points().len() as u32;
+        Self::with_capacity(Arc::new(buffer), capacity, context, label)
+    }
+
+    // Room for `capacity` points on the GPU, of which the ones of `buffer` are drawn until more are appended
+    pub fn with_capacity(
+        buffer: Arc<PointcloudBuffer>,
+        capacity: u32,
+        context: &RenderContext,
+        label: Option<String>,
+    ) -> Self {
         let num_points = buffer.points().len() as u32;
-        let vertex_buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        let capacity = capacity.max(num_points) as u64;
+        let vertex_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
             label: label.as_deref(),
-            contents: bytemuck::cast_slice(buffer.points()),
-            usage: wgpu::BufferUsages::VERTEX,
+            size: capacity * std::mem::size_of::<PointVertex>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
         });
+        context
+            .queue
+            .write_buffer(&vertex_buffer, 0, bytemuck::cast_slice(buffer.points()));
 
+        let classification_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Point classification buffer"),
+            size: capacity * std::mem::size_of::<PointClassification>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        context.queue.write_buffer(
+            &classification_buffer,
+            0,
+            bytemuck::cast_slice(&Self::classifications(&buffer.attributes())),
+        );
+
+        let allocation = context.memory.track_buffers([&vertex_buffer, &classification_buffer]);
         Self {
             label,
             vertex_buffer,
+            classification_buffer,
             num_points,
+            buffer,
+            _allocation: allocation,
         }
     }
+
+    // Uploads the points behind the ones drawn so far, false when they don't fit. Only the GPU buffers grow, picking and
+    // exporting keep seeing the points the cloud was created with
+    pub fn append(&mut self, buffer: &PointcloudBuffer, context: &RenderContext) -> bool {
+        let count = buffer.points().len() as u64;
+        let capacity = self.vertex_buffer.size() / std::mem::size_of::<PointVertex>() as u64;
+        if self.num_points as u64 + count > capacity {
+            return false;
+        }
+
+        context.queue.write_buffer(
+            &self.vertex_buffer,
+            self.num_points as u64 * std::mem::size_of::<PointVertex>() as u64,
+            bytemuck::cast_slice(buffer.points()),
+        );
+        context.queue.write_buffer(
+            &self.classification_buffer,
+            self.num_points as u64 * std::mem::size_of::<PointClassification>() as u64,
+            bytemuck::cast_slice(&Self::classifications(&buffer.attributes())),
+        );
+        self.num_points += count as u32;
+        true
+    }
+
+    fn classifications(attributes: &[PointAttributes]) -> Vec<PointClassification> {
+        attributes
+            .iter()
+            .map(|attributes| PointClassification(attributes.classification as u32))
+            .collect()
+    }
+
+    // Updates the CPU copy and uploads the changed classes. Shuffled points are spread over the whole buffer, so
+    // only indices close together are uploaded as one range
+    pub fn classify(&self, changes: &[(u32, u8)], context: &RenderContext) {
+        const MAX_GAP: u32 = 64;
+
+        self.buffer.classify(changes);
+        let mut indices = changes
+            .iter()
+            .map(|(index, _)| *index)
+            .filter(|index| (*index as usize) < self.buffer.points().len())
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let attributes = self.buffer.attributes();
+        let upload = |range: Range<u32>| {
+            let classifications = attributes[range.start as usize..range.end as usize]
+                .iter()
+                .map(|attributes| PointClassification(attributes.classification as u32))
+                .collect::<Vec<_>>();
+            let offset = range.start as u64 * std::mem::size_of::<PointClassification>() as u64;
+            context.queue.write_buffer(
+                &self.classification_buffer,
+                offset,
+                bytemuck::cast_slice(&classifications),
+            );
+        };
+
+        let Some(&first) = indices.first() else {
+            return;
+        };
+        let mut range = first..first + 1;
+        for &index in &indices[1..] {
+            if index - range.end > MAX_GAP {
+                upload(range.clone());
+                range.start = index;
+            }
+            range.end = index + 1;
+        }
+        upload(range);
+    }
+
+    // Points of the given slice when the cloud is drawn `budget` points at a time, none once they've all been drawn
+    pub fn chunk(&self, index: u32, budget: u32) -> Range<u32> {
+        let start = index.saturating_mul(budget).min(self.num_points);
+        start..start.saturating_add(budget).min(self.num_points)
+    }
 }
 
 pub trait DrawPointcloud<'a> {
-    fn draw_pointcloud(&mut self, pointcloud: &'a Pointcloud, instances: Range<u32>);
+    fn draw_pointcloud(&mut self, pointcloud: &'a Pointcloud, points: Range<u32>, instances: Range<u32>);
 }
 
 impl<'a, 'b> DrawPointcloud<'b> for wgpu::RenderPass<'a> {
-    fn draw_pointcloud(&mut self, pointcloud: &'b Pointcloud, instances: Range<u32>) {
+    fn draw_pointcloud(&mut self, pointcloud: &'b Pointcloud, points: Range<u32>, instances: Range<u32>) {
+        if points.is_empty() {
+            return;
+        }
+
         self.set_vertex_buffer(0, pointcloud.vertex_buffer.slice(..));
-        self.draw(0..pointcloud.num_points, instances);
+        self.set_vertex_buffer(1, pointcloud.classification_buffer.slice(..));
+        self.draw(points, instances);
     }
 }
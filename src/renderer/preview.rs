@@ -0,0 +1,98 @@
+use egui_wgpu::Renderer as EguiRenderer;
+
+use crate::renderer::{context::RenderContext, settings::SettingsBuffer, viewport::Viewport};
+
+pub const PREVIEW_SIZE: [u32; 2] = [320, 180];
+
+// Small offscreen view of the scene from another point of view, drawn by egui as an image
+pub struct Preview {
+    viewport: Option<Viewport>,
+    view: wgpu::TextureView,
+    texture_id: egui::TextureId,
+    interval: u32,
+    frame: u32,
+}
+
+impl Preview {
+    pub fn new(interval: u32, settings: &SettingsBuffer, context: &RenderContext, egui: &mut EguiRenderer) -> Self {
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            format,
+            width: PREVIEW_SIZE[0],
+            height: PREVIEW_SIZE[1],
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![format.add_srgb_suffix()],
+            desired_maximum_frame_latency: 1,
+        };
+
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Preview texture"),
+            size: wgpu::Extent3d {
+                width: PREVIEW_SIZE[0],
+                height: PREVIEW_SIZE[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: config.usage,
+            view_formats: &[format.add_srgb_suffix()],
+        });
+        // egui expects sRGB encoded textures
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(format.add_srgb_suffix()),
+            ..Default::default()
+        });
+        let texture_id = egui.register_native_texture(&context.device, &view, wgpu::FilterMode::Linear);
+
+        Self {
+            viewport: Some(Viewport::new(config, settings, context)),
+            view,
+            texture_id,
+            interval: interval.max(1),
+            frame: 0,
+        }
+    }
+
+    pub fn texture_id(&self) -> egui::TextureId {
+        self.texture_id
+    }
+
+    pub fn set_interval(&mut self, interval: u32) {
+        self.interval = interval.max(1);
+    }
+
+    pub fn update_camera(
+        &mut self,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+        context: &RenderContext,
+    ) {
+        if let Some(viewport) = &mut self.viewport {
+            viewport.update_camera(position, view, projection, context);
+        }
+    }
+
+    // Hands out the viewport every `interval` frames, `restore` puts it back afterwards
+    pub fn take_due(&mut self) -> Option<(Viewport, wgpu::TextureView)> {
+        let is_due = self.frame % self.interval == 0;
+        self.frame = self.frame.wrapping_add(1);
+        if !is_due {
+            return None;
+        }
+
+        self.viewport.take().map(|viewport| (viewport, self.view.clone()))
+    }
+
+    pub fn restore(&mut self, viewport: Viewport) {
+        self.viewport = Some(viewport);
+    }
+
+    pub fn free(self, egui: &mut EguiRenderer) {
+        egui.free_texture(&self.texture_id);
+    }
+}
@@ -0,0 +1,341 @@
+use uuid::Uuid;
+use wgpu::util::DeviceExt;
+
+use crate::renderer::context::RenderContext;
+
+pub const MAX_PROBES: usize = 4;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProbeUniform {
+    // Position and radius, a zero radius marks an empty slot
+    probes: [[f32; 4]; MAX_PROBES],
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Probe {
+    id: Uuid,
+    position: glam::Vec3,
+    radius: f32,
+}
+
+// Cubemaps captured from fixed points in the scene. Surfaces within a probe's radius reflect its capture instead of the
+// global environment, fading back to the environment towards the edge
+pub struct ReflectionProbes {
+    slots: [Option<Probe>; MAX_PROBES],
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    face_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    blit_sampler: wgpu::Sampler,
+}
+
+impl ReflectionProbes {
+    pub const SIZE: u32 = 128;
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    const MIP_LEVELS: u32 = Self::SIZE.ilog2() + 1;
+
+    pub fn new(context: &RenderContext) -> Self {
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Reflection probe texture"),
+            size: wgpu::Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: 6 * MAX_PROBES as u32,
+            },
+            mip_level_count: Self::MIP_LEVELS,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection probe view"),
+            dimension: Some(wgpu::TextureViewDimension::CubeArray),
+            ..Default::default()
+        });
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reflection probe sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let blit_sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Reflection probe blit sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reflection probe buffer"),
+            contents: bytemuck::bytes_of(&ProbeUniform {
+                probes: [[0.0; 4]; MAX_PROBES],
+            }),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Reflection probe blit layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Reflection probe blit shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/probe_blit.wgsl").into()),
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Reflection probe blit pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label, entry_point| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::FORMAT,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let face_pipeline = create_pipeline("Reflection probe face pipeline", "fs_face");
+        let downsample_pipeline = create_pipeline("Reflection probe downsample pipeline", "fs_downsample");
+
+        Self {
+            slots: [None; MAX_PROBES],
+            texture,
+            view,
+            sampler,
+            buffer,
+            face_pipeline,
+            downsample_pipeline,
+            layout,
+            blit_sampler,
+        }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn position(&self, id: &Uuid) -> Option<glam::Vec3> {
+        self.find(id).map(|slot| self.slots[slot].unwrap().position)
+    }
+
+    pub fn ids(&self) -> Vec<Uuid> {
+        self.slots.iter().flatten().map(|probe| probe.id).collect()
+    }
+
+    // Moves an existing probe or takes a free slot, false when all slots are in use
+    pub fn place(&mut self, id: Uuid, position: glam::Vec3, radius: f32) -> bool {
+        let Some(slot) = self.find(&id).or_else(|| self.slots.iter().position(Option::is_none)) else {
+            return false;
+        };
+
+        self.slots[slot] = Some(Probe { id, position, radius });
+        true
+    }
+
+    pub fn remove(&mut self, id: &Uuid, context: &RenderContext) {
+        if let Some(slot) = self.find(id) {
+            self.slots[slot] = None;
+            self.upload(true, context);
+        }
+    }
+
+    // Disabled while capturing, so probes don't end up reflecting each other's stale contents
+    pub fn upload(&self, is_enabled: bool, context: &RenderContext) {
+        let mut uniform = ProbeUniform {
+            probes: [[0.0; 4]; MAX_PROBES],
+        };
+
+        if is_enabled {
+            for (target, probe) in uniform.probes.iter_mut().zip(&self.slots) {
+                if let Some(probe) = probe {
+                    *target = probe.position.extend(probe.radius).to_array();
+                }
+            }
+        }
+
+        context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+
+    pub fn capture_config(format: wgpu::TextureFormat) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: Self::SIZE,
+            height: Self::SIZE,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 1,
+        }
+    }
+
+    pub fn projection() -> glam::Mat4 {
+        glam::Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, 0.05, 500.0)
+    }
+
+    // Cubemap face order with its conventional up vectors
+    pub fn face_views(position: glam::Vec3) -> [glam::Mat4; 6] {
+        [
+            (glam::Vec3::X, glam::Vec3::NEG_Y),
+            (glam::Vec3::NEG_X, glam::Vec3::NEG_Y),
+            (glam::Vec3::Y, glam::Vec3::Z),
+            (glam::Vec3::NEG_Y, glam::Vec3::NEG_Z),
+            (glam::Vec3::Z, glam::Vec3::NEG_Y),
+            (glam::Vec3::NEG_Z, glam::Vec3::NEG_Y),
+        ]
+        .map(|(direction, up)| glam::Mat4::look_to_rh(position, direction, up))
+    }
+
+    pub fn store_face(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        id: &Uuid,
+        face: u32,
+        source: &wgpu::TextureView,
+        context: &RenderContext,
+    ) {
+        if let Some(slot) = self.find(id) {
+            let layer = slot as u32 * 6 + face;
+            self.blit(encoder, &self.face_pipeline, source, layer, 0, context);
+        }
+    }
+
+    pub fn generate_mips(&self, encoder: &mut wgpu::CommandEncoder, id: &Uuid, context: &RenderContext) {
+        let Some(slot) = self.find(id) else {
+            return;
+        };
+
+        for layer in slot as u32 * 6..slot as u32 * 6 + 6 {
+            for mip_level in 1..Self::MIP_LEVELS {
+                let source = self.face_view(layer, mip_level - 1);
+                self.blit(encoder, &self.downsample_pipeline, &source, layer, mip_level, context);
+            }
+        }
+    }
+
+    fn blit(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pipeline: &wgpu::RenderPipeline,
+        source: &wgpu::TextureView,
+        layer: u32,
+        mip_level: u32,
+        context: &RenderContext,
+    ) {
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Reflection probe blit bind group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.blit_sampler),
+                },
+            ],
+        });
+
+        let target = self.face_view(layer, mip_level);
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Reflection probe blit pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &target,
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn face_view(&self, layer: u32, mip_level: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Reflection probe face view"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_mip_level: mip_level,
+            mip_level_count: Some(1),
+            base_array_layer: layer,
+            array_layer_count: Some(1),
+            ..Default::default()
+        })
+    }
+
+    fn find(&self, id: &Uuid) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|probe| probe.is_some_and(|probe| probe.id == *id))
+    }
+}
@@ -0,0 +1,185 @@
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3Swizzles;
+use half::f16;
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::{
+    mesh::{MeshVertex, TextureCoordinate},
+    pipeline::VertexLayoutKind,
+    vertex::{Vertex, VertexLayoutBuilder},
+};
+
+// Compact stores half float positions and UVs with octahedral normals and tangents, about half the memory of full
+// precision. Half floats keep three significant digits, so it suits meshes modelled around their own origin
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum VertexPrecision {
+    #[default]
+    Full,
+    Compact,
+}
+
+impl VertexPrecision {
+    pub const ALL: [Self; 2] = [Self::Full, Self::Compact];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Full => "Full",
+            Self::Compact => "Compact",
+        }
+    }
+
+    // Stored in the scene buffer's primitive headers
+    pub fn from_index(index: usize) -> Self {
+        match index {
+            1 => Self::Compact,
+            _ => Self::Full,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn vertex_layout(&self) -> VertexLayoutKind {
+        match self {
+            Self::Full => VertexLayoutKind::Mesh,
+            Self::Compact => VertexLayoutKind::CompactMesh,
+        }
+    }
+
+    // For shaders that declare the QUANTIZED override, the others read nothing but positions
+    pub fn shader_constants(&self) -> &'static [(&'static str, f64)] {
+        self.vertex_layout().shader_constants()
+    }
+
+    // The vertex buffer and `uv_sets` UV set buffers of this precision, passes push their own buffers after them
+    pub fn layout_builder(&self, uv_sets: usize) -> VertexLayoutBuilder {
+        match self {
+            Self::Full => (0..uv_sets).fold(VertexLayoutBuilder::new().push::<MeshVertex>(), |builder, _| {
+                builder.push::<TextureCoordinate>()
+            }),
+            Self::Compact => (0..uv_sets).fold(VertexLayoutBuilder::new().push::<CompactMeshVertex>(), |builder, _| {
+                builder.push::<CompactTextureCoordinate>()
+            }),
+        }
+    }
+
+    pub fn vertex_bytes(&self, vertices: &[MeshVertex]) -> Vec<u8> {
+        match self {
+            Self::Full => bytemuck::cast_slice(vertices).to_vec(),
+            Self::Compact => {
+                let vertices = vertices.iter().map(CompactMeshVertex::new).collect::<Vec<_>>();
+                bytemuck::cast_slice(&vertices).to_vec()
+            }
+        }
+    }
+
+    pub fn uv_bytes(&self, uvs: &[TextureCoordinate]) -> Vec<u8> {
+        match self {
+            Self::Full => bytemuck::cast_slice(uvs).to_vec(),
+            Self::Compact => {
+                let uvs = uvs
+                    .iter()
+                    .map(|uv| CompactTextureCoordinate(uv.to_vec().to_array().map(f16::from_f32)))
+                    .collect::<Vec<_>>();
+                bytemuck::cast_slice(&uvs).to_vec()
+            }
+        }
+    }
+}
+
+// A pass's mesh pipeline for every vertex precision, each primitive is drawn with the one matching its own
+pub struct MeshPipelines([wgpu::RenderPipeline; 2]);
+
+impl MeshPipelines {
+    pub fn new(create: impl FnMut(VertexPrecision) -> wgpu::RenderPipeline) -> Self {
+        Self(VertexPrecision::ALL.map(create))
+    }
+
+    pub fn get(&self, precision: VertexPrecision) -> &wgpu::RenderPipeline {
+        &self.0[precision.index()]
+    }
+}
+
+// The position's fourth component is padding, the tangent keeps its handedness in the third
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct CompactMeshVertex {
+    position: [f16; 4],
+    normal: [i16; 2],
+    tangent: [i16; 4],
+}
+
+impl CompactMeshVertex {
+    fn new(vertex: &MeshVertex) -> Self {
+        let [x, y, z] = vertex.position.map(f16::from_f32);
+        let normal = oct_encode(glam::Vec3::from_array(vertex.normal));
+        let [tangent_x, tangent_y] = oct_encode(glam::Vec3::from_slice(&vertex.tangent[..3]));
+
+        Self {
+            position: [x, y, z, f16::ONE],
+            normal,
+            tangent: [tangent_x, tangent_y, to_snorm(vertex.tangent[3].signum()), 0],
+        }
+    }
+}
+
+impl Vertex for CompactMeshVertex {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float16x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: 8,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Snorm16x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: 12,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Snorm16x4,
+                },
+            ],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct CompactTextureCoordinate([f16; 2]);
+
+impl Vertex for CompactTextureCoordinate {
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float16x2,
+            }],
+        }
+    }
+}
+
+// Projects the unit sphere onto an octahedron and unfolds it into a square, decoded by `oct_decode` in the shaders
+fn oct_encode(vector: glam::Vec3) -> [i16; 2] {
+    let vector = vector / (vector.x.abs() + vector.y.abs() + vector.z.abs()).max(f32::EPSILON);
+    let folded = if vector.z >= 0.0 {
+        vector.truncate()
+    } else {
+        (1.0 - vector.yx().abs()) * glam::vec2(vector.x.signum(), vector.y.signum())
+    };
+
+    [to_snorm(folded.x), to_snorm(folded.y)]
+}
+
+fn to_snorm(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use uuid::Uuid;
+
+use crate::renderer::{
+    bvh::{Aabb, Bvh, MeshBvh},
+    pointcloud::{PointAttributes, PointVertex, PointcloudBuffer},
+    ray::{Ray, SurfaceHit},
+    scene::{Geometry, RenderId, Renderable, SceneGraph, Visibility},
+    transform::TransformUniform,
+};
+
+#[derive(Copy, Clone, Debug)]
+pub struct SceneHit {
+    pub entity_id: Uuid,
+    pub render_id: RenderId,
+    pub surface: SurfaceHit,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct PointHit {
+    pub entity_id: Uuid,
+    pub index: u32,
+    pub position: glam::Vec3,
+    // In the coordinates of the source file, before the offset that keeps positions in f32 range
+    pub source_position: glam::DVec3,
+    pub color: glam::Vec3,
+    pub intensity: f32,
+    pub attributes: PointAttributes,
+    pub distance: f32,
+}
+
+struct PointInstance {
+    entity_id: Uuid,
+    transform: glam::Mat4,
+    inverse: glam::Mat4,
+    buffer: Arc<PointcloudBuffer>,
+}
+
+struct QueryInstance {
+    entity_id: Uuid,
+    render_id: RenderId,
+    inverse: glam::Mat4,
+    normal_matrix: glam::Mat3,
+    mesh: Arc<MeshBvh>,
+}
+
+// Two level hierarchy: one BVH per renderable, and one over the world bounds of all nodes
+#[derive(Default)]
+struct SceneBvh {
+    meshes: HashMap<RenderId, Arc<MeshBvh>>,
+    instances: Vec<QueryInstance>,
+    points: Vec<PointInstance>,
+    bvh: Option<Bvh>,
+}
+
+impl SceneBvh {
+    fn rebuild(&mut self, scene: &SceneGraph) {
+        let mut instances = Vec::new();
+        let mut points = Vec::new();
+        let mut bounds = Vec::new();
+
+        for (entity_id, node_index, render_id) in scene.nodes.iter_with_index() {
+            if scene.visibility(entity_id) != Visibility::Visible {
+                continue;
+            }
+
+            let Some(transform_index) = scene.node_transform_index.get_mapping(node_index) else {
+                continue;
+            };
+
+            let transform = scene
+                .transforms
+                .get_by_index(transform_index as usize)
+                .map(TransformUniform::to_mat4)
+                .unwrap_or_default();
+            let inverse = transform.inverse();
+
+            let handles = match scene.renderables.get(render_id) {
+                Some(Renderable::Mesh(handles)) => handles,
+                // Pointclouds have no surface to hit, they're searched around the ray instead
+                Some(Renderable::Pointcloud(handle)) => {
+                    if let Some(Geometry::Pointcloud(pointcloud)) = scene.geometries.get_by_id(handle.geometry_index) {
+                        points.push(PointInstance {
+                            entity_id: *entity_id,
+                            transform,
+                            inverse,
+                            buffer: Arc::clone(&pointcloud.buffer),
+                        });
+                    }
+                    continue;
+                }
+                None => continue,
+            };
+
+            let geometries = handles
+                .iter()
+                .filter_map(|handle| scene.geometries.get_by_id(handle.geometry_index))
+                .filter_map(|geometry| match geometry {
+                    Geometry::Primitive(primitive) => Some(primitive.geometry.as_ref()),
+                    _ => None,
+                });
+            // Skinned entities are posed apart, copies of a renderable can be at another point in their clip
+            let mesh = match scene.joint_palettes.get(entity_id) {
+                Some(palette) => Arc::new(MeshBvh::new(geometries, Some(palette))),
+                None => Arc::clone(
+                    self.meshes
+                        .entry(*render_id)
+                        .or_insert_with(|| Arc::new(MeshBvh::new(geometries, None))),
+                ),
+            };
+
+            bounds.push(mesh.bounds().transform(transform));
+            instances.push(QueryInstance {
+                entity_id: *entity_id,
+                render_id: *render_id,
+                inverse,
+                normal_matrix: glam::Mat3::from_mat4(inverse.transpose()),
+                mesh,
+            });
+        }
+
+        self.bvh = Some(Bvh::build(&bounds));
+        self.instances = instances;
+        self.points = points;
+    }
+
+    // The point nearest to the camera within `max_angle` of the ray, which is how far from the cursor it shows up on
+    // screen
+    fn closest_point(&self, ray: &Ray, max_angle: f32) -> Option<PointHit> {
+        let tan_squared = max_angle.tan().powi(2);
+        self.points
+            .iter()
+            .filter_map(|instance| {
+                // Normalized, the cone test compares lengths along and across the ray
+                let local_ray = ray.transform(instance.inverse);
+                let local_ray = Ray::new(local_ray.origin, local_ray.direction);
+                let points = instance.buffer.points();
+                let index = front_point_in_cone(points, &local_ray, tan_squared)?;
+                let point = &points[index];
+                let local_position = glam::Vec3::from_array(point.position);
+                let position = instance.transform.transform_point3(local_position);
+
+                Some(PointHit {
+                    entity_id: instance.entity_id,
+                    index: index as u32,
+                    position,
+                    source_position: instance.buffer.origin() + local_position.as_dvec3(),
+                    color: glam::Vec3::from_array(point.color),
+                    intensity: point.intensity,
+                    attributes: instance.buffer.attributes()[index],
+                    distance: position.distance(ray.origin),
+                })
+            })
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    fn closest_hit(&self, ray: &Ray, filter: impl Fn(&Uuid, &RenderId) -> bool) -> Option<SceneHit> {
+        let bvh = self.bvh.as_ref()?;
+        let mut closest = None;
+
+        bvh.closest_hit(ray, f32::INFINITY, |index, limit| {
+            let instance = &self.instances[index as usize];
+            if !filter(&instance.entity_id, &instance.render_id) {
+                return None;
+            }
+
+            let hit = instance.mesh.closest_hit(&ray.transform(instance.inverse), limit)?;
+            if hit.distance >= limit {
+                return None;
+            }
+
+            closest = Some(SceneHit {
+                entity_id: instance.entity_id,
+                render_id: instance.render_id,
+                surface: SurfaceHit {
+                    position: ray.at(hit.distance),
+                    normal: (instance.normal_matrix * hit.normal).normalize_or_zero(),
+                    distance: hit.distance,
+                },
+            });
+            Some(hit.distance)
+        });
+
+        closest
+    }
+
+    fn any_hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        let Some(bvh) = self.bvh.as_ref() else {
+            return false;
+        };
+
+        bvh.any_hit(ray, max_distance, |index, limit| {
+            let instance = &self.instances[index as usize];
+            let local_ray = ray.transform(instance.inverse);
+            instance.mesh.any_hit(&local_ray, limit).then_some(0.0)
+        })
+    }
+}
+
+// Shared handle for ray casts against the rendered scene, the render thread keeps it up to date
+#[derive(Clone, Default)]
+pub struct SceneQuery {
+    scene: Arc<RwLock<SceneBvh>>,
+}
+
+impl SceneQuery {
+    pub fn closest_hit(&self, ray: &Ray) -> Option<SceneHit> {
+        self.closest_hit_filtered(ray, |_| true)
+    }
+
+    pub fn closest_hit_filtered(&self, ray: &Ray, filter: impl Fn(&RenderId) -> bool) -> Option<SceneHit> {
+        self.scene
+            .read()
+            .ok()?
+            .closest_hit(ray, |_, render_id| filter(render_id))
+    }
+
+    // Skips a single entity, for placing it against the rest of the scene
+    pub fn closest_hit_excluding(&self, ray: &Ray, entity_id: Uuid) -> Option<SceneHit> {
+        self.scene.read().ok()?.closest_hit(ray, |id, _| *id != entity_id)
+    }
+
+    pub fn closest_point(&self, ray: &Ray, max_angle: f32) -> Option<PointHit> {
+        self.scene.read().ok()?.closest_point(ray, max_angle)
+    }
+
+    // The triangles of a mesh in object space, with the entity's transform
+    pub fn mesh(&self, entity_id: Uuid) -> Option<(Arc<MeshBvh>, glam::Mat4)> {
+        let scene = self.scene.read().ok()?;
+        let instance = scene
+            .instances
+            .iter()
+            .find(|instance| instance.entity_id == entity_id)?;
+        Some((Arc::clone(&instance.mesh), instance.inverse.inverse()))
+    }
+
+    // The points of a cloud as loaded, with the entity's transform
+    pub fn pointcloud(&self, entity_id: Uuid) -> Option<(Arc<PointcloudBuffer>, glam::Mat4)> {
+        let scene = self.scene.read().ok()?;
+        let instance = scene.points.iter().find(|instance| instance.entity_id == entity_id)?;
+        Some((Arc::clone(&instance.buffer), instance.transform))
+    }
+
+    pub fn any_hit(&self, ray: &Ray, max_distance: f32) -> bool {
+        self.scene
+            .read()
+            .map(|scene| scene.any_hit(ray, max_distance))
+            .unwrap_or(false)
+    }
+
+    // World bounds of the visible meshes, None before the first rebuild and while there are none
+    pub fn bounds(&self) -> Option<Aabb> {
+        let scene = self.scene.read().ok()?;
+        match scene.instances.is_empty() {
+            true => None,
+            false => Some(scene.bvh.as_ref()?.bounds()),
+        }
+    }
+
+    pub fn rebuild(&self, scene: &SceneGraph) {
+        match self.scene.write() {
+            Ok(mut bvh) => bvh.rebuild(scene),
+            Err(error) => log::error!("Unable to update scene query: {}", error),
+        }
+    }
+}
+
+// Front most point inside the cone around the ray. Clouds have no spatial index, so this scans all of them, split
+// across threads on native
+fn front_point_in_cone(points: &[PointVertex], ray: &Ray, tan_squared: f32) -> Option<usize> {
+    let scan = |offset: usize, points: &[PointVertex]| {
+        points
+            .iter()
+            .enumerate()
+            .filter_map(|(index, point)| {
+                let to_point = glam::Vec3::from_array(point.position) - ray.origin;
+                let along = to_point.dot(ray.direction);
+                let across_squared = to_point.length_squared() - along * along;
+                (along > 0.0 && across_squared <= tan_squared * along * along).then_some((offset + index, along))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    };
+
+    #[cfg(not(target_family = "wasm"))]
+    let closest = {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk_size = points.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let scans = points
+                .chunks(chunk_size)
+                .enumerate()
+                .map(|(chunk_index, chunk)| scope.spawn(move || scan(chunk_index * chunk_size, chunk)))
+                .collect::<Vec<_>>();
+            scans
+                .into_iter()
+                .filter_map(|scan| scan.join().ok().flatten())
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+        })
+    };
+
+    #[cfg(target_family = "wasm")]
+    let closest = scan(0, points);
+
+    closest.map(|(index, _)| index)
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::{context::RenderContext, texture::Texture};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RampStop {
+    pub position: f32,
+    pub color: [f32; 3],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorRamp {
+    Viridis,
+    Turbo,
+    Custom,
+}
+
+impl ColorRamp {
+    pub const ALL: [Self; 3] = [Self::Viridis, Self::Turbo, Self::Custom];
+    pub const RESOLUTION: u32 = 256;
+
+    // Sampled from the reference tables at even intervals
+    const VIRIDIS: [[f32; 3]; 5] = [
+        [0.267, 0.005, 0.329],
+        [0.230, 0.322, 0.546],
+        [0.128, 0.567, 0.551],
+        [0.369, 0.789, 0.383],
+        [0.993, 0.906, 0.144],
+    ];
+    const TURBO: [[f32; 3]; 9] = [
+        [0.190, 0.072, 0.232],
+        [0.275, 0.385, 0.852],
+        [0.163, 0.683, 0.971],
+        [0.103, 0.894, 0.718],
+        [0.556, 0.992, 0.311],
+        [0.889, 0.862, 0.219],
+        [0.995, 0.585, 0.137],
+        [0.875, 0.269, 0.036],
+        [0.480, 0.016, 0.011],
+    ];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Viridis => "Viridis",
+            Self::Turbo => "Turbo",
+            Self::Custom => "Custom",
+        }
+    }
+
+    pub fn default_stops() -> Vec<RampStop> {
+        vec![
+            RampStop {
+                position: 0.0,
+                color: [0.0, 0.0, 1.0],
+            },
+            RampStop {
+                position: 1.0,
+                color: [1.0, 0.0, 0.0],
+            },
+        ]
+    }
+
+    // `custom` is only used by the custom ramp
+    pub fn sample(&self, value: f32, custom: &[RampStop]) -> [f32; 3] {
+        let value = value.clamp(0.0, 1.0);
+        let table = match self {
+            Self::Viridis => &Self::VIRIDIS[..],
+            Self::Turbo => &Self::TURBO[..],
+            Self::Custom => return sample_stops(value, custom),
+        };
+
+        let position = value * (table.len() - 1) as f32;
+        let index = (position as usize).min(table.len() - 2);
+        let [a, b] = [table[index], table[index + 1]].map(glam::Vec3::from_array);
+        a.lerp(b, position - index as f32).to_array()
+    }
+
+    fn bake(&self, custom: &[RampStop]) -> Vec<u8> {
+        (0..Self::RESOLUTION)
+            .flat_map(|index| {
+                let [r, g, b] = self.sample(index as f32 / (Self::RESOLUTION - 1) as f32, custom);
+                [r, g, b, 1.0].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect()
+    }
+}
+
+fn sample_stops(value: f32, stops: &[RampStop]) -> [f32; 3] {
+    let mut stops = stops.to_vec();
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+    match stops.iter().position(|stop| stop.position >= value) {
+        _ if stops.is_empty() => [value; 3],
+        Some(0) => stops[0].color,
+        None => stops[stops.len() - 1].color,
+        Some(upper) => {
+            let (a, b) = (stops[upper - 1], stops[upper]);
+            let t = (value - a.position) / (b.position - a.position).max(f32::EPSILON);
+            glam::Vec3::from_array(a.color)
+                .lerp(glam::Vec3::from_array(b.color), t)
+                .to_array()
+        }
+    }
+}
+
+// Lookup table for scalar coloring, bound next to the settings in the camera group
+pub struct RampTexture {
+    texture: Texture,
+}
+
+impl RampTexture {
+    pub fn new(ramp: ColorRamp, custom: &[RampStop], context: &RenderContext) -> Self {
+        let size = wgpu::Extent3d {
+            width: ColorRamp::RESOLUTION,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture = Texture::from_bytes(
+            &context.device,
+            &context.queue,
+            &ramp.bake(custom),
+            size,
+            wgpu::TextureFormat::Rgba8Unorm,
+            &wgpu::SamplerDescriptor {
+                label: Some("Color ramp sampler"),
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            },
+            Some("Color ramp"),
+        );
+
+        Self { texture }
+    }
+
+    pub fn update(&self, ramp: ColorRamp, custom: &[RampStop], context: &RenderContext) {
+        context.queue.write_texture(
+            self.texture.texture().as_image_copy(),
+            &ramp.bake(custom),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(ColorRamp::RESOLUTION * 4),
+                rows_per_image: None,
+            },
+            self.texture.texture().size(),
+        );
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+}
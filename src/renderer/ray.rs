@@ -0,0 +1,70 @@
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: glam::Vec3,
+    pub direction: glam::Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: glam::Vec3, direction: glam::Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize_or_zero(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> glam::Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    pub fn intersect_plane(&self, point: glam::Vec3, normal: glam::Vec3) -> Option<f32> {
+        let denominator = normal.dot(self.direction);
+        if denominator.abs() < 1e-6 {
+            return None;
+        }
+
+        let distance = (point - self.origin).dot(normal) / denominator;
+        (distance >= 0.0).then_some(distance)
+    }
+
+    // Möller-Trumbore, returns the distance and the barycentric coordinates of the second and third vertex
+    pub fn intersect_triangle(&self, triangle: &[glam::Vec3; 3]) -> Option<(f32, glam::Vec2)> {
+        let edge1 = triangle[1] - triangle[0];
+        let edge2 = triangle[2] - triangle[0];
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-8 {
+            return None;
+        }
+
+        let inv_determinant = 1.0 / determinant;
+        let s = self.origin - triangle[0];
+        let u = s.dot(p) * inv_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inv_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = edge2.dot(q) * inv_determinant;
+        (distance > 0.0).then_some((distance, glam::Vec2::new(u, v)))
+    }
+
+    pub fn transform(&self, matrix: glam::Mat4) -> Self {
+        // Not normalized, so distances along the transformed ray match the original one
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SurfaceHit {
+    pub position: glam::Vec3,
+    pub normal: glam::Vec3,
+    pub distance: f32,
+}
@@ -0,0 +1,301 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::renderer::{context::RenderContext, instance::Instance, light::LightUniform};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InspectedBuffer {
+    Transforms,
+    Normals,
+    Lights,
+    Instances,
+}
+
+impl InspectedBuffer {
+    pub const ALL: [Self; 4] = [Self::Transforms, Self::Normals, Self::Lights, Self::Instances];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Transforms => "Transforms",
+            Self::Normals => "Normals",
+            Self::Lights => "Lights",
+            Self::Instances => "Instances",
+        }
+    }
+
+    fn stride(&self) -> usize {
+        match self {
+            Self::Transforms | Self::Normals => std::mem::size_of::<[f32; 16]>(),
+            Self::Lights => std::mem::size_of::<LightUniform>(),
+            Self::Instances => Instance::STRIDE,
+        }
+    }
+
+    fn format(&self, bytes: &[u8]) -> String {
+        match self {
+            Self::Transforms => {
+                let matrix = glam::Mat4::from_cols_array(&bytemuck::pod_read_unaligned(bytes));
+                let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+                format!(
+                    "translation {:.3?} rotation {:.3?} scale {:.3?}",
+                    translation.to_array(),
+                    rotation.to_array(),
+                    scale.to_array()
+                )
+            }
+            Self::Normals => {
+                let matrix = glam::Mat4::from_cols_array(&bytemuck::pod_read_unaligned(bytes));
+                format!(
+                    "{:.3?} {:.3?} {:.3?}",
+                    matrix.x_axis.truncate().to_array(),
+                    matrix.y_axis.truncate().to_array(),
+                    matrix.z_axis.truncate().to_array()
+                )
+            }
+            Self::Lights => {
+                let light: LightUniform = bytemuck::pod_read_unaligned(bytes);
+                format!(
+                    "kind {} color {:.3?} intensity {:.2} range {:.2} cone {:.3}..{:.3} shadows {}",
+                    light.kind,
+                    light.color,
+                    light.intensity,
+                    light.range,
+                    light.cos_inner_cutoff,
+                    light.cos_outer_cutoff,
+                    light.casts_shadows()
+                )
+            }
+            Self::Instances => {
+                let instance: Instance = bytemuck::pod_read_unaligned(bytes);
+                format!(
+                    "transform {} normal {}",
+                    instance.transform_index, instance.normal_index
+                )
+            }
+        }
+    }
+}
+
+// Copies the first `count` entries of a GPU buffer into a staging buffer, which is read once mapping completes
+pub struct BufferReadback {
+    kind: InspectedBuffer,
+    staging: wgpu::Buffer,
+    count: usize,
+    status: Arc<OnceLock<bool>>,
+}
+
+impl BufferReadback {
+    pub fn new(kind: InspectedBuffer, source: &wgpu::Buffer, count: usize, context: &RenderContext) -> Self {
+        let size = (count.max(1) * kind.stride()) as u64;
+        let staging = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback staging buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback encoder"),
+        });
+        encoder.copy_buffer_to_buffer(source, 0, &staging, 0, size.min(source.size()));
+        context.queue.submit(Some(encoder.finish()));
+
+        let status = Arc::new(OnceLock::new());
+        let callback_status = Arc::clone(&status);
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if let Err(error) = &result {
+                log::error!("Unable to map readback buffer: {}", error);
+            }
+            let _ = callback_status.set(result.is_ok());
+        });
+
+        Self {
+            kind,
+            staging,
+            count,
+            status,
+        }
+    }
+
+    pub fn kind(&self) -> InspectedBuffer {
+        self.kind
+    }
+
+    // None while the copy is still in flight
+    pub fn try_read(&self) -> Option<Vec<String>> {
+        if !*self.status.get()? {
+            return Some(Vec::new());
+        }
+
+        let entries = {
+            let data = self.staging.slice(..).get_mapped_range();
+            data.chunks_exact(self.kind.stride())
+                .take(self.count)
+                .map(|bytes| self.kind.format(bytes))
+                .collect()
+        };
+
+        self.staging.unmap();
+        Some(entries)
+    }
+}
+
+// Copies a rendered texture into a staging buffer, rows are padded to the copy alignment and stripped again on read
+pub struct TextureReadback {
+    staging: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    pixel_bytes: u32,
+    padded_row_bytes: u32,
+    is_bgra: bool,
+    status: Arc<OnceLock<bool>>,
+}
+
+impl TextureReadback {
+    pub fn new(texture: &wgpu::Texture, context: &RenderContext) -> Self {
+        let (width, height) = (texture.width(), texture.height());
+        let pixel_bytes = texture.format().block_copy_size(None).unwrap_or(4);
+        let padded_row_bytes =
+            (width * pixel_bytes).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture readback staging buffer"),
+            size: (padded_row_bytes * height) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Texture readback encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &staging,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        context.queue.submit(Some(encoder.finish()));
+
+        let status = Arc::new(OnceLock::new());
+        let callback_status = Arc::clone(&status);
+        staging.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            if let Err(error) = &result {
+                log::error!("Unable to map texture readback buffer: {}", error);
+            }
+            let _ = callback_status.set(result.is_ok());
+        });
+
+        Self {
+            staging,
+            width,
+            height,
+            pixel_bytes,
+            padded_row_bytes,
+            is_bgra: matches!(texture.format().remove_srgb_suffix(), wgpu::TextureFormat::Bgra8Unorm),
+            status,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // RGBA8 pixels for color textures and the texture's own layout otherwise, None while the copy is still in flight
+    pub fn try_read(&self) -> Option<anyhow::Result<Vec<u8>>> {
+        if !*self.status.get()? {
+            return Some(Err(anyhow::anyhow!("Unable to map the texture readback buffer")));
+        }
+
+        let mut pixels = {
+            let data = self.staging.slice(..).get_mapped_range();
+            data.chunks_exact(self.padded_row_bytes as usize)
+                .flat_map(|row| &row[..(self.width * self.pixel_bytes) as usize])
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        self.staging.unmap();
+
+        if self.is_bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        Some(Ok(pixels))
+    }
+}
+
+// Each tile is held in a staging buffer until it's read, a tile of a 4K window takes over 30 MiB
+pub const MAX_SCREENSHOT_TILES: u32 = 8;
+
+// Tiles of one capture, copied into the stitched image as they arrive so their staging buffers go early
+pub struct TiledReadback {
+    tiles: u32,
+    tile_width: u32,
+    tile_height: u32,
+    readbacks: Vec<Option<TextureReadback>>,
+    pixels: Vec<u8>,
+    has_failed: bool,
+}
+
+impl TiledReadback {
+    // In row major order, the top left tile first
+    pub fn new(tiles: u32, readbacks: Vec<TextureReadback>) -> Self {
+        let (tile_width, tile_height) = readbacks.first().map_or((0, 0), TextureReadback::size);
+        Self {
+            tiles,
+            tile_width,
+            tile_height,
+            readbacks: readbacks.into_iter().map(Some).collect(),
+            pixels: vec![0; (tile_width * tiles * tile_height * tiles * 4) as usize],
+            has_failed: false,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.tile_width * self.tiles, self.tile_height * self.tiles)
+    }
+
+    // Multiplied onto the camera's projection, narrows it to one tile of the view. Rows count down from the top
+    pub fn tile_projection(tiles: u32, column: u32, row: u32) -> glam::Mat4 {
+        let tiles = tiles as f32;
+        let offset = glam::Vec2::new(tiles - 1.0 - 2.0 * column as f32, -(tiles - 1.0 - 2.0 * row as f32));
+        glam::Mat4::from_translation(offset.extend(0.0)) * glam::Mat4::from_scale(glam::Vec3::new(tiles, tiles, 1.0))
+    }
+
+    // The stitched RGBA8 image, None until every tile is in
+    pub fn try_read(&mut self) -> Option<anyhow::Result<Vec<u8>>> {
+        let row_bytes = (self.tile_width * 4) as usize;
+        let stitched_row_bytes = row_bytes * self.tiles as usize;
+        for (index, slot) in self.readbacks.iter_mut().enumerate() {
+            let Some(pixels) = slot.as_ref().and_then(TextureReadback::try_read) else {
+                continue;
+            };
+            *slot = None;
+            let Ok(pixels) = pixels else {
+                self.has_failed = true;
+                continue;
+            };
+
+            let (column, row) = (index % self.tiles as usize, index / self.tiles as usize);
+            for (y, source) in pixels.chunks_exact(row_bytes).enumerate() {
+                let start = (row * self.tile_height as usize + y) * stitched_row_bytes + column * row_bytes;
+                self.pixels[start..start + row_bytes].copy_from_slice(source);
+            }
+        }
+
+        if self.readbacks.iter().any(Option::is_some) {
+            return None;
+        }
+
+        if self.has_failed {
+            Some(Err(anyhow::anyhow!("Unable to map a screenshot tile")))
+        } else {
+            Some(Ok(std::mem::take(&mut self.pixels)))
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::pointcloud::{PointGrid, PointcloudBuffer};
+
+// Shuffled clouds are an even sample of themselves from the start, so the first points stand in for the whole scan
+const SOURCE_SAMPLES: usize = 20_000;
+const TARGET_SAMPLES: usize = 1_000_000;
+const MAX_ITERATIONS: u32 = 50;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Registration {
+    // New world transform of the moving scan
+    pub transform: glam::Mat4,
+    // Root mean square distance between matched points, in world units
+    pub rms_error: f32,
+    pub matched: usize,
+    pub iterations: u32,
+}
+
+impl Registration {
+    // Rigid transform that best maps the first point of each pair onto the second, in the least squares sense. Horn's
+    // closed form with quaternions, needs at least three pairs that aren't on a line
+    pub fn from_pairs(pairs: &[(glam::Vec3, glam::Vec3)]) -> Option<glam::Mat4> {
+        if pairs.len() < 3 {
+            return None;
+        }
+
+        let count = pairs.len() as f32;
+        let source_centroid = pairs.iter().map(|(source, _)| *source).sum::<glam::Vec3>() / count;
+        let target_centroid = pairs.iter().map(|(_, target)| *target).sum::<glam::Vec3>() / count;
+
+        // Cross covariance, s[a][b] sums source axis a times target axis b
+        let mut s = [[0.0_f32; 3]; 3];
+        for (source, target) in pairs {
+            let p = (*source - source_centroid).to_array();
+            let q = (*target - target_centroid).to_array();
+            for a in 0..3 {
+                for b in 0..3 {
+                    s[a][b] += p[a] * q[b];
+                }
+            }
+        }
+
+        let [[xx, xy, xz], [yx, yy, yz], [zx, zy, zz]] = s;
+        let n = glam::Mat4::from_cols_array_2d(&[
+            [xx + yy + zz, yz - zy, zx - xz, xy - yx],
+            [yz - zy, xx - yy - zz, xy + yx, zx + xz],
+            [zx - xz, xy + yx, -xx + yy - zz, yz + zy],
+            [xy - yx, zx + xz, yz + zy, -xx - yy + zz],
+        ]);
+
+        // The rotation is the eigenvector of the largest eigenvalue. Shifted by the norm every eigenvalue is positive,
+        // and repeated squaring leaves a matrix whose columns all point along that eigenvector
+        let shift = n.to_cols_array().iter().map(|value| value * value).sum::<f32>().sqrt();
+        if shift <= f32::EPSILON {
+            return None;
+        }
+
+        let mut power = n + glam::Mat4::from_diagonal(glam::Vec4::splat(shift));
+        for _ in 0..32 {
+            power = power * power;
+            power = power
+                * power
+                    .to_cols_array()
+                    .iter()
+                    .fold(0.0_f32, |max, value| max.max(value.abs()))
+                    .recip();
+        }
+
+        let eigenvector = (0..4)
+            .map(|column| power.col(column))
+            .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))?
+            .normalize_or_zero();
+        let [w, x, y, z] = eigenvector.to_array();
+        let rotation = glam::Quat::from_xyzw(x, y, z, w).normalize();
+        let translation = target_centroid - rotation * source_centroid;
+        Some(glam::Mat4::from_rotation_translation(rotation, translation))
+    }
+
+    // World positions of the points `refine` matches, from the moving and the reference scan
+    pub fn samples(
+        source: &PointcloudBuffer,
+        source_transform: glam::Mat4,
+        target: &PointcloudBuffer,
+        target_transform: glam::Mat4,
+    ) -> (Vec<glam::Vec3>, Vec<glam::Vec3>) {
+        let to_world = |buffer: &PointcloudBuffer, transform: glam::Mat4, samples: usize| {
+            buffer
+                .points()
+                .iter()
+                .take(samples)
+                .map(|point| transform.transform_point3(glam::Vec3::from_array(point.position)))
+                .collect::<Vec<_>>()
+        };
+
+        (
+            to_world(source, source_transform, SOURCE_SAMPLES),
+            to_world(target, target_transform, TARGET_SAMPLES),
+        )
+    }
+
+    // Iterative closest point: matches every sampled point of the moving scan to its nearest neighbour in the
+    // reference within `max_distance`, moves it to fit those matches best and repeats until it settles. Only
+    // converges from a rough alignment, pairs further apart than `max_distance` are ignored
+    pub fn refine(
+        source_positions: &[glam::Vec3],
+        target_positions: &[glam::Vec3],
+        source_transform: glam::Mat4,
+        max_distance: f32,
+        is_cancelled: impl Fn() -> bool,
+    ) -> Option<Self> {
+        crate::profile_scope!("ICP");
+        if source_positions.len() < 3 || target_positions.len() < 3 {
+            return None;
+        }
+
+        let grid = PointGrid::with_cell_size(target_positions, max_distance);
+        let max_distance_squared = max_distance * max_distance;
+        let mut correction = glam::Mat4::IDENTITY;
+        let mut registration = None;
+
+        for iteration in 1..=MAX_ITERATIONS {
+            if is_cancelled() {
+                return None;
+            }
+
+            let pairs = source_positions
+                .iter()
+                .filter_map(|position| {
+                    let position = correction.transform_point3(*position);
+                    let nearest = *grid.nearest(target_positions, position, 1).first()?;
+                    let target = target_positions[nearest as usize];
+                    (position.distance_squared(target) <= max_distance_squared).then_some((position, target))
+                })
+                .collect::<Vec<_>>();
+
+            let Some(step) = Self::from_pairs(&pairs) else {
+                break;
+            };
+            correction = step * correction;
+
+            let squared_error = pairs
+                .iter()
+                .map(|(source, target)| step.transform_point3(*source).distance_squared(*target))
+                .sum::<f32>();
+            registration = Some(Self {
+                transform: correction * source_transform,
+                rms_error: (squared_error / pairs.len() as f32).sqrt(),
+                matched: pairs.len(),
+                iterations: iteration,
+            });
+
+            let (_, rotation, translation) = step.to_scale_rotation_translation();
+            if translation.length() < max_distance * 1e-4 && rotation.angle_between(glam::Quat::IDENTITY) < 1e-5 {
+                break;
+            }
+        }
+
+        registration
+    }
+}
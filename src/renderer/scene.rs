@@ -1,17 +1,30 @@
-use std::{collections::HashMap, hash::Hash, ops::Range};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    hash::Hash,
+    ops::Range,
+};
 
+use instant::Instant;
 use uuid::Uuid;
 
 use crate::renderer::{
+    animation::{AnimationPhase, JointPalette, Skeleton},
+    bvh::Aabb,
     component::{ComponentId, ComponentStore, HostComponentStore, RelationStore},
     context::RenderContext,
     environment::{self, EnvironmentMap},
+    frustum::Frustum,
     instance::{Instance, InstancePool},
+    irradiance_volume::IrradianceVolume,
+    layers::RenderLayers,
     light::{Light, LightId, LightUniform},
-    material::Material,
+    material::{Material, MaterialView},
     mesh::{DrawMesh, Mesh, Primitive, Scene},
-    pipeline::PipelineCache,
+    pipeline::{PipelineCache, PipelineKey, ShaderKind, VertexLayoutKind},
     pointcloud::{DrawPointcloud, Pointcloud},
+    probe::ReflectionProbes,
+    quantize::VertexPrecision,
+    shadow::{ShadowCaster, ShadowMaps},
     transform::TransformUniform,
 };
 
@@ -25,12 +38,41 @@ pub enum Renderable {
 }
 
 impl Renderable {
-    pub fn as_str(&self) -> &'static str {
+    // Primitives of one mesh come from the same import, so they share a vertex precision
+    pub fn pipeline_key(&self, geometries: &HostComponentStore<Geometry>) -> PipelineKey {
         match self {
-            Self::Mesh(_) => "mesh",
-            Self::Pointcloud(_) => "pointcloud",
+            Self::Mesh(handles) => {
+                let geometry = handles
+                    .first()
+                    .and_then(|handle| geometries.get_by_id(handle.geometry_index));
+                let vertex_layout = match geometry {
+                    Some(Geometry::Primitive(primitive)) if primitive.is_skinned => VertexLayoutKind::SkinnedMesh,
+                    Some(Geometry::Primitive(primitive)) => primitive.vertex_precision.vertex_layout(),
+                    _ => VertexPrecision::Full.vertex_layout(),
+                };
+
+                PipelineKey {
+                    vertex_layout,
+                    ..PipelineKey::MESH
+                }
+            }
+            Self::Pointcloud(_) => PipelineKey::POINTCLOUD,
         }
     }
+
+    // Object space bounds of every primitive, None for pointclouds since they aren't culled
+    pub fn bounds(&self, geometries: &HostComponentStore<Geometry>) -> Option<Aabb> {
+        let Self::Mesh(handles) = self else {
+            return None;
+        };
+
+        Some(handles.iter().fold(Aabb::EMPTY, |mut bounds, handle| {
+            if let Some(Geometry::Primitive(primitive)) = geometries.get_by_id(handle.geometry_index) {
+                bounds.merge(&primitive.bounds);
+            }
+            bounds
+        }))
+    }
 }
 
 pub enum Geometry {
@@ -40,7 +82,24 @@ pub enum Geometry {
 
 pub struct PrimitiveHandle {
     pub geometry_index: ComponentId<Geometry>,
+    // The material drawn with, the default one or the active variant's
     pub material_index: ComponentId<Material>,
+    pub default_material: ComponentId<Material>,
+    pub variant_materials: Vec<(usize, ComponentId<Material>)>,
+}
+
+impl PrimitiveHandle {
+    // Primitives without a material for the variant keep their default
+    fn select_variant(&mut self, variant: Option<usize>) {
+        self.material_index = variant
+            .and_then(|variant| {
+                self.variant_materials
+                    .iter()
+                    .find(|(mapped, _)| *mapped == variant)
+                    .map(|&(_, material)| material)
+            })
+            .unwrap_or(self.default_material);
+    }
 }
 
 pub struct PointcloudHandle {
@@ -58,12 +117,30 @@ impl NormalUniform {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScenePass {
+    Opaque,
+    Transmissive,
+    // Opaque meshes only, the pointclouds are splatted progressively in their own pass
+    Meshes,
+    // One slice of `budget` points from every pointcloud
+    PointChunk { index: u32, budget: u32 },
+}
+
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
 pub struct BatchKey {
-    pub pipeline_id: &'static str,
+    pub pipeline: PipelineKey,
     pub render_id: RenderId,
 }
 
+// Instances of every batch as built, before culling
+#[derive(Default)]
+struct BatchInstances {
+    render: HashMap<BatchKey, Vec<Instance>>,
+    selected: HashMap<BatchKey, Vec<Instance>>,
+    ghosted: HashMap<BatchKey, Vec<Instance>>,
+}
+
 #[derive(Debug)]
 pub struct RenderBatch {
     pub key: BatchKey,
@@ -77,6 +154,29 @@ impl RenderBatch {
     }
 }
 
+// Hidden entities aren't drawn, ghosted ones only as a faint tint. Neither is picked or path traced
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    Ghosted,
+    Hidden,
+}
+
+// Label and tags of an entity, mirrored from the app so commands can act on sets of entities
+#[derive(Clone, Debug, Default)]
+pub struct EntityTags {
+    pub label: Option<String>,
+    pub tags: BTreeSet<String>,
+}
+
+// Entities matching every given filter are outlined along with the selection
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagFilter {
+    pub label: Option<String>,
+    pub tag: Option<String>,
+}
+
 pub struct SceneGraph {
     pub nodes: HostComponentStore<RenderId>,
     pub renderables: HostComponentStore<Renderable>,
@@ -86,20 +186,54 @@ pub struct SceneGraph {
     pub normals: ComponentStore<NormalUniform>,
     pub transforms: ComponentStore<TransformUniform>,
     pub lights: ComponentStore<LightUniform>,
+    // One per entity drawing a skinned renderable
+    pub joint_palettes: ComponentStore<JointPalette>,
 
     pub node_transform_index: RelationStore<RenderId, TransformUniform>,
     pub node_normal_index: RelationStore<RenderId, NormalUniform>,
     pub lights_transform_index: RelationStore<LightUniform, TransformUniform>,
 
     pub environment_map: EnvironmentMap,
+    pub probes: ReflectionProbes,
+    pub irradiance_volume: IrradianceVolume,
+    pub shadow_maps: ShadowMaps,
     pub instance_pool: InstancePool,
+    // Only the mesh instances in view of the camera the batches were last culled for
     pub render_batches: Vec<RenderBatch>,
+    batch_instances: BatchInstances,
+    culled_view: Option<glam::Mat4>,
+    // Set when transforms moved since the last cull
+    is_culling_dirty: bool,
+    pub selection: Option<Uuid>,
+    // Instances of the selected entity only, drawn into the outline mask
+    pub selection_batches: Vec<RenderBatch>,
+    // Only entities that aren't visible
+    pub visibility: HashMap<Uuid, Visibility>,
+    pub ghost_batches: Vec<RenderBatch>,
+    // Every mesh instance, unculled, while a light casts shadows
+    pub shadow_batches: Vec<RenderBatch>,
+    // Only entities outside the scene layer
+    pub layers: HashMap<Uuid, RenderLayers>,
+    // Mask of the view the batches are built for, views with another one rebuild them before drawing
+    pub view_layers: RenderLayers,
+    pub tags: HashMap<Uuid, EntityTags>,
+    pub highlight: Option<TagFilter>,
+    skeletons: HashMap<RenderId, Skeleton>,
+    // Only renderables switched to a material variant, kept when their asset reloads
+    material_variants: HashMap<RenderId, usize>,
+    // Every skinned entity plays its clip from here
+    animation_start: Instant,
+    // Only entities out of step with the shared clock
+    animation_phases: HashMap<Uuid, AnimationPhase>,
     pub debug_id: RenderId,
     pub bind_group: wgpu::BindGroup,
     pub layout: wgpu::BindGroupLayout,
 }
 
 impl SceneGraph {
+    // Added before anything else, so it always sits in the first slot
+    pub const FALLBACK_MATERIAL: ComponentId<Material> = ComponentId::new(0);
+
     pub fn new(context: &RenderContext) -> Self {
         let layout = context
             .device
@@ -146,6 +280,52 @@ impl SceneGraph {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::CubeArray,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                        count: None,
+                    },
                 ],
             });
 
@@ -154,6 +334,7 @@ impl SceneGraph {
         let transforms = ComponentStore::new(64, wgpu::ShaderStages::VERTEX, context);
         let normals = ComponentStore::new(64, wgpu::ShaderStages::VERTEX, context);
         let lights = ComponentStore::new(64, wgpu::ShaderStages::FRAGMENT, context);
+        let joint_palettes = ComponentStore::new(4, wgpu::ShaderStages::VERTEX, context);
 
         let node_transform_index = RelationStore::new(64, wgpu::ShaderStages::VERTEX, context);
         let node_normal_index = RelationStore::new(64, wgpu::ShaderStages::VERTEX, context);
@@ -161,7 +342,11 @@ impl SceneGraph {
 
         let mut renderables = HostComponentStore::new();
         let mut geometries = HostComponentStore::new();
-        let materials = HostComponentStore::new();
+        let mut materials = HostComponentStore::new();
+        materials.add(
+            MaterialId::nil(),
+            Material::new(MaterialView::fallback(), Some("Fallback material"), context),
+        );
 
         let mesh = Mesh::unit_cube(context);
         let handles = mesh
@@ -169,19 +354,26 @@ impl SceneGraph {
             .into_iter()
             .map(|primitive| PrimitiveHandle {
                 geometry_index: geometries.add(GeometryId::new_v4(), Geometry::Primitive(primitive)),
-                material_index: ComponentId::new(0),
+                material_index: Self::FALLBACK_MATERIAL,
+                default_material: Self::FALLBACK_MATERIAL,
+                variant_materials: Vec::new(),
             })
             .collect::<Vec<_>>();
         let debug_id = RenderId::new_v4();
         renderables.add(debug_id, Renderable::Mesh(handles));
 
+        let probes = ReflectionProbes::new(context);
+        let irradiance_volume = IrradianceVolume::new(context);
+        let shadow_maps = ShadowMaps::new(context);
         let bind_group = Self::create_bind_group(
             &[
                 transforms.buffer(),
                 normals.buffer(),
                 lights.buffer(),
                 lights_transform_index.buffer(),
+                joint_palettes.buffer(),
             ],
+            &shadow_maps,
             &layout,
             context,
         );
@@ -195,13 +387,33 @@ impl SceneGraph {
             lights_transform_index,
             normals,
             node_normal_index,
+            joint_palettes,
 
             geometries,
             materials,
 
-            environment_map: EnvironmentMap::default(context),
+            environment_map: EnvironmentMap::default(&probes, &irradiance_volume, context),
+            probes,
+            irradiance_volume,
+            shadow_maps,
             instance_pool,
             render_batches: Vec::new(),
+            batch_instances: BatchInstances::default(),
+            culled_view: None,
+            is_culling_dirty: false,
+            selection: None,
+            selection_batches: Vec::new(),
+            visibility: HashMap::new(),
+            ghost_batches: Vec::new(),
+            shadow_batches: Vec::new(),
+            layers: HashMap::new(),
+            view_layers: RenderLayers::ALL,
+            tags: HashMap::new(),
+            highlight: None,
+            skeletons: HashMap::new(),
+            material_variants: HashMap::new(),
+            animation_start: Instant::now(),
+            animation_phases: HashMap::new(),
             debug_id,
             bind_group,
             layout,
@@ -213,16 +425,7 @@ impl SceneGraph {
     }
 
     pub fn add_mesh(&mut self, mesh: Mesh, material_components: &[ComponentId<Material>]) -> RenderId {
-        let handles = mesh
-            .primitives
-            .into_iter()
-            .map(|primitive| PrimitiveHandle {
-                material_index: material_components[primitive.material_index],
-                geometry_index: self.add_geometry(Geometry::Primitive(primitive)),
-            })
-            .collect::<Vec<_>>();
-
-        let renderable = Renderable::Mesh(handles);
+        let renderable = Renderable::Mesh(self.add_primitives(mesh, material_components));
         self.add_renderable(renderable)
     }
 
@@ -233,6 +436,109 @@ impl SceneGraph {
         self.add_renderable(renderable)
     }
 
+    fn add_primitives(&mut self, mesh: Mesh, material_components: &[ComponentId<Material>]) -> Vec<PrimitiveHandle> {
+        let material = |index: usize| {
+            material_components
+                .get(index)
+                .copied()
+                .unwrap_or(Self::FALLBACK_MATERIAL)
+        };
+        mesh.primitives
+            .into_iter()
+            .map(|primitive| {
+                let default_material = material(primitive.material_index);
+                PrimitiveHandle {
+                    material_index: default_material,
+                    default_material,
+                    variant_materials: primitive
+                        .variants
+                        .iter()
+                        .map(|mapping| (mapping.variant, material(mapping.material_index)))
+                        .collect(),
+                    geometry_index: self.add_geometry(Geometry::Primitive(primitive)),
+                }
+            })
+            .collect()
+    }
+
+    // Returns the materials the old primitives used, see `remove_unused_materials`
+    pub fn replace_mesh(
+        &mut self,
+        render_id: RenderId,
+        mesh: Mesh,
+        material_components: &[ComponentId<Material>],
+    ) -> Vec<ComponentId<Material>> {
+        let mut handles = self.add_primitives(mesh, material_components);
+        let variant = self.material_variants.get(&render_id).copied();
+        handles.iter_mut().for_each(|handle| handle.select_variant(variant));
+        self.replace_renderable(render_id, Renderable::Mesh(handles))
+    }
+
+    // Draws the primitives of the renderable with the materials of a KHR_materials_variants variant, None puts back
+    // their default materials
+    pub fn set_material_variant(&mut self, render_id: RenderId, variant: Option<usize>) {
+        match variant {
+            Some(variant) => self.material_variants.insert(render_id, variant),
+            None => self.material_variants.remove(&render_id),
+        };
+        if let Some(Renderable::Mesh(handles)) = self.renderables.get_mut(&render_id) {
+            handles.iter_mut().for_each(|handle| handle.select_variant(variant));
+        }
+    }
+
+    pub fn replace_pointcloud(&mut self, render_id: RenderId, pointcloud: Pointcloud) {
+        let renderable = Renderable::Pointcloud(PointcloudHandle {
+            geometry_index: self.add_geometry(Geometry::Pointcloud(pointcloud)),
+        });
+        self.replace_renderable(render_id, renderable);
+    }
+
+    // Nodes of the render id draw the new renderable once the batches are rebuilt. Its old geometry is removed, the
+    // materials may be shared with other renderables of the asset so they are only returned
+    fn replace_renderable(&mut self, render_id: RenderId, renderable: Renderable) -> Vec<ComponentId<Material>> {
+        let Some(slot) = self.renderables.get_mut(&render_id) else {
+            self.renderables.add(render_id, renderable);
+            return Vec::new();
+        };
+
+        match std::mem::replace(slot, renderable) {
+            Renderable::Mesh(handles) => handles
+                .into_iter()
+                .map(|handle| {
+                    self.geometries.remove_by_id(handle.geometry_index);
+                    handle.material_index
+                })
+                .collect(),
+            Renderable::Pointcloud(handle) => {
+                self.geometries.remove_by_id(handle.geometry_index);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn remove_unused_materials(&mut self, materials: impl IntoIterator<Item = ComponentId<Material>>) {
+        let used = self
+            .renderables
+            .iter_with_index()
+            .flat_map(|(_, _, renderable)| match renderable {
+                Renderable::Mesh(handles) => handles
+                    .iter()
+                    .flat_map(|handle| {
+                        let variants = handle.variant_materials.iter().map(|(_, material)| material.index());
+                        [handle.default_material.index()].into_iter().chain(variants)
+                    })
+                    .collect(),
+                Renderable::Pointcloud(_) => Vec::new(),
+            })
+            .collect::<HashSet<_>>();
+
+        for material in materials {
+            if material.index() != Self::FALLBACK_MATERIAL.index() && !used.contains(&material.index()) {
+                self.materials.remove_by_id(material);
+            }
+        }
+    }
+
     pub fn add_geometry(&mut self, geometry: Geometry) -> ComponentId<Geometry> {
         self.geometries.add(GeometryId::new_v4(), geometry)
     }
@@ -254,6 +560,72 @@ impl SceneGraph {
         let normal_index = self.normals.add(entity, normal_uniform, context);
         self.node_normal_index.link(node_index, normal_index, context);
 
+        if let Some(skeleton) = self.skeletons.get(&handle) {
+            let palette = skeleton.palette(self.animation_time(&entity));
+            self.joint_palettes.add(entity, palette, context);
+        }
+
+        self.build_render_batches(context);
+    }
+
+    // Works for lights too. Its transform and normal slots are reused by the next nodes
+    pub fn remove_node(&mut self, entity: Uuid, context: &RenderContext) {
+        self.forget_node(entity, context);
+        self.build_render_batches(context);
+    }
+
+    fn forget_node(&mut self, entity: Uuid, context: &RenderContext) {
+        self.nodes.remove(&entity);
+        self.transforms.remove(&entity);
+        self.normals.remove(&entity);
+        self.joint_palettes.remove(&entity);
+        self.animation_phases.remove(&entity);
+        self.visibility.remove(&entity);
+        self.layers.remove(&entity);
+        self.tags.remove(&entity);
+        // Shaders read every slot, a removed light goes dark until its slot is reused
+        if self.lights.get(&entity).is_some() {
+            self.lights.add(entity, bytemuck::Zeroable::zeroed(), context);
+            self.lights.remove(&entity);
+        }
+        if self.selection == Some(entity) {
+            self.selection = None;
+        }
+    }
+
+    // Removes the renderables along with every node drawing them, their geometry and the materials no other
+    // renderable uses, which frees their buffers and textures. The batches are rebuilt from the start of the instance
+    // pool, so the instances left are packed together again
+    pub fn unload(&mut self, render_ids: &[RenderId], context: &RenderContext) {
+        let entities = self
+            .nodes
+            .iter_with_index()
+            .filter(|(_, _, render_id)| render_ids.contains(render_id))
+            .map(|(entity, _, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in entities {
+            self.forget_node(entity, context);
+        }
+
+        let mut materials = Vec::new();
+        for render_id in render_ids.iter().filter(|&&render_id| render_id != self.debug_id) {
+            match self.renderables.get(render_id) {
+                Some(Renderable::Mesh(handles)) => {
+                    for handle in handles {
+                        self.geometries.remove_by_id(handle.geometry_index);
+                        materials.push(handle.default_material);
+                        materials.extend(handle.variant_materials.iter().map(|&(_, material)| material));
+                    }
+                }
+                Some(Renderable::Pointcloud(handle)) => self.geometries.remove_by_id(handle.geometry_index),
+                None => {}
+            }
+            self.renderables.remove(render_id);
+            self.skeletons.remove(render_id);
+            self.material_variants.remove(render_id);
+        }
+
+        self.remove_unused_materials(materials);
         self.build_render_batches(context);
     }
 
@@ -264,6 +636,181 @@ impl SceneGraph {
         self.lights_transform_index.link(light_index, transform_index, context);
     }
 
+    // The skin of a mesh renderable, None for a rigid one. Entities already drawing it get a palette or lose theirs,
+    // the batches pick up the change when they're rebuilt
+    pub fn set_skeleton(&mut self, render_id: RenderId, skeleton: Option<Skeleton>, context: &RenderContext) {
+        match skeleton {
+            Some(skeleton) => self.skeletons.insert(render_id, skeleton),
+            None => self.skeletons.remove(&render_id),
+        };
+
+        let entities = self
+            .nodes
+            .iter_with_index()
+            .filter(|(_, _, node_render_id)| **node_render_id == render_id)
+            .map(|(entity, _, _)| *entity)
+            .collect::<Vec<_>>();
+        for entity in entities {
+            let palette = self
+                .skeletons
+                .get(&render_id)
+                .map(|skeleton| skeleton.palette(self.animation_time(&entity)));
+            match palette {
+                Some(palette) => {
+                    self.joint_palettes.add(entity, palette, context);
+                }
+                None => self.joint_palettes.remove(&entity),
+            }
+        }
+    }
+
+    // Poses the entity's skin right away, so a paused scene shows the new phase too
+    pub fn set_animation_phase(&mut self, entity: Uuid, phase: AnimationPhase, context: &RenderContext) {
+        if phase == AnimationPhase::default() {
+            self.animation_phases.remove(&entity);
+        } else {
+            self.animation_phases.insert(entity, phase);
+        }
+
+        let palette = self
+            .nodes
+            .get(&entity)
+            .and_then(|render_id| self.skeletons.get(render_id))
+            .map(|skeleton| skeleton.palette(self.animation_time(&entity)));
+        if let Some(palette) = palette {
+            self.joint_palettes.set(&entity, palette, context);
+        }
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.skeletons.values().any(Skeleton::is_animated)
+    }
+
+    // Poses every animated skin for the current time, true when any of them moved
+    pub fn animate(&mut self, context: &RenderContext) -> bool {
+        if !self.is_animating() {
+            return false;
+        }
+
+        crate::profile_scope!("Animate skins");
+        // Copies of a renderable in the same phase share one pose, so a crowd only poses each phase once
+        let clock = self.animation_clock();
+        let mut palettes = HashMap::new();
+        let mut entities = Vec::new();
+        for (entity, _, render_id) in self.nodes.iter_with_index() {
+            let Some(skeleton) = self.skeletons.get(render_id).filter(|skeleton| skeleton.is_animated()) else {
+                continue;
+            };
+            let time = self
+                .animation_phases
+                .get(entity)
+                .map_or(clock, |phase| phase.time(clock));
+            let palette = *palettes
+                .entry((*render_id, time.to_bits()))
+                .or_insert_with(|| skeleton.palette(time));
+            entities.push((*entity, palette));
+        }
+        for (entity, palette) in entities {
+            self.joint_palettes.set(&entity, palette, context);
+        }
+        true
+    }
+
+    fn animation_clock(&self) -> f32 {
+        self.animation_start.elapsed().as_secs_f32()
+    }
+
+    // Seconds into the clip of the entity's skin
+    fn animation_time(&self, entity: &Uuid) -> f32 {
+        let clock = self.animation_clock();
+        self.animation_phases
+            .get(entity)
+            .map_or(clock, |phase| phase.time(clock))
+    }
+
+    pub fn set_selection(&mut self, entity: Option<Uuid>, context: &RenderContext) {
+        self.selection = entity;
+        self.build_render_batches(context);
+    }
+
+    pub fn set_visibility(&mut self, changes: Vec<(Uuid, Visibility)>, context: &RenderContext) {
+        for (entity, visibility) in changes {
+            if visibility == Visibility::Visible {
+                self.visibility.remove(&entity);
+            } else {
+                self.visibility.insert(entity, visibility);
+            }
+        }
+        self.build_render_batches(context);
+    }
+
+    pub fn visibility(&self, entity: &Uuid) -> Visibility {
+        self.visibility.get(entity).copied().unwrap_or_default()
+    }
+
+    pub fn set_layers(&mut self, entity: Uuid, layers: RenderLayers, context: &RenderContext) {
+        if layers == RenderLayers::default() {
+            self.layers.remove(&entity);
+        } else {
+            self.layers.insert(entity, layers);
+        }
+        self.build_render_batches(context);
+    }
+
+    pub fn set_view_layers(&mut self, layers: RenderLayers, context: &RenderContext) {
+        if layers != self.view_layers {
+            self.view_layers = layers;
+            self.build_render_batches(context);
+        }
+    }
+
+    pub fn set_tags(&mut self, entity: Uuid, tags: EntityTags, context: &RenderContext) {
+        self.tags.insert(entity, tags);
+        // A retagged entity can enter or leave the highlighted set
+        if self.highlight.is_some() {
+            self.build_render_batches(context);
+        }
+    }
+
+    pub fn set_highlight(&mut self, filter: Option<TagFilter>, context: &RenderContext) {
+        if filter != self.highlight {
+            self.highlight = filter;
+            self.build_render_batches(context);
+        }
+    }
+
+    fn highlighted(&self) -> HashSet<Uuid> {
+        let Some(filter) = &self.highlight else {
+            return HashSet::new();
+        };
+        let mut entities: Option<HashSet<Uuid>> = None;
+        if let Some(label) = &filter.label {
+            entities = Some(self.find_by_label(label).collect());
+        }
+        if let Some(tag) = &filter.tag {
+            let tagged = self.find_by_tag(tag);
+            entities = Some(match entities {
+                Some(labelled) => tagged.filter(|entity| labelled.contains(entity)).collect(),
+                None => tagged.collect(),
+            });
+        }
+        entities.unwrap_or_default()
+    }
+
+    pub fn find_by_label<'a>(&'a self, label: &'a str) -> impl Iterator<Item = Uuid> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(_, tags)| tags.label.as_deref() == Some(label))
+            .map(|(entity, _)| *entity)
+    }
+
+    pub fn find_by_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = Uuid> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(_, tags)| tags.tags.contains(tag))
+            .map(|(entity, _)| *entity)
+    }
+
     pub fn set_environment_map(&mut self, environment_map: EnvironmentMap) {
         self.environment_map = environment_map;
     }
@@ -276,32 +823,70 @@ impl SceneGraph {
         &self.bind_group
     }
 
+    pub fn has_transmissive(&self) -> bool {
+        self.materials
+            .components()
+            .iter()
+            .flatten()
+            .any(Material::is_transmissive)
+    }
+
+    pub fn max_pointcloud_points(&self) -> u32 {
+        self.geometries
+            .components()
+            .iter()
+            .flatten()
+            .filter_map(|geometry| match geometry {
+                Geometry::Pointcloud(pointcloud) => Some(pointcloud.num_points),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn build_render_batches(&mut self, context: &RenderContext) {
         let mut batches: HashMap<BatchKey, Vec<Instance>> = HashMap::new();
+        let mut selected: HashMap<BatchKey, Vec<Instance>> = HashMap::new();
+        let mut ghosted: HashMap<BatchKey, Vec<Instance>> = HashMap::new();
+        let highlighted = self.highlighted();
 
         // Nodes
         for (entity, render_index, render_id) in self.nodes.iter_with_index() {
+            let visibility = self.visibility(entity);
+            let layers = self.layers.get(entity).copied().unwrap_or_default();
+            if visibility == Visibility::Hidden || !layers.intersects(self.view_layers) {
+                continue;
+            }
+
             if let Some(transform_index) = self.node_transform_index.get_mapping(render_index)
                 && let Some(normal_index) = self.node_normal_index.get_mapping(render_index)
             {
                 if let Some(renderable) = self.renderables.get(render_id) {
-                    let pipeline_id = renderable.as_str();
                     let key = BatchKey {
                         render_id: *render_id,
-                        pipeline_id,
+                        pipeline: renderable.pipeline_key(&self.geometries),
                     };
 
-                    batches.entry(key).or_default().push(Instance {
+                    let instance = Instance {
                         transform_index,
                         normal_index,
-                    });
+                        palette_index: self.joint_palettes.get_index(entity).map_or(0, |index| index.index()),
+                    };
+                    if self.selection == Some(*entity) || highlighted.contains(entity) {
+                        selected.entry(key.clone()).or_default().push(instance);
+                    }
+                    if visibility == Visibility::Ghosted {
+                        ghosted.entry(key).or_default().push(instance);
+                    } else {
+                        batches.entry(key).or_default().push(instance);
+                    }
                 }
             }
         }
 
         // Lights - Debug
         for (light_id, light_index, uniform) in self.lights.iter_with_index() {
-            if uniform.kind != 1 {
+            if uniform.kind != 1 || !self.view_layers.intersects(RenderLayers::DEBUG) {
                 continue;
             }
 
@@ -309,20 +894,105 @@ impl SceneGraph {
                 if let Some(renderable) = self.renderables.get(&self.debug_id) {
                     let key = BatchKey {
                         render_id: self.debug_id,
-                        pipeline_id: "light",
+                        pipeline: PipelineKey::LIGHT,
                     };
 
                     batches.entry(key).or_default().push(Instance {
                         transform_index,
                         normal_index: 0,
+                        palette_index: 0,
                     });
                 }
             }
         }
 
+        self.batch_instances = BatchInstances {
+            render: batches,
+            selected,
+            ghosted,
+        };
+        self.upload_instances(None, context);
+    }
+
+    // Drops the mesh instances outside the view, or brings every instance back with None. Only does the work again
+    // when the view or a transform changed. The outline and ghost batches are never culled
+    pub fn cull(&mut self, view_projection: Option<glam::Mat4>, context: &RenderContext) {
+        crate::profile_scope!("Frustum culling");
+        if self.culled_view == view_projection && !self.is_culling_dirty {
+            return;
+        }
+        self.upload_instances(view_projection, context);
+    }
+
+    // Moves an entity or light, the batches are culled again for the next frame
+    pub fn set_transform(&mut self, entity: &Uuid, transform: TransformUniform, context: &RenderContext) {
+        self.transforms.set(entity, transform, context);
+        self.is_culling_dirty = true;
+    }
+
+    // Everything goes in again from the start of the pool, so the ranges of the previous upload are free to reuse
+    fn upload_instances(&mut self, view_projection: Option<glam::Mat4>, context: &RenderContext) {
+        let frustum = view_projection.map(Frustum::from_view_projection);
+        let batches = std::mem::take(&mut self.batch_instances);
+        let visible = batches
+            .render
+            .iter()
+            .map(|(key, instances)| {
+                // Skinned meshes move out of their rest bounds, they're never culled
+                let bounds = self
+                    .renderables
+                    .get(&key.render_id)
+                    .filter(|_| !self.skeletons.contains_key(&key.render_id))
+                    .and_then(|renderable| renderable.bounds(&self.geometries));
+                let instances = match (&frustum, bounds) {
+                    (Some(frustum), Some(bounds)) => instances
+                        .iter()
+                        .filter(|instance| {
+                            self.transforms
+                                .get_by_index(instance.transform_index as usize)
+                                .is_some_and(|transform| frustum.intersects(&bounds.transform(transform.to_mat4())))
+                        })
+                        .copied()
+                        .collect(),
+                    _ => instances.clone(),
+                };
+                (key, instances)
+            })
+            .filter(|(_, instances): &(_, Vec<_>)| !instances.is_empty())
+            .collect::<Vec<_>>();
+
+        self.instance_pool.reset();
+        let mut render_batches = self.upload_batches(visible, context);
+        render_batches.sort_by_key(|batch| (batch.key.pipeline.shader, batch.key.render_id));
+        self.render_batches = render_batches;
+        self.selection_batches = self.upload_batches(&batches.selected, context);
+        self.ghost_batches = self.upload_batches(&batches.ghosted, context);
+        self.shadow_batches = match self.shadow_maps.has_casters() {
+            true => {
+                let casting = batches
+                    .render
+                    .iter()
+                    .filter(|(key, _)| key.pipeline.shader == ShaderKind::Mesh);
+                self.upload_batches(casting, context)
+            }
+            false => Vec::new(),
+        };
+
+        self.batch_instances = batches;
+        self.culled_view = view_projection;
+        self.is_culling_dirty = false;
+    }
+
+    fn upload_batches<'a>(
+        &mut self,
+        batches: impl IntoIterator<Item = (&'a BatchKey, impl AsRef<[Instance]>)>,
+        context: &RenderContext,
+    ) -> Vec<RenderBatch> {
         let mut render_batches = Vec::new();
         for (key, instances) in batches {
-            let instance_offset = self.instance_pool.upload(&instances, context);
+            let instances = instances.as_ref();
+            let key = key.clone();
+            let instance_offset = self.instance_pool.upload(instances, context);
             let instance_count = instances.len();
 
             render_batches.push(RenderBatch {
@@ -332,17 +1002,52 @@ impl SceneGraph {
             })
         }
 
-        render_batches.sort_by_key(|batch| (batch.key.pipeline_id, batch.key.render_id));
-        self.render_batches = render_batches;
+        render_batches
+    }
+
+    // Fits the shadow maps to the camera and the lights casting shadows. The shadow batches are only uploaded while
+    // there are casters, so the instances go in again when the first one appears or the last one leaves
+    pub fn update_shadows(&mut self, view_projection: glam::Mat4, position: glam::Vec3, context: &RenderContext) {
+        crate::profile_scope!("Update shadows");
+        let mut casters = self
+            .lights
+            .iter_with_index()
+            .filter(|(_, _, light)| light.casts_shadows() && light.intensity > 0.0)
+            .filter_map(|(_, index, light)| {
+                let transform_index = self.lights_transform_index.get_mapping(index)?;
+                Some(ShadowCaster {
+                    index,
+                    kind: light.kind,
+                    range: light.range,
+                    transform: self.transforms.get_by_index(transform_index as usize)?.to_mat4(),
+                })
+            })
+            .collect::<Vec<_>>();
+        casters.sort_by_key(|caster| caster.index);
+
+        if self.shadow_maps.update(&casters, view_projection, position, context) {
+            self.is_culling_dirty = true;
+        }
+    }
+
+    pub fn has_ghosts(&self) -> bool {
+        !self.ghost_batches.is_empty()
+    }
+
+    pub fn has_selection(&self) -> bool {
+        !self.selection_batches.is_empty()
     }
 
     pub fn sync(&mut self, context: &RenderContext) {
+        crate::profile_scope!("Sync scene");
         if self.transforms.is_dirty()
             || self.lights.is_dirty()
             || self.node_transform_index.is_dirty()
             || self.lights_transform_index.is_dirty()
             || self.normals.is_dirty()
             || self.node_normal_index.is_dirty()
+            || self.joint_palettes.is_dirty()
+            || self.shadow_maps.is_dirty()
         {
             let bind_group = Self::create_bind_group(
                 &[
@@ -350,7 +1055,9 @@ impl SceneGraph {
                     self.normals.buffer(),
                     self.lights.buffer(),
                     self.lights_transform_index.buffer(),
+                    self.joint_palettes.buffer(),
                 ],
+                &self.shadow_maps,
                 &self.layout,
                 context,
             );
@@ -361,6 +1068,7 @@ impl SceneGraph {
 
     fn create_bind_group(
         buffers: &[&wgpu::Buffer],
+        shadow_maps: &ShadowMaps,
         layout: &wgpu::BindGroupLayout,
         context: &RenderContext,
     ) -> wgpu::BindGroup {
@@ -371,6 +1079,7 @@ impl SceneGraph {
                 binding: index as u32,
                 resource: buffer.as_entire_binding(),
             })
+            .chain(shadow_maps.bind_group_entries(buffers.len() as u32))
             .collect::<Vec<_>>();
 
         context.device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -387,6 +1096,7 @@ pub trait DrawScene<'a> {
         scene: &'a SceneGraph,
         camera_bind_group: &'a wgpu::BindGroup,
         pipeline_cache: &'a PipelineCache,
+        pass: ScenePass,
     );
 }
 
@@ -399,12 +1109,9 @@ where
         scene: &'b SceneGraph,
         camera_bind_group: &'b wgpu::BindGroup,
         pipeline_cache: &'b PipelineCache,
+        pass: ScenePass,
     ) {
         self.set_bind_group(1, camera_bind_group, &[]);
-        
-        self.set_pipeline(scene.environment_map.pipeline());
-        self.set_bind_group(0, scene.environment_map.bind_group(), &[]);
-        self.draw(0..3, 0..1);
 
         self.set_bind_group(2, scene.bind_group(), &[]);
         self.set_bind_group(3, scene.environment_map.bind_group(), &[]);
@@ -412,28 +1119,43 @@ where
         self.set_vertex_buffer(7, scene.instance_pool.buffer().slice(..));
 
         for batch in &scene.render_batches {
-            let pipeline = pipeline_cache.get(batch.key.pipeline_id).unwrap();
+            // Nothing compatible compiled yet
+            let Some(pipeline) = pipeline_cache.get(&batch.key.pipeline) else {
+                continue;
+            };
             self.set_pipeline(pipeline);
 
             if let Some(renderable) = scene.renderables.get(&batch.key.render_id) {
                 match renderable {
+                    Renderable::Mesh(_) if matches!(pass, ScenePass::PointChunk { .. }) => {}
                     Renderable::Mesh(handles) => {
                         self.set_vertex_buffer(7, scene.instance_pool.buffer().slice(..));
                         handles.iter().for_each(|handle| {
                             let geometry = scene.geometries.get_by_id(handle.geometry_index).unwrap();
                             let material = scene.materials.get_by_id(handle.material_index).unwrap();
 
+                            let is_transmissive =
+                                batch.key.pipeline.shader == ShaderKind::Mesh && material.is_transmissive();
+                            if is_transmissive != (pass == ScenePass::Transmissive) {
+                                return;
+                            }
+
                             if let Geometry::Primitive(primitive) = geometry {
                                 self.draw_primitive_instanced(primitive, material, batch.instance_range());
                             }
                         });
                     }
+                    Renderable::Pointcloud(_) if matches!(pass, ScenePass::Transmissive | ScenePass::Meshes) => {}
                     Renderable::Pointcloud(handle) => {
-                        self.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+                        self.set_vertex_buffer(2, scene.instance_pool.buffer().slice(..));
                         let geometry = scene.geometries.get_by_id(handle.geometry_index).unwrap();
 
                         if let Geometry::Pointcloud(pointcloud) = geometry {
-                            self.draw_pointcloud(pointcloud, batch.instance_range());
+                            let points = match pass {
+                                ScenePass::PointChunk { index, budget } => pointcloud.chunk(index, budget),
+                                _ => 0..pointcloud.num_points,
+                            };
+                            self.draw_pointcloud(pointcloud, points, batch.instance_range());
                         }
                     }
                 }
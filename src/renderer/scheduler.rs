@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use instant::Instant;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TaskPriority {
+    // Speculative loads nobody is waiting on yet
+    Prefetch,
+    // Started by the user, ahead of everything else in the queue
+    User,
+}
+
+struct Queued<T> {
+    item: T,
+    priority: TaskPriority,
+    enqueued: Instant,
+}
+
+// Priority queue with a cap on how many tasks run at once, shared by the loader threads and the web worker pool.
+// Lower priorities move up once they've waited long enough, so a steady stream of user loads can't starve them
+pub struct Scheduler<T> {
+    queue: Vec<Queued<T>>,
+    running: usize,
+    max_concurrency: usize,
+}
+
+impl<T> Scheduler<T> {
+    const STARVATION_TIME: Duration = Duration::from_secs(10);
+
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            queue: Vec::new(),
+            running: 0,
+            max_concurrency: max_concurrency.max(1),
+        }
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    pub fn set_max_concurrency(&mut self, max_concurrency: usize) {
+        self.max_concurrency = max_concurrency.max(1);
+    }
+
+    #[cfg(target_family = "wasm")]
+    pub fn has_queued(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    pub fn push(&mut self, item: T, priority: TaskPriority) {
+        self.queue.push(Queued {
+            item,
+            priority,
+            enqueued: Instant::now(),
+        });
+    }
+
+    // Highest effective priority first, oldest first within a priority. Counts as running until `finish`
+    pub fn pop(&mut self) -> Option<T> {
+        if self.running >= self.max_concurrency {
+            return None;
+        }
+
+        let index = self
+            .queue
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, queued)| (self.effective_priority(queued), std::cmp::Reverse(*index)))
+            .map(|(index, _)| index)?;
+
+        self.running += 1;
+        Some(self.queue.remove(index).item)
+    }
+
+    pub fn finish(&mut self) {
+        self.running = self.running.saturating_sub(1);
+    }
+
+    fn effective_priority(&self, queued: &Queued<T>) -> TaskPriority {
+        if queued.enqueued.elapsed() >= Self::STARVATION_TIME {
+            TaskPriority::User
+        } else {
+            queued.priority
+        }
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+type Job = Box<dyn FnOnce() + Send>;
+
+// Native loads get a thread each, but only as many as the scheduler lets run at once
+#[cfg(not(target_family = "wasm"))]
+#[derive(Clone)]
+pub struct LoadThreads {
+    scheduler: std::sync::Arc<std::sync::Mutex<Scheduler<Job>>>,
+}
+
+#[cfg(not(target_family = "wasm"))]
+impl LoadThreads {
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            scheduler: std::sync::Arc::new(std::sync::Mutex::new(Scheduler::new(max_concurrency))),
+        }
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.scheduler.lock().unwrap().max_concurrency()
+    }
+
+    pub fn set_max_concurrency(&self, max_concurrency: usize) {
+        self.scheduler.lock().unwrap().set_max_concurrency(max_concurrency);
+        self.dispatch();
+    }
+
+    pub fn spawn(&self, priority: TaskPriority, job: impl FnOnce() + Send + 'static) {
+        self.scheduler.lock().unwrap().push(Box::new(job), priority);
+        self.dispatch();
+    }
+
+    fn dispatch(&self) {
+        loop {
+            let Some(job) = self.scheduler.lock().unwrap().pop() else {
+                break;
+            };
+
+            let slot = RunningSlot(self.clone());
+            std::thread::spawn(move || {
+                let _slot = slot;
+                job();
+            });
+        }
+    }
+}
+
+// Frees the slot when the job ends, also when a failed load panics its thread
+#[cfg(not(target_family = "wasm"))]
+struct RunningSlot(LoadThreads);
+
+#[cfg(not(target_family = "wasm"))]
+impl Drop for RunningSlot {
+    fn drop(&mut self) {
+        self.0.scheduler.lock().unwrap().finish();
+        self.0.dispatch();
+    }
+}
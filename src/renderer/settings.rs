@@ -0,0 +1,442 @@
+use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    locale::Language,
+    renderer::{
+        bvh::Aabb,
+        context::RenderContext,
+        ramp::{ColorRamp, RampStop, RampTexture},
+        ui::UiStyle,
+    },
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub render_mode: RenderMode,
+    pub max_bounces: u32,
+    pub displacement_scale: f32,
+    pub parallax_quality: ParallaxQuality,
+    pub environment_sampling: EnvironmentSampling,
+    pub environment_samples: u32,
+    pub pointcloud_shading: PointcloudShading,
+    // Points of each pointcloud drawn per frame, the rest fill in while the view holds still. Zero draws them all
+    pub points_per_frame: u32,
+    // Of the directional light cascades, point and spot light cubes get half
+    pub shadow_map_size: u32,
+    pub color_ramp: ColorRamp,
+    pub custom_ramp: Vec<RampStop>,
+    // Scalar values mapped to the ends of the ramp
+    pub ramp_range: [f32; 2],
+    // Entity details next to the cursor, not used by the renderer
+    pub show_tooltips: bool,
+    pub show_irradiance_probes: bool,
+    // Skips the environment background, only composited as such where the surface supports alpha
+    pub transparent_background: bool,
+    // Last applied preset, cleared once a bundled setting is changed by hand
+    pub quality: Option<QualityPreset>,
+    // Steps between presets based on frame times, not used by the renderer
+    pub auto_quality: bool,
+    // Not used by the renderer either
+    pub language: Language,
+    pub ui_style: UiStyle,
+    pub background_mode: BackgroundMode,
+    // Only draws when something changed, not used by the renderer
+    pub render_on_demand: bool,
+    // The renderer only takes the exposure and lens distortion from it, the app the field of view
+    pub physical_camera: PhysicalCamera,
+    // Leaves out mesh instances whose bounds are outside the camera frustum
+    pub frustum_culling: bool,
+    // Poses skinned meshes for picking every frame a clip plays, otherwise they're picked as last posed when the
+    // scene changed
+    pub animated_picking: bool,
+    // Not used by the renderer, the app's projection takes them
+    pub clip_planes: ClipPlanes,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            render_mode: RenderMode::Raster,
+            max_bounces: 4,
+            displacement_scale: 1.0,
+            parallax_quality: ParallaxQuality::Medium,
+            environment_sampling: EnvironmentSampling::Prefiltered,
+            environment_samples: 4,
+            pointcloud_shading: PointcloudShading::Color,
+            points_per_frame: 2_000_000,
+            shadow_map_size: 2048,
+            color_ramp: ColorRamp::Viridis,
+            custom_ramp: ColorRamp::default_stops(),
+            ramp_range: [0.0, 1.0],
+            show_tooltips: true,
+            show_irradiance_probes: false,
+            transparent_background: false,
+            quality: Some(QualityPreset::Medium),
+            auto_quality: false,
+            language: Language::default(),
+            ui_style: UiStyle::default(),
+            background_mode: BackgroundMode::Throttled,
+            render_on_demand: false,
+            physical_camera: PhysicalCamera::default(),
+            frustum_culling: true,
+            animated_picking: true,
+            clip_planes: ClipPlanes::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    pub const MAX_ENVIRONMENT_SAMPLES: u32 = 64;
+    pub const MAX_BOUNCES: u32 = 16;
+    pub const SHADOW_MAP_SIZES: [u32; 4] = [512, 1024, 2048, 4096];
+    // While idle on demand, how often renderer events, remote calls and file changes are checked for
+    pub const ON_DEMAND_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+    pub fn to_uniform(&self, frame_index: u32) -> SettingsUniform {
+        SettingsUniform {
+            displacement_scale: self.displacement_scale,
+            parallax_step_scale: self.parallax_quality.step_scale(),
+            environment_sampling: self.environment_sampling as u32,
+            environment_samples: self.environment_samples.clamp(1, Self::MAX_ENVIRONMENT_SAMPLES),
+            frame_index,
+            pointcloud_shading: self.pointcloud_shading as u32,
+            ramp_min: self.ramp_range[0],
+            ramp_max: self.ramp_range[1],
+        }
+    }
+
+    pub fn is_accumulating(&self) -> bool {
+        self.render_mode == RenderMode::Raster && self.environment_sampling == EnvironmentSampling::Stochastic
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenderMode {
+    Raster,
+    PathTraced,
+    Sketch,
+}
+
+impl RenderMode {
+    pub const ALL: [Self; 3] = [Self::Raster, Self::PathTraced, Self::Sketch];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Raster => "Raster",
+            Self::PathTraced => "Path traced",
+            Self::Sketch => "Shader sketch",
+        }
+    }
+}
+
+// Bundles of the settings that trade image quality for frame time and memory. Modes that change how the image looks
+// rather than what it costs, like stochastic environment sampling, are left to the user
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+}
+
+impl QualityPreset {
+    pub const ALL: [Self; 4] = [Self::Low, Self::Medium, Self::High, Self::Ultra];
+
+    // Starting point for the auto mode, frame times move it from there
+    pub fn from_adapter(info: &wgpu::AdapterInfo, limits: &wgpu::Limits) -> Self {
+        match info.device_type {
+            wgpu::DeviceType::Cpu => Self::Low,
+            wgpu::DeviceType::DiscreteGpu if limits.max_texture_dimension_2d >= 16384 => Self::Ultra,
+            wgpu::DeviceType::DiscreteGpu => Self::High,
+            _ if limits.max_storage_buffer_binding_size < 1 << 27 => Self::Low,
+            _ => Self::Medium,
+        }
+    }
+
+    pub fn apply(&self, settings: &mut RenderSettings) {
+        let (parallax_quality, max_bounces, points_per_frame, shadow_map_size) = match self {
+            Self::Low => (ParallaxQuality::Off, 2, 1_000_000, 1024),
+            Self::Medium => (ParallaxQuality::Medium, 4, 2_000_000, 2048),
+            Self::High => (ParallaxQuality::High, 8, 4_000_000, 2048),
+            Self::Ultra => (ParallaxQuality::High, 12, 0, 4096),
+        };
+
+        settings.parallax_quality = parallax_quality;
+        settings.max_bounces = max_bounces;
+        settings.points_per_frame = points_per_frame;
+        settings.shadow_map_size = shadow_map_size;
+        settings.quality = Some(*self);
+    }
+
+    pub fn lower(&self) -> Option<Self> {
+        Self::ALL.get((*self as usize).checked_sub(1)?).copied()
+    }
+
+    pub fn higher(&self) -> Option<Self> {
+        Self::ALL.get(*self as usize + 1).copied()
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::Medium => "Medium",
+            Self::High => "High",
+            Self::Ultra => "Ultra",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParallaxQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl ParallaxQuality {
+    pub const ALL: [Self; 4] = [Self::Off, Self::Low, Self::Medium, Self::High];
+
+    pub fn step_scale(&self) -> f32 {
+        match self {
+            Self::Off => 0.0,
+            Self::Low => 0.5,
+            Self::Medium => 1.0,
+            Self::High => 2.0,
+        }
+    }
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Off => "Off",
+            Self::Low => "Low",
+            Self::Medium => "Medium",
+            Self::High => "High",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnvironmentSampling {
+    Prefiltered = 0,
+    Stochastic = 1,
+}
+
+impl EnvironmentSampling {
+    pub const ALL: [Self; 2] = [Self::Prefiltered, Self::Stochastic];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Prefiltered => "Prefiltered",
+            Self::Stochastic => "Stochastic",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PointcloudShading {
+    Color = 0,
+    // Diffuse lighting from the estimated normals
+    Lit = 1,
+    // Intensity or imported scalar through the color ramp
+    Scalar = 2,
+    // ASPRS class colors, for checking and editing classifications
+    Classification = 3,
+}
+
+impl PointcloudShading {
+    pub const ALL: [Self; 4] = [Self::Color, Self::Lit, Self::Scalar, Self::Classification];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            Self::Color => "Color",
+            Self::Lit => "Lit",
+            Self::Scalar => "Scalar",
+            Self::Classification => "Classification",
+        }
+    }
+}
+
+// How often the window renders while it's unfocused or the tab is hidden, not used by the renderer
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    Full,
+    Throttled,
+    // Waits for the window to come back
+    Paused,
+}
+
+impl BackgroundMode {
+    pub const ALL: [Self; 3] = [Self::Full, Self::Throttled, Self::Paused];
+    pub const THROTTLED_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Full => "Full rate",
+            Self::Throttled => "5 FPS",
+            Self::Paused => "Paused",
+        }
+    }
+}
+
+// Focal length and sensor width in millimeters, shutter speed in seconds. Light intensities in the scene aren't
+// photometric, so exposure is relative to the defaults: twice the ISO or shutter time is twice as bright
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicalCamera {
+    pub enabled: bool,
+    pub focal_length: f32,
+    pub sensor_width: f32,
+    pub iso: f32,
+    pub shutter_speed: f32,
+    pub aperture: f32,
+    // Radial coefficient of a Brown-Conrady model at the image corners, positive values bulge the image outward like
+    // a wide angle lens. Only previewed in the tone map pass, picking and captured buffers stay rectilinear
+    pub distortion: f32,
+}
+
+impl Default for PhysicalCamera {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            focal_length: 35.0,
+            sensor_width: 36.0,
+            iso: 100.0,
+            shutter_speed: 1.0 / 60.0,
+            aperture: 8.0,
+            distortion: 0.0,
+        }
+    }
+}
+
+// Distances from the camera in world units. Auto fits them around everything visible each frame, so large scenes aren't
+// clipped and small ones keep their depth precision
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipPlanes {
+    pub auto: bool,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for ClipPlanes {
+    fn default() -> Self {
+        Self {
+            auto: false,
+            near: 0.1,
+            far: 500.0,
+        }
+    }
+}
+
+impl ClipPlanes {
+    // Of far over near, the depth buffer runs out of precision quickly past it
+    const MAX_RATIO: f32 = 1e5;
+    // Added around the scene, so surfaces right on its bounds aren't clipped
+    const MARGIN: f32 = 0.01;
+
+    // The set planes while auto is off or nothing visible is in front of the camera
+    pub fn fit(&self, view: glam::Mat4, bounds: &Aabb) -> (f32, f32) {
+        if !self.auto || bounds.is_empty() {
+            return (self.near, self.far);
+        }
+
+        // The camera looks down -Z, the near plane stays in front of it when it's inside the bounds
+        let bounds = bounds.transform(view);
+        let far = -bounds.min.z * (1.0 + Self::MARGIN);
+        if far <= 0.0 {
+            return (self.near, self.far);
+        }
+        let near = (-bounds.max.z * (1.0 - Self::MARGIN)).max(far / Self::MAX_RATIO);
+        (near, far)
+    }
+}
+
+impl PhysicalCamera {
+    // 35 mm full frame and common crop sensors
+    pub const SENSORS: [(&'static str, f32); 4] = [
+        ("Full frame", 36.0),
+        ("APS-C", 23.5),
+        ("Micro Four Thirds", 17.3),
+        ("1 inch", 13.2),
+    ];
+
+    // Exposure value at ISO 100
+    pub fn ev100(&self) -> f32 {
+        (self.aperture * self.aperture / self.shutter_speed * 100.0 / self.iso).log2()
+    }
+
+    // Multiplier on the HDR image, one while disabled
+    pub fn exposure(&self) -> f32 {
+        match self.enabled {
+            true => 2.0_f32.powf(Self::default().ev100() - self.ev100()),
+            false => 1.0,
+        }
+    }
+
+    // Focal length and sensor width for the projection while enabled
+    pub fn lens(&self) -> Option<(f32, f32)> {
+        self.enabled.then_some((self.focal_length, self.sensor_width))
+    }
+
+    pub fn lens_distortion(&self) -> f32 {
+        match self.enabled {
+            true => self.distortion,
+            false => 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct SettingsUniform {
+    pub displacement_scale: f32,
+    pub parallax_step_scale: f32,
+    pub environment_sampling: u32,
+    pub environment_samples: u32,
+    pub frame_index: u32,
+    pub pointcloud_shading: u32,
+    pub ramp_min: f32,
+    pub ramp_max: f32,
+}
+
+pub struct SettingsBuffer {
+    buffer: wgpu::Buffer,
+    ramp: RampTexture,
+}
+
+impl SettingsBuffer {
+    pub fn new(settings: &RenderSettings, context: &RenderContext) -> Self {
+        let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Settings buffer"),
+            contents: bytemuck::bytes_of(&settings.to_uniform(0)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let ramp = RampTexture::new(settings.color_ramp, &settings.custom_ramp, context);
+
+        Self { buffer, ramp }
+    }
+
+    pub fn update(&self, settings: &RenderSettings, frame_index: u32, context: &RenderContext) {
+        context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&settings.to_uniform(frame_index)));
+    }
+
+    // Kept out of `update`, which also runs every accumulated frame
+    pub fn update_ramp(&self, settings: &RenderSettings, context: &RenderContext) {
+        self.ramp.update(settings.color_ramp, &settings.custom_ramp, context);
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn ramp(&self) -> &RampTexture {
+        &self.ramp
+    }
+}
@@ -0,0 +1,639 @@
+use std::num::NonZeroU64;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::renderer::{
+    animation::SkinVertex,
+    context::RenderContext,
+    instance::Instance,
+    light::LightKind,
+    probe::ReflectionProbes,
+    quantize::{MeshPipelines, VertexPrecision},
+    scene::{Geometry, Renderable, SceneGraph},
+    settings::RenderSettings,
+    texture::Texture,
+};
+
+pub const SHADOW_CASCADES: usize = 3;
+// Per kind, the lights after these light without shadows
+const MAX_SHADOW_LIGHTS: usize = 4;
+// Cascaded and cube maps together, the size of the uniform array the shaders look lights up in
+const MAX_SHADOWS: usize = MAX_SHADOW_LIGHTS * 2;
+// Between even and logarithmic splits, logarithmic ones keep the texels about the same size on screen
+const SPLIT_BLEND: f32 = 0.9;
+// Of the far plane of a cube
+const CUBE_NEAR: f32 = 1e-3;
+// Offsets of the per layer view projections, the minimum uniform offset alignment
+const VIEW_STRIDE: usize = 256;
+const MAX_VIEWS: usize = MAX_SHADOW_LIGHTS * (SHADOW_CASCADES + 6);
+
+// Per shadow map. Directional lights keep the view projection and the camera distance each cascade reaches, point and
+// spot lights the depth range of their cube
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct ShadowUniform {
+    view_projections: [[[f32; 4]; 4]; SHADOW_CASCADES],
+    splits: [f32; 4],
+    // First cascade layer or the cube, NO_SHADOW for unused slots
+    layer: u32,
+    near: f32,
+    far: f32,
+    _padding: u32,
+}
+
+impl ShadowUniform {
+    pub const NO_SHADOW: u32 = u32::MAX;
+
+    fn none() -> Self {
+        Self {
+            layer: Self::NO_SHADOW,
+            ..Zeroable::zeroed()
+        }
+    }
+}
+
+// A uniform for every shadow map and the light slot it belongs to, NO_SHADOW for unused ones. A uniform rather than a
+// storage buffer, WebGPU's downlevel limits leave the fragment stage four storage buffers and the scene uses them all
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct ShadowsUniform {
+    shadows: [ShadowUniform; MAX_SHADOWS],
+    lights: [u32; MAX_SHADOWS],
+}
+
+// A light casting shadows, with its index in the scene's lights
+pub struct ShadowCaster {
+    pub index: usize,
+    pub kind: u32,
+    pub range: f32,
+    pub transform: glam::Mat4,
+}
+
+// A layer rendered this frame, with the offset of its view projection
+#[derive(Copy, Clone, Debug)]
+struct ShadowView {
+    is_cube: bool,
+    layer: usize,
+    offset: u32,
+}
+
+// Depth layers for up to `capacity` lights. A single texel while nobody casts shadows, the shaders bind it regardless
+struct ShadowTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    layers: Vec<wgpu::TextureView>,
+    capacity: usize,
+}
+
+impl ShadowTexture {
+    fn new(
+        label: &str,
+        resolution: u32,
+        capacity: usize,
+        layers_per_light: usize,
+        dimension: wgpu::TextureViewDimension,
+        context: &RenderContext,
+    ) -> Self {
+        let resolution = if capacity == 0 { 1 } else { resolution };
+        let layer_count = (capacity.max(1) * layers_per_light) as u32;
+        let texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: resolution,
+                height: resolution,
+                depth_or_array_layers: layer_count,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(dimension),
+            ..Default::default()
+        });
+        let layers = (0..layer_count)
+            .map(|layer| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    dimension: Some(wgpu::TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        Self {
+            texture,
+            view,
+            layers,
+            capacity,
+        }
+    }
+}
+
+// Camera frustum corners, the near plane's first
+struct CameraFrustum {
+    near: [glam::Vec3; 4],
+    far: [glam::Vec3; 4],
+    near_distance: f32,
+    far_distance: f32,
+}
+
+impl CameraFrustum {
+    fn new(view_projection: glam::Mat4, position: glam::Vec3) -> Self {
+        let inverse = view_projection.inverse();
+        let corners = |depth: f32| {
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .map(|(x, y)| inverse.project_point3(glam::Vec3::new(x, y, depth)))
+        };
+        let (near, far) = (corners(0.0), corners(1.0));
+        let center = |corners: &[glam::Vec3; 4]| corners.iter().sum::<glam::Vec3>() * 0.25;
+        let forward = (center(&far) - center(&near)).normalize_or_zero();
+        Self {
+            near,
+            far,
+            near_distance: (center(&near) - position).dot(forward),
+            far_distance: (center(&far) - position).dot(forward),
+        }
+    }
+
+    // Camera distances where each cascade ends
+    fn splits(&self) -> [f32; SHADOW_CASCADES] {
+        // An orthographic camera starts at zero, where the logarithmic split is undefined
+        let near = self.near_distance.max(self.far_distance * 1e-4);
+        std::array::from_fn(|cascade| {
+            let fraction = (cascade + 1) as f32 / SHADOW_CASCADES as f32;
+            let logarithmic = near * (self.far_distance / near).powf(fraction);
+            let even = self.near_distance + (self.far_distance - self.near_distance) * fraction;
+            even + (logarithmic - even) * SPLIT_BLEND
+        })
+    }
+
+    // Corners of the part of the frustum between two camera distances
+    fn slice(&self, from: f32, to: f32) -> [glam::Vec3; 8] {
+        let depth = (self.far_distance - self.near_distance).max(f32::EPSILON);
+        let (from, to) = ((from - self.near_distance) / depth, (to - self.near_distance) / depth);
+        std::array::from_fn(|index| {
+            let (near, far) = (self.near[index % 4], self.far[index % 4]);
+            near.lerp(far, if index < 4 { from } else { to })
+        })
+    }
+
+    // Orthographic light views around each slice. The bounding sphere keeps the size fixed while the camera turns
+    // and the center snaps to whole texels, so shadow edges don't shimmer. Casters up to the camera's far distance
+    // behind a slice still land in its map
+    fn cascades(&self, direction: glam::Vec3, resolution: u32) -> [(glam::Mat4, f32); SHADOW_CASCADES] {
+        let up = if direction.y.abs() > 0.99 {
+            glam::Vec3::Z
+        } else {
+            glam::Vec3::Y
+        };
+        let light_view = glam::Mat4::look_to_rh(glam::Vec3::ZERO, direction, up);
+        let splits = self.splits();
+
+        std::array::from_fn(|cascade| {
+            let from = match cascade {
+                0 => self.near_distance,
+                _ => splits[cascade - 1],
+            };
+            let corners = self.slice(from, splits[cascade]);
+            let center = corners.iter().sum::<glam::Vec3>() / corners.len() as f32;
+            let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0, f32::max);
+            let radius = ((radius * 16.0).ceil() / 16.0).max(f32::EPSILON);
+
+            let texel = 2.0 * radius / resolution as f32;
+            let center = light_view.transform_point3(center);
+            let (x, y) = ((center.x / texel).floor() * texel, (center.y / texel).floor() * texel);
+            let projection = glam::Mat4::orthographic_rh(
+                x - radius,
+                x + radius,
+                y - radius,
+                y + radius,
+                -center.z - radius - self.far_distance,
+                -center.z + radius,
+            );
+            (projection * light_view, splits[cascade])
+        })
+    }
+}
+
+// Cascades of every directional light casting shadows, fit to the camera each frame, and a cube around every point
+// or spot light. Textures are sized for the casters there are and only grow. Cubes get half the cascade resolution
+pub struct ShadowMaps {
+    resolution: u32,
+    cascades: ShadowTexture,
+    cubes: ShadowTexture,
+    sampler: wgpu::Sampler,
+    uniforms: wgpu::Buffer,
+    view_buffer: wgpu::Buffer,
+    views: Vec<ShadowView>,
+    is_dirty: bool,
+}
+
+impl ShadowMaps {
+    pub fn new(context: &RenderContext) -> Self {
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let uniforms = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow buffer"),
+            size: std::mem::size_of::<ShadowsUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let view_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow view buffer"),
+            size: (MAX_VIEWS * VIEW_STRIDE) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let resolution = RenderSettings::default().shadow_map_size;
+        Self {
+            resolution,
+            cascades: Self::create_cascades(resolution, 0, context),
+            cubes: Self::create_cubes(resolution, 0, context),
+            sampler,
+            uniforms,
+            view_buffer,
+            views: Vec::new(),
+            is_dirty: false,
+        }
+    }
+
+    pub fn has_casters(&self) -> bool {
+        !self.views.is_empty()
+    }
+
+    // Cascades of the directional lights and the cube faces of the point lights
+    pub fn textures(&self) -> [&wgpu::Texture; 2] {
+        [&self.cascades.texture, &self.cubes.texture]
+    }
+
+    // Set when a texture was replaced, the scene bind group has to follow
+    pub fn is_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.is_dirty)
+    }
+
+    // Returns whether casters appeared or all went away
+    pub fn update(
+        &mut self,
+        casters: &[ShadowCaster],
+        view_projection: glam::Mat4,
+        position: glam::Vec3,
+        context: &RenderContext,
+    ) -> bool {
+        let had_casters = self.has_casters();
+        let (directional, positional): (Vec<_>, Vec<_>) = casters
+            .iter()
+            .partition(|caster| caster.kind == LightKind::Directional as u32);
+        let directional = &directional[..directional.len().min(MAX_SHADOW_LIGHTS)];
+        let positional = &positional[..positional.len().min(MAX_SHADOW_LIGHTS)];
+        self.reserve(directional.len(), positional.len(), context);
+
+        let frustum = CameraFrustum::new(view_projection, position);
+        let mut uniforms = ShadowsUniform {
+            shadows: [ShadowUniform::none(); MAX_SHADOWS],
+            lights: [ShadowUniform::NO_SHADOW; MAX_SHADOWS],
+        };
+        let mut view_projections = Vec::new();
+        self.views.clear();
+
+        for (slot, caster) in directional.iter().enumerate() {
+            let direction = (-caster.transform.z_axis.truncate()).normalize_or(glam::Vec3::NEG_Y);
+            let mut uniform = ShadowUniform {
+                layer: (slot * SHADOW_CASCADES) as u32,
+                ..Zeroable::zeroed()
+            };
+            for (cascade, (view_projection, split)) in
+                frustum.cascades(direction, self.resolution).into_iter().enumerate()
+            {
+                uniform.view_projections[cascade] = view_projection.to_cols_array_2d();
+                uniform.splits[cascade] = split;
+                self.push_view(
+                    false,
+                    slot * SHADOW_CASCADES + cascade,
+                    &mut view_projections,
+                    view_projection,
+                );
+            }
+            uniforms.shadows[slot] = uniform;
+            uniforms.lights[slot] = caster.index as u32;
+        }
+
+        for (slot, caster) in positional.iter().enumerate() {
+            // Without a range the light reaches as far as the camera sees
+            let far = match caster.range > 0.0 {
+                true => caster.range,
+                false => frustum.far_distance.max(f32::EPSILON),
+            };
+            let near = far * CUBE_NEAR;
+            // Flipped vertically, the face views follow the OpenGL convention where rows run bottom up
+            let projection = glam::Mat4::from_scale(glam::Vec3::new(1.0, -1.0, 1.0))
+                * glam::Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, near, far);
+            let position = caster.transform.w_axis.truncate();
+            for (face, view) in ReflectionProbes::face_views(position).into_iter().enumerate() {
+                self.push_view(true, slot * 6 + face, &mut view_projections, projection * view);
+            }
+            uniforms.shadows[MAX_SHADOW_LIGHTS + slot] = ShadowUniform {
+                layer: slot as u32,
+                near,
+                far,
+                ..Zeroable::zeroed()
+            };
+            uniforms.lights[MAX_SHADOW_LIGHTS + slot] = caster.index as u32;
+        }
+
+        context
+            .queue
+            .write_buffer(&self.uniforms, 0, bytemuck::cast_slice(&uniforms.shadows));
+        context.queue.write_buffer(
+            &self.uniforms,
+            std::mem::size_of_val(&uniforms.shadows) as u64,
+            bytemuck::cast_slice(&uniforms.lights),
+        );
+        if !view_projections.is_empty() {
+            context.queue.write_buffer(&self.view_buffer, 0, &view_projections);
+        }
+        had_casters != self.has_casters()
+    }
+
+    fn push_view(&mut self, is_cube: bool, layer: usize, buffer: &mut Vec<u8>, view_projection: glam::Mat4) {
+        let offset = buffer.len();
+        buffer.extend_from_slice(bytemuck::bytes_of(&view_projection.to_cols_array_2d()));
+        buffer.resize(offset + VIEW_STRIDE, 0);
+        self.views.push(ShadowView {
+            is_cube,
+            layer,
+            offset: offset as u32,
+        });
+    }
+
+    fn reserve(&mut self, directional: usize, positional: usize, context: &RenderContext) {
+        if directional > self.cascades.capacity {
+            self.cascades = Self::create_cascades(self.resolution, directional, context);
+            self.is_dirty = true;
+        }
+        if positional > self.cubes.capacity {
+            self.cubes = Self::create_cubes(self.resolution, positional, context);
+            self.is_dirty = true;
+        }
+    }
+
+    // Of the cascades, clamped to what the device supports. Takes effect for the textures at once, the maps are
+    // rendered again every frame anyway
+    pub fn set_resolution(&mut self, resolution: u32, context: &RenderContext) {
+        let resolution = resolution.clamp(1, context.device.limits().max_texture_dimension_2d);
+        if resolution == self.resolution {
+            return;
+        }
+
+        self.resolution = resolution;
+        self.cascades = Self::create_cascades(resolution, self.cascades.capacity, context);
+        self.cubes = Self::create_cubes(resolution, self.cubes.capacity, context);
+        self.is_dirty = true;
+    }
+
+    fn create_cascades(resolution: u32, capacity: usize, context: &RenderContext) -> ShadowTexture {
+        ShadowTexture::new(
+            "Shadow cascades",
+            resolution,
+            capacity,
+            SHADOW_CASCADES,
+            wgpu::TextureViewDimension::D2Array,
+            context,
+        )
+    }
+
+    fn create_cubes(resolution: u32, capacity: usize, context: &RenderContext) -> ShadowTexture {
+        ShadowTexture::new(
+            "Shadow cubes",
+            (resolution / 2).max(1),
+            capacity,
+            6,
+            wgpu::TextureViewDimension::CubeArray,
+            context,
+        )
+    }
+
+    // Bindings of the scene bind group, from `binding` on
+    pub fn bind_group_entries(&self, binding: u32) -> [wgpu::BindGroupEntry<'_>; 4] {
+        [
+            wgpu::BindGroupEntry {
+                binding,
+                resource: self.uniforms.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: binding + 1,
+                resource: wgpu::BindingResource::TextureView(&self.cascades.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: binding + 2,
+                resource: wgpu::BindingResource::TextureView(&self.cubes.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: binding + 3,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+        ]
+    }
+
+    // Target layer and view projection offset of everything to render this frame
+    fn views(&self) -> impl Iterator<Item = (&wgpu::TextureView, u32)> {
+        self.views.iter().map(|view| {
+            let texture = if view.is_cube { &self.cubes } else { &self.cascades };
+            (&texture.layers[view.layer], view.offset)
+        })
+    }
+}
+
+// Depth only draws of the scene's meshes into every shadow map layer. The instances aren't culled against the camera,
+// whatever is out of view may still cast into it. Alpha masked materials cast solid shadows
+pub struct ShadowPass {
+    mesh_pipelines: MeshPipelines,
+    skinned_pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+impl ShadowPass {
+    pub fn new(context: &RenderContext) -> Self {
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Shadow layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    storage(1),
+                    storage(2),
+                ],
+            });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/shadow.wgsl").into()),
+        });
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |label: &str, entry_point, buffers: &[wgpu::VertexBufferLayout]| {
+            context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some(entry_point),
+                    compilation_options: Default::default(),
+                    buffers,
+                },
+                fragment: None,
+                // Both sides, the cube faces are flipped and open meshes should still cast
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: None,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let mesh_pipelines = MeshPipelines::new(|precision| {
+            create_pipeline(
+                &format!("Shadow mesh pipeline ({})", precision.to_str()),
+                "vs_mesh",
+                &precision.layout_builder(0).push::<Instance>().build(),
+            )
+        });
+        let skinned_pipeline = create_pipeline(
+            "Shadow skinned mesh pipeline",
+            "vs_skinned",
+            &VertexPrecision::Full
+                .layout_builder(0)
+                .push::<SkinVertex>()
+                .push::<Instance>()
+                .build(),
+        );
+
+        Self {
+            mesh_pipelines,
+            skinned_pipeline,
+            layout,
+        }
+    }
+
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, scene: &SceneGraph, context: &RenderContext) {
+        let bind_group = context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow bind group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &scene.shadow_maps.view_buffer,
+                        offset: 0,
+                        size: NonZeroU64::new(std::mem::size_of::<glam::Mat4>() as u64),
+                    }),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: scene.transforms.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: scene.joint_palettes.buffer().as_entire_binding(),
+                },
+            ],
+        });
+
+        for (target, offset) in scene.shadow_maps.views() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow render pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: target,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_bind_group(0, &bind_group, &[offset]);
+
+            for batch in &scene.shadow_batches {
+                let Some(Renderable::Mesh(handles)) = scene.renderables.get(&batch.key.render_id) else {
+                    continue;
+                };
+
+                for handle in handles {
+                    let Some(Geometry::Primitive(primitive)) = scene.geometries.get_by_id(handle.geometry_index) else {
+                        continue;
+                    };
+
+                    render_pass.set_vertex_buffer(0, primitive.vertex_buffer.slice(..));
+                    match (primitive.is_skinned, primitive.vertex_precision) {
+                        (true, _) => {
+                            render_pass.set_pipeline(&self.skinned_pipeline);
+                            // Joints and weights take the place of the last UV set
+                            let skin = &primitive.uv_buffers[RenderContext::MAX_UV_SETS - 1];
+                            render_pass.set_vertex_buffer(1, skin.slice(..));
+                            render_pass.set_vertex_buffer(2, scene.instance_pool.buffer().slice(..));
+                        }
+                        (false, precision) => {
+                            render_pass.set_pipeline(self.mesh_pipelines.get(precision));
+                            render_pass.set_vertex_buffer(1, scene.instance_pool.buffer().slice(..));
+                        }
+                    }
+                    render_pass.set_index_buffer(primitive.index_buffer.slice(..), primitive.index_format);
+                    render_pass.draw_indexed(0..primitive.num_elements, 0, batch.instance_range());
+                }
+            }
+        }
+    }
+}
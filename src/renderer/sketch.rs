@@ -0,0 +1,386 @@
+use crossbeam::channel::{Receiver, Sender};
+use instant::Instant;
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{context::RenderContext, mesh::PrimitiveGeometry};
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SketchUniform {
+    resolution: [f32; 2],
+    mouse: [f32; 2],
+    time: f32,
+    frame: u32,
+    custom_count: u32,
+    _padding: u32,
+}
+
+// Where the custom attributes of one primitive start in the attribute buffer, attribute by attribute with a value
+// per vertex each
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct CustomRange {
+    offset: u32,
+    vertex_count: u32,
+    attribute_count: u32,
+}
+
+// Fullscreen fragment shader written by the user, drawn over the rendered scene. Edits compile in the background,
+// the previous version keeps drawing until they're done
+pub struct ShaderSketch {
+    layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: Option<wgpu::RenderPipeline>,
+    // Sources handed out and the latest one compiled, an older compilation finishing late is dropped
+    generation: u64,
+    compiled_generation: u64,
+    compiled_tx: Sender<(u64, wgpu::RenderPipeline)>,
+    compiled_rx: Receiver<(u64, wgpu::RenderPipeline)>,
+    buffer: wgpu::Buffer,
+    custom_attributes: wgpu::Buffer,
+    custom_ranges: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    uniform: SketchUniform,
+    start: Instant,
+}
+
+impl ShaderSketch {
+    const PRELUDE: &str = include_str!("../../res/sketch_prelude.wgsl");
+    const DEFAULT_SOURCE: &str = include_str!("../../res/sketch.wgsl");
+
+    pub fn new(context: &RenderContext) -> Self {
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Sketch layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture_entry(1, wgpu::TextureSampleType::Float { filterable: true }),
+                    texture_entry(2, wgpu::TextureSampleType::Depth),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    storage_entry(4),
+                    storage_entry(5),
+                ],
+            });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Sketch pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let uniform = SketchUniform {
+            resolution: [context.config.width as f32, context.config.height as f32],
+            mouse: [0.0; 2],
+            time: 0.0,
+            frame: 0,
+            custom_count: 0,
+            _padding: 0,
+        };
+
+        let buffer = context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sketch buffer"),
+            contents: bytemuck::bytes_of(&uniform),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let (custom_attributes, custom_ranges) = Self::create_custom_buffers(&[], &[], context);
+        let bind_group = Self::create_bind_group(&layout, &buffer, &custom_attributes, &custom_ranges, context);
+        let (compiled_tx, compiled_rx) = crossbeam::channel::unbounded();
+        let mut sketch = Self {
+            layout,
+            pipeline_layout,
+            pipeline: None,
+            generation: 0,
+            compiled_generation: 0,
+            compiled_tx,
+            compiled_rx,
+            buffer,
+            custom_attributes,
+            custom_ranges,
+            bind_group,
+            uniform,
+            start: Instant::now(),
+        };
+
+        sketch.set_source(Self::DEFAULT_SOURCE, context);
+        sketch
+    }
+
+    // Keeps the previous pipeline when the new source doesn't compile
+    pub fn set_source(&mut self, source: &str, context: &RenderContext) {
+        let source = format!("{}\n{}", Self::PRELUDE, source);
+        if let Err(error) = Self::validate(&source) {
+            log::error!("Shader sketch failed to compile:\n{}", error);
+            return;
+        }
+
+        self.generation += 1;
+        let generation = self.generation;
+        let device = context.device.clone();
+        let pipeline_layout = self.pipeline_layout.clone();
+        let format = context.hdr.format();
+        let compiled_tx = self.compiled_tx.clone();
+        let compile = move || {
+            let pipeline = Self::create_pipeline(&source, &pipeline_layout, format, &device);
+            compiled_tx.send((generation, pipeline)).ok();
+        };
+
+        #[cfg(not(target_family = "wasm"))]
+        std::thread::spawn(compile);
+        // WebGPU objects can't leave the main thread
+        #[cfg(target_family = "wasm")]
+        compile();
+    }
+
+    fn create_pipeline(
+        source: &str,
+        pipeline_layout: &wgpu::PipelineLayout,
+        format: wgpu::TextureFormat,
+        device: &wgpu::Device,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sketch shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sketch pipeline"),
+            layout: Some(pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    pub fn set_mouse(&mut self, position: glam::Vec2) {
+        self.uniform.mouse = position.to_array();
+    }
+
+    pub fn resize(&mut self, context: &RenderContext) {
+        self.uniform.resolution = [context.config.width as f32, context.config.height as f32];
+        self.bind_group = self.rebind(context);
+    }
+
+    // Gathers the custom glTF attributes of every primitive that has them, in the order given
+    pub fn set_custom_attributes<'a>(
+        &mut self,
+        geometries: impl IntoIterator<Item = &'a PrimitiveGeometry>,
+        context: &RenderContext,
+    ) {
+        let mut attributes = Vec::new();
+        let mut ranges = Vec::new();
+        for geometry in geometries {
+            if geometry.custom_attributes.is_empty() {
+                continue;
+            }
+
+            ranges.push(CustomRange {
+                offset: attributes.len() as u32,
+                vertex_count: geometry.vertices.len() as u32,
+                attribute_count: geometry.custom_attributes.len() as u32,
+            });
+            attributes.extend(geometry.custom_attributes.iter().flatten());
+        }
+
+        self.uniform.custom_count = ranges.len() as u32;
+        (self.custom_attributes, self.custom_ranges) = Self::create_custom_buffers(&attributes, &ranges, context);
+        self.bind_group = self.rebind(context);
+    }
+
+    // Reads the scene color copy and depth, writes the HDR target
+    pub fn render(&mut self, encoder: &mut wgpu::CommandEncoder, context: &RenderContext) {
+        while let Ok((generation, pipeline)) = self.compiled_rx.try_recv() {
+            if generation > self.compiled_generation {
+                self.compiled_generation = generation;
+                self.pipeline = Some(pipeline);
+            }
+        }
+
+        let Some(pipeline) = &self.pipeline else {
+            return;
+        };
+
+        self.uniform.time = self.start.elapsed().as_secs_f32();
+        context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.uniform));
+        self.uniform.frame = self.uniform.frame.wrapping_add(1);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Sketch render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    // Naga catches what would otherwise be an uncaptured device error
+    fn validate(source: &str) -> Result<(), String> {
+        use naga::{
+            ShaderStage,
+            front::wgsl,
+            valid::{Capabilities, ValidationFlags, Validator},
+        };
+
+        let module = wgsl::parse_str(source).map_err(|error| error.emit_to_string(source))?;
+        Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .map_err(|error| error.emit_to_string(source))?;
+
+        let has_entry_point = module
+            .entry_points
+            .iter()
+            .any(|entry_point| entry_point.name == "fs_main" && entry_point.stage == ShaderStage::Fragment);
+        if !has_entry_point {
+            return Err("Missing @fragment fn fs_main".to_string());
+        }
+
+        Ok(())
+    }
+
+    // Storage buffers can't be empty, without custom attributes both hold a single zeroed element
+    fn create_custom_buffers(
+        attributes: &[[f32; 4]],
+        ranges: &[CustomRange],
+        context: &RenderContext,
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
+        let attributes = match attributes {
+            [] => &[[0.0; 4]],
+            attributes => attributes,
+        };
+        let ranges = match ranges {
+            [] => &[CustomRange::default()],
+            ranges => ranges,
+        };
+
+        let create_buffer = |label, contents| {
+            context.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE,
+            })
+        };
+
+        (
+            create_buffer("Sketch custom attributes", bytemuck::cast_slice(attributes)),
+            create_buffer("Sketch custom ranges", bytemuck::cast_slice(ranges)),
+        )
+    }
+
+    fn rebind(&self, context: &RenderContext) -> wgpu::BindGroup {
+        Self::create_bind_group(
+            &self.layout,
+            &self.buffer,
+            &self.custom_attributes,
+            &self.custom_ranges,
+            context,
+        )
+    }
+
+    fn create_bind_group(
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        custom_attributes: &wgpu::Buffer,
+        custom_ranges: &wgpu::Buffer,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
+        let scene_color = context.hdr.scene_color();
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sketch bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&scene_color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&context.depth_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&scene_color.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: custom_attributes.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: custom_ranges.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
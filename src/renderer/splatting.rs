@@ -0,0 +1,281 @@
+use crate::renderer::{
+    context::RenderContext,
+    pipeline::PipelineCache,
+    scene::{DrawScene, SceneGraph, ScenePass},
+    texture::Texture,
+};
+
+// Pointclouds too large to draw every frame are drawn a slice at a time into their own color and depth targets. The
+// targets are only cleared when the view changes, so a still view fills in to the full density over a few frames
+pub struct PointSplats {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    color: Texture,
+    depth: Texture,
+    bind_group: wgpu::BindGroup,
+    chunk: u32,
+    chunk_count: u32,
+    budget: u32,
+}
+
+impl PointSplats {
+    pub fn new(context: &RenderContext) -> Self {
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Point splat layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = context.device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Point splat shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../../res/splat_composite.wgsl").into()),
+        });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Point splat pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        // Depth tested against the meshes, and written so transmissive surfaces and volumes are hidden behind points
+        let pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Point splat pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.hdr.format(),
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let (color, depth) = Self::create_targets(context);
+        let bind_group = Self::create_bind_group(&color, &depth, &layout, context);
+
+        Self {
+            pipeline,
+            layout,
+            color,
+            depth,
+            bind_group,
+            chunk: 0,
+            chunk_count: 0,
+            budget: 0,
+        }
+    }
+
+    pub fn resize(&mut self, context: &RenderContext) {
+        (self.color, self.depth) = Self::create_targets(context);
+        self.bind_group = Self::create_bind_group(&self.color, &self.depth, &self.layout, context);
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.chunk = 0;
+    }
+
+    // Slices the largest pointcloud needs at the given budget. Returns whether splatting is worth it, clouds that fit
+    // in a single frame are drawn with the meshes
+    pub fn prepare(&mut self, scene: &SceneGraph, budget: u32) -> bool {
+        let chunk_count = match budget {
+            0 => 1,
+            budget => scene.max_pointcloud_points().div_ceil(budget),
+        };
+
+        if chunk_count != self.chunk_count || budget != self.budget {
+            self.chunk_count = chunk_count;
+            self.budget = budget;
+            self.reset();
+        }
+
+        self.is_active()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.chunk_count > 1
+    }
+
+    pub fn is_complete(&self) -> bool {
+        !self.is_active() || self.chunk >= self.chunk_count
+    }
+
+    pub fn targets(&self) -> [&Texture; 2] {
+        [&self.color, &self.depth]
+    }
+
+    // Draws the next slice over the previous ones, the targets are cleared for the first
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        scene: &SceneGraph,
+        camera_bind_group: &wgpu::BindGroup,
+        pipeline_cache: &PipelineCache,
+    ) {
+        if self.is_complete() {
+            return;
+        }
+
+        let (color_load, depth_load) = if self.chunk == 0 {
+            (wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), wgpu::LoadOp::Clear(1.0))
+        } else {
+            (wgpu::LoadOp::Load, wgpu::LoadOp::Load)
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Point splat render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.color.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: color_load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: self.depth.view(),
+                depth_ops: Some(wgpu::Operations {
+                    load: depth_load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.draw_scene(
+            scene,
+            camera_bind_group,
+            pipeline_cache,
+            ScenePass::PointChunk {
+                index: self.chunk,
+                budget: self.budget,
+            },
+        );
+        drop(render_pass);
+
+        self.chunk += 1;
+    }
+
+    // Copies whatever has been splatted so far into the HDR and depth targets, behind the meshes where they overlap
+    pub fn composite(&self, encoder: &mut wgpu::CommandEncoder, context: &RenderContext) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Point splat composite pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &context.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn create_targets(context: &RenderContext) -> (Texture, Texture) {
+        let color = Texture::create_2d_texture(
+            &context.device,
+            context.config.width,
+            context.config.height,
+            context.hdr.format(),
+            &wgpu::SamplerDescriptor::default(),
+            Some("Point splat color texture"),
+        );
+        let depth = Texture::create_depth_texture(&context.device, &context.config, Some("Point splat depth texture"));
+
+        (color, depth)
+    }
+
+    fn create_bind_group(
+        color: &Texture,
+        depth: &Texture,
+        layout: &wgpu::BindGroupLayout,
+        context: &RenderContext,
+    ) -> wgpu::BindGroup {
+        context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Point splat bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(color.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(depth.view()),
+                },
+            ],
+        })
+    }
+}
@@ -23,10 +23,21 @@ pub struct Surface {
     config: wgpu::SurfaceConfiguration,
     state: SurfaceState,
     pending_resize: Option<(wgpu::SurfaceConfiguration, wgpu::Device)>,
+    // Set once a resize went out to the render thread, until its configuration is applied
+    is_resize_in_flight: bool,
+    // Whichever device configured the surface last, so a lost or outdated surface can be configured again in place
+    device: Option<wgpu::Device>,
+    configured_size: Option<(u32, u32)>,
+    // Kept for creating surfaces for additional windows
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
 }
 
 impl Surface {
-    pub async fn initialize(window: Arc<Window>) -> anyhow::Result<(Self, RenderContext)> {
+    pub async fn initialize(window: Arc<Window>, is_uncapped: bool) -> anyhow::Result<(Self, RenderContext)> {
+        #[cfg(target_family = "wasm")]
+        crate::capability::Capabilities::detect().check()?;
+
         let size = window.inner_size();
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             #[cfg(not(target_family = "wasm"))]
@@ -60,8 +71,8 @@ impl Surface {
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_capabilities.present_modes[0],
-            alpha_mode: surface_capabilities.alpha_modes[0],
+            present_mode: Self::present_mode(&surface_capabilities, is_uncapped),
+            alpha_mode: Self::alpha_mode(&surface_capabilities),
             view_formats: vec![surface_format.add_srgb_suffix()],
             desired_maximum_frame_latency: 2,
         };
@@ -72,11 +83,79 @@ impl Surface {
             config,
             state: SurfaceState::Unconfigured,
             pending_resize: None,
+            is_resize_in_flight: false,
+            device: None,
+            configured_size: None,
+            instance,
+            adapter,
         };
 
         Ok((surface_state, context))
     }
 
+    // Uncapped presents without waiting for vblank where the surface allows it, so frame times measure the renderer
+    fn present_mode(capabilities: &wgpu::SurfaceCapabilities, is_uncapped: bool) -> wgpu::PresentMode {
+        let uncapped = [wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox]
+            .into_iter()
+            .find(|mode| capabilities.present_modes.contains(mode));
+        match uncapped {
+            Some(mode) if is_uncapped => mode,
+            _ => capabilities.present_modes[0],
+        }
+    }
+
+    // The rendered alpha is premultiplied and 1 wherever something was drawn, so a canvas that composites with the page
+    // only differs once the background is made transparent
+    fn alpha_mode(capabilities: &wgpu::SurfaceCapabilities) -> wgpu::CompositeAlphaMode {
+        if cfg!(target_family = "wasm")
+            && capabilities
+                .alpha_modes
+                .contains(&wgpu::CompositeAlphaMode::PreMultiplied)
+        {
+            wgpu::CompositeAlphaMode::PreMultiplied
+        } else {
+            capabilities.alpha_modes[0]
+        }
+    }
+
+    // Shares the instance and adapter so the surface can be configured with the existing device
+    pub fn create_secondary(&self, window: Arc<Window>) -> anyhow::Result<Self> {
+        let size = window.inner_size();
+        let surface = self.instance.create_surface(window)?;
+        let surface_capabilities = surface.get_capabilities(&self.adapter);
+        let surface_format = if surface_capabilities.formats.contains(&self.config.format) {
+            self.config.format
+        } else {
+            *surface_capabilities
+                .formats
+                .first()
+                .ok_or_else(|| anyhow::anyhow!("Surface is not supported by the adapter"))?
+        };
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: surface_capabilities.present_modes[0],
+            alpha_mode: surface_capabilities.alpha_modes[0],
+            view_formats: vec![surface_format.add_srgb_suffix()],
+            desired_maximum_frame_latency: 2,
+        };
+
+        Ok(Self {
+            surface: Some(surface),
+            config,
+            state: SurfaceState::Unconfigured,
+            pending_resize: None,
+            is_resize_in_flight: false,
+            device: None,
+            configured_size: None,
+            instance: self.instance.clone(),
+            adapter: self.adapter.clone(),
+        })
+    }
+
     pub fn config(&self) -> &wgpu::SurfaceConfiguration {
         &self.config
     }
@@ -85,37 +164,63 @@ impl Surface {
         &self.state
     }
 
+    pub fn is_resizing(&self) -> bool {
+        self.is_resize_in_flight || self.pending_resize.is_some()
+    }
+
+    // The size of the last configuration, reported once
+    pub fn take_configured_size(&mut self) -> Option<(u32, u32)> {
+        self.configured_size.take()
+    }
+
     pub fn acquire(&mut self) -> Result<wgpu::TextureView, wgpu::SurfaceError> {
-        if let Some(surface) = &self.surface {
-            let output = surface.get_current_texture()?;
-            let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
-                format: Some(self.config.format.add_srgb_suffix()),
-                ..Default::default()
-            });
-
-            self.state = SurfaceState::Acquired(output);
-            Ok(view)
-        } else {
-            Err(wgpu::SurfaceError::Lost)
-        }
+        let Some(surface) = &self.surface else {
+            return Err(wgpu::SurfaceError::Lost);
+        };
+
+        // An outdated swapchain usually only needs configuring again, going through the render thread is only needed
+        // when the size changed
+        let output = match surface.get_current_texture() {
+            Ok(output) => output,
+            Err(error @ (wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => {
+                let Some(device) = &self.device else {
+                    return Err(error);
+                };
+
+                log::warn!("Surface is {}, configuring it again", error);
+                surface.configure(device, &self.config);
+                surface.get_current_texture()?
+            }
+            Err(error) => return Err(error),
+        };
+
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.config.format.add_srgb_suffix()),
+            ..Default::default()
+        });
+
+        self.state = SurfaceState::Acquired(output);
+        Ok(view)
     }
 
     pub fn present(&mut self) {
         if let SurfaceState::Acquired(output) = std::mem::replace(&mut self.state, SurfaceState::Configured) {
-            if let Some((config, device)) = self.pending_resize.take()
-                && let Some(surface) = &self.surface
-            {
+            if let Some((config, device)) = self.pending_resize.take() {
                 drop(output);
-
-                self.config = config;
-                surface.configure(&device, &self.config);
+                self.configure(config, device);
             } else {
                 output.present();
             }
         }
     }
 
-    pub fn request_resize(&mut self, width: u32, height: u32) -> wgpu::SurfaceConfiguration {
+    // A minimized window reports a zero size, which can't be configured. The old swapchain is kept until it has a
+    // size again
+    pub fn request_resize(&mut self, width: u32, height: u32) -> Option<wgpu::SurfaceConfiguration> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+
         if let SurfaceState::Unconfigured | SurfaceState::Configured = &mut self.state {
             self.state = SurfaceState::Resizing;
         }
@@ -123,28 +228,35 @@ impl Surface {
         let mut config = self.config.clone();
         config.width = width;
         config.height = height;
+        self.is_resize_in_flight = true;
 
-        config
+        Some(config)
     }
 
     pub fn apply_resize(&mut self, config: wgpu::SurfaceConfiguration, device: wgpu::Device) {
+        self.is_resize_in_flight = false;
         match &mut self.state {
             SurfaceState::Acquired(_) | SurfaceState::Configured => {
                 self.pending_resize = Some((config, device));
             }
-            SurfaceState::Resizing => {
-                if let Some(surface) = &self.surface {
-                    self.config = config;
-                    surface.configure(&device, &self.config);
-                    self.state = SurfaceState::Configured;
-                }
-            }
+            SurfaceState::Resizing => self.configure(config, device),
             _ => (),
         }
     }
 
+    fn configure(&mut self, config: wgpu::SurfaceConfiguration, device: wgpu::Device) {
+        if let Some(surface) = &self.surface {
+            self.config = config;
+            surface.configure(&device, &self.config);
+            self.state = SurfaceState::Configured;
+            self.configured_size = Some((self.config.width, self.config.height));
+            self.device = Some(device);
+        }
+    }
+
     pub fn drop(&mut self) {
         self.state = SurfaceState::Unconfigured;
         self.surface = None;
+        self.device = None;
     }
 }
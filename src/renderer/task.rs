@@ -0,0 +1,103 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use instant::Instant;
+
+#[derive(Clone, Debug)]
+pub struct TaskInfo {
+    pub id: u64,
+    pub label: String,
+    pub elapsed: Duration,
+}
+
+struct Task {
+    id: u64,
+    label: String,
+    start: Instant,
+    is_cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct TaskListInner {
+    tasks: Vec<Task>,
+    next_id: u64,
+}
+
+// Background loads in flight, shared between the loader threads or workers and the UI
+#[derive(Clone, Default)]
+pub struct TaskList {
+    inner: Arc<Mutex<TaskListInner>>,
+}
+
+impl TaskList {
+    pub fn start(&self, label: impl Into<String>) -> TaskHandle {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        inner.tasks.push(Task {
+            id,
+            label: label.into(),
+            start: Instant::now(),
+            is_cancelled: Arc::clone(&is_cancelled),
+        });
+
+        TaskHandle {
+            id,
+            is_cancelled,
+            list: self.clone(),
+        }
+    }
+
+    // Work already running can't be interrupted, its result is dropped once it's done
+    pub fn cancel(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(index) = inner.tasks.iter().position(|task| task.id == id) {
+            let task = inner.tasks.remove(index);
+            task.is_cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<TaskInfo> {
+        self.inner
+            .lock()
+            .unwrap()
+            .tasks
+            .iter()
+            .map(|task| TaskInfo {
+                id: task.id,
+                label: task.label.clone(),
+                elapsed: task.start.elapsed(),
+            })
+            .collect()
+    }
+
+    fn finish(&self, id: u64) {
+        self.inner.lock().unwrap().tasks.retain(|task| task.id != id);
+    }
+}
+
+// Removes its task from the list when dropped, so a load that bails out early doesn't linger in the status bar
+pub struct TaskHandle {
+    id: u64,
+    is_cancelled: Arc<AtomicBool>,
+    list: TaskList,
+}
+
+impl TaskHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for TaskHandle {
+    fn drop(&mut self) {
+        self.list.finish(self.id);
+    }
+}
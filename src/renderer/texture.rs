@@ -189,8 +189,7 @@ pub struct Texture {
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    pub fn create_placeholder(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
-        let data = [255u8, 255, 255, 255];
+    pub fn create_placeholder(device: &wgpu::Device, queue: &wgpu::Queue, data: [u8; 4]) -> Self {
         let size = wgpu::Extent3d {
             width: 1,
             height: 1,
@@ -392,7 +391,10 @@ impl Texture {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         };
 
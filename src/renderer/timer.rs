@@ -0,0 +1,168 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::renderer::context::RenderContext;
+
+// Timestamps around every frame graph pass in the render encoder. Compute work recorded on the separate compute
+// encoder is not covered
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    period: f32,
+    pending: Vec<TimerReadback>,
+}
+
+// Handed to the frame while the graph records
+pub struct PassTimestamps {
+    query_set: wgpu::QuerySet,
+    names: Vec<&'static str>,
+}
+
+struct TimerReadback {
+    names: Vec<&'static str>,
+    staging: wgpu::Buffer,
+    status: Option<Arc<OnceLock<bool>>>,
+}
+
+impl GpuTimer {
+    const MAX_PASSES: u32 = 32;
+    pub const FEATURES: wgpu::Features =
+        wgpu::Features::TIMESTAMP_QUERY.union(wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS);
+
+    pub fn new(context: &RenderContext) -> Option<Self> {
+        if !context.device.features().contains(Self::FEATURES) {
+            log::info!("GPU pass timing is not supported by this device");
+            return None;
+        }
+
+        let query_set = context.device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Pass timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::MAX_PASSES * 2,
+        });
+        let resolve_buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp resolve buffer"),
+            size: (Self::MAX_PASSES * 2) as u64 * std::mem::size_of::<u64>() as u64,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            period: context.queue.get_timestamp_period(),
+            pending: Vec::new(),
+        })
+    }
+
+    pub fn begin_frame(&self) -> PassTimestamps {
+        PassTimestamps {
+            query_set: self.query_set.clone(),
+            names: Vec::new(),
+        }
+    }
+
+    pub fn end_frame(
+        &mut self,
+        timestamps: PassTimestamps,
+        encoder: &mut wgpu::CommandEncoder,
+        context: &RenderContext,
+    ) {
+        if timestamps.names.is_empty() {
+            return;
+        }
+
+        let count = timestamps.names.len() as u32 * 2;
+        let size = count as u64 * std::mem::size_of::<u64>() as u64;
+        let staging = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp staging buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &staging, 0, size);
+
+        self.pending.push(TimerReadback {
+            names: timestamps.names,
+            staging,
+            status: None,
+        });
+    }
+
+    // Mapping can only start once the copy has been submitted
+    pub fn after_submit(&mut self) {
+        for readback in self.pending.iter_mut().filter(|readback| readback.status.is_none()) {
+            let status = Arc::new(OnceLock::new());
+            let callback_status = Arc::clone(&status);
+            readback
+                .staging
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if let Err(error) = &result {
+                        log::error!("Unable to map timestamp buffer: {}", error);
+                    }
+                    let _ = callback_status.set(result.is_ok());
+                });
+            readback.status = Some(status);
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Milliseconds per pass for every frame whose timestamps arrived
+    pub fn poll(&mut self) -> Vec<Vec<(&'static str, f32)>> {
+        let mut frames = Vec::new();
+        let mut pending = Vec::new();
+        for readback in self.pending.drain(..) {
+            let Some(is_mapped) = readback.status.as_ref().and_then(|status| status.get()).copied() else {
+                pending.push(readback);
+                continue;
+            };
+
+            if !is_mapped {
+                continue;
+            }
+
+            let ticks = {
+                let data = readback.staging.slice(..).get_mapped_range();
+                bytemuck::cast_slice::<u8, u64>(&data).to_vec()
+            };
+            readback.staging.unmap();
+
+            let period = self.period;
+            frames.push(
+                readback
+                    .names
+                    .iter()
+                    .zip(ticks.chunks_exact(2))
+                    .map(|(name, ticks)| (*name, ticks[1].saturating_sub(ticks[0]) as f32 * period / 1_000_000.0))
+                    .collect(),
+            );
+        }
+
+        self.pending = pending;
+        frames
+    }
+}
+
+impl PassTimestamps {
+    // Passes beyond the query capacity go untimed
+    pub fn begin_pass(&mut self, name: &'static str, encoder: &mut wgpu::CommandEncoder) -> bool {
+        let index = self.names.len() as u32;
+        if index >= GpuTimer::MAX_PASSES {
+            return false;
+        }
+
+        self.names.push(name);
+        encoder.write_timestamp(&self.query_set, index * 2);
+        true
+    }
+
+    pub fn end_pass(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let index = self.names.len() as u32 - 1;
+        encoder.write_timestamp(&self.query_set, index * 2 + 1);
+    }
+}
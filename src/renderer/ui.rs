@@ -3,6 +3,7 @@ use std::sync::Arc;
 use egui::{ClippedPrimitive, Context, TexturesDelta};
 use egui_wgpu::ScreenDescriptor;
 use egui_winit::State;
+use serde::{Deserialize, Serialize};
 use winit::{event::WindowEvent, window::Window};
 
 pub struct UiData {
@@ -11,11 +12,73 @@ pub struct UiData {
     pub screen_descriptor: ScreenDescriptor,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UiTheme {
+    Dark,
+    Light,
+}
+
+impl UiTheme {
+    pub const ALL: [Self; 2] = [Self::Dark, Self::Light];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiStyle {
+    pub theme: UiTheme,
+    // Selection and highlight color in sRGB, the theme's own when unset
+    pub accent: Option<[u8; 3]>,
+    // On top of the display's scale factor
+    pub scale: f32,
+}
+
+impl Default for UiStyle {
+    fn default() -> Self {
+        Self {
+            theme: UiTheme::Dark,
+            accent: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl UiStyle {
+    pub const MIN_SCALE: f32 = 0.5;
+    pub const MAX_SCALE: f32 = 3.0;
+
+    fn visuals(&self) -> egui::Visuals {
+        let mut visuals = match self.theme {
+            UiTheme::Dark => egui::Visuals::dark(),
+            UiTheme::Light => egui::Visuals::light(),
+        };
+
+        if let Some([r, g, b]) = self.accent {
+            let accent = egui::Color32::from_rgb(r, g, b);
+            visuals.selection.bg_fill = accent;
+            visuals.hyperlink_color = accent;
+            visuals.widgets.hovered.bg_stroke.color = accent;
+            visuals.widgets.active.bg_fill = accent;
+        }
+
+        visuals
+    }
+}
+
 pub struct Ui {
     window: Arc<Window>,
     context: Context,
     state: State,
     pending_resize: bool,
+    // Size of the surface egui draws into, which trails the window while a resize is in flight
+    surface_size: Option<[u32; 2]>,
+    style: Option<UiStyle>,
 }
 
 impl Ui {
@@ -28,9 +91,23 @@ impl Ui {
             context,
             state,
             pending_resize: false,
+            surface_size: None,
+            style: None,
         }
     }
 
+    // Only touches egui when the style changed, a new zoom factor lays out everything again
+    pub fn set_style(&mut self, style: &UiStyle) {
+        if self.style.as_ref() == Some(style) {
+            return;
+        }
+
+        self.context.set_visuals(style.visuals());
+        self.context
+            .set_zoom_factor(style.scale.clamp(UiStyle::MIN_SCALE, UiStyle::MAX_SCALE));
+        self.style = Some(*style);
+    }
+
     pub fn on_event(&mut self, event: &WindowEvent) -> bool {
         self.state.on_window_event(&self.window, event).consumed
     }
@@ -54,7 +131,7 @@ impl Ui {
             .tessellate(full_output.shapes, full_output.pixels_per_point);
 
         let screen_descriptor = egui_wgpu::ScreenDescriptor {
-            size_in_pixels: self.window.inner_size().into(),
+            size_in_pixels: self.surface_size.unwrap_or_else(|| self.window.inner_size().into()),
             pixels_per_point: self.context.pixels_per_point(),
         };
 
@@ -69,6 +146,10 @@ impl Ui {
         self.pending_resize = true;
     }
 
+    pub fn set_surface_size(&mut self, width: u32, height: u32) {
+        self.surface_size = Some([width, height]);
+    }
+
     pub fn context(&self) -> &Context {
         &self.context
     }
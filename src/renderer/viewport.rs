@@ -0,0 +1,66 @@
+use crate::renderer::{
+    camera::Camera, context::RenderContext, hdr::HdrPipeline, layers::RenderLayers, settings::SettingsBuffer,
+    texture::Texture,
+};
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ViewportId(pub u32);
+
+// An additional window onto the same scene. Its camera and size dependent targets are swapped into the core while it
+// renders, so the regular passes draw into them unchanged
+pub struct Viewport {
+    camera: Camera,
+    config: wgpu::SurfaceConfiguration,
+    depth_texture: Texture,
+    hdr: HdrPipeline,
+    layers: RenderLayers,
+}
+
+impl Viewport {
+    pub fn new(config: wgpu::SurfaceConfiguration, settings: &SettingsBuffer, context: &RenderContext) -> Self {
+        let depth_texture = Texture::create_depth_texture(&context.device, &config, Some("Viewport depth texture"));
+        let hdr = HdrPipeline::new(&context.device, &config);
+        let camera = Camera::with_hdr(&hdr, settings, context);
+
+        Self {
+            camera,
+            config,
+            depth_texture,
+            hdr,
+            layers: RenderLayers::ALL,
+        }
+    }
+
+    pub fn resize(&mut self, config: wgpu::SurfaceConfiguration, settings: &SettingsBuffer, context: &RenderContext) {
+        self.depth_texture = Texture::create_depth_texture(&context.device, &config, Some("Viewport depth texture"));
+        self.hdr.resize(&context.device, &config);
+        self.camera.rebind_hdr(&self.hdr, settings, context);
+        self.config = config;
+    }
+
+    pub fn update_camera(
+        &mut self,
+        position: glam::Vec3,
+        view: glam::Mat4,
+        projection: glam::Mat4,
+        context: &RenderContext,
+    ) {
+        self.camera.update(position, view, projection, context);
+    }
+
+    pub fn layers(&self) -> RenderLayers {
+        self.layers
+    }
+
+    pub fn set_layers(&mut self, layers: RenderLayers) {
+        self.layers = layers;
+    }
+
+    // Called once before and once after the viewport's frame
+    pub fn swap(&mut self, camera: &mut Camera, context: &mut RenderContext) {
+        std::mem::swap(&mut self.camera, camera);
+        std::mem::swap(&mut self.config, &mut context.config);
+        std::mem::swap(&mut self.depth_texture, &mut context.depth_texture);
+        std::mem::swap(&mut self.hdr, &mut context.hdr);
+    }
+}
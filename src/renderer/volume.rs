@@ -0,0 +1,624 @@
+use half::f16;
+use serde::{Deserialize, Serialize};
+use wgpu::util::DeviceExt;
+
+use crate::renderer::{camera::Camera, context::RenderContext, memory::Allocation};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ScalarType {
+    Int8,
+    Uint8,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "signed char" | "int8" | "int8_t" => Some(Self::Int8),
+            "uchar" | "unsigned char" | "uint8" | "uint8_t" => Some(Self::Uint8),
+            "short" | "short int" | "signed short" | "int16" | "int16_t" => Some(Self::Int16),
+            "ushort" | "unsigned short" | "unsigned short int" | "uint16" | "uint16_t" => Some(Self::Uint16),
+            "int" | "signed int" | "int32" | "int32_t" => Some(Self::Int32),
+            "uint" | "unsigned int" | "uint32" | "uint32_t" => Some(Self::Uint32),
+            "float" | "float32" => Some(Self::Float32),
+            "double" | "float64" => Some(Self::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Int8 | Self::Uint8 => 1,
+            Self::Int16 | Self::Uint16 => 2,
+            Self::Int32 | Self::Uint32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8], is_little_endian: bool) -> f32 {
+        macro_rules! read {
+            ($type:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                if is_little_endian {
+                    <$type>::from_le_bytes(bytes) as f32
+                } else {
+                    <$type>::from_be_bytes(bytes) as f32
+                }
+            }};
+        }
+
+        match self {
+            Self::Int8 => read!(i8),
+            Self::Uint8 => read!(u8),
+            Self::Int16 => read!(i16),
+            Self::Uint16 => read!(u16),
+            Self::Int32 => read!(i32),
+            Self::Uint32 => read!(u32),
+            Self::Float32 => read!(f32),
+            Self::Float64 => read!(f64),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct VolumeHeader {
+    pub dimensions: [u32; 3],
+    pub spacing: [f32; 3],
+}
+
+// Scalar field normalized to [0, 1] and stored as f16, ready for upload
+pub struct VolumeBuffer {
+    pub header: VolumeHeader,
+    pub data: Vec<u8>,
+}
+
+impl VolumeBuffer {
+    pub fn from_file(file_name: &str, data: &[u8]) -> anyhow::Result<Self> {
+        crate::profile_scope!("Parse volume");
+        let file_name_lower = file_name.to_ascii_lowercase();
+        if file_name_lower.ends_with(".nrrd") {
+            Self::from_nrrd(data)
+        } else if file_name_lower.ends_with(".dcm") || file_name_lower.ends_with(".dicom") {
+            // A series is one file per slice plus tags for their order and spacing, nothing here reads those yet
+            anyhow::bail!("DICOM series are not supported, convert {} to NRRD first", file_name)
+        } else {
+            Self::from_raw(file_name, data)
+        }
+    }
+
+    // Attached NRRD with raw encoding
+    pub fn from_nrrd(data: &[u8]) -> anyhow::Result<Self> {
+        let header_end = data
+            .windows(2)
+            .position(|window| window == b"\n\n")
+            .ok_or_else(|| anyhow::anyhow!("NRRD header is not terminated"))?;
+        let header = std::str::from_utf8(&data[..header_end])?;
+
+        let mut lines = header.lines();
+        if !lines.next().is_some_and(|magic| magic.starts_with("NRRD")) {
+            anyhow::bail!("Not a NRRD file");
+        }
+
+        let mut scalar_type = None;
+        let mut dimensions = None;
+        let mut spacing = [1.0; 3];
+        let mut is_little_endian = true;
+
+        for line in lines.filter(|line| !line.starts_with('#')) {
+            let Some((field, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim_start_matches('=').trim();
+
+            match field.trim() {
+                "type" => scalar_type = ScalarType::from_str(value),
+                "dimension" if value != "3" => anyhow::bail!("Only 3D volumes are supported, got {}", value),
+                "sizes" => {
+                    let sizes = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<Vec<u32>, _>>()?;
+                    dimensions = sizes.try_into().ok();
+                }
+                "spacings" => {
+                    let spacings = value
+                        .split_whitespace()
+                        .filter_map(|spacing| spacing.parse().ok())
+                        .collect::<Vec<f32>>();
+                    spacing = spacings.try_into().unwrap_or(spacing);
+                }
+                // Each direction vector is a voxel step, its length is the spacing along that axis
+                "space directions" => {
+                    let lengths = value
+                        .split_whitespace()
+                        .filter_map(|direction| {
+                            let components = direction
+                                .trim_matches(|c| c == '(' || c == ')')
+                                .split(',')
+                                .map(str::parse::<f32>)
+                                .collect::<Result<Vec<_>, _>>()
+                                .ok()?;
+                            Some(
+                                components
+                                    .iter()
+                                    .map(|component| component * component)
+                                    .sum::<f32>()
+                                    .sqrt(),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    spacing = lengths.try_into().unwrap_or(spacing);
+                }
+                "endian" => is_little_endian = value == "little",
+                "encoding" if value != "raw" => anyhow::bail!("Unsupported NRRD encoding {}", value),
+                "data file" | "datafile" => anyhow::bail!("Detached NRRD data files are not supported"),
+                _ => (),
+            }
+        }
+
+        let scalar_type = scalar_type.ok_or_else(|| anyhow::anyhow!("NRRD type is missing or unsupported"))?;
+        let dimensions = dimensions.ok_or_else(|| anyhow::anyhow!("NRRD sizes are missing"))?;
+
+        Self::from_samples(
+            &data[header_end + 2..],
+            scalar_type,
+            is_little_endian,
+            VolumeHeader { dimensions, spacing },
+        )
+    }
+
+    // Headerless data, the layout comes from the file name, e.g. `engine_256x256x128_uint8.raw`
+    pub fn from_raw(file_name: &str, data: &[u8]) -> anyhow::Result<Self> {
+        let stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem);
+        let tokens = stem.split('_').collect::<Vec<_>>();
+
+        let dimensions = tokens
+            .iter()
+            .find_map(|token| {
+                let sizes = token.split('x').map(str::parse).collect::<Result<Vec<u32>, _>>().ok()?;
+                sizes.try_into().ok()
+            })
+            .ok_or_else(|| anyhow::anyhow!("No WIDTHxHEIGHTxDEPTH in {}", file_name))?;
+        let scalar_type = tokens
+            .iter()
+            .find_map(|token| ScalarType::from_str(token))
+            .unwrap_or(ScalarType::Uint8);
+
+        Self::from_samples(
+            data,
+            scalar_type,
+            true,
+            VolumeHeader {
+                dimensions,
+                spacing: [1.0; 3],
+            },
+        )
+    }
+
+    fn from_samples(
+        data: &[u8],
+        scalar_type: ScalarType,
+        is_little_endian: bool,
+        header: VolumeHeader,
+    ) -> anyhow::Result<Self> {
+        if header.dimensions.contains(&0) {
+            anyhow::bail!("Volume has no voxels, dimensions are {:?}", header.dimensions);
+        }
+        // Header sizes come straight from the file, so don't trust their product to fit
+        let size = header
+            .dimensions
+            .iter()
+            .try_fold(scalar_type.size(), |size, &dimension| {
+                size.checked_mul(dimension as usize)
+            })
+            .ok_or_else(|| anyhow::anyhow!("Volume dimensions {:?} are too large", header.dimensions))?;
+        if data.len() < size {
+            anyhow::bail!("Expected {} bytes of volume data, got {}", size, data.len());
+        }
+
+        let samples = data[..size]
+            .chunks_exact(scalar_type.size())
+            .map(|bytes| scalar_type.decode(bytes, is_little_endian))
+            .collect::<Vec<_>>();
+
+        let (min, max) = samples.iter().fold((f32::MAX, f32::MIN), |(min, max), &sample| {
+            (min.min(sample), max.max(sample))
+        });
+        let range = (max - min).max(f32::EPSILON);
+
+        let data = samples
+            .into_iter()
+            .flat_map(|sample| f16::from_f32((sample - min) / range).to_le_bytes())
+            .collect();
+
+        Ok(Self { header, data })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransferPoint {
+    pub value: f32,
+    pub color: [f32; 3],
+    pub opacity: f32,
+}
+
+// Maps normalized volume values to color and opacity per world unit
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransferFunction {
+    pub points: Vec<TransferPoint>,
+    pub density: f32,
+    pub steps: u32,
+    // Color from the shared scalar color ramp instead of the points
+    pub use_color_ramp: bool,
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        Self {
+            points: vec![
+                TransferPoint {
+                    value: 0.0,
+                    color: [0.0, 0.0, 0.0],
+                    opacity: 0.0,
+                },
+                TransferPoint {
+                    value: 0.3,
+                    color: [0.8, 0.3, 0.1],
+                    opacity: 0.0,
+                },
+                TransferPoint {
+                    value: 0.6,
+                    color: [1.0, 0.8, 0.6],
+                    opacity: 0.4,
+                },
+                TransferPoint {
+                    value: 1.0,
+                    color: [1.0, 1.0, 1.0],
+                    opacity: 0.9,
+                },
+            ],
+            density: 4.0,
+            steps: 256,
+            use_color_ramp: false,
+        }
+    }
+}
+
+impl TransferFunction {
+    pub const RESOLUTION: u32 = 256;
+    pub const MAX_STEPS: u32 = 1024;
+
+    fn bake(&self) -> Vec<u8> {
+        let mut points = self.points.clone();
+        points.sort_by(|a, b| a.value.total_cmp(&b.value));
+
+        (0..Self::RESOLUTION)
+            .flat_map(|index| {
+                let value = index as f32 / (Self::RESOLUTION - 1) as f32;
+                let upper = points.iter().position(|point| point.value >= value);
+                let (color, opacity) = match upper {
+                    _ if points.is_empty() => (glam::Vec3::ZERO, 0.0),
+                    Some(0) => (glam::Vec3::from(points[0].color), points[0].opacity),
+                    None => {
+                        let last = points[points.len() - 1];
+                        (glam::Vec3::from(last.color), last.opacity)
+                    }
+                    Some(upper) => {
+                        let (a, b) = (points[upper - 1], points[upper]);
+                        let t = (value - a.value) / (b.value - a.value).max(f32::EPSILON);
+                        (
+                            glam::Vec3::from(a.color).lerp(glam::Vec3::from(b.color), t),
+                            a.opacity + (b.opacity - a.opacity) * t,
+                        )
+                    }
+                };
+
+                color
+                    .extend(opacity)
+                    .to_array()
+                    .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+            })
+            .collect()
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct VolumeUniform {
+    world_to_volume: [[f32; 4]; 4],
+    steps: u32,
+    density: f32,
+    use_color_ramp: u32,
+    _padding: u32,
+}
+
+struct VolumeTexture {
+    view: wgpu::TextureView,
+    world_to_volume: glam::Mat4,
+    _allocation: Allocation,
+}
+
+// Raymarches a single scalar volume through a transfer function, composited over the opaque scene
+pub struct VolumeRenderer {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    transfer_texture: wgpu::Texture,
+    transfer_view: wgpu::TextureView,
+    transfer_function: TransferFunction,
+    volume: Option<VolumeTexture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl VolumeRenderer {
+    // Height of the largest volume axis in world units
+    const SIZE: f32 = 10.0;
+
+    pub fn new(context: &RenderContext) -> Self {
+        let texture_entry = |binding, view_dimension, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let filterable = wgpu::TextureSampleType::Float { filterable: true };
+        let layout = context
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Volume layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture_entry(1, wgpu::TextureViewDimension::D3, filterable),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    texture_entry(3, wgpu::TextureViewDimension::D2, filterable),
+                    texture_entry(4, wgpu::TextureViewDimension::D2, wgpu::TextureSampleType::Depth),
+                ],
+            });
+
+        let pipeline_layout = context.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Volume pipeline layout"),
+            bind_group_layouts: &[&layout, &context.camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = context
+            .device
+            .create_shader_module(wgpu::include_wgsl!("../../res/volume.wgsl"));
+
+        let pipeline = context.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Volume pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                compilation_options: Default::default(),
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: context.hdr.format(),
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+
+        let sampler = context.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Volume sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let buffer = context.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Volume buffer"),
+            size: std::mem::size_of::<VolumeUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let transfer_texture = context.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Transfer function"),
+            size: wgpu::Extent3d {
+                width: TransferFunction::RESOLUTION,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let transfer_view = transfer_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut renderer = Self {
+            layout,
+            pipeline,
+            sampler,
+            buffer,
+            transfer_texture,
+            transfer_view,
+            transfer_function: TransferFunction::default(),
+            volume: None,
+            bind_group: None,
+        };
+
+        renderer.set_transfer_function(TransferFunction::default(), context);
+        renderer
+    }
+
+    pub fn has_volume(&self) -> bool {
+        self.volume.is_some()
+    }
+
+    pub fn set_volume(&mut self, buffer: VolumeBuffer, label: Option<&str>, context: &RenderContext) {
+        let [width, height, depth] = buffer.header.dimensions;
+        let texture = context.device.create_texture_with_data(
+            &context.queue,
+            &wgpu::TextureDescriptor {
+                label,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: depth,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::R16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            &buffer.data,
+        );
+
+        // Volume data is usually stored with the slice axis last, stand it up along world Y
+        let dimensions = glam::UVec3::from_array(buffer.header.dimensions).as_vec3();
+        let extent = dimensions * glam::Vec3::from_array(buffer.header.spacing);
+        let size = extent / extent.max_element() * Self::SIZE;
+        let volume_to_world = glam::Mat4::from_translation(glam::Vec3::new(-size.x / 2.0, 0.0, size.y / 2.0))
+            * glam::Mat4::from_cols(glam::Vec4::X, glam::Vec4::NEG_Z, glam::Vec4::Y, glam::Vec4::W)
+            * glam::Mat4::from_scale(size);
+
+        self.volume = Some(VolumeTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            world_to_volume: volume_to_world.inverse(),
+            _allocation: context.memory.track_textures([&texture]),
+        });
+        self.write_uniform(context);
+        self.resize(context);
+    }
+
+    pub fn set_transfer_function(&mut self, transfer_function: TransferFunction, context: &RenderContext) {
+        context.queue.write_texture(
+            self.transfer_texture.as_image_copy(),
+            &transfer_function.bake(),
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(TransferFunction::RESOLUTION * 4),
+                rows_per_image: None,
+            },
+            self.transfer_texture.size(),
+        );
+
+        self.transfer_function = transfer_function;
+        self.write_uniform(context);
+    }
+
+    // The depth texture is recreated on resize
+    pub fn resize(&mut self, context: &RenderContext) {
+        self.bind_group = self.volume.as_ref().map(|volume| {
+            context.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Volume bind group"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: self.buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&volume.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&self.transfer_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: wgpu::BindingResource::TextureView(&context.depth_texture.view),
+                    },
+                ],
+            })
+        });
+    }
+
+    // Reads the depth buffer, blends over the HDR target
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, camera: &Camera, context: &RenderContext) {
+        let Some(bind_group) = &self.bind_group else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Volume render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: context.hdr.view(),
+                resolve_target: None,
+                depth_slice: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.set_bind_group(1, camera.bind_group(), &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    fn write_uniform(&self, context: &RenderContext) {
+        let world_to_volume = self
+            .volume
+            .as_ref()
+            .map_or(glam::Mat4::IDENTITY, |volume| volume.world_to_volume);
+        let uniform = VolumeUniform {
+            world_to_volume: world_to_volume.to_cols_array_2d(),
+            steps: self.transfer_function.steps.clamp(1, TransferFunction::MAX_STEPS),
+            density: self.transfer_function.density,
+            use_color_ramp: self.transfer_function.use_color_ramp as u32,
+            _padding: 0,
+        };
+
+        context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+    }
+}
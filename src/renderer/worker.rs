@@ -1,9 +1,8 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 use std::rc::Rc;
 use std::time::Duration;
 
-use crossbeam::channel::Sender;
 use instant::Instant;
 use js_sys::global;
 use serde::{Deserialize, Serialize};
@@ -11,11 +10,15 @@ use wasm_bindgen::{JsCast, prelude::*};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::DedicatedWorkerGlobalScope;
 
-use crate::renderer::asset::{AssetBuffer, AssetKind, SerializableResourcePath};
+use crate::renderer::asset::{AssetBuffer, AssetKind, ImportOptions, ImportReport, SerializableResourcePath};
 use crate::renderer::environment::HdrBuffer;
 use crate::renderer::mesh::SceneBuffer;
 use crate::renderer::pointcloud::PointcloudBuffer;
-use crate::renderer::{RenderCommand, ResourcePath};
+use crate::renderer::registration::Registration;
+use crate::renderer::scheduler::{Scheduler, TaskPriority};
+use crate::renderer::task::{TaskHandle, TaskList};
+use crate::renderer::volume::{VolumeBuffer, VolumeHeader};
+use crate::renderer::{RendererClient, ResourcePath};
 
 macro_rules! js_object {
     ({ $($key:literal : $value:expr),* $(,)? }) => {{
@@ -33,6 +36,7 @@ pub fn init_worker() {
     let mut runtime = WorkerRuntime::new();
     runtime.register::<LoadTask>();
     runtime.register::<UploadTask>();
+    runtime.register::<RefineTask>();
     runtime.run();
 }
 pub struct WorkerRuntime {
@@ -87,8 +91,11 @@ pub trait WorkerTask: 'static {
 
     fn from_message(payload: JsValue) -> Self;
     fn to_message(&self) -> JsValue;
+    fn label(&self) -> String;
     fn run(self, scope: &DedicatedWorkerGlobalScope) -> impl Future<Output = ()>;
-    fn on_complete(&self, result: JsValue, sender: Sender<RenderCommand>, duration: Duration);
+    fn on_complete(&self, result: JsValue, client: RendererClient, duration: Duration);
+    // Instead of `on_complete` once the task was cancelled, queued or running
+    fn on_cancel(&self) {}
 
     fn boxed(self) -> Box<dyn AnyTask>
     where
@@ -101,7 +108,9 @@ pub trait WorkerTask: 'static {
 pub trait AnyTask {
     fn handle(&self) -> &'static str;
     fn to_message(&self) -> JsValue;
-    fn on_complete(&self, result: JsValue, sender: Sender<RenderCommand>, duration: Duration);
+    fn label(&self) -> String;
+    fn on_complete(&self, result: JsValue, client: RendererClient, duration: Duration);
+    fn on_cancel(&self);
 }
 
 impl<T: WorkerTask> AnyTask for T {
@@ -113,8 +122,16 @@ impl<T: WorkerTask> AnyTask for T {
         self.to_message()
     }
 
-    fn on_complete(&self, result: JsValue, sender: Sender<RenderCommand>, duration: Duration) {
-        self.on_complete(result, sender, duration);
+    fn label(&self) -> String {
+        self.label()
+    }
+
+    fn on_complete(&self, result: JsValue, client: RendererClient, duration: Duration) {
+        self.on_complete(result, client, duration);
+    }
+
+    fn on_cancel(&self) {
+        self.on_cancel();
     }
 }
 
@@ -122,6 +139,7 @@ impl<T: WorkerTask> AnyTask for T {
 pub struct LoadTask {
     pub kind: AssetKind,
     pub path: SerializableResourcePath,
+    pub options: ImportOptions,
 }
 
 impl WorkerTask for LoadTask {
@@ -140,72 +158,91 @@ impl WorkerTask for LoadTask {
         object.into()
     }
 
+    fn label(&self) -> String {
+        format!("Loading {}", ResourcePath::from(self.path.clone()).file_name())
+    }
+
     async fn run(self, scope: &DedicatedWorkerGlobalScope) {
         let path: ResourcePath = self.path.into();
         let meta = js_sys::Object::new();
         let buffer = match self.kind {
             AssetKind::Obj => {
-                let scene = SceneBuffer::from_obj(&path).await.unwrap();
+                let (scene, report) = SceneBuffer::from_obj(&path, &self.options).await.unwrap();
+                set_report(&meta, &report);
                 let raw = scene.buffer();
-                js_sys::Uint8Array::new_from_slice(raw).buffer()
+                share_bytes(raw)
             }
             AssetKind::Gltf => {
                 let data = path.load_binary().await.unwrap();
-                let scene = SceneBuffer::from_gltf(data).unwrap();
+                let (scene, report) = SceneBuffer::from_gltf(data, &self.options).unwrap();
+                set_report(&meta, &report);
                 let raw = scene.buffer();
-                js_sys::Uint8Array::new_from_slice(raw).buffer()
+                share_bytes(raw)
             }
             AssetKind::Pointcloud => {
-                let data = path.load_binary().await.unwrap();
-                let pointcloud = PointcloudBuffer::from_las(data).unwrap();
-                let raw = bytemuck::cast_slice(pointcloud.points());
-                js_sys::Uint8Array::new_from_slice(raw).buffer()
+                let pointcloud = match path.load_binary().await {
+                    Ok(data) => PointcloudBuffer::from_file(&path.file_name(), data),
+                    Err(error) => Err(error),
+                };
+                let pointcloud = match pointcloud {
+                    Ok(pointcloud) => pointcloud,
+                    Err(error) => return post_error(scope, &error),
+                };
+                share_bytes(&pointcloud.to_bytes())
             }
             AssetKind::EnvironmentMap => {
                 let data = path.load_binary().await.unwrap();
-                let buffer = HdrBuffer::from_hdr(&data);
+                let buffer = HdrBuffer::from_hdr(&data).unwrap();
                 js_sys::Reflect::set(&meta, &"width".into(), &JsValue::from(buffer.width)).unwrap();
                 js_sys::Reflect::set(&meta, &"height".into(), &JsValue::from(buffer.height)).unwrap();
-                js_sys::Uint8Array::new_from_slice(&buffer.pixels).buffer()
+                share_bytes(&buffer.pixels)
+            }
+            AssetKind::Volume => {
+                let volume = match path.load_binary().await {
+                    Ok(data) => VolumeBuffer::from_file(&path.file_name(), &data),
+                    Err(error) => Err(error),
+                };
+                let volume = match volume {
+                    Ok(volume) => volume,
+                    Err(error) => return post_error(scope, &error),
+                };
+                set_volume_header(&meta, &volume.header);
+                share_bytes(&volume.data)
             }
         };
 
-        let object = js_object!({
-            "data": &buffer,
-            "meta": &meta,
-        });
-
-        scope
-            .post_message_with_transfer(&object, &js_sys::Array::of1(&buffer))
-            .unwrap();
+        post_result(scope, &buffer, &meta);
     }
 
-    fn on_complete(&self, result: JsValue, sender: Sender<RenderCommand>, duration: Duration) {
+    fn on_complete(&self, result: JsValue, client: RendererClient, duration: Duration) {
+        let path: ResourcePath = self.path.clone().into();
+        let file_name = path.file_name().to_string();
+        if let Some(error) = get_error(&result) {
+            log::error!("Unable to load {}: {}", file_name, error);
+            return;
+        }
+
         let data = js_sys::Reflect::get(&result, &"data".into()).unwrap();
         let array = js_sys::Uint8Array::new(&data);
         let mut bytes = vec![0u8; array.length() as usize];
         array.copy_to(&mut bytes);
 
-        let path: ResourcePath = self.path.clone().into();
-        let file_name = path.file_name().to_string();
         match self.kind {
             AssetKind::Obj | AssetKind::Gltf => {
                 let scene = SceneBuffer::from_bytes(&bytes);
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Scene(
+                client
+                    .load_asset(AssetBuffer::Scene(
                         scene,
                         Some(file_name.clone()),
-                    )))
+                        get_report(&result),
+                        self.options.mode,
+                    ))
                     .unwrap();
             }
             AssetKind::Pointcloud => {
-                let points = bytemuck::cast_slice(&bytes);
-                let pointcloud = PointcloudBuffer::new(points.to_vec());
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Pointcloud(
-                        pointcloud,
-                        Some(file_name.clone()),
-                    )))
+                let pointcloud = PointcloudBuffer::from_bytes(&bytes);
+                client
+                    .load_asset(AssetBuffer::Pointcloud(pointcloud, Some(file_name.clone())))
                     .unwrap();
             }
             AssetKind::EnvironmentMap => {
@@ -218,11 +255,21 @@ impl WorkerTask for LoadTask {
                     height,
                 };
 
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::EnvironmentMap {
+                client
+                    .load_asset(AssetBuffer::EnvironmentMap {
                         buffer,
                         label: Some(file_name.clone()),
-                    }))
+                    })
+                    .unwrap();
+            }
+            AssetKind::Volume => {
+                let buffer = VolumeBuffer {
+                    header: get_volume_header(&result),
+                    data: bytes,
+                };
+
+                client
+                    .load_asset(AssetBuffer::Volume(buffer, Some(file_name.clone())))
                     .unwrap();
             }
         }
@@ -234,6 +281,7 @@ impl WorkerTask for LoadTask {
 pub struct UploadTask {
     pub kind: AssetKind,
     pub path: ResourcePath,
+    pub options: ImportOptions,
 }
 
 impl WorkerTask for UploadTask {
@@ -249,7 +297,10 @@ impl WorkerTask for UploadTask {
         let file: web_sys::File = js_sys::Reflect::get(&payload, &"file".into()).unwrap().unchecked_into();
         let path = ResourcePath::Upload(file);
 
-        Self { path, kind }
+        let options = js_sys::Reflect::get(&payload, &"options".into()).unwrap();
+        let options = serde_wasm_bindgen::from_value(options).unwrap_or_default();
+
+        Self { path, kind, options }
     }
 
     fn to_message(&self) -> JsValue {
@@ -257,6 +308,7 @@ impl WorkerTask for UploadTask {
         let payload = js_object!({
             "file": file.value_of(),
             "kind": JsValue::from_str(self.kind.to_str()),
+            "options": serde_wasm_bindgen::to_value(&self.options).unwrap(),
         });
 
         let object = js_object!({
@@ -267,46 +319,63 @@ impl WorkerTask for UploadTask {
         object.into()
     }
 
+    fn label(&self) -> String {
+        format!("Loading {}", self.path.file_name())
+    }
+
     async fn run(self, scope: &DedicatedWorkerGlobalScope) {
-        let bytes = self.path.load_binary().await.unwrap();
+        let bytes = match self.path.load_binary().await {
+            Ok(bytes) => bytes,
+            Err(error) => return post_error(scope, &error),
+        };
         let meta = js_sys::Object::new();
         let buffer = match self.kind {
             AssetKind::Obj => {
                 // TODO This does not work for uploads
-                let scene = SceneBuffer::from_obj(&self.path).await.unwrap();
+                let (scene, report) = SceneBuffer::from_obj(&self.path, &self.options).await.unwrap();
+                set_report(&meta, &report);
                 let raw = scene.buffer();
-                js_sys::Uint8Array::new_from_slice(raw).buffer()
+                share_bytes(raw)
             }
             AssetKind::Gltf => {
-                let scene = SceneBuffer::from_gltf(bytes).unwrap();
+                let (scene, report) = SceneBuffer::from_gltf(bytes, &self.options).unwrap();
+                set_report(&meta, &report);
                 let raw = scene.buffer();
-                js_sys::Uint8Array::new_from_slice(raw).buffer()
+                share_bytes(raw)
             }
             AssetKind::Pointcloud => {
-                let pointcloud = PointcloudBuffer::from_las(bytes).unwrap();
-                let raw = bytemuck::cast_slice(pointcloud.points());
-                js_sys::Uint8Array::new_from_slice(raw).buffer()
+                let pointcloud = match PointcloudBuffer::from_file(&self.path.file_name(), bytes) {
+                    Ok(pointcloud) => pointcloud,
+                    Err(error) => return post_error(scope, &error),
+                };
+                share_bytes(&pointcloud.to_bytes())
             }
             AssetKind::EnvironmentMap => {
-                let buffer = HdrBuffer::from_hdr(&bytes);
+                let buffer = HdrBuffer::from_hdr(&bytes).unwrap();
                 js_sys::Reflect::set(&meta, &"width".into(), &JsValue::from(buffer.width)).unwrap();
                 js_sys::Reflect::set(&meta, &"height".into(), &JsValue::from(buffer.height)).unwrap();
-                js_sys::Uint8Array::new_from_slice(&buffer.pixels).buffer()
+                share_bytes(&buffer.pixels)
+            }
+            AssetKind::Volume => {
+                let volume = match VolumeBuffer::from_file(&self.path.file_name(), &bytes) {
+                    Ok(volume) => volume,
+                    Err(error) => return post_error(scope, &error),
+                };
+                set_volume_header(&meta, &volume.header);
+                share_bytes(&volume.data)
             }
         };
 
-        let object = js_object!({
-            "data": &buffer,
-            "meta": &meta,
-        });
-
-        scope
-            .post_message_with_transfer(&object, &js_sys::Array::of1(&buffer))
-            .unwrap();
+        post_result(scope, &buffer, &meta);
     }
 
-    fn on_complete(&self, result: JsValue, sender: Sender<RenderCommand>, duration: Duration) {
+    fn on_complete(&self, result: JsValue, client: RendererClient, duration: Duration) {
         let file_name = self.path.file_name().to_string();
+        if let Some(error) = get_error(&result) {
+            log::error!("Unable to load {}: {}", file_name, error);
+            return;
+        }
+
         let data = js_sys::Reflect::get(&result, &"data".into()).unwrap();
 
         let array = js_sys::Uint8Array::new(&data);
@@ -316,21 +385,19 @@ impl WorkerTask for UploadTask {
         match self.kind {
             AssetKind::Obj | AssetKind::Gltf => {
                 let model = SceneBuffer::from_bytes(&bytes);
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Scene(
+                client
+                    .load_asset(AssetBuffer::Scene(
                         model,
                         Some(file_name.clone()),
-                    )))
+                        get_report(&result),
+                        self.options.mode,
+                    ))
                     .unwrap();
             }
             AssetKind::Pointcloud => {
-                let points = bytemuck::cast_slice(&bytes);
-                let pointcloud = PointcloudBuffer::new(points.to_vec());
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::Pointcloud(
-                        pointcloud,
-                        Some(file_name.clone()),
-                    )))
+                let pointcloud = PointcloudBuffer::from_bytes(&bytes);
+                client
+                    .load_asset(AssetBuffer::Pointcloud(pointcloud, Some(file_name.clone())))
                     .unwrap();
             }
             AssetKind::EnvironmentMap => {
@@ -343,11 +410,21 @@ impl WorkerTask for UploadTask {
                     height,
                 };
 
-                sender
-                    .send(RenderCommand::LoadAsset(AssetBuffer::EnvironmentMap {
+                client
+                    .load_asset(AssetBuffer::EnvironmentMap {
                         buffer,
                         label: Some(file_name.clone()),
-                    }))
+                    })
+                    .unwrap();
+            }
+            AssetKind::Volume => {
+                let buffer = VolumeBuffer {
+                    header: get_volume_header(&result),
+                    data: bytes,
+                };
+
+                client
+                    .load_asset(AssetBuffer::Volume(buffer, Some(file_name.clone())))
                     .unwrap();
             }
         }
@@ -356,8 +433,176 @@ impl WorkerTask for UploadTask {
     }
 }
 
+// ICP for scan registration. The page samples both scans, so only world positions travel to the worker
+pub struct RefineTask {
+    pub source: Vec<glam::Vec3>,
+    pub target: Vec<glam::Vec3>,
+    pub source_transform: glam::Mat4,
+    pub max_distance: f32,
+    // Only on the page, gets None when the task was cancelled and Some(None) when ICP found too few matches
+    pub on_result: Option<Box<dyn Fn(Option<Option<Registration>>)>>,
+}
+
+impl WorkerTask for RefineTask {
+    const HANDLE: &'static str = "refine";
+
+    fn from_message(payload: JsValue) -> Self {
+        let positions = |key: &str| {
+            let array = js_sys::Float32Array::new(&js_sys::Reflect::get(&payload, &key.into()).unwrap());
+            array
+                .to_vec()
+                .chunks_exact(3)
+                .map(glam::Vec3::from_slice)
+                .collect::<Vec<_>>()
+        };
+        let transform = js_sys::Float32Array::new(&js_sys::Reflect::get(&payload, &"transform".into()).unwrap());
+        let max_distance = js_sys::Reflect::get(&payload, &"max_distance".into())
+            .unwrap()
+            .as_f64()
+            .unwrap();
+
+        Self {
+            source: positions("source"),
+            target: positions("target"),
+            source_transform: glam::Mat4::from_cols_slice(&transform.to_vec()),
+            max_distance: max_distance as f32,
+            on_result: None,
+        }
+    }
+
+    fn to_message(&self) -> JsValue {
+        let positions = |positions: &[glam::Vec3]| {
+            let flat = positions
+                .iter()
+                .flat_map(|position| position.to_array())
+                .collect::<Vec<_>>();
+            js_sys::Float32Array::from(flat.as_slice())
+        };
+        let payload = js_object!({
+            "source": positions(&self.source),
+            "target": positions(&self.target),
+            "transform": js_sys::Float32Array::from(self.source_transform.to_cols_array().as_slice()),
+            "max_distance": JsValue::from(self.max_distance),
+        });
+
+        let object = js_object!({
+            "type": JsValue::from_str(self.handle()),
+            "payload": payload,
+        });
+
+        object.into()
+    }
+
+    fn label(&self) -> String {
+        "Registering scans".to_string()
+    }
+
+    // Cancelling only drops the result, the worker runs ICP to the end
+    async fn run(self, scope: &DedicatedWorkerGlobalScope) {
+        let registration = Registration::refine(
+            &self.source,
+            &self.target,
+            self.source_transform,
+            self.max_distance,
+            || false,
+        );
+        let object = js_object!({
+            "registration": serde_wasm_bindgen::to_value(&registration).unwrap(),
+        });
+        scope.post_message(&object).unwrap();
+    }
+
+    fn on_complete(&self, result: JsValue, _client: RendererClient, duration: Duration) {
+        let registration = js_sys::Reflect::get(&result, &"registration".into())
+            .ok()
+            .and_then(|value| serde_wasm_bindgen::from_value(value).ok())
+            .flatten();
+        log::info!("Refined the registration in {} s", duration.as_secs_f32());
+        if let Some(on_result) = &self.on_result {
+            on_result(Some(registration));
+        }
+    }
+
+    fn on_cancel(&self) {
+        if let Some(on_result) = &self.on_result {
+            on_result(None);
+        }
+    }
+}
+
+// Cross-origin isolated pages get a SharedArrayBuffer that the page reads in place, without a transfer detaching it
+// here. Elsewhere a plain buffer is transferred. Each wasm instance still has its own memory, so the bytes are copied
+// into the buffer here and out of it in `on_complete` either way
+fn is_shared_memory_available() -> bool {
+    js_sys::Reflect::get(&global(), &"crossOriginIsolated".into()).is_ok_and(|value| value.is_truthy())
+        && js_sys::Reflect::has(&global(), &"SharedArrayBuffer".into()).unwrap_or(false)
+}
+
+fn share_bytes(bytes: &[u8]) -> JsValue {
+    if is_shared_memory_available() {
+        let buffer = js_sys::SharedArrayBuffer::new(bytes.len() as u32);
+        js_sys::Uint8Array::new(&buffer).copy_from(bytes);
+        buffer.into()
+    } else {
+        js_sys::Uint8Array::new_from_slice(bytes).buffer().into()
+    }
+}
+
+// Plain buffers are transferred, shared ones can't be and don't need to be
+fn post_result(scope: &DedicatedWorkerGlobalScope, buffer: &JsValue, meta: &js_sys::Object) {
+    let object = js_object!({
+        "data": buffer,
+        "meta": meta,
+    });
+
+    let result = if buffer.is_instance_of::<js_sys::ArrayBuffer>() {
+        scope.post_message_with_transfer(&object, &js_sys::Array::of1(buffer))
+    } else {
+        scope.post_message(&object)
+    };
+    result.unwrap();
+}
+
+// Parse failures come back instead of a result, so the page logs them rather than the worker panicking
+fn post_error(scope: &DedicatedWorkerGlobalScope, error: &anyhow::Error) {
+    let object = js_object!({
+        "error": JsValue::from_str(&error.to_string()),
+    });
+    scope.post_message(&object).unwrap();
+}
+
+fn get_error(result: &JsValue) -> Option<String> {
+    js_sys::Reflect::get(result, &"error".into()).ok()?.as_string()
+}
+
+// Import reports travel next to the scene blob in the message meta
+fn set_report(meta: &js_sys::Object, report: &ImportReport) {
+    let value = serde_wasm_bindgen::to_value(report).unwrap();
+    js_sys::Reflect::set(meta, &"report".into(), &value).unwrap();
+}
+
+fn get_report(result: &JsValue) -> ImportReport {
+    js_sys::Reflect::get(result, &"meta".into())
+        .and_then(|meta| js_sys::Reflect::get(&meta, &"report".into()))
+        .ok()
+        .and_then(|value| serde_wasm_bindgen::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn set_volume_header(meta: &js_sys::Object, header: &VolumeHeader) {
+    let value = serde_wasm_bindgen::to_value(header).unwrap();
+    js_sys::Reflect::set(meta, &"volume".into(), &value).unwrap();
+}
+
+fn get_volume_header(result: &JsValue) -> VolumeHeader {
+    let meta = js_sys::Reflect::get(result, &"meta".into()).unwrap();
+    let value = js_sys::Reflect::get(&meta, &"volume".into()).unwrap();
+    serde_wasm_bindgen::from_value(value).unwrap()
+}
+
 struct Submission {
     task: Box<dyn AnyTask>,
+    handle: TaskHandle,
     start: Instant,
 }
 
@@ -409,14 +654,14 @@ pub struct WorkerPool {
 }
 
 impl WorkerPool {
-    pub fn new(sender: Sender<RenderCommand>) -> Self {
+    pub fn new(client: RendererClient, tasks: TaskList) -> Self {
         let capacity = web_sys::window().unwrap().navigator().hardware_concurrency();
         let inner = WorkerPoolInner {
             workers: Vec::new(),
-            queue: VecDeque::new(),
-            capacity: capacity as usize,
-            render_tx: sender,
+            scheduler: Scheduler::new(capacity as usize),
+            client,
             submissions: HashMap::new(),
+            tasks,
         };
 
         Self {
@@ -424,20 +669,33 @@ impl WorkerPool {
         }
     }
 
-    pub fn submit<T>(&self, task: T)
+    pub fn submit<T>(&self, task: T, priority: TaskPriority)
     where
         T: WorkerTask,
     {
         let mut pool = self.inner.borrow_mut();
-        if let Some(worker) = pool.workers.iter_mut().find(|w| matches!(w.state, WorkerState::Ready)) {
-            let worker_id = worker.id;
-            pool.assign_task(worker_id, task.boxed());
-            return;
-        }
+        let handle = pool.tasks.start(task.label());
+        pool.scheduler.push((task.boxed(), handle), priority);
+        pool.dispatch_next();
+        self.spawn_workers(&mut pool);
+    }
 
-        pool.queue.push_back(task.boxed());
+    pub fn max_concurrency(&self) -> usize {
+        self.inner.borrow().scheduler.max_concurrency()
+    }
+
+    pub fn set_max_concurrency(&self, max_concurrency: usize) {
+        let mut pool = self.inner.borrow_mut();
+        pool.scheduler.set_max_concurrency(max_concurrency);
+        pool.dispatch_next();
+        self.spawn_workers(&mut pool);
+    }
 
-        if pool.workers.len() < pool.capacity {
+    // Workers start on demand, never more than the scheduler lets run at once. Surplus workers from a higher limit
+    // stay alive but only get as many tasks as the limit allows
+    fn spawn_workers(&self, pool: &mut WorkerPoolInner) {
+        let is_waiting = pool.scheduler.has_queued();
+        if is_waiting && pool.workers.len() < pool.scheduler.max_concurrency() {
             let id = pool.workers.len();
             let worker = Worker::new(id, &self.inner);
             pool.workers.push(worker);
@@ -447,10 +705,10 @@ impl WorkerPool {
 
 pub struct WorkerPoolInner {
     workers: Vec<Worker>,
-    queue: VecDeque<Box<dyn AnyTask>>,
-    capacity: usize,
-    render_tx: Sender<RenderCommand>,
+    scheduler: Scheduler<(Box<dyn AnyTask>, TaskHandle)>,
+    client: RendererClient,
     submissions: HashMap<usize, Submission>,
+    tasks: TaskList,
 }
 
 impl WorkerPoolInner {
@@ -466,8 +724,13 @@ impl WorkerPoolInner {
         }
 
         if let Some(submission) = self.submissions.remove(&worker_id) {
-            let duration = submission.start.elapsed();
-            submission.task.on_complete(data, self.render_tx.clone(), duration);
+            self.scheduler.finish();
+            if submission.handle.is_cancelled() {
+                submission.task.on_cancel();
+            } else {
+                let duration = submission.start.elapsed();
+                submission.task.on_complete(data, self.client.clone(), duration);
+            }
         }
 
         if let Some(worker) = self.workers.get_mut(worker_id) {
@@ -478,17 +741,26 @@ impl WorkerPoolInner {
     }
 
     fn dispatch_next(&mut self) {
-        if let Some(next_task) = self.queue.pop_front() {
-            if let Some(worker) = self.workers.iter_mut().find(|w| matches!(w.state, WorkerState::Ready)) {
-                let worker_id = worker.id;
-                self.assign_task(worker_id, next_task);
-            } else {
-                self.queue.push_front(next_task);
+        while let Some(worker_id) = self
+            .workers
+            .iter()
+            .find(|w| matches!(w.state, WorkerState::Ready))
+            .map(|worker| worker.id)
+        {
+            let Some((next_task, handle)) = self.scheduler.pop() else {
+                break;
+            };
+            // Tasks cancelled while queued never reach a worker
+            if handle.is_cancelled() {
+                next_task.on_cancel();
+                self.scheduler.finish();
+                continue;
             }
+            self.assign_task(worker_id, next_task, handle);
         }
     }
 
-    fn assign_task(&mut self, worker_id: usize, task: Box<dyn AnyTask>) {
+    fn assign_task(&mut self, worker_id: usize, task: Box<dyn AnyTask>, handle: TaskHandle) {
         let message = task.to_message();
 
         let worker = &mut self.workers[worker_id];
@@ -499,6 +771,7 @@ impl WorkerPoolInner {
             worker_id,
             Submission {
                 task,
+                handle,
                 start: Instant::now(),
             },
         );
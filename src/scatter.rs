@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::renderer::{RenderId, SurfaceHit};
+
+pub struct ScatterBrush {
+    pub enabled: bool,
+    pub render_id: Option<RenderId>,
+    pub radius: f32,
+    pub density: f32,
+    pub scale: f32,
+    pub scale_jitter: f32,
+    pub rotation_jitter: f32,
+    is_painting: bool,
+    pending: f32,
+    rng: fastrand::Rng,
+}
+
+impl ScatterBrush {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            render_id: None,
+            radius: 2.0,
+            density: 10.0,
+            scale: 1.0,
+            scale_jitter: 0.25,
+            rotation_jitter: 180.0,
+            is_painting: false,
+            pending: 0.0,
+            rng: fastrand::Rng::with_seed(Uuid::new_v4().as_u64_pair().0),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.render_id.is_some()
+    }
+
+    pub fn is_painting(&self) -> bool {
+        self.is_painting
+    }
+
+    pub fn begin_stroke(&mut self) {
+        self.is_painting = self.is_active();
+        self.pending = 0.0;
+    }
+
+    pub fn end_stroke(&mut self) {
+        self.is_painting = false;
+    }
+
+    pub fn update(&mut self, dt: Duration, hit: Option<SurfaceHit>) -> Vec<glam::Mat4> {
+        let Some(hit) = hit else {
+            return Vec::new();
+        };
+
+        if !self.is_painting {
+            return Vec::new();
+        }
+
+        self.pending += self.density * dt.as_secs_f32();
+        let count = self.pending.floor() as usize;
+        self.pending -= count as f32;
+
+        (0..count).map(|_| self.sample(&hit)).collect()
+    }
+
+    fn sample(&mut self, hit: &SurfaceHit) -> glam::Mat4 {
+        let alignment = glam::Quat::from_rotation_arc(glam::Vec3::Y, hit.normal.normalize_or(glam::Vec3::Y));
+
+        let angle = self.rng.f32() * std::f32::consts::TAU;
+        let distance = self.radius * self.rng.f32().sqrt();
+        let offset = alignment * glam::Vec3::new(angle.cos() * distance, 0.0, angle.sin() * distance);
+
+        let yaw = self.jitter() * self.rotation_jitter.to_radians();
+        let rotation = alignment * glam::Quat::from_rotation_y(yaw);
+        let scale = (self.scale * (1.0 + self.jitter() * self.scale_jitter)).max(0.01);
+
+        glam::Mat4::from_scale_rotation_translation(glam::Vec3::splat(scale), rotation, hit.position + offset)
+    }
+
+    fn jitter(&mut self) -> f32 {
+        self.rng.f32() * 2.0 - 1.0
+    }
+}
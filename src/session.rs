@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufRead, BufReader, Write},
+    net::{IpAddr, Ipv4Addr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crossbeam::channel::{Receiver, Sender};
+use instant::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    camera::Camera,
+    entity::EntityId,
+    renderer::{AssetKind, ImportOptions, RenderId, ResourcePath},
+};
+
+const CAMERA_INTERVAL: Duration = Duration::from_millis(100);
+// Cameras that stopped reporting belong to viewers that left
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+// Spawns waiting for an asset whose load failed or never had that node
+const PENDING_TIMEOUT: Duration = Duration::from_secs(300);
+
+// First line every viewer sends, the relay drops connections with the wrong token
+#[derive(Serialize, Deserialize)]
+struct Handshake {
+    token: Option<String>,
+}
+
+// One JSON object per line, ids as strings and matrices column major like glam
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum SessionMessage {
+    Load {
+        path: String,
+        options: ImportOptions,
+    },
+    // Assets are matched by file name and node, every viewer loads them from their own copy of the dataset
+    Spawn {
+        entity: String,
+        asset: String,
+        node: usize,
+        transform: [f32; 16],
+        label: Option<String>,
+    },
+    Transform {
+        entity: String,
+        transform: [f32; 16],
+    },
+    Camera {
+        peer: String,
+        name: String,
+        position: [f32; 3],
+        forward: [f32; 3],
+    },
+}
+
+// What another viewer did, for the app to mirror
+pub enum SessionEvent {
+    Load {
+        path: ResourcePath,
+        options: ImportOptions,
+    },
+    Spawn {
+        entity_id: EntityId,
+        render_id: RenderId,
+        asset_id: Uuid,
+        transform: glam::Mat4,
+        label: Option<String>,
+    },
+    Transform {
+        entity_id: EntityId,
+        transform: glam::Mat4,
+    },
+    // A camera moved, joined or left
+    Presence,
+}
+
+pub struct PeerCamera {
+    pub name: String,
+    pub position: glam::Vec3,
+    pub forward: glam::Vec3,
+    last_seen: Instant,
+}
+
+// `--relay <port>` hosts a session and joins it, `--join <address>` joins one, `--name <name>` is shown to the
+// others. The relay only listens on localhost unless `--relay-host <address>` is given, which needs a
+// `--token <token>` (or `SESSION_TOKEN`) that every viewer joining it passes too. Loads from the others are only
+// opened below a `--session-root <directory or url>`, the working directory without any. Viewers share loads,
+// spawns, transforms and their camera, but only what happens while they're connected. There's no authority, the
+// last transform to arrive wins
+pub struct Session {
+    peer: String,
+    name: String,
+    outgoing_tx: Sender<SessionMessage>,
+    incoming_rx: Receiver<SessionMessage>,
+    peers: HashMap<String, PeerCamera>,
+    last_camera: Option<(Instant, glam::Vec3, glam::Vec3)>,
+    // Loads requested by others, so they aren't sent back and their nodes wait for the others' spawns
+    remote_paths: Vec<String>,
+    remote_labels: Vec<String>,
+    remote_assets: HashSet<Uuid>,
+    // File name and node index of every loaded renderable, the same on every viewer
+    nodes: HashMap<Uuid, Vec<RenderId>>,
+    keys: HashMap<RenderId, (String, usize)>,
+    renderables: HashMap<(String, usize), (Uuid, RenderId)>,
+    // Spawns of assets that haven't finished loading here yet, with when they arrived
+    pending: Vec<(Instant, SessionMessage)>,
+    // Canonical directories and urls loads from the others have to be in
+    root_dirs: Vec<PathBuf>,
+    root_urls: Vec<reqwest::Url>,
+}
+
+impl Session {
+    pub fn from_args() -> Option<Self> {
+        let args = std::env::args().collect::<Vec<_>>();
+        let value = |flag: &str| {
+            args.iter()
+                .position(|arg| arg == flag)
+                .and_then(|index| args.get(index + 1))
+                .cloned()
+        };
+
+        let token = value("--token").or_else(|| std::env::var("SESSION_TOKEN").ok());
+        let address = match (value("--relay"), value("--join")) {
+            (Some(port), _) => {
+                let host = value("--relay-host");
+                match port
+                    .parse()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|port| relay(port, host.as_deref(), token.clone()))
+                {
+                    Ok(port) => format!("127.0.0.1:{}", port),
+                    Err(error) => {
+                        log::error!("Unable to start session relay: {}", error);
+                        return None;
+                    }
+                }
+            }
+            (None, Some(address)) => address,
+            (None, None) => return None,
+        };
+
+        let mut roots = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--session-root")
+            .map(|pair| pair[1].clone())
+            .collect::<Vec<_>>();
+        if roots.is_empty() {
+            roots.push(".".to_string());
+        }
+
+        let name = value("--name")
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| "Viewer".to_string());
+        match Self::connect(&address, name, token, &roots) {
+            Ok(session) => Some(session),
+            Err(error) => {
+                log::error!("Unable to join session at {}: {}", address, error);
+                None
+            }
+        }
+    }
+
+    fn connect(address: &str, name: String, token: Option<String>, roots: &[String]) -> anyhow::Result<Self> {
+        let mut root_dirs = Vec::new();
+        let mut root_urls = Vec::new();
+        for root in roots {
+            match reqwest::Url::parse(root) {
+                Ok(mut url) if matches!(url.scheme(), "http" | "https") => {
+                    // So a root doesn't also allow its siblings sharing a prefix
+                    if !url.path().ends_with('/') {
+                        url.set_path(&format!("{}/", url.path()));
+                    }
+                    root_urls.push(url);
+                }
+                _ => root_dirs.push(std::fs::canonicalize(root)?),
+            }
+        }
+
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut writer = stream.try_clone()?;
+        writeln!(writer, "{}", serde_json::to_string(&Handshake { token })?)?;
+        log::info!("Joined session at {}", address);
+
+        let (outgoing_tx, outgoing_rx) = crossbeam::channel::unbounded::<SessionMessage>();
+        std::thread::spawn(move || {
+            for message in outgoing_rx {
+                let Ok(line) = serde_json::to_string(&message) else {
+                    continue;
+                };
+                if writeln!(writer, "{}", line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (incoming_tx, incoming_rx) = crossbeam::channel::unbounded();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                match serde_json::from_str(&line) {
+                    Ok(message) => {
+                        if incoming_tx.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(error) => log::warn!("Ignoring session message: {}", error),
+                }
+            }
+            log::warn!("Session connection closed");
+        });
+
+        Ok(Self {
+            peer: Uuid::new_v4().to_string(),
+            name,
+            outgoing_tx,
+            incoming_rx,
+            peers: HashMap::new(),
+            last_camera: None,
+            remote_paths: Vec::new(),
+            remote_labels: Vec::new(),
+            remote_assets: HashSet::new(),
+            nodes: HashMap::new(),
+            keys: HashMap::new(),
+            renderables: HashMap::new(),
+            pending: Vec::new(),
+            root_dirs,
+            root_urls,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &PeerCamera> {
+        self.peers.values()
+    }
+
+    // Everything the others did since the last poll, and spawns that were waiting for their asset to load here
+    pub fn poll(&mut self) -> Vec<SessionEvent> {
+        let peer_count = self.peers.len();
+        self.peers.retain(|_, peer| peer.last_seen.elapsed() < PEER_TIMEOUT);
+        let has_left = self.peers.len() != peer_count;
+
+        let now = Instant::now();
+        let incoming = self
+            .incoming_rx
+            .try_iter()
+            .map(|message| (now, message))
+            .collect::<Vec<_>>();
+        let messages = std::mem::take(&mut self.pending)
+            .into_iter()
+            .chain(incoming)
+            .collect::<Vec<_>>();
+        let mut events = messages
+            .into_iter()
+            .filter_map(|(received, message)| self.receive(message, received))
+            .collect::<Vec<_>>();
+        if has_left {
+            events.push(SessionEvent::Presence);
+        }
+        events
+    }
+
+    fn receive(&mut self, message: SessionMessage, received: Instant) -> Option<SessionEvent> {
+        match message {
+            SessionMessage::Load { path, options } => {
+                let Some(path) = self.allowed_path(&path) else {
+                    log::warn!("Not loading {} for the session, it's outside the session roots", path);
+                    return None;
+                };
+                let kind = path.extension().as_deref().and_then(AssetKind::from_extension);
+                if kind.is_some_and(|kind| kind.adds_nodes()) {
+                    self.remote_labels.push(path.file_name().to_string());
+                }
+                self.remote_paths.push(path.as_str().to_string());
+                Some(SessionEvent::Load { path, options })
+            }
+            SessionMessage::Spawn {
+                ref entity,
+                ref asset,
+                node,
+                transform,
+                ref label,
+            } => {
+                let Some(&(asset_id, render_id)) = self.renderables.get(&(asset.clone(), node)) else {
+                    // Only waits while the asset is loading here, its first node shows up before the others
+                    let is_loading =
+                        self.remote_labels.contains(asset) || self.renderables.contains_key(&(asset.clone(), 0));
+                    if is_loading && received.elapsed() < PENDING_TIMEOUT {
+                        self.pending.push((received, message));
+                    } else {
+                        log::warn!("Dropping spawn of node {} of {}, it isn't loaded here", node, asset);
+                    }
+                    return None;
+                };
+
+                Some(SessionEvent::Spawn {
+                    entity_id: Uuid::parse_str(entity).ok()?,
+                    render_id,
+                    asset_id,
+                    transform: glam::Mat4::from_cols_array(&transform),
+                    label: label.clone(),
+                })
+            }
+            SessionMessage::Transform { entity, transform } => Some(SessionEvent::Transform {
+                entity_id: Uuid::parse_str(&entity).ok()?,
+                transform: glam::Mat4::from_cols_array(&transform),
+            }),
+            SessionMessage::Camera {
+                peer,
+                name,
+                position,
+                forward,
+            } => {
+                self.peers.insert(
+                    peer,
+                    PeerCamera {
+                        name,
+                        position: glam::Vec3::from_array(position),
+                        forward: glam::Vec3::from_array(forward),
+                        last_seen: Instant::now(),
+                    },
+                );
+                Some(SessionEvent::Presence)
+            }
+        }
+    }
+
+    // Files are resolved first, so links and `..` can't leave the roots
+    fn allowed_path(&self, path: &str) -> Option<ResourcePath> {
+        match reqwest::Url::parse(path) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => self
+                .root_urls
+                .iter()
+                .any(|root| url.origin() == root.origin() && url.path().starts_with(root.path()))
+                .then_some(ResourcePath::Url(url)),
+            _ => {
+                let path = std::fs::canonicalize(path).ok()?;
+                self.root_dirs
+                    .iter()
+                    .any(|root| path.starts_with(root))
+                    .then_some(ResourcePath::File(path))
+            }
+        }
+    }
+
+    // Shares a load the app started. Returns false for loads it made on behalf of the others, which aren't sent back
+    pub fn load(&mut self, path: String, options: ImportOptions) -> bool {
+        if let Some(index) = self.remote_paths.iter().position(|remote| *remote == path) {
+            self.remote_paths.remove(index);
+            return false;
+        }
+
+        self.send(SessionMessage::Load { path, options });
+        true
+    }
+
+    // Remembers where a loaded node sits in its asset. Returns whether the asset was loaded for the others, its
+    // entities are then spawned by their messages instead of here
+    pub fn register_node(&mut self, asset_id: Uuid, render_id: RenderId, label: Option<&str>) -> bool {
+        let Some(label) = label else {
+            return false;
+        };
+
+        let nodes = self.nodes.entry(asset_id).or_default();
+        let key = (label.to_string(), nodes.len());
+        nodes.push(render_id);
+        self.keys.insert(render_id, key.clone());
+        self.renderables.insert(key, (asset_id, render_id));
+
+        if nodes.len() == 1
+            && let Some(index) = self.remote_labels.iter().position(|remote| remote == label)
+        {
+            self.remote_labels.remove(index);
+            self.remote_assets.insert(asset_id);
+        }
+
+        self.remote_assets.contains(&asset_id)
+    }
+
+    pub fn spawn(&self, entity_id: EntityId, render_id: RenderId, transform: glam::Mat4, label: Option<String>) {
+        if let Some((asset, node)) = self.keys.get(&render_id) {
+            self.send(SessionMessage::Spawn {
+                entity: entity_id.to_string(),
+                asset: asset.clone(),
+                node: *node,
+                transform: transform.to_cols_array(),
+                label,
+            });
+        }
+    }
+
+    pub fn transform(&self, entity_id: EntityId, transform: glam::Mat4) {
+        self.send(SessionMessage::Transform {
+            entity: entity_id.to_string(),
+            transform: transform.to_cols_array(),
+        });
+    }
+
+    // At most ten times a second, and only when it moved
+    pub fn camera(&mut self, camera: &Camera) {
+        let (position, forward) = (camera.position(), camera.forward());
+        if let Some((sent, last_position, last_forward)) = self.last_camera
+            && (sent.elapsed() < CAMERA_INTERVAL || (last_position == position && last_forward == forward))
+        {
+            return;
+        }
+
+        self.last_camera = Some((Instant::now(), position, forward));
+        self.send(SessionMessage::Camera {
+            peer: self.peer.clone(),
+            name: self.name.clone(),
+            position: position.to_array(),
+            forward: forward.to_array(),
+        });
+    }
+
+    fn send(&self, message: SessionMessage) {
+        self.outgoing_tx.send(message).ok();
+    }
+}
+
+// Forwards every line a viewer sends to all the others, once its handshake carries the token. Listens on localhost
+// unless a host is given, other machines can only join with a token. Returns the port it listens on
+fn relay(port: u16, host: Option<&str>, token: Option<String>) -> anyhow::Result<u16> {
+    let host = match host {
+        Some(host) if token.is_none() => anyhow::bail!("listening on {} needs a --token", host),
+        Some(host) => host.parse()?,
+        None => IpAddr::V4(Ipv4Addr::LOCALHOST),
+    };
+    let listener = TcpListener::bind((host, port))?;
+    let port = listener.local_addr()?.port();
+    log::info!("Session relay listening on {}:{}", host, port);
+
+    let peers = Arc::new(Mutex::new(Vec::<(usize, TcpStream)>::new()));
+    std::thread::spawn(move || {
+        for (index, stream) in listener.incoming().flatten().enumerate() {
+            let peers = Arc::clone(&peers);
+            let token = token.clone();
+            std::thread::spawn(move || {
+                let Ok(writer) = stream.try_clone() else {
+                    return;
+                };
+                let mut lines = BufReader::new(stream).lines();
+                let handshake = lines
+                    .next()
+                    .and_then(Result::ok)
+                    .and_then(|line| serde_json::from_str::<Handshake>(&line).ok());
+                if !handshake.is_some_and(|handshake| token.is_none() || handshake.token == token) {
+                    log::warn!("Refused a session viewer without the token");
+                    return;
+                }
+                peers.lock().unwrap().push((index, writer));
+
+                for line in lines.map_while(Result::ok) {
+                    // Viewers whose connection broke are dropped
+                    peers
+                        .lock()
+                        .unwrap()
+                        .retain_mut(|(peer, writer)| *peer == index || writeln!(writer, "{}", line).is_ok());
+                }
+                peers.lock().unwrap().retain(|(peer, _)| *peer != index);
+            });
+        }
+    });
+
+    Ok(port)
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use crate::{renderer::RenderSettings, watch::FileWatcher};
+
+// Render settings persisted as JSON in the working directory and reloaded when edited outside the app
+pub struct SettingsFile {
+    file: FileWatcher,
+}
+
+impl SettingsFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: FileWatcher::new(path),
+        }
+    }
+
+    pub fn load(&mut self) -> Option<RenderSettings> {
+        let contents = self.file.read()?;
+        self.parse(&contents)
+    }
+
+    pub fn save(&mut self, settings: &RenderSettings) {
+        let result = serde_json::to_string_pretty(settings)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| self.file.write(&contents));
+
+        if let Err(error) = result {
+            log::error!("Unable to write {}: {}", self.file.path().display(), error);
+        }
+    }
+
+    pub fn poll(&mut self) -> Option<RenderSettings> {
+        let contents = self.file.poll()?;
+        self.parse(&contents)
+    }
+
+    fn parse(&self, contents: &str) -> Option<RenderSettings> {
+        match serde_json::from_str(contents) {
+            Ok(settings) => Some(settings),
+            Err(error) => {
+                log::error!("Unable to parse {}: {}", self.file.path().display(), error);
+                None
+            }
+        }
+    }
+}
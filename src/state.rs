@@ -6,15 +6,60 @@ use std::{
 
 use glam::Vec4Swizzles;
 use instant::Instant;
-use winit::{event_loop::ActiveEventLoop, window::Window};
+use uuid::Uuid;
+use winit::{
+    dpi::LogicalSize,
+    event::{MouseButton, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow},
+    keyboard::{KeyCode, ModifiersState},
+    window::{Window, WindowId},
+};
 
 use crate::{
+    benchmark::{Benchmark, BenchmarkConfig},
     camera::{Camera, CameraController, Projection},
-    dialog::open_file_dialog,
-    entity::{Entity, EntityId},
-    renderer::{AssetLoader, Light, RenderCommand, RenderEvent, RenderId, Renderer, ResourcePath, Ui},
+    change_detection::ChangeDetection,
+    classify::ClassificationBrush,
+    clipboard::EntityClipboard,
+    entity::{Entity, EntityId, EntityStore},
+    explode::ExplodeView,
+    frame_graph::FrameGraphWindow,
+    isolate::Isolation,
+    photo_match::PhotoMatch,
+    placement::{self, MoveTool, Snapping, TransformEdit},
+    prefab::{PrefabChange, PrefabLibrary},
+    preview::PreviewWindow,
+    profiler::{self, ProfilerWindow},
+    quality::AutoQuality,
+    recording::InputRecorder,
+    registration::ScanRegistration,
+    renderer::{
+        Aabb, AssetKind, AssetLoader, AssetStats, BackgroundMode, EntityTags, ImportOptions, ImportReport,
+        InspectedBuffer, IrradianceGrid, Light, MemoryUsage, NodeMetadata, PointHit, PointcloudBuffer, Ray,
+        RenderCommand, RenderEvent, RenderId, RenderLayers, RenderMode, RenderSettings, Renderer, ResourcePath,
+        SceneHit, SurfaceHit, TagFilter, TaskPriority, TransferFunction, Ui, Visibility,
+    },
+    scatter::ScatterBrush,
+    settings::SettingsFile,
+    turntable::{Turntable, TurntableTarget},
+    units::{ScaleReference, WorldUnit},
+    viewport::ViewportWindow,
+    watch::FileWatcher,
+};
+#[cfg(not(target_family = "wasm"))]
+use crate::{
+    dataset::{Dataset, DatasetSpec},
+    dialog::export_points_dialog,
+    floorplan::Floorplan,
+    proxy::LoadingProxies,
+    reload::AssetReloader,
+    remote::{RemoteCall, RemoteServer},
+    session::{Session, SessionEvent},
+    sun_study::SunStudy,
 };
 
+mod ui;
+
 pub struct State {
     window: Arc<Window>,
     ui: Ui,
@@ -23,26 +68,200 @@ pub struct State {
     projection: Projection,
     loader: AssetLoader,
     timestamp: Instant,
-    entities: HashMap<EntityId, Entity>,
+    entities: EntityStore,
+    assets: Vec<(RenderId, Option<String>)>,
+    asset_stats: HashMap<RenderId, AssetStats>,
+    node_metadata: HashMap<RenderId, NodeMetadata>,
+    // Render ids of the nodes of every asset, in load order
+    asset_nodes: HashMap<Uuid, Vec<RenderId>>,
+    // Only assets switched to one of their material variants
+    material_variants: HashMap<Uuid, usize>,
     renderer: Renderer,
     event_queue: Vec<RenderEvent>,
     fps: f32,
-    light_color: [u8; 3],
-    light_intensity: f32,
+    light: Light,
+    import_options: ImportOptions,
+    place_on_ground: bool,
+    render_settings: RenderSettings,
+    settings_file: SettingsFile,
+    auto_quality: AutoQuality,
+    sketch_file: FileWatcher,
+    cursor_position: glam::Vec2,
+    // Where the left button went down, a release close to it selects instead of orbiting
+    press_position: Option<glam::Vec2>,
+    selected: Option<EntityId>,
+    clipboard: EntityClipboard,
+    prefabs: PrefabLibrary,
+    // Name being typed for a new prefab
+    prefab_name: String,
+    // Tag being typed for the selected entity
+    tag_input: String,
+    // Tag whose entities are outlined while its isolate button is hovered
+    hovered_tag: Option<String>,
+    isolation: Isolation,
+    explode: ExplodeView,
+    turntable: Turntable,
+    // Of the main window
+    view_layers: RenderLayers,
+    modifiers: ModifiersState,
+    snapping: Snapping,
+    move_tool: Option<MoveTool>,
+    transform_edit: Option<TransformEdit>,
+    world_unit: WorldUnit,
+    // Reflection probe id, position and radius
+    probes: Vec<(Uuid, glam::Vec3, f32)>,
+    probe_radius: f32,
+    irradiance_grid: IrradianceGrid,
+    has_irradiance_volume: bool,
+    lightmap_resolution: u32,
+    // Depth, normals and an id mask saved along with the screenshot
+    export_aovs: bool,
+    // Per axis, for print sized screenshots
+    screenshot_tiles: u32,
+    panorama_width: u32,
+    panorama_strips: u32,
+    lightmap_samples: u32,
+    // Entity being baked with its samples so far and in total
+    lightmap_progress: Option<(EntityId, u32, u32)>,
+    // Percent read of files still streaming in, by label
+    load_progress: HashMap<String, f32>,
+    memory_usage: MemoryUsage,
+    queued_loads: usize,
+    // Any of the app's windows, on the web the canvas. Hidden tabs and minimized windows arrive as occluded
+    is_focused: bool,
+    is_occluded: bool,
+    // Only consulted when rendering on demand, set by input and anything else the next frame should show
+    is_redraw_requested: bool,
+    is_idle: bool,
+    is_renderer_refining: bool,
+    scatter: ScatterBrush,
+    classification_brush: ClassificationBrush,
+    registration: ScanRegistration,
+    change_detection: ChangeDetection,
+    photo_match: PhotoMatch,
+    inspected_buffer: InspectedBuffer,
+    buffer_contents: Option<(InspectedBuffer, Vec<String>)>,
+    toasts: VecDeque<(String, Instant)>,
+    import_reports: Vec<(String, ImportReport)>,
+    picked_point: Option<PointHit>,
+    transfer_function: TransferFunction,
+    profiler: ProfilerWindow,
+    preview: PreviewWindow,
+    frame_graph: FrameGraphWindow,
+    benchmark: Option<Benchmark>,
+    recorder: InputRecorder,
+    viewports: Vec<ViewportWindow>,
+    is_viewport_requested: bool,
+    #[cfg(not(target_family = "wasm"))]
+    dataset: Option<Dataset>,
+    #[cfg(not(target_family = "wasm"))]
+    remote: Option<RemoteServer>,
+    #[cfg(not(target_family = "wasm"))]
+    session: Option<Session>,
+    #[cfg(not(target_family = "wasm"))]
+    reloader: AssetReloader,
+    #[cfg(not(target_family = "wasm"))]
+    proxies: LoadingProxies,
+    #[cfg(not(target_family = "wasm"))]
+    export_in_view: bool,
+    #[cfg(not(target_family = "wasm"))]
+    floorplan: Floorplan,
+    #[cfg(not(target_family = "wasm"))]
+    sun_study: SunStudy,
+    captures: Captures,
+}
+
+// What a requested screenshot is for, looked up by the id its capture command carried
+enum Capture {
+    #[cfg(not(target_family = "wasm"))]
+    Save,
+    #[cfg(not(target_family = "wasm"))]
+    Dataset,
+    #[cfg(not(target_family = "wasm"))]
+    Floorplan,
+    #[cfg(not(target_family = "wasm"))]
+    SunStudy,
+    #[cfg(not(target_family = "wasm"))]
+    Remote(std::path::PathBuf, RemoteCall),
+    #[cfg(target_family = "wasm")]
+    Web,
+}
+
+#[derive(Default)]
+struct Captures {
+    pending: HashMap<u64, Capture>,
+    next_id: u64,
+}
+
+impl Captures {
+    // The id for the capture command
+    fn request(&mut self, capture: Capture) -> u64 {
+        self.next_id += 1;
+        self.pending.insert(self.next_id, capture);
+        self.next_id
+    }
+
+    fn take(&mut self, id: u64) -> Option<Capture> {
+        self.pending.remove(&id)
+    }
 }
 
 impl State {
     pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
-        let renderer = Renderer::new(Arc::clone(&window)).await;
+        let benchmark = BenchmarkConfig::from_args().map(Benchmark::new);
+        let renderer = Renderer::new(Arc::clone(&window), benchmark.is_some()).await?;
         let size = window.inner_size();
         let camera = Camera::new((0.0, 5.0, 10.0), 45.0_f32.to_radians(), -20.0_f32.to_radians());
         let projection = Projection::new(size.width, size.height, 60.0_f32.to_radians(), 0.1, 500.0);
         let camera_controller = CameraController::new(8.0, 0.004);
-        let loader = AssetLoader::new(renderer.sender());
-        let ui = Ui::new(Arc::clone(&window));
-        let mut entities = HashMap::new();
+        let loader = AssetLoader::new(renderer.client().clone());
+        let auto_quality = AutoQuality::new(renderer.adapter_quality());
+        let mut ui = Ui::new(Arc::clone(&window));
+        let mut entities = EntityStore::new();
+
+        let mut settings_file = SettingsFile::new("render_settings.json");
+        #[allow(unused_mut)]
+        let mut render_settings = settings_file.load().unwrap_or_default();
+        #[cfg(target_family = "wasm")]
+        if crate::web::canvas_flag("transparent") {
+            render_settings.transparent_background = true;
+        }
+        ui.set_style(&render_settings.ui_style);
+        renderer.send_command(RenderCommand::UpdateSettings(render_settings.clone()))?;
+
+        // The renderer starts with a built-in sketch, a sketch.wgsl in the working directory replaces it
+        let mut sketch_file = FileWatcher::new("sketch.wgsl");
+        if let Some(source) = sketch_file.read() {
+            renderer.client().update_sketch(source)?;
+        }
+
+        // Nobody is waiting on the built-in cube, anything loaded right away goes first
+        loader.load_with_priority(
+            ResourcePath::new("cube.obj").unwrap(),
+            ImportOptions::default(),
+            TaskPriority::Prefetch,
+        );
+        loader.load_with_priority(
+            ResourcePath::new(ScaleReference::FILE).unwrap(),
+            ImportOptions::default(),
+            TaskPriority::Prefetch,
+        );
+
+        if let Some(benchmark) = &benchmark {
+            log::info!("Benchmarking {}", benchmark.scene());
+            loader.load(ResourcePath::new(benchmark.scene())?);
+            renderer.send_command(RenderCommand::SetGpuTiming(true))?;
+        }
+
+        #[cfg(not(target_family = "wasm"))]
+        let dataset = DatasetSpec::from_args().map(Dataset::new);
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(dataset) = &dataset {
+            for scene in dataset.scenes() {
+                loader.load(ResourcePath::new(scene)?);
+            }
+        }
 
-        loader.load(ResourcePath::new("cube.obj").unwrap());
         // loader.load(ResourcePath::new("pure-sky.hdr").unwrap());
         // loader.load(ResourcePath::new("1612_9070.laz"));
 
@@ -54,17 +273,20 @@ impl State {
             },
             color: glam::Vec3 { x: 0.9, y: 0.9, z: 0.6 },
             intensity: 100.0,
+            range: 0.0,
+            casts_shadows: true,
         };
 
         let transform = light.to_transform();
-        let entity = Entity::new(transform, Some("light".to_string()));
+        let entity = Entity::new(transform, Some("light".to_string())).with_tag("light");
 
         renderer.send_command(RenderCommand::SpawnLight {
             entity_id: entity.id(),
-            light,
+            light: light.clone(),
         })?;
         // render_sender.send()?;
-        entities.insert(entity.id(), entity);
+        renderer.send_command(entity_tags(&entity))?;
+        entities.insert(entity);
 
         let directional = Light::Directional {
             direction: glam::Vec3 {
@@ -78,16 +300,18 @@ impl State {
                 z: 0.897,
             },
             intensity: 1.0,
+            casts_shadows: true,
         };
 
         let directional_transform = directional.to_transform();
-        let directional_entity = Entity::new(directional_transform, Some("dir_light".to_string()));
+        let directional_entity = Entity::new(directional_transform, Some("dir_light".to_string())).with_tag("light");
 
         // renderer.send_command(RenderCommand::SpawnLight {
         //     entity_id: directional_entity.id(),
         //     light: directional,
         // })?;
-        entities.insert(directional_entity.id(), directional_entity);
+        renderer.send_command(entity_tags(&directional_entity))?;
+        entities.insert(directional_entity);
 
         Ok(Self {
             window,
@@ -97,28 +321,196 @@ impl State {
             projection,
             loader,
             entities,
+            assets: Vec::new(),
+            asset_stats: HashMap::new(),
+            node_metadata: HashMap::new(),
+            asset_nodes: HashMap::new(),
+            material_variants: HashMap::new(),
             timestamp: Instant::now(),
             renderer,
             event_queue: Vec::new(),
             fps: 0.0,
-            light_color: [230, 230, 153],
-            light_intensity: 100.0,
+            light,
+            import_options: ImportOptions::default(),
+            place_on_ground: false,
+            auto_quality,
+            render_settings,
+            settings_file,
+            sketch_file,
+            cursor_position: glam::Vec2::ZERO,
+            press_position: None,
+            selected: None,
+            clipboard: EntityClipboard::new(),
+            prefabs: PrefabLibrary::new(),
+            prefab_name: String::new(),
+            tag_input: String::new(),
+            hovered_tag: None,
+            isolation: Isolation::new(),
+            explode: ExplodeView::new(),
+            turntable: Turntable::new(),
+            view_layers: RenderLayers::ALL,
+            modifiers: ModifiersState::empty(),
+            snapping: Snapping::new(),
+            move_tool: None,
+            transform_edit: None,
+            world_unit: WorldUnit::Meters,
+            probes: Vec::new(),
+            probe_radius: 5.0,
+            irradiance_grid: IrradianceGrid::default(),
+            has_irradiance_volume: false,
+            export_aovs: false,
+            screenshot_tiles: 4,
+            panorama_width: 4096,
+            panorama_strips: 64,
+            lightmap_resolution: 512,
+            lightmap_samples: 256,
+            lightmap_progress: None,
+            load_progress: HashMap::new(),
+            memory_usage: MemoryUsage::default(),
+            queued_loads: 0,
+            is_focused: true,
+            is_occluded: false,
+            is_redraw_requested: true,
+            is_idle: false,
+            is_renderer_refining: false,
+            scatter: ScatterBrush::new(),
+            classification_brush: ClassificationBrush::new(),
+            registration: ScanRegistration::new(),
+            change_detection: ChangeDetection::new(),
+            photo_match: PhotoMatch::new(),
+            inspected_buffer: InspectedBuffer::Transforms,
+            buffer_contents: None,
+            toasts: VecDeque::new(),
+            import_reports: Vec::new(),
+            picked_point: None,
+            transfer_function: TransferFunction::default(),
+            profiler: ProfilerWindow::new(),
+            preview: PreviewWindow::new(),
+            frame_graph: FrameGraphWindow::new(),
+            benchmark,
+            recorder: InputRecorder::new("recording.json"),
+            viewports: Vec::new(),
+            is_viewport_requested: false,
+            #[cfg(not(target_family = "wasm"))]
+            dataset,
+            #[cfg(not(target_family = "wasm"))]
+            remote: RemoteServer::from_args(),
+            #[cfg(not(target_family = "wasm"))]
+            session: Session::from_args(),
+            #[cfg(not(target_family = "wasm"))]
+            reloader: AssetReloader::new(),
+            #[cfg(not(target_family = "wasm"))]
+            proxies: LoadingProxies::new(),
+            #[cfg(not(target_family = "wasm"))]
+            export_in_view: false,
+            #[cfg(not(target_family = "wasm"))]
+            floorplan: Floorplan::new(),
+            #[cfg(not(target_family = "wasm"))]
+            sun_study: SunStudy::new(),
+            captures: Captures::default(),
         })
     }
 
     pub fn update(&mut self, event_loop: &ActiveEventLoop) {
-        self.window.request_redraw();
+        // The previous update's scopes have all ended here
+        profiler::new_frame();
+        crate::profile_scope!("Update");
+
+        #[cfg(target_family = "wasm")]
+        self.handle_web_requests();
+        #[cfg(not(target_family = "wasm"))]
+        self.handle_remote_calls();
+        #[cfg(not(target_family = "wasm"))]
+        self.handle_load_requests();
+        #[cfg(not(target_family = "wasm"))]
+        self.handle_session_messages();
+        self.update_prefabs();
+
+        if let Some(settings) = self.settings_file.poll() {
+            self.is_redraw_requested = true;
+            self.render_settings = settings;
+            self.ui.set_style(&self.render_settings.ui_style);
+            self.renderer
+                .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                .unwrap();
+        }
+
+        if self.render_settings.render_mode == RenderMode::Sketch
+            && let Some(source) = self.sketch_file.poll()
+        {
+            self.is_redraw_requested = true;
+            self.renderer.client().update_sketch(source).unwrap();
+        }
 
         let should_update = self.renderer.poll_events(&mut self.event_queue, event_loop);
-        for event in self.event_queue.drain(..) {
+        self.is_redraw_requested |= !self.event_queue.is_empty();
+        // Taken out of self for the duration, handling an event may need all of it
+        let mut events = std::mem::take(&mut self.event_queue);
+        for event in events.drain(..) {
             match event {
                 RenderEvent::LoadComplete {
                     render_id,
+                    asset_id,
                     transform,
                     label,
+                    report,
+                    replaces_scene,
+                    stats,
+                    metadata,
                 } => {
+                    self.assets.push((render_id, label.clone()));
+                    self.asset_nodes.entry(asset_id).or_default().push(render_id);
+                    self.asset_stats.insert(render_id, stats);
+                    // Named nodes are shown by their name, the others by their file
+                    let entity_label = metadata.name.clone().or_else(|| label.clone());
+                    self.node_metadata.insert(render_id, metadata);
+                    #[cfg(target_family = "wasm")]
+                    crate::web::emit(
+                        "loadComplete",
+                        &[
+                            ("renderId", render_id.to_string().into()),
+                            ("label", label.clone().into()),
+                            ("triangles", stats.triangles.into()),
+                            ("points", stats.points.into()),
+                        ],
+                    );
+                    // The built-in cube loads too, only the benchmarked scene starts the run
+                    if let Some(benchmark) = &mut self.benchmark
+                        && label.as_deref().is_some_and(|label| benchmark.scene().ends_with(label))
+                    {
+                        benchmark.on_load_complete();
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    if let Some(dataset) = &mut self.dataset
+                        && let Some(label) = &label
+                    {
+                        dataset.on_load_complete(label);
+                    }
+                    if let Some(report) = report.filter(|report| !report.is_empty()) {
+                        self.import_reports.push((asset_name(&render_id, &label), report));
+                    }
+
+                    #[cfg(not(target_family = "wasm"))]
+                    self.reloader.register_node(asset_id, render_id, label.as_deref());
+                    // Nodes of an asset another viewer loaded are spawned by their messages
+                    #[cfg(not(target_family = "wasm"))]
+                    let is_shared = self
+                        .session
+                        .as_mut()
+                        .is_some_and(|session| session.register_node(asset_id, render_id, label.as_deref()));
+                    #[cfg(target_family = "wasm")]
+                    let is_shared = false;
+                    // So are the nodes of files imported for a prefab instance
+                    let is_prefab = self.prefabs.register_node(asset_id, render_id, label.as_deref());
+                    if replaces_scene && !is_shared && !is_prefab {
+                        self.clear_scene();
+                    }
+
                     if label.clone().unwrap() == "cube.obj" {
-                        for entity in create_instances(label) {
+                        for entity in create_instances(render_id, label.clone()) {
+                            #[cfg(not(target_family = "wasm"))]
+                            let entity =
+                                replace_proxy(&mut self.proxies, &self.renderer, entity, label.as_deref(), asset_id);
                             self.renderer
                                 .send_command(RenderCommand::SpawnAsset {
                                     entity_id: entity.id(),
@@ -126,11 +518,26 @@ impl State {
                                     transform: entity.transform(),
                                 })
                                 .unwrap();
-                            self.entities.insert(entity.id(), entity);
+                            self.renderer.send_command(entity_tags(&entity)).unwrap();
+                            self.entities.insert(entity);
+                        }
+                    } else if !is_shared && !is_prefab && label.as_deref() != Some(ScaleReference::FILE) {
+                        let mut transform = transform.unwrap_or(glam::Mat4::IDENTITY);
+                        if self.place_on_ground {
+                            let bounds = stats.bounds.transform(transform);
+                            let offset = placement::ground_offset(&bounds, &self.renderer.scene_query());
+                            transform = glam::Mat4::from_translation(glam::Vec3::Y * offset) * transform;
+                        }
+                        let entity = Entity::new(transform, entity_label.clone())
+                            .with_render_id(render_id)
+                            .with_asset_id(asset_id);
+                        #[cfg(not(target_family = "wasm"))]
+                        let entity =
+                            replace_proxy(&mut self.proxies, &self.renderer, entity, label.as_deref(), asset_id);
+                        #[cfg(not(target_family = "wasm"))]
+                        if let Some(session) = &self.session {
+                            session.spawn(entity.id(), render_id, transform, entity_label);
                         }
-                    } else {
-                        let transform = transform.unwrap_or(glam::Mat4::IDENTITY);
-                        let entity = Entity::new(transform, label);
 
                         self.renderer
                             .send_command(RenderCommand::SpawnAsset {
@@ -139,42 +546,256 @@ impl State {
                                 transform,
                             })
                             .unwrap();
-                        self.entities.insert(entity.id(), entity);
+                        self.renderer.send_command(entity_tags(&entity)).unwrap();
+                        self.entities.insert(entity);
+                    }
+                }
+                RenderEvent::AssetReloaded { render_id, stats } => {
+                    self.asset_stats.insert(render_id, stats);
+                }
+                RenderEvent::BufferContents { buffer, entries } => {
+                    self.buffer_contents = Some((buffer, entries));
+                }
+                RenderEvent::PreviewTexture(texture_id) => self.preview.set_texture(texture_id),
+                RenderEvent::FrameGraph(layout) => self.frame_graph.set_layout(layout),
+                RenderEvent::LightmapProgress {
+                    entity_id,
+                    samples,
+                    total,
+                } => self.lightmap_progress = Some((entity_id, samples, total)),
+                RenderEvent::LoadProgress { label, percent } => {
+                    if percent < 100.0 {
+                        self.load_progress.insert(label, percent);
+                    } else {
+                        self.load_progress.remove(&label);
+                    }
+                }
+                RenderEvent::LoadAborted { asset_id } => self.unload_asset(asset_id),
+                RenderEvent::Refining(is_refining) => self.is_renderer_refining = is_refining,
+                RenderEvent::MemoryUsage { usage, queued_loads } => {
+                    let language = self.render_settings.language;
+                    if usage.is_near_limit() && !self.memory_usage.is_near_limit() {
+                        self.toasts.push_back((
+                            format!(
+                                "{} ({} / {} MiB)",
+                                language.tr("GPU memory is running low"),
+                                usage.allocated_mib(),
+                                usage.budget_mib()
+                            ),
+                            Instant::now(),
+                        ));
+                    }
+                    if queued_loads > self.queued_loads {
+                        self.toasts.push_back((
+                            language.tr("Load waits for free GPU memory").to_string(),
+                            Instant::now(),
+                        ));
+                    }
+
+                    self.memory_usage = usage;
+                    self.queued_loads = queued_loads;
+                }
+                RenderEvent::GpuTimings(passes) => {
+                    if let Some(benchmark) = &mut self.benchmark {
+                        benchmark.record_gpu_timings(&passes);
+                    }
+                }
+                RenderEvent::Screenshot {
+                    id,
+                    width,
+                    height,
+                    pixels,
+                } => match self.captures.take(id) {
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Save) => {
+                        match image::save_buffer("screenshot.png", &pixels, width, height, image::ColorType::Rgba8) {
+                            Ok(()) => log::info!("Saved screenshot.png"),
+                            Err(error) => self
+                                .toasts
+                                .push_back((format!("Screenshot: {}", error), Instant::now())),
+                        }
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Dataset) => {
+                        if let Some(dataset) = &mut self.dataset {
+                            dataset.record_rgb(width, height, pixels);
+                        }
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Floorplan) => match self.floorplan.save(width, height, pixels, self.world_unit) {
+                        Ok(()) => log::info!("Saved floorplan.png"),
+                        Err(error) => self.toasts.push_back((format!("Floorplan: {}", error), Instant::now())),
+                    },
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::SunStudy) => {
+                        if let Err(error) = self.sun_study.save(width, height, pixels) {
+                            self.sun_study.stop();
+                            self.toasts.push_back((format!("Sun study: {}", error), Instant::now()));
+                        }
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Remote(path, call)) => call.reply(
+                        image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8)
+                            .map(|_| serde_json::json!({ "width": width, "height": height }))
+                            .map_err(|error| error.to_string()),
+                    ),
+                    #[cfg(target_family = "wasm")]
+                    Some(Capture::Web) => crate::web::resolve_screenshot(width, height, &pixels),
+                    None => log::warn!("Screenshot {} was not requested", id),
+                },
+                RenderEvent::ScreenshotFailed { id, message } => match self.captures.take(id) {
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Save) => self
+                        .toasts
+                        .push_back((format!("Screenshot: {}", message), Instant::now())),
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Dataset) => {
+                        // Writing the pose fails on the missing pixels and ends the run
+                        if let Some(dataset) = &mut self.dataset {
+                            dataset.record_rgb(0, 0, Vec::new());
+                        }
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Floorplan) => {
+                        self.floorplan.cancel();
+                        self.toasts
+                            .push_back((format!("Floorplan: {}", message), Instant::now()));
                     }
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::SunStudy) => {
+                        self.sun_study.stop();
+                        self.toasts
+                            .push_back((format!("Sun study: {}", message), Instant::now()));
+                    }
+                    #[cfg(not(target_family = "wasm"))]
+                    Some(Capture::Remote(_, call)) => call.reply(Err(message)),
+                    #[cfg(target_family = "wasm")]
+                    Some(Capture::Web) => crate::web::reject_screenshot(&message),
+                    None => log::warn!("Screenshot {} was not requested", id),
+                },
+                #[cfg(not(target_family = "wasm"))]
+                RenderEvent::Aovs(images) if self.dataset.as_ref().is_some_and(Dataset::is_capturing) => {
+                    self.dataset.as_mut().unwrap().record_aovs(images);
+                }
+                #[cfg(not(target_family = "wasm"))]
+                RenderEvent::Aovs(images) => match crate::aov::save_aovs(&images, &self.entities) {
+                    Ok(()) => log::info!("Saved depth, normals and id mask"),
+                    Err(error) => self
+                        .toasts
+                        .push_back((format!("Screenshot: {}", error), Instant::now())),
+                },
+                RenderEvent::PickResult { entity_id } => self.select(entity_id),
+                RenderEvent::Error { label, message } => {
+                    #[cfg(target_family = "wasm")]
+                    crate::web::emit("error", &[("label", label.into()), ("message", message.clone().into())]);
+                    self.toasts
+                        .push_back((format!("{}: {}", label, message), Instant::now()));
+                }
+                // Follows the surface rather than the window, a resize only takes effect once it's configured
+                RenderEvent::SurfaceConfigured { width, height } => {
+                    self.projection.resize(width, height);
+                    self.ui.set_surface_size(width, height);
                 }
                 _ => (),
             }
         }
+        self.event_queue = events;
+
+        self.is_idle = self.render_settings.render_on_demand
+            && !std::mem::take(&mut self.is_redraw_requested)
+            && !self.is_animating();
+        self.schedule_redraw(event_loop);
 
-        if should_update {
+        if should_update && !self.is_idle {
             let timestep = self.timestamp.elapsed();
             self.timestamp = Instant::now();
             let average_fps = self.update_fps(timestep).round();
 
-            // Debug
-            let light = self
-                .entities
-                .values_mut()
-                .find(|entity| entity.label().as_ref().unwrap() == "light")
-                .unwrap();
+            // Throttled frame rates in the background say nothing about the scene. Only presets picked by hand are
+            // saved, the auto mode starts over from the adapter next time
+            if self.render_settings.auto_quality
+                && !self.recorder.is_replaying()
+                && !self.is_in_background()
+                && let Some(preset) = self.auto_quality.update(average_fps, self.render_settings.quality)
+            {
+                preset.apply(&mut self.render_settings);
+                self.renderer
+                    .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                    .unwrap();
+            }
 
-            let position = light.transform().w_axis.truncate();
-            let rotation = glam::Quat::from_rotation_y(10.0_f32.to_radians() * timestep.as_secs_f32());
-            let new_position = rotation * position;
-            let transform = glam::Mat4::from_translation(new_position);
-            light.set_transform(transform);
+            let moved = self.move_tool.as_ref().and_then(|tool| {
+                let transform = tool.update(&self.cursor_ray(), &self.renderer.scene_query(), &self.snapping)?;
+                Some((tool.entity_id(), transform))
+            });
+            if let Some((entity_id, transform)) = moved {
+                self.set_entity_transform(entity_id, transform);
+            }
 
-            self.renderer
-                .send_command(RenderCommand::UpdateTransform {
-                    entity_id: light.id(),
-                    transform,
-                })
-                .unwrap();
+            if self.scatter.is_painting() {
+                let hit = self.surface_under_cursor();
+                if let Some(render_id) = self.scatter.render_id {
+                    for transform in self.scatter.update(timestep, hit) {
+                        self.spawn_entity(render_id, transform);
+                    }
+                }
+            }
+
+            if let Some((entity_id, transform)) = self.registration.poll() {
+                self.set_entity_transform(entity_id, transform);
+            }
+            if let Some((render_id, buffer)) = self.change_detection.poll() {
+                self.renderer
+                    .send_command(RenderCommand::ReplacePoints { render_id, buffer })
+                    .unwrap();
+            }
+
+            if self.classification_brush.is_painting()
+                && let Some((_, buffer, transform)) = self.selected_pointcloud()
+            {
+                let size = self.window.inner_size();
+                let view_projection = self.projection.matrix() * self.camera.view_matrix() * transform;
+                let size = glam::Vec2::new(size.width as f32, size.height as f32);
+                if let Some((render_id, changes)) =
+                    self.classification_brush
+                        .paint(&buffer, view_projection, self.cursor_position, size)
+                {
+                    self.renderer
+                        .send_command(RenderCommand::ClassifyPoints { render_id, changes })
+                        .unwrap();
+                }
+            }
+
+            // Debug, holds still when rendering on demand so the scene can settle
+            let light_id = self.entities.find_by_label("light").next().unwrap().id();
+            let is_sun_study_running = self.is_sun_study_running();
+            let light = self.entities.get_mut(&light_id).unwrap();
+
+            if !self.render_settings.render_on_demand && !is_sun_study_running {
+                let rotation = glam::Quat::from_rotation_y(10.0_f32.to_radians() * timestep.as_secs_f32());
+                let transform = glam::Mat4::from_quat(rotation) * light.transform();
+                light.set_transform(transform);
+
+                self.renderer
+                    .send_command(RenderCommand::UpdateTransform {
+                        entity_id: light.id(),
+                        transform,
+                    })
+                    .unwrap();
+            }
+            let light_id = light.id();
 
             // end debug
 
             // UI
-            let ctx = self.ui.begin_frame();
+            crate::profile_scope!("Ui");
+            let hovered = if self.render_settings.show_tooltips {
+                self.hovered_details()
+            } else {
+                None
+            };
+            let ctx = self.ui.begin_frame().clone();
+            self.photo_match.poll(&ctx);
 
             // egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             //     ui.horizontal(|ui| {
@@ -202,58 +823,346 @@ impl State {
             //         ui.label("Put your scene or widgets here!");
             //     });
 
-            egui::Window::new("Debug")
-                .resizable(true)
-                .movable(true)
-                .show(ctx, |ui| {
-                    ui.label(format!("FPS: {}", average_fps));
-                    ui.add_space(10.0);
-                    if ui.button("Load Asset").clicked() {
-                        open_file_dialog(self.loader.clone());
-                    }
-                    ui.add_space(10.0);
-
-                    ui.label("Light color");
-                    if ui.color_edit_button_srgb(&mut self.light_color).changed() {
-                        self.renderer
-                            .send_command(RenderCommand::UpdateLight {
-                                entity_id: light.id(),
-                                kind: 1,
-                                color: glam::Vec3::from_array(self.light_color.map(|u| u as f32 / 255.0)),
-                                intensity: self.light_intensity,
-                                cutoff: 0.0,
-                            })
-                            .unwrap();
-                    }
-                    ui.label("Intensity");
-                    if ui
-                        .add(egui::Slider::new(&mut self.light_intensity, 0.0..=255.0))
-                        .changed()
-                    {
-                        self.renderer
-                            .send_command(RenderCommand::UpdateLight {
-                                entity_id: light.id(),
-                                kind: 1,
-                                color: glam::Vec3::from_array(self.light_color.map(|u| u as f32 / 255.0)),
-                                intensity: self.light_intensity,
-                                cutoff: 0.0,
-                            })
-                            .unwrap();
-                    }
-                });
+            let actions = self.show_ui(&ctx, average_fps, light_id, hovered);
             // End UI
 
             let ui_data = self.ui.end_frame();
+            self.apply_ui_actions(actions);
 
             self.camera_controller.update_camera(&mut self.camera, timestep);
+            if self.turntable.is_spinning() {
+                self.spin_turntable(timestep);
+            }
+            if let Some(frame) =
+                self.recorder
+                    .update(timestep, &self.camera, &self.render_settings, &self.turntable.settings)
+            {
+                if let Some(camera) = frame.camera {
+                    self.camera = camera;
+                }
+                if let Some(turntable) = frame.turntable {
+                    self.turntable.settings = turntable;
+                    self.turntable.reset_focus();
+                }
+                if let Some(settings) = frame.settings {
+                    self.render_settings = settings;
+                    self.renderer
+                        .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                        .unwrap();
+                }
+            }
+            if let Some(benchmark) = &mut self.benchmark
+                && benchmark.is_running()
+            {
+                if let Some((position, target)) = benchmark.camera() {
+                    self.camera = Camera::look_at(position, target);
+                }
+
+                if benchmark.record_frame(timestep) {
+                    if let Err(error) = benchmark.write_report() {
+                        log::error!("Unable to write benchmark report: {}", error);
+                    }
+                    self.renderer.exit();
+                }
+            } else if let Some(benchmark) = &self.benchmark
+                && benchmark.has_load_timed_out()
+            {
+                log::error!("Benchmark scene {} did not load in time", benchmark.scene());
+                self.renderer.exit();
+            }
+            #[cfg(not(target_family = "wasm"))]
+            self.update_dataset();
+            #[cfg(not(target_family = "wasm"))]
+            self.update_sun_study();
+            self.projection.set_lens(self.render_settings.physical_camera.lens());
+            let clip_planes = &self.render_settings.clip_planes;
+            let (z_near, z_far) = match clip_planes.auto {
+                true => clip_planes.fit(
+                    self.camera.view_matrix(),
+                    &visible_bounds(&self.entities, &self.asset_stats),
+                ),
+                false => (clip_planes.near, clip_planes.far),
+            };
+            self.projection.set_clip_planes(z_near, z_far);
             self.renderer.update_camera(
                 self.camera.position(),
                 self.camera.view_matrix(),
                 self.projection.matrix(),
             );
 
+            self.preview.update(&self.entities, &self.renderer);
+            self.frame_graph.update(&self.renderer);
             self.renderer.request_frame(&self.window, ui_data);
         }
+
+        if std::mem::take(&mut self.is_viewport_requested) {
+            self.open_viewport(event_loop);
+        }
+    }
+
+    // Starts at the main camera, moving independently from there
+    fn open_viewport(&mut self, event_loop: &ActiveEventLoop) {
+        let attributes = Window::default_attributes()
+            .with_title("Viewport")
+            .with_inner_size(LogicalSize::new(640.0, 480.0));
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(error) => {
+                log::error!("Unable to create window: {}", error);
+                return;
+            }
+        };
+
+        if let Some(id) = self.renderer.open_viewport(Arc::clone(&window)) {
+            self.viewports
+                .push(ViewportWindow::new(id, window, self.camera.clone()));
+        }
+    }
+
+    pub fn handle_viewport_event(&mut self, window_id: WindowId, event: WindowEvent) {
+        let Some(index) = self
+            .viewports
+            .iter()
+            .position(|viewport| viewport.window().id() == window_id)
+        else {
+            return;
+        };
+
+        if !self.viewports[index].handle_event(event, &mut self.renderer) {
+            let viewport = self.viewports.remove(index);
+            self.renderer.close_viewport(viewport.id());
+        }
+    }
+
+    pub fn handle_mouse_motion(&mut self, dx: f64, dy: f64) {
+        let controllers = std::iter::once(&mut self.camera_controller).chain(
+            self.viewports
+                .iter_mut()
+                .map(|viewport| viewport.camera_controller_mut()),
+        );
+        for controller in controllers {
+            if controller.is_mouse_pressed() {
+                controller.handle_mouse(-dx, dy);
+            }
+        }
+
+        // Raw motion keeps arriving while the cursor is elsewhere, only a drag counts as input
+        if self.camera_controller.is_moving() {
+            self.request_redraw();
+        }
+    }
+
+    // Loads started since the last update are watched for changes on disk and shared with the session
+    #[cfg(not(target_family = "wasm"))]
+    fn handle_load_requests(&mut self) {
+        for request in self.loader.take_requested() {
+            self.prefabs.record_load(&request.path, &request.options);
+            // Prefetches are the app's own, like the built-in cube, and so are imports for prefab instances
+            if request.priority == TaskPriority::User && !self.prefabs.is_requested(&request.path) {
+                // Loads made on behalf of the others get their entities from them
+                let is_shared = self
+                    .session
+                    .as_mut()
+                    .is_some_and(|session| !session.load(request.path.as_str().to_string(), request.options.clone()));
+                if !is_shared {
+                    self.spawn_proxy(&request.path);
+                }
+            }
+            self.reloader.watch(request);
+        }
+
+        for (label, bounds) in self.loader.take_estimates() {
+            if let Some(entity_id) = self.proxies.find(&label)
+                && let Some(entity) = self.entities.get_mut(&entity_id)
+            {
+                entity.set_transform(LoadingProxies::transform(&bounds));
+                self.send_transform(entity_id);
+            }
+        }
+
+        self.reloader.poll(&self.loader);
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn spawn_proxy(&mut self, path: &ResourcePath) {
+        let adds_nodes = path
+            .extension()
+            .as_deref()
+            .and_then(AssetKind::from_extension)
+            .is_some_and(|kind| kind.adds_nodes());
+        let Some(render_id) = self.cube_render_id().filter(|_| adds_nodes) else {
+            return;
+        };
+
+        let label = path.file_name().to_string();
+        let transform = LoadingProxies::transform(&LoadingProxies::DEFAULT_BOUNDS);
+        let mut entity = Entity::new(transform, Some(label.clone())).with_render_id(render_id);
+        entity.set_visibility(Visibility::Ghosted);
+        entity.set_layers(RenderLayers::HELPERS);
+        let entity_id = entity.id();
+
+        self.renderer
+            .send_command(RenderCommand::SpawnAsset {
+                entity_id,
+                render_id,
+                transform,
+            })
+            .unwrap();
+        self.renderer
+            .send_command(RenderCommand::SetVisibility(vec![(entity_id, Visibility::Ghosted)]))
+            .unwrap();
+        self.renderer
+            .send_command(RenderCommand::SetEntityLayers {
+                entity_id,
+                layers: RenderLayers::HELPERS,
+            })
+            .unwrap();
+        self.insert_entity(entity);
+        self.proxies.insert(label, entity_id);
+    }
+
+    // Mirrors what the other viewers of a shared session did, and tells them what happened here
+    #[cfg(not(target_family = "wasm"))]
+    fn handle_session_messages(&mut self) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+
+        session.camera(&self.camera);
+
+        let events = session.poll();
+        self.is_redraw_requested |= !events.is_empty();
+        for event in events {
+            match event {
+                SessionEvent::Load { path, options } => self.loader.load_with_options(path, options),
+                SessionEvent::Spawn {
+                    entity_id,
+                    render_id,
+                    asset_id,
+                    transform,
+                    label,
+                } => {
+                    if let Some(entity) = self.entities.get_mut(&entity_id) {
+                        entity.set_transform(transform);
+                        self.send_transform(entity_id);
+                        continue;
+                    }
+
+                    let entity = Entity::new(transform, label)
+                        .with_id(entity_id)
+                        .with_render_id(render_id)
+                        .with_asset_id(asset_id);
+                    self.renderer
+                        .send_command(RenderCommand::SpawnAsset {
+                            entity_id,
+                            render_id,
+                            transform,
+                        })
+                        .unwrap();
+                    self.insert_entity(entity);
+                }
+                // Not sent back, every viewer already got it from the relay
+                SessionEvent::Transform { entity_id, transform } => {
+                    if let Some(entity) = self.entities.get_mut(&entity_id) {
+                        entity.set_transform(transform);
+                        self.send_transform(entity_id);
+                    }
+                }
+                SessionEvent::Presence => {}
+            }
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn handle_remote_calls(&mut self) {
+        use crate::remote::RemoteRequest;
+
+        let Some(calls) = self.remote.as_ref().map(RemoteServer::poll) else {
+            return;
+        };
+        self.is_redraw_requested |= !calls.is_empty();
+
+        for call in calls {
+            let result = match &call.request {
+                RemoteRequest::LoadAsset { path } => match ResourcePath::new(path) {
+                    Ok(path) => {
+                        self.loader.load(path);
+                        Ok(serde_json::Value::Null)
+                    }
+                    Err(error) => Err(error.to_string()),
+                },
+                RemoteRequest::ListEntities => Ok(self.entities.iter().map(entity_json).collect()),
+                RemoteRequest::FindEntities { label, tag } => Ok(self
+                    .entities
+                    .iter()
+                    .filter(|entity| label.is_none() || entity.label() == label)
+                    .filter(|entity| tag.as_ref().is_none_or(|tag| entity.has_tag(tag)))
+                    .map(entity_json)
+                    .collect()),
+                RemoteRequest::SetTransform { entity, transform } => match Uuid::parse_str(entity) {
+                    Ok(entity_id) if self.entities.contains(&entity_id) => {
+                        self.set_entity_transform(entity_id, glam::Mat4::from_cols_array(transform));
+                        Ok(serde_json::Value::Null)
+                    }
+                    Ok(_) => Err(format!("Unknown entity {}", entity)),
+                    Err(error) => Err(error.to_string()),
+                },
+                RemoteRequest::HighlightEntities { label, tag } => {
+                    let filter = (label.is_some() || tag.is_some()).then(|| TagFilter {
+                        label: label.clone(),
+                        tag: tag.clone(),
+                    });
+                    self.renderer.send_command(RenderCommand::SetHighlight(filter)).unwrap();
+                    Ok(serde_json::Value::Null)
+                }
+                RemoteRequest::SetCamera { position, target } => {
+                    self.camera = Camera::look_at(glam::Vec3::from(*position), glam::Vec3::from(*target));
+                    Ok(serde_json::Value::Null)
+                }
+                RemoteRequest::CaptureFrame { path } => {
+                    // Replied to once the screenshot arrives
+                    let id = self.captures.request(Capture::Remote(path.clone(), call));
+                    self.renderer
+                        .send_command(RenderCommand::CaptureScreenshot { id })
+                        .unwrap();
+                    continue;
+                }
+            };
+
+            call.reply(result);
+        }
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn handle_web_requests(&mut self) {
+        use crate::web::WebRequest;
+
+        let requests = crate::web::take_requests();
+        self.is_redraw_requested |= !requests.is_empty();
+        for request in requests {
+            match request {
+                WebRequest::LoadAsset(url) => match ResourcePath::new(&url) {
+                    Ok(path) => self.loader.load(path),
+                    Err(error) => log::error!("Unable to load {}: {}", url, error),
+                },
+                WebRequest::ResizeCanvas { width, height } => {
+                    let _ = self.window.request_inner_size(LogicalSize::new(width, height));
+                }
+                WebRequest::SetCamera { position, target } => self.camera = Camera::look_at(position, target),
+                WebRequest::CaptureScreenshot => {
+                    let id = self.captures.request(Capture::Web);
+                    self.renderer
+                        .send_command(RenderCommand::CaptureScreenshot { id })
+                        .unwrap();
+                }
+                WebRequest::SetTransparentBackground(is_transparent) => {
+                    self.render_settings.transparent_background = is_transparent;
+                    self.renderer
+                        .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                        .unwrap();
+                }
+            }
+        }
     }
 
     pub fn update_fps(&mut self, timestep: Duration) -> f32 {
@@ -262,16 +1171,786 @@ impl State {
         self.fps
     }
 
+    fn is_in_background(&self) -> bool {
+        !self.is_focused || self.is_occluded
+    }
+
+    fn schedule_redraw(&self, event_loop: &ActiveEventLoop) {
+        let mode = if self.is_in_background() {
+            self.render_settings.background_mode
+        } else {
+            BackgroundMode::Full
+        };
+
+        // The throttled and idle redraws are requested once the event loop wakes up again
+        match mode {
+            BackgroundMode::Full if self.is_idle => {
+                event_loop.set_control_flow(ControlFlow::wait_duration(RenderSettings::ON_DEMAND_POLL_INTERVAL))
+            }
+            BackgroundMode::Full => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                self.window.request_redraw();
+            }
+            BackgroundMode::Throttled => {
+                event_loop.set_control_flow(ControlFlow::wait_duration(BackgroundMode::THROTTLED_INTERVAL))
+            }
+            BackgroundMode::Paused => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
+    // Anything that changes the picture by itself, on-demand rendering keeps drawing while one of these runs
+    fn is_animating(&self) -> bool {
+        self.is_renderer_refining
+            || self.camera_controller.is_moving()
+            || self.move_tool.is_some()
+            || self.scatter.is_painting()
+            || self.classification_brush.is_painting()
+            || self.registration.is_refining()
+            || self.change_detection.is_computing()
+            || self.turntable.is_spinning()
+            || self.preview.is_open
+            || self.profiler.is_open
+            || self.recorder.is_recording()
+            || self.recorder.is_replaying()
+            || self.benchmark.as_ref().is_some_and(Benchmark::is_active)
+            || self.is_dataset_running()
+            || self.is_sun_study_running()
+            || !self.toasts.is_empty()
+            || !self.loader.tasks().snapshot().is_empty()
+            || self.ui.context().has_requested_repaint()
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn is_dataset_running(&self) -> bool {
+        self.dataset.as_ref().is_some_and(Dataset::is_running)
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn is_dataset_running(&self) -> bool {
+        false
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn is_sun_study_running(&self) -> bool {
+        self.sun_study.is_running()
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn is_sun_study_running(&self) -> bool {
+        false
+    }
+
+    // Writes the pose whose captures came in, then moves the camera on and requests the next captures once it settled
+    #[cfg(not(target_family = "wasm"))]
+    fn update_dataset(&mut self) {
+        let Some(dataset) = &mut self.dataset else {
+            return;
+        };
+
+        let is_loader_idle = self.loader.tasks().snapshot().is_empty();
+        let query = self.renderer.scene_query();
+        let result = dataset
+            .start_when_loaded(is_loader_idle, &self.entities, query, self.projection.fov_y())
+            .and_then(|_| dataset.write_pose(&self.entities, self.camera.view_matrix(), self.projection.matrix()));
+        match result {
+            Ok(true) => self.renderer.exit(),
+            Ok(false) => {}
+            Err(error) => {
+                log::error!("Unable to write dataset: {}", error);
+                self.renderer.exit();
+            }
+        }
+
+        if let Some(pose) = dataset.camera() {
+            self.camera = Camera::look_at(pose.position, pose.target);
+        }
+        if dataset.record_frame() {
+            let id = self.captures.request(Capture::Dataset);
+            self.renderer
+                .send_command(RenderCommand::CaptureScreenshot { id })
+                .unwrap();
+            self.renderer.send_command(RenderCommand::CaptureAovs).unwrap();
+        }
+    }
+
+    // Moves the sun on to each step and requests its screenshot once the shadows caught up, then puts the app's own
+    // light back
+    #[cfg(not(target_family = "wasm"))]
+    fn update_sun_study(&mut self) {
+        if let Some(light) = self.sun_study.take_restore() {
+            self.set_light(light);
+            return;
+        }
+
+        if let Some(sun) = self.sun_study.light()
+            && sun != self.light
+        {
+            self.set_light(sun);
+        }
+        if self.sun_study.record_frame() {
+            let id = self.captures.request(Capture::SunStudy);
+            self.renderer
+                .send_command(RenderCommand::CaptureScreenshot { id })
+                .unwrap();
+        }
+    }
+
+    // Replaces the app's light and moves its entity along
+    #[cfg(not(target_family = "wasm"))]
+    fn set_light(&mut self, light: Light) {
+        let Some(entity) = self.entities.find_by_label("light").next().map(Entity::id) else {
+            return;
+        };
+        if let Some(entity) = self.entities.get_mut(&entity) {
+            entity.set_transform(light.to_transform());
+        }
+        self.light = light;
+        self.renderer
+            .send_command(RenderCommand::UpdateLight {
+                entity_id: entity,
+                light: self.light.clone(),
+            })
+            .unwrap();
+    }
+
+    // Window input, a no-op unless rendering on demand or in the background
+    pub fn request_redraw(&mut self) {
+        if self.is_in_background() {
+            return;
+        }
+
+        self.is_redraw_requested = true;
+        if self.is_idle {
+            // Time spent idle shouldn't count as one long frame
+            self.is_idle = false;
+            self.timestamp = Instant::now();
+            self.window.request_redraw();
+        }
+    }
+
+    pub fn set_focused(&mut self, is_focused: bool) {
+        self.set_background(is_focused, self.is_occluded);
+    }
+
+    pub fn set_occluded(&mut self, is_occluded: bool) {
+        self.set_background(self.is_focused, is_occluded);
+    }
+
+    fn set_background(&mut self, is_focused: bool, is_occluded: bool) {
+        let was_in_background = self.is_in_background();
+        self.is_focused = is_focused;
+        self.is_occluded = is_occluded;
+
+        // Coming back, the time spent away shouldn't count as one long frame
+        if was_in_background && !self.is_in_background() {
+            self.is_redraw_requested = true;
+            self.timestamp = Instant::now();
+            self.window.request_redraw();
+        }
+    }
+
+    // Minimizing reports a zero size, the surface keeps its last size until the window is restored
     pub fn resize(&mut self, width: u32, height: u32) {
-        if width <= 0 || height <= 0 {
+        if width == 0 || height == 0 {
             return;
         }
 
-        self.projection.resize(width, height);
         self.renderer.resize(width, height);
         self.ui.drop_frame();
     }
 
+    pub fn handle_cursor_moved(&mut self, x: f64, y: f64) {
+        self.cursor_position = glam::Vec2::new(x as f32, y as f32);
+        if self.render_settings.render_mode == RenderMode::Sketch {
+            // Only the latest position matters, it's dropped while the render thread is behind
+            self.renderer.client().update_cursor(self.cursor_position).ok();
+        }
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        if pressed && let Some(tool) = &self.move_tool {
+            let (entity_id, original) = (tool.entity_id(), tool.original());
+            match button {
+                MouseButton::Left => self.move_tool = None,
+                MouseButton::Right => {
+                    self.set_entity_transform(entity_id, original);
+                    self.move_tool = None;
+                }
+                _ => (),
+            }
+
+            return;
+        }
+
+        if button == MouseButton::Left && self.scatter.is_active() {
+            if pressed {
+                self.scatter.begin_stroke();
+            } else {
+                self.scatter.end_stroke();
+            }
+
+            return;
+        }
+
+        if button == MouseButton::Left && self.classification_brush.enabled {
+            if !pressed {
+                self.classification_brush.end_stroke();
+            } else if let Some((render_id, ..)) = self.selected_pointcloud() {
+                self.classification_brush.begin_stroke(render_id);
+            }
+
+            return;
+        }
+
+        if button == MouseButton::Left {
+            if pressed {
+                self.press_position = Some(self.cursor_position);
+            } else if let Some(position) = self.press_position.take()
+                && position.distance(self.cursor_position) < 4.0
+            {
+                self.pick_under_cursor();
+            }
+        }
+
+        self.camera_controller.handle_mouse_button(button, pressed);
+    }
+
+    pub fn handle_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    // Returns true when the key was used by a shortcut
+    pub fn handle_shortcut(&mut self, code: KeyCode) -> bool {
+        if code == KeyCode::Escape && self.isolation.is_active() {
+            self.restore_visibility();
+            return true;
+        }
+
+        if code == KeyCode::KeyI && self.modifiers.is_empty() {
+            if self.isolation.is_active() {
+                self.restore_visibility();
+            } else if let Some(entity_id) = self.selected {
+                self.isolate(&[entity_id]);
+            }
+
+            return true;
+        }
+
+        if code == KeyCode::KeyG && self.modifiers.is_empty() {
+            if self.move_tool.is_none()
+                && let Some(entity) = self.selected.and_then(|id| self.entities.get(&id))
+            {
+                self.move_tool = Some(MoveTool::new(entity));
+            }
+
+            return true;
+        }
+
+        if !(self.modifiers.control_key() || self.modifiers.super_key()) {
+            return false;
+        }
+
+        match code {
+            KeyCode::KeyZ => self.undo_classification(),
+            KeyCode::KeyC => self.copy_selection(),
+            KeyCode::KeyV => self.paste(),
+            KeyCode::KeyD => {
+                self.copy_selection();
+                self.paste();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn isolate(&mut self, isolated: &[EntityId]) {
+        let changes = self.isolation.isolate(&mut self.entities, isolated);
+        self.renderer
+            .send_command(RenderCommand::SetVisibility(changes))
+            .unwrap();
+    }
+
+    fn restore_visibility(&mut self) {
+        let changes = self.isolation.restore(&mut self.entities);
+        self.renderer
+            .send_command(RenderCommand::SetVisibility(changes))
+            .unwrap();
+    }
+
+    fn selected_pointcloud(&self) -> Option<(RenderId, Arc<PointcloudBuffer>, glam::Mat4)> {
+        let entity_id = self.selected?;
+        let render_id = self.entities.get(&entity_id)?.render_id()?;
+        let (buffer, transform) = self.renderer.scene_query().pointcloud(entity_id)?;
+        Some((render_id, buffer, transform))
+    }
+
+    fn undo_classification(&mut self) {
+        if let Some((render_id, changes)) = self.classification_brush.undo() {
+            self.renderer
+                .send_command(RenderCommand::ClassifyPoints { render_id, changes })
+                .unwrap();
+        }
+    }
+
+    fn copy_selection(&mut self) {
+        if let Some(entity) = self.selected.and_then(|id| self.entities.get(&id)) {
+            self.clipboard.copy([entity]);
+        }
+    }
+
+    // Goes through SpawnAsset like any other entity, the last pasted one becomes the selection
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            return;
+        }
+
+        let mut pasted = None;
+        for (render_id, transform) in self.clipboard.paste() {
+            pasted = Some(self.spawn_entity(render_id, transform));
+        }
+        self.select(pasted);
+    }
+
+    // Despawns every entity with geometry, except the proxies of imports still running. Only here, a shared session
+    // keeps its entities on the other viewers
+    fn clear_scene(&mut self) {
+        let entity_ids = self
+            .entities
+            .iter()
+            .filter(|entity| entity.render_id().is_some())
+            .map(Entity::id)
+            .collect::<Vec<_>>();
+        for entity_id in entity_ids {
+            #[cfg(not(target_family = "wasm"))]
+            if self.proxies.contains(entity_id) {
+                continue;
+            }
+            self.despawn_entity(entity_id);
+        }
+    }
+
+    fn despawn_entity(&mut self, entity_id: EntityId) {
+        if self.selected == Some(entity_id) {
+            self.select(None);
+        }
+        self.prefabs.remove_entity(entity_id);
+        self.renderer
+            .send_command(RenderCommand::DespawnAsset(entity_id))
+            .unwrap();
+        self.entities.remove(&entity_id);
+    }
+
+    // Saved around the selected entity's position
+    fn save_prefab(&mut self, name: &str) {
+        let Some(selected) = self.selected.and_then(|id| self.entities.get(&id)) else {
+            return;
+        };
+
+        let origin = glam::Mat4::from_translation(selected.transform().w_axis.truncate());
+        let composition = composition(&self.entities, &self.prefabs, &self.light, selected);
+        self.prefabs.save(name, origin, &composition);
+    }
+
+    fn apply_prefab(&mut self) {
+        let Some(selected) = self.selected.and_then(|id| self.entities.get(&id)) else {
+            return;
+        };
+
+        let composition = composition(&self.entities, &self.prefabs, &self.light, selected);
+        self.prefabs.apply(selected.id(), &composition);
+    }
+
+    // Imports the files new instances need and keeps the entities of every instance in line with their prefab
+    fn update_prefabs(&mut self) {
+        for (path, options) in self.prefabs.take_loads() {
+            self.loader.load_with_options(path, options);
+        }
+
+        let changes = self.prefabs.poll();
+        self.is_redraw_requested |= !changes.is_empty();
+        for change in changes {
+            match change {
+                PrefabChange::Spawn(entity, light) => {
+                    let entity_id = entity.id();
+                    let transform = entity.transform();
+                    match (light, entity.render_id()) {
+                        (Some(light), _) => self
+                            .renderer
+                            .send_command(RenderCommand::SpawnLight { entity_id, light })
+                            .unwrap(),
+                        (None, Some(render_id)) => {
+                            #[cfg(not(target_family = "wasm"))]
+                            if let Some(session) = &self.session {
+                                session.spawn(entity_id, render_id, transform, entity.label().clone());
+                            }
+                            self.renderer
+                                .send_command(RenderCommand::SpawnAsset {
+                                    entity_id,
+                                    render_id,
+                                    transform,
+                                })
+                                .unwrap();
+                        }
+                        (None, None) => continue,
+                    }
+                    self.insert_entity(entity);
+                }
+                PrefabChange::Update {
+                    entity_id,
+                    transform,
+                    label,
+                    tags,
+                    light,
+                } => {
+                    let Some(entity) = self.entities.get_mut(&entity_id) else {
+                        continue;
+                    };
+                    entity.set_label(label);
+                    entity.set_tags(tags);
+                    self.renderer.send_command(entity_tags(entity)).unwrap();
+                    match light {
+                        Some(light) => {
+                            entity.set_transform(transform);
+                            self.renderer
+                                .send_command(RenderCommand::UpdateLight { entity_id, light })
+                                .unwrap();
+                        }
+                        None => self.set_entity_transform(entity_id, transform),
+                    }
+                }
+                PrefabChange::Despawn(entity_id) => self.despawn_entity(entity_id),
+            }
+        }
+    }
+
+    fn select(&mut self, entity_id: Option<EntityId>) {
+        if entity_id == self.selected {
+            return;
+        }
+
+        self.selected = entity_id;
+        self.renderer
+            .send_command(RenderCommand::SetSelection(entity_id))
+            .unwrap();
+    }
+
+    // Entities duplicated from the asset's nodes go with it, the renderer drops their nodes along with the renderables
+    fn unload_asset(&mut self, asset_id: Uuid) {
+        let Some(render_ids) = self.asset_nodes.remove(&asset_id) else {
+            return;
+        };
+
+        let entities = self
+            .entities
+            .iter()
+            .filter(|entity| entity.render_id().is_some_and(|id| render_ids.contains(&id)))
+            .map(Entity::id)
+            .collect::<Vec<_>>();
+        for entity_id in entities {
+            if self.selected == Some(entity_id) {
+                self.select(None);
+            }
+            self.prefabs.remove_entity(entity_id);
+            self.entities.remove(&entity_id);
+        }
+
+        self.assets.retain(|(render_id, _)| !render_ids.contains(render_id));
+        for render_id in &render_ids {
+            self.asset_stats.remove(render_id);
+            self.node_metadata.remove(render_id);
+        }
+        if let Some(render_id) = self.scatter.render_id
+            && render_ids.contains(&render_id)
+        {
+            self.scatter.render_id = None;
+        }
+        self.material_variants.remove(&asset_id);
+        #[cfg(not(target_family = "wasm"))]
+        self.reloader.unwatch(asset_id);
+        self.renderer
+            .send_command(RenderCommand::UnloadAsset(render_ids))
+            .unwrap();
+    }
+
+    fn set_material_variant(&mut self, asset_id: Uuid, variant: Option<usize>) {
+        match variant {
+            Some(variant) => self.material_variants.insert(asset_id, variant),
+            None => self.material_variants.remove(&asset_id),
+        };
+        let mut render_ids = self
+            .entities
+            .iter()
+            .filter(|entity| entity.asset_id() == Some(asset_id))
+            .filter_map(Entity::render_id)
+            .collect::<Vec<_>>();
+        render_ids.sort_unstable();
+        render_ids.dedup();
+        self.renderer
+            .send_command(RenderCommand::SetMaterialVariant { render_ids, variant })
+            .unwrap();
+    }
+
+    // Points are a pixel or two across, so they're picked within a few pixels of the cursor and win over a surface
+    // further away. Surfaces are picked by the renderer from what it drew, the selection follows with the PickResult
+    fn pick_under_cursor(&mut self) {
+        const PICK_RADIUS: f32 = 6.0;
+
+        let ray = self.cursor_ray();
+        let height = self.window.inner_size().height.max(1) as f32;
+        let max_angle = PICK_RADIUS * self.projection.fov_y() / height;
+
+        let query = self.renderer.scene_query();
+        let hit = query.closest_hit(&ray);
+        let point = query
+            .closest_point(&ray, max_angle)
+            .filter(|point| hit.is_none_or(|hit| point.distance < hit.surface.distance));
+        if point.as_ref().is_some_and(|point| self.registration.pick(point)) {
+            return;
+        }
+
+        match point {
+            Some(point) => {
+                self.select(Some(point.entity_id));
+                self.picked_point = Some(point);
+            }
+            None => {
+                let pixel = self.cursor_position.max(glam::Vec2::ZERO).as_uvec2();
+                self.renderer
+                    .send_command(RenderCommand::Pick { x: pixel.x, y: pixel.y })
+                    .unwrap();
+                self.picked_point = None;
+            }
+        }
+    }
+
+    // Keeps the file's own coordinates and attributes, the entity's transform only decides which points are in view
+    #[cfg(not(target_family = "wasm"))]
+    fn export_points(&self, buffer: Arc<PointcloudBuffer>, transform: glam::Mat4) {
+        let file_name = self
+            .selected
+            .and_then(|entity_id| self.entities.get(&entity_id))
+            .and_then(|entity| entity.label().as_deref())
+            .map_or("points", |label| label.rsplit_once('.').map_or(label, |(stem, _)| stem));
+        let view_projection = self
+            .export_in_view
+            .then(|| self.projection.matrix() * self.camera.view_matrix() * transform);
+
+        export_points_dialog(format!("{}.laz", file_name), move |compressed| {
+            buffer.to_las(compressed, |_, position| {
+                view_projection.is_none_or(|view_projection| {
+                    let clip = view_projection * position.extend(1.0);
+                    clip.w > 0.0 && clip.x.abs() <= clip.w && clip.y.abs() <= clip.w && (0.0..=clip.w).contains(&clip.z)
+                })
+            })
+        });
+    }
+
+    fn hit_under_cursor(&self) -> Option<SceneHit> {
+        self.renderer.scene_query().closest_hit(&self.cursor_ray())
+    }
+
+    // A replay already holds the camera's path, only a spinning selection is left to the turntable then
+    fn spin_turntable(&mut self, timestep: Duration) {
+        match self.turntable.settings.target {
+            TurntableTarget::Camera if !self.recorder.is_replaying() => {
+                let focus = self.turntable.focus().unwrap_or_else(|| self.view_focus());
+                self.turntable.orbit_camera(&mut self.camera, focus, timestep);
+            }
+            TurntableTarget::Camera => {}
+            TurntableTarget::Selection => {
+                let Some(entity) = self.selected.and_then(|id| self.entities.get(&id)) else {
+                    return;
+                };
+                let center = entity
+                    .render_id()
+                    .and_then(|render_id| self.asset_stats.get(&render_id))
+                    .map_or(glam::Vec3::ZERO, |stats| stats.bounds.centroid());
+                let center = entity.transform().transform_point3(center);
+                let transform = self.turntable.spin(entity.transform(), center, timestep);
+                self.set_entity_transform(entity.id(), transform);
+            }
+        }
+    }
+
+    // Whatever lies in the middle of the view, or a point in front of the camera when that's empty
+    fn view_focus(&self) -> glam::Vec3 {
+        const DEFAULT_DISTANCE: f32 = 10.0;
+        let ray = Ray::new(self.camera.position(), self.camera.forward());
+        self.renderer
+            .scene_query()
+            .closest_hit(&ray)
+            .map_or(ray.origin + ray.direction * DEFAULT_DISTANCE, |hit| {
+                hit.surface.position
+            })
+    }
+
+    fn cursor_ray(&self) -> Ray {
+        let size = self.window.inner_size();
+        let viewport = glam::Vec2::new(size.width as f32, size.height as f32);
+        self.camera.screen_ray(&self.projection, self.cursor_position, viewport)
+    }
+
+    fn set_entity_transform(&mut self, entity_id: EntityId, transform: glam::Mat4) {
+        if let Some(entity) = self.entities.get_mut(&entity_id) {
+            entity.set_transform(transform);
+            self.send_transform(entity_id);
+            #[cfg(not(target_family = "wasm"))]
+            if let Some(session) = &self.session {
+                session.transform(entity_id, transform);
+            }
+        }
+    }
+
+    // The entity's own transform with the explode view on top
+    fn send_transform(&self, entity_id: EntityId) {
+        if let Some(entity) = self.entities.get(&entity_id) {
+            let transform = self.explode.apply(entity_id, entity.transform());
+            self.renderer.client().update_transform(entity_id, transform).unwrap();
+        }
+    }
+
+    // Explodes the selected assembly, one at a time so the previous one is put back together first
+    fn update_explode(&mut self) {
+        let asset_id = self
+            .selected
+            .and_then(|id| self.entities.get(&id))
+            .and_then(Entity::asset_id);
+        if asset_id != self.explode.asset_id() {
+            let centers = self
+                .entities
+                .iter()
+                .filter(|entity| asset_id.is_some() && entity.asset_id() == asset_id)
+                .filter_map(|entity| {
+                    let bounds = self.asset_stats.get(&entity.render_id()?)?.bounds;
+                    Some((entity.id(), entity.transform().transform_point3(bounds.centroid())))
+                })
+                .collect();
+            for entity_id in self.explode.set_assembly(asset_id, centers) {
+                self.send_transform(entity_id);
+            }
+        }
+
+        for entity_id in self.explode.nodes().collect::<Vec<_>>() {
+            self.send_transform(entity_id);
+        }
+    }
+
+    fn hovered_details(&self) -> Option<Vec<(&'static str, String)>> {
+        let hit = self.hit_under_cursor()?;
+        let entity = self.entities.get(&hit.entity_id)?;
+        let source = self
+            .assets
+            .iter()
+            .find(|(render_id, _)| *render_id == hit.render_id)
+            .map(|(render_id, label)| asset_name(render_id, label))
+            .unwrap_or_else(|| "Unknown".to_string());
+
+        let mut details = vec![
+            ("Label", entity.label().clone().unwrap_or_else(|| "None".to_string())),
+            ("Source", source),
+        ];
+        if let Some(stats) = self.asset_stats.get(&hit.render_id) {
+            if stats.points > 0 {
+                details.push(("Points", stats.points.to_string()));
+            } else {
+                details.push(("Triangles", stats.triangles.to_string()));
+            }
+            details.push(("Materials", stats.materials.to_string()));
+        }
+
+        Some(details)
+    }
+
+    fn surface_under_cursor(&self) -> Option<SurfaceHit> {
+        let ray = self.cursor_ray();
+
+        // Scene geometry first, skipping the instances being painted so strokes don't stack on themselves
+        let scene_hit = self
+            .renderer
+            .scene_query()
+            .closest_hit_filtered(&ray, |render_id| Some(*render_id) != self.scatter.render_id);
+        if let Some(hit) = scene_hit {
+            return Some(hit.surface);
+        }
+
+        ray.intersect_plane(glam::Vec3::ZERO, glam::Vec3::Y)
+            .map(|distance| SurfaceHit {
+                position: ray.at(distance),
+                normal: glam::Vec3::Y,
+                distance,
+            })
+    }
+
+    fn apply_world_unit(&mut self) {
+        let (z_near, z_far) = self.world_unit.clip_planes();
+        self.render_settings.clip_planes.near = z_near;
+        self.render_settings.clip_planes.far = z_far;
+        self.settings_file.save(&self.render_settings);
+        self.camera_controller.set_speed(self.world_unit.camera_speed());
+        self.snapping.grid_step = self.world_unit.grid_step();
+    }
+
+    fn cube_render_id(&self) -> Option<RenderId> {
+        self.assets
+            .iter()
+            .find(|(_, label)| label.as_deref() == Some("cube.obj"))
+            .map(|(render_id, _)| *render_id)
+    }
+
+    fn reference_render_id(&self) -> Option<RenderId> {
+        self.assets
+            .iter()
+            .find(|(_, label)| label.as_deref() == Some(ScaleReference::FILE))
+            .map(|(render_id, _)| *render_id)
+    }
+
+    // A few meters in front of the camera, standing on the ground
+    fn spawn_reference(&mut self, reference: ScaleReference) {
+        let Some(render_id) = self.reference_render_id() else {
+            return;
+        };
+
+        let distance = 5.0 * self.world_unit.per_meter();
+        let position = self.camera.position() + self.camera.forward().with_y(0.0).normalize_or_zero() * distance;
+        let transform = reference.transform(position, self.world_unit);
+        let entity_id = self.spawn_labeled(render_id, transform, Some(reference.to_str().to_string()));
+        self.select(Some(entity_id));
+    }
+
+    fn spawn_entity(&mut self, render_id: RenderId, transform: glam::Mat4) -> EntityId {
+        let label = self
+            .assets
+            .iter()
+            .find(|(id, _)| *id == render_id)
+            .and_then(|(_, label)| label.clone());
+
+        self.spawn_labeled(render_id, transform, label)
+    }
+
+    fn spawn_labeled(&mut self, render_id: RenderId, transform: glam::Mat4, label: Option<String>) -> EntityId {
+        let entity = Entity::new(transform, label.clone()).with_render_id(render_id);
+        let entity_id = entity.id();
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(session) = &self.session {
+            session.spawn(entity_id, render_id, transform, label);
+        }
+
+        self.renderer
+            .send_command(RenderCommand::SpawnAsset {
+                entity_id,
+                render_id,
+                transform,
+            })
+            .unwrap();
+        self.insert_entity(entity)
+    }
+
+    fn insert_entity(&mut self, entity: Entity) -> EntityId {
+        self.renderer.send_command(entity_tags(&entity)).unwrap();
+        self.entities.insert(entity)
+    }
+
     pub fn exit(&mut self) {
         self.renderer.exit();
     }
@@ -293,7 +1972,130 @@ impl State {
     }
 }
 
-fn create_instances(label: Option<String>) -> Vec<Entity> {
+fn asset_name(render_id: &RenderId, label: &Option<String>) -> String {
+    let id = render_id.simple().to_string();
+    match label {
+        Some(label) => format!("{} ({})", label, &id[..8]),
+        None => id[..8].to_string(),
+    }
+}
+
+fn entity_tags(entity: &Entity) -> RenderCommand {
+    RenderCommand::UpdateTags {
+        entity_id: entity.id(),
+        tags: EntityTags {
+            label: entity.label().clone(),
+            tags: entity.tags().clone(),
+        },
+    }
+}
+
+// The first node of a load takes over the entity of its proxy, which is labeled with the file name
+#[cfg(not(target_family = "wasm"))]
+fn replace_proxy(
+    proxies: &mut LoadingProxies,
+    renderer: &Renderer,
+    entity: Entity,
+    label: Option<&str>,
+    asset_id: Uuid,
+) -> Entity {
+    let Some(entity_id) = label.and_then(|label| proxies.take(label, asset_id)) else {
+        return entity;
+    };
+
+    renderer
+        .send_command(RenderCommand::SetVisibility(vec![(entity_id, entity.visibility())]))
+        .unwrap();
+    renderer
+        .send_command(RenderCommand::SetEntityLayers {
+            entity_id,
+            layers: entity.layers(),
+        })
+        .unwrap();
+    entity.with_id(entity_id)
+}
+
+// World bounds of everything not hidden
+fn visible_bounds(entities: &EntityStore, asset_stats: &HashMap<RenderId, AssetStats>) -> Aabb {
+    let mut bounds = Aabb::EMPTY;
+    for entity in entities.iter() {
+        if entity.visibility() == Visibility::Hidden {
+            continue;
+        }
+        if let Some(stats) = entity.render_id().and_then(|render_id| asset_stats.get(&render_id)) {
+            bounds.merge(&stats.bounds.transform(entity.transform()));
+        }
+    }
+    bounds
+}
+
+// The entity's prefab instance, or its assembly when it isn't part of one, with the settings of the lights among them
+fn composition<'a>(
+    entities: &'a EntityStore,
+    prefabs: &PrefabLibrary,
+    light: &Light,
+    selected: &Entity,
+) -> Vec<(&'a Entity, Option<Light>)> {
+    let entity_ids = match (prefabs.instance_entities(selected.id()), selected.asset_id()) {
+        (Some(entity_ids), _) => entity_ids,
+        (None, Some(asset_id)) => entities
+            .iter()
+            .filter(|entity| entity.asset_id() == Some(asset_id))
+            .map(Entity::id)
+            .collect(),
+        (None, None) => vec![selected.id()],
+    };
+
+    // The app only keeps the settings of its own light, the others belong to instances
+    let light_id = entities.find_by_label("light").next().map(Entity::id);
+    entity_ids
+        .iter()
+        .filter_map(|entity_id| entities.get(entity_id))
+        .map(|entity| {
+            let entity_light = match prefabs.light(entity.id()) {
+                Some(entity_light) => Some(entity_light.clone()),
+                None => (Some(entity.id()) == light_id).then(|| light.clone()),
+            };
+            (entity, entity_light)
+        })
+        .collect()
+}
+
+fn entity_json(entity: &Entity) -> serde_json::Value {
+    serde_json::json!({
+        "id": entity.id().to_string(),
+        "label": entity.label(),
+        "tags": entity.tags(),
+    })
+}
+
+// ASPRS standard classes, shared by LAS 1.1 through 1.4
+fn classification_name(classification: u8) -> &'static str {
+    match classification {
+        0 => "Never classified",
+        1 => "Unclassified",
+        2 => "Ground",
+        3 => "Low vegetation",
+        4 => "Medium vegetation",
+        5 => "High vegetation",
+        6 => "Building",
+        7 => "Low point",
+        8 => "Model key point",
+        9 => "Water",
+        10 => "Rail",
+        11 => "Road surface",
+        12 => "Overlap",
+        13 => "Wire guard",
+        14 => "Wire conductor",
+        15 => "Transmission tower",
+        16 => "Wire connector",
+        17 => "Bridge deck",
+        18 => "High noise",
+        _ => "User defined",
+    }
+}
+
+fn create_instances(render_id: RenderId, label: Option<String>) -> Vec<Entity> {
     #[derive(Clone)]
     pub struct DemoInstance {
         pub position: glam::Vec3,
@@ -341,7 +2143,8 @@ fn create_instances(label: Option<String>) -> Vec<Entity> {
             let mut entity = Entity::new(
                 glam::Mat4::from_rotation_translation(instance.rotation, instance.position),
                 label.clone(),
-            );
+            )
+            .with_render_id(render_id);
 
             let translation = glam::Vec3 {
                 x: 0.0,
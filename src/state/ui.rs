@@ -0,0 +1,258 @@
+use std::collections::BTreeSet;
+
+use uuid::Uuid;
+
+use crate::{
+    camera::Camera,
+    entity::{Entity, EntityId},
+    photo_match::CameraMatch,
+    renderer::{AnimationPhase, RenderCommand},
+    state::{State, entity_tags},
+    units::ScaleReference,
+};
+
+mod capture;
+mod import;
+mod inspector;
+mod interface;
+mod lighting;
+mod overlays;
+mod quality;
+#[cfg(not(target_family = "wasm"))]
+mod session;
+mod tools;
+
+// What the panels asked for, applied after the frame's UI ended
+#[derive(Default)]
+pub(super) struct UiActions {
+    clear_selection: bool,
+    edited_transform: Option<(EntityId, glam::Mat4)>,
+    edited_tags: Option<(EntityId, BTreeSet<String>)>,
+    edited_variant: Option<(Uuid, Option<usize>)>,
+    edited_phases: Vec<(EntityId, AnimationPhase)>,
+    unloaded_asset: Option<Uuid>,
+    isolated: Option<Vec<EntityId>>,
+    is_restore_requested: bool,
+    is_explode_changed: bool,
+    saved_prefab: Option<String>,
+    placed_prefab: Option<String>,
+    is_prefab_applied: bool,
+    is_unit_changed: bool,
+    is_style_changed: bool,
+    spawned_reference: Option<ScaleReference>,
+    undo_classification: bool,
+    is_align_requested: bool,
+    is_refine_requested: bool,
+    is_comparison_requested: bool,
+    is_restore_colors_requested: bool,
+    is_recolor_requested: bool,
+    camera_match: Option<CameraMatch>,
+}
+
+impl State {
+    // Every window and overlay of the frame, returns what they asked for
+    pub(super) fn show_ui(
+        &mut self,
+        ctx: &egui::Context,
+        average_fps: f32,
+        light_id: EntityId,
+        hovered: Option<Vec<(&'static str, String)>>,
+    ) -> UiActions {
+        let mut actions = UiActions::default();
+        self.status_bar(ctx);
+        self.debug_window(ctx, average_fps, light_id, &mut actions);
+        self.import_report_window(ctx);
+        self.point_window(ctx);
+        self.show_toasts(ctx);
+
+        self.profiler.show(ctx);
+        self.preview.show(ctx, &self.entities, &self.camera);
+        self.frame_graph.show(ctx);
+
+        self.hover_tooltip(ctx, hovered);
+        self.color_ramp_legend(ctx);
+        #[cfg(not(target_family = "wasm"))]
+        self.peer_cameras(ctx);
+        self.photo_match
+            .show(ctx, self.projection.matrix() * self.camera.view_matrix());
+        actions
+    }
+
+    fn debug_window(&mut self, ctx: &egui::Context, average_fps: f32, light_id: EntityId, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        egui::Window::new(language.tr("Debug"))
+            .id(egui::Id::new("debug_window"))
+            .resizable(true)
+            .movable(true)
+            .show(ctx, |ui| {
+                self.interface_panel(ui, average_fps, actions);
+                ui.add_space(10.0);
+                self.import_panel(ui);
+                ui.add_space(10.0);
+
+                self.light_panel(ui, light_id);
+                self.quality_panel(ui);
+
+                ui.add_space(10.0);
+                self.world_panel(ui, actions);
+                self.probes_panel(ui);
+                self.irradiance_volume_panel(ui);
+                self.lightmap_panel(ui);
+                self.selection_panel(ui, actions);
+                self.prefabs_panel(ui, actions);
+                self.scatter_panel(ui);
+                self.classify_panel(ui, actions);
+                self.registration_panel(ui, actions);
+                self.photo_match_panel(ui, actions);
+                self.change_detection_panel(ui, actions);
+                self.render_layers_panel(ui);
+                #[cfg(not(target_family = "wasm"))]
+                self.session_panel(ui);
+                self.turntable_panel(ui);
+                self.recording_panel(ui);
+                #[cfg(not(target_family = "wasm"))]
+                self.screenshot_panel(ui);
+                #[cfg(not(target_family = "wasm"))]
+                self.floorplan_panel(ui);
+                #[cfg(not(target_family = "wasm"))]
+                self.sun_study_panel(ui);
+                self.buffer_inspector_panel(ui);
+                self.volume_panel(ui);
+            });
+    }
+
+    // Runs once the frame's UI has ended, the panels only record what was asked for
+    pub(super) fn apply_ui_actions(&mut self, actions: UiActions) {
+        let UiActions {
+            clear_selection,
+            edited_transform,
+            edited_tags,
+            edited_variant,
+            edited_phases,
+            unloaded_asset,
+            isolated,
+            is_restore_requested,
+            is_explode_changed,
+            saved_prefab,
+            placed_prefab,
+            is_prefab_applied,
+            is_unit_changed,
+            is_style_changed,
+            spawned_reference,
+            undo_classification,
+            is_align_requested,
+            is_refine_requested,
+            is_comparison_requested,
+            is_restore_colors_requested,
+            is_recolor_requested,
+            camera_match,
+        } = actions;
+        if clear_selection {
+            self.select(None);
+        }
+        if let Some((entity_id, transform)) = edited_transform {
+            self.set_entity_transform(entity_id, transform);
+        }
+        if let Some(isolated) = isolated {
+            self.isolate(&isolated);
+        }
+        if is_restore_requested {
+            self.restore_visibility();
+        }
+        if is_explode_changed {
+            self.update_explode();
+        }
+        if let Some(name) = saved_prefab {
+            self.save_prefab(&name);
+        }
+        if is_prefab_applied {
+            self.apply_prefab();
+        }
+        if let Some(name) = placed_prefab {
+            let origin = glam::Mat4::from_translation(self.view_focus());
+            self.prefabs.instantiate(&name, origin);
+        }
+        if let Some((entity_id, tags)) = edited_tags
+            && let Some(entity) = self.entities.get_mut(&entity_id)
+        {
+            entity.set_tags(tags);
+            self.renderer.send_command(entity_tags(entity)).unwrap();
+        }
+        if let Some((asset_id, variant)) = edited_variant {
+            self.set_material_variant(asset_id, variant);
+        }
+        for (entity_id, phase) in edited_phases {
+            if let Some(entity) = self.entities.get_mut(&entity_id) {
+                entity.set_animation_phase(phase);
+                self.renderer
+                    .send_command(RenderCommand::SetAnimationPhase { entity_id, phase })
+                    .unwrap();
+            }
+        }
+        if let Some(asset_id) = unloaded_asset {
+            self.unload_asset(asset_id);
+        }
+        if is_unit_changed {
+            self.apply_world_unit();
+        }
+        if is_style_changed {
+            self.ui.set_style(&self.render_settings.ui_style);
+        }
+        if let Some(reference) = spawned_reference {
+            self.spawn_reference(reference);
+        }
+        if undo_classification {
+            self.undo_classification();
+        }
+        if is_align_requested
+            && let Some(transform) = self
+                .registration
+                .moving
+                .and_then(|entity_id| self.entities.get(&entity_id))
+                .map(Entity::transform)
+            && let Some((entity_id, transform)) = self.registration.align(transform)
+        {
+            self.set_entity_transform(entity_id, transform);
+        }
+        // Keeps the position, the focal length goes through the physical camera
+        if let Some(solved) = camera_match {
+            self.camera = Camera::from_orientation(self.camera.position(), solved.orientation);
+            let camera = &mut self.render_settings.physical_camera;
+            camera.enabled = true;
+            camera.focal_length = solved.focal_length * camera.sensor_width;
+            self.renderer
+                .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                .unwrap();
+            self.settings_file.save(&self.render_settings);
+        }
+        if is_refine_requested {
+            self.registration.refine(self.renderer.scene_query(), &self.loader);
+        }
+        let compared = self
+            .change_detection
+            .compared
+            .and_then(|entity_id| self.entities.get(&entity_id))
+            .and_then(Entity::render_id);
+        if is_comparison_requested && let Some(render_id) = compared {
+            let task = self.loader.tasks().start("Comparing scans");
+            self.change_detection.compute(
+                render_id,
+                self.renderer.scene_query(),
+                self.render_settings.color_ramp,
+                self.render_settings.custom_ramp.clone(),
+                task,
+            );
+        }
+        if is_recolor_requested {
+            self.change_detection.recolor();
+        }
+        if is_restore_colors_requested
+            && let Some(render_id) = compared
+            && let Some(buffer) = self.change_detection.restore(render_id)
+        {
+            self.renderer
+                .send_command(RenderCommand::ReplacePoints { render_id, buffer })
+                .unwrap();
+        }
+    }
+}
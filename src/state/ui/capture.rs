@@ -0,0 +1,245 @@
+use instant::Instant;
+
+#[cfg(not(target_family = "wasm"))]
+use crate::{
+    floorplan::FloorplanView,
+    renderer::{MAX_SCREENSHOT_TILES, RenderCommand},
+    state::{Capture, visible_bounds},
+};
+use crate::{state::State, turntable::TurntableTarget};
+
+impl State {
+    pub(super) fn turntable_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Turntable"), |ui| {
+            let mut changed = ui.checkbox(&mut self.turntable.settings.enabled, tr("Spin")).changed();
+            ui.horizontal(|ui| {
+                for target in TurntableTarget::ALL {
+                    changed |= ui
+                        .radio_value(&mut self.turntable.settings.target, target, tr(target.to_str()))
+                        .changed();
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut self.turntable.settings.speed, -90.0..=90.0)
+                    .suffix("°/s")
+                    .text(tr("Speed")),
+            );
+            if changed {
+                self.turntable.reset_focus();
+            }
+        });
+    }
+
+    // Camera and settings on a timeline, for repeatable demo runs and navigation bugs
+    pub(super) fn recording_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Recording"), |ui| {
+            ui.horizontal(|ui| {
+                if self.recorder.is_recording() {
+                    if ui.button(tr("Stop recording")).clicked() {
+                        self.recorder.stop();
+                    }
+                } else if ui
+                    .add_enabled(!self.recorder.is_replaying(), egui::Button::new(tr("Record")))
+                    .clicked()
+                {
+                    self.recorder.start_recording();
+                }
+
+                if self.recorder.is_replaying() {
+                    if ui.button(tr("Stop replay")).clicked() {
+                        self.recorder.stop();
+                    }
+                } else if ui
+                    .add_enabled(!self.recorder.is_recording(), egui::Button::new(tr("Replay")))
+                    .clicked()
+                    && let Err(error) = self.recorder.start_replay()
+                {
+                    self.toasts.push_back((format!("Replay: {}", error), Instant::now()));
+                }
+            });
+
+            if self.recorder.is_recording() {
+                ui.label(format!("Recording {:.1} s", self.recorder.duration()));
+            } else if self.recorder.is_replaying() {
+                ui.add(egui::ProgressBar::new(self.recorder.progress()).text(format!(
+                    "{:.1} / {:.1} s",
+                    self.recorder.progress() * self.recorder.duration(),
+                    self.recorder.duration()
+                )));
+            }
+        });
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(super) fn screenshot_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.horizontal(|ui| {
+            if ui.button(tr("Save screenshot")).clicked() {
+                let id = self.captures.request(Capture::Save);
+                self.renderer
+                    .send_command(RenderCommand::CaptureScreenshot { id })
+                    .unwrap();
+                if self.export_aovs {
+                    self.renderer.send_command(RenderCommand::CaptureAovs).unwrap();
+                }
+            }
+            ui.checkbox(&mut self.export_aovs, tr("with depth, normals and ids"));
+        });
+        ui.horizontal(|ui| {
+            let size = self.window.inner_size();
+            ui.add(
+                egui::DragValue::new(&mut self.screenshot_tiles)
+                    .range(2..=MAX_SCREENSHOT_TILES)
+                    .suffix("×"),
+            );
+            if ui
+                .button(format!(
+                    "{} ({} × {})",
+                    tr("Save tiled screenshot"),
+                    size.width * self.screenshot_tiles,
+                    size.height * self.screenshot_tiles
+                ))
+                .clicked()
+            {
+                let id = self.captures.request(Capture::Save);
+                self.renderer
+                    .send_command(RenderCommand::CaptureTiledScreenshot {
+                        id,
+                        tiles: self.screenshot_tiles,
+                        position: self.camera.position(),
+                        view: self.camera.view_matrix(),
+                        projection: self.projection.matrix(),
+                    })
+                    .unwrap();
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.panorama_width)
+                    .range(512..=8192)
+                    .speed(16.0)
+                    .suffix(" px"),
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.panorama_strips)
+                    .range(4..=512)
+                    .suffix(format!(" {}", tr("strips"))),
+            );
+            if ui.button(tr("Save stereo panorama")).clicked() {
+                let forward = self.camera.forward();
+                let id = self.captures.request(Capture::Save);
+                self.renderer
+                    .send_command(RenderCommand::CaptureStereoPanorama {
+                        id,
+                        position: self.camera.position(),
+                        yaw: forward.x.atan2(-forward.z),
+                        width: self.panorama_width,
+                        // Average human eye distance
+                        eye_distance: 0.064 * self.world_unit.per_meter(),
+                        strips: self.panorama_strips,
+                    })
+                    .unwrap();
+            }
+        });
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(super) fn floorplan_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Floorplan"), |ui| {
+            ui.horizontal(|ui| {
+                for view in FloorplanView::ALL {
+                    if ui
+                        .radio_value(&mut self.floorplan.view, view, tr(view.to_str()))
+                        .changed()
+                    {
+                        self.floorplan
+                            .fit_slab(&visible_bounds(&self.entities, &self.asset_stats));
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                let [bottom, top] = &mut self.floorplan.slab;
+                ui.label(tr("Slab"));
+                ui.add(egui::DragValue::new(bottom).speed(0.01 * self.world_unit.per_meter()));
+                ui.add(egui::DragValue::new(top).speed(0.01 * self.world_unit.per_meter()));
+                if ui.button(tr("Fit")).clicked() {
+                    self.floorplan
+                        .fit_slab(&visible_bounds(&self.entities, &self.asset_stats));
+                }
+            });
+            ui.add(
+                egui::DragValue::new(&mut self.floorplan.pixels_per_meter)
+                    .range(1.0..=2000.0)
+                    .suffix(format!(" {}", tr("px per meter"))),
+            );
+            if ui.button(tr("Save floorplan")).clicked() {
+                let size = self.window.inner_size();
+                let id = self.captures.request(Capture::Floorplan);
+                match self.floorplan.capture(
+                    id,
+                    &visible_bounds(&self.entities, &self.asset_stats),
+                    (size.width, size.height),
+                    self.world_unit,
+                ) {
+                    Some(command) => self.renderer.send_command(command).unwrap(),
+                    None => {
+                        self.captures.take(id);
+                    }
+                }
+            }
+        });
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(super) fn sun_study_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Sun study"), |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.sun_study.latitude, -90.0..=90.0)
+                    .suffix("°")
+                    .text(tr("Latitude")),
+            );
+            ui.horizontal(|ui| {
+                let days = self.sun_study.days_in_month();
+                ui.label(tr("Date"));
+                ui.add(egui::DragValue::new(&mut self.sun_study.day).range(1..=days));
+                ui.add(egui::DragValue::new(&mut self.sun_study.month).range(1..=12));
+            });
+            ui.horizontal(|ui| {
+                let [start, end] = &mut self.sun_study.hours;
+                ui.label(tr("Hours"));
+                ui.add(egui::DragValue::new(start).range(0.0..=24.0).speed(0.25).suffix(" h"));
+                ui.add(egui::DragValue::new(end).range(0.0..=24.0).speed(0.25).suffix(" h"));
+                ui.add(
+                    egui::DragValue::new(&mut self.sun_study.step_minutes)
+                        .range(5..=240)
+                        .suffix(" min"),
+                );
+            });
+            if self.sun_study.is_running() {
+                let (saved, total) = self.sun_study.progress();
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::ProgressBar::new(saved as f32 / total.max(1) as f32)
+                            .text(format!("{} / {}", saved, total)),
+                    );
+                    if ui.button(tr("Stop")).clicked() {
+                        self.sun_study.stop();
+                    }
+                });
+            } else if ui.button(tr("Export sun study")).clicked()
+                && let Err(error) = self.sun_study.start(&self.light)
+            {
+                self.toasts.push_back((format!("Sun study: {}", error), Instant::now()));
+            }
+        });
+    }
+}
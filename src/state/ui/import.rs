@@ -0,0 +1,73 @@
+use crate::{
+    dialog::open_file_dialog,
+    renderer::{ImportMode, ImportOptions, VertexPrecision},
+    state::State,
+};
+
+impl State {
+    pub(super) fn import_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        #[cfg(not(target_family = "wasm"))]
+        let mut exported_points = None;
+        if ui.button(tr("Load Asset")).clicked() {
+            open_file_dialog(self.loader.clone(), self.import_options.clone());
+        }
+        #[cfg(not(target_family = "wasm"))]
+        ui.horizontal(|ui| {
+            let pointcloud = self
+                .selected
+                .and_then(|entity_id| self.renderer.scene_query().pointcloud(entity_id));
+            if ui
+                .add_enabled(pointcloud.is_some(), egui::Button::new(tr("Export points")))
+                .on_disabled_hover_text(tr("Select a pointcloud first"))
+                .clicked()
+            {
+                exported_points = pointcloud;
+            }
+            ui.checkbox(&mut self.export_in_view, tr("Only points in view"));
+        });
+        #[cfg(not(target_family = "wasm"))]
+        if ui.button(tr("Open window")).clicked() {
+            self.is_viewport_requested = true;
+        }
+        ui.add(
+            egui::Slider::new(
+                &mut self.import_options.subdivision_levels,
+                0..=ImportOptions::MAX_SUBDIVISION_LEVELS,
+            )
+            .text(tr("Import subdivision")),
+        );
+        ui.checkbox(&mut self.import_options.compact_indices, tr("16-bit indices"));
+        ui.checkbox(&mut self.import_options.static_batching, tr("Batch small meshes"))
+            .on_hover_text(tr("Merges small parts sharing a material into one draw"));
+        ui.checkbox(&mut self.import_options.texture_atlas, tr("Pack textures into atlases"))
+            .on_hover_text(tr("Merges materials that only differ in their small textures"));
+        egui::ComboBox::from_label(tr("Import mode"))
+            .selected_text(tr(self.import_options.mode.to_str()))
+            .show_ui(ui, |ui| {
+                for mode in ImportMode::ALL {
+                    ui.selectable_value(&mut self.import_options.mode, mode, tr(mode.to_str()));
+                }
+            });
+        egui::ComboBox::from_label(tr("Vertex precision"))
+            .selected_text(self.import_options.vertex_precision.to_str())
+            .show_ui(ui, |ui| {
+                for precision in VertexPrecision::ALL {
+                    ui.selectable_value(&mut self.import_options.vertex_precision, precision, precision.to_str());
+                }
+            });
+        let mut max_loads = self.loader.max_concurrency();
+        if ui
+            .add(egui::Slider::new(&mut max_loads, 1..=16).text(tr("Concurrent loads")))
+            .changed()
+        {
+            self.loader.set_max_concurrency(max_loads);
+        }
+        ui.checkbox(&mut self.place_on_ground, tr("Place imports on the ground"));
+        #[cfg(not(target_family = "wasm"))]
+        if let Some((buffer, transform)) = exported_points {
+            self.export_points(buffer, transform);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use crate::{
+    entity::Entity,
+    explode::ExplodeView,
+    locale::Language,
+    placement::TransformEdit,
+    renderer::{AnimationPhase, RenderCommand, RenderLayers, TagFilter},
+    state::{State, ui::UiActions},
+    units::{ScaleReference, WorldUnit},
+};
+
+impl State {
+    pub(super) fn world_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        let has_reference_box = self.reference_render_id().is_some();
+        ui.collapsing(tr("World"), |ui| {
+            egui::ComboBox::from_label(tr("Units"))
+                .selected_text(self.world_unit.to_str())
+                .show_ui(ui, |ui| {
+                    for unit in WorldUnit::ALL {
+                        actions.is_unit_changed |=
+                            ui.selectable_value(&mut self.world_unit, unit, unit.to_str()).changed();
+                    }
+                });
+
+            // References are scaled copies of the bundled reference box
+            ui.horizontal(|ui| {
+                for reference in ScaleReference::ALL {
+                    if ui
+                        .add_enabled(has_reference_box, egui::Button::new(reference.to_str()))
+                        .clicked()
+                    {
+                        actions.spawned_reference = Some(reference);
+                    }
+                }
+            });
+        });
+    }
+
+    pub(super) fn selection_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Selection"), |ui| {
+            let selected = self
+                .selected
+                .and_then(|id| self.entities.get(&id))
+                .map(|entity| entity.label().clone().unwrap_or_else(|| entity.id().to_string()))
+                .unwrap_or_else(|| tr("None").to_string());
+            ui.label(format!("{}: {}", tr("Selected"), selected));
+
+            if let Some(entity) = self.selected.and_then(|id| self.entities.get(&id)) {
+                let (scale, rotation, translation) = entity.transform().to_scale_rotation_translation();
+                let (yaw, pitch, roll) = rotation.to_euler(glam::EulerRot::YXZ);
+                let (mut translation, mut angles) = match self.transform_edit.take() {
+                    Some(edit) if edit.entity_id == entity.id() => (edit.translation, edit.angles),
+                    _ => (translation, [yaw, pitch, roll].map(f32::to_degrees)),
+                };
+
+                let mut changed = false;
+                let mut is_editing = false;
+                let mut track = |response: egui::Response| {
+                    changed |= response.changed();
+                    is_editing |= response.dragged() || response.has_focus();
+                };
+                ui.horizontal(|ui| {
+                    ui.label(tr("Position"));
+                    for axis in 0..3 {
+                        track(ui.add(egui::DragValue::new(&mut translation[axis]).speed(0.05)));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("Rotation"));
+                    for angle in &mut angles {
+                        track(ui.add(egui::DragValue::new(angle).speed(1.0).suffix("°")));
+                    }
+                });
+
+                if is_editing {
+                    self.transform_edit = Some(TransformEdit {
+                        entity_id: entity.id(),
+                        translation,
+                        angles,
+                    });
+                }
+                if changed {
+                    let [yaw, pitch, roll] = angles.map(|angle| self.snapping.snap_angle(angle.to_radians()));
+                    let rotation = glam::Quat::from_euler(glam::EulerRot::YXZ, yaw, pitch, roll);
+                    let translation = self.snapping.snap_position(translation);
+                    actions.edited_transform = Some((
+                        entity.id(),
+                        glam::Mat4::from_scale_rotation_translation(scale, rotation, translation),
+                    ));
+                }
+
+                if let Some(metadata) = entity
+                    .render_id()
+                    .and_then(|render_id| self.node_metadata.get(&render_id))
+                    && (!metadata.materials.is_empty() || !metadata.extras.is_empty())
+                {
+                    egui::Grid::new("node_metadata").num_columns(2).show(ui, |ui| {
+                        if !metadata.materials.is_empty() {
+                            ui.label(tr("Materials"));
+                            ui.label(metadata.materials.join(", "));
+                            ui.end_row();
+                        }
+                        for (key, value) in &metadata.extras {
+                            ui.label(key);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+                }
+
+                // Switches every node of the asset, not only the selected one
+                if let Some(metadata) = entity
+                    .render_id()
+                    .and_then(|render_id| self.node_metadata.get(&render_id))
+                    && let Some(asset_id) = entity.asset_id()
+                    && !metadata.variants.is_empty()
+                {
+                    let active = self.material_variants.get(&asset_id).copied();
+                    let mut variant = active;
+                    let variant_name = |variant: Option<usize>| {
+                        variant
+                            .and_then(|variant| metadata.variants.get(variant))
+                            .map_or(tr("Default"), String::as_str)
+                    };
+                    egui::ComboBox::from_label(tr("Material variant"))
+                        .selected_text(variant_name(active))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut variant, None, tr("Default"));
+                            for (index, name) in metadata.variants.iter().enumerate() {
+                                ui.selectable_value(&mut variant, Some(index), name);
+                            }
+                        });
+                    if variant != active {
+                        actions.edited_variant = Some((asset_id, variant));
+                    }
+                }
+
+                if let Some(render_id) = entity.render_id()
+                    && let Some(duration) = self
+                        .node_metadata
+                        .get(&render_id)
+                        .and_then(|metadata| metadata.animation_duration)
+                {
+                    let mut phase = entity.animation_phase();
+                    let offset_changed = ui
+                        .add(
+                            egui::Slider::new(&mut phase.offset, 0.0..=duration)
+                                .text(tr("Animation offset"))
+                                .suffix(" s"),
+                        )
+                        .changed();
+                    let speed_changed = ui
+                        .add(egui::Slider::new(&mut phase.speed, -2.0..=2.0).text(tr("Animation speed")))
+                        .changed();
+                    if offset_changed || speed_changed {
+                        actions.edited_phases.push((entity.id(), phase));
+                    }
+                    // Every entity drawing the same node, like the copies of a scattered crowd
+                    if ui
+                        .button(tr("Stagger copies"))
+                        .on_hover_text(tr(
+                            "Starts every copy of this node at a random point in its clip, at a slightly \
+                             different speed",
+                        ))
+                        .clicked()
+                    {
+                        actions.edited_phases.extend(
+                            self.entities
+                                .iter()
+                                .filter(|copy| copy.render_id() == Some(render_id))
+                                .map(|copy| {
+                                    let phase = AnimationPhase {
+                                        offset: fastrand::f32() * duration,
+                                        speed: 0.9 + 0.2 * fastrand::f32(),
+                                    };
+                                    (copy.id(), phase)
+                                }),
+                        );
+                    }
+                }
+
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(tr("Tags"));
+                    for tag in entity.tags() {
+                        if ui.small_button(format!("{} ×", tag)).clicked() {
+                            let mut tags = entity.tags().clone();
+                            tags.remove(tag);
+                            actions.edited_tags = Some((entity.id(), tags));
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let response = ui.text_edit_singleline(&mut self.tag_input);
+                    let is_submitted = response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+                    let tag = self.tag_input.trim();
+                    if (ui.button(tr("Add tag")).clicked() || is_submitted) && !tag.is_empty() {
+                        let mut tags = entity.tags().clone();
+                        tags.insert(tag.to_string());
+                        actions.edited_tags = Some((entity.id(), tags));
+                        self.tag_input.clear();
+                    }
+                });
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.selected.is_some(), egui::Button::new(tr("Clear")))
+                    .clicked()
+                {
+                    actions.clear_selection = true;
+                }
+                let asset_id = self
+                    .selected
+                    .and_then(|id| self.entities.get(&id))
+                    .and_then(Entity::asset_id);
+                if ui
+                    .add_enabled(asset_id.is_some(), egui::Button::new(tr("Unload asset")))
+                    .on_hover_text(tr("Removes every entity of the selected asset and frees its memory"))
+                    .clicked()
+                {
+                    actions.unloaded_asset = asset_id;
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.selected.is_some(), egui::Button::new(tr("Isolate")))
+                    .clicked()
+                {
+                    actions.isolated = self.selected.map(|entity_id| vec![entity_id]);
+                }
+                if ui
+                    .add_enabled(self.isolation.is_active(), egui::Button::new(tr("Show all")))
+                    .clicked()
+                {
+                    actions.is_restore_requested = true;
+                }
+                ui.checkbox(&mut self.isolation.ghost_others, tr("Ghost others"));
+            });
+            let tags = self.entities.tags();
+            let mut hovered_tag = None;
+            if !tags.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(tr("Isolate tag"));
+                    for tag in tags {
+                        let response = ui.small_button(tag);
+                        if response.hovered() {
+                            hovered_tag = Some(tag.to_string());
+                        }
+                        if response.clicked() {
+                            actions.isolated = Some(self.entities.find_by_tag(tag).map(Entity::id).collect());
+                        }
+                    }
+                });
+            }
+            if hovered_tag != self.hovered_tag {
+                let filter = hovered_tag.clone().map(|tag| TagFilter {
+                    label: None,
+                    tag: Some(tag),
+                });
+                self.renderer.send_command(RenderCommand::SetHighlight(filter)).unwrap();
+                self.hovered_tag = hovered_tag;
+            }
+            ui.label(tr("I to isolate the selection, Escape to show everything again"));
+
+            let asset_id = self
+                .selected
+                .and_then(|id| self.entities.get(&id))
+                .and_then(Entity::asset_id);
+            let is_assembly = asset_id.is_some_and(|asset_id| {
+                self.entities
+                    .iter()
+                    .filter(|entity| entity.asset_id() == Some(asset_id))
+                    .count()
+                    > 1
+            });
+            ui.add_enabled_ui(is_assembly, |ui| {
+                actions.is_explode_changed = ui
+                    .add(egui::Slider::new(&mut self.explode.factor, 0.0..=ExplodeView::MAX_FACTOR).text(tr("Explode")))
+                    .changed();
+            });
+
+            ui.horizontal(|ui| {
+                ui.label(tr("Duplicate offset"));
+                for axis in 0..3 {
+                    ui.add(egui::DragValue::new(&mut self.clipboard.offset[axis]).speed(0.1));
+                }
+            });
+            ui.label(tr("Ctrl+C / Ctrl+V to copy and paste, Ctrl+D to duplicate"));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.snapping.grid, tr("Grid snap"));
+                ui.add(
+                    egui::DragValue::new(&mut self.snapping.grid_step)
+                        .range(0.01..=100.0)
+                        .speed(0.05),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.snapping.angle, tr("Angle snap"));
+                ui.add(
+                    egui::DragValue::new(&mut self.snapping.angle_step)
+                        .range(1.0..=180.0)
+                        .suffix("°"),
+                );
+            });
+            ui.checkbox(&mut self.snapping.surface, tr("Surface snap"));
+            ui.label(tr(
+                "G to move with the cursor, left click to place, right click to cancel",
+            ));
+        });
+    }
+
+    pub(super) fn prefabs_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Prefabs"), |ui| {
+            if let Some(name) = self.selected.and_then(|id| self.prefabs.instance_of(id)) {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}: {}", tr("Instance of"), name));
+                    actions.is_prefab_applied = ui.button(tr("Apply to prefab")).clicked();
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut self.prefab_name);
+                let name = self.prefab_name.trim();
+                let is_valid = self.selected.is_some() && !name.is_empty() && !name.contains(['/', '\\', '.']);
+                if ui
+                    .add_enabled(is_valid, egui::Button::new(tr("Save as prefab")))
+                    .clicked()
+                {
+                    actions.saved_prefab = Some(name.to_string());
+                }
+            });
+            ui.label(tr("Saves the selection's instance or assembly, lights included"));
+
+            ui.horizontal_wrapped(|ui| {
+                ui.label(tr("Place"));
+                for name in self.prefabs.names() {
+                    if ui.small_button(name).clicked() {
+                        actions.placed_prefab = Some(name.to_string());
+                    }
+                }
+            });
+        });
+    }
+
+    pub(super) fn render_layers_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Render layers"), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("Main window"));
+                if layer_checkboxes(ui, &mut self.view_layers, language) {
+                    self.renderer
+                        .send_command(RenderCommand::SetViewLayers {
+                            viewport: None,
+                            layers: self.view_layers,
+                        })
+                        .unwrap();
+                }
+            });
+            for viewport in &mut self.viewports {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} {}", tr("Window"), viewport.id().0));
+                    if layer_checkboxes(ui, &mut viewport.layers, language) {
+                        self.renderer
+                            .send_command(RenderCommand::SetViewLayers {
+                                viewport: Some(viewport.id()),
+                                layers: viewport.layers,
+                            })
+                            .unwrap();
+                    }
+                });
+            }
+            if let Some(entity) = self.selected.and_then(|id| self.entities.get_mut(&id)) {
+                ui.horizontal(|ui| {
+                    ui.label(tr("Selected"));
+                    let mut layers = entity.layers();
+                    if layer_checkboxes(ui, &mut layers, language) {
+                        entity.set_layers(layers);
+                        self.renderer
+                            .send_command(RenderCommand::SetEntityLayers {
+                                entity_id: entity.id(),
+                                layers,
+                            })
+                            .unwrap();
+                    }
+                });
+            }
+        });
+    }
+}
+
+// A checkbox per named layer, returns whether any of them changed
+fn layer_checkboxes(ui: &mut egui::Ui, layers: &mut RenderLayers, language: Language) -> bool {
+    let mut changed = false;
+    for (index, name) in RenderLayers::NAMES.into_iter().enumerate() {
+        let mut is_enabled = layers.contains_layer(index);
+        if ui.checkbox(&mut is_enabled, language.tr(name)).changed() {
+            layers.set_layer(index, is_enabled);
+            changed = true;
+        }
+    }
+    changed
+}
@@ -0,0 +1,221 @@
+use crate::{
+    locale::Language,
+    renderer::{BackgroundMode, InspectedBuffer, RenderCommand, UiStyle, UiTheme},
+    state::{State, ui::UiActions},
+};
+
+impl State {
+    pub(super) fn status_bar(&mut self, ctx: &egui::Context) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        let adapter = format!(
+            "{} ({})",
+            self.renderer.adapter_info().name,
+            self.renderer.adapter_info().backend.to_str()
+        );
+        let (triangles, points) = self
+            .entities
+            .iter()
+            .filter_map(|entity| self.asset_stats.get(&entity.render_id()?))
+            .fold((0, 0), |(triangles, points), stats| {
+                (triangles + stats.triangles as u64, points + stats.points as u64)
+            });
+
+        // Background work on the left, totals and the adapter on the right
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for task in self.loader.tasks().snapshot() {
+                    ui.spinner();
+                    ui.label(format!("{} ({:.0} s)", task.label, task.elapsed.as_secs_f32()));
+                    if ui.small_button("✕").on_hover_text(tr("Cancel")).clicked() {
+                        self.loader.tasks().cancel(task.id);
+                    }
+                    ui.separator();
+                }
+
+                let mut streams = self.load_progress.iter().collect::<Vec<_>>();
+                streams.sort_by(|a, b| a.0.cmp(b.0));
+                for (label, percent) in streams {
+                    ui.add(
+                        egui::ProgressBar::new(percent / 100.0)
+                            .desired_width(160.0)
+                            .text(format!("{} {:.0}%", label, percent)),
+                    );
+                    ui.separator();
+                }
+
+                if let Some((entity_id, samples, total)) = self.lightmap_progress
+                    && samples < total
+                {
+                    ui.spinner();
+                    ui.label(format!("{} {} / {}", tr("Baking lightmap"), samples, total));
+                    if ui.small_button("✕").on_hover_text(tr("Cancel")).clicked() {
+                        self.renderer
+                            .send_command(RenderCommand::ClearLightmap(entity_id))
+                            .unwrap();
+                        self.lightmap_progress = None;
+                    }
+                }
+
+                if self.queued_loads > 0 {
+                    ui.label(format!("{} {}", self.queued_loads, tr("loads waiting for GPU memory")));
+                    if ui.small_button(tr("Load anyway")).clicked() {
+                        self.renderer.send_command(RenderCommand::ForceQueuedLoads).unwrap();
+                    }
+                    if ui.small_button(tr("Discard")).clicked() {
+                        self.renderer.send_command(RenderCommand::DiscardQueuedLoads).unwrap();
+                    }
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.label(adapter);
+                    ui.separator();
+                    let memory = format!(
+                        "{} / {} MiB",
+                        self.memory_usage.allocated_mib(),
+                        self.memory_usage.budget_mib()
+                    );
+                    if self.memory_usage.is_near_limit() {
+                        ui.colored_label(ui.visuals().warn_fg_color, memory);
+                    } else {
+                        ui.label(memory);
+                    }
+                    ui.separator();
+                    ui.label(format!(
+                        "{} {}, {} {}, {} {}",
+                        self.entities.len(),
+                        tr("entities"),
+                        triangles,
+                        tr("triangles"),
+                        points,
+                        tr("points")
+                    ));
+                });
+            });
+        });
+    }
+
+    pub(super) fn interface_panel(&mut self, ui: &mut egui::Ui, average_fps: f32, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.label(format!("FPS: {}", average_fps));
+        ui.checkbox(&mut self.profiler.is_open, tr("Profiler"));
+        ui.checkbox(&mut self.preview.is_open, tr("Preview"));
+        ui.checkbox(&mut self.frame_graph.is_open, tr("Frame graph"));
+        if ui
+            .checkbox(&mut self.render_settings.show_tooltips, tr("Hover tooltips"))
+            .changed()
+        {
+            self.settings_file.save(&self.render_settings);
+        }
+        if ui
+            .checkbox(&mut self.render_settings.render_on_demand, tr("Render on demand"))
+            .on_hover_text(tr("Only draws after a change"))
+            .changed()
+        {
+            self.settings_file.save(&self.render_settings);
+        }
+        egui::ComboBox::from_label(tr("In the background"))
+            .selected_text(tr(self.render_settings.background_mode.to_str()))
+            .show_ui(ui, |ui| {
+                for mode in BackgroundMode::ALL {
+                    if ui
+                        .selectable_value(&mut self.render_settings.background_mode, mode, tr(mode.to_str()))
+                        .changed()
+                    {
+                        self.settings_file.save(&self.render_settings);
+                    }
+                }
+            });
+        egui::ComboBox::from_label(tr("Language"))
+            .selected_text(self.render_settings.language.to_str())
+            .show_ui(ui, |ui| {
+                for option in Language::ALL {
+                    if ui
+                        .selectable_value(&mut self.render_settings.language, option, option.to_str())
+                        .changed()
+                    {
+                        self.settings_file.save(&self.render_settings);
+                    }
+                }
+            });
+        ui.collapsing(tr("Interface"), |ui| {
+            let style = &mut self.render_settings.ui_style;
+            let mut style_changed = false;
+            egui::ComboBox::from_label(tr("Theme"))
+                .selected_text(tr(style.theme.to_str()))
+                .show_ui(ui, |ui| {
+                    for theme in UiTheme::ALL {
+                        style_changed |= ui
+                            .selectable_value(&mut style.theme, theme, tr(theme.to_str()))
+                            .changed();
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                let mut has_accent = style.accent.is_some();
+                if ui.checkbox(&mut has_accent, tr("Custom accent")).changed() {
+                    style.accent = has_accent.then_some([255, 140, 0]);
+                    style_changed = true;
+                }
+                if let Some(accent) = &mut style.accent {
+                    style_changed |= ui.color_edit_button_srgb(accent).changed();
+                }
+            });
+
+            // Applied when the drag ends, scaling mid-drag moves the slider away from the cursor
+            let response = ui.add(
+                egui::Slider::new(&mut style.scale, UiStyle::MIN_SCALE..=UiStyle::MAX_SCALE)
+                    .step_by(0.05)
+                    .text(tr("UI scale")),
+            );
+            style_changed |= response.drag_stopped() || (response.changed() && !response.dragged());
+
+            if style_changed {
+                actions.is_style_changed = true;
+                self.settings_file.save(&self.render_settings);
+            }
+        });
+        if ui
+            .checkbox(
+                &mut self.render_settings.transparent_background,
+                tr("Transparent background"),
+            )
+            .changed()
+        {
+            self.renderer
+                .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                .unwrap();
+            self.settings_file.save(&self.render_settings);
+        }
+    }
+
+    pub(super) fn buffer_inspector_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Buffer inspector"), |ui| {
+            egui::ComboBox::from_label(tr("Buffer"))
+                .selected_text(self.inspected_buffer.to_str())
+                .show_ui(ui, |ui| {
+                    for buffer in InspectedBuffer::ALL {
+                        ui.selectable_value(&mut self.inspected_buffer, buffer, buffer.to_str());
+                    }
+                });
+
+            if ui.button(tr("Read back")).clicked() {
+                self.renderer
+                    .send_command(RenderCommand::InspectBuffer(self.inspected_buffer))
+                    .unwrap();
+            }
+
+            if let Some((buffer, entries)) = &self.buffer_contents {
+                ui.label(format!("{}: {} entries", buffer.to_str(), entries.len()));
+                egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                    for (index, entry) in entries.iter().enumerate() {
+                        ui.monospace(format!("{:>4}: {}", index, entry));
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{
+    entity::EntityId,
+    locale::Language,
+    renderer::{IrradianceGrid, Light, LightKind, MAX_LIGHTMAP_RESOLUTION, MAX_PROBES, RenderCommand},
+    state::State,
+};
+
+impl State {
+    pub(super) fn light_panel(&mut self, ui: &mut egui::Ui, light_id: EntityId) {
+        if light_controls(ui, &mut self.light, self.render_settings.language)
+            && let Some(light) = self.entities.get_mut(&light_id)
+        {
+            // Starts from wherever the light was moved to since the last edit
+            self.light.set_transform(light.transform());
+            light.set_transform(self.light.to_transform());
+            self.renderer
+                .send_command(RenderCommand::UpdateLight {
+                    entity_id: light_id,
+                    light: self.light.clone(),
+                })
+                .unwrap();
+        }
+    }
+
+    // Captures are not updated automatically, recapture after changing the scene around a probe
+    pub(super) fn probes_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Reflection probes"), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("Radius"));
+                ui.add(
+                    egui::DragValue::new(&mut self.probe_radius)
+                        .range(0.1..=1000.0)
+                        .speed(0.1),
+                );
+                if ui
+                    .add_enabled(self.probes.len() < MAX_PROBES, egui::Button::new(tr("Place at camera")))
+                    .clicked()
+                {
+                    let probe = (
+                        self.renderer.client().allocate_id(),
+                        self.camera.position(),
+                        self.probe_radius,
+                    );
+                    self.renderer
+                        .send_command(RenderCommand::PlaceProbe {
+                            probe_id: probe.0,
+                            position: probe.1,
+                            radius: probe.2,
+                        })
+                        .unwrap();
+                    self.probes.push(probe);
+                }
+            });
+
+            let mut removed = None;
+            for (index, (probe_id, position, radius)) in self.probes.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Probe {} at ({:.1}, {:.1}, {:.1})",
+                        index + 1,
+                        position.x,
+                        position.y,
+                        position.z
+                    ));
+                    let response = ui.add(egui::DragValue::new(radius).range(0.1..=1000.0).speed(0.1));
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        self.renderer
+                            .send_command(RenderCommand::PlaceProbe {
+                                probe_id: *probe_id,
+                                position: *position,
+                                radius: *radius,
+                            })
+                            .unwrap();
+                    }
+                    if ui.button(tr("Remove")).clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+
+            if let Some(index) = removed {
+                let (probe_id, _, _) = self.probes.remove(index);
+                self.renderer
+                    .send_command(RenderCommand::RemoveProbe(probe_id))
+                    .unwrap();
+            }
+
+            if ui
+                .add_enabled(!self.probes.is_empty(), egui::Button::new(tr("Recapture all")))
+                .clicked()
+            {
+                self.renderer.send_command(RenderCommand::CaptureProbes).unwrap();
+            }
+        });
+    }
+
+    // Edits to the grid re-bake right away, the button re-bakes after the scene changed
+    pub(super) fn irradiance_volume_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Irradiance volume"), |ui| {
+            let mut is_grid_changed = ui.checkbox(&mut self.has_irradiance_volume, tr("Enabled")).changed();
+            ui.add_enabled_ui(self.has_irradiance_volume, |ui| {
+                let grid = &mut self.irradiance_grid;
+                for (label, corner) in [("Min", &mut grid.min), ("Max", &mut grid.max)] {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        for value in [&mut corner.x, &mut corner.y, &mut corner.z] {
+                            let response = ui.add(egui::DragValue::new(value).speed(0.1));
+                            is_grid_changed |= response.drag_stopped() || (response.changed() && !response.dragged());
+                        }
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    ui.label(tr("Probes"));
+                    let resolution = &mut grid.resolution;
+                    for value in [&mut resolution.x, &mut resolution.y, &mut resolution.z] {
+                        let response = ui.add(egui::DragValue::new(value).range(2..=IrradianceGrid::MAX_RESOLUTION));
+                        is_grid_changed |= response.drag_stopped() || (response.changed() && !response.dragged());
+                    }
+                });
+
+                if ui.button(tr("Re-bake")).clicked() {
+                    self.renderer.send_command(RenderCommand::BakeIrradianceVolume).unwrap();
+                }
+            });
+
+            if is_grid_changed {
+                let grid = self.has_irradiance_volume.then_some(self.irradiance_grid);
+                self.renderer
+                    .send_command(RenderCommand::SetIrradianceVolume(grid))
+                    .unwrap();
+            }
+
+            if ui
+                .checkbox(&mut self.render_settings.show_irradiance_probes, tr("Show probes"))
+                .changed()
+            {
+                self.renderer
+                    .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                    .unwrap();
+                self.settings_file.save(&self.render_settings);
+            }
+        });
+    }
+
+    // Bakes are static, re-bake after moving the mesh or the lights around it
+    pub(super) fn lightmap_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Lightmap"), |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("Resolution"));
+                ui.add(egui::DragValue::new(&mut self.lightmap_resolution).range(16..=MAX_LIGHTMAP_RESOLUTION));
+                ui.label(tr("Samples"));
+                ui.add(egui::DragValue::new(&mut self.lightmap_samples).range(1..=16384));
+            });
+
+            let mesh = self
+                .selected
+                .filter(|id| self.entities.get(id).is_some_and(|entity| entity.render_id().is_some()));
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(mesh.is_some(), egui::Button::new(tr("Bake selected")))
+                    .clicked()
+                    && let Some(entity_id) = mesh
+                {
+                    self.renderer
+                        .send_command(RenderCommand::BakeLightmap {
+                            entity_id,
+                            resolution: self.lightmap_resolution,
+                            samples: self.lightmap_samples,
+                        })
+                        .unwrap();
+                }
+                if ui.add_enabled(mesh.is_some(), egui::Button::new(tr("Clear"))).clicked()
+                    && let Some(entity_id) = mesh
+                {
+                    self.renderer
+                        .send_command(RenderCommand::ClearLightmap(entity_id))
+                        .unwrap();
+                    if self.lightmap_progress.is_some_and(|(id, _, _)| id == entity_id) {
+                        self.lightmap_progress = None;
+                    }
+                }
+            });
+
+            if let Some((entity_id, samples, total)) = self.lightmap_progress {
+                let label = self
+                    .entities
+                    .get(&entity_id)
+                    .and_then(|entity| entity.label().clone())
+                    .unwrap_or_else(|| entity_id.to_string());
+                ui.label(label);
+                ui.add(
+                    egui::ProgressBar::new(samples as f32 / total as f32)
+                        .text(format!("{} / {} samples", samples, total)),
+                );
+            }
+        });
+    }
+}
+
+// Edits every parameter of the light, returns whether any of them changed
+fn light_controls(ui: &mut egui::Ui, light: &mut Light, language: Language) -> bool {
+    let tr = |text: &'static str| language.tr(text);
+    let mut changed = false;
+    let mut kind = light.kind();
+    egui::ComboBox::from_label(tr("Light type"))
+        .selected_text(tr(kind.to_str()))
+        .show_ui(ui, |ui| {
+            for option in LightKind::ALL {
+                ui.selectable_value(&mut kind, option, tr(option.to_str()));
+            }
+        });
+    if kind != light.kind() {
+        *light = light.with_kind(kind);
+        changed = true;
+    }
+
+    let (Light::Directional {
+        color,
+        intensity,
+        casts_shadows,
+        ..
+    }
+    | Light::Point {
+        color,
+        intensity,
+        casts_shadows,
+        ..
+    }
+    | Light::Spot {
+        color,
+        intensity,
+        casts_shadows,
+        ..
+    }) = light;
+
+    ui.label(tr("Light color"));
+    let mut rgb = color.to_array();
+    if ui.color_edit_button_rgb(&mut rgb).changed() {
+        *color = glam::Vec3::from_array(rgb);
+        changed = true;
+    }
+    ui.label(tr("Intensity"));
+    changed |= ui.add(egui::Slider::new(intensity, 0.0..=255.0)).changed();
+    changed |= ui.checkbox(casts_shadows, tr("Casts shadows")).changed();
+
+    if let Light::Point { range, .. } | Light::Spot { range, .. } = light {
+        changed |= ui
+            .add(egui::Slider::new(range, 0.0..=100.0).text(tr("Range")))
+            .on_hover_text(tr("Zero never cuts the light off"))
+            .changed();
+    }
+
+    // Shown as degrees, the inner cone can't grow past the outer one
+    if let Light::Spot {
+        inner_cutoff,
+        outer_cutoff,
+        ..
+    } = light
+    {
+        let mut inner = inner_cutoff.to_degrees();
+        let mut outer = outer_cutoff.to_degrees();
+        let inner_changed = ui
+            .add(egui::Slider::new(&mut inner, 0.0..=89.0).text(tr("Inner cone")))
+            .changed();
+        let outer_changed = ui
+            .add(egui::Slider::new(&mut outer, 0.0..=89.0).text(tr("Outer cone")))
+            .changed();
+        if inner_changed || outer_changed {
+            *inner_cutoff = inner.min(outer).to_radians();
+            *outer_cutoff = outer.max(inner).to_radians();
+            changed = true;
+        }
+    }
+
+    changed
+}
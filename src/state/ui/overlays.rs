@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use crate::{
+    renderer::PointcloudShading,
+    state::{State, classification_name},
+};
+
+impl State {
+    pub(super) fn import_report_window(&mut self, ctx: &egui::Context) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        if !self.import_reports.is_empty() {
+            let mut is_open = true;
+            egui::Window::new(tr("Import report"))
+                .id(egui::Id::new("import_report_window"))
+                .open(&mut is_open)
+                .resizable(true)
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for (label, report) in &self.import_reports {
+                            let title = format!("{} ({} warnings)", label, report.warnings().len());
+                            ui.collapsing(title, |ui| {
+                                for warning in report.warnings() {
+                                    ui.label(warning.to_string());
+                                }
+                            });
+                        }
+                    });
+                });
+
+            if !is_open {
+                self.import_reports.clear();
+            }
+        }
+    }
+
+    pub(super) fn point_window(&mut self, ctx: &egui::Context) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        if let Some(point) = self.picked_point {
+            let mut is_open = true;
+            egui::Window::new(tr("Point"))
+                .id(egui::Id::new("point_window"))
+                .open(&mut is_open)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("point_attributes").num_columns(2).show(ui, |ui| {
+                        let attributes = point.attributes;
+                        let rows = [
+                            (tr("Index"), point.index.to_string()),
+                            (tr("Position"), format!("{:.3}", point.position)),
+                            (tr("Source position"), format!("{:.3}", point.source_position)),
+                            (tr("Color"), format!("{:.3}", point.color)),
+                            (tr("Intensity"), format!("{:.3}", point.intensity)),
+                            (
+                                tr("Classification"),
+                                format!(
+                                    "{} ({})",
+                                    attributes.classification,
+                                    tr(classification_name(attributes.classification))
+                                ),
+                            ),
+                            (
+                                tr("Return"),
+                                format!("{} / {}", attributes.return_number, attributes.number_of_returns),
+                            ),
+                            (
+                                tr("GPS time"),
+                                attributes
+                                    .gps_time()
+                                    .map_or_else(|| tr("None").to_string(), |time| format!("{:.6}", time)),
+                            ),
+                        ];
+
+                        for (label, value) in rows {
+                            ui.label(label);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+                });
+
+            if !is_open {
+                self.picked_point = None;
+            }
+        }
+    }
+
+    // Renderer errors stay on screen for a while, the browser console is easy to miss
+    pub(super) fn show_toasts(&mut self, ctx: &egui::Context) {
+        self.toasts
+            .retain(|(_, timestamp)| timestamp.elapsed() < Duration::from_secs(8));
+        while self.toasts.len() > 5 {
+            self.toasts.pop_front();
+        }
+
+        if !self.toasts.is_empty() {
+            egui::Area::new(egui::Id::new("toasts"))
+                .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+                .show(ctx, |ui| {
+                    for (message, _) in &self.toasts {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.set_max_width(400.0);
+                            ui.colored_label(ui.visuals().error_fg_color, message);
+                        });
+                    }
+                });
+        }
+    }
+
+    // Hidden while the pointer is over a window or dragging the camera
+    pub(super) fn hover_tooltip(&self, ctx: &egui::Context, hovered: Option<Vec<(&'static str, String)>>) {
+        if let Some(details) = hovered
+            && let Some(position) = ctx.pointer_hover_pos()
+            && !ctx.is_pointer_over_area()
+            && self.press_position.is_none()
+        {
+            egui::Area::new(egui::Id::new("hover_tooltip"))
+                .fixed_pos(position + egui::vec2(16.0, 16.0))
+                .order(egui::Order::Tooltip)
+                .interactable(false)
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        egui::Grid::new("hover_tooltip_grid").num_columns(2).show(ui, |ui| {
+                            for (name, value) in details {
+                                ui.label(name);
+                                ui.label(value);
+                                ui.end_row();
+                            }
+                        });
+                    });
+                });
+        }
+    }
+
+    pub(super) fn color_ramp_legend(&self, ctx: &egui::Context) {
+        let shows_ramp = self.render_settings.pointcloud_shading == PointcloudShading::Scalar
+            || self.transfer_function.use_color_ramp;
+        if shows_ramp {
+            egui::Area::new(egui::Id::new("color_ramp_legend"))
+                .anchor(egui::Align2::LEFT_BOTTOM, egui::vec2(10.0, -10.0))
+                .show(ctx, |ui| {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        const SEGMENTS: usize = 64;
+                        let (rect, _) = ui.allocate_exact_size(egui::vec2(200.0, 16.0), egui::Sense::hover());
+                        let width = rect.width() / SEGMENTS as f32;
+                        for segment in 0..SEGMENTS {
+                            let value = (segment as f32 + 0.5) / SEGMENTS as f32;
+                            let [r, g, b] = self
+                                .render_settings
+                                .color_ramp
+                                .sample(value, &self.render_settings.custom_ramp)
+                                .map(|channel| (channel * 255.0) as u8);
+                            let min = rect.min + egui::vec2(segment as f32 * width, 0.0);
+                            ui.painter().rect_filled(
+                                egui::Rect::from_min_size(min, egui::vec2(width + 0.5, rect.height())),
+                                0.0,
+                                egui::Color32::from_rgb(r, g, b),
+                            );
+                        }
+
+                        let [min, max] = self.render_settings.ramp_range;
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{:.2}", min));
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                ui.label(format!("{:.2}", max));
+                            });
+                        });
+                    });
+                });
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use crate::{
+    renderer::{
+        ColorRamp, EnvironmentSampling, ParallaxQuality, PhysicalCamera, PointcloudShading, QualityPreset, RampStop,
+        RenderCommand, RenderMode, RenderSettings, TransferFunction, TransferPoint,
+    },
+    state::State,
+};
+
+impl State {
+    pub(super) fn quality_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        // The path traced view is a reference for the raster pipeline
+        let mut settings_changed = false;
+        egui::ComboBox::from_label(tr("Render mode"))
+            .selected_text(self.render_settings.render_mode.to_str())
+            .show_ui(ui, |ui| {
+                for mode in RenderMode::ALL {
+                    settings_changed |= ui
+                        .selectable_value(&mut self.render_settings.render_mode, mode, mode.to_str())
+                        .changed();
+                }
+            });
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_label(tr("Quality"))
+                .selected_text(
+                    self.render_settings
+                        .quality
+                        .map_or(tr("Custom"), |preset| preset.to_str()),
+                )
+                .show_ui(ui, |ui| {
+                    for preset in QualityPreset::ALL {
+                        let is_selected = self.render_settings.quality == Some(preset);
+                        if ui.selectable_label(is_selected, preset.to_str()).clicked() {
+                            preset.apply(&mut self.render_settings);
+                            self.render_settings.auto_quality = false;
+                            settings_changed = true;
+                        }
+                    }
+                });
+
+            if ui
+                .checkbox(&mut self.render_settings.auto_quality, tr("Auto"))
+                .changed()
+            {
+                self.auto_quality.reset();
+                settings_changed = true;
+            }
+        });
+
+        // Tuning any of the bundled settings by hand leaves the preset behind
+        let mut quality_changed = false;
+        if self.render_settings.render_mode == RenderMode::PathTraced {
+            ui.label(tr("Max bounces"));
+            quality_changed |= ui
+                .add(egui::Slider::new(
+                    &mut self.render_settings.max_bounces,
+                    1..=RenderSettings::MAX_BOUNCES,
+                ))
+                .changed();
+        }
+
+        ui.label(tr("Displacement scale"));
+        settings_changed |= ui
+            .add(egui::Slider::new(
+                &mut self.render_settings.displacement_scale,
+                0.0..=4.0,
+            ))
+            .changed();
+
+        egui::ComboBox::from_label(tr("Parallax quality"))
+            .selected_text(self.render_settings.parallax_quality.to_str())
+            .show_ui(ui, |ui| {
+                for quality in ParallaxQuality::ALL {
+                    quality_changed |= ui
+                        .selectable_value(&mut self.render_settings.parallax_quality, quality, quality.to_str())
+                        .changed();
+                }
+            });
+
+        // Stochastic sampling converges over frames while the camera is static
+        egui::ComboBox::from_label(tr("Environment lighting"))
+            .selected_text(self.render_settings.environment_sampling.to_str())
+            .show_ui(ui, |ui| {
+                for sampling in EnvironmentSampling::ALL {
+                    quality_changed |= ui
+                        .selectable_value(
+                            &mut self.render_settings.environment_sampling,
+                            sampling,
+                            sampling.to_str(),
+                        )
+                        .changed();
+                }
+            });
+
+        if self.render_settings.environment_sampling == EnvironmentSampling::Stochastic {
+            ui.label(tr("Environment samples"));
+            quality_changed |= ui
+                .add(egui::Slider::new(
+                    &mut self.render_settings.environment_samples,
+                    1..=RenderSettings::MAX_ENVIRONMENT_SAMPLES,
+                ))
+                .changed();
+        }
+
+        egui::ComboBox::from_label(tr("Shadow resolution"))
+            .selected_text(self.render_settings.shadow_map_size.to_string())
+            .show_ui(ui, |ui| {
+                for size in RenderSettings::SHADOW_MAP_SIZES {
+                    quality_changed |= ui
+                        .selectable_value(&mut self.render_settings.shadow_map_size, size, size.to_string())
+                        .changed();
+                }
+            });
+
+        egui::ComboBox::from_label(tr("Pointcloud shading"))
+            .selected_text(self.render_settings.pointcloud_shading.to_str())
+            .show_ui(ui, |ui| {
+                for shading in PointcloudShading::ALL {
+                    settings_changed |= ui
+                        .selectable_value(&mut self.render_settings.pointcloud_shading, shading, shading.to_str())
+                        .changed();
+                }
+            });
+        quality_changed |= ui
+            .add(
+                egui::Slider::new(&mut self.render_settings.points_per_frame, 0..=20_000_000)
+                    .logarithmic(true)
+                    .text(tr("Points per frame")),
+            )
+            .on_hover_text(tr("The rest fills in while still"))
+            .changed();
+
+        if quality_changed {
+            self.render_settings.quality = None;
+            self.render_settings.auto_quality = false;
+            settings_changed = true;
+        }
+
+        egui::ComboBox::from_label(tr("Color ramp"))
+            .selected_text(self.render_settings.color_ramp.to_str())
+            .show_ui(ui, |ui| {
+                for ramp in ColorRamp::ALL {
+                    settings_changed |= ui
+                        .selectable_value(&mut self.render_settings.color_ramp, ramp, ramp.to_str())
+                        .changed();
+                }
+            });
+
+        if self.render_settings.color_ramp == ColorRamp::Custom {
+            let mut removed = None;
+            for (index, stop) in self.render_settings.custom_ramp.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    settings_changed |= ui
+                        .add(egui::DragValue::new(&mut stop.position).range(0.0..=1.0).speed(0.005))
+                        .changed();
+                    settings_changed |= ui.color_edit_button_rgb(&mut stop.color).changed();
+                    if ui.button(tr("Remove")).clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+
+            if let Some(index) = removed {
+                self.render_settings.custom_ramp.remove(index);
+                settings_changed = true;
+            }
+
+            if ui.button(tr("Add stop")).clicked() {
+                self.render_settings.custom_ramp.push(RampStop {
+                    position: 0.5,
+                    color: [1.0, 1.0, 1.0],
+                });
+                settings_changed = true;
+            }
+        }
+
+        ui.horizontal(|ui| {
+            let [min, max] = &mut self.render_settings.ramp_range;
+            ui.label(tr("Ramp range"));
+            settings_changed |= ui
+                .add(egui::DragValue::new(min).range(0.0..=1.0).speed(0.005))
+                .changed();
+            settings_changed |= ui
+                .add(egui::DragValue::new(max).range(0.0..=1.0).speed(0.005))
+                .changed();
+        });
+
+        settings_changed |= ui
+            .checkbox(&mut self.render_settings.frustum_culling, tr("Frustum culling"))
+            .on_hover_text(tr("Skips meshes outside the view"))
+            .changed();
+        settings_changed |= ui
+            .checkbox(&mut self.render_settings.animated_picking, tr("Pick animated meshes"))
+            .on_hover_text(tr("Selects and measures skinned meshes in the pose they're drawn in"))
+            .changed();
+
+        // Exposure is relative to ISO 100, 1/60 s at f/8, the unchanged image
+        let field_of_view = self.projection.fov_y().to_degrees();
+        ui.collapsing(tr("Camera"), |ui| {
+            let camera = &mut self.render_settings.physical_camera;
+            settings_changed |= ui.checkbox(&mut camera.enabled, tr("Physical camera")).changed();
+            ui.add_enabled_ui(camera.enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("Focal length"));
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut camera.focal_length)
+                                .range(4.0..=800.0)
+                                .speed(0.5)
+                                .suffix(" mm"),
+                        )
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("Sensor width"));
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut camera.sensor_width)
+                                .range(1.0..=100.0)
+                                .speed(0.1)
+                                .suffix(" mm"),
+                        )
+                        .changed();
+                    egui::ComboBox::from_id_salt("sensor_preset")
+                        .selected_text(tr("Sensor"))
+                        .show_ui(ui, |ui| {
+                            for (name, width) in PhysicalCamera::SENSORS {
+                                if ui.selectable_label(camera.sensor_width == width, tr(name)).clicked() {
+                                    camera.sensor_width = width;
+                                    settings_changed = true;
+                                }
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("ISO"));
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut camera.iso)
+                                .range(25.0..=102_400.0)
+                                .speed(10.0),
+                        )
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    // Edited as the denominator photographers use
+                    let mut shutter = 1.0 / camera.shutter_speed;
+                    ui.label(tr("Shutter speed"));
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut shutter)
+                                .range(0.5..=8000.0)
+                                .speed(1.0)
+                                .prefix("1/")
+                                .suffix(" s"),
+                        )
+                        .changed()
+                    {
+                        camera.shutter_speed = 1.0 / shutter;
+                        settings_changed = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(tr("Aperture"));
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut camera.aperture)
+                                .range(1.0..=32.0)
+                                .speed(0.1)
+                                .prefix("f/"),
+                        )
+                        .changed();
+                });
+                settings_changed |= ui
+                    .add(egui::Slider::new(&mut camera.distortion, -0.5..=0.5).text(tr("Lens distortion")))
+                    .on_hover_text(tr("Preview only, picking and captured buffers are undistorted"))
+                    .changed();
+                ui.label(format!("EV100 {:.1}, {:.1}°", camera.ev100(), field_of_view));
+            });
+
+            let clip_planes = &mut self.render_settings.clip_planes;
+            let speed = 0.01 * self.world_unit.per_meter();
+            settings_changed |= ui
+                .checkbox(&mut clip_planes.auto, tr("Fit clip planes to the scene"))
+                .on_hover_text(tr("Avoids clipped large scenes and flickering small ones"))
+                .changed();
+            ui.add_enabled_ui(!clip_planes.auto, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("Clip planes"));
+                    let far = clip_planes.far;
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut clip_planes.near)
+                                .range(1e-4..=far)
+                                .speed(speed * 0.1),
+                        )
+                        .changed();
+                    let near = clip_planes.near;
+                    settings_changed |= ui
+                        .add(
+                            egui::DragValue::new(&mut clip_planes.far)
+                                .range(near..=1e7)
+                                .speed(speed * 100.0),
+                        )
+                        .changed();
+                });
+            });
+        });
+
+        if settings_changed {
+            self.renderer
+                .send_command(RenderCommand::UpdateSettings(self.render_settings.clone()))
+                .unwrap();
+            self.settings_file.save(&self.render_settings);
+        }
+    }
+
+    // Opacity is per world unit, density scales all of it at once
+    pub(super) fn volume_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Volume"), |ui| {
+            let mut changed = false;
+            let mut removed = None;
+            for (index, point) in self.transfer_function.points.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut point.value).range(0.0..=1.0).speed(0.005))
+                        .changed();
+                    changed |= ui.color_edit_button_rgb(&mut point.color).changed();
+                    changed |= ui
+                        .add(egui::Slider::new(&mut point.opacity, 0.0..=1.0).text(tr("Opacity")))
+                        .changed();
+                    if ui.button(tr("Remove")).clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+
+            if let Some(index) = removed {
+                self.transfer_function.points.remove(index);
+                changed = true;
+            }
+
+            if ui.button(tr("Add point")).clicked() {
+                self.transfer_function.points.push(TransferPoint {
+                    value: 0.5,
+                    color: [1.0, 1.0, 1.0],
+                    opacity: 0.5,
+                });
+                changed = true;
+            }
+
+            changed |= ui
+                .checkbox(&mut self.transfer_function.use_color_ramp, tr("Use color ramp"))
+                .changed();
+            changed |= ui
+                .add(egui::Slider::new(&mut self.transfer_function.density, 0.0..=32.0).text(tr("Density")))
+                .changed();
+            changed |= ui
+                .add(
+                    egui::Slider::new(&mut self.transfer_function.steps, 16..=TransferFunction::MAX_STEPS)
+                        .text(tr("Steps")),
+                )
+                .changed();
+
+            if changed {
+                self.renderer
+                    .send_command(RenderCommand::UpdateTransferFunction(self.transfer_function.clone()))
+                    .unwrap();
+            }
+        });
+    }
+}
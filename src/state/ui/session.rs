@@ -0,0 +1,50 @@
+use crate::state::State;
+
+impl State {
+    pub(super) fn session_panel(&self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        if let Some(session) = &self.session {
+            ui.collapsing(tr("Session"), |ui| {
+                ui.label(format!("{} ({})", session.name(), tr("you")));
+                for peer in session.peers() {
+                    ui.label(&peer.name);
+                }
+            });
+        }
+    }
+
+    // The other viewers' cameras, a dot with a line along the view direction
+    pub(super) fn peer_cameras(&self, ctx: &egui::Context) {
+        if let Some(session) = &self.session {
+            let view_projection = self.projection.matrix() * self.camera.view_matrix();
+            let screen = ctx.content_rect();
+            let to_screen = |position: glam::Vec3| {
+                let clip = view_projection * position.extend(1.0);
+                (clip.w > 0.0).then(|| {
+                    let ndc = clip.truncate() / clip.w;
+                    screen.min + egui::vec2(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * screen.size()
+                })
+            };
+
+            let painter = ctx.layer_painter(egui::LayerId::background());
+            let color = egui::Color32::from_rgb(255, 170, 0);
+            for peer in session.peers() {
+                let Some(position) = to_screen(peer.position) else {
+                    continue;
+                };
+                if let Some(target) = to_screen(peer.position + peer.forward) {
+                    painter.line_segment([position, target], egui::Stroke::new(2.0, color));
+                }
+                painter.circle_filled(position, 6.0, color);
+                painter.text(
+                    position + egui::vec2(10.0, -10.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    &peer.name,
+                    egui::FontId::proportional(14.0),
+                    color,
+                );
+            }
+        }
+    }
+}
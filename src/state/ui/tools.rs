@@ -0,0 +1,374 @@
+use std::sync::Arc;
+
+use crate::{
+    change_detection::ChangeDetection,
+    entity::EntityId,
+    registration::ScanRegistration,
+    state::{State, asset_name, classification_name, ui::UiActions},
+};
+
+impl State {
+    // Entities drawing a pointcloud, with the names they're listed by
+    fn pointcloud_names(&self) -> Vec<(EntityId, String)> {
+        self.entities
+            .iter()
+            .filter(|entity| self.renderer.scene_query().pointcloud(entity.id()).is_some())
+            .map(|entity| (entity.id(), asset_name(&entity.id(), entity.label())))
+            .collect()
+    }
+
+    fn mesh_names(&self) -> Vec<(EntityId, String)> {
+        self.entities
+            .iter()
+            .filter(|entity| self.renderer.scene_query().mesh(entity.id()).is_some())
+            .map(|entity| (entity.id(), asset_name(&entity.id(), entity.label())))
+            .collect()
+    }
+
+    pub(super) fn scatter_panel(&mut self, ui: &mut egui::Ui) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Scatter"), |ui| {
+            ui.checkbox(&mut self.scatter.enabled, tr("Paint with left mouse button"));
+
+            let selected = self
+                .scatter
+                .render_id
+                .and_then(|id| self.assets.iter().find(|(render_id, _)| *render_id == id))
+                .map(|(render_id, label)| asset_name(render_id, label))
+                .unwrap_or_else(|| tr("None").to_string());
+
+            egui::ComboBox::from_label(tr("Asset"))
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (render_id, label) in &self.assets {
+                        ui.selectable_value(
+                            &mut self.scatter.render_id,
+                            Some(*render_id),
+                            asset_name(render_id, label),
+                        );
+                    }
+                });
+
+            ui.add(egui::Slider::new(&mut self.scatter.radius, 0.1..=20.0).text(tr("Radius")));
+            ui.add(egui::Slider::new(&mut self.scatter.density, 1.0..=50.0).text(tr("Density")));
+            ui.add(egui::Slider::new(&mut self.scatter.scale, 0.1..=5.0).text(tr("Scale")));
+            ui.add(egui::Slider::new(&mut self.scatter.scale_jitter, 0.0..=1.0).text(tr("Scale jitter")));
+            ui.add(egui::Slider::new(&mut self.scatter.rotation_jitter, 0.0..=180.0).text(tr("Rotation jitter")));
+        });
+    }
+
+    pub(super) fn classify_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        let has_pointcloud = self.selected_pointcloud().is_some();
+        ui.collapsing(tr("Classify points"), |ui| {
+            ui.add_enabled(
+                has_pointcloud,
+                egui::Checkbox::new(
+                    &mut self.classification_brush.enabled,
+                    tr("Paint with left mouse button"),
+                ),
+            )
+            .on_disabled_hover_text(tr("Select a pointcloud first"));
+
+            let classification = self.classification_brush.classification;
+            egui::ComboBox::from_label(tr("Class"))
+                .selected_text(format!(
+                    "{} ({})",
+                    classification,
+                    tr(classification_name(classification))
+                ))
+                .show_ui(ui, |ui| {
+                    for option in 1..=18 {
+                        ui.selectable_value(
+                            &mut self.classification_brush.classification,
+                            option,
+                            format!("{} ({})", option, tr(classification_name(option))),
+                        );
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut self.classification_brush.radius, 2.0..=200.0)
+                    .text(tr("Radius"))
+                    .suffix(" px"),
+            );
+
+            if ui
+                .add_enabled(self.classification_brush.can_undo(), egui::Button::new(tr("Undo")))
+                .on_hover_text("Ctrl+Z")
+                .clicked()
+            {
+                actions.undo_classification = true;
+            }
+        });
+    }
+
+    // Scans are registered onto the reference by moving the other one
+    pub(super) fn registration_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        let pointclouds = self.pointcloud_names();
+        ui.collapsing(tr("Registration"), |ui| {
+            let name = |entity_id: Option<EntityId>| {
+                pointclouds
+                    .iter()
+                    .find(|(id, _)| Some(*id) == entity_id)
+                    .map_or_else(|| tr("None").to_string(), |(_, name)| name.clone())
+            };
+            for (label, scan) in [
+                (tr("Reference scan"), &mut self.registration.reference),
+                (tr("Moving scan"), &mut self.registration.moving),
+            ] {
+                egui::ComboBox::from_label(label)
+                    .selected_text(name(*scan))
+                    .show_ui(ui, |ui| {
+                        for (entity_id, name) in &pointclouds {
+                            ui.selectable_value(scan, Some(*entity_id), name);
+                        }
+                    });
+            }
+
+            let has_scans = self.registration.reference.is_some()
+                && self.registration.moving.is_some()
+                && self.registration.reference != self.registration.moving;
+            ui.horizontal(|ui| {
+                ui.add_enabled(
+                    has_scans,
+                    egui::Checkbox::new(&mut self.registration.is_picking, tr("Pick point pairs")),
+                );
+                ui.label(format!(
+                    "{} / {}",
+                    self.registration.pair_count(),
+                    ScanRegistration::PAIRS
+                ));
+                if ui.button(tr("Clear")).clicked() {
+                    self.registration.clear_pairs();
+                }
+            });
+            if self.registration.is_picking {
+                ui.label(if self.registration.is_waiting_for_reference() {
+                    tr("Click the matching point on the reference scan")
+                } else {
+                    tr("Click a point on the moving scan")
+                });
+            }
+
+            if ui
+                .add_enabled(
+                    self.registration.pair_count() >= ScanRegistration::PAIRS,
+                    egui::Button::new(tr("Align pairs")),
+                )
+                .clicked()
+            {
+                actions.is_align_requested = true;
+            }
+
+            ui.add(
+                egui::Slider::new(&mut self.registration.max_distance, 0.01..=10.0)
+                    .logarithmic(true)
+                    .text(tr("Maximum distance")),
+            );
+            if ui
+                .add_enabled(
+                    has_scans && !self.registration.is_refining(),
+                    egui::Button::new(tr("Refine with ICP")),
+                )
+                .clicked()
+            {
+                actions.is_refine_requested = true;
+            }
+            if let Some(result) = self.registration.result() {
+                ui.label(format!(
+                    "{}: {:.4}, {} {}, {} {}",
+                    tr("RMS error"),
+                    result.rms_error,
+                    result.matched,
+                    tr("matches"),
+                    result.iterations,
+                    tr("iterations")
+                ));
+            }
+        });
+    }
+
+    // Line up the scene with a photo by eye, or solve the camera from two vanishing points
+    pub(super) fn photo_match_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        ui.collapsing(tr("Match photo"), |ui| {
+            ui.horizontal(|ui| {
+                if ui.button(tr("Open photo")).clicked() {
+                    self.photo_match.open();
+                }
+                if ui
+                    .add_enabled(self.photo_match.has_photo(), egui::Button::new(tr("Remove")))
+                    .clicked()
+                {
+                    self.photo_match.close();
+                }
+            });
+            ui.add(egui::Slider::new(&mut self.photo_match.opacity, 0.0..=1.0).text(tr("Opacity")));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.photo_match.show_grid, tr("Ground grid"));
+                ui.add(
+                    egui::DragValue::new(&mut self.photo_match.grid_spacing)
+                        .range(0.01..=100.0)
+                        .speed(0.05),
+                );
+            });
+            ui.checkbox(&mut self.photo_match.show_vanishing_lines, tr("Vanishing lines"))
+                .on_hover_text(tr(
+                    "Drag the red lines along edges parallel to X, the blue ones along Z",
+                ));
+
+            let solved = self
+                .photo_match
+                .show_vanishing_lines
+                .then(|| self.photo_match.solve(ui.ctx().content_rect()))
+                .flatten();
+            if let Some(solved) = solved {
+                let field_of_view = 2.0 * (0.5 / solved.focal_length).atan();
+                ui.label(format!(
+                    "{}: {:.1}°",
+                    tr("Horizontal field of view"),
+                    field_of_view.to_degrees()
+                ));
+            }
+            if ui
+                .add_enabled(solved.is_some(), egui::Button::new(tr("Match camera")))
+                .clicked()
+            {
+                actions.camera_match = solved;
+            }
+        });
+    }
+
+    // A scan colored by how far it is from an earlier epoch or from the design model
+    pub(super) fn change_detection_panel(&mut self, ui: &mut egui::Ui, actions: &mut UiActions) {
+        let language = self.render_settings.language;
+        let tr = |text: &'static str| language.tr(text);
+        let pointclouds = self.pointcloud_names();
+        let meshes = self.mesh_names();
+        ui.collapsing(tr("Change detection"), |ui| {
+            let name = |entity_id: Option<EntityId>| {
+                pointclouds
+                    .iter()
+                    .chain(&meshes)
+                    .find(|(id, _)| Some(*id) == entity_id)
+                    .map_or_else(|| tr("None").to_string(), |(_, name)| name.clone())
+            };
+            egui::ComboBox::from_label(tr("Reference"))
+                .selected_text(name(self.change_detection.reference))
+                .show_ui(ui, |ui| {
+                    for (entity_id, name) in pointclouds.iter().chain(&meshes) {
+                        ui.selectable_value(&mut self.change_detection.reference, Some(*entity_id), name);
+                    }
+                });
+            egui::ComboBox::from_label(tr("Compared scan"))
+                .selected_text(name(self.change_detection.compared))
+                .show_ui(ui, |ui| {
+                    for (entity_id, name) in &pointclouds {
+                        ui.selectable_value(&mut self.change_detection.compared, Some(*entity_id), name);
+                    }
+                });
+
+            ui.add(
+                egui::Slider::new(&mut self.change_detection.max_distance, 0.001..=10.0)
+                    .logarithmic(true)
+                    .text(tr("Maximum distance")),
+            )
+            .on_hover_text(tr("Points further from the reference count as unmatched"));
+            let has_scans = self.change_detection.reference.is_some()
+                && self.change_detection.compared.is_some()
+                && self.change_detection.reference != self.change_detection.compared;
+            ui.horizontal(|ui| {
+                actions.is_comparison_requested = ui
+                    .add_enabled(
+                        has_scans && !self.change_detection.is_computing(),
+                        egui::Button::new(tr("Compare")),
+                    )
+                    .clicked();
+                actions.is_restore_colors_requested = ui.button(tr("Restore colors")).clicked();
+            });
+
+            let Some(result) = self.change_detection.result() else {
+                return;
+            };
+            let distances = Arc::clone(&result.distances);
+            ui.label(format!(
+                "{}: {:.4}, {}: {:.4} .. {:.4}, {} / {} {}",
+                tr("Mean distance"),
+                distances.mean,
+                tr("Range"),
+                distances.min,
+                distances.max,
+                distances.matched,
+                result.total,
+                tr("matches")
+            ));
+            distance_histogram(ui, &self.change_detection);
+
+            // Against a mesh the distances are signed, points behind the surface are negative
+            let max_distance = self.change_detection.max_distance;
+            let range = match distances.is_signed {
+                true => -max_distance..=max_distance,
+                false => 0.0..=max_distance,
+            };
+            let [lower, upper] = &mut self.change_detection.thresholds;
+            let lower_changed = ui
+                .add(egui::Slider::new(lower, range.clone()).text(tr("Lower threshold")))
+                .changed();
+            let upper_changed = ui
+                .add(egui::Slider::new(upper, range).text(tr("Upper threshold")))
+                .changed();
+            if lower_changed || upper_changed {
+                *upper = upper.max(*lower);
+                actions.is_recolor_requested = true;
+            }
+        });
+    }
+}
+
+// Bars between the smallest and largest distance in the color they get, with lines at the thresholds
+fn distance_histogram(ui: &mut egui::Ui, change_detection: &ChangeDetection) {
+    let Some(result) = change_detection.result() else {
+        return;
+    };
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 64.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    let (min, max) = (result.distances.min, result.distances.max);
+    let range = (max - min).max(f32::EPSILON);
+    let tallest = result.histogram.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let width = rect.width() / result.histogram.len() as f32;
+    for (bin, count) in result.histogram.iter().enumerate() {
+        let distance = min + (bin as f32 + 0.5) / result.histogram.len() as f32 * range;
+        let [r, g, b] = change_detection
+            .color(Some(distance))
+            .map(|channel| (channel * 255.0) as u8);
+        let height = *count as f32 / tallest * rect.height();
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(rect.min.x + bin as f32 * width, rect.max.y - height),
+                egui::pos2(rect.min.x + (bin + 1) as f32 * width, rect.max.y),
+            ),
+            0.0,
+            egui::Color32::from_rgb(r, g, b),
+        );
+    }
+
+    for threshold in change_detection.thresholds {
+        let x = rect.min.x + (threshold - min) / range * rect.width();
+        if (rect.min.x..=rect.max.x).contains(&x) {
+            painter.vline(x, rect.y_range(), egui::Stroke::new(1.0, ui.visuals().text_color()));
+        }
+    }
+    ui.horizontal(|ui| {
+        ui.label(format!("{:.3}", min));
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(format!("{:.3}", max));
+        });
+    });
+}
@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use crate::renderer::Light;
+
+const DAYS_IN_MONTH: [u32; 12] = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
+const SUN_COLOR: glam::Vec3 = glam::Vec3::new(1.0, 0.956, 0.897);
+const SUN_INTENSITY: f32 = 3.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    // Frames rendered with the sun at the step so far
+    Settling(usize, u32),
+    Capturing(usize),
+}
+
+// The app's light turned into the sun over a day at a latitude, each daylight step saved as a numbered frame into
+// sun_study/. North is -Z and east +X, like the floorplan's plan. Hours are solar time, without the equation of time
+// or a time zone, which is close enough to see which parts of a site are in the shade and when
+pub struct SunStudy {
+    // Degrees, south is negative
+    pub latitude: f32,
+    pub month: u32,
+    pub day: u32,
+    pub hours: [f32; 2],
+    pub step_minutes: u32,
+    phase: Phase,
+    // Hours of the steps with the sun above the horizon
+    steps: Vec<f32>,
+    // The light before the study started, put back once it's done or stopped
+    restore: Option<Light>,
+}
+
+impl SunStudy {
+    // The light update reaches the renderer a frame after it's sent and the shadows are drawn the frame after
+    const SETTLE_FRAMES: u32 = 4;
+
+    pub fn new() -> Self {
+        Self {
+            latitude: 52.0,
+            month: 6,
+            day: 21,
+            hours: [6.0, 20.0],
+            step_minutes: 30,
+            phase: Phase::Idle,
+            steps: Vec::new(),
+            restore: None,
+        }
+    }
+
+    pub fn days_in_month(&self) -> u32 {
+        DAYS_IN_MONTH[(self.month.clamp(1, 12) - 1) as usize]
+    }
+
+    fn day_of_year(&self) -> u32 {
+        let month = self.month.clamp(1, 12) as usize;
+        DAYS_IN_MONTH[..month - 1].iter().sum::<u32>() + self.day.clamp(1, self.days_in_month())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.phase != Phase::Idle
+    }
+
+    // Steps saved so far and in total
+    pub fn progress(&self) -> (usize, usize) {
+        match self.phase {
+            Phase::Settling(step, _) | Phase::Capturing(step) => (step, self.steps.len()),
+            Phase::Idle => (0, self.steps.len()),
+        }
+    }
+
+    pub fn start(&mut self, light: &Light) -> anyhow::Result<()> {
+        let step = self.step_minutes.max(1) as f32 / 60.0;
+        let [start, end] = self.hours;
+        let count = ((end - start) / step).floor().max(-1.0) as i32 + 1;
+        self.steps = (0..count)
+            .map(|index| start + index as f32 * step)
+            .filter(|&hour| self.sun_direction(hour).is_some())
+            .collect();
+        if self.steps.is_empty() {
+            anyhow::bail!("The sun stays below the horizon between those hours");
+        }
+
+        std::fs::create_dir_all(Self::output())?;
+        self.restore = Some(light.clone());
+        self.phase = Phase::Settling(0, 0);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.phase = Phase::Idle;
+    }
+
+    // The light to put back, once after the study ended
+    pub fn take_restore(&mut self) -> Option<Light> {
+        match self.phase {
+            Phase::Idle => self.restore.take(),
+            _ => None,
+        }
+    }
+
+    // The sun at the current step
+    pub fn light(&self) -> Option<Light> {
+        let (Phase::Settling(step, _) | Phase::Capturing(step)) = self.phase else {
+            return None;
+        };
+        Some(Light::Directional {
+            direction: self.sun_direction(self.steps[step])?,
+            color: SUN_COLOR,
+            intensity: SUN_INTENSITY,
+            casts_shadows: true,
+        })
+    }
+
+    // Returns true on the frame the screenshot should be requested
+    pub fn record_frame(&mut self) -> bool {
+        match self.phase {
+            Phase::Settling(step, frame) if frame + 1 >= Self::SETTLE_FRAMES => {
+                self.phase = Phase::Capturing(step);
+                true
+            }
+            Phase::Settling(step, frame) => {
+                self.phase = Phase::Settling(step, frame + 1);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Writes the step's frame and moves on to the next one
+    pub fn save(&mut self, width: u32, height: u32, pixels: Vec<u8>) -> anyhow::Result<()> {
+        let Phase::Capturing(step) = self.phase else {
+            return Ok(());
+        };
+        let hour = self.steps[step];
+        let minutes = (hour * 60.0).round() as u32;
+        let path = Self::output().join(format!("{:03}_{:02}{:02}.png", step, minutes / 60, minutes % 60));
+        image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8)?;
+
+        self.phase = match step + 1 < self.steps.len() {
+            true => Phase::Settling(step + 1, 0),
+            false => {
+                log::info!(
+                    "Saved {} sun study frames to {}",
+                    self.steps.len(),
+                    Self::output().display()
+                );
+                Phase::Idle
+            }
+        };
+        Ok(())
+    }
+
+    fn output() -> PathBuf {
+        PathBuf::from("sun_study")
+    }
+
+    // Where the sunlight points at `hour`, None while the sun is below the horizon. Declination from the day of the
+    // year, the hour angle turns 15 degrees an hour from solar noon
+    fn sun_direction(&self, hour: f32) -> Option<glam::Vec3> {
+        let latitude = self.latitude.clamp(-90.0, 90.0).to_radians();
+        let declination =
+            23.44_f32.to_radians() * (std::f32::consts::TAU * (284 + self.day_of_year()) as f32 / 365.0).sin();
+        let hour_angle = (15.0 * (hour - 12.0)).to_radians();
+
+        let up = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+        if up <= 0.0 {
+            return None;
+        }
+        let east = -declination.cos() * hour_angle.sin();
+        let north = declination.sin() * latitude.cos() - declination.cos() * hour_angle.cos() * latitude.sin();
+        Some(-glam::Vec3::new(east, up, -north).normalize())
+    }
+}
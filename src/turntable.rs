@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TurntableTarget {
+    // Circles the point the camera looked at when it started
+    Camera,
+    // Spins the selected entity around its own center
+    Selection,
+}
+
+impl TurntableTarget {
+    pub const ALL: [Self; 2] = [Self::Camera, Self::Selection];
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            Self::Camera => "Camera",
+            Self::Selection => "Selection",
+        }
+    }
+}
+
+// Part of input recordings, so a replay spins the same way it did while recording
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TurntableSettings {
+    pub enabled: bool,
+    pub target: TurntableTarget,
+    // Degrees per second around the vertical axis, negative turns clockwise seen from above
+    pub speed: f32,
+}
+
+// Slow rotation about the vertical axis for showing off a scene or recording a preview
+pub struct Turntable {
+    pub settings: TurntableSettings,
+    focus: Option<glam::Vec3>,
+}
+
+impl Turntable {
+    pub fn new() -> Self {
+        Self {
+            settings: TurntableSettings {
+                enabled: false,
+                target: TurntableTarget::Camera,
+                speed: 20.0,
+            },
+            focus: None,
+        }
+    }
+
+    pub fn is_spinning(&self) -> bool {
+        self.settings.enabled && self.settings.speed != 0.0
+    }
+
+    // The camera picks a new focus the next time it spins
+    pub fn reset_focus(&mut self) {
+        self.focus = None;
+    }
+
+    fn angle(&self, timestep: Duration) -> f32 {
+        (self.settings.speed * timestep.as_secs_f32()).to_radians()
+    }
+
+    // The point the camera circles, picked when it starts spinning
+    pub fn focus(&self) -> Option<glam::Vec3> {
+        self.focus
+    }
+
+    pub fn orbit_camera(&mut self, camera: &mut Camera, focus: glam::Vec3, timestep: Duration) {
+        self.focus = Some(focus);
+        camera.orbit(focus, self.angle(timestep));
+    }
+
+    // `center` is in world space, the entity keeps its distance from it
+    pub fn spin(&self, transform: glam::Mat4, center: glam::Vec3, timestep: Duration) -> glam::Mat4 {
+        glam::Mat4::from_translation(center)
+            * glam::Mat4::from_rotation_y(self.angle(timestep))
+            * glam::Mat4::from_translation(-center)
+            * transform
+    }
+}
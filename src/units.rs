@@ -0,0 +1,78 @@
+// Scale the scene is authored in. Camera speed, clip planes and the snapping grid follow it so a scene in
+// centimeters handles like the same scene in meters
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WorldUnit {
+    Meters,
+    Centimeters,
+    Feet,
+}
+
+impl WorldUnit {
+    pub const ALL: [Self; 3] = [Self::Meters, Self::Centimeters, Self::Feet];
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Meters => "Meters",
+            Self::Centimeters => "Centimeters",
+            Self::Feet => "Feet",
+        }
+    }
+
+    pub fn per_meter(&self) -> f32 {
+        match self {
+            Self::Meters => 1.0,
+            Self::Centimeters => 100.0,
+            Self::Feet => 3.28084,
+        }
+    }
+
+    pub fn camera_speed(&self) -> f32 {
+        8.0 * self.per_meter()
+    }
+
+    pub fn clip_planes(&self) -> (f32, f32) {
+        (0.1 * self.per_meter(), 500.0 * self.per_meter())
+    }
+
+    // Round numbers in the unit itself rather than converted meters
+    pub fn grid_step(&self) -> f32 {
+        match self {
+            Self::Meters => 0.5,
+            Self::Centimeters => 10.0,
+            Self::Feet => 1.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScaleReference {
+    Human,
+    Vehicle,
+}
+
+impl ScaleReference {
+    pub const ALL: [Self; 2] = [Self::Human, Self::Vehicle];
+    // Loaded at startup, only spawned on request
+    pub const FILE: &str = "scale_reference.obj";
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::Human => "Human reference",
+            Self::Vehicle => "Vehicle reference",
+        }
+    }
+
+    // Width, height and depth in meters
+    pub fn size(&self) -> glam::Vec3 {
+        match self {
+            Self::Human => glam::Vec3::new(0.5, 1.8, 0.3),
+            Self::Vehicle => glam::Vec3::new(1.8, 1.5, 4.5),
+        }
+    }
+
+    // Scales the unit reference box, which stands on its origin, to the reference size on the ground at `position`
+    pub fn transform(&self, position: glam::Vec3, unit: WorldUnit) -> glam::Mat4 {
+        let size = self.size() * unit.per_meter();
+        glam::Mat4::from_scale_rotation_translation(size, glam::Quat::IDENTITY, position.with_y(0.0))
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use instant::Instant;
+use winit::{
+    event::{KeyEvent, WindowEvent},
+    keyboard::PhysicalKey,
+    window::Window,
+};
+
+use crate::{
+    camera::{Camera, CameraController, Projection},
+    renderer::{RenderLayers, Renderer, ViewportId},
+};
+
+// An additional OS window with its own camera onto the shared scene
+pub struct ViewportWindow {
+    id: ViewportId,
+    window: Arc<Window>,
+    camera: Camera,
+    camera_controller: CameraController,
+    projection: Projection,
+    timestamp: Instant,
+    pub layers: RenderLayers,
+}
+
+impl ViewportWindow {
+    pub fn new(id: ViewportId, window: Arc<Window>, camera: Camera) -> Self {
+        let size = window.inner_size();
+        let projection = Projection::new(size.width, size.height, 60.0_f32.to_radians(), 0.1, 500.0);
+
+        Self {
+            id,
+            window,
+            camera,
+            camera_controller: CameraController::new(8.0, 0.004),
+            projection,
+            timestamp: Instant::now(),
+            layers: RenderLayers::ALL,
+        }
+    }
+
+    pub fn id(&self) -> ViewportId {
+        self.id
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn camera_controller_mut(&mut self) -> &mut CameraController {
+        &mut self.camera_controller
+    }
+
+    // Returns false once the window asked to close
+    pub fn handle_event(&mut self, event: WindowEvent, renderer: &mut Renderer) -> bool {
+        match event {
+            WindowEvent::CloseRequested => return false,
+            WindowEvent::Resized(size) => {
+                if size.width > 0 && size.height > 0 {
+                    self.projection.resize(size.width, size.height);
+                    renderer.resize_viewport(self.id, size.width, size.height);
+                }
+            }
+            WindowEvent::RedrawRequested => self.update(renderer),
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.camera_controller.handle_mouse_button(button, state.is_pressed());
+            }
+            WindowEvent::MouseWheel { delta, .. } => self.camera_controller.handle_scroll(&delta),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                self.camera_controller.handle_key(code, state);
+            }
+            _ => (),
+        }
+
+        true
+    }
+
+    fn update(&mut self, renderer: &mut Renderer) {
+        self.window.request_redraw();
+
+        let timestep = self.timestamp.elapsed();
+        self.timestamp = Instant::now();
+        self.camera_controller.update_camera(&mut self.camera, timestep);
+
+        renderer.update_viewport_camera(
+            self.id,
+            self.camera.position(),
+            self.camera.view_matrix(),
+            self.projection.matrix(),
+        );
+        renderer.request_viewport_frame(self.id, &self.window);
+    }
+}
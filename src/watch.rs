@@ -0,0 +1,179 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+#[cfg(not(target_family = "wasm"))]
+use crossbeam::channel::{Receiver, unbounded};
+#[cfg(not(target_family = "wasm"))]
+use notify::Watcher;
+
+use instant::Instant;
+
+// Watches a file for outside edits, through notify on native. The browser has no file to watch, the polling fallback
+// there never finds a change
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // Watches the directory, exporters often replace the file rather than write into it. None while it doesn't exist
+    #[cfg(not(target_family = "wasm"))]
+    watcher: Option<(notify::RecommendedWatcher, Receiver<notify::Result<notify::Event>>)>,
+    // Last event for the file, the change counts once the writes settle
+    #[cfg(not(target_family = "wasm"))]
+    last_event: Option<Instant>,
+    #[cfg(target_family = "wasm")]
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    #[cfg(not(target_family = "wasm"))]
+    const SETTLE_TIME: Duration = Duration::from_millis(250);
+    #[cfg(target_family = "wasm")]
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut watcher = Self {
+            path: path.into(),
+            modified: None,
+            #[cfg(not(target_family = "wasm"))]
+            watcher: None,
+            #[cfg(not(target_family = "wasm"))]
+            last_event: None,
+            #[cfg(target_family = "wasm")]
+            last_poll: Instant::now(),
+        };
+        watcher.watch();
+        watcher
+    }
+
+    // Starts from the file as it is now, only later edits count as changes
+    pub fn from_current(path: impl Into<PathBuf>) -> Self {
+        let mut watcher = Self::new(path);
+        watcher.modified = watcher.modified_time();
+        watcher
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn read(&mut self) -> Option<String> {
+        if cfg!(target_family = "wasm") {
+            return None;
+        }
+
+        let contents = std::fs::read_to_string(&self.path).ok()?;
+        self.modified = self.modified_time();
+        Some(contents)
+    }
+
+    pub fn write(&mut self, contents: &str) -> anyhow::Result<()> {
+        if cfg!(target_family = "wasm") {
+            return Ok(());
+        }
+
+        std::fs::write(&self.path, contents)?;
+        // Remember our own write so the next check doesn't report it
+        self.modified = self.modified_time();
+        // The directory may only exist since this write
+        self.watch();
+        Ok(())
+    }
+
+    // Returns the new contents when the file changed on disk since the last read or write
+    pub fn poll(&mut self) -> Option<String> {
+        if !self.has_changed() {
+            return None;
+        }
+
+        log::info!("Reloading {}", self.path.display());
+        self.read()
+    }
+
+    // Whether the file changed on disk since the last check, read or write, without reading it. A file that is
+    // missing for a moment, like while an exporter replaces it, isn't a change yet
+    pub fn has_changed(&mut self) -> bool {
+        #[cfg(not(target_family = "wasm"))]
+        {
+            if let Some((_, events)) = &self.watcher {
+                for event in events.try_iter() {
+                    match event {
+                        Ok(event) if !event.kind.is_access() && self.is_about_file(&event) => {
+                            self.last_event = Some(Instant::now());
+                        }
+                        Ok(_) => {}
+                        Err(error) => log::warn!("Unable to watch {}: {}", self.path.display(), error),
+                    }
+                }
+            }
+            if self
+                .last_event
+                .is_none_or(|last_event| last_event.elapsed() < Self::SETTLE_TIME)
+            {
+                return false;
+            }
+            self.last_event = None;
+        }
+
+        #[cfg(target_family = "wasm")]
+        {
+            if self.last_poll.elapsed() < Self::POLL_INTERVAL {
+                return false;
+            }
+            self.last_poll = Instant::now();
+        }
+
+        let Some(modified) = self.modified_time() else {
+            return false;
+        };
+        if self.modified == Some(modified) {
+            return false;
+        }
+
+        self.modified = Some(modified);
+        true
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn watch(&mut self) {
+        if self.watcher.is_some() {
+            return;
+        }
+
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        if !directory.is_dir() {
+            return;
+        }
+
+        let (sender, receiver) = unbounded();
+        let watcher = notify::recommended_watcher(move |event| {
+            sender.send(event).ok();
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(directory, notify::RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => self.watcher = Some((watcher, receiver)),
+            Err(error) => log::warn!("Unable to watch {}: {}", self.path.display(), error),
+        }
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn watch(&mut self) {}
+
+    // Only the directory is watched, so events for its other files come in too
+    #[cfg(not(target_family = "wasm"))]
+    fn is_about_file(&self, event: &notify::Event) -> bool {
+        event.paths.iter().any(|path| path.file_name() == self.path.file_name())
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
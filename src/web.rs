@@ -0,0 +1,119 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+};
+
+use wasm_bindgen::prelude::*;
+
+// Calls from the surrounding page, queued until the next frame picks them up on the main thread
+pub enum WebRequest {
+    LoadAsset(String),
+    ResizeCanvas { width: u32, height: u32 },
+    SetTransparentBackground(bool),
+    SetCamera { position: glam::Vec3, target: glam::Vec3 },
+    CaptureScreenshot,
+}
+
+thread_local! {
+    static REQUESTS: RefCell<VecDeque<WebRequest>> = const { RefCell::new(VecDeque::new()) };
+    static LISTENERS: RefCell<HashMap<String, Vec<js_sys::Function>>> = RefCell::new(HashMap::new());
+    // Resolved in order, each capture answers the oldest waiting promise. Resolve and reject
+    static SCREENSHOTS: RefCell<VecDeque<(js_sys::Function, js_sys::Function)>> = const { RefCell::new(VecDeque::new()) };
+}
+
+pub fn take_requests() -> Vec<WebRequest> {
+    REQUESTS.with_borrow_mut(|requests| requests.drain(..).collect())
+}
+
+fn push(request: WebRequest) {
+    REQUESTS.with_borrow_mut(|requests| requests.push_back(request));
+}
+
+// Relative paths resolve against the res folder next to the page, like the built-in assets
+#[wasm_bindgen(js_name = loadAsset)]
+pub fn load_asset(url: String) {
+    push(WebRequest::LoadAsset(url));
+}
+
+// In CSS pixels. Leaving the size to the page's own layout works as well, the canvas follows its element size
+#[wasm_bindgen(js_name = setCanvasSize)]
+pub fn set_canvas_size(width: u32, height: u32) {
+    push(WebRequest::ResizeCanvas { width, height });
+}
+
+#[wasm_bindgen(js_name = setTransparentBackground)]
+pub fn set_transparent_background(is_transparent: bool) {
+    push(WebRequest::SetTransparentBackground(is_transparent));
+}
+
+#[wasm_bindgen(js_name = setCamera)]
+pub fn set_camera(x: f32, y: f32, z: f32, target_x: f32, target_y: f32, target_z: f32) {
+    push(WebRequest::SetCamera {
+        position: glam::vec3(x, y, z),
+        target: glam::vec3(target_x, target_y, target_z),
+    });
+}
+
+// Resolves with `{ width, height, pixels }`, the pixels are RGBA so `new ImageData(pixels, width, height)` takes them.
+// Rejects with the error message when the pixels can't be read back
+#[wasm_bindgen(js_name = captureScreenshot)]
+pub fn capture_screenshot() -> js_sys::Promise {
+    push(WebRequest::CaptureScreenshot);
+    js_sys::Promise::new(&mut |resolve, reject| {
+        SCREENSHOTS.with_borrow_mut(|screenshots| screenshots.push_back((resolve, reject)));
+    })
+}
+
+// Events: loadComplete `{ renderId, label, triangles, points }`, error `{ label, message }`
+#[wasm_bindgen]
+pub fn on(event: String, callback: js_sys::Function) {
+    LISTENERS.with_borrow_mut(|listeners| listeners.entry(event).or_default().push(callback));
+}
+
+pub fn emit(event: &str, fields: &[(&str, JsValue)]) {
+    let listeners = LISTENERS.with_borrow(|listeners| listeners.get(event).cloned().unwrap_or_default());
+    if listeners.is_empty() {
+        return;
+    }
+
+    let payload = object(fields);
+    for listener in listeners {
+        if let Err(error) = listener.call1(&JsValue::NULL, &payload) {
+            log::error!("Listener for {} failed: {:?}", event, error);
+        }
+    }
+}
+
+pub fn resolve_screenshot(width: u32, height: u32, pixels: &[u8]) {
+    if let Some((resolve, _)) = SCREENSHOTS.with_borrow_mut(VecDeque::pop_front) {
+        let payload = object(&[
+            ("width", width.into()),
+            ("height", height.into()),
+            ("pixels", js_sys::Uint8ClampedArray::from(pixels).into()),
+        ]);
+        let _ = resolve.call1(&JsValue::NULL, &payload);
+    }
+}
+
+pub fn reject_screenshot(message: &str) {
+    if let Some((_, reject)) = SCREENSHOTS.with_borrow_mut(VecDeque::pop_front) {
+        let _ = reject.call1(&JsValue::NULL, &JsValue::from_str(message));
+    }
+}
+
+fn object(fields: &[(&str, JsValue)]) -> JsValue {
+    let object = js_sys::Object::new();
+    for (key, value) in fields {
+        let _ = js_sys::Reflect::set(&object, &JsValue::from_str(key), value);
+    }
+
+    object.into()
+}
+
+// `<canvas id="canvas" data-transparent>` starts without the environment background
+pub fn canvas_flag(name: &str) -> bool {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.get_element_by_id("canvas"))
+        .is_some_and(|canvas| canvas.has_attribute(&format!("data-{}", name)))
+}